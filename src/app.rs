@@ -3,7 +3,7 @@ use std::ffi::OsStr;
 use std::fmt::Display;
 use std::io::{self, Write};
 use std::process::{Child, ExitStatus};
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::mpsc::{Receiver, RecvTimeoutError};
 use std::sync::Arc;
 use std::time::Duration;
@@ -12,9 +12,13 @@ use std::{env, fmt, thread};
 use console::Color::{Cyan, Green, Red, Yellow};
 use console::{Color, Style, Term};
 use parking_lot::Mutex;
+use strum::VariantNames as _;
 
+use crate::book::AstStats;
+use crate::diag::BuildDiagnostic;
 use crate::parser::Diagnostic;
 use crate::prelude::*;
+use crate::project::Format;
 use crate::util::{ErrorExt as _, ImgCache, ProcessLines};
 
 #[derive(clap::Parser, Clone, Default)]
@@ -41,18 +45,120 @@ impl StdioOpts {
     }
 }
 
+#[derive(clap::Parser, Clone, Default)]
+pub struct InitOpts {
+    /// Don't add the default fonts to output/fonts, eg. when using system fonts in custom templates
+    #[arg(long)]
+    pub no_fonts: bool,
+    /// Title of the book in the new bard.toml
+    #[arg(long)]
+    pub title: Option<String>,
+    /// Name of the output files, without the extension, "songbook" by default
+    #[arg(long)]
+    pub name: Option<String>,
+    /// Formats of the outputs in the new bard.toml, eg. pdf,html (the default)
+    #[arg(long, value_delimiter = ',', value_parser = parse_format)]
+    pub format: Vec<Format>,
+    /// Don't add the example song, songs are then loaded from all the .md files in the songs directory
+    #[arg(long)]
+    pub no_example_song: bool,
+
+    #[clap(flatten)]
+    pub stdio: StdioOpts,
+}
+
+fn parse_format(s: &str) -> Result<Format, String> {
+    s.trim().to_lowercase().parse().map_err(|_| {
+        format!(
+            "unknown format '{}', supported formats are: {}",
+            s,
+            Format::VARIANTS.join(", ")
+        )
+    })
+}
+
+/// Format a duration for status messages, eg. `850ms` or `3.4s`.
+fn fmt_elapsed(elapsed: Duration) -> String {
+    if elapsed.as_secs() == 0 {
+        format!("{}ms", elapsed.as_millis())
+    } else {
+        format!("{:.1}s", elapsed.as_secs_f64())
+    }
+}
+
 #[derive(clap::Parser, Clone, Default)]
 pub struct MakeOpts {
-    /// Don't run post-processing steps, ie. TeX and scripts, if any
+    /// Don't run post-processing steps, ie. TeX, scripts and hooks, if any
     #[arg(short = 'p', long)]
     pub no_postprocess: bool,
     /// Keep the TeX file when generating PDF. Use twice to keep TeX build directory as well.
     #[arg(short = 'k', long, action = clap::ArgAction::Count)]
     pub keep: u8,
+    /// Render only the songs matching this title or glob pattern, each into its own PDF file, eg. for handouts
+    #[arg(long, value_name = "TITLE")]
+    pub song: Option<String>,
+    /// Render only the output with this file name, eg. songbook.html, or outputs matching a glob pattern, eg. '*.html'.
+    /// Can be given more than once
+    #[arg(short = 'o', long = "output", value_name = "FILE")]
+    pub outputs: Vec<String>,
+    /// If the TeX run fails, find the songs that break it and render the book without them.
+    /// This takes many TeX runs, the songs left out are listed in quarantine.txt in the output directory
+    #[arg(long)]
+    pub quarantine: bool,
+    /// Exit with an error if any warnings were emitted, eg. about song content, for use in CI.
+    /// Has no effect in watch mode
+    #[arg(long)]
+    pub fail_on_warnings: bool,
+    /// Fail the build if a song file has errors. By default, the file's songs are left out
+    /// and the rest of the book is built, the skipped files are listed at the end
+    #[arg(long)]
+    pub strict: bool,
+    /// Don't run the outputs' on_update commands in watch mode
+    #[arg(long)]
+    pub no_notify: bool,
+    /// Update templates which are unmodified copies of an older default template
+    #[arg(long)]
+    pub update_templates: bool,
+    /// Report numbers of songs and AST nodes, cache sizes and approximate memory usage
+    /// of the book after each build. These are reported with --verbose too
+    #[arg(long)]
+    pub stats_memory: bool,
+    /// Remove files generated by previous builds which the current configuration no longer produces,
+    /// eg. outputs removed from bard.toml. Files not generated by bard are never removed
+    #[arg(long)]
+    pub prune: bool,
+    /// Report how long parsing the songs, rendering templates, TeX, and scripts take for each output
+    #[arg(long)]
+    pub timings: bool,
     #[clap(flatten)]
     pub stdio: StdioOpts,
 }
 
+/// Environment variable enabling `ProjectOpts::strict_project_dir`, for use in CI.
+pub const STRICT_PROJECT_DIR_ENV: &str = "BARD_STRICT_PROJECT_DIR";
+
+#[derive(clap::Parser, Clone, Default, Debug)]
+pub struct ProjectOpts {
+    /// Use the project in this directory rather than looking up bard.toml in the current and parent directories
+    #[arg(long, global = true, value_name = "PATH")]
+    pub project_dir: Option<PathBuf>,
+    /// Don't look up bard.toml in parent directories, fail if it's not in the current directory.
+    /// Can also be enabled with the BARD_STRICT_PROJECT_DIR environment variable
+    #[arg(long, global = true)]
+    pub strict_project_dir: bool,
+}
+
+impl ProjectOpts {
+    /// Enable `strict_project_dir` if set in the environment.
+    fn apply_env(&mut self) {
+        if let Some(value) = env::var_os(STRICT_PROJECT_DIR_ENV) {
+            if !value.is_empty() && value != "0" && value != "false" {
+                self.strict_project_dir = true;
+            }
+        }
+    }
+}
+
 impl From<StdioOpts> for MakeOpts {
     fn from(stdio: StdioOpts) -> Self {
         Self {
@@ -125,6 +231,34 @@ impl InterruptFlag {
     }
 }
 
+/// Sizes of the loaded book and of the caches kept across builds, see `App::debug_stats()`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct DebugStats {
+    pub ast: AstStats,
+    /// Entries of `App::img_cache()`.
+    pub img_cache: usize,
+    /// Entries of the regex cache of the `matches` template helper.
+    pub regex_cache: usize,
+}
+
+impl fmt::Display for DebugStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} songs, {} blocks, {} inlines, ~{:.1} KiB of AST",
+            self.ast.songs,
+            self.ast.blocks,
+            self.ast.inlines,
+            self.ast.heap_bytes as f64 / 1024.0
+        )?;
+        write!(
+            f,
+            "Cached images: {}, cached regexes: {}",
+            self.img_cache, self.regex_cache
+        )
+    }
+}
+
 /// Runtime config and stdio output fns.
 #[derive(Clone, Debug)]
 pub struct App {
@@ -133,6 +267,27 @@ pub struct App {
     post_process: bool,
     /// See `keeplevel` for levels.
     keep_interm: u8,
+    /// Song title or glob of `bard make --song`, see `Project::render()`.
+    song: Option<String>,
+    /// File names or globs of `bard make --output`, see `Project::selected_outputs()`.
+    outputs: Vec<String>,
+    /// Whether to leave out songs that break the TeX run, see `Project::render_quarantined()`.
+    quarantine: bool,
+    /// Whether `bard make` fails if there were any warnings, see `check_warnings()`.
+    fail_on_warnings: bool,
+    /// Whether song files with errors fail the build rather than being left out,
+    /// see `Project::skipped_files()`.
+    strict: bool,
+    /// Whether outputs' `on_update` commands run in watch mode, see `Project::notify_update()`.
+    notify: bool,
+    /// Whether outdated unmodified default templates are updated, see `HbRender::check_default()`.
+    update_templates: bool,
+    /// Whether `debug_stats()` are reported regardless of verbosity.
+    stats_memory: bool,
+    /// Whether stale generated files are removed, see `Project::render()`.
+    prune: bool,
+    /// Whether durations of build phases are reported, see `timing()`.
+    timings: bool,
 
     // stdio stuff
     term: Term,
@@ -150,6 +305,13 @@ pub struct App {
 
     /// Parser diagnostic messages, these are only collected in `test_mode`.
     parser_diags: Option<ParserDiags>,
+    /// Diagnostics of all the build phases, including the parser ones, see `diag()`.
+    diags: Arc<Mutex<Vec<BuildDiagnostic>>>,
+    /// Number of warnings emitted, including parser diagnostics, these are always counted.
+    warnings: Arc<AtomicUsize>,
+
+    /// How the project directory is found, see `Project::locate()`.
+    project_opts: ProjectOpts,
 }
 
 impl App {
@@ -158,6 +320,16 @@ impl App {
             interrupt,
             post_process: !opts.no_postprocess,
             keep_interm: opts.keep,
+            song: opts.song.clone(),
+            outputs: opts.outputs.clone(),
+            quarantine: opts.quarantine,
+            fail_on_warnings: opts.fail_on_warnings,
+            strict: opts.strict,
+            notify: !opts.no_notify,
+            update_templates: opts.update_templates,
+            stats_memory: opts.stats_memory,
+            prune: opts.prune,
+            timings: opts.timings,
             term: Term::stderr(),
             verbosity: opts.stdio.verbosity(),
             test_mode: false,
@@ -165,6 +337,9 @@ impl App {
             self_name: "bard",
             img_cache: ImgCache::new(),
             parser_diags: None,
+            diags: Arc::new(Mutex::new(vec![])),
+            warnings: Arc::new(AtomicUsize::new(0)),
+            project_opts: ProjectOpts::default(),
         }
     }
 
@@ -175,6 +350,16 @@ impl App {
             interrupt,
             post_process,
             keep_interm: keeplevel::ALL,
+            song: None,
+            outputs: vec![],
+            quarantine: false,
+            fail_on_warnings: false,
+            strict: false,
+            notify: true,
+            update_templates: false,
+            stats_memory: false,
+            prune: false,
+            timings: false,
             term: Term::stderr(),
             verbosity: 2,
             test_mode: true,
@@ -182,9 +367,61 @@ impl App {
             self_name: "bard",
             img_cache: ImgCache::new(),
             parser_diags: Some(Arc::new(Mutex::new(vec![]))),
+            diags: Arc::new(Mutex::new(vec![])),
+            warnings: Arc::new(AtomicUsize::new(0)),
+            project_opts: ProjectOpts::default(),
         }
     }
 
+    /// Set project lookup options, `strict_project_dir` may also be enabled from the environment.
+    pub fn with_project_opts(mut self, mut opts: ProjectOpts) -> Self {
+        opts.apply_env();
+        self.project_opts = opts;
+        self
+    }
+
+    /// See `MakeOpts::fail_on_warnings`.
+    pub fn with_fail_on_warnings(mut self, fail_on_warnings: bool) -> Self {
+        self.fail_on_warnings = fail_on_warnings;
+        self
+    }
+
+    /// See `MakeOpts::outputs`.
+    pub fn with_outputs(mut self, outputs: Vec<String>) -> Self {
+        self.outputs = outputs;
+        self
+    }
+
+    /// See `MakeOpts::strict`.
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// See `MakeOpts::no_notify`.
+    pub fn with_notify(mut self, notify: bool) -> Self {
+        self.notify = notify;
+        self
+    }
+
+    /// See `MakeOpts::update_templates`.
+    pub fn with_update_templates(mut self, update_templates: bool) -> Self {
+        self.update_templates = update_templates;
+        self
+    }
+
+    /// See `MakeOpts::stats_memory`.
+    pub fn with_stats_memory(mut self, stats_memory: bool) -> Self {
+        self.stats_memory = stats_memory;
+        self
+    }
+
+    /// See `MakeOpts::prune`.
+    pub fn with_prune(mut self, prune: bool) -> Self {
+        self.prune = prune;
+        self
+    }
+
     #[cfg(feature = "tectonic")]
     pub fn new_as_tectonic(interrupt: InterruptFlag) -> Self {
         let mut this = Self::new(&MakeOpts::default(), interrupt);
@@ -201,10 +438,47 @@ impl App {
         self.keep_interm
     }
 
+    pub fn song(&self) -> Option<&str> {
+        self.song.as_deref()
+    }
+
+    pub fn outputs(&self) -> &[String] {
+        &self.outputs
+    }
+
+    pub fn quarantine(&self) -> bool {
+        self.quarantine
+    }
+
+    pub fn prune(&self) -> bool {
+        self.prune
+    }
+
+    pub fn strict(&self) -> bool {
+        self.strict
+    }
+
+    pub fn notify(&self) -> bool {
+        self.notify
+    }
+
+    pub fn update_templates(&self) -> bool {
+        self.update_templates
+    }
+
     pub fn verbosity(&self) -> u8 {
         self.verbosity
     }
 
+    /// See `verbosity` for levels.
+    pub fn set_verbosity(&mut self, verbosity: u8) {
+        self.verbosity = verbosity;
+    }
+
+    pub fn project_opts(&self) -> &ProjectOpts {
+        &self.project_opts
+    }
+
     pub fn use_color(&self) -> bool {
         console::colors_enabled_stderr()
     }
@@ -221,6 +495,36 @@ impl App {
         self.parser_diags.as_ref().unwrap()
     }
 
+    /// Diagnostics reported so far, see `diag()`.
+    pub fn diags(&self) -> Vec<BuildDiagnostic> {
+        self.diags.lock().clone()
+    }
+
+    /// Forget the diagnostics and warnings reported so far,
+    /// so that each rebuild in watch mode only reports its own.
+    pub fn reset_diags(&self) {
+        self.diags.lock().clear();
+        self.warnings.store(0, Ordering::Relaxed);
+    }
+
+    /// Number of warnings emitted so far.
+    pub fn warnings(&self) -> usize {
+        self.warnings.load(Ordering::Relaxed)
+    }
+
+    /// With `--fail-on-warnings`, fail if any warnings were emitted so far.
+    pub fn check_warnings(&self) -> Result<()> {
+        let warnings = self.warnings();
+        if self.fail_on_warnings && warnings > 0 {
+            bail!(
+                "{} warning{} emitted, failing because of --fail-on-warnings",
+                warnings,
+                if warnings == 1 { " was" } else { "s were" }
+            );
+        }
+        Ok(())
+    }
+
     // SIGINT support
 
     pub fn check_interrupted(&self) -> Result<(), InterruptError> {
@@ -292,7 +596,33 @@ impl App {
         self.status_inner(verb, &self.color(Green), "");
     }
 
+    /// Like `status()`, but highlighted, for things the user should take notice of.
+    pub fn status_notice(&self, verb: &str, status: impl Display) {
+        self.status_inner(verb, &self.color(Yellow), status);
+    }
+
+    /// Report that `what` is done and how long it took, eg. `Finished songbook.pdf in 3.4s`.
+    pub fn finished(&self, what: impl Display, elapsed: Duration) {
+        let status = format!("{} in {}", what, fmt_elapsed(elapsed));
+        self.status_inner("Finished", &self.color(Green), status);
+    }
+
+    /// With `--timings`, report how long a build `phase` took, eg. `TeX: 3.1s`.
+    pub fn timing(&self, phase: &str, elapsed: Duration) {
+        if self.timings {
+            self.indent(format!("{}: {}", phase, fmt_elapsed(elapsed)));
+        }
+    }
+
+    /// Report `stats` of a build, if enabled with `--stats-memory` or in verbose mode.
+    pub fn debug_stats(&self, stats: &DebugStats) {
+        if self.stats_memory || self.verbosity >= verbosity::VERBOSE {
+            self.status_inner("Stats", &self.color(Cyan), stats);
+        }
+    }
+
     pub fn warning(&self, msg: impl Display) {
+        self.warnings.fetch_add(1, Ordering::Relaxed);
         self.status_inner("Warning", &self.color(Yellow), msg);
     }
 
@@ -335,11 +665,24 @@ impl App {
                 .push(diag.clone());
         }
 
+        self.diag(diag.into());
+    }
+
+    /// Report a diagnostic of any build phase: it's collected and printed,
+    /// a warning counts towards `--fail-on-warnings`.
+    pub fn diag(&self, diag: BuildDiagnostic) {
         if diag.is_error() {
-            self.error_generic(diag);
+            self.error_generic(&diag);
         } else {
-            self.warning(diag);
+            self.warning(&diag);
         }
+        self.diags.lock().push(diag);
+    }
+
+    /// Collect a diagnostic of an error which is also returned as the error of the build,
+    /// so it isn't printed twice.
+    pub fn record_diag(&self, diag: BuildDiagnostic) {
+        self.diags.lock().push(diag);
     }
 
     pub fn subprocess_output(
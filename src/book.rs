@@ -1,19 +1,25 @@
 //! AST of a bard songbook
 
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
+use std::mem;
+use std::slice;
+use std::sync::Arc;
 
-use image::image_dimensions;
-use serde::Serialize;
+use image::ImageError;
+use serde::{Serialize, Serializer};
+use strum::Display;
 
-use crate::music::Notation;
+use crate::music::{self, MinorChords, Notation};
 use crate::prelude::*;
-use crate::project::Settings;
-use crate::util::{sort_lexical_by, BStr, ImgCache};
+use crate::project::{Metadata, Settings};
+use crate::time::Duration;
+use crate::util::{slugify, slugify_id, BStr, ImgCache, SortOrder};
 
+pub mod audit;
 pub mod version;
 mod xml;
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Clone, Debug)]
 #[serde(tag = "type")]
 pub enum Block {
     #[serde(rename = "b-verse")]
@@ -27,6 +33,9 @@ pub enum Block {
     /// An HTML block contains inlines which can only be `Text`, `HtmlTag`, or `Break`.
     #[serde(rename = "b-html-block")]
     HtmlBlock(Inlines),
+    /// Only found in front and back matter, in songs headings are titles and verse labels.
+    #[serde(rename = "b-heading")]
+    Heading { level: u32, text: BStr },
 }
 
 impl Block {
@@ -57,6 +66,26 @@ impl Block {
         }
     }
 
+    /// See `Song::visit_text()`.
+    pub fn visit_text(&self, visitor: &mut impl FnMut(&str)) {
+        let inlines_text = |inlines: &[Inline]| {
+            let mut text = String::new();
+            inlines.iter().for_each(|i| i.push_text(&mut text));
+            text
+        };
+
+        match self {
+            Self::Verse(verse) => verse
+                .paragraphs
+                .iter()
+                .for_each(|p| visitor(&inlines_text(p))),
+            Self::BulletList(list) => list.items.iter().for_each(|item| visitor(item)),
+            Self::HtmlBlock(inlines) => visitor(&inlines_text(&inlines.inlines)),
+            Self::Heading { text, .. } => visitor(text),
+            Self::HorizontalLine | Self::Pre { .. } => {}
+        }
+    }
+
     fn verse(&self) -> Option<&Verse> {
         match self {
             Self::Verse(verse) => Some(verse),
@@ -73,7 +102,7 @@ impl Block {
 }
 
 /// Needed for Inline enum tagging in JSON and similar...
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Clone, Debug)]
 pub struct Inlines {
     pub inlines: Box<[Inline]>,
 }
@@ -102,15 +131,21 @@ impl AsRef<[Inline]> for Inlines {
     }
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Clone, Debug)]
 #[serde(rename_all = "snake_case")]
 #[serde(tag = "type")]
 pub enum Inline {
     #[serde(rename = "i-text")]
-    Text { text: BStr },
+    Text {
+        text: BStr,
+        /// Source line, see `Inline::line()`, **not** part of the AST.
+        #[serde(skip)]
+        line: Option<u32>,
+    },
     #[serde(rename = "i-chord")]
     Chord(Chord),
-    /// All line breaks are considered hard breaks
+    /// Line breaks are all considered hard breaks,
+    /// soft breaks are only turned into spaces with `soft_breaks = "space"`
     #[serde(rename = "i-break")]
     Break,
     #[serde(rename = "i-emph")]
@@ -123,8 +158,15 @@ pub enum Inline {
     Image(Image),
     #[serde(rename = "i-chorus-ref")]
     ChorusRef(ChorusRef),
+    /// A reference to several choruses in a row, `!>1-3`.
+    #[serde(rename = "i-chorus-ref-range")]
+    ChorusRefRange(ChorusRefRange),
     #[serde(rename = "i-tag")]
     HtmlTag(HtmlTag),
+    /// A musical directive within song text.
+    /// Directives found before the first verse are moved into `Song` fields instead.
+    #[serde(rename = "i-directive")]
+    Directive(Directive),
 
     /// Only used internally by the parser to apply transposition.
     /// Removed from the resulting AST, except in tests where this
@@ -135,20 +177,41 @@ pub enum Inline {
 
 impl Inline {
     pub fn text(text: impl Into<BStr>) -> Self {
-        Self::Text { text: text.into() }
+        Self::text_at(text, None)
+    }
+
+    pub fn text_at(text: impl Into<BStr>, line: Option<u32>) -> Self {
+        Self::Text {
+            text: text.into(),
+            line,
+        }
+    }
+
+    /// Line of the source file where this text or chord is, 1-indexed. Lines are only recorded
+    /// with `ParserConfig::record_positions`, for other inlines this is always `None`.
+    pub fn line(&self) -> Option<u32> {
+        match self {
+            Self::Text { line, .. } => *line,
+            Self::Chord(chord) => chord.line,
+            _ => None,
+        }
     }
 
     pub fn is_break(&self) -> bool {
         matches!(self, Self::Break)
     }
 
+    pub fn is_directive(&self) -> bool {
+        matches!(self, Self::Directive(..))
+    }
+
     pub fn is_xpose(&self) -> bool {
         matches!(self, Self::Transpose(..))
     }
 
     pub fn unwrap_xpose(&self) -> Transpose {
         match self {
-            Self::Transpose(xpose) => *xpose,
+            Self::Transpose(xpose) => xpose.clone(),
             _ => panic!("Unexpected inline: {:?}", self),
         }
     }
@@ -176,31 +239,101 @@ impl Inline {
             _ => None,
         }
     }
+
+    /// Append the plain text of this inline to `out`, see `Song::visit_text()`.
+    fn push_text(&self, out: &mut String) {
+        match self {
+            Self::Text { text, .. } => out.push_str(text),
+            Self::Chord(Chord { inlines, .. })
+            | Self::Emph(Inlines { inlines })
+            | Self::Strong(Inlines { inlines }) => inlines.iter().for_each(|i| i.push_text(out)),
+            Self::Link(link) => out.push_str(&link.text),
+            Self::Break => out.push('\n'),
+            // Tags don't separate words, eg. in `<i>Sum</i>mer`
+            Self::HtmlTag(..) => {}
+            Self::Image(..)
+            | Self::ChorusRef(..)
+            | Self::ChorusRefRange(..)
+            | Self::Directive(..)
+            | Self::Transpose(..) => out.push(' '),
+        }
+    }
+
+    /// Collect links in this inline, including ones nested in chords and emphasis.
+    fn collect_links<'s>(&'s self, links: &mut Vec<&'s Link>) {
+        match self {
+            Self::Link(link) => links.push(link),
+            Self::Chord(Chord { inlines, .. })
+            | Self::Emph(Inlines { inlines })
+            | Self::Strong(Inlines { inlines }) => {
+                inlines.iter().for_each(|i| i.collect_links(links))
+            }
+            _ => {}
+        }
+    }
+
+    /// Call `f` with the chords in this inline, including ones nested in chords and emphasis.
+    fn visit_chords_mut(&mut self, f: &mut impl FnMut(&mut Chord)) {
+        match self {
+            Self::Chord(chord) => {
+                f(chord);
+                chord.inlines.iter_mut().for_each(|i| i.visit_chords_mut(f));
+            }
+            Self::Emph(Inlines { inlines }) | Self::Strong(Inlines { inlines }) => {
+                inlines.iter_mut().for_each(|i| i.visit_chords_mut(f))
+            }
+            _ => {}
+        }
+    }
+
+    /// Call `visitor` with this inline and then with the ones nested in it, see `Song::visit_inlines()`.
+    fn visit<'s>(&'s self, visitor: &mut impl FnMut(&'s Inline)) {
+        visitor(self);
+        match self {
+            Self::Chord(Chord { inlines, .. })
+            | Self::Emph(Inlines { inlines })
+            | Self::Strong(Inlines { inlines }) => inlines.iter().for_each(|i| i.visit(visitor)),
+            _ => {}
+        }
+    }
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Clone, Debug)]
 pub struct Chord {
     pub chord: BStr,
     pub alt_chord: Option<BStr>,
+    /// Chromatic index of the chord's root (0 for C), if the chord is a single chord
+    /// without a bass note, so that templates can transpose it, see `music::chord_root()`.
+    pub root_chromatic: Option<u8>,
+    /// The rest of the chord after the root, eg. `m7`, set along with `root_chromatic`.
+    pub suffix: Option<BStr>,
     pub backticks: usize,
     pub baseline: bool,
     pub inlines: Box<[Inline]>,
+    /// Source line, see `Inline::line()`, **not** part of the AST.
+    #[serde(skip)]
+    pub line: Option<u32>,
 }
 
 impl Chord {
     pub fn new(
         chord: BStr,
         alt_chord: Option<BStr>,
+        root: Option<(u8, BStr)>,
         backticks: usize,
         baseline: bool,
         inlines: Vec<Inline>,
     ) -> Self {
+        let (root_chromatic, suffix) = root.unzip();
         Self {
             chord,
             alt_chord,
+            root_chromatic,
+            suffix,
             backticks,
             baseline,
             inlines: inlines.into(),
+            line: None,
         }
     }
 
@@ -209,7 +342,7 @@ impl Chord {
     }
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Clone, Debug)]
 pub struct Link {
     pub url: BStr,
     pub title: BStr,
@@ -222,11 +355,13 @@ impl Link {
     }
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Clone, Debug)]
 pub struct Image {
     pub path: BStr,
     pub title: BStr,
+    /// All of `classes` separated by a space.
     pub class: BStr,
+    pub classes: Box<[BStr]>,
     /// Size in pixels, initially `0`, resolved during book postprocessing.
     pub width: u32,
     /// Size in pixels, initially `0`, resolved during book postprocessing.
@@ -236,45 +371,65 @@ pub struct Image {
     /// **not** part of the AST.
     #[serde(skip)]
     full_path: Option<PathBuf>,
+    /// Error reading the image file during book postprocessing, **not** part of the AST.
+    /// It is reported by the outputs which include the image, see `Image::check()`.
+    #[serde(skip)]
+    error: Option<Arc<ImageError>>,
 }
 
 impl Image {
-    pub fn new(path: BStr, title: BStr, class: BStr) -> Self {
+    /// Classes given a specific layout by the default templates, other classes are passed through to HTML.
+    pub const KNOWN_CLASSES: &'static [&'static str] = &["center", "right", "fullwidth"];
+
+    pub fn new(path: BStr, title: BStr, classes: Vec<BStr>) -> Self {
+        let class = classes.join(" ").into();
         Self {
             path,
             title,
             class,
+            classes: classes.into(),
             width: 0,
             height: 0,
             full_path: None,
+            error: None,
         }
     }
 
-    fn resolve(&mut self, output_dir: &Path, img_cache: &ImgCache) -> Result<()> {
+    pub(crate) fn resolve(&mut self, output_dir: &Path, img_cache: &ImgCache) -> Result<()> {
         let path = Path::new(&*self.path);
         if self.path.contains("://") || path.is_absolute() {
             bail!("Image path has to be relative and pointing to a local file.");
         }
 
         let full_path = output_dir.join(path);
-        let (w, h) = img_cache.try_get(&full_path, || {
-            image_dimensions(&full_path)
-                .with_context(|| format!("Couldn't read image file {:?}", full_path))
-        })?;
-
-        self.width = w;
-        self.height = h;
+        match img_cache.dimensions(&full_path) {
+            Ok((w, h)) => {
+                self.width = w;
+                self.height = h;
+                self.error = None;
+            }
+            Err(err) => self.error = Some(err),
+        }
         self.full_path = Some(full_path);
 
         Ok(())
     }
 
+    /// Returns the error reading the image file, if there was one during book postprocessing.
+    pub fn check(&self) -> Result<()> {
+        match self.error.as_ref() {
+            Some(err) => Err(Error::new(err.clone()))
+                .with_context(|| format!("Couldn't read image file {:?}", self.full_path())),
+            None => Ok(()),
+        }
+    }
+
     pub fn full_path(&self) -> &Path {
         self.full_path.as_deref().unwrap()
     }
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Clone, Debug)]
 pub struct ChorusRef {
     pub num: Option<u32>,
     pub prefix_space: BStr,
@@ -289,14 +444,147 @@ impl ChorusRef {
     }
 }
 
-#[derive(Serialize, Debug)]
+/// A reference to choruses `start` to `end` inclusive, `!>1-3`.
+///
+/// Unlike in `ChorusRef`, the numbers are kept in songs with a single chorus.
+#[derive(Serialize, Clone, Debug)]
+pub struct ChorusRefRange {
+    pub start: u32,
+    pub end: u32,
+    pub prefix_space: BStr,
+}
+
+impl ChorusRefRange {
+    pub fn new(start: u32, end: u32, prefix_space: bool) -> Self {
+        Self {
+            start,
+            end,
+            prefix_space: if prefix_space { " ".into() } else { "".into() },
+        }
+    }
+
+    /// Whether this is a range of at least two choruses counted from 1.
+    pub fn is_valid(&self) -> bool {
+        self.start > 0 && self.start < self.end
+    }
+}
+
+#[derive(Serialize, Display, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum DirectiveKind {
+    /// Tempo in beats per minute, `!tempo(120)`.
+    Tempo,
+    /// Time signature, `!time(3/4)`.
+    Time,
+    /// Song duration, `!duration(3:45)`.
+    Duration,
+    /// Musical key, `!key(Am)`, transposed along with chords.
+    Key,
+    /// A previous title of the song, `!alias(Old Title)`, not rendered.
+    Alias,
+    /// Text direction of the song, `!dir(rtl)` or `!dir(ltr)`, not rendered.
+    Dir,
+}
+
+/// Text direction of a song.
+#[derive(Serialize, Display, Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum TextDir {
+    #[default]
+    Ltr,
+    Rtl,
+}
+
+impl TextDir {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "ltr" => Some(Self::Ltr),
+            "rtl" => Some(Self::Rtl),
+            _ => None,
+        }
+    }
+
+    /// Direction of the first strongly-directional char of `text`, `Ltr` if there is none.
+    pub fn detect(text: &str) -> Self {
+        text.chars()
+            .find_map(|c| {
+                if is_rtl_char(c) {
+                    Some(Self::Rtl)
+                } else if c.is_alphabetic() {
+                    Some(Self::Ltr)
+                } else {
+                    None
+                }
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// Which word a chord belongs to, set per song with the `!chordpos(after)` extension.
+#[derive(Serialize, Display, Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum ChordPos {
+    /// Chords are written before the text they belong to, which is the Markdown order.
+    #[default]
+    Before,
+    /// Chords are written after the word they belong to, eg. in imported songs,
+    /// the parser attaches each chord to the preceding word.
+    After,
+}
+
+impl ChordPos {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "before" => Some(Self::Before),
+            "after" => Some(Self::After),
+            _ => None,
+        }
+    }
+}
+
+/// Whether `c` is a letter of a right-to-left script, ie. Hebrew, Arabic, Syriac, Thaana, etc.
+fn is_rtl_char(c: char) -> bool {
+    matches!(c,
+        '\u{0590}'..='\u{08FF}'
+        | '\u{FB1D}'..='\u{FDFF}'
+        | '\u{FE70}'..='\u{FEFF}'
+        | '\u{10800}'..='\u{10FFF}'
+        | '\u{1E800}'..='\u{1EFFF}'
+    ) && c.is_alphabetic()
+}
+
+#[derive(Serialize, Clone, PartialEq, Eq, Debug)]
+pub struct Directive {
+    pub kind: DirectiveKind,
+    pub value: BStr,
+    pub prefix_space: BStr,
+}
+
+impl Directive {
+    pub fn new(kind: DirectiveKind, value: impl Into<BStr>, prefix_space: bool) -> Self {
+        Self {
+            kind,
+            value: value.into(),
+            prefix_space: if prefix_space { " ".into() } else { "".into() },
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
 pub struct HtmlTag {
     pub name: BStr,
     pub attrs: BTreeMap<BStr, BStr>,
+    /// Text following the tag in an HTML block, only kept if the parser is configured so,
+    /// see `ParserConfig::html_text`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<BStr>,
 }
 
 /// Transposition extensions. See Comment in `Inline`.
-#[derive(Serialize, Clone, Copy, Debug)]
+#[derive(Serialize, Clone, Debug)]
 pub enum Transpose {
     #[serde(rename = "t-transpose")]
     Transpose(i32),
@@ -306,6 +594,9 @@ pub enum Transpose {
     AltTranspose(i32),
     #[serde(rename = "t-alt-notation")]
     AltNotation(Notation),
+    /// Alt chords in the Roman notation with lowercase minor chords, see `MinorChords::Lowercase`
+    #[serde(rename = "t-alt-roman-minor")]
+    AltRomanMinor,
     /// Turn off alt chords
     #[serde(rename = "t-alt-none")]
     AltNone,
@@ -328,15 +619,28 @@ impl VerseLabel {
 
 pub type Paragraph = Box<[Inline]>;
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Clone, Debug)]
 pub struct Verse {
     pub label: VerseLabel,
     pub paragraphs: Vec<Paragraph>,
+    /// Whether this is the first verse block of the song, set in `Song::postprocess()`.
+    pub first_verse: bool,
+    /// Whether this is the last verse block of the song, set in `Song::postprocess()`.
+    pub last_verse: bool,
+    /// Whether this verse continues the previous one after an interrupting block, eg. a preformatted one.
+    /// It has the label of the previous verse, which isn't meant to be rendered again.
+    pub continued: bool,
 }
 
 impl Verse {
     pub fn new(label: VerseLabel, paragraphs: Vec<Paragraph>) -> Self {
-        Self { label, paragraphs }
+        Self {
+            label,
+            paragraphs,
+            first_verse: false,
+            last_verse: false,
+            continued: false,
+        }
     }
 
     pub fn is_empty(&self) -> bool {
@@ -352,27 +656,244 @@ impl Verse {
     }
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Clone, Debug)]
 pub struct BulletList {
     pub items: Box<[BStr]>,
 }
 
-#[derive(Serialize, Debug)]
+/// Where a song is defined, used to point users to the source in reports.
+#[derive(Clone, Default, Debug)]
+pub struct SongLocation {
+    /// The song file, relative to the project directory.
+    pub file: PathBuf,
+    /// Line of the song title (or first element), 1-indexed.
+    pub line: usize,
+}
+
+impl SongLocation {
+    pub fn new(file: impl Into<PathBuf>, line: usize) -> Self {
+        Self {
+            file: file.into(),
+            line,
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
 pub struct Song {
+    /// Plain text title, chords in the title are left out, see `TitleChords`.
     pub title: BStr,
+    /// Id of the song made from its title, unique in the book, eg. for links to the song in HTML,
+    /// assigned in `Book::postprocess()`.
+    pub id: BStr,
+    /// The title including chords, if any.
+    pub title_inlines: Box<[Inline]>,
     pub subtitles: Box<[BStr]>,
+    /// Each block is serialized along with its `block_index`.
+    #[serde(serialize_with = "serialize_blocks")]
     pub blocks: Vec<Block>,
     pub notation: Notation,
+    /// Tempo from a `!tempo()` directive before the first verse, if any.
+    pub tempo: Option<BStr>,
+    /// Time signature from a `!time()` directive before the first verse, if any.
+    pub time: Option<BStr>,
+    /// Song duration from a `!duration()` directive before the first verse, if any.
+    pub duration: Option<Duration>,
+    /// Key from a `!key()` directive before the first verse, if any.
+    pub key: Option<BStr>,
+    /// Previous titles of the song from `!alias()` directives before the first verse.
+    pub aliases: Box<[BStr]>,
+    /// Values from a ` ```meta ` TOML block after the title or the subtitles, eg. `capo = 2`,
+    /// passed to templates as they are.
+    pub metadata: Metadata,
+    /// Text direction from a `!dir()` directive before the first verse,
+    /// otherwise detected from the title.
+    pub dir: TextDir,
+    /// How chords were attached to text in the song's source, see `ChordPos`,
+    /// in the AST chords always contain the text they belong to.
+    pub chord_pos: ChordPos,
+    /// The Markdown source text of the song, only recorded with the `include_sources` setting.
+    pub source: Option<BStr>,
+    /// Distinct chords of the song, including ones in the title, in order of first appearance,
+    /// eg. for chord diagrams. Chords are taken verbatim, even ones that aren't recognized, like `N.C.`
+    pub chords_used: Vec<BStr>,
+    /// Distinct alt chords (2nd row) of the song, like `chords_used`.
+    pub alt_chords_used: Vec<BStr>,
+    /// Notations alt chords in the song were converted to, collected into `Book::alt_notations`.
+    #[serde(skip)]
+    pub alt_notations: Box<[Notation]>,
+    #[serde(skip)]
+    pub location: SongLocation,
 }
 
 impl Song {
+    /// Set a unique `id` of each of the `songs` made from its title,
+    /// if an id is taken, a number is appended to it, eg. `danny-boy-2`.
+    fn assign_ids(songs: &mut [Song]) {
+        let mut ids = BTreeSet::new();
+        for song in songs.iter_mut() {
+            let slug = slugify_id(&song.title);
+            let mut id = slug.clone();
+            for n in 2.. {
+                if ids.insert(id.clone()) {
+                    break;
+                }
+                id = format!("{}-{}", slug, n);
+            }
+            song.id = id.into();
+        }
+    }
+
+    /// Links in the song's verses, in order of appearance.
+    pub fn links(&self) -> Vec<&Link> {
+        let mut links = vec![];
+        self.blocks
+            .iter()
+            .filter_map(Block::verse)
+            .flat_map(|v| v.inlines())
+            .for_each(|i| i.collect_links(&mut links));
+        links
+    }
+
+    /// Images in the song's verses, in order of appearance.
+    pub fn images(&self) -> impl Iterator<Item = &Image> {
+        self.blocks
+            .iter()
+            .filter_map(Block::verse)
+            .flat_map(|v| v.inlines())
+            .filter_map(Inline::image)
+    }
+
+    /// Call `visitor` with runs of the song's plain text in order of appearance:
+    /// the title, subtitles, each paragraph, bullet list item, HTML block, and heading.
+    ///
+    /// Chord names, preformatted blocks, HTML tags, images, and directives are left out.
+    /// Text split by chords is joined back together, line breaks are passed as `'\n'`.
+    pub fn visit_text(&self, mut visitor: impl FnMut(&str)) {
+        visitor(&self.title);
+        self.subtitles.iter().for_each(|s| visitor(s));
+        self.blocks.iter().for_each(|b| b.visit_text(&mut visitor));
+    }
+
+    /// The first line of lyrics of the song, for an index of first lines: the text of the first line
+    /// of a verse or chorus that has any, with chords left out. Blocks other than verses are skipped.
+    ///
+    /// Empty if the song has no lyrics, eg. only chords or no verses at all.
+    pub fn first_line(&self) -> BStr {
+        let lines = self
+            .blocks
+            .iter()
+            .filter_map(Block::verse)
+            .flat_map(|v| v.paragraphs.iter())
+            .flat_map(|p| p.split(Inline::is_break));
+        for line in lines {
+            let mut text = String::new();
+            line.iter().for_each(|i| i.push_text(&mut text));
+            let text = text.trim();
+            if !text.is_empty() {
+                return text.into();
+            }
+        }
+        BStr::default()
+    }
+
+    /// Call `visitor` with all the inlines of the song in order of appearance, depth-first:
+    /// the title, paragraphs of verses, and HTML blocks. A chord is visited before the inlines it contains.
+    ///
+    /// Together with `Inline::line()`, this lets tools point to the source of text and chords.
+    pub fn visit_inlines<'s>(&'s self, mut visitor: impl FnMut(&'s Inline)) {
+        self.title_inlines
+            .iter()
+            .for_each(|i| i.visit(&mut visitor));
+        for block in self.blocks.iter() {
+            let paragraphs = match block {
+                Block::Verse(verse) => &verse.paragraphs[..],
+                Block::HtmlBlock(inlines) => slice::from_ref(&inlines.inlines),
+                _ => continue,
+            };
+            paragraphs
+                .iter()
+                .flat_map(|p| p.iter())
+                .for_each(|i| i.visit(&mut visitor));
+        }
+    }
+
+    /// Convert chords of the song, as well as the `key` and `chords_used`, from the song's notation to `notation`,
+    /// eg. for an output with its own notation. Alt chords are in a notation of their own and stay as they are.
+    ///
+    /// Returns the chords that couldn't be converted, these are left as they are.
+    pub fn convert_notation(&mut self, notation: &Notation, minor: MinorChords) -> Vec<BStr> {
+        let src = mem::replace(&mut self.notation, notation.clone());
+        let mut failed: Vec<BStr> = vec![];
+        let mut convert = |chord: &mut BStr| -> bool {
+            match music::transpose(chord, 0, &src, notation, minor) {
+                Ok(converted) => {
+                    *chord = converted.into();
+                    true
+                }
+                Err(_) => {
+                    if !failed.contains(chord) {
+                        failed.push(chord.clone());
+                    }
+                    false
+                }
+            }
+        };
+
+        let verses = self.blocks.iter_mut().filter_map(Block::verse_mut);
+        let inlines = self
+            .title_inlines
+            .iter_mut()
+            .chain(verses.flat_map(|v| v.inlines_mut()));
+        for inline in inlines {
+            inline.visit_chords_mut(&mut |chord| {
+                if convert(&mut chord.chord) {
+                    let (root, suffix) = music::chord_root(&chord.chord, notation)
+                        .map(|(root, suffix)| (root.into(), suffix.into()))
+                        .unzip();
+                    chord.root_chromatic = root;
+                    chord.suffix = suffix;
+                }
+            });
+        }
+        if let Some(key) = self.key.as_mut() {
+            convert(key);
+        }
+        self.chords_used.iter_mut().for_each(|chord| {
+            convert(chord);
+        });
+
+        failed
+    }
+
+    /// Chords in the song's verses, in order of appearance.
+    pub fn chords(&self) -> Vec<&Chord> {
+        self.blocks
+            .iter()
+            .filter_map(Block::verse)
+            .flat_map(|v| v.inlines())
+            .filter_map(|i| match i {
+                Inline::Chord(chord) => Some(chord),
+                _ => None,
+            })
+            .collect()
+    }
+
     /// AST postprocessing.
     ///
     /// This entails removing empty paragraphs and verses
     /// which linger when transposition extensions are applied & removed.
+    /// With `merge_text`, consecutive text inlines in paragraphs
+    /// left over by removed extensions are merged, see `merge_text()`.
     ///
     /// Distinct from `Book::postprocess()`, this is done by `Parser`.
-    pub fn postprocess(&mut self) {
+    pub fn postprocess(&mut self, merge_text: bool) {
+        if merge_text {
+            for verse in self.blocks.iter_mut().filter_map(Block::verse_mut) {
+                verse.paragraphs.iter_mut().for_each(merge_text_inlines);
+            }
+        }
+
         // Remove paragraphs which contain nothing or linebreaks only
         for verse in self.blocks.iter_mut().filter_map(Block::verse_mut) {
             verse
@@ -385,12 +906,103 @@ impl Song {
             Block::Verse(verse) => verse.label.is_some() || !verse.paragraphs.is_empty(),
             _ => true,
         });
+
+        // Mark the first and last verse for templates
+        let mut verses: Vec<_> = self
+            .blocks
+            .iter_mut()
+            .filter_map(Block::verse_mut)
+            .collect();
+        if let Some(first) = verses.first_mut() {
+            first.first_verse = true;
+        }
+        if let Some(last) = verses.last_mut() {
+            last.last_verse = true;
+        }
+
+        self.collect_chords_used();
     }
+
+    /// Collect `chords_used` and `alt_chords_used`.
+    fn collect_chords_used(&mut self) {
+        let mut chords: Vec<BStr> = vec![];
+        let mut alt_chords: Vec<BStr> = vec![];
+        self.visit_inlines(|inline| {
+            if let Inline::Chord(chord) = inline {
+                if !chord.chord.is_empty() && !chords.contains(&chord.chord) {
+                    chords.push(chord.chord.clone());
+                }
+                if let Some(alt_chord) = chord.alt_chord.as_ref() {
+                    if !alt_chords.contains(alt_chord) {
+                        alt_chords.push(alt_chord.clone());
+                    }
+                }
+            }
+        });
+
+        self.chords_used = chords;
+        self.alt_chords_used = alt_chords;
+    }
+}
+
+/// Merge consecutive `Text` inlines, recursively in chords and emphasis.
+///
+/// Where an extension was removed, both of the texts around it may keep their whitespace,
+/// eg. `Lyrics ` and ` more`, the duplicate whitespace at the boundary is collapsed
+/// into that of the first text.
+fn merge_text_inlines(inlines: &mut Box<[Inline]>) {
+    let mut merged: Vec<Inline> = Vec::with_capacity(inlines.len());
+    for mut inline in mem::take(inlines).into_vec() {
+        match &mut inline {
+            Inline::Chord(Chord { inlines, .. })
+            | Inline::Emph(Inlines { inlines })
+            | Inline::Strong(Inlines { inlines }) => merge_text_inlines(inlines),
+            _ => {}
+        }
+
+        match (merged.last_mut(), inline) {
+            (Some(Inline::Text { text: prev, .. }), Inline::Text { text, .. }) => {
+                let text = if prev.ends_with(char::is_whitespace) {
+                    text.trim_start()
+                } else {
+                    &text
+                };
+                *prev = format!("{}{}", prev, text).into();
+            }
+            (_, inline) => merged.push(inline),
+        }
+    }
+
+    *inlines = merged.into();
+}
+
+/// Serialize `Song::blocks` with the index of each block in the song added as `block_index`.
+fn serialize_blocks<S>(blocks: &[Block], ser: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    #[derive(Serialize)]
+    struct IndexedBlock<'a> {
+        #[serde(flatten)]
+        block: &'a Block,
+        block_index: usize,
+    }
+
+    ser.collect_seq(
+        blocks
+            .iter()
+            .enumerate()
+            .map(|(block_index, block)| IndexedBlock { block, block_index }),
+    )
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Clone, Debug)]
 pub struct SongRef {
     pub title: BStr,
+    /// The song's `id`.
+    pub id: BStr,
+    /// See `Song::first_line()`.
+    pub first_line: BStr,
     /// index of the song in the Book::songs vector
     pub idx: usize,
 }
@@ -399,24 +1011,107 @@ impl SongRef {
     pub fn new((idx, songs): (usize, &Song)) -> Self {
         Self {
             title: songs.title.clone(),
+            id: songs.id.clone(),
+            first_line: songs.first_line(),
             idx,
         }
     }
 }
 
+/// A previous title of a song from an `!alias()` directive.
+#[derive(Serialize, Clone, Debug)]
+pub struct SongAlias {
+    pub alias: BStr,
+    /// The alias made into a slug, usable as an anchor or a file name
+    pub slug: BStr,
+    /// index of the song in the Book::songs vector
+    pub idx: usize,
+}
+
+impl SongAlias {
+    /// Collect aliases of all the `songs`, an alias is an error if it's the same
+    /// as the title or an alias of another song, compared as slugs.
+    fn build(songs: &[Song]) -> Result<Vec<Self>> {
+        let mut slugs: BTreeMap<String, usize> = BTreeMap::new();
+        for (idx, song) in songs.iter().enumerate() {
+            slugs.entry(slugify(&song.title)).or_insert(idx);
+        }
+
+        let mut aliases = vec![];
+        for (idx, song) in songs.iter().enumerate() {
+            for alias in song.aliases.iter() {
+                let slug = slugify(alias);
+                match slugs.get(&slug) {
+                    Some(&other) if other != idx => bail!(
+                        "Alias '{}' of song '{}' conflicts with song '{}'.",
+                        alias,
+                        song.title,
+                        songs[other].title
+                    ),
+                    Some(_) => {}
+                    None => {
+                        slugs.insert(slug.clone(), idx);
+                        aliases.push(Self {
+                            alias: alias.clone(),
+                            slug: slug.into(),
+                            idx,
+                        });
+                    }
+                }
+            }
+        }
+
+        Ok(aliases)
+    }
+}
+
+/// Book-level content of a front or back matter file, rendered before or after the songs,
+/// see `Settings::front_matter`.
+#[derive(Serialize, Clone, Debug)]
+#[serde(transparent)]
+pub struct Matter {
+    pub blocks: Box<[Block]>,
+}
+
+impl Matter {
+    pub fn images(&self) -> impl Iterator<Item = &Image> {
+        self.blocks
+            .iter()
+            .filter_map(Block::verse)
+            .flat_map(|v| v.inlines())
+            .filter_map(Inline::image)
+    }
+}
+
 #[derive(Debug)]
 pub struct Book {
     pub songs: Vec<Song>,
+    pub front_matter: Vec<Matter>,
+    pub back_matter: Vec<Matter>,
     pub songs_sorted: Vec<SongRef>,
+    /// Aliases of all songs, in song order.
+    pub aliases: Vec<SongAlias>,
+    /// Whether any of the songs is right-to-left.
+    pub has_rtl: bool,
     pub notation: Notation,
+    /// Notations of alt chords used in the songs, other than `notation`.
+    pub alt_notations: Vec<Notation>,
+    /// Order of `songs_sorted`.
+    pub sort: SortOrder,
 }
 
 impl Book {
     pub fn new(settings: &Settings) -> Book {
         Book {
             songs: vec![],
+            front_matter: vec![],
+            back_matter: vec![],
             songs_sorted: vec![],
-            notation: settings.notation,
+            aliases: vec![],
+            has_rtl: false,
+            notation: settings.notation.clone(),
+            alt_notations: vec![],
+            sort: settings.sort.clone(),
         }
     }
 
@@ -429,11 +1124,24 @@ impl Book {
     ///
     /// Steps taken:
     /// 1. Generation of the songs_sorted vec,
-    /// 2. Resolving of image elements (checking path, reading image dimensions).
+    /// 2. Collecting song aliases and checking them for conflicts,
+    /// 3. Checking for right-to-left songs and collecting alt chord notations,
+    /// 4. Resolving of image elements (checking path, reading image dimensions),
+    ///    images that can't be read only fail the outputs which include them.
     pub fn postprocess(&mut self, output_dir: &Path, img_cache: &ImgCache) -> Result<()> {
         self.songs.shrink_to_fit();
+        Song::assign_ids(&mut self.songs);
         self.songs_sorted = self.songs.iter().enumerate().map(SongRef::new).collect();
-        sort_lexical_by(&mut self.songs_sorted, |songref| songref.title.as_ref());
+        self.sort
+            .sort_by(&mut self.songs_sorted, |songref| songref.title.as_ref());
+        self.aliases = SongAlias::build(&self.songs)?;
+        self.has_rtl = self.songs.iter().any(|song| song.dir == TextDir::Rtl);
+        self.alt_notations.clear();
+        for notation in self.songs.iter().flat_map(|song| song.alt_notations.iter()) {
+            if *notation != self.notation && !self.alt_notations.contains(notation) {
+                self.alt_notations.push(notation.clone());
+            }
+        }
 
         for image in self.iter_images_mut() {
             image.resolve(output_dir, img_cache)?;
@@ -442,25 +1150,183 @@ impl Book {
         Ok(())
     }
 
+    /// Node counts and approximate heap usage of the book's AST, see `AstStats`.
+    pub fn ast_stats(&self) -> AstStats {
+        let mut stats = AstStats {
+            songs: self.songs.len(),
+            ..Default::default()
+        };
+
+        stats.add_vec(&self.songs);
+        self.songs.iter().for_each(|song| stats.add_song(song));
+        for matter in self.matter() {
+            stats.add_slice(&matter.blocks);
+            matter
+                .blocks
+                .iter()
+                .for_each(|block| stats.add_block(block));
+        }
+        stats.add_vec(&self.songs_sorted);
+        for songref in self.songs_sorted.iter() {
+            stats.add_str(&songref.title);
+            stats.add_str(&songref.first_line);
+        }
+        stats.add_vec(&self.aliases);
+        for alias in self.aliases.iter() {
+            stats.add_str(&alias.alias);
+            stats.add_str(&alias.slug);
+        }
+
+        stats
+    }
+
+    /// Matter before the songs followed by matter after the songs.
+    pub fn matter(&self) -> impl Iterator<Item = &Matter> {
+        self.front_matter.iter().chain(self.back_matter.iter())
+    }
+
     pub fn iter_images(&self) -> impl Iterator<Item = &Image> {
+        let matter = self.matter().flat_map(Matter::images);
+        self.songs.iter().flat_map(Song::images).chain(matter)
+    }
+
+    /// Links of all songs along with the song they're in.
+    pub fn iter_links(&self) -> impl Iterator<Item = (&Song, &Link)> {
         self.songs
             .iter()
-            .flat_map(|s| s.blocks.iter())
-            .filter_map(Block::verse)
-            .flat_map(|v| v.inlines())
-            .filter_map(Inline::image)
+            .flat_map(|s| s.links().into_iter().map(move |link| (s, link)))
     }
 
     pub fn iter_images_mut(&mut self) -> impl Iterator<Item = &mut Image> {
+        let matter = self
+            .front_matter
+            .iter_mut()
+            .chain(self.back_matter.iter_mut())
+            .flat_map(|m| m.blocks.iter_mut());
         self.songs
             .iter_mut()
             .flat_map(|s| s.blocks.iter_mut())
+            .chain(matter)
             .filter_map(Block::verse_mut)
             .flat_map(|v| v.inlines_mut())
             .filter_map(Inline::image_mut)
     }
 }
 
+/// Node counts of a book's AST and an estimate of the heap memory it takes, see `Book::ast_stats()`.
+///
+/// The estimate counts the contents of strings, boxed slices and vectors owned by the AST,
+/// without allocator overhead, values computed for templates such as image dimensions aren't counted.
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct AstStats {
+    pub songs: usize,
+    pub blocks: usize,
+    /// Inlines including nested ones, eg. in chords and emphasis.
+    pub inlines: usize,
+    /// Approximate heap usage in bytes.
+    pub heap_bytes: usize,
+}
+
+impl AstStats {
+    fn add_str(&mut self, s: &str) {
+        self.heap_bytes += s.len();
+    }
+
+    fn add_slice<T>(&mut self, slice: &[T]) {
+        self.heap_bytes += mem::size_of_val(slice);
+    }
+
+    fn add_vec<T>(&mut self, vec: &Vec<T>) {
+        self.heap_bytes += vec.capacity() * mem::size_of::<T>();
+    }
+
+    fn add_song(&mut self, song: &Song) {
+        self.add_str(&song.title);
+        self.add_inlines(&song.title_inlines);
+        self.add_slice(&song.subtitles);
+        song.subtitles.iter().for_each(|s| self.add_str(s));
+        self.add_vec(&song.blocks);
+        song.blocks.iter().for_each(|block| self.add_block(block));
+        let directives = [&song.tempo, &song.time, &song.key, &song.source];
+        directives
+            .iter()
+            .copied()
+            .flatten()
+            .for_each(|s| self.add_str(s));
+        self.add_slice(&song.aliases);
+        song.aliases.iter().for_each(|s| self.add_str(s));
+        self.add_slice(&song.alt_notations);
+        self.heap_bytes += song.location.file.as_os_str().len();
+    }
+
+    fn add_block(&mut self, block: &Block) {
+        self.blocks += 1;
+        match block {
+            Block::Verse(verse) => {
+                if let VerseLabel::Custom(label) = &verse.label {
+                    self.add_str(label);
+                }
+                self.add_vec(&verse.paragraphs);
+                verse.paragraphs.iter().for_each(|p| self.add_inlines(p));
+            }
+            Block::BulletList(list) => {
+                self.add_slice(&list.items);
+                list.items.iter().for_each(|item| self.add_str(item));
+            }
+            Block::HtmlBlock(inlines) => self.add_inlines(&inlines.inlines),
+            Block::Pre { text } | Block::Heading { text, .. } => self.add_str(text),
+            Block::HorizontalLine => {}
+        }
+    }
+
+    fn add_inlines(&mut self, inlines: &[Inline]) {
+        self.add_slice(inlines);
+        for inline in inlines {
+            self.inlines += 1;
+            match inline {
+                Inline::Text { text, .. } => self.add_str(text),
+                Inline::Chord(chord) => {
+                    self.add_str(&chord.chord);
+                    if let Some(alt_chord) = chord.alt_chord.as_ref() {
+                        self.add_str(alt_chord);
+                    }
+                    self.add_inlines(&chord.inlines);
+                }
+                Inline::Emph(Inlines { inlines }) | Inline::Strong(Inlines { inlines }) => {
+                    self.add_inlines(inlines)
+                }
+                Inline::Link(link) => {
+                    self.add_str(&link.url);
+                    self.add_str(&link.title);
+                    self.add_str(&link.text);
+                }
+                Inline::Image(image) => {
+                    self.add_str(&image.path);
+                    self.add_str(&image.title);
+                    self.add_str(&image.class);
+                    self.add_slice(&image.classes);
+                    image.classes.iter().for_each(|c| self.add_str(c));
+                }
+                Inline::ChorusRef(chorus_ref) => self.add_str(&chorus_ref.prefix_space),
+                Inline::ChorusRefRange(range) => self.add_str(&range.prefix_space),
+                Inline::HtmlTag(tag) => {
+                    self.add_str(&tag.name);
+                    for (name, value) in tag.attrs.iter() {
+                        self.heap_bytes += 2 * mem::size_of::<BStr>();
+                        self.add_str(name);
+                        self.add_str(value);
+                    }
+                }
+                Inline::Directive(directive) => {
+                    self.add_str(&directive.value);
+                    self.add_str(&directive.prefix_space);
+                }
+                Inline::Break | Inline::Transpose(..) => {}
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 pub trait AssertJsonEq {
     fn assert_json_eq(&self, value: serde_json::Value);
@@ -0,0 +1,393 @@
+//! Structural checks of a parsed book before printing, see `bard util audit`.
+//!
+//! Each check walks the AST of the songs and reports findings, nothing is rendered or written.
+
+use std::collections::HashMap;
+
+use strum::Display;
+
+use super::{Block, Book, Inline, Inlines, Song, SongLocation, VerseLabel};
+use crate::diag::Severity;
+use crate::music;
+
+/// The check a finding comes from, findings are reported grouped by checks in this order.
+#[derive(Display, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
+pub enum AuditCheck {
+    #[strum(serialize = "Chorus references")]
+    ChorusRefs,
+    #[strum(serialize = "Images")]
+    Images,
+    #[strum(serialize = "Chords")]
+    Chords,
+    #[strum(serialize = "Duplicate titles")]
+    DuplicateTitles,
+    #[strum(serialize = "Empty verses")]
+    EmptyVerses,
+}
+
+/// A problem found by `audit()` in a song.
+#[derive(Debug)]
+pub struct Finding {
+    pub check: AuditCheck,
+    pub severity: Severity,
+    pub song: String,
+    pub location: SongLocation,
+    pub message: String,
+}
+
+impl Finding {
+    fn new(check: AuditCheck, severity: Severity, song: &Song, message: String) -> Self {
+        Self {
+            check,
+            severity,
+            song: song.title.to_string(),
+            location: song.location.clone(),
+            message,
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+/// Run all the checks over the songs of the `book`, the findings are sorted by check,
+/// then by song order.
+///
+/// Errors are problems that break the printed book, such as missing images or chorus references
+/// to nothing, warnings are things that are likely mistakes, such as unrecognized chords.
+pub fn audit(book: &Book) -> Vec<Finding> {
+    let mut findings = vec![];
+    for song in book.songs.iter() {
+        check_chorus_refs(song, &mut findings);
+        check_images(song, &mut findings);
+        check_chords(song, &mut findings);
+        check_empty_verses(song, &mut findings);
+    }
+    check_duplicate_titles(&book.songs, &mut findings);
+
+    // The sort is stable, songs stay in order within each check
+    findings.sort_by_key(|f| f.check);
+    findings
+}
+
+/// Collect chorus references in `inlines`, including ones nested in chords and emphasis.
+fn collect_chorus_refs(inlines: &[Inline], nums: &mut Vec<Option<u32>>) {
+    for inline in inlines {
+        match inline {
+            Inline::ChorusRef(chorus_ref) => nums.push(chorus_ref.num),
+            Inline::ChorusRefRange(range) => nums.extend((range.start..=range.end).map(Some)),
+            Inline::Chord(super::Chord { inlines, .. })
+            | Inline::Emph(Inlines { inlines })
+            | Inline::Strong(Inlines { inlines }) => collect_chorus_refs(inlines, nums),
+            _ => {}
+        }
+    }
+}
+
+/// Chorus references (`!>`, `!>>`, ..., `!>1-3`) need the referenced choruses to exist in the song.
+fn check_chorus_refs(song: &Song, findings: &mut Vec<Finding>) {
+    let mut refs = vec![];
+    for verse in song.blocks.iter().filter_map(Block::verse) {
+        verse
+            .paragraphs
+            .iter()
+            .for_each(|para| collect_chorus_refs(para, &mut refs));
+    }
+
+    let choruses: Vec<_> = song
+        .blocks
+        .iter()
+        .filter_map(|block| match block.verse()?.label {
+            VerseLabel::Chorus(num) => Some(num),
+            _ => None,
+        })
+        .collect();
+
+    let mut reported = vec![];
+    for num in refs {
+        // Chorus numbers are removed in songs with a single chorus, a reference to any chorus is fine then,
+        // ranges keep their numbers, these are checked against the count of unnumbered choruses
+        let unnumbered = choruses.iter().all(Option::is_none);
+        let found = match num {
+            Some(n) if unnumbered => n as usize <= choruses.len(),
+            Some(_) => choruses.contains(&num),
+            None => !choruses.is_empty(),
+        };
+        if found || reported.contains(&num) {
+            continue;
+        }
+
+        reported.push(num);
+        let message = match num {
+            Some(num) => format!("Reference to chorus {}, which the song doesn't have", num),
+            None => "Reference to a chorus, but the song has no chorus".to_string(),
+        };
+        findings.push(Finding::new(
+            AuditCheck::ChorusRefs,
+            Severity::Error,
+            song,
+            message,
+        ));
+    }
+}
+
+/// Images need to be readable, see `Image::check()`.
+fn check_images(song: &Song, findings: &mut Vec<Finding>) {
+    for image in song.images() {
+        if let Err(err) = image.check() {
+            findings.push(Finding::new(
+                AuditCheck::Images,
+                Severity::Error,
+                song,
+                format!("{:#}", err),
+            ));
+        }
+    }
+}
+
+/// Chords should be recognized in the song's notation, otherwise they can't be transposed.
+/// Chords which aren't meant to be chords, eg. `N.C.`, are reported too, hence only a warning.
+fn check_chords(song: &Song, findings: &mut Vec<Finding>) {
+    let mut reported = vec![];
+    for chord in song.chords() {
+        let unrecognized = match music::unrecognized_chord(&chord.chord, &song.notation) {
+            Some(unrecognized) => unrecognized.trim_end(),
+            None => continue,
+        };
+        if reported.contains(&unrecognized) {
+            continue;
+        }
+
+        reported.push(unrecognized);
+        findings.push(Finding::new(
+            AuditCheck::Chords,
+            Severity::Warning,
+            song,
+            format!(
+                "Unrecognized chord '{}' in {} notation",
+                unrecognized, song.notation
+            ),
+        ));
+    }
+}
+
+/// Verses with a custom label but no lyrics are probably unfinished.
+fn check_empty_verses(song: &Song, findings: &mut Vec<Finding>) {
+    for verse in song.blocks.iter().filter_map(Block::verse) {
+        if let VerseLabel::Custom(label) = &verse.label {
+            if verse.is_empty() {
+                findings.push(Finding::new(
+                    AuditCheck::EmptyVerses,
+                    Severity::Warning,
+                    song,
+                    format!("Verse labeled '{}' has no lyrics", label),
+                ));
+            }
+        }
+    }
+}
+
+/// Titles should be unique, compared case-insensitively, each song with a title
+/// of a previous song is reported.
+fn check_duplicate_titles(songs: &[Song], findings: &mut Vec<Finding>) {
+    let mut titles: HashMap<String, &Song> = HashMap::new();
+    for song in songs {
+        let key = song.title.trim().to_lowercase();
+        match titles.get(&key) {
+            Some(first) => findings.push(Finding::new(
+                AuditCheck::DuplicateTitles,
+                Severity::Warning,
+                song,
+                format!(
+                    "The title is also used by the song at {}:{}",
+                    first.location.file.display(),
+                    first.location.line
+                ),
+            )),
+            None => {
+                titles.insert(key, song);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+    use crate::music::Notation;
+    use crate::parser::{Diagnostic, Parser, ParserConfig};
+    use crate::prelude::*;
+    use crate::util::{ImgCache, SortOrder, TempPath};
+
+    fn parse(input: &str) -> Vec<Song> {
+        let config = ParserConfig::default();
+        let diag_sink = |_: Diagnostic| {};
+        Parser::new(input, Path::new("songs.md"), config, diag_sink)
+            .parse()
+            .unwrap()
+    }
+
+    fn messages(findings: &[Finding]) -> Vec<(&str, bool, &str)> {
+        findings
+            .iter()
+            .map(|f| (f.song.as_str(), f.is_error(), f.message.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn chorus_refs() {
+        let songs = parse(
+            "# Fine\n\n> Chorus.\n\n1. Verse !>\n\n# No Chorus\n\n1. Verse !>\n!>\n\n# Numbered\n\n> One.\n\n>> Two.\n\n1. `C`Verse !>> !>>>\n",
+        );
+        let mut findings = vec![];
+        songs
+            .iter()
+            .for_each(|song| check_chorus_refs(song, &mut findings));
+        assert_eq!(
+            messages(&findings),
+            [
+                (
+                    "No Chorus",
+                    true,
+                    "Reference to a chorus, but the song has no chorus"
+                ),
+                (
+                    "Numbered",
+                    true,
+                    "Reference to chorus 3, which the song doesn't have"
+                ),
+            ]
+        );
+        assert_eq!(findings[1].location.line, 12);
+
+        let songs = parse(
+            "# Range\n\n> One.\n\n>> Two.\n\n1. Verse !>1-2 !>2-4\n\n# Single\n\n> Chorus.\n\n1. Verse !>1-2\n",
+        );
+        let mut findings = vec![];
+        songs
+            .iter()
+            .for_each(|song| check_chorus_refs(song, &mut findings));
+        assert_eq!(
+            messages(&findings),
+            [
+                (
+                    "Range",
+                    true,
+                    "Reference to chorus 3, which the song doesn't have"
+                ),
+                (
+                    "Range",
+                    true,
+                    "Reference to chorus 4, which the song doesn't have"
+                ),
+                (
+                    "Single",
+                    true,
+                    "Reference to chorus 2, which the song doesn't have"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn images() {
+        let songs = parse("# Song\n\n1. ![](present.png) ![](missing.png)\n");
+        let dir = TempPath::make_temp_dir(env::temp_dir().join("bard-audit-images"), true).unwrap();
+        let dir = dir.as_ref();
+        image::RgbImage::new(4, 4)
+            .save(dir.join("present.png"))
+            .unwrap();
+
+        let mut book = Book {
+            songs,
+            front_matter: vec![],
+            back_matter: vec![],
+            songs_sorted: vec![],
+            aliases: vec![],
+            has_rtl: false,
+            notation: Notation::default(),
+            alt_notations: vec![],
+            sort: SortOrder::default(),
+        };
+        book.postprocess(dir, &ImgCache::new()).unwrap();
+
+        let mut findings = vec![];
+        check_images(&book.songs[0], &mut findings);
+        assert_eq!(findings.len(), 1);
+        assert!(findings[0].message.contains("missing.png"));
+        assert!(findings[0].is_error());
+    }
+
+    #[test]
+    fn chords() {
+        let songs = parse("# Song\n\n1. `Am`Lyrics `X`more `N.C.`lyrics `X`again `C/G`end\n");
+        let mut findings = vec![];
+        check_chords(&songs[0], &mut findings);
+        assert_eq!(
+            messages(&findings),
+            [
+                ("Song", false, "Unrecognized chord 'X' in english notation"),
+                (
+                    "Song",
+                    false,
+                    "Unrecognized chord 'N.C.' in english notation"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_verses() {
+        let songs = parse("# Song\n\n1. Lyrics.\n\n### Bridge\n\n### Outro\nMore lyrics.\n");
+        let mut findings = vec![];
+        check_empty_verses(&songs[0], &mut findings);
+        assert_eq!(
+            messages(&findings),
+            [("Song", false, "Verse labeled 'Bridge' has no lyrics")]
+        );
+    }
+
+    #[test]
+    fn duplicate_titles() {
+        let songs = parse("# Song\n\n1. A.\n\n# Other\n\n1. B.\n\n# SONG \n\n1. C.\n");
+        let mut findings = vec![];
+        check_duplicate_titles(&songs, &mut findings);
+        assert_eq!(
+            messages(&findings),
+            [(
+                "SONG",
+                false,
+                "The title is also used by the song at songs.md:1"
+            )]
+        );
+    }
+
+    #[test]
+    fn audit_sorted() {
+        let songs = parse("# Song\n\n1. `X`Lyrics !>\n\n# Song\n\n### Bridge\n");
+        let book = Book {
+            songs,
+            front_matter: vec![],
+            back_matter: vec![],
+            songs_sorted: vec![],
+            aliases: vec![],
+            has_rtl: false,
+            notation: Notation::default(),
+            alt_notations: vec![],
+            sort: SortOrder::default(),
+        };
+        let checks: Vec<_> = audit(&book).iter().map(|f| f.check).collect();
+        assert_eq!(
+            checks,
+            [
+                AuditCheck::ChorusRefs,
+                AuditCheck::Chords,
+                AuditCheck::DuplicateTitles,
+                AuditCheck::EmptyVerses
+            ]
+        );
+    }
+}
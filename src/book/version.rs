@@ -3,6 +3,7 @@ use std::fmt;
 use semver::Version;
 
 use crate::app::App;
+use crate::diag::{BuildDiagnostic, Phase};
 use crate::prelude::*;
 
 pub struct AstVersion {
@@ -29,6 +30,38 @@ pub static AST_VERSION_LOG: &[AstVersion] = &[
     AstVersion::new(1, 0, "Initial version"),
     AstVersion::new(1, 1, "New style, added support for HTML snippets, TTF font files, and baseline chords"),
     AstVersion::new(1, 2, "Added scaling of images in HTML via the dpi setting, width and height are now provided in i-image elements"),
+    AstVersion::new(1, 3, "Added book style hints in the styles object"),
+    AstVersion::new(1, 4, "Added the volume object for PDF outputs split into volumes"),
+    AstVersion::new(1, 5, "Added tempo and time directives: the tempo and time song fields and the i-directive element"),
+    AstVersion::new(1, 6, "Added the source song field with the include_sources setting"),
+    AstVersion::new(1, 7, "Added song durations: the duration song field, the duration directive, and total_duration"),
+    AstVersion::new(1, 8, "Added verse and chorus label formats in the labels object and the format_label helper"),
+    AstVersion::new(1, 9, "Added the classes image field, the contains helper also works with arrays"),
+    AstVersion::new(1, 10, "Added the handout flag for single songs rendered with bard make --song"),
+    AstVersion::new(1, 11, "Added the first_verse and last_verse verse fields and the block_index of blocks"),
+    AstVersion::new(1, 12, "Added the key directive and the key song field"),
+    AstVersion::new(1, 13, "Added the alias directive, the aliases song field and the aliases book-level map"),
+    AstVersion::new(1, 14, "Added the dir directive and song field, and the has_rtl flag"),
+    AstVersion::new(1, 15, "Added title_inlines to songs, chords are left out of song titles"),
+    AstVersion::new(1, 16, "Added front_matter and back_matter, and the b-heading block"),
+    AstVersion::new(1, 17, "Added notation_legend"),
+    AstVersion::new(1, 18, "Added the continued verse field"),
+    AstVersion::new(1, 19, "Added the images output format and the slide render context field"),
+    AstVersion::new(1, 20, "Consecutive text inlines are merged"),
+    AstVersion::new(1, 21, "Added the chord_pos song field"),
+    AstVersion::new(1, 22, "Added the fonts_available render context field"),
+    AstVersion::new(1, 23, "Added the root_chromatic and suffix chord fields"),
+    AstVersion::new(1, 24, "Added the book_meta render context field"),
+    AstVersion::new(1, 25, "Added the HTML tag text field and the html_content output field"),
+    AstVersion::new(1, 26, "Added the fonts render context field"),
+    AstVersion::new(1, 27, "Added the i-chorus-ref-range inline"),
+    AstVersion::new(1, 28, "Added the chords_used and alt_chords_used song fields"),
+    AstVersion::new(1, 29, "Added the metadata song field"),
+    AstVersion::new(1, 30, "Added the id song and song reference field, the HTML template links to songs by their ids"),
+    AstVersion::new(1, 31, "Added the first_line song reference field and songs_by_first_line"),
+    AstVersion::new(1, 32, "Added the hovorka render context field with the song text prepared for the Hovorka app"),
+    AstVersion::new(1, 33, "Extensions are only recognized when delimited by whitespace and can be escaped, other ones are left in text inlines, a removed transposition takes one adjacent whitespace char wherever it is on the line"),
+    AstVersion::new(1, 34, "Added the needs_toc helper"),
 ];
 
 pub fn current() -> &'static Version {
@@ -52,19 +85,20 @@ pub fn compat_check(app: &App, tpl_path: &Path, tpl_version: &Version) {
     let current = current();
     if current < tpl_version {
         // Template's AST is newer than this bard's AST
-        app.warning(format!(
-            "The version of template {:?} is {}, which is newer than what this bard uses ({}).
+        let msg = format!(
+            "The version of the template is {}, which is newer than what this bard uses ({}).
 Maybe this project was created with a newer bard version.
 This may cause errors while rendering...",
-            tpl_path, tpl_version, current,
-        ));
+            tpl_version, current,
+        );
+        app.diag(BuildDiagnostic::warning(Phase::Render, msg).with_file(tpl_path));
     } else if current.major > tpl_version.major {
         // Template's AST major version is older than this bard's AST, incompatibly
-        app.warning(
-            format!("The version of template {:?} is {}, which is from an older generation than what this bard uses ({}).
+        let msg = format!("The version of the template is {}, which is from an older generation than what this bard uses ({}).
 This may cause errors while rendering. It may be needed to convert the template to the newer format.",
-            tpl_path, tpl_version, current,
-        ));
+            tpl_version, current,
+        );
+        app.diag(BuildDiagnostic::warning(Phase::Render, msg).with_file(tpl_path));
         log_changes(app, tpl_version);
     } else if current > tpl_version {
         // Template's AST version is older than this bard's AST, compatibly
@@ -8,13 +8,19 @@ use super::*;
 xml_write!(struct Chord {
     chord,
     alt_chord,
+    root_chromatic,
+    suffix,
     backticks,
     baseline,
     inlines,
+    line,
 } -> |w| {
+    let _ = line;
     w.tag("chord")
         .attr(chord)
         .attr_opt("alt-chord", alt_chord.unwrap())
+        .attr_opt("root-chromatic", &root_chromatic.unwrap().map(|r| r.to_string()))
+        .attr_opt("suffix", suffix.unwrap())
         .attr(backticks)
         .attr(baseline)
         .content()?
@@ -37,11 +43,16 @@ xml_write!(struct Image {
     path,
     title,
     class,
+    classes,
     width,
     height,
     full_path,
+    error,
 } -> |w| {
+    // The class attribute already contains all the classes
+    let _ = classes;
     let _ = full_path;
+    let _ = error;
     w.tag("image",)
         .attr(path)
         .attr(title)
@@ -59,11 +70,34 @@ xml_write!(struct ChorusRef {
         .attr(prefix_space)
 });
 
+xml_write!(struct ChorusRefRange {
+    start,
+    end,
+    prefix_space,
+} -> |w| {
+    w.tag("chorus-ref-range")
+        .attr(start)
+        .attr(end)
+        .attr(prefix_space)
+});
+
+xml_write!(struct Directive {
+    kind,
+    value,
+    prefix_space,
+} -> |w| {
+    w.tag("directive")
+        .attr(kind)
+        .attr(value)
+        .attr(prefix_space)
+});
+
 xml_write!(struct HtmlTag {
     name,
     attrs,
+    text,
 } -> |w| {
-    let tag = w.tag("tag").attr(name);
+    let tag = w.tag("tag").attr(name).attr_opt("text", text.unwrap());
     let attrs = attrs.unwrap();
     if attrs.is_empty() {
         return tag.finish();
@@ -73,7 +107,7 @@ xml_write!(struct HtmlTag {
 });
 
 xml_write!(enum Inline |w| {
-    Text { text } => { w.write_text(text)?; },
+    Text { text, .. } => { w.write_text(text)?; },
     Chord(c) => { w.write_value(c)?; },
     Break => { w.tag("br").finish()?; },
     Emph(i) => { w.tag("emph").content()?.many(i)?.finish()?; },
@@ -81,7 +115,9 @@ xml_write!(enum Inline |w| {
     Link(l) => { w.write_value(l)?; },
     Image(i) => { w.write_value(i)?; },
     ChorusRef(cr) => { w.write_value(cr)?; },
+    ChorusRefRange(cr) => { w.write_value(cr)?; },
     HtmlTag(tag) => { w.write_value(tag)?; },
+    Directive(d) => { w.write_value(d)?; },
 
     Transpose(..) => { unreachable!() },
 });
@@ -89,7 +125,13 @@ xml_write!(enum Inline |w| {
 xml_write!(struct Verse {
     label,
     paragraphs,
+    first_verse,
+    last_verse,
+    continued,
 } -> |w| {
+    // The order of verses is apparent from the XML document itself
+    let _ = first_verse;
+    let _ = last_verse;
     use VerseLabel::*;
     let label = label.unwrap();
     let label_type = match label {
@@ -108,6 +150,7 @@ xml_write!(struct Verse {
     w.tag("verse")
         .attr(("label-type", label_type))
         .attr_opt("label", &label)
+        .attr(continued)
         .content()?
         .many_tags("p", paragraphs)?
 });
@@ -122,27 +165,76 @@ xml_write!(enum Block |w| {
     HorizontalLine => { w.tag("hr").finish()?; },
     Pre { text } => { w.tag("pre").content()?.text(text)?.finish()?; },
     HtmlBlock(i) => { w.tag("html-block").content()?.many(i)?.finish()?; },
+    Heading { level, text } => { w.tag("heading").attr(("level", level)).content()?.text(text)?.finish()?; },
+});
+
+xml_write!(struct Matter { blocks, } -> |w| {
+    w.tag("matter").content()?.many(blocks)?
 });
 
 xml_write!(struct Song {
     title,
+    id,
+    title_inlines,
     subtitles,
     blocks,
     notation,
+    tempo,
+    time,
+    duration,
+    key,
+    aliases,
+    metadata,
+    dir,
+    chord_pos,
+    source,
+    chords_used,
+    alt_chords_used,
+    alt_notations,
+    location,
 } -> |w| {
+    let _ = (alt_notations, location);
     w.tag("song")
         .attr(title)
+        .attr(id)
         .attr(notation)
+        .attr_opt("tempo", tempo.unwrap())
+        .attr_opt("time", time.unwrap())
+        .attr_opt("duration", &duration.unwrap().map(|d| d.to_string()))
+        .attr_opt("key", key.unwrap())
+        .attr(dir)
+        .attr(("chord-pos", chord_pos.unwrap()))
         .content()?
+        .field(title_inlines)?
         .many_tags("subtitle", subtitles)?
+        .many_tags("alias", aliases)?
+        .field(metadata)?
+        .many_tags("chord-used", chords_used)?
+        .many_tags("alt-chord-used", alt_chords_used)?
         .many(blocks)?
+        .field_opt(source)?
+});
+
+xml_write!(struct SongAlias {
+    alias,
+    slug,
+    idx,
+} -> |w| {
+    w.tag("song-alias")
+        .attr(alias)
+        .attr(slug)
+        .attr(idx)
 });
 
 xml_write!(struct SongRef {
     title,
+    id,
+    first_line,
     idx,
 } -> |w| {
     w.tag("song-ref")
         .attr(title)
+        .attr(id)
+        .attr(("first-line", first_line.unwrap()))
         .attr(idx)
 });
@@ -1,9 +1,21 @@
+use std::borrow::Cow;
 use std::fs;
 use std::path::MAIN_SEPARATOR;
 
+use crate::app::InitOpts;
 use crate::prelude::*;
+use crate::project::Format;
 use crate::util::PathBufExt as _;
 
+const PROJECT_FILE: &str = "bard.toml";
+const EXAMPLE_SONG: &str = "songs/yippie.md";
+
+/// Parts of the default `bard.toml` replaced according to `InitOpts`, see `project_file()`.
+const DEFAULT_SONGS: &str = "songs = [\n    \"yippie.md\",\n]\n";
+const DEFAULT_OUTPUTS: &str =
+    "[[output]]\nfile = \"songbook.pdf\"\n\n[[output]]\nfile = \"songbook.html\"\n";
+const DEFAULT_TITLE: &str = "title = \"My Songbook\"\n";
+
 /// A filesystem node, either a file (with content), or a directory.
 #[derive(Debug)]
 enum Node {
@@ -21,20 +33,27 @@ impl Node {
         Self::Dir { path }
     }
 
-    fn path_buf(&self) -> PathBuf {
+    fn path(&self) -> &'static str {
         match self {
             Self::File { path, .. } => path,
             Self::Dir { path } => path,
         }
-        .replace('/', &String::from(MAIN_SEPARATOR))
-        .into() // MAIN_SEPARATOR_STR isn't stable :-|
+    }
+
+    fn path_buf(&self) -> PathBuf {
+        self.path()
+            .replace('/', &String::from(MAIN_SEPARATOR))
+            .into() // MAIN_SEPARATOR_STR isn't stable :-|
     }
 
     fn resolve(&self, base: &Path) -> NodeResolved {
         let mut path = self.path_buf();
         path.resolve(base);
         match self {
-            Self::File { content, .. } => NodeResolved::File { path, content },
+            Self::File { content, .. } => NodeResolved::File {
+                path,
+                content: Cow::Borrowed(content),
+            },
             Self::Dir { .. } => NodeResolved::Dir { path },
         }
     }
@@ -43,7 +62,7 @@ impl Node {
 enum NodeResolved {
     File {
         path: PathBuf,
-        content: &'static [u8],
+        content: Cow<'static, [u8]>,
     },
     Dir {
         path: PathBuf,
@@ -89,15 +108,107 @@ macro_rules! node_file {
 #[derive(Debug)]
 pub struct DefaultProject {
     nodes: &'static [Node],
+    /// The default fonts, left out with `bard init --no-fonts`.
+    fonts: &'static [Node],
 }
 
 impl DefaultProject {
-    pub fn resolve(&self, project_dir: &Path) -> DefaultProjectResolved {
-        let nodes = self.nodes.iter().map(|n| n.resolve(project_dir)).collect();
+    pub fn resolve(&self, project_dir: &Path, fonts: bool) -> DefaultProjectResolved {
+        let opts = InitOpts {
+            no_fonts: !fonts,
+            ..Default::default()
+        };
+        self.resolve_with(project_dir, &opts)
+    }
+
+    /// Like `resolve()`, with the project customized according to `opts` of `bard init`.
+    pub fn resolve_with(&self, project_dir: &Path, opts: &InitOpts) -> DefaultProjectResolved {
+        let fonts = if opts.no_fonts { &[] } else { self.fonts };
+        let mut nodes = vec![];
+        for node in self.nodes.iter().chain(fonts.iter()) {
+            match node {
+                Node::File { path, content } if *path == PROJECT_FILE => {
+                    let mut node = node.resolve(project_dir);
+                    if let NodeResolved::File { content: c, .. } = &mut node {
+                        *c = project_file(content, opts);
+                    }
+                    nodes.push(node);
+                }
+                Node::File { path, .. } if *path == EXAMPLE_SONG && opts.no_example_song => {
+                    nodes.push(Node::dir("songs").resolve(project_dir));
+                }
+                _ => nodes.push(node.resolve(project_dir)),
+            }
+        }
         DefaultProjectResolved { nodes }
     }
 }
 
+/// The default `bard.toml` `content` with the title, songs, and outputs replaced according to `opts`.
+fn project_file(content: &'static [u8], opts: &InitOpts) -> Cow<'static, [u8]> {
+    let customized = opts.title.is_some()
+        || opts.name.is_some()
+        || !opts.format.is_empty()
+        || opts.no_example_song;
+    if !customized {
+        return Cow::Borrowed(content);
+    }
+
+    let mut toml = String::from_utf8_lossy(content).into_owned();
+    let mut replace = |from: &str, to: &str| {
+        assert!(
+            toml.contains(from),
+            "Default bard.toml doesn't contain {:?}",
+            from
+        );
+        toml = toml.replacen(from, to, 1);
+    };
+
+    if opts.no_example_song {
+        replace(DEFAULT_SONGS, "songs = \"*.md\"\n");
+    }
+    if opts.name.is_some() || !opts.format.is_empty() {
+        let name = opts.name.as_deref().unwrap_or("songbook");
+        let formats = match &opts.format[..] {
+            [] => &[Format::Pdf, Format::Html][..],
+            formats => formats,
+        };
+        let outputs: Vec<_> = formats
+            .iter()
+            .map(|&format| output_entry(name, format))
+            .collect();
+        replace(DEFAULT_OUTPUTS, &outputs.join("\n"));
+    }
+    if let Some(title) = opts.title.as_deref() {
+        let title = format!("title = {}\n", toml::Value::from(title));
+        replace(DEFAULT_TITLE, &title);
+    }
+
+    Cow::Owned(toml.into_bytes())
+}
+
+/// An `[[output]]` table of the `format` for `bard.toml`, the `format` is only set
+/// if it can't be detected from the file extension.
+fn output_entry(name: &str, format: Format) -> String {
+    let (file, explicit) = match format {
+        Format::Pdf => (format!("{}.pdf", name), false),
+        Format::Html => (format!("{}.html", name), false),
+        Format::Hovorka => (format!("{}.xml", name), true),
+        Format::Json => (format!("{}.json", name), false),
+        Format::Jsonl => (format!("{}.jsonl", name), false),
+        Format::Xml => (format!("{}.xml", name), false),
+        Format::Txt => (format!("{}.txt", name), false),
+        Format::ChordPro => (format!("{}.cho", name), false),
+        Format::Images => (format!("{}-slides", name), true),
+    };
+
+    let mut entry = format!("[[output]]\nfile = {}\n", toml::Value::from(file));
+    if explicit {
+        entry.push_str(&format!("format = \"{}\"\n", format));
+    }
+    entry
+}
+
 pub const DEFAULT_PROJECT: DefaultProject = DefaultProject {
     nodes: &[
         // Project file:
@@ -106,7 +217,8 @@ pub const DEFAULT_PROJECT: DefaultProject = DefaultProject {
         node_file!("songs/yippie.md"),
         // Output dir:
         Node::dir("output"),
-        // Fonts:
+    ],
+    fonts: &[
         Node::dir("output/fonts"),
         node_file!("output/fonts/BardSerif-Regular.ttf"),
         node_file!("output/fonts/BardSerif-BoldItalic.ttf"),
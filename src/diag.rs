@@ -0,0 +1,144 @@
+//! Build diagnostics: warnings and errors from all the phases of a build,
+//! collected centrally by `App`, see `App::diag()`.
+//!
+//! Parser diagnostics are converted from `parser::Diagnostic`,
+//! which is still what the parser reports through its `DiagSink`.
+
+use std::fmt;
+
+use serde::Serialize;
+use strum::Display;
+
+use crate::parser;
+use crate::prelude::*;
+
+/// The build phase a diagnostic comes from.
+#[derive(Serialize, Display, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum Phase {
+    /// Parsing song files.
+    Parse,
+    /// Rendering templates.
+    Render,
+    /// Running TeX.
+    Tex,
+    /// Running output scripts, hooks, and `on_update` commands.
+    Script,
+}
+
+#[derive(Serialize, Display, PartialEq, Eq, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// A warning or error from any phase of the build.
+#[derive(Serialize, PartialEq, Eq, Clone, Debug)]
+pub struct BuildDiagnostic {
+    pub phase: Phase,
+    pub severity: Severity,
+    /// The file the diagnostic is about, eg. a song file, a template, or a script.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<PathBuf>,
+    /// 1-indexed line in `file`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    pub message: String,
+    /// The original diagnostic of the parse phase.
+    #[serde(skip)]
+    pub parser: Option<parser::Diagnostic>,
+}
+
+impl BuildDiagnostic {
+    pub fn warning(phase: Phase, message: impl fmt::Display) -> Self {
+        Self::new(phase, Severity::Warning, message)
+    }
+
+    pub fn error(phase: Phase, message: impl fmt::Display) -> Self {
+        Self::new(phase, Severity::Error, message)
+    }
+
+    fn new(phase: Phase, severity: Severity, message: impl fmt::Display) -> Self {
+        Self {
+            phase,
+            severity,
+            file: None,
+            line: None,
+            message: message.to_string(),
+            parser: None,
+        }
+    }
+
+    pub fn with_file(mut self, file: impl Into<PathBuf>) -> Self {
+        self.file = Some(file.into());
+        self
+    }
+
+    pub fn with_line(mut self, line: usize) -> Self {
+        self.line = Some(line);
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl From<parser::Diagnostic> for BuildDiagnostic {
+    fn from(diag: parser::Diagnostic) -> Self {
+        let severity = if diag.is_error() {
+            Severity::Error
+        } else {
+            Severity::Warning
+        };
+
+        Self {
+            phase: Phase::Parse,
+            severity,
+            file: Some(diag.file.clone()),
+            line: Some(diag.line),
+            message: diag.kind.to_string(),
+            parser: Some(diag),
+        }
+    }
+}
+
+impl fmt::Display for BuildDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.file.as_ref(), self.line) {
+            (Some(file), Some(line)) => write!(f, "{}:{}: {}", file.display(), line, self.message),
+            (Some(file), None) => write!(f, "{}: {}", file.display(), self.message),
+            _ => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// Count the warnings among `diags` by phase, eg. `2 parse, 1 render`,
+/// warnings not reported as diagnostics out of the `total` are counted as `other`.
+/// `None` if none of the warnings are diagnostics.
+pub fn warnings_summary(diags: &[BuildDiagnostic], total: usize) -> Option<String> {
+    let mut counts: Vec<(Phase, usize)> = vec![];
+    for diag in diags.iter().filter(|d| !d.is_error()) {
+        match counts.iter_mut().find(|(phase, _)| *phase == diag.phase) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((diag.phase, 1)),
+        }
+    }
+    if counts.is_empty() {
+        return None;
+    }
+
+    counts.sort();
+    let diag_warnings: usize = counts.iter().map(|(_, count)| count).sum();
+    let mut parts: Vec<_> = counts
+        .iter()
+        .map(|(phase, count)| format!("{} {}", count, phase))
+        .collect();
+    if total > diag_warnings {
+        parts.push(format!("{} other", total - diag_warnings));
+    }
+    Some(parts.join(", "))
+}
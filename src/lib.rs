@@ -10,14 +10,16 @@
 
 use std::env;
 use std::ffi::OsString;
+use std::time::{Duration, Instant};
 
-use app::{App, InterruptFlag, MakeOpts, StdioOpts};
+use app::{verbosity, App, InitOpts, InterruptFlag, MakeOpts, ProjectOpts};
 use clap::{CommandFactory as _, Parser as _};
 use serde::Serialize;
 
 pub mod app;
 pub mod book;
 pub mod default_project;
+pub mod diag;
 pub mod music;
 pub mod parser;
 pub mod prelude;
@@ -25,14 +27,15 @@ pub mod project;
 pub mod render;
 #[cfg(feature = "tectonic")]
 pub mod tectonic_embed;
+pub mod time;
 pub mod util;
 pub mod util_cmd;
 pub mod watch;
 
 use crate::prelude::*;
-use crate::project::{Project, Settings};
+use crate::project::{BuildOrigin, BuildState, Project, Settings};
 use crate::util_cmd::UtilCmd;
-use crate::watch::Watch;
+use crate::watch::{Watch, WatchEvent};
 
 #[derive(Serialize, Clone, Debug)]
 pub struct ProgramMeta {
@@ -71,6 +74,9 @@ struct Cli {
     /// Print project template AST version in semver format
     #[arg(long, conflicts_with = "version")]
     pub version_ast: bool,
+
+    #[clap(flatten)]
+    project: ProjectOpts,
 }
 
 impl Cli {
@@ -94,7 +100,7 @@ enum Command {
     /// Initialize a new bard project skeleton in this directory
     Init {
         #[clap(flatten)]
-        opts: StdioOpts,
+        opts: InitOpts,
     },
     /// Build the current project"
     Make {
@@ -105,6 +111,10 @@ enum Command {
     Watch {
         #[clap(flatten)]
         opts: MakeOpts,
+        /// Check files for changes periodically instead of relying on notifications of the OS,
+        /// eg. on network filesystems. The interval is in seconds by default, eg. 5 or 1m
+        #[arg(long, value_name = "INTERVAL", num_args = 0..=1, default_missing_value = "2s", value_parser = parse_poll_interval)]
+        poll: Option<Duration>,
     },
     /// CLI utilities for postprocessing
     #[command(subcommand)]
@@ -120,9 +130,9 @@ impl Command {
         use Command::*;
 
         match self {
-            Init { .. } => bard_init(app),
+            Init { opts } => bard_init(app, &opts),
             Make { .. } => bard_make(app),
-            Watch { .. } => bard_watch(app),
+            Watch { poll, .. } => bard_watch(app, poll),
             Util(cmd) => cmd.run(app),
 
             #[cfg(feature = "tectonic")]
@@ -131,28 +141,47 @@ impl Command {
     }
 }
 
+fn parse_poll_interval(s: &str) -> Result<Duration, String> {
+    project::parse_interval(s)
+        .filter(|interval| !interval.is_zero())
+        .ok_or_else(|| format!("invalid interval '{}', expected eg. 2, 30s, or 1m", s))
+}
+
 fn get_cwd() -> Result<PathBuf> {
     env::current_dir().context("Could not read current directory")
 }
 
-pub fn bard_init_at<P: AsRef<Path>>(app: &App, path: P) -> Result<()> {
+pub fn bard_init_at<P: AsRef<Path>>(app: &App, path: P, opts: &InitOpts) -> Result<()> {
     let path = path.as_ref();
 
     app.status("Initialize", format!("new project at {:?}", path));
-    Project::init(path).context("Could not initialize a new project")?;
+    Project::init(path, opts).context("Could not initialize a new project")?;
     app.success("Done!");
     Ok(())
 }
 
-pub fn bard_init(app: &App) -> Result<()> {
+pub fn bard_init(app: &App, opts: &InitOpts) -> Result<()> {
     let cwd = get_cwd()?;
-    bard_init_at(app, cwd)
+    match app.project_opts().project_dir.as_deref() {
+        Some(dir) => bard_init_at(app, cwd.join(dir), opts),
+        None => bard_init_at(app, cwd, opts),
+    }
 }
 
 pub fn bard_make_at<P: AsRef<Path>>(app: &App, path: P) -> Result<Project> {
-    Project::new(app, path.as_ref())
+    let project = make_at(app, path.as_ref(), &mut BuildState::new(BuildOrigin::Make))?;
+    app.check_warnings()?;
+    Ok(project)
+}
+
+fn make_at(app: &App, path: &Path, build: &mut BuildState) -> Result<Project> {
+    let start = Instant::now();
+    Project::new(app, path)
         .and_then(|project| {
-            project.render(app)?;
+            project.render(app, build)?;
+            app.finished("build", start.elapsed());
+            app.debug_stats(&project.debug_stats(app));
+            project.report_skipped_files(app);
             Ok(project)
         })
         .context("Could not make project")
@@ -161,32 +190,124 @@ pub fn bard_make_at<P: AsRef<Path>>(app: &App, path: P) -> Result<Project> {
 pub fn bard_make(app: &App) -> Result<()> {
     let cwd = get_cwd()?;
 
-    bard_make_at(app, cwd)?;
-    app.success("Done!");
+    let project = bard_make_at(app, cwd)?;
+    project.check_make_opts(app);
+    // Warnings about the make options count too
+    app.check_warnings()?;
+
+    let warnings = app.warnings();
+    if warnings == 0 {
+        app.success("Done!");
+        return Ok(());
+    }
+
+    let mut summary = match warnings {
+        1 => "with 1 warning".to_string(),
+        n => format!("with {} warnings", n),
+    };
+    // Warnings by build phase, eg. (2 parse, 1 render)
+    if let Some(phases) = diag::warnings_summary(&app.diags(), warnings) {
+        summary = format!("{} ({})", summary, phases);
+    }
+    app.status_notice("Done!", summary);
     Ok(())
 }
 
+fn open_output(app: &App, project: &Project) -> Result<()> {
+    let (outputs, _) = project.selected_outputs(app.outputs())?;
+    let output = outputs
+        .first()
+        .ok_or_else(|| anyhow!("The project has no outputs"))?;
+    let file = if output.volumes.is_empty() {
+        output.file.clone()
+    } else {
+        output.volume_file(1)
+    };
+
+    app.indent(format!("Opening {:?} ...", file));
+    util::open(&file)
+}
+
 pub fn bard_watch_at<P: AsRef<Path>>(app: &App, path: P, mut watch: Watch) -> Result<()> {
-    loop {
-        let project = bard_make_at(app, &path)?;
+    let mut app = app.clone();
+    // Kept across rebuilds so that script_min_interval can be honored
+    let mut build = BuildState::new(BuildOrigin::Watch);
+    let path = path.as_ref();
+    app.reset_diags();
+    let mut project = make_at(&app, path, &mut build)?;
+    project.check_make_opts(&app);
 
+    loop {
         eprintln!();
-        app.status("Watching", "for changes in the project ...");
-        match watch.watch(&project, app.interrupt_flag())? {
-            Some(paths) if paths.len() == 1 => {
-                app.indent(format!("Change detected at {:?} ...", paths[0]))
+        match watch.poll_interval() {
+            Some(interval) => app.status(
+                "Watching",
+                format!(
+                    "for changes in the project, polling every {:?} ...",
+                    interval
+                ),
+            ),
+            None => app.status("Watching", "for changes in the project ..."),
+        }
+        if watch.has_key_listener() {
+            app.indent(watch::KEYS_HELP);
+        }
+
+        loop {
+            match watch.watch(&project, app.interrupt_flag())? {
+                WatchEvent::Change(paths) => {
+                    for path in paths.iter() {
+                        app.img_cache().invalidate(path);
+                    }
+                    if paths.len() == 1 {
+                        app.indent(format!("Change detected at {:?} ...", paths[0]));
+                    } else {
+                        app.indent("Change detected ...");
+                    }
+                    break;
+                }
+                WatchEvent::Rebuild => {
+                    app.img_cache().clear();
+                    if watch.missed_changes() {
+                        app.warning(
+                            "Project files changed, but no change notifications were received, the filesystem may not support them.\n\
+                            Hint: Use 'bard watch --poll' to check files for changes periodically.",
+                        );
+                    }
+                    app.indent("Rebuilding ...");
+                    break;
+                }
+                WatchEvent::Open => {
+                    if let Err(err) = open_output(&app, &project) {
+                        app.error(err);
+                    }
+                }
+                WatchEvent::ToggleVerbose => {
+                    if app.verbosity() >= verbosity::VERBOSE {
+                        app.set_verbosity(verbosity::NORMAL);
+                        app.indent("Verbose output disabled");
+                    } else {
+                        app.set_verbosity(verbosity::VERBOSE);
+                        app.indent("Verbose output enabled");
+                    }
+                }
+                WatchEvent::Quit => return Ok(()),
             }
-            Some(..) => app.indent("Change detected ..."),
-            None => break,
         }
-    }
 
-    Ok(())
+        // The diagnostics of the previous build are stale, they would pile up otherwise
+        app.reset_diags();
+        project = make_at(&app, path, &mut build)?;
+    }
 }
 
-pub fn bard_watch(app: &App) -> Result<()> {
+pub fn bard_watch(app: &App, poll: Option<Duration>) -> Result<()> {
     let cwd = get_cwd()?;
-    let watch = Watch::new()?;
+    let watch = match poll {
+        Some(interval) => Watch::with_poll(interval)?,
+        None => Watch::new()?,
+    };
+    let watch = watch.with_key_listener();
     bard_watch_at(app, cwd, watch)
 }
 
@@ -204,14 +325,15 @@ pub fn bard(args: &[OsString], interrupt: InterruptFlag) -> i32 {
     };
 
     let app = match &cmd {
-        Command::Init { opts } => App::new(&opts.clone().into(), interrupt),
+        Command::Init { opts } => App::new(&opts.stdio.clone().into(), interrupt),
         Command::Make { opts } => App::new(opts, interrupt),
-        Command::Watch { opts } => App::new(opts, interrupt),
+        Command::Watch { opts, .. } => App::new(opts, interrupt),
         Command::Util(_) => App::new(&Default::default(), interrupt),
 
         #[cfg(feature = "tectonic")]
         Command::Tectonic(_) => App::new_as_tectonic(interrupt),
     };
+    let app = app.with_project_opts(cli.project);
 
     if let Err(err) = cmd.run(&app) {
         app.error(err);
@@ -1,21 +1,27 @@
+use std::collections::BTreeMap;
 use std::fmt;
+use std::iter::FromIterator;
 use std::ops;
 use std::str::FromStr;
+use std::sync::Arc;
 
-use serde::{Deserialize, Serialize};
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::prelude::*;
 
-#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Debug)]
-#[serde(rename_all = "lowercase")]
 /// Musical note notation convention
 /// Variant naming follows <https://en.wikipedia.org/wiki/Musical_note#12-tone_chromatic_scale>
+#[derive(Clone, PartialEq, Eq, Debug)]
 pub enum Notation {
-    #[serde(alias = "western")]
-    #[serde(alias = "dutch")]
+    /// Also accepted as `western` or `dutch`
     English,
-    #[serde(alias = "czech")]
+    /// Also accepted as `czech`
     German,
     Nashville,
     Roman,
+    /// A user-defined notation from the `[notations]` section in `bard.toml`
+    Custom(Arc<NotationTable>),
 }
 
 #[allow(clippy::derivable_impls)] // Due to MSRV
@@ -25,6 +31,7 @@ impl Default for Notation {
     }
 }
 
+/// Only parses the built-in notations, use `Notations::get()` to also look up user-defined ones.
 impl FromStr for Notation {
     type Err = ();
 
@@ -42,6 +49,204 @@ impl FromStr for Notation {
     }
 }
 
+impl Serialize for Notation {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Notation {
+    fn deserialize<D: Deserializer<'de>>(de: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(de)?;
+        name.parse()
+            .map_err(|_| D::Error::custom(format!("unknown notation '{}'", name)))
+    }
+}
+
+/// A user-defined notation: names of the 12 notes starting from C, in uppercase and lowercase variants.
+/// As with the built-in notations, lowercase chords are minor chords.
+#[derive(PartialEq, Eq, Debug)]
+pub struct NotationTable {
+    name: String,
+    notes: Vec<String>,
+    lowercase: Vec<String>,
+}
+
+impl NotationTable {
+    /// Create a notation table, `lowercase` defaults to lowercased `notes`.
+    pub fn new(name: &str, notes: Vec<String>, lowercase: Option<Vec<String>>) -> Result<Self> {
+        let valid_name = name.chars().next().map_or(false, char::is_alphabetic)
+            && name
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '-' || c == '_');
+        if !valid_name {
+            bail!("Invalid notation name '{}': It must start with a letter and contain only letters, digits, '-' and '_'.", name);
+        }
+        if name.parse::<Notation>().is_ok() || name.eq_ignore_ascii_case("none") {
+            bail!(
+                "Notation '{}': The name is reserved for a built-in notation.",
+                name
+            );
+        }
+
+        let lowercase =
+            lowercase.unwrap_or_else(|| notes.iter().map(|note| note.to_lowercase()).collect());
+        for (list, key) in [(&notes, "notes"), (&lowercase, "lowercase")] {
+            if list.len() != 12 {
+                bail!(
+                    "Notation '{}': '{}' must contain exactly 12 note names starting from C, found {}.",
+                    name,
+                    key,
+                    list.len()
+                );
+            }
+
+            let invalid = list.iter().find(|note| {
+                note.is_empty() || note.chars().any(|c| is_chord_separator(c) || c == '`')
+            });
+            if let Some(note) = invalid {
+                bail!("Notation '{}': Invalid note name {:?}.", name, note);
+            }
+        }
+
+        // The same name must not stand for different notes
+        let all = notes.iter().zip(0..).chain(lowercase.iter().zip(0..));
+        let mut seen = BTreeMap::new();
+        for (note, i) in all {
+            if let Some(prev) = seen.insert(note, i) {
+                if prev != i {
+                    bail!(
+                        "Notation '{}': Note name {:?} is used more than once.",
+                        name,
+                        note
+                    );
+                }
+            }
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            notes,
+            lowercase,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn notes(&self) -> &[String] {
+        &self.notes
+    }
+
+    pub fn lowercase(&self) -> &[String] {
+        &self.lowercase
+    }
+
+    /// The longest note name at the start of `from`, this way multi-character names take
+    /// precedence over their prefixes (eg. `Sol` over `So`). Returns the note and the size of its name.
+    fn parse_name(&self, from: &str) -> Option<(Chromatic, usize)> {
+        self.notes
+            .iter()
+            .zip(0..)
+            .chain(self.lowercase.iter().zip(0..))
+            .filter(|(note, _)| from.starts_with(note.as_str()))
+            .max_by_key(|(note, _)| note.len())
+            .map(|(note, i)| (Chromatic::new(i), note.len()))
+    }
+
+    fn note(&self, chromatic: Chromatic, uppercase: bool) -> &str {
+        let notes = if uppercase {
+            &self.notes
+        } else {
+            &self.lowercase
+        };
+        &notes[chromatic.0 as usize]
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct NotationDef {
+    notes: Vec<String>,
+    lowercase: Option<Vec<String>>,
+}
+
+/// Registry of the user-defined notations from the `[notations]` section in `bard.toml`.
+#[derive(Clone, Default, Debug)]
+pub struct Notations {
+    custom: Vec<Arc<NotationTable>>,
+}
+
+impl Notations {
+    /// Look up a notation by name, either a built-in or a user-defined one, case-insensitively.
+    pub fn get(&self, name: &str) -> Option<Notation> {
+        name.parse().ok().or_else(|| {
+            self.custom
+                .iter()
+                .find(|table| table.name.eq_ignore_ascii_case(name))
+                .map(|table| Notation::Custom(table.clone()))
+        })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &NotationTable> {
+        self.custom.iter().map(|table| &**table)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.custom.is_empty()
+    }
+}
+
+impl FromIterator<NotationTable> for Notations {
+    fn from_iter<T: IntoIterator<Item = NotationTable>>(iter: T) -> Self {
+        Self {
+            custom: iter.into_iter().map(Arc::new).collect(),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Notations {
+    fn deserialize<D: Deserializer<'de>>(de: D) -> Result<Self, D::Error> {
+        let defs = BTreeMap::<String, NotationDef>::deserialize(de)?;
+        let mut names = BTreeMap::new();
+        defs.into_iter()
+            .map(|(name, def)| {
+                if let Some(prev) = names.insert(name.to_ascii_lowercase(), name.clone()) {
+                    return Err(D::Error::custom(format!(
+                        "notations '{}' and '{}' differ only in case",
+                        prev, name
+                    )));
+                }
+                NotationTable::new(&name, def.notes, def.lowercase)
+                    .map_err(|err| D::Error::custom(format!("{:#}", err)))
+            })
+            .collect()
+    }
+}
+
+/// How minor chords are written when converting to Nashville or Roman notation
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum MinorChords {
+    /// Keep the convention used in the source, ie. the case of the chord and its suffix.
+    /// Nashville numbers always get an `m` suffix since they can't be lowercase.
+    Keep,
+    /// Minor chords are written with lowercase numerals without the `m` suffix, eg. `vi7`,
+    /// diminished chords with lowercase numerals and `°`, eg. `vii°`.
+    /// Nashville numbers get an `m` suffix.
+    Lowercase,
+    /// Minor chords are written with uppercase numerals and an `m` suffix, eg. `VIm7`.
+    Suffix,
+}
+
+#[allow(clippy::derivable_impls)] // Due to MSRV
+impl Default for MinorChords {
+    fn default() -> MinorChords {
+        MinorChords::Keep
+    }
+}
+
 impl fmt::Display for Notation {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let name = match self {
@@ -49,11 +254,30 @@ impl fmt::Display for Notation {
             Notation::German => "german",
             Notation::Nashville => "nashville",
             Notation::Roman => "roman",
+            Notation::Custom(table) => &table.name,
         };
         write!(f, "{}", name)
     }
 }
 
+impl Notation {
+    /// The built-in notations, ie. those that don't need to be defined in `[notations]`.
+    pub const BUILTIN: &'static [Notation] = &[
+        Notation::English,
+        Notation::German,
+        Notation::Nashville,
+        Notation::Roman,
+    ];
+
+    /// Names of the seven natural tones from C, eg. `C, D, E, F, G, A, B` in the English notation.
+    pub fn note_names(&self) -> Vec<&str> {
+        [0, 2, 4, 5, 7, 9, 11]
+            .iter()
+            .map(|&i| Chromatic::new(i).as_str(self, true))
+            .collect()
+    }
+}
+
 /// Represents a half-tone in a 12-tone chromatic scale in equal temperament
 /// tuning, starting from C (ie. C = 0, C# = 1, ...)
 #[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
@@ -221,7 +445,7 @@ impl Chromatic {
     /// Parses a chromatic from a start of a string, which may continue with
     /// arbitrary other characters. Returns the `Chromatic` parsed and
     /// the size of the chromatic part in bytes.
-    pub fn parse_span(from: &str, notation: Notation) -> Option<(Chromatic, usize)> {
+    pub fn parse_span(from: &str, notation: &Notation) -> Option<(Chromatic, usize)> {
         use self::Notation::*;
 
         if from.is_empty() {
@@ -232,11 +456,15 @@ impl Chromatic {
                 German => Self::parse_western(from, true),
                 Nashville => Self::parse_nashvile(from),
                 Roman => Self::parse_roman(from),
+                Custom(table) => {
+                    let (base, base_size) = table.parse_name(from)?;
+                    Some(Self::parse_halftone(from, base, base_size))
+                }
             }
         }
     }
 
-    pub fn parse(from: &str, notation: Notation) -> Option<Chromatic> {
+    pub fn parse(from: &str, notation: &Notation) -> Option<Chromatic> {
         Self::parse_span(from, notation).map(|(chromatic, _)| chromatic)
     }
 
@@ -297,13 +525,14 @@ impl Chromatic {
         }
     }
 
-    fn as_str(&self, notation: Notation, uppercase: bool) -> &'static str {
+    fn as_str<'n>(&self, notation: &'n Notation, uppercase: bool) -> &'n str {
         use self::Notation::*;
         match notation {
             English => self.as_str_western(false, uppercase),
             German => self.as_str_western(true, uppercase),
             Nashville => self.as_str_nashville(),
             Roman => self.as_str_roman(uppercase),
+            Custom(table) => table.note(*self, uppercase),
         }
     }
 
@@ -317,44 +546,205 @@ impl Chromatic {
 
 impl fmt::Display for Chromatic {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.as_str(Notation::English, true))
+        write!(f, "{}", self.as_str(&Notation::English, true))
+    }
+}
+
+/// A tone of a `NotationLegend`.
+#[derive(Serialize, Clone, PartialEq, Eq, Debug)]
+pub struct LegendEntry {
+    pub chromatic_index: u8,
+    pub name: String,
+    /// The name in the alt notation, if the legend has one.
+    pub alt_name: Option<String>,
+}
+
+/// Names of the 12 tones from C in a notation and optionally in an alt notation,
+/// so that templates can render a legend of the notations used in the book.
+#[derive(Serialize, Clone, Debug)]
+pub struct NotationLegend {
+    pub notation: Notation,
+    pub alt_notation: Option<Notation>,
+    pub entries: Vec<LegendEntry>,
+}
+
+impl NotationLegend {
+    pub fn new(notation: &Notation, alt_notation: Option<&Notation>) -> Self {
+        let entries = (0..12)
+            .map(Chromatic::new)
+            .map(|tone| LegendEntry {
+                chromatic_index: tone.num(),
+                name: tone.as_str(notation, true).to_string(),
+                alt_name: alt_notation.map(|alt| tone.as_str(alt, true).to_string()),
+            })
+            .collect();
+
+        Self {
+            notation: notation.clone(),
+            alt_notation: alt_notation.cloned(),
+            entries,
+        }
+    }
+
+    /// Legends of the book's `notation`, one for each of the `alt_notations`
+    /// or a single one without alt names if there are none.
+    pub fn for_book(notation: &Notation, alt_notations: &[Notation]) -> Vec<Self> {
+        if alt_notations.is_empty() {
+            vec![Self::new(notation, None)]
+        } else {
+            alt_notations
+                .iter()
+                .map(|alt| Self::new(notation, Some(alt)))
+                .collect()
+        }
+    }
+}
+
+/// Length of the minor marker (`m`, `mi`, or `min`) at the start of a chord suffix,
+/// zero if the suffix doesn't denote a minor chord.
+fn minor_marker_len(suffix: &str) -> usize {
+    if suffix.starts_with("maj") {
+        0
+    } else if suffix.starts_with("min") {
+        3
+    } else if suffix.starts_with("mi") {
+        2
+    } else if suffix.starts_with('m') {
+        1
+    } else {
+        0
+    }
+}
+
+/// The bass note of a slash chord, eg. the `G` in `C/G`.
+#[derive(Clone, Copy, Debug)]
+struct Bass {
+    note: Chromatic,
+    uppercase: bool,
+}
+
+impl Bass {
+    /// Parses a bass note from a start of a string, the note must be followed
+    /// by a separator or the end of the string. Returns the size of the note in bytes.
+    fn parse(src: &str, notation: &Notation) -> Option<(Self, usize)> {
+        let (note, size) = Chromatic::parse_span(src, notation)?;
+        if !src[size..].chars().next().map_or(true, is_chord_separator) {
+            return None;
+        }
+
+        let bass = Self {
+            note,
+            uppercase: !src.chars().next().unwrap().is_lowercase(),
+        };
+        Some((bass, size))
     }
 }
 
 #[derive(Debug)]
 struct Chord<'s> {
+    /// The chord as written, including the trailing separators.
+    src: &'s str,
+    /// The root as written, eg. `Ab` in `Abmi7`.
+    root: &'s str,
     base: Chromatic,
     uppercase: bool,
     suffix: &'s str,
+    bass: Option<Bass>,
+    /// Separator characters and non-chord tokens following the chord
+    trailing: &'s str,
 }
 
 impl<'s> Chord<'s> {
-    fn parse(src: &'s str, notation: Notation) -> Result<Self, &'s str> {
-        let (base, base_size) = Chromatic::parse_span(src, notation).ok_or(src)?;
+    /// Parses a chord from a start of the string in the form `root suffix (/bass)?`
+    /// followed by any number of separators and non-chord tokens, see `is_non_chord()`.
+    /// Returns the `Chord` and the rest of the input.
+    fn parse(src: &'s str, notation: &Notation) -> Result<(Self, &'s str), &'s str> {
+        let (base, base_size) = Chromatic::parse_span(src, notation).ok_or_else(|| {
+            let len = chunk_len(src);
+            &src[..len + non_chord_len(&src[len..])]
+        })?;
+
+        let suffix_end = src[base_size..]
+            .find(is_chord_separator)
+            .map(|i| base_size + i)
+            .unwrap_or(src.len());
+        let suffix = &src[base_size..suffix_end];
+        let mut rest = &src[suffix_end..];
+
+        let mut bass = None;
+        if let Some(bass_src) = rest.strip_prefix('/') {
+            if let Some((b, size)) = Bass::parse(bass_src, notation) {
+                bass = Some(b);
+                rest = &bass_src[size..];
+            }
+        }
 
-        Ok(Self {
+        let (trailing, rest) = rest.split_at(non_chord_len(rest));
+
+        let chord = Self {
+            src: &src[..src.len() - rest.len()],
+            root: &src[..base_size],
             base,
-            uppercase: src.chars().next().unwrap().is_uppercase(),
-            suffix: &src[base_size..],
-        })
+            // Nashville numbers don't have a case, those count as uppercase
+            uppercase: !src.chars().next().unwrap().is_lowercase(),
+            suffix,
+            bass,
+            trailing,
+        };
+        Ok((chord, rest))
     }
 
     fn transposed(self, by: impl Into<Chromatic>) -> Self {
+        let by = by.into();
         Self {
             base: self.base.transposed(by),
-            uppercase: self.uppercase,
-            suffix: self.suffix,
+            bass: self.bass.map(|bass| Bass {
+                note: bass.note.transposed(by),
+                uppercase: bass.uppercase,
+            }),
+            ..self
         }
     }
 
-    fn str_len(&self, notation: Notation) -> usize {
-        self.base.as_str(notation, self.uppercase).len() + self.suffix.len()
+    /// Whether this is a minor chord, either by the lowercase convention
+    /// or by the suffix.
+    fn is_minor(&self) -> bool {
+        !self.uppercase || minor_marker_len(self.suffix) > 0
     }
 
-    fn write_string(&self, mut to: String, notation: Notation) -> String {
-        let base = self.base.as_str(notation, self.uppercase);
-        to.push_str(base);
-        to.push_str(self.suffix);
+    fn write_string(&self, mut to: String, notation: &Notation, minor: MinorChords) -> String {
+        let is_minor = self.is_minor();
+        let marker_len = minor_marker_len(self.suffix);
+
+        let (uppercase, minor_marker, suffix) = match (notation, minor) {
+            (Notation::English | Notation::German | Notation::Custom(_), _)
+            | (Notation::Roman, MinorChords::Keep) => (self.uppercase, "", self.suffix),
+            // Nashville numbers can't be lowercase, the minor marker needs to be present
+            (Notation::Nashville, _) | (Notation::Roman, MinorChords::Suffix) => {
+                let marker = if is_minor && marker_len == 0 { "m" } else { "" };
+                (true, marker, self.suffix)
+            }
+            // Diminished chords are lowercase as well, with `dim` written as `°`, eg. `vii°7`
+            (Notation::Roman, MinorChords::Lowercase) => match self.suffix.strip_prefix("dim") {
+                Some(rest) => (false, "\u{b0}", rest),
+                None if self.suffix.starts_with(['\u{b0}', '\u{f8}']) => (false, "", self.suffix),
+                None => (!is_minor, "", &self.suffix[marker_len..]),
+            },
+        };
+
+        to.push_str(self.base.as_str(notation, uppercase));
+        to.push_str(minor_marker);
+        to.push_str(suffix);
+        if let Some(bass) = self.bass {
+            let uppercase = match (notation, minor) {
+                (Notation::English | Notation::German | Notation::Custom(_), _)
+                | (_, MinorChords::Keep) => bass.uppercase,
+                _ => true,
+            };
+            to.push('/');
+            to.push_str(bass.note.as_str(notation, uppercase));
+        }
+        to.push_str(self.trailing);
         to
     }
 }
@@ -367,14 +757,60 @@ fn is_chord_separator(c: char) -> bool {
     }
 }
 
+/// Whether `token` is a timing or repeat sign written among chords rather than a chord,
+/// ie. a bar repeat `%`, repeat signs `𝄆` and `𝄇`, a number of repeats such as `x2`, or a run of dots.
+/// These are kept as they are when chords are transposed.
+fn is_non_chord(token: &str) -> bool {
+    match token {
+        "%" | "\u{1d106}" | "\u{1d107}" => true,
+        _ if !token.is_empty() && token.chars().all(|c| c == '.') => true,
+        _ => token.strip_prefix(['x', '\u{d7}']).map_or(false, |n| {
+            !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit())
+        }),
+    }
+}
+
+/// Length of the separators and non-chord tokens at the start of `src`, eg. `| % | ` in `| % | G`.
+fn non_chord_len(src: &str) -> usize {
+    let mut len = 0;
+    loop {
+        let rest = &src[len..];
+        len += rest.find(|c| !is_chord_separator(c)).unwrap_or(rest.len());
+
+        let rest = &src[len..];
+        let token = &rest[..rest.find(is_chord_separator).unwrap_or(rest.len())];
+        if !is_non_chord(token) {
+            return len;
+        }
+        len += token.len();
+    }
+}
+
+/// Length of a chord along with the following separators,
+/// used to report unrecognized chords.
+fn chunk_len(src: &str) -> usize {
+    let mut split_found = false;
+    // Find split such that multiple consecutive split chars are all
+    // added as suffix to its preceiding chord.
+    src.find(|c| {
+        if !split_found {
+            split_found = is_chord_separator(c);
+            false
+        } else {
+            !is_chord_separator(c)
+        }
+    })
+    .unwrap_or(src.len())
+}
+
 #[derive(Debug)]
-struct ChordIter<'s> {
+struct ChordIter<'s, 'n> {
     rest: &'s str,
-    notation: Notation,
+    notation: &'n Notation,
 }
 
-impl<'s> ChordIter<'s> {
-    fn new(src: &'s str, src_notation: Notation) -> Self {
+impl<'s, 'n> ChordIter<'s, 'n> {
+    fn new(src: &'s str, src_notation: &'n Notation) -> Self {
         Self {
             rest: src,
             notation: src_notation,
@@ -382,7 +818,7 @@ impl<'s> ChordIter<'s> {
     }
 }
 
-impl<'s> Iterator for ChordIter<'s> {
+impl<'s, 'n> Iterator for ChordIter<'s, 'n> {
     type Item = Result<Chord<'s>, &'s str>;
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -390,60 +826,271 @@ impl<'s> Iterator for ChordIter<'s> {
             return None;
         }
 
-        let mut split_found = false;
-        // Find split such that multiple consecutive split chars are all
-        // added as suffix to its preceiding chord.
-        let split = self
-            .rest
-            .find(|c| {
-                if !split_found {
-                    split_found = is_chord_separator(c);
-                    false
-                } else {
-                    !is_chord_separator(c)
-                }
-            })
-            .unwrap_or(self.rest.len());
-
-        let (next, rest) = self.rest.split_at(split);
-        self.rest = rest;
-
-        Some(Chord::parse(next, self.notation))
+        match Chord::parse(self.rest, self.notation) {
+            Ok((chord, rest)) => {
+                self.rest = rest;
+                Some(Ok(chord))
+            }
+            Err(chunk) => {
+                self.rest = &self.rest[chunk.len()..];
+                Some(Err(chunk))
+            }
+        }
     }
 }
 
-pub fn transpose(
-    chord_set: &str,
+pub fn transpose<'s>(
+    chord_set: &'s str,
     by: impl Into<Chromatic>,
-    src_notation: Notation,
-    to_notation: Notation,
-) -> Result<String, &str> {
+    src_notation: &Notation,
+    to_notation: &Notation,
+    minor: MinorChords,
+) -> Result<String, &'s str> {
     let by = by.into();
 
-    // Split the leading prefix, if any, from the chord set
-    let prefix_at = chord_set
-        .find(|c: char| !is_chord_separator(c))
-        .unwrap_or(0);
-    let (prefix, rest) = chord_set.split_at(prefix_at);
+    // Split the leading separators and non-chord tokens, if any, from the chord set
+    let (prefix, rest) = chord_set.split_at(non_chord_len(chord_set));
 
-    // Compute the resulting string's length
-    let mut transposed_len = prefix.len();
-    for chord in ChordIter::new(rest, src_notation) {
-        transposed_len += chord?.transposed(by).str_len(to_notation);
-    }
+    let chords = ChordIter::new(rest, src_notation).collect::<Result<Vec<_>, _>>()?;
 
     // Render the resulting string
-    let mut res = String::with_capacity(transposed_len);
+    let mut res = String::with_capacity(chord_set.len() + 8);
     res.push_str(prefix);
-    Ok(ChordIter::new(rest, src_notation).fold(res, |res, chord| {
-        chord.unwrap().transposed(by).write_string(res, to_notation)
+    Ok(chords.into_iter().fold(res, |res, chord| {
+        chord.transposed(by).write_string(res, to_notation, minor)
     }))
 }
 
+/// A musical key, ie. the tonic and whether the key is minor.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Key {
+    pub tonic: Chromatic,
+    pub minor: bool,
+}
+
+impl Key {
+    /// The key as written in `notation`, eg. `Am`, as used in the `!key()` directive.
+    pub fn name(&self, notation: &Notation) -> String {
+        let mut name = self.tonic.as_str(notation, true).to_string();
+        if self.minor {
+            name.push('m');
+        }
+        name
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.name(&Notation::English))
+    }
+}
+
+/// Roots of the chords in a chord set along with whether each chord is minor.
+/// Unrecognized chords are skipped.
+pub fn chord_roots(chord_set: &str, notation: &Notation) -> Vec<(Chromatic, bool)> {
+    let chords = &chord_set[non_chord_len(chord_set)..];
+    ChordIter::new(chords, notation)
+        .filter_map(Result::ok)
+        .map(|chord| (chord.base, chord.is_minor()))
+        .collect()
+}
+
+/// The root and the suffix of `chord` if it's a single chord without a bass note in `notation`,
+/// eg. `(A, "m7")` for `Am7`. `None` for chord sets, slash chords, and unrecognized chords.
+pub fn chord_root<'s>(chord: &'s str, notation: &Notation) -> Option<(Chromatic, &'s str)> {
+    let (chord, rest) = Chord::parse(chord.trim(), notation).ok()?;
+    let single = rest.is_empty() && chord.trailing.is_empty() && chord.bass.is_none();
+    single.then_some((chord.base, chord.suffix))
+}
+
+/// The first chord in a chord set which isn't recognized in `notation`, if any.
+pub fn unrecognized_chord<'s>(chord_set: &'s str, notation: &Notation) -> Option<&'s str> {
+    let chords = &chord_set[non_chord_len(chord_set)..];
+    ChordIter::new(chords, notation).find_map(Result::err)
+}
+
+/// All the chords in a chord set which aren't recognized in `notation`,
+/// without the separators and non-chord tokens following them.
+pub fn unrecognized_chords<'s>(chord_set: &'s str, notation: &Notation) -> Vec<&'s str> {
+    let chords = &chord_set[non_chord_len(chord_set)..];
+    ChordIter::new(chords, notation)
+        .filter_map(Result::err)
+        .map(|chunk| &chunk[..chunk.find(is_chord_separator).unwrap_or(chunk.len())])
+        .collect()
+}
+
+/// The built-in notations in which all chords of `chord_set` are recognized,
+/// eg. `[German]` for `Hm7`, to suggest the notation a chord is written in.
+pub fn detect_notation(chord_set: &str) -> Vec<Notation> {
+    Notation::BUILTIN
+        .iter()
+        .filter(|notation| unrecognized_chord(chord_set, notation).is_none())
+        .cloned()
+        .collect()
+}
+
+/// A chord of a chord set split into its parts, see `split_chords()`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ChordParts<'s> {
+    /// The root as written, eg. `Ab` in `Abmi7/Eb`.
+    pub root: &'s str,
+    /// The suffix between the root and the bass note, eg. `mi7` in `Abmi7/Eb`, may be empty.
+    pub suffix: &'s str,
+    /// The bass note and the separators following the chord, eg. `/Eb` in `Abmi7/Eb`.
+    pub rest: &'s str,
+}
+
+/// Split chords of `chord_set` into their roots and suffixes.
+/// Separators at the start of the set and unrecognized chords are returned as `Err` chunks,
+/// so that concatenating all the items yields the original `chord_set`.
+pub fn split_chords<'s>(
+    chord_set: &'s str,
+    notation: &Notation,
+) -> Vec<Result<ChordParts<'s>, &'s str>> {
+    ChordIter::new(chord_set, notation)
+        .map(|chord| {
+            chord.map(|chord| ChordParts {
+                root: chord.root,
+                suffix: chord.suffix,
+                rest: &chord.src[chord.root.len() + chord.suffix.len()..],
+            })
+        })
+        .collect()
+}
+
+/// Replace suffixes of chords in `chord_set` found in `map`, eg. `Amin7` becomes `Am7` with `"min7" = "m7"`.
+/// Suffixes are matched as a whole, unmapped suffixes and unrecognized chords are kept as they are.
+pub fn normalize_suffixes(
+    chord_set: &str,
+    notation: &Notation,
+    map: &BTreeMap<String, String>,
+) -> String {
+    let mut res = String::with_capacity(chord_set.len() + 8);
+    for item in split_chords(chord_set, notation) {
+        match item {
+            Ok(parts) => {
+                res.push_str(parts.root);
+                res.push_str(map.get(parts.suffix).map_or(parts.suffix, String::as_str));
+                res.push_str(parts.rest);
+            }
+            Err(chunk) => res.push_str(chunk),
+        }
+    }
+    res
+}
+
+/// Chords fitting a major key: interval of the root from the tonic, whether the chord is minor, and weight.
+/// Besides the diatonic chords, the flat seventh (as in mixolydian) is common in folk songs.
+const MAJOR_KEY_CHORDS: &[(u8, bool, f64)] = &[
+    (0, false, 1.0),
+    (2, true, 0.8),
+    (4, true, 0.6),
+    (5, false, 1.0),
+    (7, false, 1.0),
+    (9, true, 0.8),
+    (10, false, 0.4),
+];
+
+/// Chords fitting a minor key, same as `MAJOR_KEY_CHORDS`, both the minor and the major dominant are included.
+const MINOR_KEY_CHORDS: &[(u8, bool, f64)] = &[
+    (0, true, 1.0),
+    (3, false, 0.8),
+    (5, true, 0.8),
+    (7, false, 1.0),
+    (7, true, 0.8),
+    (8, false, 0.8),
+    (10, false, 0.8),
+];
+
+/// Extra weight of the tonic chord, also added for the first and the last chord of a song if it's the tonic.
+/// This tells apart relative keys, which otherwise share chords.
+const TONIC_WEIGHT: f64 = 1.0;
+
+/// Guesses with a lower confidence are ambiguous, see `KeyGuess`.
+const MIN_CONFIDENCE: f64 = 0.15;
+
+/// Result of `detect_key()`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct KeyGuess {
+    /// The best scoring key.
+    pub key: Key,
+    /// How much the key scores above the runner-up relative to its score, from 0 to 1.
+    pub confidence: f64,
+    /// The runner-up key if the confidence is too low to tell the two apart.
+    pub alternative: Option<Key>,
+}
+
+impl KeyGuess {
+    pub fn is_ambiguous(&self) -> bool {
+        self.alternative.is_some()
+    }
+}
+
+/// Guess the key of a song from the roots of its `chords` in order, as returned by `chord_roots()`.
+///
+/// A histogram of chord roots, separately for major and minor chords, is matched against the chords
+/// fitting each of the 24 major and minor keys, the tonic chord is weighted extra.
+/// Returns `None` if there are no chords.
+pub fn detect_key(chords: &[(Chromatic, bool)]) -> Option<KeyGuess> {
+    let (first, last) = (*chords.first()?, *chords.last()?);
+
+    let mut histogram = [[0.0; 2]; 12];
+    for &(root, minor) in chords {
+        histogram[root.num() as usize][minor as usize] += 1.0;
+    }
+
+    let score = |key: Key| {
+        let template = if key.minor {
+            MINOR_KEY_CHORDS
+        } else {
+            MAJOR_KEY_CHORDS
+        };
+        let fitting: f64 = template
+            .iter()
+            .map(|&(interval, minor, weight)| {
+                let root = key.tonic.transposed(interval);
+                histogram[root.num() as usize][minor as usize] * weight
+            })
+            .sum();
+
+        let tonic = (key.tonic, key.minor);
+        let tonic_count = histogram[key.tonic.num() as usize][key.minor as usize]
+            + (first == tonic) as u8 as f64
+            + (last == tonic) as u8 as f64;
+        fitting + tonic_count * TONIC_WEIGHT
+    };
+
+    let mut scores: Vec<_> = (0..12)
+        .flat_map(|tonic| {
+            [false, true].iter().map(move |&minor| Key {
+                tonic: tonic.into(),
+                minor,
+            })
+        })
+        .map(|key| (key, score(key)))
+        .collect();
+    scores.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+
+    let (key, best) = scores[0];
+    let (runner_up, second) = scores[1];
+    let confidence = if best > 0.0 {
+        (best - second) / best
+    } else {
+        0.0
+    };
+
+    Some(KeyGuess {
+        key,
+        confidence,
+        alternative: (confidence < MIN_CONFIDENCE).then_some(runner_up),
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    use MinorChords::*;
     use Notation::*;
 
     #[test]
@@ -461,7 +1108,7 @@ mod tests {
         // Test from_str:
         let parsed: Vec<_> = names
             .iter()
-            .map(|s| Notation::from_str(*s).unwrap())
+            .map(|s| Notation::from_str(s).unwrap())
             .collect();
         assert_eq!(&parsed, &expected);
 
@@ -486,166 +1133,549 @@ mod tests {
 
     #[test]
     fn chromatic_basic() {
-        let c = Chromatic::parse("C", English).unwrap();
+        let c = Chromatic::parse("C", &English).unwrap();
         assert_eq!(format!("{}", c), "C");
 
-        assert_eq!(Chromatic::parse("", English), None);
-        assert_eq!(Chromatic::parse("", German), None);
-        assert_eq!(Chromatic::parse("", Nashville), None);
-        assert_eq!(Chromatic::parse("", Roman), None);
+        assert_eq!(Chromatic::parse("", &English), None);
+        assert_eq!(Chromatic::parse("", &German), None);
+        assert_eq!(Chromatic::parse("", &Nashville), None);
+        assert_eq!(Chromatic::parse("", &Roman), None);
     }
 
     #[test]
     fn chromatic_transposition() {
         let c: Chromatic = 0.into();
         let transposed = c.transposed(-1);
-        assert_eq!(transposed.as_str(German, false), "h");
+        assert_eq!(transposed.as_str(&German, false), "h");
 
         let transposed = c.transposed(3);
-        assert_eq!(transposed.as_str(German, true), "Eb");
+        assert_eq!(transposed.as_str(&German, true), "Eb");
     }
 
     #[test]
     fn chromatic_english() {
         let c: Chromatic = 0.into();
         let fsharp: Chromatic = 6.into();
-        assert_eq!(Chromatic::parse("C", English).unwrap(), c);
-        assert_eq!(Chromatic::parse("F#", English).unwrap(), fsharp);
-        assert_eq!(Chromatic::parse("F♯", English).unwrap(), fsharp);
-        assert_eq!(Chromatic::parse("Gb", English).unwrap(), fsharp);
-        assert_eq!(Chromatic::parse("G♭", English).unwrap(), fsharp);
-        assert_eq!(Chromatic::parse("X", English), None);
+        assert_eq!(Chromatic::parse("C", &English).unwrap(), c);
+        assert_eq!(Chromatic::parse("F#", &English).unwrap(), fsharp);
+        assert_eq!(Chromatic::parse("F♯", &English).unwrap(), fsharp);
+        assert_eq!(Chromatic::parse("Gb", &English).unwrap(), fsharp);
+        assert_eq!(Chromatic::parse("G♭", &English).unwrap(), fsharp);
+        assert_eq!(Chromatic::parse("X", &English), None);
     }
 
     #[test]
     fn chromatic_german() {
         let c: Chromatic = 0.into();
         let fsharp: Chromatic = 6.into();
-        assert_eq!(Chromatic::parse("C", German).unwrap(), c);
+        assert_eq!(Chromatic::parse("C", &German).unwrap(), c);
         assert_eq!(
-            Chromatic::parse("H", German).unwrap(),
-            Chromatic::parse("B", English).unwrap()
+            Chromatic::parse("H", &German).unwrap(),
+            Chromatic::parse("B", &English).unwrap()
         );
         assert_eq!(
-            Chromatic::parse("B", German).unwrap(),
-            Chromatic::parse("Bb", English).unwrap()
+            Chromatic::parse("B", &German).unwrap(),
+            Chromatic::parse("Bb", &English).unwrap()
         );
-        assert_eq!(Chromatic::parse("F#", German).unwrap(), fsharp);
-        assert_eq!(Chromatic::parse("F♯", German).unwrap(), fsharp);
-        assert_eq!(Chromatic::parse("Gb", German).unwrap(), fsharp);
-        assert_eq!(Chromatic::parse("G♭", German).unwrap(), fsharp);
-        assert_eq!(Chromatic::parse("X", German), None);
+        assert_eq!(Chromatic::parse("F#", &German).unwrap(), fsharp);
+        assert_eq!(Chromatic::parse("F♯", &German).unwrap(), fsharp);
+        assert_eq!(Chromatic::parse("Gb", &German).unwrap(), fsharp);
+        assert_eq!(Chromatic::parse("G♭", &German).unwrap(), fsharp);
+        assert_eq!(Chromatic::parse("X", &German), None);
     }
 
     #[test]
     fn chromatic_nashville() {
         let c: Chromatic = 0.into();
         let fsharp: Chromatic = 6.into();
-        assert_eq!(Chromatic::parse("1", Nashville).unwrap(), c);
+        assert_eq!(Chromatic::parse("1", &Nashville).unwrap(), c);
         assert_eq!(
-            Chromatic::parse("2", Nashville).unwrap(),
-            Chromatic::parse("D", German).unwrap()
+            Chromatic::parse("2", &Nashville).unwrap(),
+            Chromatic::parse("D", &German).unwrap()
         );
-        assert_eq!(Chromatic::parse("4#", Nashville).unwrap(), fsharp);
-        assert_eq!(Chromatic::parse("4♯", Nashville).unwrap(), fsharp);
-        assert_eq!(Chromatic::parse("5b", Nashville).unwrap(), fsharp);
-        assert_eq!(Chromatic::parse("5♭", Nashville).unwrap(), fsharp);
-        assert_eq!(Chromatic::parse("0", Nashville), None);
-        assert_eq!(Chromatic::parse("8", Nashville), None);
-        assert_eq!(Chromatic::parse("X", Nashville), None);
+        assert_eq!(Chromatic::parse("4#", &Nashville).unwrap(), fsharp);
+        assert_eq!(Chromatic::parse("4♯", &Nashville).unwrap(), fsharp);
+        assert_eq!(Chromatic::parse("5b", &Nashville).unwrap(), fsharp);
+        assert_eq!(Chromatic::parse("5♭", &Nashville).unwrap(), fsharp);
+        assert_eq!(Chromatic::parse("0", &Nashville), None);
+        assert_eq!(Chromatic::parse("8", &Nashville), None);
+        assert_eq!(Chromatic::parse("X", &Nashville), None);
     }
 
     #[test]
     fn chromatic_roman() {
         let c: Chromatic = 0.into();
         let fsharp: Chromatic = 6.into();
-        assert_eq!(Chromatic::parse("I", Roman).unwrap(), c);
+        assert_eq!(Chromatic::parse("I", &Roman).unwrap(), c);
         assert_eq!(
-            Chromatic::parse("II", Roman).unwrap(),
-            Chromatic::parse("D", German).unwrap()
+            Chromatic::parse("II", &Roman).unwrap(),
+            Chromatic::parse("D", &German).unwrap()
         );
-        assert_eq!(Chromatic::parse("IV#", Roman).unwrap(), fsharp);
-        assert_eq!(Chromatic::parse("IV♯", Roman).unwrap(), fsharp);
-        assert_eq!(Chromatic::parse("Vb", Roman).unwrap(), fsharp);
-        assert_eq!(Chromatic::parse("V♭", Roman).unwrap(), fsharp);
-        assert_eq!(Chromatic::parse("C", Roman), None);
-        assert_eq!(Chromatic::parse("X", Roman), None);
+        assert_eq!(Chromatic::parse("IV#", &Roman).unwrap(), fsharp);
+        assert_eq!(Chromatic::parse("IV♯", &Roman).unwrap(), fsharp);
+        assert_eq!(Chromatic::parse("Vb", &Roman).unwrap(), fsharp);
+        assert_eq!(Chromatic::parse("V♭", &Roman).unwrap(), fsharp);
+        assert_eq!(Chromatic::parse("C", &Roman), None);
+        assert_eq!(Chromatic::parse("X", &Roman), None);
     }
 
     #[test]
     fn chromatic_span() {
-        assert_eq!(Chromatic::parse_span("A", English).unwrap().1, 1);
-        assert_eq!(Chromatic::parse_span("D#", English).unwrap().1, 2);
-        assert_eq!(Chromatic::parse_span("H#", German).unwrap().1, 2);
-        assert_eq!(Chromatic::parse_span("1#", Nashville).unwrap().1, 2);
+        assert_eq!(Chromatic::parse_span("A", &English).unwrap().1, 1);
+        assert_eq!(Chromatic::parse_span("D#", &English).unwrap().1, 2);
+        assert_eq!(Chromatic::parse_span("H#", &German).unwrap().1, 2);
+        assert_eq!(Chromatic::parse_span("1#", &Nashville).unwrap().1, 2);
     }
 
     #[test]
     fn transpose_basic() {
-        let t = transpose("C", 2, English, English).unwrap();
+        let t = transpose("C", 2, &English, &English, Keep).unwrap();
         assert_eq!(t, "D");
     }
 
     #[test]
     fn transpose_multiple() {
-        let t = transpose("C/D,E", 2, English, English).unwrap();
+        let t = transpose("C/D,E", 2, &English, &English, Keep).unwrap();
         assert_eq!(t, "D/E,F#");
 
-        let t = transpose("C / D , E", 2, English, English).unwrap();
+        let t = transpose("C / D , E", 2, &English, &English, Keep).unwrap();
         assert_eq!(t, "D / E , F#");
     }
 
     #[test]
     fn transpose_suffixes() {
-        let t = transpose("Cm/D°,Emaj7", 2, English, English).unwrap();
+        let t = transpose("Cm/D°,Emaj7", 2, &English, &English, Keep).unwrap();
         assert_eq!(t, "Dm/E°,F#maj7");
     }
 
     #[test]
     fn transpose_multiple_separators() {
-        let t = transpose("C/|\\/D,,   ,,E,,,", 2, English, English).unwrap();
+        let t = transpose("C/|\\/D,,   ,,E,,,", 2, &English, &English, Keep).unwrap();
         assert_eq!(t, "D/|\\/E,,   ,,F#,,,");
     }
 
     #[test]
     fn transpose_leading_separators() {
-        let t = transpose(",C", 2, English, English).unwrap();
+        let t = transpose(",C", 2, &English, &English, Keep).unwrap();
         assert_eq!(t, ",D");
     }
 
     #[test]
     fn transpose_whitespace() {
-        let t = transpose("   /C  ", 2, English, English).unwrap();
+        let t = transpose("   /C  ", 2, &English, &English, Keep).unwrap();
         assert_eq!(t, "   /D  ");
     }
 
     #[test]
     fn transpose_german() {
-        let t = transpose("H/B", 0, German, English).unwrap();
+        let t = transpose("H/B", 0, &German, &English, Keep).unwrap();
         assert_eq!(t, "B/Bb");
     }
 
     #[test]
     fn transpose_roman() {
-        let t = transpose("C/D,E", 5, English, Roman).unwrap();
+        let t = transpose("C/D,E", 5, &English, &Roman, Keep).unwrap();
         assert_eq!(t, "IV/V,VI");
 
-        let t = transpose("C/D,E", 5, English, Roman).unwrap();
+        let t = transpose("C/D,E", 5, &English, &Roman, Keep).unwrap();
         assert_eq!(t, "IV/V,VI");
     }
 
     #[test]
     fn transpose_nashville() {
-        let t = transpose("I/II,III", 0, Roman, Nashville).unwrap();
+        let t = transpose("I/II,III", 0, &Roman, &Nashville, Keep).unwrap();
         assert_eq!(t, "1/2,3");
     }
 
     #[test]
     fn transpose_lowercase() {
-        let t = transpose("c", 2, English, Roman).unwrap();
+        let t = transpose("c", 2, &English, &Roman, Keep).unwrap();
         assert_eq!(t, "ii");
 
-        let t = transpose("c,d,e,", 2, English, Roman).unwrap();
+        let t = transpose("c,d,e,", 2, &English, &Roman, Keep).unwrap();
         assert_eq!(t, "ii,iii,iv#,");
     }
+
+    #[test]
+    fn transpose_slash_chords() {
+        let cases: &[(&str, i32, Notation, Notation, MinorChords, &str)] = &[
+            ("Am7/G", 2, English, English, Keep, "Bm7/A"),
+            ("C/G7", 2, English, English, Keep, "D/A7"),
+            ("G/B", 0, English, German, Keep, "G/H"),
+            ("G/H", 0, German, English, Keep, "G/B"),
+            ("G/B", 2, German, German, Keep, "A/C"),
+            ("Am/G", 0, English, Nashville, Keep, "6m/5"),
+            ("a/G", 0, English, Nashville, Keep, "6m/5"),
+            ("Am/G", 0, English, Roman, Keep, "VIm/V"),
+            ("a/g", 0, English, Roman, Keep, "vi/v"),
+            ("a/g", 0, English, Roman, Lowercase, "vi/V"),
+            ("Am/G", 0, English, Roman, Lowercase, "vi/V"),
+            ("a/g", 0, English, Roman, Suffix, "VIm/V"),
+            ("6m/5", 2, Nashville, English, Keep, "Bm/A"),
+            ("6m/5", 0, Nashville, Roman, Lowercase, "vi/V"),
+            ("vi7/V", 0, Roman, English, Keep, "a7/G"),
+            ("vi7/V", 0, Roman, Nashville, Keep, "6m7/5"),
+            ("I/V, IV/I", 7, Roman, English, Keep, "G/D, C/G"),
+        ];
+
+        for (chord, by, from, to, minor, expected) in cases {
+            let t = transpose(chord, *by, from, to, *minor).unwrap();
+            assert_eq!(
+                t, *expected,
+                "{:?} transposed by {} from {} to {} ({:?})",
+                chord, by, from, to, minor
+            );
+        }
+
+        assert_eq!(
+            transpose("C/X", 0, &English, &English, Keep).unwrap_err(),
+            "X"
+        );
+    }
+
+    #[test]
+    fn transpose_minor_nashville() {
+        for minor in [Keep, Lowercase, Suffix] {
+            let t = transpose("a,Am,Amin7,Amaj7,C", 0, &English, &Nashville, minor).unwrap();
+            assert_eq!(t, "6m,6m,6min7,6maj7,1");
+        }
+    }
+
+    #[test]
+    fn transpose_minor_roman() {
+        let t = transpose("Am,Amin7,a7,Amaj7,C", 0, &English, &Roman, Lowercase).unwrap();
+        assert_eq!(t, "vi,vi7,vi7,VImaj7,I");
+
+        let t = transpose("Am,Amin7,a7,Amaj7,C", 0, &English, &Roman, Suffix).unwrap();
+        assert_eq!(t, "VIm,VImin7,VIm7,VImaj7,I");
+
+        let t = transpose("Am,a7,C", 0, &English, &Roman, Keep).unwrap();
+        assert_eq!(t, "VIm,vi7,I");
+    }
+
+    #[test]
+    fn transpose_diminished_roman() {
+        let t = transpose(
+            "Adim,Bdim7,B\u{b0},B\u{f8}7,Am7b5",
+            0,
+            &English,
+            &Roman,
+            Lowercase,
+        )
+        .unwrap();
+        assert_eq!(t, "vi\u{b0},vii\u{b0}7,vii\u{b0},vii\u{f8}7,vi7b5");
+
+        let t = transpose("Adim,Bdim7", 0, &English, &Roman, Keep).unwrap();
+        assert_eq!(t, "VIdim,VIIdim7");
+
+        let t = transpose("Adim", 0, &English, &Roman, Suffix).unwrap();
+        assert_eq!(t, "VIdim");
+    }
+
+    fn solfege() -> Notation {
+        let notes = [
+            "Do", "Do#", "Re", "Mib", "Mi", "Fa", "Fa#", "Sol", "Lab", "La", "Sib", "Si",
+        ];
+        let notes = notes.iter().map(|s| s.to_string()).collect();
+        Notation::Custom(Arc::new(
+            NotationTable::new("solfege", notes, None).unwrap(),
+        ))
+    }
+
+    #[test]
+    fn transpose_non_chords() {
+        let cases: &[(&str, i32, Notation, &str)] = &[
+            ("| G | % | x2 |", 2, English, "| A | % | x2 |"),
+            ("G.. D..", 2, English, "A.. E.."),
+            ("G .. D . .", 2, English, "A .. E . ."),
+            (
+                "\u{1d106} Am | % \u{1d107} \u{d7}3",
+                2,
+                English,
+                "\u{1d106} Bm | % \u{1d107} \u{d7}3",
+            ),
+            ("% | G/B", 0, German, "% | G/H"),
+            ("%", 5, English, "%"),
+        ];
+
+        for (chord, by, to, expected) in cases {
+            let t = transpose(chord, *by, &English, to, Keep).unwrap();
+            assert_eq!(t, *expected, "{:?} transposed by {}", chord, by);
+        }
+
+        assert_eq!(
+            transpose("G x Am", 2, &English, &English, Keep).unwrap_err(),
+            "x "
+        );
+        assert_eq!(
+            super::unrecognized_chords("| G | X % | x2 |", &English),
+            ["X"]
+        );
+        assert_eq!(
+            chord_roots("% G x2", &English),
+            [(Chromatic::new(7), false)]
+        );
+        assert_eq!(
+            split_chords("% G % X %", &English),
+            [
+                Err("% "),
+                Ok(ChordParts {
+                    root: "G",
+                    suffix: "",
+                    rest: " % ",
+                }),
+                Err("X %"),
+            ]
+        );
+    }
+
+    #[test]
+    fn split_chords_parts() {
+        let parts = split_chords(" Amin7/G, Cmaj7 X Hm", &English);
+        let part = |root, suffix, rest| Ok(ChordParts { root, suffix, rest });
+        assert_eq!(
+            parts,
+            [
+                Err(" "),
+                part("A", "min7", "/G, "),
+                part("C", "maj7", " "),
+                Err("X "),
+                Err("Hm"),
+            ]
+        );
+        assert_eq!(split_chords("Bbmi/F", &German), [part("Bb", "mi", "/F")]);
+    }
+
+    #[test]
+    fn normalize_suffixes_map() {
+        let map: BTreeMap<_, _> = [("min", "m"), ("-", "m"), ("maj7", "Δ")]
+            .iter()
+            .map(|(from, to)| (from.to_string(), to.to_string()))
+            .collect();
+        let cases = [
+            ("Amin", English, "Am"),
+            ("A-/G, Cmaj7", English, "Am/G, CΔ"),
+            ("Amin7 C", English, "Amin7 C"),
+            (" Hmin X", German, " Hm X"),
+            ("6- 4maj7", Nashville, "6m 4Δ"),
+        ];
+        for (chord_set, notation, expected) in cases {
+            assert_eq!(normalize_suffixes(chord_set, &notation, &map), expected);
+        }
+    }
+
+    #[test]
+    fn chromatic_custom() {
+        let solfege = solfege();
+        assert_eq!(Chromatic::parse_span("Do", &solfege), Some((0.into(), 2)));
+        assert_eq!(Chromatic::parse_span("Do#", &solfege), Some((1.into(), 3)));
+        assert_eq!(Chromatic::parse_span("Reb", &solfege), Some((1.into(), 3)));
+        assert_eq!(Chromatic::parse_span("Sol7", &solfege), Some((7.into(), 3)));
+        assert_eq!(Chromatic::parse_span("Sol#", &solfege), Some((8.into(), 4)));
+        assert_eq!(Chromatic::parse_span("sib", &solfege), Some((10.into(), 3)));
+        assert_eq!(Chromatic::parse("C", &solfege), None);
+        assert_eq!(Chromatic::parse("", &solfege), None);
+        assert_eq!(Chromatic::new(10).as_str(&solfege, true), "Sib");
+        assert_eq!(Chromatic::new(10).as_str(&solfege, false), "sib");
+    }
+
+    #[test]
+    fn chromatic_custom_longest_name() {
+        // Both So and Sol are note names, the longer one takes precedence
+        let notes = [
+            "C", "C#", "D", "D#", "E", "F", "So", "Sol", "G#", "A", "A#", "B",
+        ];
+        let notes = notes.iter().map(|s| s.to_string()).collect();
+        let table = NotationTable::new("test", notes, None).unwrap();
+        let notation = Notation::Custom(Arc::new(table));
+        assert_eq!(Chromatic::parse_span("So", &notation), Some((6.into(), 2)));
+        assert_eq!(Chromatic::parse_span("Som", &notation), Some((6.into(), 2)));
+        assert_eq!(Chromatic::parse_span("Sol", &notation), Some((7.into(), 3)));
+        assert_eq!(
+            Chromatic::parse_span("Solm7", &notation),
+            Some((7.into(), 3))
+        );
+    }
+
+    #[test]
+    fn notation_table_invalid() {
+        let table = |name: &str, notes: &[&str], lowercase: Option<&[&str]>| {
+            let notes = notes.iter().map(|s| s.to_string()).collect();
+            let lowercase = lowercase.map(|l| l.iter().map(|s| s.to_string()).collect());
+            NotationTable::new(name, notes, lowercase)
+                .map(|_| ())
+                .map_err(|err| err.to_string())
+        };
+        let notes = [
+            "Do", "Do#", "Re", "Mib", "Mi", "Fa", "Fa#", "Sol", "Lab", "La", "Sib", "Si",
+        ];
+
+        assert_eq!(table("solfege", &notes, None), Ok(()));
+        assert!(table("english", &notes, None)
+            .unwrap_err()
+            .contains("reserved"));
+        assert!(table("None", &notes, None)
+            .unwrap_err()
+            .contains("reserved"));
+        assert!(table("5ths", &notes, None)
+            .unwrap_err()
+            .contains("Invalid notation name"));
+        assert!(table("sol fege", &notes, None)
+            .unwrap_err()
+            .contains("Invalid notation name"));
+        assert!(table("solfege", &notes[..11], None)
+            .unwrap_err()
+            .contains("exactly 12"));
+        assert!(table("solfege", &notes, Some(&notes[..3]))
+            .unwrap_err()
+            .contains("exactly 12"));
+
+        let mut invalid = notes;
+        invalid[3] = "Mi/b";
+        assert!(table("solfege", &invalid, None)
+            .unwrap_err()
+            .contains("Invalid note name"));
+        invalid[3] = "";
+        assert!(table("solfege", &invalid, None)
+            .unwrap_err()
+            .contains("Invalid note name"));
+        invalid[3] = "Re";
+        assert!(table("solfege", &invalid, None)
+            .unwrap_err()
+            .contains("more than once"));
+
+        // A lowercase variant can't be the name of another note
+        let mut lowercase = notes;
+        lowercase[0] = "Re";
+        assert!(table("solfege", &notes, Some(&lowercase))
+            .unwrap_err()
+            .contains("more than once"));
+    }
+
+    #[test]
+    fn notations_registry() {
+        let notations: Notations = toml::from_str(
+            r#"
+            [Solfege]
+            notes = ["Do", "Do#", "Re", "Mib", "Mi", "Fa", "Fa#", "Sol", "Lab", "La", "Sib", "Si"]
+            lowercase = ["do", "do#", "re", "mib", "mi", "fa", "fa#", "sol", "lab", "la", "sib", "si"]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(notations.get("czech"), Some(German));
+        assert_eq!(notations.get("solfege").unwrap().to_string(), "Solfege");
+        assert_eq!(notations.get("SOLFEGE"), notations.get("Solfege"));
+        assert_eq!(notations.get("tonic-solfa"), None);
+        assert_eq!(Notations::default().get("solfege"), None);
+
+        let err = toml::from_str::<Notations>("[bad]\nnotes = [\"C\"]\n").unwrap_err();
+        assert!(err.to_string().contains("exactly 12"));
+    }
+
+    #[test]
+    fn transpose_custom() {
+        let solfege = solfege();
+        let t = transpose("Do/Sol,Rem7", 2, &solfege, &solfege, Keep).unwrap();
+        assert_eq!(t, "Re/La,Mim7");
+
+        let t = transpose("Lam/Sol la", 0, &solfege, &English, Keep).unwrap();
+        assert_eq!(t, "Am/G a");
+
+        let t = transpose("Am/G,Bb7,f#", 0, &English, &solfege, Keep).unwrap();
+        assert_eq!(t, "Lam/Sol,Sib7,fa#");
+
+        let t = transpose("Sol,mi", 0, &solfege, &Roman, Lowercase).unwrap();
+        assert_eq!(t, "V,iii");
+
+        assert_eq!(
+            transpose("Do/X", 0, &solfege, &English, Keep).unwrap_err(),
+            "X"
+        );
+    }
+
+    fn guess(chords: &str) -> KeyGuess {
+        detect_key(&chord_roots(chords, &English)).unwrap()
+    }
+
+    #[test]
+    fn detect_key_known() {
+        // Wild Mountain Thyme
+        let g = guess("G C G C G Em C D G C G Em C D G");
+        assert_eq!(g.key.to_string(), "G");
+        assert!(!g.is_ambiguous());
+
+        // House of the Rising Sun
+        let g = guess("Am C D F Am C E Am C D F Am E Am");
+        assert_eq!(g.key.to_string(), "Am");
+        assert!(!g.is_ambiguous());
+
+        // Let It Be
+        let g = guess("C G Am F C G F C");
+        assert_eq!(g.key.to_string(), "C");
+        assert!(!g.is_ambiguous());
+
+        // Scarborough Fair, dorian, but the closest key is still the minor one
+        let g = guess("Em D Em G Em A Em G D Em");
+        assert_eq!(g.key.to_string(), "Em");
+
+        // Slash chords and suffixes
+        let g = guess("D/F# G Bm7 A7sus4 D");
+        assert_eq!(g.key.to_string(), "D");
+    }
+
+    #[test]
+    fn detect_key_ambiguous() {
+        let g = guess("G C G C");
+        assert!(g.is_ambiguous(), "{:?}", g);
+        let keys = [g.key.to_string(), g.alternative.unwrap().to_string()];
+        assert!(keys.contains(&"G".to_string()), "{:?}", keys);
+        assert!(keys.contains(&"C".to_string()), "{:?}", keys);
+
+        assert_eq!(detect_key(&[]), None);
+        assert_eq!(chord_roots("X Y", &English), []);
+    }
+
+    #[test]
+    fn unrecognized_chords() {
+        assert_eq!(unrecognized_chord("Am7 D/F#, G", &English), None);
+        assert_eq!(unrecognized_chord(" C N.C. X", &English), Some("N.C. "));
+        assert_eq!(unrecognized_chord("H7", &English), Some("H7"));
+        assert_eq!(unrecognized_chord("H7", &German), None);
+        assert_eq!(unrecognized_chord("", &English), None);
+    }
+
+    #[test]
+    fn key_name() {
+        let key = Key {
+            tonic: 10.into(),
+            minor: true,
+        };
+        assert_eq!(key.to_string(), "Bbm");
+        assert_eq!(key.name(&German), "Bm");
+    }
+
+    #[test]
+    fn notation_legend() {
+        let legend = NotationLegend::new(&English, Some(&German));
+        assert_eq!(legend.entries.len(), 12);
+        let names: Vec<_> = legend.entries[9..]
+            .iter()
+            .map(|e| (e.chromatic_index, &*e.name, e.alt_name.as_deref()))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                (9, "A", Some("A")),
+                (10, "Bb", Some("B")),
+                (11, "B", Some("H"))
+            ]
+        );
+
+        let legends = NotationLegend::for_book(&Nashville, &[]);
+        assert_eq!(legends.len(), 1);
+        assert_eq!(legends[0].alt_notation, None);
+        assert_eq!(legends[0].entries[5].name, "4");
+        assert_eq!(legends[0].entries[5].alt_name, None);
+    }
 }
@@ -5,23 +5,30 @@
 //!
 //! The API is provided by the `Parser` type, it's `parse()` method is the entry point.
 
+use std::borrow::Cow;
 use std::cell::{Cell, Ref, RefCell, RefMut};
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
 use std::mem;
 use std::str;
 use std::sync::mpsc;
 
-use comrak::nodes::{Ast, AstNode, ListType, NodeCode, NodeValue};
+use comrak::nodes::{Ast, AstNode, ListType, NodeCode, NodeValue, Sourcepos};
 use comrak::{ComrakExtensionOptions, ComrakOptions, ComrakParseOptions, ComrakRenderOptions};
+use icu_normalizer::ComposingNormalizer;
 use once_cell::sync::Lazy;
 use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 use crate::book::*;
-use crate::music::{self, Notation};
+use crate::music::{self, MinorChords, Notation, Notations};
 use crate::prelude::*;
+use crate::project::Metadata;
+use crate::time::Duration;
 use crate::util::{BStr, StrExt};
 
+pub mod chordpro;
 pub mod html;
 
 type AstRef<'a> = &'a AstNode<'a>;
@@ -29,18 +36,66 @@ type Arena<'a> = comrak::Arena<AstNode<'a>>;
 
 const FALLBACK_TITLE: &str = "[Untitled]";
 
-static EXTENSION: Lazy<Regex> = Lazy::new(|| Regex::new(r"(^|\s)(!+)(\S+)").unwrap());
+/// An extension is a run of non-whitespace chars, except for the argument
+/// of a function-like one, eg. `!alias(Old Title)`, which may contain spaces.
+static EXTENSION: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(^|\s)(!+)([a-z]+\([^()\n]*\)|\S+)").unwrap());
+
+/// Split an attribute-style list of classes, eg. `{.center .qr}`, off the end of image alt text.
+/// Returns the remaining text and the classes, or `None` if the text doesn't end with such a list.
+fn split_image_attrs(alt: &str) -> Option<(&str, Vec<&str>)> {
+    let inner = alt.trim_end().strip_suffix('}')?;
+    let start = inner.rfind('{')?;
+    let classes: Vec<_> = inner[start + 1..]
+        .split_whitespace()
+        .map(|attr| attr.strip_prefix('.').filter(|class| !class.is_empty()))
+        .collect::<Option<_>>()?;
+    if classes.is_empty() {
+        return None;
+    }
+    Some((inner[..start].trim_end(), classes))
+}
 
 #[derive(Error, PartialEq, Eq, Clone, Debug)]
 pub enum DiagKind {
     #[error("Control character not allowed: 0x{char:x}")]
     ControlChar { char: u32 },
-    #[error("Unrecognized chord: {chord}")]
-    Transposition { chord: BStr },
+    #[error("Unrecognized chord: {chord}\n{}", chord_hint(.chord, .notation))]
+    Transposition { chord: BStr, notation: Notation },
     #[error("The HTML tag <{tag}> is reserved for future use.")]
     HtmlReservedTag { tag: BStr },
     #[error("Text in HTML block ignored: \"{text}\"\nYou may need a blank line between the HTML block and the following text.")]
     HtmlIgnoredText { text: BStr },
+    #[error("File too large: {size} bytes, the limit is {limit} bytes (limits.max_file_size)")]
+    FileTooLarge { size: usize, limit: usize },
+    #[error("Line too long: {length} bytes, the limit is {limit} bytes (limits.max_line_length)")]
+    LineTooLong { length: usize, limit: usize },
+    #[error("Too many elements in a paragraph or block: more than {limit} (limits.max_inlines)")]
+    TooManyInlines { limit: usize },
+    #[error("Invalid duration: \"{value}\", expected minutes and seconds (3:45) or seconds (225)")]
+    InvalidDuration { value: BStr },
+    #[error("Unknown image class: \"{class}\", it is not listed in image_classes")]
+    UnknownImageClass { class: BStr },
+    #[error("Unknown snippet: \"{name}\", it is neither in [snippets] nor in the snippets file")]
+    UnknownSnippet { name: BStr },
+    #[error("Snippet \"{name}\" is used within itself")]
+    RecursiveSnippet { name: BStr },
+    #[error("Nothing to continue: !cont is not preceded by a verse")]
+    NothingToContinue,
+    #[error("Invalid chorus reference: \"{reference}\", expected a chorus number or a range of choruses, eg. !>2 or !>1-3")]
+    InvalidChorusRef { reference: BStr },
+    #[error("Text is not in Unicode NFC form, it was normalized (normalize_unicode)")]
+    NotNormalized,
+    #[error("Invalid song metadata, it is left out: {message}")]
+    InvalidMetadata { message: BStr },
+    #[error("ChordPro directive not supported, it is left out: {directive}")]
+    ChordProDirective { directive: BStr },
+    #[error("Unrecognized chord: {chord}\n{}\nHint: If it isn't meant to be a chord, add it to chord_lint_allow in bard.toml.", chord_hint(.chord, .notation))]
+    UnrecognizedChord { chord: BStr, notation: Notation },
+    #[error("Label not at the start of a paragraph, it is kept as text: {ext}")]
+    MisplacedLabel { ext: BStr },
+    #[error("The verse already has a label, this one is ignored: {ext}")]
+    DuplicateLabel { ext: BStr },
 }
 
 impl DiagKind {
@@ -50,6 +105,21 @@ impl DiagKind {
             Self::Transposition { .. } => true,
             Self::HtmlReservedTag { .. } => true,
             Self::HtmlIgnoredText { .. } => false,
+            Self::FileTooLarge { .. } => true,
+            Self::LineTooLong { .. } => true,
+            Self::TooManyInlines { .. } => true,
+            Self::InvalidDuration { .. } => false,
+            Self::UnknownImageClass { .. } => false,
+            Self::UnknownSnippet { .. } => true,
+            Self::RecursiveSnippet { .. } => true,
+            Self::NothingToContinue => false,
+            Self::InvalidChorusRef { .. } => false,
+            Self::NotNormalized => false,
+            Self::InvalidMetadata { .. } => false,
+            Self::ChordProDirective { .. } => false,
+            Self::UnrecognizedChord { .. } => false,
+            Self::MisplacedLabel { .. } => false,
+            Self::DuplicateLabel { .. } => false,
         }
     }
 
@@ -67,6 +137,32 @@ impl DiagKind {
     }
 }
 
+/// Hint for `DiagKind::Transposition` and `DiagKind::UnrecognizedChord`, lists the note names of the song's `notation`
+/// and suggests other built-in notations in which the `chord` is recognized.
+fn chord_hint(chord: &str, notation: &Notation) -> String {
+    let mut hint = format!(
+        "Hint: Note names in the {} notation are {}, optionally followed by # or b.",
+        notation,
+        notation.note_names().join(", ")
+    );
+
+    let others: Vec<_> = music::detect_notation(chord)
+        .into_iter()
+        .filter(|nt| nt != notation)
+        .collect();
+    if let Some(first) = others.first() {
+        let names: Vec<_> = others.iter().map(|nt| nt.to_string()).collect();
+        let plural = if names.len() > 1 { "s" } else { "" };
+        hint.push_str(&format!(
+            "\nThe chord is recognized in the {} notation{}, did you mean to set `notation = \"{}\"` in bard.toml?",
+            names.join(" and "),
+            plural,
+            first
+        ));
+    }
+    hint
+}
+
 /// Parser diagnostic report type.
 ///
 /// Reports kind of diagnostic (error or warning), filename, line number and containts the specific error/warning.
@@ -117,9 +213,8 @@ struct Extension {
     prefix_space: bool,
 }
 
-impl<'a> From<Captures<'a>> for Extension {
-    fn from(caps: Captures<'a>) -> Self {
-        let prefix_space = caps.get(1).unwrap().as_str().chars().next().is_some();
+impl Extension {
+    fn new(caps: &Captures, prefix_space: bool) -> Self {
         let num_excls = caps.get(2).unwrap().as_str().len() as _;
         let content = caps.get(3).unwrap().as_str().to_owned();
         Self {
@@ -128,10 +223,8 @@ impl<'a> From<Captures<'a>> for Extension {
             prefix_space,
         }
     }
-}
 
-impl Extension {
-    fn try_parse_xpose(&self) -> Option<Transpose> {
+    fn try_parse_xpose(&self, notations: &Notations) -> Option<Transpose> {
         if self.content.starts_with(&['+', '-'][..]) {
             if let Ok(delta) = self.content.parse::<i32>() {
                 match self.num_excls {
@@ -142,7 +235,7 @@ impl Extension {
             }
         }
 
-        if let Ok(notation) = self.content.parse::<Notation>() {
+        if let Some(notation) = notations.get(&self.content) {
             match self.num_excls {
                 1 => return Some(Transpose::Notation(notation)),
                 2 => return Some(Transpose::AltNotation(notation)),
@@ -153,27 +246,123 @@ impl Extension {
         if self.num_excls == 2 && self.content == "none" {
             return Some(Transpose::AltNone);
         }
+        if self.num_excls == 2 && self.content == "roman-minor" {
+            return Some(Transpose::AltRomanMinor);
+        }
 
         None
     }
 
-    fn try_parse_chorus_ref(&self) -> Option<ChorusRef> {
-        if self.num_excls == 1 && self.content.chars().all(|c| c == '>') {
+    /// Parse a chorus reference, ie. `!>`, `!>>`, ... or the numeric forms `!>2` and `!>1-3`.
+    /// Numbers are validated in `VerseBuilder::parse_text()` so that invalid ones can be reported.
+    fn try_parse_chorus_ref(&self) -> Option<Inline> {
+        if self.num_excls != 1 {
+            return None;
+        }
+
+        if self.content.chars().all(|c| c == '>') {
             let num = self.content.len() as _;
-            Some(ChorusRef::new(Some(num), self.prefix_space))
-        } else {
-            None
+            return Some(Inline::ChorusRef(ChorusRef::new(
+                Some(num),
+                self.prefix_space,
+            )));
+        }
+
+        let number = |s: &str| match s.bytes().all(|b| b.is_ascii_digit()) {
+            true => s.parse::<u32>().ok(),
+            false => None,
+        };
+        let nums = self.content.strip_prefix('>')?;
+        let inline = match nums.split_once('-') {
+            Some((start, end)) => Inline::ChorusRefRange(ChorusRefRange::new(
+                number(start)?,
+                number(end)?,
+                self.prefix_space,
+            )),
+            None => Inline::ChorusRef(ChorusRef::new(Some(number(nums)?), self.prefix_space)),
+        };
+        Some(inline)
+    }
+
+    /// Parse a directive, ie. `!tempo(120)`, `!time(3/4)`, `!duration(3:45)`, `!key(Am)`,
+    /// `!alias(Old Title)`, or `!dir(rtl)`.
+    /// The duration value is validated in `VerseBuilder::parse_text()` so that it can be reported.
+    fn try_parse_directive(&self) -> Option<Directive> {
+        if self.num_excls != 1 {
+            return None;
         }
+
+        let positive = |s: &str| s.parse::<u32>().map(|n| n > 0).unwrap_or(false);
+        let (name, arg) = self.content.strip_suffix(')')?.split_once('(')?;
+        let kind = match name {
+            "tempo" if positive(arg) => DirectiveKind::Tempo,
+            "time" => {
+                let (beats, unit) = arg.split_once('/')?;
+                if !positive(beats) || !positive(unit) {
+                    return None;
+                }
+                DirectiveKind::Time
+            }
+            "duration" => DirectiveKind::Duration,
+            "key" if !arg.is_empty() => DirectiveKind::Key,
+            "alias" if !arg.trim().is_empty() => DirectiveKind::Alias,
+            "dir" if TextDir::parse(arg).is_some() => DirectiveKind::Dir,
+            _ => return None,
+        };
+
+        Some(Directive::new(kind, arg, self.prefix_space))
     }
 
-    fn try_parse(&self) -> Option<Inline> {
-        if let Some(xpose) = self.try_parse_xpose() {
+    /// Whether this is the `!cont` extension marking a verse continuation, see `SongBuilder::parse()`.
+    fn is_continuation(&self) -> bool {
+        self.num_excls == 1 && self.content == "cont"
+    }
+
+    /// Parse a custom verse label, ie. `!label:Bridge` or `!label(Second Bridge)`, returns the label.
+    fn try_parse_label(&self) -> Option<&str> {
+        if self.num_excls != 1 {
+            return None;
+        }
+
+        let label = match self.content.strip_prefix("label:") {
+            Some(label) => label,
+            None => self.content.strip_prefix("label(")?.strip_suffix(')')?,
+        };
+        let label = label.trim();
+        (!label.is_empty()).then_some(label)
+    }
+
+    /// Parse the chord position extension, ie. `!chordpos(after)` or `!chordpos(before)`,
+    /// see `ChordPos`.
+    fn try_parse_chord_pos(&self) -> Option<ChordPos> {
+        if self.num_excls != 1 {
+            return None;
+        }
+
+        let arg = self.content.strip_prefix("chordpos(")?.strip_suffix(')')?;
+        ChordPos::parse(arg)
+    }
+
+    /// Parse a snippet reference, ie. `!use(name)`, returns the snippet name.
+    fn try_parse_snippet(&self) -> Option<&str> {
+        if self.num_excls != 1 {
+            return None;
+        }
+
+        let name = self.content.strip_prefix("use(")?.strip_suffix(')')?;
+        (!name.is_empty()).then_some(name)
+    }
+
+    fn try_parse(&self, notations: &Notations) -> Option<Inline> {
+        if let Some(xpose) = self.try_parse_xpose(notations) {
             // Transposition extension recognized
             Some(Inline::Transpose(xpose))
+        } else if let Some(directive) = self.try_parse_directive() {
+            Some(Inline::Directive(directive))
         } else {
             // Try parsing chorus reference,
             // push as regular text if not recognized
-            self.try_parse_chorus_ref().map(Inline::ChorusRef)
+            self.try_parse_chorus_ref()
         }
     }
 }
@@ -183,6 +372,8 @@ impl Extension {
 pub struct Transposition {
     /// Source notation of the song
     src_notation: Notation,
+    /// How minor chords are written in Nashville and Roman notations
+    minor_chords: MinorChords,
     /// Transposition of chords
     xpose: Option<i32>,
     /// Notation conversion of chords
@@ -191,6 +382,11 @@ pub struct Transposition {
     alt_xpose: Option<i32>,
     /// Notation conversion of alt chords (2nd row)
     alt_notation: Option<Notation>,
+    /// How minor chords of alt chords are written, if it differs from `minor_chords`, see `Transpose::AltRomanMinor`
+    alt_minor_chords: Option<MinorChords>,
+    /// Transposition of a song variant, added to the transposition of both chords and alt chords,
+    /// see `Parser::parse_variants()`.
+    offset: i32,
 
     /// Option to disable transposition for unit testing,
     /// ie. leave `Inline::Transpose` in the AST so they can be checked.
@@ -198,14 +394,23 @@ pub struct Transposition {
 }
 
 impl Transposition {
-    fn new(src_notation: Notation, disabled: bool) -> Self {
+    fn new(src_notation: Notation, minor_chords: MinorChords, disabled: bool) -> Self {
         Self {
             src_notation,
+            minor_chords,
             disabled,
             ..Default::default()
         }
     }
 
+    /// The state at the start of a song variant transposed by `offset` semitones.
+    fn with_offset(&self, offset: i32) -> Self {
+        Self {
+            offset,
+            ..self.clone()
+        }
+    }
+
     fn update(&mut self, xpose: Transpose) {
         if self.disabled {
             return;
@@ -215,19 +420,55 @@ impl Transposition {
             Transpose::Transpose(d) => self.xpose = Some(d),
             Transpose::Notation(nt) => self.notation = Some(nt),
             Transpose::AltTranspose(d) => self.alt_xpose = Some(d),
-            Transpose::AltNotation(nt) => self.alt_notation = Some(nt),
+            Transpose::AltNotation(nt) => {
+                self.alt_notation = Some(nt);
+                self.alt_minor_chords = None;
+            }
+            Transpose::AltRomanMinor => {
+                self.alt_notation = Some(Notation::Roman);
+                self.alt_minor_chords = Some(MinorChords::Lowercase);
+            }
             Transpose::AltNone => {
                 self.alt_xpose = None;
                 self.alt_notation = None;
+                self.alt_minor_chords = None;
             }
         }
     }
 
+    /// Notation of chords after the notation conversion, if any.
+    fn chord_notation(&self) -> &Notation {
+        self.notation.as_ref().unwrap_or(&self.src_notation)
+    }
+
+    /// A `DiagKind::Transposition` for `chord`, which isn't recognized in the source notation.
+    fn chord_error(&self, chord: &str) -> DiagKind {
+        DiagKind::Transposition {
+            chord: chord.into(),
+            notation: self.src_notation.clone(),
+        }
+    }
+
+    /// Transpose the value of a `!key()` directive the same way as chords, the alt chords row doesn't apply.
+    fn transpose_key(&self, key: &mut BStr) -> Result<(), DiagKind> {
+        if self.disabled || (self.xpose.is_none() && self.notation.is_none() && self.offset == 0) {
+            return Ok(());
+        }
+
+        let delta = self.xpose.unwrap_or(0) + self.offset;
+        let to_nt = self.notation.as_ref().unwrap_or(&self.src_notation);
+        *key = music::transpose(key, delta, &self.src_notation, to_nt, self.minor_chords)
+            .map_err(|chord| self.chord_error(chord))?
+            .into();
+        Ok(())
+    }
+
     fn is_some(&self) -> bool {
         self.xpose.is_some()
             || self.notation.is_some()
             || self.alt_xpose.is_some()
             || self.alt_notation.is_some()
+            || self.offset != 0
     }
 }
 
@@ -239,11 +480,14 @@ trait NodeExt<'a> {
     fn is_p(&self) -> bool;
     fn is_code(&self) -> bool;
     fn is_break(&self) -> bool;
+    fn is_soft_break(&self) -> bool;
     fn is_link(&self) -> bool;
     fn is_item(&self) -> bool;
     fn is_bq(&self) -> bool;
     fn is_img(&self) -> bool;
     fn is_inline_html(&self) -> bool;
+    /// Content of a fenced code block with the `meta` info string, see `Song::metadata`.
+    fn meta_block(&self) -> Option<String>;
 
     /// Elements that shouldn't go into chord child inlines,
     /// ie. line break or and image
@@ -316,6 +560,11 @@ impl<'a> NodeExt<'a> for AstNode<'a> {
         )
     }
 
+    #[inline]
+    fn is_soft_break(&self) -> bool {
+        matches!(self.data.borrow().value, NodeValue::SoftBreak)
+    }
+
     #[inline]
     fn is_link(&self) -> bool {
         matches!(self.data.borrow().value, NodeValue::Link(..))
@@ -341,6 +590,15 @@ impl<'a> NodeExt<'a> for AstNode<'a> {
         matches!(self.data.borrow().value, NodeValue::HtmlInline(..))
     }
 
+    fn meta_block(&self) -> Option<String> {
+        match &self.data.borrow().value {
+            NodeValue::CodeBlock(cb) if cb.fenced && cb.info.trim() == "meta" => {
+                Some(cb.literal.clone())
+            }
+            _ => None,
+        }
+    }
+
     #[inline]
     fn ends_chord(&self) -> bool {
         self.is_break() || self.is_img() || self.is_inline_html()
@@ -457,21 +715,27 @@ impl<'a> NodeExt<'a> for AstNode<'a> {
 struct ChordBuilder {
     chord: BStr,
     alt_chord: Option<BStr>,
+    /// See `Chord::root_chromatic` and `Chord::suffix`.
+    root: Option<(u8, BStr)>,
     backticks: usize,
     baseline: bool,
     inlines: Vec<Inline>,
+    /// See `Inline::line()`.
+    line: Option<u32>,
 }
 
 impl ChordBuilder {
-    fn new(code: &NodeCode) -> Self {
+    fn new(code: &NodeCode, line: Option<u32>) -> Self {
         let (chord, baseline) = Self::preprocess_chord_set(&code.literal);
 
         Self {
             chord,
             alt_chord: None,
+            root: None,
             backticks: code.num_backticks,
             baseline,
             inlines: vec![],
+            line,
         }
     }
 
@@ -517,45 +781,92 @@ impl ChordBuilder {
         &mut self.inlines
     }
 
-    fn transpose(&mut self, xp: &Transposition) -> Result<(), BStr> {
+    fn transpose(&mut self, xp: &Transposition) -> Result<(), DiagKind> {
         if xp.disabled {
             return Ok(());
         }
 
-        let src_nt = xp.src_notation;
+        let src_nt = &xp.src_notation;
+        let minor = xp.minor_chords;
 
         // alt_xpose needs to be done first, because self.chord may be overwritten
         if xp.alt_xpose.is_some() || xp.alt_notation.is_some() {
-            let delta = xp.alt_xpose.unwrap_or(0);
-            let to_nt = xp.alt_notation.unwrap_or(src_nt);
-            self.alt_chord = Some(music::transpose(&self.chord, delta, src_nt, to_nt)?.into());
+            let delta = xp.alt_xpose.unwrap_or(0) + xp.offset;
+            let to_nt = xp.alt_notation.as_ref().unwrap_or(src_nt);
+            let alt_minor = xp.alt_minor_chords.unwrap_or(minor);
+            let alt_chord = music::transpose(&self.chord, delta, src_nt, to_nt, alt_minor)
+                .map_err(|chord| xp.chord_error(chord))?;
+            self.alt_chord = Some(alt_chord.into());
         }
 
-        if xp.xpose.is_some() || xp.notation.is_some() {
-            let delta = xp.xpose.unwrap_or(0);
-            let to_nt = xp.notation.unwrap_or(src_nt);
-            self.chord = music::transpose(&self.chord, delta, src_nt, to_nt)?.into();
+        if xp.xpose.is_some() || xp.notation.is_some() || xp.offset != 0 {
+            let delta = xp.xpose.unwrap_or(0) + xp.offset;
+            let to_nt = xp.notation.as_ref().unwrap_or(src_nt);
+            self.chord = music::transpose(&self.chord, delta, src_nt, to_nt, minor)
+                .map_err(|chord| xp.chord_error(chord))?
+                .into();
         }
 
         Ok(())
     }
 
+    /// Replace suffixes of the chord and the alt chord found in `map`, once they're transposed,
+    /// see `music::normalize_suffixes()`.
+    fn normalize(&mut self, xp: &Transposition, map: &BTreeMap<String, String>) {
+        if map.is_empty() {
+            return;
+        }
+
+        self.chord = music::normalize_suffixes(&self.chord, xp.chord_notation(), map).into();
+        if let Some(alt_chord) = self.alt_chord.as_mut() {
+            let notation = xp.alt_notation.as_ref().unwrap_or(&xp.src_notation);
+            *alt_chord = music::normalize_suffixes(alt_chord, notation, map).into();
+        }
+    }
+
+    /// Find the root and suffix of the chord once it's transposed, see `music::chord_root()`.
+    fn parse_root(&mut self, xp: &Transposition) {
+        self.root = music::chord_root(&self.chord, xp.chord_notation())
+            .map(|(root, suffix)| (root.into(), suffix.into()));
+    }
+
     fn finalize(self, inlines: &mut Vec<Inline>) {
-        let chord = Chord::new(
+        let mut chord = Chord::new(
             self.chord,
             self.alt_chord,
+            self.root,
             self.backticks,
             self.baseline,
             self.inlines,
         );
+        chord.line = self.line;
         inlines.push(Inline::Chord(chord));
     }
 }
 
+/// A custom verse label given by the `!label:...` extension at the start of a paragraph.
+#[derive(Debug)]
+struct LabelExt {
+    /// Index of the paragraph in the verse.
+    para: usize,
+    label: BStr,
+    /// The extension as written, for diagnostics.
+    ext: BStr,
+    line: usize,
+}
+
 #[derive(Debug)]
 struct VerseBuilder<'a> {
     label: VerseLabel,
     paragraphs: Vec<Paragraph>,
+    /// See `Verse::continued`.
+    continued: bool,
+    /// A `!cont` extension was found, taken by `SongBuilder::parse()` after each paragraph.
+    continuation: bool,
+    /// Labels found at the start of paragraphs, applied by `SongBuilder`, see `VerseBuilder::apply_labels()`.
+    labels: Vec<LabelExt>,
+    /// Whether no inlines of the current paragraph have been parsed yet, labels are only recognized there.
+    para_start: bool,
     ctx: &'a ParserCtx<'a>,
 }
 
@@ -564,6 +875,10 @@ impl<'a> VerseBuilder<'a> {
         Self {
             label,
             paragraphs: vec![],
+            continued: false,
+            continuation: false,
+            labels: vec![],
+            para_start: false,
             ctx,
         }
     }
@@ -587,36 +902,131 @@ impl<'a> VerseBuilder<'a> {
             .text()
             .unwrap_or_else(|| unreachable!("Unexpected element: {:?}", &data.value));
 
+        let escaped = self.ctx.escaped_excls(&data.sourcepos, text);
+        self.parse_str(text, data.sourcepos.start.line, &escaped, target);
+    }
+
+    /// Parse extensions in `text` located on `line`, `escaped` are offsets of `!` chars
+    /// escaped with a backslash, see `ParserCtx::escaped_excls()`.
+    fn parse_str(&mut self, text: &str, line: usize, escaped: &[usize], target: &mut Vec<Inline>) {
+        // Extensions must be delimited by whitespace or text boundaries on both sides.
+        // An extension that is removed from the text (ie. applied transposition)
+        // is removed along with one adjacent whitespace char - the preceding one if available,
+        // otherwise the following one. This way the result is the same regardless of whether
+        // the extension is at the start, in the middle, or at the end of a line.
+        // Extensions that are kept in the AST (ie. chorus refs, directives) take the preceding whitespace
+        // char, if any, which is then recorded in `prefix_space`.
+        let text_line = self.ctx.position(line);
         let mut pos = 0;
         for caps in EXTENSION.captures_iter(text) {
-            let hit = caps.get(0).unwrap();
+            let ws = caps.get(1).unwrap();
+            let ext_start = caps.get(2).unwrap().start();
+            let ext_end = caps.get(3).unwrap().end();
+
+            if escaped.contains(&ext_start) {
+                // Escaped with a backslash, this is regular text
+                continue;
+            }
+
+            // The preceding whitespace may have been taken by the previous extension already
+            let prefix_space = !ws.as_str().is_empty() && ws.start() >= pos;
 
             // Try parsing an extension
-            let ext = Extension::from(caps);
-            if let Some(inline) = ext.try_parse() {
+            let ext = Extension::new(&caps, prefix_space);
+            let chord_pos = ext.try_parse_chord_pos();
+            let label = ext.try_parse_label();
+            let para_start =
+                self.para_start && target.is_empty() && text[pos..ext_start].trim().is_empty();
+            if label.is_some() && !para_start {
+                // Labels elsewhere are reported and kept as regular text
+                let kind = DiagKind::MisplacedLabel {
+                    ext: text[ext_start..ext_end].into(),
+                };
+                self.ctx.report_diag(line, kind);
+                continue;
+            }
+
+            if ext.is_continuation() || chord_pos.is_some() || label.is_some() {
+                // Removed from the text along with adjacent whitespace like a transposition
+                let start = if prefix_space { ws.start() } else { ext_start };
+                let preceding = &text[pos..start];
+                if !preceding.is_empty() {
+                    target.push(Inline::text_at(preceding, text_line));
+                }
+                pos = ext_end;
+                if !prefix_space {
+                    if let Some(c) = text[pos..].chars().next().filter(|c| c.is_whitespace()) {
+                        pos += c.len_utf8();
+                    }
+                }
+
+                match (chord_pos, label) {
+                    (Some(chord_pos), _) => self.ctx.chord_pos.set(chord_pos),
+                    (_, Some(label)) => self.add_label(label, &text[ext_start..ext_end], line),
+                    _ => self.continuation = true,
+                }
+            } else if let Some(name) = ext.try_parse_snippet() {
+                // The snippet replaces just the reference, whitespace around it is kept
+                let preceding = &text[pos..ext_start];
+                if !preceding.is_empty() {
+                    target.push(Inline::text_at(preceding, text_line));
+                }
+                pos = ext_end;
+
+                self.expand_snippet(name, &text[ext_start..ext_end], line, target);
+            } else if let Some(mut inline) = ext.try_parse(&self.ctx.notations) {
+                let valid_chorus_ref = match &inline {
+                    Inline::ChorusRef(cr) => cr.num != Some(0),
+                    Inline::ChorusRefRange(range) => range.is_valid(),
+                    _ => true,
+                };
+                if !valid_chorus_ref {
+                    // Invalid chorus numbers are reported and kept as regular text
+                    let kind = DiagKind::InvalidChorusRef {
+                        reference: text[ext_start..ext_end].into(),
+                    };
+                    self.ctx.report_diag(line, kind);
+                    continue;
+                }
+
+                if let Inline::Directive(d) = &mut inline {
+                    if d.kind == DirectiveKind::Duration && Duration::parse(&d.value).is_none() {
+                        // Invalid duration is reported and kept as regular text
+                        let kind = DiagKind::InvalidDuration {
+                            value: d.value.clone(),
+                        };
+                        self.ctx.report_diag(line, kind);
+                        continue;
+                    }
+
+                    if d.kind == DirectiveKind::Key {
+                        if let Err(kind) = self.ctx.xp().transpose_key(&mut d.value) {
+                            self.ctx.report_diag(line, kind);
+                        }
+                    }
+                }
+
                 // First see if there's regular text preceding the extension
-                let preceding = &text[pos..hit.start()];
+                let start = if prefix_space { ws.start() } else { ext_start };
+                let preceding = &text[pos..start];
                 if !preceding.is_empty() {
-                    target.push(Inline::text(preceding));
+                    target.push(Inline::text_at(preceding, text_line));
                 }
+                pos = ext_end;
 
                 if inline.is_xpose() && !self.ctx.xp().disabled {
                     // Update transposition state and throw the inline away,
                     // we're normally not keeping them in the AST
                     self.ctx.xp_mut().update(inline.unwrap_xpose());
 
-                    // If the extension is first on the line (ie. no leading ws)
-                    // then we should consume the following whitespace char
-                    // (there must be either whitespace or EOL).
-                    if !ext.prefix_space && hit.end() < text.len() {
-                        pos = hit.end() + 1;
-                    } else {
-                        pos = hit.end();
+                    if !prefix_space {
+                        if let Some(c) = text[pos..].chars().next().filter(|c| c.is_whitespace()) {
+                            pos += c.len_utf8();
+                        }
                     }
                 } else {
                     // inline not xpose or xp disabled
                     target.push(inline);
-                    pos = hit.end();
                 }
             }
         }
@@ -624,7 +1034,109 @@ impl<'a> VerseBuilder<'a> {
         // Also add text past the last extension (if any)
         let rest = &text[pos..];
         if !rest.is_empty() {
-            target.push(Inline::text(rest));
+            target.push(Inline::text_at(rest, text_line));
+        }
+    }
+
+    /// Record the `label` of the current paragraph given by the extension `ext`,
+    /// a paragraph can only have one.
+    fn add_label(&mut self, label: &str, ext: &str, line: usize) {
+        let para = self.paragraphs.len();
+        if self.labels.last().map_or(false, |l| l.para == para) {
+            let kind = DiagKind::DuplicateLabel { ext: ext.into() };
+            self.ctx.report_diag(line, kind);
+            return;
+        }
+
+        self.labels.push(LabelExt {
+            para,
+            label: label.into(),
+            ext: ext.into(),
+            line,
+        });
+    }
+
+    /// Whether the current paragraph has a label, see `add_label()`.
+    fn para_labeled(&self) -> bool {
+        self.labels
+            .last()
+            .map_or(false, |l| l.para == self.paragraphs.len())
+    }
+
+    /// Replace the label of the verse by the label found at the start of its first paragraph, if any,
+    /// and return whether it was replaced. Labels of other paragraphs are reported, the verse is labeled already.
+    fn apply_labels(&mut self) -> bool {
+        let mut replaced = false;
+        for label in mem::take(&mut self.labels) {
+            if label.para == 0 {
+                self.label = VerseLabel::Custom(label.label);
+                replaced = true;
+            } else {
+                let kind = DiagKind::DuplicateLabel { ext: label.ext };
+                self.ctx.report_diag(label.line, kind);
+            }
+        }
+        replaced
+    }
+
+    /// Expand the snippet `name`, referenced by `reference` on `line`, into `target`.
+    ///
+    /// Single-line snippets are plain text, though extensions in them are parsed,
+    /// multi-line snippets are parsed as Markdown paragraphs.
+    /// Unknown and recursive references are reported and kept as text.
+    fn expand_snippet(
+        &mut self,
+        name: &str,
+        reference: &str,
+        line: usize,
+        target: &mut Vec<Inline>,
+    ) {
+        let ctx = self.ctx;
+        let kind = match ctx.snippets.get(name) {
+            None => DiagKind::UnknownSnippet { name: name.into() },
+            Some(_) if ctx.snippet_stack.borrow().iter().any(|(n, _)| n == name) => {
+                DiagKind::RecursiveSnippet { name: name.into() }
+            }
+            Some(snippet) => {
+                ctx.snippet_stack.borrow_mut().push((name.into(), line));
+                let snippet = snippet.trim();
+                if snippet.contains('\n') {
+                    self.parse_snippet_md(snippet, target);
+                } else {
+                    self.parse_str(snippet, line, &[], target);
+                }
+                ctx.snippet_stack.borrow_mut().pop();
+                return;
+            }
+        };
+
+        ctx.report_diag(line, kind);
+        target.push(Inline::text_at(reference, ctx.position(line)));
+    }
+
+    /// Parse a multi-line snippet as Markdown, paragraphs are separated by a line break.
+    /// Other blocks are ignored and inline code is kept as text, chords can't be nested
+    /// in the chord the snippet may be in.
+    fn parse_snippet_md(&mut self, snippet: &str, target: &mut Vec<Inline>) {
+        let arena = Arena::new();
+        let config = Parser::comrak_config(self.ctx.smart_punctuation);
+        let root = comrak::parse_document(&arena, snippet, &config);
+        root.preprocess(&arena);
+
+        for (i, para) in root.children().filter(|node| node.is_p()).enumerate() {
+            if i > 0 {
+                target.push(Inline::Break);
+            }
+
+            for node in para.children() {
+                match &node.data.borrow().value {
+                    NodeValue::Code(code) => target.push(Inline::text_at(
+                        &*code.literal,
+                        self.ctx.position(node.source_line()),
+                    )),
+                    _ => self.make_inlines(node, target),
+                }
+            }
         }
     }
 
@@ -645,6 +1157,7 @@ impl<'a> VerseBuilder<'a> {
                 self.parse_text(node, target);
                 return;
             }
+            NodeValue::SoftBreak if self.ctx.soft_breaks == SoftBreaks::Space => Inline::text(" "),
             NodeValue::SoftBreak | NodeValue::LineBreak => Inline::Break,
             NodeValue::HtmlInline(..) => {
                 node.parse_html(target, self.ctx);
@@ -663,12 +1176,19 @@ impl<'a> VerseBuilder<'a> {
                 Inline::Link(link)
             }
             NodeValue::Image(link) => {
-                let img = Image::new(
-                    link.url.clone_bstr(),
-                    node.as_plaintext().into(),
-                    link.title.clone_bstr(),
-                );
-                Inline::Image(img)
+                // Classes come from the title string and from the {.class} list in the alt text
+                let alt = node.as_plaintext();
+                let (title, attr_classes) = split_image_attrs(&alt).unwrap_or((&alt, vec![]));
+                let mut classes: Vec<BStr> = vec![];
+                for class in link.title.split_whitespace().chain(attr_classes) {
+                    if !classes.iter().any(|c| &**c == class) {
+                        classes.push(class.into());
+                    }
+                }
+
+                let line = node.data.borrow().sourcepos.start.line;
+                self.ctx.check_image_classes(line, &classes);
+                Inline::Image(Image::new(link.url.clone_bstr(), title.into(), classes))
             }
             NodeValue::FootnoteReference(..) => return,
 
@@ -681,11 +1201,57 @@ impl<'a> VerseBuilder<'a> {
         target.push(single);
     }
 
+    /// Whether the node is a soft break that's to be joined as a space,
+    /// such a break doesn't end the current chord.
+    fn joins_soft_break(&self, node: AstRef) -> bool {
+        self.ctx.soft_breaks == SoftBreaks::Space && node.is_soft_break()
+    }
+
+    /// Split the last word off the text at the end of `para` for a chord written after it,
+    /// see `ChordPos::After`. Returns the word and the whitespace following it, if any,
+    /// which is to stay after the chord. Only text is split, there's no word
+    /// if `para` ends with anything else, eg. a line break.
+    fn take_preceding_word(para: &mut Vec<Inline>) -> (Option<Inline>, Option<Inline>) {
+        // The text may be split into multiple inlines by extensions or by the MD parser
+        let num_texts = para
+            .iter()
+            .rev()
+            .take_while(|inline| matches!(inline, Inline::Text { .. }))
+            .count();
+        let mut line = None;
+        let text: String = para
+            .drain(para.len() - num_texts..)
+            .map(|inline| match inline {
+                Inline::Text { text, line: l } => {
+                    line = line.or(l);
+                    text
+                }
+                _ => unreachable!(),
+            })
+            .collect();
+
+        let word_end = text.trim_end().len();
+        let word_start = text[..word_end]
+            .char_indices()
+            .rev()
+            .find(|(_, c)| c.is_whitespace())
+            .map_or(0, |(i, c)| i + c.len_utf8());
+
+        if word_start > 0 {
+            para.push(Inline::text_at(&text[..word_start], line));
+        }
+        let word =
+            (word_start < word_end).then(|| Inline::text_at(&text[word_start..word_end], line));
+        let space = (word_end < text.len()).then(|| Inline::text_at(&text[word_end..], line));
+        (word, space)
+    }
+
     fn add_p_inner(&mut self, node: AstRef) {
         assert!(node.is_p());
 
         let mut para: Vec<Inline> = vec![];
         let mut cb = None::<ChordBuilder>;
+        self.para_start = true;
         for c in node.children() {
             let c_data = c.data.borrow();
             if let NodeValue::Code(code) = &c_data.value {
@@ -693,22 +1259,33 @@ impl<'a> VerseBuilder<'a> {
                     cb.finalize(&mut para);
                 }
 
-                let mut new_cb = ChordBuilder::new(code);
+                let mut new_cb = ChordBuilder::new(code, self.ctx.position(c.source_line()));
                 let xp = self.ctx.xp();
+                self.ctx.lint_chord(c.source_line(), &new_cb.chord, &xp);
                 if xp.is_some() {
-                    if let Err(chord) = new_cb.transpose(&xp) {
-                        self.ctx
-                            .report_diag(c.source_line(), DiagKind::Transposition { chord });
+                    if let Err(kind) = new_cb.transpose(&xp) {
+                        self.ctx.report_diag(c.source_line(), kind);
+                    }
+                    if let (Some(notation), Some(_)) = (&xp.alt_notation, &new_cb.alt_chord) {
+                        self.ctx.add_alt_notation(notation);
                     }
                 }
+                new_cb.normalize(&xp, &self.ctx.chord_suffixes);
+                new_cb.parse_root(&xp);
 
                 if new_cb.baseline {
                     // Baseline chords don't take any inlines, finalize right away...
                     new_cb.finalize(&mut para);
+                } else if self.ctx.chord_pos.get() == ChordPos::After {
+                    // The chord takes the preceding word instead of the following inlines
+                    let (word, space) = Self::take_preceding_word(&mut para);
+                    new_cb.inlines.extend(word);
+                    new_cb.finalize(&mut para);
+                    para.extend(space);
                 } else {
                     cb = Some(new_cb);
                 }
-            } else if c.ends_chord() {
+            } else if c.ends_chord() && !self.joins_soft_break(c) {
                 if let Some(cb) = cb.take() {
                     cb.finalize(&mut para);
                 }
@@ -725,12 +1302,19 @@ impl<'a> VerseBuilder<'a> {
                     self.make_inlines(c, &mut para);
                 }
             }
+            self.para_start = false;
         }
 
         if let Some(cb) = cb.take() {
             cb.finalize(&mut para);
         }
 
+        if self.continuation || self.para_labeled() {
+            // `!cont` or a label on a line of its own leaves a leading line break
+            let num_breaks = para.iter().take_while(|i| i.is_break()).count();
+            para.drain(..num_breaks);
+        }
+
         if !para.is_empty() {
             self.paragraphs.push(para.into());
         }
@@ -762,7 +1346,9 @@ impl<'a> VerseBuilder<'a> {
     }
 
     fn finalize(self) -> Verse {
-        Verse::new(self.label, self.paragraphs)
+        let mut verse = Verse::new(self.label, self.paragraphs);
+        verse.continued = self.continued;
+        verse
     }
 }
 
@@ -770,21 +1356,32 @@ impl<'a> VerseBuilder<'a> {
 struct SongBuilder<'a> {
     nodes: &'a [AstRef<'a>],
     title: String,
+    title_inlines: Vec<Inline>,
     subtitles: Vec<BStr>,
+    metadata: Metadata,
     verse: Option<VerseBuilder<'a>>,
     blocks: Vec<Block>,
     verse_num: u32,
+    /// Parsing front or back matter, headings are kept as `Block::Heading`.
+    matter: bool,
     ctx: &'a ParserCtx<'a>,
 }
 
 impl<'a> SongBuilder<'a> {
     fn new(nodes: &'a [AstRef<'a>], ctx: &'a ParserCtx<'a>) -> Self {
         // Read song title or use fallback
-        let (title, nodes) = match nodes.first() {
-            Some(n) if n.is_h(1) => (n.as_plaintext(), &nodes[1..]),
-            _ => (ctx.fallback_title.clone(), nodes),
+        let ((title, title_inlines), nodes) = match nodes.first() {
+            Some(n) if n.is_h(1) => (Self::read_title(n, ctx), &nodes[1..]),
+            _ => {
+                let title = ctx.fallback_title.clone();
+                let inlines = vec![Inline::text(&*title)];
+                ((title, inlines), nodes)
+            }
         };
 
+        // The metadata block may come either right after the title or after the subtitles
+        let (mut metadata, nodes) = Self::read_metadata(nodes, ctx);
+
         // Collect subtitles - H2s following the title (if any)
         let subtitles: Vec<_> = nodes
             .iter()
@@ -793,20 +1390,150 @@ impl<'a> SongBuilder<'a> {
             .collect();
 
         // Shift nodes to the song content
-        let nodes = &nodes[subtitles.len()..];
+        let mut nodes = &nodes[subtitles.len()..];
+        if metadata.is_none() {
+            (metadata, nodes) = Self::read_metadata(nodes, ctx);
+        }
 
         Self {
             nodes,
             title,
+            title_inlines,
             subtitles,
+            metadata: metadata.unwrap_or_default(),
             verse: None,
             blocks: vec![],
             // xp: Transposition::new(ctx.config.notation, ctx.config.xp_disabled),
             verse_num: 0,
+            matter: false,
+            ctx,
+        }
+    }
+
+    /// Append `suffix` to the title of a song variant, see `Parser::parse_variants()`.
+    fn title_suffix(mut self, suffix: &str) -> Self {
+        self.title = format!("{} {}", self.title, suffix);
+        match self.title_inlines.last_mut() {
+            Some(Inline::Text { text, .. }) => *text = format!("{} {}", text, suffix).into(),
+            _ => self
+                .title_inlines
+                .push(Inline::text(format!(" {}", suffix))),
+        }
+        self
+    }
+
+    /// Parse the metadata block if it's the first of `nodes`, returns the metadata, if any,
+    /// and the rest of the nodes. Invalid metadata is reported and left out.
+    fn read_metadata(
+        nodes: &'a [AstRef<'a>],
+        ctx: &ParserCtx,
+    ) -> (Option<Metadata>, &'a [AstRef<'a>]) {
+        let (node, literal) = match nodes.first().and_then(|n| Some((n, n.meta_block()?))) {
+            Some(meta) => meta,
+            None => return (None, nodes),
+        };
+
+        let metadata = match toml::from_str::<toml::Table>(&literal) {
+            Ok(table) => table.into_iter().map(|(k, v)| (k.into(), v)).collect(),
+            Err(err) => {
+                let message = err.message().trim().into();
+                ctx.report_diag(node.source_line(), DiagKind::InvalidMetadata { message });
+                Metadata::new()
+            }
+        };
+
+        // The duration is used if there's no `!duration()` directive, see `finalize()`
+        if let Some(value) = metadata.get("duration") {
+            if Duration::from_toml(value).is_none() {
+                let value = match value {
+                    toml::Value::String(s) => s.as_str().into(),
+                    value => value.to_string().into(),
+                };
+                ctx.report_diag(node.source_line(), DiagKind::InvalidDuration { value });
+            }
+        }
+
+        (Some(metadata), &nodes[1..])
+    }
+
+    /// Builder for front or back matter, there's no title and all the `nodes` are content.
+    fn new_matter(nodes: &'a [AstRef<'a>], ctx: &'a ParserCtx<'a>) -> Self {
+        Self {
+            nodes,
+            title: String::new(),
+            title_inlines: vec![],
+            subtitles: vec![],
+            metadata: Metadata::new(),
+            verse: None,
+            blocks: vec![],
+            verse_num: 0,
+            matter: true,
             ctx,
         }
     }
 
+    /// Read the song title from the `h1` node, both as plain text and as inlines, which include chords.
+    ///
+    /// Chords are left out of the plain text title, or written in parentheses with `title_chords = "parens"`,
+    /// so that they don't end up in the table of contents, sorting, or file names.
+    fn read_title(h1: AstRef, ctx: &ParserCtx) -> (String, Vec<Inline>) {
+        let mut title = String::new();
+        let mut inlines = vec![];
+        let mut has_chords = false;
+        let mut cb = None::<ChordBuilder>;
+        for c in h1.children() {
+            let c_data = c.data.borrow();
+            if let NodeValue::Code(code) = &c_data.value {
+                if let Some(cb) = cb.take() {
+                    cb.finalize(&mut inlines);
+                }
+
+                let mut new_cb = ChordBuilder::new(code, ctx.position(c.source_line()));
+                let xp = ctx.xp();
+                ctx.lint_chord(c.source_line(), &new_cb.chord, &xp);
+                if xp.is_some() {
+                    if let Err(kind) = new_cb.transpose(&xp) {
+                        ctx.report_diag(c.source_line(), kind);
+                    }
+                }
+                new_cb.normalize(&xp, &ctx.chord_suffixes);
+                new_cb.parse_root(&xp);
+
+                has_chords = true;
+                if ctx.title_chords == TitleChords::Parens {
+                    title.push('(');
+                    title.push_str(&new_cb.chord);
+                    title.push(')');
+                }
+
+                if new_cb.baseline {
+                    new_cb.finalize(&mut inlines);
+                } else {
+                    cb = Some(new_cb);
+                }
+            } else {
+                let text = c.as_plaintext();
+                title.push_str(&text);
+                let target = match cb.as_mut() {
+                    Some(cb) => cb.inlines_mut(),
+                    None => &mut inlines,
+                };
+                target.push(Inline::text_at(text, ctx.position(c.source_line())));
+            }
+        }
+
+        if let Some(cb) = cb.take() {
+            cb.finalize(&mut inlines);
+        }
+
+        if has_chords {
+            // Chords are usually surrounded by spaces or in the middle of a word
+            title = title.split_whitespace().collect::<Vec<_>>().join(" ");
+        }
+
+        (title, inlines)
+    }
+
     fn next_verse_num(&mut self) -> u32 {
         self.verse_num += 1;
         self.verse_num
@@ -826,6 +1553,58 @@ impl<'a> SongBuilder<'a> {
         }
     }
 
+    /// Handle a paragraph `node` marked with `!cont`. If it starts a new unlabeled verse,
+    /// ie. the verse before it was interrupted by another block, the new verse continues
+    /// the previous one: it takes over its label and is marked as `continued`.
+    /// A paragraph that's already part of a verse is left as it is.
+    fn continue_verse(&mut self, node: AstRef) {
+        let verse = self.verse.as_mut().unwrap();
+        if verse.label != (VerseLabel::None {}) || verse.paragraphs.len() > 1 {
+            return;
+        }
+
+        let prev = self.blocks.iter().rev().find_map(|block| match block {
+            Block::Verse(prev) => Some(prev),
+            _ => None,
+        });
+        match prev {
+            Some(prev) => {
+                verse.label = prev.label.clone();
+                verse.continued = true;
+            }
+            None => self
+                .ctx
+                .report_diag(node.source_line(), DiagKind::NothingToContinue),
+        }
+    }
+
+    /// Handle a `!label` of a paragraph added to the current verse, which had `num_paras` paragraphs before.
+    /// The labeled paragraph starts a new verse, unless it's the first one of the verse,
+    /// like a paragraph following an H3 heading. A verse that has a label already keeps it.
+    fn label_verse(&mut self, num_paras: usize) {
+        let verse = self.verse.as_mut().unwrap();
+        let label = match verse.labels.pop() {
+            Some(label) => label,
+            None => return,
+        };
+
+        if num_paras > 0 {
+            let paragraphs = verse.paragraphs.split_off(num_paras);
+            self.verse_finalize();
+            let mut verse = VerseBuilder::new(VerseLabel::None {}, self.ctx);
+            verse.paragraphs = paragraphs;
+            self.verse = Some(verse);
+        }
+
+        let verse = self.verse.as_mut().unwrap();
+        if verse.label == (VerseLabel::None {}) {
+            verse.label = VerseLabel::Custom(label.label);
+        } else {
+            let kind = DiagKind::DuplicateLabel { ext: label.ext };
+            self.ctx.report_diag(label.line, kind);
+        }
+    }
+
     fn parse_bq(&mut self, bq: AstRef, level: u32) {
         assert!(bq.is_bq());
 
@@ -847,7 +1626,9 @@ impl<'a> SongBuilder<'a> {
                     self.verse = Some(verse);
                 }
 
-                self.verse_mut().add_p_node(c);
+                let verse = self.verse_mut();
+                verse.add_p_node(c);
+                verse.apply_labels();
             }
         }
     }
@@ -859,7 +1640,15 @@ impl<'a> SongBuilder<'a> {
             }
 
             match &node.data.borrow().value {
-                NodeValue::Paragraph => self.verse_mut().add_p_node(node),
+                NodeValue::Paragraph => {
+                    let verse = self.verse_mut();
+                    let num_paras = verse.paragraphs.len();
+                    verse.add_p_node(node);
+                    if mem::take(&mut verse.continuation) {
+                        self.continue_verse(node);
+                    }
+                    self.label_verse(num_paras);
+                }
 
                 NodeValue::List(list) if matches!(list.list_type, ListType::Ordered) => {
                     for item in node.children() {
@@ -867,7 +1656,12 @@ impl<'a> SongBuilder<'a> {
                         self.verse_finalize();
 
                         let label = VerseLabel::Verse(self.next_verse_num());
-                        let verse = VerseBuilder::with_p_nodes(label, self.ctx, item.children());
+                        let mut verse =
+                            VerseBuilder::with_p_nodes(label, self.ctx, item.children());
+                        if verse.apply_labels() {
+                            // The custom label replaces the number, the next verse gets it
+                            self.verse_num -= 1;
+                        }
                         self.verse = Some(verse);
                     }
                 }
@@ -885,6 +1679,11 @@ impl<'a> SongBuilder<'a> {
 
                 NodeValue::BlockQuote => self.parse_bq(node, 1),
 
+                NodeValue::Heading(h) if self.matter => self.blocks.push(Block::Heading {
+                    level: h.level.into(),
+                    text: node.as_plaintext().into(),
+                }),
+
                 NodeValue::Heading(h) if h.level >= 3 => {
                     let label = VerseLabel::Custom(node.as_plaintext().into());
                     self.verse = Some(VerseBuilder::new(label, self.ctx));
@@ -913,30 +1712,106 @@ impl<'a> SongBuilder<'a> {
         self
     }
 
+    /// Remove directives found before the first verse, ie. in leading paragraphs
+    /// of an unlabeled verse that don't contain anything else, and return them.
+    fn take_leading_directives(&mut self) -> Vec<Directive> {
+        let verse = match self.blocks.first_mut() {
+            Some(Block::Verse(verse)) if verse.label == VerseLabel::None {} => verse,
+            _ => return vec![],
+        };
+
+        let directives_only = |para: &Paragraph| {
+            para.iter().any(Inline::is_directive)
+                && para.iter().all(|inline| match inline {
+                    Inline::Directive(..) | Inline::Break => true,
+                    Inline::Text { text, .. } => text.trim().is_empty(),
+                    _ => false,
+                })
+        };
+
+        let num_leading = verse
+            .paragraphs
+            .iter()
+            .take_while(|para| directives_only(para))
+            .count();
+        verse
+            .paragraphs
+            .drain(..num_leading)
+            .flat_map(|para| para.into_vec())
+            .filter_map(|inline| match inline {
+                Inline::Directive(directive) => Some(directive),
+                _ => None,
+            })
+            .collect()
+    }
+
     fn finalize(mut self) -> Song {
         self.verse_finalize();
 
+        let mut tempo = None;
+        let mut time = None;
+        let mut duration = None;
+        let mut key = None;
+        let mut aliases = vec![];
+        let mut dir = None;
+        for directive in self.take_leading_directives() {
+            match directive.kind {
+                DirectiveKind::Tempo => tempo = Some(directive.value),
+                DirectiveKind::Time => time = Some(directive.value),
+                DirectiveKind::Duration => duration = Duration::parse(&directive.value),
+                DirectiveKind::Key => key = Some(directive.value),
+                DirectiveKind::Alias => aliases.push(directive.value.trim().into()),
+                DirectiveKind::Dir => dir = TextDir::parse(&directive.value),
+            }
+        }
+
         // Chorus labels and chorus references carry a number
         // identifying the chorus. However, if there's just one chorus
         // in the song, we set the number to None, the number would be useless/distracting.
+        // This can be overridden either way with `ChorusNumbers`.
         let max_chorus = self
             .blocks
             .iter()
             .map(|b| b.chorus_num().unwrap_or(0))
             .max()
             .unwrap_or(0);
-        if max_chorus < 2 {
+        let remove_chorus_num = match self.ctx.chorus_numbers {
+            ChorusNumbers::Auto => max_chorus < 2,
+            ChorusNumbers::Always => false,
+            ChorusNumbers::Never => true,
+        };
+        if remove_chorus_num {
             self.blocks.iter_mut().for_each(Block::remove_chorus_num);
         }
 
+        let duration =
+            duration.or_else(|| self.metadata.get("duration").and_then(Duration::from_toml));
+        let dir = dir.unwrap_or_else(|| TextDir::detect(&self.title));
+        // Tests with transposition disabled check the raw structure around extensions
+        let merge_text = !self.ctx.xp().disabled;
         let mut song = Song {
             title: self.title.into(),
+            id: BStr::default(),
+            title_inlines: self.title_inlines.into(),
             subtitles: self.subtitles.into(),
             blocks: self.blocks,
-            notation: self.ctx.xp().src_notation,
+            notation: self.ctx.xp().src_notation.clone(),
+            tempo,
+            time,
+            duration,
+            key,
+            aliases: aliases.into(),
+            metadata: self.metadata,
+            dir,
+            chord_pos: self.ctx.chord_pos.take(),
+            source: None,
+            chords_used: vec![],
+            alt_chords_used: vec![],
+            alt_notations: self.ctx.alt_notations.take().into(),
+            location: SongLocation::default(),
         };
 
-        song.postprocess();
+        song.postprocess(merge_text);
         song
     }
 }
@@ -982,60 +1857,400 @@ impl<'s, 'a> Iterator for SongsIter<'s, 'a> {
     }
 }
 
+/// How soft line breaks (ie. a single newline) in the source are treated
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum SoftBreaks {
+    /// Soft breaks are treated the same as hard breaks
+    Break,
+    /// Soft breaks are joined as a space, the lines are rendered as flowing text
+    Space,
+}
+
+#[allow(clippy::derivable_impls)] // Due to MSRV
+impl Default for SoftBreaks {
+    fn default() -> SoftBreaks {
+        SoftBreaks::Break
+    }
+}
+
+/// Whether chorus labels and references carry the chorus number
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ChorusNumbers {
+    /// Numbers are removed in songs with a single chorus, where they would be distracting
+    Auto,
+    /// Numbers are kept even in songs with a single chorus
+    Always,
+    /// Numbers are removed even in songs with multiple choruses
+    Never,
+}
+
+#[allow(clippy::derivable_impls)] // Due to MSRV
+impl Default for ChorusNumbers {
+    fn default() -> ChorusNumbers {
+        ChorusNumbers::Auto
+    }
+}
+
+/// How chords in song titles are written in the plain text `Song::title`,
+/// the chords are always kept in `Song::title_inlines`
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum TitleChords {
+    /// Chords are left out of the title
+    Drop,
+    /// Chords are written in parentheses, eg. `Medley (G)`
+    Parens,
+}
+
+#[allow(clippy::derivable_impls)] // Due to MSRV
+impl Default for TitleChords {
+    fn default() -> TitleChords {
+        TitleChords::Drop
+    }
+}
+
+/// A variant of a song with its own transposition, configured in the `[variants]` section of `bard.toml`.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct SongVariant {
+    /// Appended to the song's title, eg. `(D)`.
+    pub suffix: String,
+    /// Semitones by which the variant is transposed on top of the song's own transposition.
+    #[serde(default)]
+    pub transpose: i32,
+}
+
+/// Limits on the input checked by the parser, guarding against pathological inputs,
+/// such as generated files, which would otherwise take up excessive time or memory to parse.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct InputLimits {
+    /// Max size of an input file in bytes.
+    pub max_file_size: usize,
+    /// Max length of a line in bytes.
+    pub max_line_length: usize,
+    /// Max number of elements (text, chords, emphasis, etc.) in a single paragraph or another top-level block.
+    pub max_inlines: usize,
+}
+
+impl Default for InputLimits {
+    fn default() -> Self {
+        Self {
+            max_file_size: 16 * 1024 * 1024,
+            max_line_length: 64 * 1024,
+            max_inlines: 10_000,
+        }
+    }
+}
+
+/// The `[chords]` section of the project settings.
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Eq, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChordSettings {
+    /// Replacements of chord suffixes, eg. `"min" = "m"`, see `music::normalize_suffixes()`.
+    pub normalize: BTreeMap<String, String>,
+}
+
 #[derive(Debug)]
 pub struct ParserConfig {
     pub notation: Notation,
+    /// User-defined notations available to notation extensions, eg. `!!solfege`.
+    pub notations: Notations,
+    pub minor_chords: MinorChords,
     pub fallback_title: String,
     pub xp_disabled: bool,
     pub smart_punctuation: bool,
+    pub soft_breaks: SoftBreaks,
+    pub title_chords: TitleChords,
+    pub chorus_numbers: ChorusNumbers,
+    pub include_sources: bool,
+    pub limits: InputLimits,
+    /// Image classes allowed in addition to `Image::KNOWN_CLASSES`, if `None`, classes aren't checked.
+    pub image_classes: Option<Vec<String>>,
+    /// Text snippets referenced with `!use(name)`.
+    pub snippets: BTreeMap<String, String>,
+    /// Variants of songs by their title, see `Parser::parse_variants()`.
+    pub variants: BTreeMap<String, Vec<SongVariant>>,
+    /// Normalize the input to Unicode NFC, so that eg. decomposed accented letters
+    /// don't produce titles or chords that look the same but compare differently.
+    pub normalize_unicode: bool,
+    /// Record source lines on text and chord inlines, see `Inline::line()`.
+    /// Off by default, only tools which point to the source need them.
+    pub record_positions: bool,
+    /// Keep text inside simple tags in HTML blocks on the tags instead of reporting it as ignored,
+    /// see `HtmlTag::text`.
+    pub html_text: bool,
+    /// Replacements of chord suffixes, applied to chords and alt chords, see `ChordSettings::normalize`.
+    pub chord_suffixes: BTreeMap<String, String>,
+    /// Report chords which aren't recognized as warnings even if they aren't transposed,
+    /// except those listed. If `None`, only transposed chords are checked.
+    pub chord_lint: Option<Vec<String>>,
 }
 
 impl ParserConfig {
     pub fn new(notation: Notation, smart_punctuation: bool) -> Self {
         Self {
             notation,
+            notations: Notations::default(),
+            minor_chords: MinorChords::default(),
             fallback_title: FALLBACK_TITLE.into(),
             xp_disabled: false,
             smart_punctuation,
+            soft_breaks: SoftBreaks::default(),
+            title_chords: TitleChords::default(),
+            chorus_numbers: ChorusNumbers::default(),
+            include_sources: false,
+            limits: InputLimits::default(),
+            image_classes: None,
+            snippets: BTreeMap::new(),
+            variants: BTreeMap::new(),
+            normalize_unicode: true,
+            record_positions: false,
+            html_text: false,
+            chord_suffixes: BTreeMap::new(),
+            chord_lint: None,
         }
     }
 
+    pub fn soft_breaks(mut self, soft_breaks: SoftBreaks) -> Self {
+        self.soft_breaks = soft_breaks;
+        self
+    }
+
+    pub fn title_chords(mut self, title_chords: TitleChords) -> Self {
+        self.title_chords = title_chords;
+        self
+    }
+
+    pub fn chorus_numbers(mut self, chorus_numbers: ChorusNumbers) -> Self {
+        self.chorus_numbers = chorus_numbers;
+        self
+    }
+
+    pub fn minor_chords(mut self, minor_chords: MinorChords) -> Self {
+        self.minor_chords = minor_chords;
+        self
+    }
+
     pub fn xp_disabled(mut self, xp_disabled: bool) -> Self {
         self.xp_disabled = xp_disabled;
         self
     }
+
+    pub fn include_sources(mut self, include_sources: bool) -> Self {
+        self.include_sources = include_sources;
+        self
+    }
+
+    pub fn limits(mut self, limits: InputLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn notations(mut self, notations: Notations) -> Self {
+        self.notations = notations;
+        self
+    }
+
+    pub fn image_classes(mut self, image_classes: Option<Vec<String>>) -> Self {
+        self.image_classes = image_classes;
+        self
+    }
+
+    pub fn snippets(mut self, snippets: BTreeMap<String, String>) -> Self {
+        self.snippets = snippets;
+        self
+    }
+
+    pub fn variants(mut self, variants: BTreeMap<String, Vec<SongVariant>>) -> Self {
+        self.variants = variants;
+        self
+    }
+
+    pub fn normalize_unicode(mut self, normalize_unicode: bool) -> Self {
+        self.normalize_unicode = normalize_unicode;
+        self
+    }
+
+    pub fn record_positions(mut self, record_positions: bool) -> Self {
+        self.record_positions = record_positions;
+        self
+    }
+
+    pub fn html_text(mut self, html_text: bool) -> Self {
+        self.html_text = html_text;
+        self
+    }
+
+    pub fn chord_suffixes(mut self, chord_suffixes: BTreeMap<String, String>) -> Self {
+        self.chord_suffixes = chord_suffixes;
+        self
+    }
+
+    pub fn chord_lint(mut self, chord_lint: Option<Vec<String>>) -> Self {
+        self.chord_lint = chord_lint;
+        self
+    }
 }
 
 impl Default for ParserConfig {
     fn default() -> Self {
         Self {
             notation: Notation::default(),
+            notations: Notations::default(),
+            minor_chords: MinorChords::default(),
             fallback_title: FALLBACK_TITLE.into(),
             xp_disabled: false,
             smart_punctuation: true,
+            soft_breaks: SoftBreaks::default(),
+            title_chords: TitleChords::default(),
+            chorus_numbers: ChorusNumbers::default(),
+            include_sources: false,
+            limits: InputLimits::default(),
+            image_classes: None,
+            snippets: BTreeMap::new(),
+            variants: BTreeMap::new(),
+            normalize_unicode: true,
+            record_positions: false,
+            html_text: false,
+            chord_suffixes: BTreeMap::new(),
+            chord_lint: None,
         }
     }
 }
 
 struct ParserCtx<'d> {
+    /// Lines of the input, normalized the same way as the input, see `Parser::new()`.
+    source_lines: Vec<Cow<'d, str>>,
     fallback_title: String,
     xp: RefCell<Transposition>,
+    notations: Notations,
     input_file: PathBuf,
     diag_sink: Box<dyn DiagSink + 'd>,
     error_seen: Cell<bool>,
     smart_punctuation: bool,
+    soft_breaks: SoftBreaks,
+    title_chords: TitleChords,
+    chorus_numbers: ChorusNumbers,
+    include_sources: bool,
+    limits: InputLimits,
+    image_classes: Option<Vec<String>>,
+    snippets: BTreeMap<String, String>,
+    /// Snippets being expanded along with the lines of their references, outermost first.
+    snippet_stack: RefCell<Vec<(String, usize)>>,
+    /// Notations alt chords of the current song were converted to, see `Song::alt_notations`.
+    alt_notations: RefCell<Vec<Notation>>,
+    /// Chord position of the current song, set with `!chordpos()`, see `Song::chord_pos`.
+    chord_pos: Cell<ChordPos>,
+    /// Variants of songs by lowercase title.
+    variants: HashMap<String, Vec<SongVariant>>,
+    /// Building a song variant, the song's diagnostics were reported already,
+    /// only transposition errors are new, see `Parser::parse_variants()`.
+    in_variant: Cell<bool>,
+    record_positions: bool,
+    html_text: bool,
+    chord_suffixes: BTreeMap<String, String>,
+    chord_lint: Option<Vec<String>>,
 }
 
 impl<'d> ParserCtx<'d> {
-    fn new(config: ParserConfig, input_file: &Path, diag_sink: Box<dyn DiagSink + 'd>) -> Self {
+    fn new(
+        input: &'d str,
+        config: ParserConfig,
+        input_file: &Path,
+        diag_sink: Box<dyn DiagSink + 'd>,
+    ) -> Self {
         Self {
+            source_lines: input
+                .lines()
+                .map(|line| nfc(line, config.normalize_unicode))
+                .collect(),
             fallback_title: config.fallback_title,
-            xp: RefCell::new(Transposition::new(config.notation, config.xp_disabled)),
+            xp: RefCell::new(Transposition::new(
+                config.notation,
+                config.minor_chords,
+                config.xp_disabled,
+            )),
+            notations: config.notations,
             input_file: input_file.to_owned(),
             diag_sink,
             error_seen: Cell::new(false),
             smart_punctuation: config.smart_punctuation,
+            soft_breaks: config.soft_breaks,
+            title_chords: config.title_chords,
+            chorus_numbers: config.chorus_numbers,
+            include_sources: config.include_sources,
+            limits: config.limits,
+            image_classes: config.image_classes,
+            snippets: config.snippets,
+            snippet_stack: RefCell::new(vec![]),
+            alt_notations: RefCell::new(vec![]),
+            chord_pos: Cell::new(ChordPos::default()),
+            variants: config
+                .variants
+                .into_iter()
+                .map(|(title, variants)| (title.trim().to_lowercase(), variants))
+                .collect(),
+            in_variant: Cell::new(false),
+            record_positions: config.record_positions,
+            html_text: config.html_text,
+            chord_suffixes: config.chord_suffixes,
+            chord_lint: config.chord_lint,
+        }
+    }
+
+    fn add_alt_notation(&self, notation: &Notation) {
+        let mut alt_notations = self.alt_notations.borrow_mut();
+        if !alt_notations.contains(notation) {
+            alt_notations.push(notation.clone());
+        }
+    }
+
+    /// Find which `!` chars in a text node were escaped with a backslash in the source.
+    ///
+    /// Comrak removes the escaping backslashes, so this is recovered from the node's source position.
+    /// Returns byte offsets of the escaped `!` chars in `text`. If the source can't be
+    /// matched up with the text, no escapes are reported, nor are they in snippets,
+    /// whose source positions don't refer to the input.
+    fn escaped_excls(&self, sourcepos: &Sourcepos, text: &str) -> Vec<usize> {
+        let (start, end) = (sourcepos.start, sourcepos.end);
+        if start.line != end.line || !text.contains('!') || self.in_snippet() {
+            return vec![];
         }
+
+        let source = self
+            .source_lines
+            .get(start.line.wrapping_sub(1))
+            .and_then(|line| line.get(start.column.saturating_sub(1)..end.column));
+        let source = match source {
+            Some(source) => source,
+            None => return vec![],
+        };
+
+        let mut source_excls = vec![];
+        let mut chars = source.chars();
+        while let Some(c) = chars.next() {
+            if c == '\\' {
+                // The escaped char is skipped in any case
+                if chars.next() == Some('!') {
+                    source_excls.push(true);
+                }
+            } else if c == '!' {
+                source_excls.push(false);
+            }
+        }
+
+        let text_excls: Vec<_> = text.match_indices('!').map(|(i, _)| i).collect();
+        if text_excls.len() != source_excls.len() {
+            return vec![];
+        }
+
+        text_excls
+            .into_iter()
+            .zip(source_excls)
+            .filter_map(|(i, escaped)| escaped.then_some(i))
+            .collect()
     }
 
     fn xp(&self) -> Ref<'_, Transposition> {
@@ -1046,7 +2261,70 @@ impl<'d> ParserCtx<'d> {
         self.xp.borrow_mut()
     }
 
+    /// Report image classes which are neither known nor listed in `image_classes`, if set.
+    fn check_image_classes(&self, line: usize, classes: &[BStr]) {
+        let allowed = match self.image_classes.as_ref() {
+            Some(allowed) => allowed,
+            None => return,
+        };
+
+        for class in classes.iter() {
+            if !Image::KNOWN_CLASSES.contains(&&**class) && !allowed.iter().any(|a| **a == **class)
+            {
+                let kind = DiagKind::UnknownImageClass {
+                    class: class.clone(),
+                };
+                self.report_diag(line, kind);
+            }
+        }
+    }
+
+    /// Report the first chord of `chord_set` which isn't recognized and isn't allowed by `chord_lint`, if set.
+    /// Chords which are transposed are checked by the transposition already.
+    fn lint_chord(&self, line: usize, chord_set: &str, xp: &Transposition) {
+        let allowed = match self.chord_lint.as_ref() {
+            Some(allowed) => allowed,
+            None => return,
+        };
+        if xp.is_some() && !xp.disabled {
+            return;
+        }
+
+        let unrecognized = music::unrecognized_chords(chord_set, &xp.src_notation)
+            .into_iter()
+            .find(|chord| !allowed.iter().any(|a| a.trim() == *chord));
+        if let Some(chord) = unrecognized {
+            let kind = DiagKind::UnrecognizedChord {
+                chord: chord.into(),
+                notation: xp.src_notation.clone(),
+            };
+            self.report_diag(line, kind);
+        }
+    }
+
+    fn in_snippet(&self) -> bool {
+        !self.snippet_stack.borrow().is_empty()
+    }
+
+    /// The source line of `line`, in a snippet, it's the line of the outermost reference.
+    fn source_line(&self, line: usize) -> usize {
+        self.snippet_stack
+            .borrow()
+            .first()
+            .map_or(line, |&(_, line)| line)
+    }
+
+    /// The line to record on an inline at `line`, if `record_positions` is set, see `Inline::line()`.
+    fn position(&self, line: usize) -> Option<u32> {
+        self.record_positions.then(|| self.source_line(line) as u32)
+    }
+
+    /// Report a diagnostic on `line`, in a snippet, the line of the outermost reference is reported.
     fn report_diag(&self, line: usize, kind: DiagKind) {
+        let line = self.source_line(line);
+        if self.in_variant.get() && !matches!(kind, DiagKind::Transposition { .. }) {
+            return;
+        }
         if kind.is_error() {
             self.error_seen.set(true);
         }
@@ -1078,21 +2356,36 @@ impl<'d> fmt::Debug for ParserCtx<'d> {
     }
 }
 
+/// Compose `text` into Unicode NFC if `normalize` is set, the text is borrowed if unchanged.
+fn nfc(text: &str, normalize: bool) -> Cow<'_, str> {
+    let normalizer = ComposingNormalizer::new_nfc();
+    if normalize && !normalizer.is_normalized(text) {
+        Cow::Owned(normalizer.normalize(text))
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
 #[derive(Debug)]
 pub struct Parser<'i, 'd> {
-    input: &'i str,
+    input: Cow<'i, str>,
     ctx: ParserCtx<'d>,
 }
 
-impl<'i, 'd> Parser<'i, 'd> {
+impl<'i: 'd, 'd> Parser<'i, 'd> {
     pub fn new(
         input: &'i str,
         input_file: &Path,
         config: ParserConfig,
         diagnostic_sink: impl DiagSink + 'd,
     ) -> Self {
-        let ctx = ParserCtx::new(config, input_file, Box::new(diagnostic_sink));
-        Self { input, ctx }
+        // NFC never composes across line breaks, so the ctx can normalize line by line
+        let normalized = nfc(input, config.normalize_unicode);
+        let ctx = ParserCtx::new(input, config, input_file, Box::new(diagnostic_sink));
+        Self {
+            input: normalized,
+            ctx,
+        }
     }
 
     fn comrak_config(smart_punctuation: bool) -> ComrakOptions {
@@ -1123,7 +2416,106 @@ impl<'i, 'd> Parser<'i, 'd> {
         self.ctx.diag_result(())
     }
 
-    /// Parsing is done in four steps:
+    /// Warn about the input not being in Unicode NFC, only the first normalized line is reported.
+    fn check_normalized(&self) {
+        let normalized = self
+            .ctx
+            .source_lines
+            .iter()
+            .position(|line| matches!(line, Cow::Owned(..)));
+        if let Some(num) = normalized {
+            self.ctx.report_diag(num + 1, DiagKind::NotNormalized);
+        }
+    }
+
+    /// Verify the input size and line lengths are within limits.
+    /// Only the first line that is too long is reported.
+    fn check_size_limits(&mut self) -> Result<()> {
+        let limits = self.ctx.limits;
+        if self.input.len() > limits.max_file_size {
+            let kind = DiagKind::FileTooLarge {
+                size: self.input.len(),
+                limit: limits.max_file_size,
+            };
+            self.ctx.report_diag(1, kind);
+            return Err(());
+        }
+
+        let long_line = self
+            .input
+            .lines()
+            .enumerate()
+            .find(|(_, line)| line.len() > limits.max_line_length);
+        if let Some((num, line)) = long_line {
+            let kind = DiagKind::LineTooLong {
+                length: line.len(),
+                limit: limits.max_line_length,
+            };
+            self.ctx.report_diag(num + 1, kind);
+            return Err(());
+        }
+
+        Ok(())
+    }
+
+    /// Verify top-level blocks don't contain too many nodes, this is done before preprocessing,
+    /// which may split the nodes further. Only the first block over the limit is reported.
+    fn check_inlines_limit(&self, root_elems: &[AstRef]) -> Result<()> {
+        let limit = self.ctx.limits.max_inlines;
+        let over_limit = root_elems
+            .iter()
+            .find(|node| node.descendants().skip(1).nth(limit).is_some());
+        if let Some(node) = over_limit {
+            let line = node.data.borrow().sourcepos.start.line;
+            self.ctx
+                .report_diag(line, DiagKind::TooManyInlines { limit });
+            return Err(());
+        }
+
+        Ok(())
+    }
+
+    /// Byte offsets of the starts of lines in the input, line endings are the same as in comrak,
+    /// ie. LF, CRLF, or a lone CR.
+    fn line_offsets(&self) -> Vec<usize> {
+        let bytes = self.input.as_bytes();
+        let mut offsets = vec![0];
+        for (i, &b) in bytes.iter().enumerate() {
+            let line_end = b == b'\n' || (b == b'\r' && bytes.get(i + 1) != Some(&b'\n'));
+            if line_end && i + 1 < bytes.len() {
+                offsets.push(i + 1);
+            }
+        }
+        offsets
+    }
+
+    /// Split the input into source text of individual songs.
+    ///
+    /// Each song starts on the line of its first node, the first song starts
+    /// at the beginning of the input, ie. concatenating the sources yields the whole input.
+    fn song_sources(&self, root_elems: &[AstRef]) -> Vec<BStr> {
+        let line_offsets = self.line_offsets();
+        let mut starts: Vec<_> = SongsIter::new(root_elems)
+            .skip(1)
+            .map(|nodes| {
+                let line = nodes[0].data.borrow().sourcepos.start.line;
+                line_offsets
+                    .get(line.wrapping_sub(1))
+                    .copied()
+                    .unwrap_or(self.input.len())
+            })
+            .collect();
+        starts.insert(0, 0);
+
+        let ends = starts.iter().skip(1).copied().chain([self.input.len()]);
+        starts
+            .iter()
+            .zip(ends)
+            .map(|(&start, end)| self.input[start..end].into())
+            .collect()
+    }
+
+    /// Parsing is done in four steps, after the input is checked against `InputLimits`:
     ///
     /// 1. Split the source AST in individual songs (they are separated by H1s),
     ///    this is done by `SongIter`.
@@ -1147,24 +2539,104 @@ impl<'i, 'd> Parser<'i, 'd> {
     /// See the `book` module where the bard AST is defined.
     #[allow(clippy::result_unit_err)]
     pub fn parse(&mut self) -> Result<Vec<Song>> {
+        self.check_size_limits()?;
         self.check_control_chars()?;
+        self.check_normalized();
 
         let arena = Arena::new();
         let config = Self::comrak_config(self.ctx.smart_punctuation);
-        let root = comrak::parse_document(&arena, self.input, &config);
+        let root = comrak::parse_document(&arena, &self.input, &config);
         let root_elems: Vec<_> = root.children().collect();
+        self.check_inlines_limit(&root_elems)?;
+        let mut sources = if self.ctx.include_sources {
+            self.song_sources(&root_elems)
+        } else {
+            vec![]
+        }
+        .into_iter();
+
         let songs_iter = SongsIter::new(&root_elems);
         let songs = Vec::with_capacity(songs_iter.size_hint().0);
         let songs = songs_iter.fold(songs, |mut songs, nodes| {
             nodes.iter().for_each(|node| node.preprocess(&arena));
 
-            let song = SongBuilder::new(nodes, &self.ctx);
-            songs.push(song.parse().finalize());
+            let line = nodes.first().map_or(1, |node| node.source_line());
+            let xp = self.ctx.xp().clone();
+            let mut song = SongBuilder::new(nodes, &self.ctx).parse().finalize();
+            song.source = sources.next();
+            song.location = SongLocation::new(&self.ctx.input_file, line);
+            let variants = self.parse_variants(nodes, &song, &xp);
+            songs.push(song);
+            songs.extend(variants);
             songs
         });
 
         self.ctx.diag_result(songs)
     }
+
+    /// Build the variants of `song` configured with `ParserConfig::variants()` out of the song's `nodes`.
+    ///
+    /// The Markdown is parsed just once, the song content is built again for each variant,
+    /// starting from `xp`, the transposition state at the start of the song, with the variant's offset.
+    /// Aliases are only kept in the original song.
+    fn parse_variants<'a>(
+        &'a self,
+        nodes: &'a [AstRef<'a>],
+        song: &Song,
+        xp: &Transposition,
+    ) -> Vec<Song> {
+        let variants = match self.ctx.variants.get(&song.title.trim().to_lowercase()) {
+            Some(variants) => variants,
+            None => return vec![],
+        };
+
+        let xp_after = self.ctx.xp().clone();
+        self.ctx.in_variant.set(true);
+        let variants = variants
+            .iter()
+            .map(|variant| {
+                *self.ctx.xp_mut() = xp.with_offset(variant.transpose);
+                let mut variant = SongBuilder::new(nodes, &self.ctx)
+                    .title_suffix(&variant.suffix)
+                    .parse()
+                    .finalize();
+                variant.aliases = Box::new([]);
+                variant.source = song.source.clone();
+                variant.location = song.location.clone();
+                variant
+            })
+            .collect();
+        self.ctx.in_variant.set(false);
+        *self.ctx.xp_mut() = xp_after;
+
+        variants
+    }
+
+    /// Parse front or back matter, the input is not split into songs, headings of all levels
+    /// are kept as `Block::Heading`. Directives before the first verse are dropped,
+    /// they only apply to songs.
+    #[allow(clippy::result_unit_err)]
+    pub fn parse_matter(&mut self) -> Result<Matter> {
+        self.check_size_limits()?;
+        self.check_control_chars()?;
+        self.check_normalized();
+
+        let arena = Arena::new();
+        let config = Self::comrak_config(self.ctx.smart_punctuation);
+        let root = comrak::parse_document(&arena, &self.input, &config);
+        let root_elems: Vec<_> = root.children().collect();
+        self.check_inlines_limit(&root_elems)?;
+        root_elems.iter().for_each(|node| node.preprocess(&arena));
+
+        let song = SongBuilder::new_matter(&root_elems, &self.ctx)
+            .parse()
+            .finalize();
+        let matter = Matter {
+            blocks: song.blocks.into(),
+        };
+
+        self.ctx.diag_result(matter)
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,270 @@
+//! ChordPro input, converted to bard Markdown before parsing.
+//!
+//! Each ChordPro line is converted to exactly one Markdown line, so that the line numbers
+//! of diagnostics reported by the `Parser` refer to the ChordPro file as well.
+//! Directives that have no Markdown equivalent are left out with a warning.
+
+use super::{DiagKind, DiagSink, Diagnostic};
+use crate::prelude::*;
+
+/// File extensions of ChordPro files.
+pub static EXTENSIONS: &[&str] = &["cho", "chordpro"];
+
+/// Whether `path` is a ChordPro file judging by its extension.
+pub fn is_chordpro(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map_or(false, |ext| {
+            EXTENSIONS.iter().any(|e| e.eq_ignore_ascii_case(ext))
+        })
+}
+
+/// Convert the ChordPro `input` into bard Markdown to be parsed by the `Parser`,
+/// unsupported directives are reported to `diag_sink` as warnings.
+pub fn to_markdown(input: &str, input_file: &Path, diag_sink: &dyn DiagSink) -> String {
+    let mut conv = Converter::default();
+    for (num, line) in input.lines().enumerate() {
+        if let Err(directive) = conv.line(line) {
+            diag_sink.report(Diagnostic {
+                file: input_file.to_owned(),
+                line: num + 1,
+                kind: DiagKind::ChordProDirective {
+                    directive: directive.into(),
+                },
+            });
+        }
+    }
+
+    let mut md = conv.lines.join("\n");
+    md.push('\n');
+    md
+}
+
+#[derive(Default, Debug)]
+struct Converter {
+    lines: Vec<String>,
+    in_chorus: bool,
+    in_tab: bool,
+    /// Index of a verse label written for a comment, if no lyrics followed it yet.
+    pending_label: Option<usize>,
+    /// Whether the last line is a line of lyrics, which the next one is joined to with a hard break.
+    after_lyrics: bool,
+}
+
+impl Converter {
+    /// Convert one line, returns the directive if it isn't supported.
+    fn line<'l>(&mut self, line: &'l str) -> Result<(), &'l str> {
+        let trimmed = line.trim();
+        if self.in_tab {
+            if let Some((name, _)) = parse_directive(trimmed) {
+                if matches!(name.as_str(), "end_of_tab" | "eot") {
+                    self.in_tab = false;
+                    self.push("```".into());
+                    return Ok(());
+                }
+            }
+            self.push(line.into());
+            return Ok(());
+        }
+
+        if trimmed.starts_with('#') {
+            // A ChordPro comment, not meant to be rendered
+            self.push(String::new());
+            return Ok(());
+        }
+
+        let (name, value) = match parse_directive(trimmed) {
+            Some(directive) => directive,
+            None => {
+                self.lyrics(trimmed);
+                return Ok(());
+            }
+        };
+
+        let md = match name.as_str() {
+            "title" | "t" => format!("# {}", escape(value)),
+            "subtitle" | "st" => format!("## {}", escape(value)),
+            "key" | "tempo" | "time" | "duration" => format!("!{}({})", name, value),
+            "comment" | "c" | "comment_italic" | "ci" | "comment_box" | "cb" => {
+                self.pending_label = Some(self.lines.len());
+                format!("### {}", escape(value))
+            }
+            "start_of_verse" | "sov" if !value.is_empty() => {
+                self.pending_label = Some(self.lines.len());
+                format!("### {}", escape(value))
+            }
+            "start_of_chorus" | "soc" => {
+                // The chorus has its own label
+                if let Some(label) = self.pending_label.take() {
+                    self.lines[label].clear();
+                }
+                self.in_chorus = true;
+                String::new()
+            }
+            "end_of_chorus" | "eoc" => {
+                self.in_chorus = false;
+                String::new()
+            }
+            "chorus" => "!>".into(),
+            "start_of_tab" | "sot" => {
+                self.in_tab = true;
+                "```".into()
+            }
+            "start_of_verse" | "sov" | "end_of_verse" | "eov" | "new_song" | "ns" => String::new(),
+            _ => {
+                self.push(String::new());
+                return Err(trimmed);
+            }
+        };
+        self.push(md);
+        Ok(())
+    }
+
+    fn lyrics(&mut self, line: &str) {
+        if line.is_empty() {
+            self.push(if self.in_chorus { ">" } else { "" }.into());
+            return;
+        }
+
+        let mut md = String::with_capacity(line.len() + 8);
+        let mut rest = line;
+        while let Some(start) = rest.find('[') {
+            let end = match rest[start..].find(']') {
+                Some(end) => start + end,
+                None => break,
+            };
+            md.push_str(&escape(&rest[..start]));
+            md.push('`');
+            md.push_str(rest[start + 1..end].trim());
+            md.push('`');
+            rest = &rest[end + 1..];
+        }
+        md.push_str(&escape(rest));
+        escape_line_start(&mut md);
+        if self.in_chorus {
+            md.insert_str(0, "> ");
+        } else if !self.after_lyrics && self.pending_label.is_none() {
+            // Verses are numbered, a paragraph on its own would continue the previous verse
+            md.insert_str(0, "1. ");
+        }
+
+        // Lines of a paragraph are separated by hard breaks
+        if self.after_lyrics {
+            self.lines.last_mut().unwrap().push('\\');
+        }
+        self.pending_label = None;
+        self.lines.push(md);
+        self.after_lyrics = true;
+    }
+
+    fn push(&mut self, line: String) {
+        self.lines.push(line);
+        self.after_lyrics = false;
+    }
+}
+
+/// Split a `{name: value}` or `{name}` directive into the lowercase name and the value.
+fn parse_directive(line: &str) -> Option<(String, &str)> {
+    let inner = line.strip_prefix('{')?.strip_suffix('}')?;
+    let (name, value) = match inner.find(|c: char| c == ':' || c.is_whitespace()) {
+        Some(pos) => (&inner[..pos], inner[pos + 1..].trim_start_matches(':')),
+        None => (inner, ""),
+    };
+    Some((name.trim().to_ascii_lowercase(), value.trim()))
+}
+
+/// Escape characters of `text` which have a meaning in bard Markdown.
+fn escape(text: &str) -> String {
+    let mut res = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(
+            c,
+            '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '!' | '&' | '~' | '|'
+        ) {
+            res.push('\\');
+        }
+        res.push(c);
+    }
+    res
+}
+
+/// Escape a list item or heading marker at the start of `md`, eg. `1.` or `-`,
+/// so that the line stays a regular line of lyrics.
+fn escape_line_start(md: &mut String) {
+    let digits = md.find(|c: char| !c.is_ascii_digit()).unwrap_or(0);
+    if digits > 0 && md[digits..].starts_with(['.', ')']) {
+        md.insert(digits, '\\');
+    } else if md.starts_with(['-', '+', '=', '#']) {
+        md.insert(0, '\\');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use super::*;
+
+    #[test]
+    fn chordpro_to_markdown() {
+        let input = r#"{title: Song}
+{st: Subtitle}
+{tempo: 120}
+
+# Comment
+[G]Lyrics *with* [C] chords
+1. Not a list
+
+{c: Intro}
+[Am]
+
+{soc}
+Chorus
+
+Chorus
+{eoc}
+{chorus}
+{sot}
+  e|--3--|
+{eot}
+{capo: 2}
+"#;
+
+        let (tx, rx) = mpsc::channel();
+        let md = to_markdown(input, Path::new("song.cho"), &tx);
+        let expected = r#"# Song
+## Subtitle
+!tempo(120)
+
+
+1. `G`Lyrics \*with\* `C` chords\
+1\. Not a list
+
+### Intro
+`Am`
+
+
+> Chorus
+>
+> Chorus
+
+!>
+```
+  e|--3--|
+```
+
+"#;
+        assert_eq!(md, expected);
+        assert_eq!(md.lines().count(), input.lines().count());
+
+        let diags: Vec<_> = rx.try_iter().collect();
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0].line, 21);
+        assert_eq!(
+            diags[0].kind,
+            DiagKind::ChordProDirective {
+                directive: "{capo: 2}".into()
+            }
+        );
+    }
+}
@@ -18,8 +18,33 @@ use crate::util::BStr;
 
 pub static RESERVED_TAGS: &[&str] = &["html", "tex"];
 
+/// Simple tags whose text is kept with `ParserConfig::html_text`,
+/// so that outputs which don't render HTML can render the text as such.
+pub static TEXT_TAGS: &[&str] = &[
+    "span", "div", "p", "small", "big", "b", "i", "em", "strong", "u", "s", "sub", "sup", "mark",
+    "abbr", "cite", "q", "del", "ins", "center", "font",
+];
+
+/// Tags which are never closed in HTML.
+static VOID_TAGS: &[&str] = &["br", "hr", "img", "wbr"];
+
+/// Collapse whitespace runs in `text` into single spaces, the way HTML renders text.
+fn collapse_whitespace(text: &str) -> String {
+    let mut res = String::with_capacity(text.len());
+    for c in text.chars() {
+        if !c.is_whitespace() {
+            res.push(c);
+        } else if !res.ends_with(' ') {
+            res.push(' ');
+        }
+    }
+    res
+}
+
 struct Sink<'c> {
     inlines: Vec<HtmlTag>,
+    /// Names of the currently open tags, innermost last, used to find out which text is kept.
+    open_tags: Vec<String>,
     start_line: usize,
     text_buffer: String,
     text_start_line: usize,
@@ -30,6 +55,7 @@ impl<'c> Sink<'c> {
     fn new(start_line: usize, ctx: &'c ParserCtx<'c>) -> Self {
         Self {
             inlines: vec![],
+            open_tags: vec![],
             start_line,
             text_buffer: String::new(),
             text_start_line: 0,
@@ -38,6 +64,22 @@ impl<'c> Sink<'c> {
     }
 
     fn append_tag(&mut self, tag: Tag, line_num: u64) {
+        match (tag.kind, tag.self_closing) {
+            (TagKind::StartTag, false) if !VOID_TAGS.contains(&&*tag.name) => {
+                self.open_tags.push(tag.name.to_string());
+            }
+            (TagKind::EndTag, _) => {
+                if let Some(pos) = self
+                    .open_tags
+                    .iter()
+                    .rposition(|t| t.as_str() == &*tag.name)
+                {
+                    self.open_tags.truncate(pos);
+                }
+            }
+            _ => {}
+        }
+
         if RESERVED_TAGS.contains(&tag.name.to_ascii_lowercase().as_ref()) {
             self.ctx.report_diag(
                 line_num as _,
@@ -65,18 +107,31 @@ impl<'c> Sink<'c> {
             })
             .collect();
 
-        let tag = HtmlTag { name, attrs };
+        let tag = HtmlTag {
+            name,
+            attrs,
+            text: None,
+        };
         self.inlines.push(tag);
     }
 
     fn append_text(&mut self, text: &str, line_num: usize) {
         // Text within HTML blocks is ignored, but it is accumulated here
         // so that a warning can be emitted.
+        // With `html_text`, text within simple tags is kept on the preceding tag instead.
 
-        let text = text.trim();
-        if text.is_empty() {
+        if text.trim().is_empty() {
             return;
         }
+        if self.keeps_text() {
+            if let Some(tag) = self.inlines.last_mut() {
+                let joined = format!("{}{}", tag.text.as_deref().unwrap_or(""), text);
+                tag.text = Some(collapse_whitespace(&joined).into());
+                return;
+            }
+        }
+
+        let text = text.trim();
 
         if self.text_buffer.is_empty() {
             self.text_start_line = line_num;
@@ -84,6 +139,15 @@ impl<'c> Sink<'c> {
         self.text_buffer.push_str(text);
     }
 
+    fn keeps_text(&self) -> bool {
+        self.ctx.html_text
+            && self
+                .open_tags
+                .last()
+                .map(|tag| TEXT_TAGS.contains(&tag.as_str()))
+                .unwrap_or(false)
+    }
+
     fn ignored_text_warn(&mut self) {
         if self.text_buffer.is_empty() {
             return;
@@ -9,7 +9,7 @@ use super::*;
 
 // Parsing helpers
 struct TetsParser<'a> {
-    parser: Parser<'a, 'static>,
+    parser: Parser<'a, 'a>,
     rx: mpsc::Receiver<Diagnostic>,
 }
 
@@ -35,9 +35,9 @@ impl<'a> TetsParser<'a> {
 
     fn parse_one_para(self) -> Paragraph {
         let blocks = self.parse_one().blocks;
-        let block = Vec::from(blocks).drain(..).next().unwrap();
+        let block = blocks.into_iter().next().unwrap();
         match block {
-            Block::Verse(v) => Vec::from(v.paragraphs).drain(..).next().unwrap(),
+            Block::Verse(v) => v.paragraphs.into_iter().next().unwrap(),
             _ => panic!("First block in this Song isn't a Verse"),
         }
     }
@@ -92,20 +92,62 @@ fn song(
 
     json!({
         "title": title.as_ref(),
+        "id": "",
+        "title_inlines": [i_text(title.as_ref())],
         "subtitles": subtitles,
         "notation": notation.as_ref(),
-        "blocks": blocks.into_iter().collect::<Vec<_>>(),
+        "blocks": index_blocks(blocks),
+        "tempo": Null,
+        "time": Null,
+        "duration": Null,
+        "key": Null,
+        "aliases": [],
+        "metadata": {},
+        "dir": "ltr",
+        "chord_pos": "before",
+        "source": Null,
+        "chords_used": [],
+        "alt_chords_used": [],
     })
 }
 
+/// Add the `first_verse` and `last_verse` flags to verses in `blocks`.
+fn verse_flags(blocks: impl IntoIterator<Item = Json>) -> Vec<Json> {
+    let mut blocks: Vec<_> = blocks.into_iter().collect();
+    let verses: Vec<_> = (0..blocks.len())
+        .filter(|&i| blocks[i]["type"] == "b-verse")
+        .collect();
+    for &i in verses.iter() {
+        blocks[i]["first_verse"] = json!(verses.first() == Some(&i));
+        blocks[i]["last_verse"] = json!(verses.last() == Some(&i));
+    }
+    blocks
+}
+
+/// Blocks of a song as serialized with `Song`, ie. with verse flags and the `block_index` of each block.
+fn index_blocks(blocks: impl IntoIterator<Item = Json>) -> Vec<Json> {
+    let mut blocks = verse_flags(blocks);
+    for (i, block) in blocks.iter_mut().enumerate() {
+        block["block_index"] = json!(i);
+    }
+    blocks
+}
+
 fn b_verse(typ: &str, label: impl Serialize, paras: impl IntoIterator<Item = Json>) -> Json {
     json!({
         "type": "b-verse",
         "label": { typ: label },
         "paragraphs": paras.into_iter().collect::<Vec<_>>(),
+        "continued": false,
     })
 }
 
+/// Mark a verse as continuing the previous one.
+fn continued(mut verse: Json) -> Json {
+    verse["continued"] = json!(true);
+    verse
+}
+
 fn ver_verse(label: u32, paras: impl IntoIterator<Item = Json>) -> Json {
     b_verse("verse", label, paras)
 }
@@ -151,10 +193,27 @@ fn b_html(inlines: impl IntoIterator<Item = Json>) -> Json {
     })
 }
 
+fn b_heading(level: u32, text: &str) -> Json {
+    json!({
+        "type": "b-heading",
+        "level": level,
+        "text": text,
+    })
+}
+
 fn i_text(text: impl AsRef<str>) -> Json {
     json!({ "type": "i-text", "text": text.as_ref() })
 }
 
+fn i_directive(kind: &str, value: &str, prefix_space: &str) -> Json {
+    json!({
+        "type": "i-directive",
+        "kind": kind,
+        "value": value,
+        "prefix_space": prefix_space,
+    })
+}
+
 fn i_break() -> Json {
     json!({ "type": "i-break" })
 }
@@ -189,16 +248,20 @@ impl TestChordInlines for Baseline {
     }
 }
 
+/// A chord inline, its root and suffix are those of `chord` in the English notation.
 fn i_chord(
     chord: &str,
     alt_chord: impl Serialize,
     backticks: u32,
     inlines: impl TestChordInlines,
 ) -> Json {
+    let root = music::chord_root(chord, &Notation::English);
     json!({
         "type": "i-chord",
         "chord": chord,
         "alt_chord": alt_chord,
+        "root_chromatic": root.map(|(root, _)| u8::from(root)),
+        "suffix": root.map(|(_, suffix)| suffix),
         "backticks": backticks,
         "baseline": inlines.baseline(),
         "inlines": inlines.inlines(),
@@ -221,6 +284,10 @@ fn i_chorus_ref(num: impl Serialize, prefix_space: &str) -> Json {
     json!({ "type": "i-chorus-ref", "num": num, "prefix_space": prefix_space })
 }
 
+fn i_chorus_ref_range(start: u32, end: u32, prefix_space: &str) -> Json {
+    json!({ "type": "i-chorus-ref-range", "start": start, "end": end, "prefix_space": prefix_space })
+}
+
 fn i_link(text: &str, url: &str, title: &str) -> Json {
     json!({
         "type": "i-link",
@@ -230,12 +297,13 @@ fn i_link(text: &str, url: &str, title: &str) -> Json {
     })
 }
 
-fn i_image(path: &str, title: &str, class: &str) -> Json {
+fn i_image(path: &str, title: &str, classes: &[&str]) -> Json {
     json!({
         "type": "i-image",
         "path": path,
         "title": title,
-        "class": class,
+        "class": classes.join(" "),
+        "classes": classes,
         "width": 0,
         "height": 0,
     })
@@ -259,7 +327,7 @@ Lyrics lyrics...
 Lyrics lyrics...
     "#;
 
-    let songs = parse(&input, false);
+    let songs = parse(input, false);
 
     assert_eq!(songs.len(), 3);
     assert_eq!(&*songs[0].title, FALLBACK_TITLE);
@@ -416,6 +484,205 @@ More lyrics to the chorus 3.
     ));
 }
 
+#[test]
+fn parse_verse_continuation() {
+    let input = r#"
+# Song
+
+1. First verse.
+
+```
+Interlude
+```
+
+!cont More of the first verse.
+
+Another paragraph.
+
+> Chorus.
+
+---
+
+!cont
+More of the chorus.
+
+### Bridge
+
+Bridge lyrics.
+
+```
+Interlude
+```
+
+!cont More of the bridge.
+
+!cont Already a part of the bridge.
+
+2. Second verse.
+"#;
+
+    let (songs, diag) = TetsParser::new(input, ParserConfig::default()).parse();
+    let [parsed]: [_; 1] = songs.unwrap().try_into().unwrap();
+    assert!(diag.is_empty());
+    parsed.assert_json_eq(song(
+        "Song",
+        [],
+        "english",
+        [
+            ver_verse(1, [p([i_text("First verse.")])]),
+            b_pre("Interlude\n"),
+            continued(ver_verse(
+                1,
+                [
+                    p([i_text("More of the first verse.")]),
+                    p([i_text("Another paragraph.")]),
+                ],
+            )),
+            ver_chorus(Null, [p([i_text("Chorus.")])]),
+            b_hr(),
+            continued(ver_chorus(Null, [p([i_text("More of the chorus.")])])),
+            ver_custom("Bridge", [p([i_text("Bridge lyrics.")])]),
+            b_pre("Interlude\n"),
+            continued(ver_custom(
+                "Bridge",
+                [
+                    p([i_text("More of the bridge.")]),
+                    p([i_text("Already a part of the bridge.")]),
+                ],
+            )),
+            ver_verse(2, [p([i_text("Second verse.")])]),
+        ],
+    ));
+
+    // There's no verse to continue at the start of a song
+    let input = "# Song\n\n!cont Lyrics.\n";
+    let (songs, diag) = TetsParser::new(input, ParserConfig::default()).parse();
+    let [parsed]: [_; 1] = songs.unwrap().try_into().unwrap();
+    parsed.assert_json_eq(song(
+        "Song",
+        [],
+        "english",
+        [ver_none([p([i_text("Lyrics.")])])],
+    ));
+    let diag: Vec<_> = diag.into_iter().map(|d| (d.line, d.kind)).collect();
+    assert_eq!(diag, [(3, DiagKind::NothingToContinue)]);
+}
+
+#[test]
+fn parse_verse_label_ext() {
+    let input = r#"
+# Song
+
+1. First verse.
+
+!label:Bridge Bridge lyrics.
+
+!label(Pre Chorus)
+Before the chorus.
+
+> !label:Refrain Chorus.
+
+2. !label:Intermezzo Not a numbered verse.
+
+3. Second verse !label:Coda.
+
+### Outro
+
+!label:Ending Outro lyrics.
+
+!label:One !label:Two Lyrics.
+"#;
+
+    let (songs, diag) = TetsParser::new(input, ParserConfig::default()).parse();
+    let [parsed]: [_; 1] = songs.unwrap().try_into().unwrap();
+    parsed.assert_json_eq(song(
+        "Song",
+        [],
+        "english",
+        [
+            ver_verse(1, [p([i_text("First verse.")])]),
+            ver_custom("Bridge", [p([i_text("Bridge lyrics.")])]),
+            ver_custom("Pre Chorus", [p([i_text("Before the chorus.")])]),
+            ver_custom("Refrain", [p([i_text("Chorus.")])]),
+            ver_custom("Intermezzo", [p([i_text("Not a numbered verse.")])]),
+            ver_verse(2, [p([i_text("Second verse !label:Coda.")])]),
+            ver_custom("Outro", [p([i_text("Outro lyrics.")])]),
+            ver_custom("One", [p([i_text("Lyrics.")])]),
+        ],
+    ));
+
+    let diag: Vec<_> = diag.into_iter().map(|d| (d.line, d.kind)).collect();
+    assert_eq!(
+        diag,
+        [
+            (
+                15,
+                DiagKind::MisplacedLabel {
+                    ext: "!label:Coda.".into()
+                }
+            ),
+            (
+                19,
+                DiagKind::DuplicateLabel {
+                    ext: "!label:Ending".into()
+                }
+            ),
+            (
+                21,
+                DiagKind::DuplicateLabel {
+                    ext: "!label:Two".into()
+                }
+            ),
+        ]
+    );
+    assert!(!diag[0].1.is_error());
+}
+
+#[test]
+fn parse_verse_flags() {
+    let input = r#"
+# Song
+
+- Intro
+
+1. First verse.
+
+> Chorus.
+
+---
+
+2. Last verse.
+
+- Outro
+"#;
+
+    let song = serde_json::to_value(parse_one(input)).unwrap();
+    let flags: Vec<_> = song["blocks"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|b| {
+            (
+                b["block_index"].clone(),
+                b.get("first_verse"),
+                b.get("last_verse"),
+            )
+        })
+        .collect();
+    let (t, f) = (Some(&Json::Bool(true)), Some(&Json::Bool(false)));
+    assert_eq!(
+        flags,
+        [
+            (json!(0), None, None),
+            (json!(1), t, f),
+            (json!(2), f, f),
+            (json!(3), None, None),
+            (json!(4), f, t),
+            (json!(5), None, None),
+        ]
+    );
+}
+
 #[test]
 fn parse_subtitles() {
     let input = r#"
@@ -471,6 +738,146 @@ fn parse_chords_baseline() {
     ]));
 }
 
+#[test]
+fn parse_chord_roots() {
+    let input = "# Song\n\n1. `Am7`a `C/G`b `C G`c `X`d `f#`e\n\n2. !german `Bb`f `B`g\n";
+    let song = parse_one(input);
+    let roots: Vec<_> = song
+        .chords()
+        .iter()
+        .map(|c| (&*c.chord, c.root_chromatic, c.suffix.as_deref()))
+        .collect();
+    assert_eq!(
+        roots,
+        [
+            ("Am7", Some(9), Some("m7")),
+            ("C/G", None, None),
+            ("C G", None, None),
+            ("X", None, None),
+            ("f#", Some(6), Some("")),
+            // Roots are found in the notation chords were converted to
+            ("B", Some(10), Some("")),
+            ("H", Some(11), Some("")),
+        ]
+    );
+}
+
+#[test]
+fn parse_chords_after() {
+    let before = "# Song\n\n1. `G`Sailing round, `D`the ocean`A`\n`C`sea, sea!\n";
+    parse_one_para(before).assert_json_eq(json!([
+        i_chord("G", Null, 1, [i_text("Sailing round, ")]),
+        i_chord("D", Null, 1, [i_text("the ocean")]),
+        i_chord("A", Null, 1, []),
+        i_break(),
+        i_chord("C", Null, 1, [i_text("sea, sea!")]),
+    ]));
+
+    // Chords take the preceding word including punctuation, whitespace stays outside of the chord
+    let input = r#"
+# Song
+
+!chordpos(after)
+
+1. `G`Sailing round,`D` the ocean`A`
+sea, `C`sea!`E`
+
+# Other Song
+
+1. `G`Lyrics.
+"#;
+    let songs = parse(input, false);
+    assert_eq!(songs[0].chord_pos, ChordPos::After);
+    songs[0].blocks.assert_json_eq(json!(verse_flags([ver_verse(
+        1,
+        [p([
+            i_chord("G", Null, 1, []),
+            i_text("Sailing "),
+            i_chord("D", Null, 1, [i_text("round,")]),
+            i_text(" the "),
+            i_chord("A", Null, 1, [i_text("ocean")]),
+            i_break(),
+            i_chord("C", Null, 1, [i_text("sea,")]),
+            i_text(" "),
+            i_chord("E", Null, 1, [i_text("sea!")]),
+        ])]
+    )])));
+
+    // The chord position is reset for each song
+    assert_eq!(songs[1].chord_pos, ChordPos::Before);
+    songs[1].blocks.assert_json_eq(json!(verse_flags([ver_verse(
+        1,
+        [p([i_chord("G", Null, 1, [i_text("Lyrics.")])])]
+    )])));
+}
+
+#[test]
+fn parse_variants() {
+    let input = r#"
+# Song
+
+1. `C`Lyrics `Am`more.
+
+# Other Song
+
+!+2
+
+> `C`Chorus.
+"#;
+    let variant = |suffix: &str, transpose| SongVariant {
+        suffix: suffix.into(),
+        transpose,
+    };
+    let mut variants = BTreeMap::new();
+    variants.insert(
+        "song".to_string(),
+        vec![variant("(D)", 2), variant("(Bb)", -2)],
+    );
+    variants.insert("Other Song".to_string(), vec![variant("(E)", 2)]);
+    let config = ParserConfig::default().variants(variants);
+    let (songs, diags) = TetsParser::new(input, config).parse();
+    let songs = songs.unwrap();
+    assert!(diags.is_empty());
+
+    let titles: Vec<_> = songs.iter().map(|s| &*s.title).collect();
+    assert_eq!(
+        titles,
+        [
+            "Song",
+            "Song (D)",
+            "Song (Bb)",
+            "Other Song",
+            "Other Song (E)"
+        ]
+    );
+    songs[1]
+        .title_inlines
+        .assert_json_eq(json!([i_text("Song (D)")]));
+
+    let verse = |chord1, chord2| {
+        json!(verse_flags([ver_verse(
+            1,
+            [p([
+                i_chord(chord1, Null, 1, [i_text("Lyrics ")]),
+                i_chord(chord2, Null, 1, [i_text("more.")]),
+            ])]
+        )]))
+    };
+    songs[0].blocks.assert_json_eq(verse("C", "Am"));
+    songs[1].blocks.assert_json_eq(verse("D", "Bm"));
+    songs[2].blocks.assert_json_eq(verse("Bb", "Gm"));
+
+    // The variant's transposition is added to the song's own
+    let chorus = |chord| {
+        json!(verse_flags([ver_chorus(
+            Null,
+            [p([i_chord(chord, Null, 1, [i_text("Chorus.")])])]
+        )]))
+    };
+    songs[3].blocks.assert_json_eq(chorus("D"));
+    songs[4].blocks.assert_json_eq(chorus("E"));
+}
+
 #[test]
 fn parse_inlines() {
     let input = r#"
@@ -493,6 +900,108 @@ Sailing_ round the `D`sea.**
     ]));
 }
 
+#[test]
+fn parse_soft_breaks() {
+    let input = r#"
+# Song
+1. Sailing round `G`the ocean,
+Sailing round the `D`sea.\
+Hard break `Am`*flowing
+text*
+"#;
+    let config = ParserConfig::default().soft_breaks(SoftBreaks::Space);
+    let para = TetsParser::new(input, config).parse_one_para();
+    para.assert_json_eq(json!([
+        i_text("Sailing round "),
+        i_chord("G", Null, 1, [i_text("the ocean, Sailing round the ")]),
+        i_chord("D", Null, 1, [i_text("sea.")]),
+        i_break(),
+        i_text("Hard break "),
+        i_chord(
+            "Am",
+            Null,
+            1,
+            [
+                i_emph([i_text("flowing")]),
+                i_text(" "),
+                i_emph([i_text("text")])
+            ]
+        ),
+    ]));
+
+    // The default is to treat soft breaks as hard breaks
+    parse_one_para(input).assert_json_eq(json!([
+        i_text("Sailing round "),
+        i_chord("G", Null, 1, [i_text("the ocean,")]),
+        i_break(),
+        i_text("Sailing round the "),
+        i_chord("D", Null, 1, [i_text("sea.")]),
+        i_break(),
+        i_text("Hard break "),
+        i_chord("Am", Null, 1, [i_emph([i_text("flowing")])]),
+        i_break(),
+        i_emph([i_text("text")]),
+    ]));
+}
+
+#[test]
+fn parse_soft_breaks_chord_only() {
+    let input = r#"
+# Song
+1. `G`
+lyrics after `C`
+"#;
+    let config = ParserConfig::default().soft_breaks(SoftBreaks::Space);
+    let para = TetsParser::new(input, config).parse_one_para();
+    para.assert_json_eq(json!([
+        i_chord("G", Null, 1, [i_text(" lyrics after ")]),
+        i_chord("C", Null, 1, []),
+    ]));
+}
+
+#[test]
+fn parse_title_chords() {
+    let input = r#"
+# Medley `C`Sailing `G` Round
+1. Lyrics.
+"#;
+    let song = parse_one(input);
+    assert_eq!(&*song.title, "Medley Sailing Round");
+    song.title_inlines.assert_json_eq(json!([
+        i_text("Medley "),
+        i_chord("C", Null, 1, [i_text("Sailing ")]),
+        i_chord("G", Null, 1, [i_text(" Round")]),
+    ]));
+
+    let config = ParserConfig::default().title_chords(TitleChords::Parens);
+    let song = TetsParser::new(input, config).parse_one();
+    assert_eq!(&*song.title, "Medley (C)Sailing (G) Round");
+}
+
+#[test]
+fn parse_matter() {
+    let input = r#"
+# Preface
+These songs were collected *over the years*.
+
+## Thanks
+To everyone.
+"#;
+    let src_file = PathBuf::from("<test>");
+    let mut parser = Parser::new(input, &src_file, ParserConfig::default(), |_| {});
+    let matter = parser.parse_matter().unwrap();
+    matter.blocks.assert_json_eq(json!(verse_flags([
+        b_heading(1, "Preface"),
+        ver_none([p([
+            i_text("These songs were collected "),
+            i_emph([i_text("over the years")]),
+            i_text("."),
+        ])]),
+        b_heading(2, "Thanks"),
+        ver_none([p([i_text("To everyone.")])]),
+    ])));
+}
+
 #[test]
 fn parse_extensions() {
     let input = r#"
@@ -520,7 +1029,7 @@ Mixed !>> in text.
 
     let songs = parse(input, true);
 
-    songs[0].blocks.assert_json_eq(json!([
+    songs[0].blocks.assert_json_eq(json!(verse_flags([
         ver_none([p([
             i_xpose("t-transpose", 5),
             i_break(),
@@ -539,9 +1048,9 @@ Mixed !>> in text.
                 i_xpose("t-alt-none", ()),
             ])]
         ),
-    ]));
+    ])));
 
-    songs[1].blocks.assert_json_eq(json!([
+    songs[1].blocks.assert_json_eq(json!(verse_flags([
         ver_chorus(1, [p([i_text("Chorus.")])]),
         ver_chorus(2, [p([i_text("Chorus two.")])]),
         ver_verse(
@@ -559,62 +1068,649 @@ Mixed !>> in text.
                 i_text(" in text."),
             ])]
         ),
-    ]));
+    ])));
 }
 
 #[test]
-fn transposition() {
-    let input = r#"
-# Song
-
-!+5
-!!czech
-
-> `Bm`Yippie yea `D`oh! !+0
-!+0 Yippie yea `Bm`yay!
-
-!!none
-
-1. `Bm`Yippie yea `D`oh! !+0
-Yippie yea `Bm`yay!
+fn parse_chorus_numbers() {
+    let single = "# Song\n\n> Chorus.\n\n1. Lyrics !>\n";
+    let multiple = "# Song\n\n> Chorus.\n\n>> Chorus two.\n\n1. Lyrics !> !>>\n";
+    let parse_with = |input, chorus_numbers| {
+        let config = ParserConfig::default().chorus_numbers(chorus_numbers);
+        TetsParser::new(input, config).parse_one().blocks
+    };
+
+    // By default, numbers are only removed in songs with a single chorus
+    parse_with(single, ChorusNumbers::Auto).assert_json_eq(json!(verse_flags([
+        ver_chorus(Null, [p([i_text("Chorus.")])]),
+        ver_verse(1, [p([i_text("Lyrics"), i_chorus_ref(Null, " ")])]),
+    ])));
 
-"#;
+    parse_with(single, ChorusNumbers::Always).assert_json_eq(json!(verse_flags([
+        ver_chorus(1, [p([i_text("Chorus.")])]),
+        ver_verse(1, [p([i_text("Lyrics"), i_chorus_ref(1, " ")])]),
+    ])));
 
-    let song = parse_one(input);
-    song.blocks.assert_json_eq(json!([
-        ver_chorus(
-            Null,
+    parse_with(multiple, ChorusNumbers::Always).assert_json_eq(json!(verse_flags([
+        ver_chorus(1, [p([i_text("Chorus.")])]),
+        ver_chorus(2, [p([i_text("Chorus two.")])]),
+        ver_verse(
+            1,
             [p([
-                i_chord("Em", "Hm", 1, [i_text("Yippie yea ")]),
-                i_chord("G", "D", 1, [i_text("oh!")]),
-                i_break(),
-                i_text("Yippie yea "),
-                i_chord("Bm", "Hm", 1, [i_text("yay!")]),
+                i_text("Lyrics"),
+                i_chorus_ref(1, " "),
+                i_chorus_ref(2, " ")
             ])]
         ),
+    ])));
+
+    parse_with(multiple, ChorusNumbers::Never).assert_json_eq(json!(verse_flags([
+        ver_chorus(Null, [p([i_text("Chorus.")])]),
+        ver_chorus(Null, [p([i_text("Chorus two.")])]),
         ver_verse(
             1,
             [p([
-                i_chord("Bm", Null, 1, [i_text("Yippie yea ")]),
-                i_chord("D", Null, 1, [i_text("oh!")]),
-                i_break(),
-                i_text("Yippie yea "),
-                i_chord("Bm", Null, 1, [i_text("yay!")]),
+                i_text("Lyrics"),
+                i_chorus_ref(Null, " "),
+                i_chorus_ref(Null, " ")
             ])]
-        )
-    ]));
+        ),
+    ])));
 }
 
 #[test]
-fn transposition_error() {
+fn parse_chorus_ref_numbers() {
     let input = r#"
 # Song
 
-!+5
+> Chorus one.
 
-> 1. `Bm`Yippie yea `D`oh!
-Yippie yea `X`yay!
-Yippie yea `Y`yay!
+>> Chorus two.
+
+>>> Chorus three.
+
+1. Lyrics !>2 !>1-3
+!>2-3 more !>>
+"#;
+
+    let (songs, diag) = try_parse(input, false);
+    assert!(diag.is_empty());
+    songs.unwrap()[0]
+        .get_verse(3)
+        .paragraphs
+        .assert_json_eq(json!([[
+            i_text("Lyrics"),
+            i_chorus_ref(2, " "),
+            i_chorus_ref_range(1, 3, " "),
+            i_break(),
+            i_chorus_ref_range(2, 3, ""),
+            i_text(" more"),
+            i_chorus_ref(2, " "),
+        ]]));
+
+    // Ranges keep their numbers in songs with a single chorus
+    let song = parse_one("# Song\n\n> Chorus.\n\n1. Lyrics !>1 !>1-2\n");
+    song.get_verse(1).paragraphs.assert_json_eq(json!([[
+        i_text("Lyrics"),
+        i_chorus_ref(Null, " "),
+        i_chorus_ref_range(1, 2, " "),
+    ]]));
+
+    // Malformed numbers are regular text and reported as warnings
+    let input = "# Song\n\n> Chorus.\n\n1. Lyrics !>3-1 !>0-2\n!>0 !>2-2 !>1- !>x-2\n";
+    let (songs, diag) = try_parse(input, false);
+    songs.unwrap()[0]
+        .get_verse(1)
+        .paragraphs
+        .assert_json_eq(json!([[
+            i_text("Lyrics !>3-1 !>0-2"),
+            i_break(),
+            i_text("!>0 !>2-2 !>1- !>x-2"),
+        ]]));
+
+    let diag: Vec<_> = diag.iter().map(|d| (d.line, &d.kind)).collect();
+    let invalid = |reference: &str| DiagKind::InvalidChorusRef {
+        reference: reference.into(),
+    };
+    assert_eq!(
+        diag,
+        [
+            (5, &invalid("!>3-1")),
+            (5, &invalid("!>0-2")),
+            (6, &invalid("!>0")),
+            (6, &invalid("!>2-2")),
+        ]
+    );
+    assert!(!diag[0].1.is_error());
+}
+
+#[test]
+fn parse_chords_used() {
+    let input = r#"
+# `G`Song
+
+> `Am`Chorus *`C`emph **`Am`strong `N.C.`end***.
+
+!!+2
+
+1. `G`Lyrics `C`more `Am`lyrics `G`again.
+"#;
+
+    let song = parse_one(input);
+    assert_eq!(
+        song.chords_used,
+        ["G".into(), "Am".into(), "C".into(), "N.C.".into()]
+    );
+    assert_eq!(song.alt_chords_used, ["A".into(), "D".into(), "Bm".into()]);
+}
+
+#[test]
+fn parse_transpose_non_chords() {
+    let input = r#"
+# Song
+
+!+2
+!!german
+
+1. `| G | % | x2 |`Lyrics `G.. D..`more.
+"#;
+
+    let song = parse_one(input);
+    assert_eq!(
+        song.chords_used,
+        ["| A | % | x2 |".into(), "A.. E..".into()]
+    );
+    assert_eq!(
+        song.alt_chords_used,
+        ["| G | % | x2 |".into(), "G.. D..".into()]
+    );
+}
+
+#[test]
+fn parse_alt_roman_minor() {
+    let input = r#"
+# Song
+
+!!roman-minor
+
+1. `Am`Lyrics `Amaj7`more `Bdim`lyrics `C`end.
+
+!!roman
+
+2. `Am`Lyrics.
+"#;
+
+    let song = parse_one(input);
+    assert_eq!(
+        song.alt_chords_used,
+        [
+            "vi".into(),
+            "VImaj7".into(),
+            "vii\u{b0}".into(),
+            "I".into(),
+            "VIm".into()
+        ]
+    );
+}
+
+#[test]
+fn parse_chord_suffixes() {
+    let input = r#"
+# `Ami`Song
+
+1. `Amin`Lyrics `A-/G`more `Cmaj7 Dmi`lyrics `_Emi_` `F#min7`end.
+
+!!+2
+
+2. `Amin`Lyrics `Cmaj7`more.
+"#;
+
+    let suffixes = [("mi", "m"), ("min", "m"), ("-", "m"), ("maj7", "Δ")]
+        .iter()
+        .map(|(from, to)| (from.to_string(), to.to_string()))
+        .collect();
+    let config = ParserConfig::default().chord_suffixes(suffixes);
+    let song = TetsParser::new(input, config).parse_one();
+    assert_eq!(
+        song.chords_used,
+        [
+            "Am".into(),
+            "Am/G".into(),
+            "CΔ Dm".into(),
+            "Em".into(),
+            "F#min7".into(),
+            "CΔ".into()
+        ]
+    );
+    assert_eq!(song.alt_chords_used, ["Bm".into(), "DΔ".into()]);
+}
+
+#[test]
+fn parse_metadata() {
+    let input = r#"
+# Song
+```meta
+author = "Harlej"
+capo = 2
+tags = ["folk"]
+```
+## Subtitle
+
+1. Lyrics.
+
+# Song 2
+## Subtitle
+
+```meta
+capo = 3
+```
+
+```meta
+not = "metadata"
+```
+"#;
+
+    let songs = parse(input, false);
+    assert_eq!(&*songs[0].subtitles, ["Subtitle".into()]);
+    assert_eq!(
+        serde_json::to_value(&songs[0].metadata).unwrap(),
+        json!({
+            "author": "Harlej",
+            "capo": 2,
+            "tags": ["folk"],
+        })
+    );
+    assert_eq!(songs[0].blocks.len(), 1);
+
+    // Only the first meta block before the lyrics is metadata
+    assert_eq!(
+        serde_json::to_value(&songs[1].metadata).unwrap(),
+        json!({ "capo": 3 })
+    );
+    assert_eq!(
+        serde_json::to_value(&songs[1].blocks).unwrap(),
+        json!([b_pre("not = \"metadata\"\n")])
+    );
+
+    // Invalid metadata is left out with a warning
+    let (songs, diag) = try_parse(
+        "# Song
+
+```meta
+capo = 
+```
+",
+        false,
+    );
+    assert!(songs.unwrap()[0].metadata.is_empty());
+    assert_eq!(diag.len(), 1);
+    assert_eq!(diag[0].line, 3);
+    assert!(matches!(diag[0].kind, DiagKind::InvalidMetadata { .. }));
+    assert!(!diag[0].kind.is_error());
+}
+
+#[test]
+fn parse_alt_notations() {
+    let input = r#"
+# Song
+1. `C`Lyrics !!czech `Bb`more !!none `C`lyrics.
+
+# Song 2
+1. `A`Lyrics !!nashville `A`more.
+"#;
+    let (songs, _) = try_parse(input, false);
+    let songs = songs.unwrap();
+    assert_eq!(&*songs[0].alt_notations, [Notation::German]);
+    assert_eq!(&*songs[1].alt_notations, [Notation::Nashville]);
+
+    // Without an alt chord, a notation switch isn't recorded
+    let song = parse_one("# Song\n\n1. Lyrics !!czech more.\n");
+    assert!(song.alt_notations.is_empty());
+}
+
+#[test]
+fn parse_extensions_edge_cases() {
+    let cases = [
+        // Punctuation adjacency, not extensions
+        ("Hey! Ho", json!([i_text("Hey! Ho")])),
+        ("Hey!! Ho!!", json!([i_text("Hey!! Ho!!")])),
+        ("Hey!!none ho", json!([i_text("Hey!!none ho")])),
+        ("Lyrics !>, more", json!([i_text("Lyrics !>, more")])),
+        ("Lyrics (!>) more", json!([i_text("Lyrics (!>) more")])),
+        ("Lyrics !+2. more", json!([i_text("Lyrics !+2. more")])),
+        ("Hey !!!", json!([i_text("Hey !!!")])),
+        // Whitespace consumption is the same in any position
+        ("!!none Lyrics", json!([i_text("Lyrics")])),
+        ("Lyrics !!none more", json!([i_text("Lyrics more")])),
+        ("Lyrics !!none", json!([i_text("Lyrics")])),
+        // Texts around a removed extension are merged, with duplicate whitespace collapsed
+        ("Lyrics  !!none  more", json!([i_text("Lyrics more")])),
+        // Multiple extensions in a row
+        ("!+2 !!czech Lyrics", json!([i_text("Lyrics")])),
+        (
+            "Lyrics !+2 !!czech !!none more",
+            json!([i_text("Lyrics more")]),
+        ),
+        (
+            "Lyrics !> !>",
+            json!([
+                i_text("Lyrics"),
+                i_chorus_ref(Null, " "),
+                i_chorus_ref(Null, " ")
+            ]),
+        ),
+        (
+            "!+2 !> Lyrics",
+            json!([i_chorus_ref(Null, ""), i_text(" Lyrics")]),
+        ),
+        // Escaped forms
+        ("\\!+5 Lyrics", json!([i_text("!+5 Lyrics")])),
+        ("Lyrics \\!> more", json!([i_text("Lyrics !> more")])),
+        (
+            "Lyrics \\!> !>",
+            json!([i_text("Lyrics !>"), i_chorus_ref(Null, " ")]),
+        ),
+        ("Lyrics \\\\!> more", json!([i_text("Lyrics \\!> more")])),
+    ];
+
+    for (input, expected) in cases {
+        let input = format!("# Song\n\n1. {}\n", input);
+        parse_one_para(&input).assert_json_eq(expected);
+    }
+}
+
+#[test]
+fn parse_directives_before_verses() {
+    let input = r#"
+# Song
+## Subtitle
+
+!tempo(120) !time(3/4)
+
+1. `C`Lyrics.
+"#;
+
+    let song = parse_one(input);
+    assert_eq!(song.tempo.as_deref(), Some("120"));
+    assert_eq!(song.time.as_deref(), Some("3/4"));
+    song.blocks.assert_json_eq(json!(verse_flags([ver_verse(
+        1,
+        [p([i_chord("C", Null, 1, [i_text("Lyrics.")])])]
+    )])));
+
+    // Only one of them, on separate lines
+    let input = r#"
+# Song
+
+!time(6/8)
+
+> Chorus.
+"#;
+
+    let song = parse_one(input);
+    assert_eq!(song.tempo, None);
+    assert_eq!(song.time.as_deref(), Some("6/8"));
+    song.blocks.assert_json_eq(json!(verse_flags([ver_chorus(
+        Null,
+        [p([i_text("Chorus.")])]
+    )])));
+
+    // Paragraph with text is not hoisted, the plain-text approach works as before
+    let input = r#"
+# Song
+
+♩ = 120 !time(3/4)
+
+1. Lyrics.
+"#;
+
+    let song = parse_one(input);
+    assert_eq!(song.tempo, None);
+    assert_eq!(song.time, None);
+    song.blocks.assert_json_eq(json!(verse_flags([
+        ver_none([p([i_text("♩ = 120"), i_directive("time", "3/4", " ")])]),
+        ver_verse(1, [p([i_text("Lyrics.")])]),
+    ])));
+}
+
+#[test]
+fn parse_directives_inline() {
+    let input = r#"
+# Song
+
+1. Lyrics.
+
+!tempo(90) !time(4/4)
+More lyrics.
+
+> Chorus !tempo(140)
+"#;
+
+    let song = parse_one(input);
+    assert_eq!(song.tempo, None);
+    assert_eq!(song.time, None);
+    song.blocks.assert_json_eq(json!(verse_flags([
+        ver_verse(
+            1,
+            [
+                p([i_text("Lyrics.")]),
+                p([
+                    i_directive("tempo", "90", ""),
+                    i_directive("time", "4/4", " "),
+                    i_break(),
+                    i_text("More lyrics."),
+                ]),
+            ]
+        ),
+        ver_chorus(
+            Null,
+            [p([i_text("Chorus"), i_directive("tempo", "140", " ")])]
+        ),
+    ])));
+
+    // Invalid directives are regular text
+    let cases = [
+        "!tempo(fast)",
+        "!tempo(0)",
+        "!tempo()",
+        "!time(3)",
+        "!time(3/x)",
+        "!!tempo(120)",
+        "!tempo(120",
+        "!meter(3/4)",
+    ];
+    for case in cases {
+        let input = format!("# Song\n\n1. Lyrics {}\n", case);
+        parse_one_para(&input).assert_json_eq(json!([i_text(format!("Lyrics {}", case))]));
+    }
+}
+
+#[test]
+fn parse_key() {
+    let input = r#"
+# Song
+
+!key(Am) !tempo(90)
+
+1. `Am`Lyrics. !key(C)
+"#;
+
+    let song = parse_one(input);
+    assert_eq!(song.key.as_deref(), Some("Am"));
+    song.blocks.assert_json_eq(json!(verse_flags([ver_verse(
+        1,
+        [p([i_chord(
+            "Am",
+            Null,
+            1,
+            [i_text("Lyrics."), i_directive("key", "C", " ")]
+        )])]
+    )])));
+
+    // The key is transposed along with chords
+    let input = "# Song\n\n!+2 !key(F#m)\n\n1. `F#m`Lyrics.\n";
+    let song = parse(input, false).pop().unwrap();
+    assert_eq!(song.key.as_deref(), Some("Abm"));
+
+    let (res, diag) = try_parse("# Song\n\n!+2 !key(X)\n", false);
+    res.unwrap_err();
+    assert_eq!(
+        diag[0].kind,
+        DiagKind::Transposition {
+            chord: "X".into(),
+            notation: Notation::English,
+        }
+    );
+
+    parse_one_para("# Song\n\n1. Lyrics !key()\n").assert_json_eq(json!([i_text("Lyrics !key()")]));
+}
+
+#[test]
+fn parse_alias() {
+    let input = r#"
+# Song
+
+!alias(Old Title) !alias( old-slug )
+
+1. Lyrics. !alias(Later)
+"#;
+
+    let song = parse_one(input);
+    assert_eq!(&*song.aliases, &["Old Title".into(), "old-slug".into()]);
+    song.blocks.assert_json_eq(json!(verse_flags([ver_verse(
+        1,
+        [p([i_text("Lyrics."), i_directive("alias", "Later", " ")])]
+    )])));
+
+    parse_one_para("# Song\n\n1. Lyrics !alias( )\n")
+        .assert_json_eq(json!([i_text("Lyrics !alias( )")]));
+}
+
+#[test]
+fn parse_dir() {
+    assert_eq!(parse_one("# Song\n\n1. Lyrics.\n").dir, TextDir::Ltr);
+    assert_eq!(parse_one("# שיר\n\n1. Lyrics.\n").dir, TextDir::Rtl);
+    assert_eq!(
+        parse_one("# 1. (אבא) Song\n\n1. Lyrics.\n").dir,
+        TextDir::Rtl
+    );
+    assert_eq!(parse_one("# Song שיר\n\n1. Lyrics.\n").dir, TextDir::Ltr);
+    assert_eq!(
+        parse_one("# Song\n\n!dir(rtl)\n\n1. Lyrics.\n").dir,
+        TextDir::Rtl
+    );
+    assert_eq!(
+        parse_one("# שיר\n\n!dir(ltr)\n\n1. Lyrics.\n").dir,
+        TextDir::Ltr
+    );
+
+    parse_one_para("# Song\n\n1. Lyrics !dir(up)\n")
+        .assert_json_eq(json!([i_text("Lyrics !dir(up)")]));
+}
+
+#[test]
+fn parse_duration() {
+    let input = r#"
+# Song
+
+!tempo(120) !duration(3:45)
+
+1. Lyrics. !duration(225)
+"#;
+
+    let (songs, diag) = try_parse(input, false);
+    let [song]: [_; 1] = songs.unwrap().try_into().unwrap();
+    assert!(diag.is_empty());
+    assert_eq!(song.tempo.as_deref(), Some("120"));
+    assert_eq!(song.duration, Some(Duration::from_secs(225)));
+    song.blocks.assert_json_eq(json!(verse_flags([ver_verse(
+        1,
+        [p([i_text("Lyrics."), i_directive("duration", "225", " ")])]
+    )])));
+
+    // Invalid durations are regular text and reported as warnings
+    let input = "# Song\n\n!duration(3:75)\n\n1. Lyrics !duration(fast)\n";
+    let (songs, diag) = try_parse(input, false);
+    let [song]: [_; 1] = songs.unwrap().try_into().unwrap();
+    assert_eq!(song.duration, None);
+    song.blocks.assert_json_eq(json!(verse_flags([
+        ver_none([p([i_text("!duration(3:75)")])]),
+        ver_verse(1, [p([i_text("Lyrics !duration(fast)")])]),
+    ])));
+
+    let diag: Vec<_> = diag.iter().map(|d| (d.line, &d.kind)).collect();
+    assert_eq!(
+        diag,
+        [
+            (
+                3,
+                &DiagKind::InvalidDuration {
+                    value: "3:75".into()
+                }
+            ),
+            (
+                5,
+                &DiagKind::InvalidDuration {
+                    value: "fast".into()
+                }
+            ),
+        ]
+    );
+}
+
+#[test]
+fn transposition() {
+    let input = r#"
+# Song
+
+!+5
+!!czech
+
+> `Bm`Yippie yea `D`oh! !+0
+!+0 Yippie yea `Bm`yay!
+
+!!none
+
+1. `Bm`Yippie yea `D`oh! !+0
+Yippie yea `Bm`yay!
+
+"#;
+
+    let song = parse_one(input);
+    song.blocks.assert_json_eq(json!(verse_flags([
+        ver_chorus(
+            Null,
+            [p([
+                i_chord("Em", "Hm", 1, [i_text("Yippie yea ")]),
+                i_chord("G", "D", 1, [i_text("oh!")]),
+                i_break(),
+                i_text("Yippie yea "),
+                i_chord("Bm", "Hm", 1, [i_text("yay!")]),
+            ])]
+        ),
+        ver_verse(
+            1,
+            [p([
+                i_chord("Bm", Null, 1, [i_text("Yippie yea ")]),
+                i_chord("D", Null, 1, [i_text("oh!")]),
+                i_break(),
+                i_text("Yippie yea "),
+                i_chord("Bm", Null, 1, [i_text("yay!")]),
+            ])]
+        )
+    ])));
+}
+
+#[test]
+fn transposition_error() {
+    let input = r#"
+# Song
+
+!+5
+
+> 1. `Bm`Yippie yea `D`oh!
+Yippie yea `X`yay!
+Yippie yea `Y`yay!
 "#;
 
     let (res, diag) = try_parse(input, false);
@@ -623,12 +1719,90 @@ Yippie yea `Y`yay!
     assert!(diag[0].is_error());
     assert_eq!(diag[0].file.as_os_str(), "<test>");
     assert_eq!(diag[0].line, 7);
-    assert_eq!(diag[0].kind, DiagKind::Transposition { chord: "X".into() });
+    assert_eq!(
+        diag[0].kind,
+        DiagKind::Transposition {
+            chord: "X".into(),
+            notation: Notation::English,
+        }
+    );
 
     assert!(diag[1].is_error());
     assert_eq!(diag[1].file.as_os_str(), "<test>");
     assert_eq!(diag[1].line, 8);
-    assert_eq!(diag[1].kind, DiagKind::Transposition { chord: "Y".into() });
+    assert_eq!(
+        diag[1].kind,
+        DiagKind::Transposition {
+            chord: "Y".into(),
+            notation: Notation::English,
+        }
+    );
+}
+
+#[test]
+fn parse_transposition_hint() {
+    // A German chord in an English song
+    let (_, diag) = try_parse("# Song\n\n!+2\n\n1. `Hm7`Lyrics.\n", false);
+    assert_eq!(
+        diag[0].kind.to_string(),
+        "Unrecognized chord: Hm7\n\
+        Hint: Note names in the english notation are C, D, E, F, G, A, B, optionally followed by # or b.\n\
+        The chord is recognized in the german notation, did you mean to set `notation = \"german\"` in bard.toml?"
+    );
+
+    // An English chord in a Roman song, German has the same chord names apart from B and H
+    let config = ParserConfig::new(Notation::Roman, true);
+    let (_, diag) = TetsParser::new("# Song\n\n!+2\n\n1. `Am`Lyrics.\n", config).parse();
+    assert_eq!(
+        diag[0].kind,
+        DiagKind::Transposition {
+            chord: "Am".into(),
+            notation: Notation::Roman,
+        }
+    );
+    assert_eq!(
+        diag[0].kind.to_string(),
+        "Unrecognized chord: Am\n\
+        Hint: Note names in the roman notation are I, II, III, IV, V, VI, VII, optionally followed by # or b.\n\
+        The chord is recognized in the english and german notations, did you mean to set `notation = \"english\"` in bard.toml?"
+    );
+
+    // No suggestion if the chord isn't recognized in any notation
+    let (_, diag) = try_parse("# Song\n\n!+2\n\n1. `X`Lyrics.\n", false);
+    assert!(diag[0]
+        .kind
+        .to_string()
+        .ends_with("optionally followed by # or b."));
+}
+
+#[test]
+fn parse_chord_lint() {
+    let input = "# Song `N.C.`\n\n1. `Am7`Lyrics `la la` la `G % C`.\n";
+
+    // Chords aren't checked without transposition by default
+    let (songs, diag) = TetsParser::new(input, ParserConfig::default()).parse();
+    assert_eq!(songs.unwrap().len(), 1);
+    assert!(diag.is_empty(), "{:?}", diag);
+
+    let config = ParserConfig::default().chord_lint(Some(vec!["N.C.".into(), "%".into()]));
+    let (songs, diag) = TetsParser::new(input, config).parse();
+    assert_eq!(songs.unwrap().len(), 1);
+    assert_eq!(diag.len(), 1, "{:?}", diag);
+    assert_eq!(diag[0].line, 3);
+    assert_eq!(
+        diag[0].kind,
+        DiagKind::UnrecognizedChord {
+            chord: "la".into(),
+            notation: Notation::English,
+        }
+    );
+    assert!(!diag[0].is_error());
+
+    // Transposed chords are reported by the transposition only
+    let config = ParserConfig::default().chord_lint(Some(vec![]));
+    let (_, diag) = TetsParser::new("# Song\n\n!+2\n\n1. `X`Lyrics.\n", config).parse();
+    assert_eq!(diag.len(), 1, "{:?}", diag);
+    assert!(matches!(diag[0].kind, DiagKind::Transposition { .. }));
 }
 
 #[test]
@@ -758,13 +1932,113 @@ fn parse_image() {
         [ver_verse(
             1,
             [
-                p([i_text("First verse. "), i_image("foo.jpg", "Foo", "")]),
-                p([i_image("bar.jpg", "Bar", "center")]),
+                p([i_text("First verse. "), i_image("foo.jpg", "Foo", &[])]),
+                p([i_image("bar.jpg", "Bar", &["center"])]),
             ],
         )],
     ));
 }
 
+#[test]
+fn parse_image_classes() {
+    let input = r#"
+# Song
+
+1. ![Foo {.qr .right}](foo.jpg) ![Bar](bar.jpg " center  fullwidth ")
+![Baz {.fullwidth}](baz.jpg "center fullwidth") ![Braces {not classes}](qux.jpg)
+"#;
+
+    let config = ParserConfig::default().image_classes(Some(vec!["qr".into()]));
+    let (songs, diag) = TetsParser::new(input, config).parse();
+    let [song]: [_; 1] = songs.unwrap().try_into().unwrap();
+    song.blocks.assert_json_eq(json!(verse_flags([ver_verse(
+        1,
+        [p([
+            i_image("foo.jpg", "Foo", &["qr", "right"]),
+            i_text(" "),
+            i_image("bar.jpg", "Bar", &["center", "fullwidth"]),
+            i_break(),
+            i_image("baz.jpg", "Baz", &["center", "fullwidth"]),
+            i_text(" "),
+            i_image("qux.jpg", "Braces {not classes}", &[]),
+        ])]
+    )])));
+    assert!(diag.is_empty());
+
+    let input = "# Song\n\n![Foo {.rigth}](foo.jpg)\n";
+    let config = ParserConfig::default().image_classes(Some(vec![]));
+    let (songs, diag) = TetsParser::new(input, config).parse();
+    songs.unwrap();
+    assert_eq!(diag.len(), 1);
+    assert!(!diag[0].is_error());
+    assert_eq!(diag[0].line, 3);
+    assert_eq!(
+        diag[0].kind,
+        DiagKind::UnknownImageClass {
+            class: "rigth".into()
+        }
+    );
+}
+
+#[test]
+fn parse_snippets() {
+    let snippets: BTreeMap<String, String> = [
+        ("trad", "Traditional, arr. !>"),
+        ("bridge", "La *la* `C`la\n!use(trad)\n\nLa"),
+        ("loop", "A\n!use(loop2)"),
+        ("loop2", "!use(loop)"),
+    ]
+    .iter()
+    .map(|(name, text)| (name.to_string(), text.to_string()))
+    .collect();
+
+    let input = "# Song\n\n`C`Lyrics !use(trad)\n!use(bridge) \\!use(trad)\n";
+    let config = ParserConfig::default().snippets(snippets.clone());
+    let (songs, diag) = TetsParser::new(input, config).parse();
+    let [song]: [_; 1] = songs.unwrap().try_into().unwrap();
+    song.blocks.assert_json_eq(json!(verse_flags([ver_none([p([
+        i_chord(
+            "C",
+            Null,
+            1,
+            [i_text("Lyrics Traditional, arr."), i_chorus_ref(Null, " "),],
+        ),
+        i_break(),
+        i_text("La "),
+        i_emph([i_text("la")]),
+        i_text(" Cla"),
+        i_break(),
+        i_text("Traditional, arr."),
+        i_chorus_ref(Null, " "),
+        i_break(),
+        i_text("La !use(trad)"),
+    ])])])));
+    assert!(diag.is_empty());
+
+    let input = "# Song\n\n!use(trad)\n\n!use(trda) !use(loop)\n";
+    let config = ParserConfig::default().snippets(snippets);
+    let (songs, diag) = TetsParser::new(input, config).parse();
+    songs.unwrap_err();
+    let diag: Vec<_> = diag.into_iter().map(|d| (d.line, d.kind)).collect();
+    assert_eq!(
+        diag,
+        [
+            (
+                5,
+                DiagKind::UnknownSnippet {
+                    name: "trda".into()
+                }
+            ),
+            (
+                5,
+                DiagKind::RecursiveSnippet {
+                    name: "loop".into()
+                }
+            ),
+        ]
+    );
+}
+
 #[test]
 fn parse_html() {
     let input = r#"
@@ -842,6 +2116,54 @@ Trailing text.
     );
 }
 
+#[test]
+fn parse_html_text() {
+    let input = r#"
+# Song
+
+<div class="note">
+  Capo <b>2</b>,
+  play softly.
+</div>
+
+<table>
+Text in a table.
+</table>
+"#;
+
+    let config = ParserConfig::default().html_text(true);
+    let (res, diag) = TetsParser::new(input, config).parse();
+
+    let songs = res.unwrap();
+    songs[0].assert_json_eq(song(
+        "Song",
+        [],
+        "english",
+        [
+            b_html([
+                json!({
+                    "type": "i-tag",
+                    "name": "div",
+                    "attrs": {"class": "note"},
+                    "text": " Capo ",
+                }),
+                json!({"type": "i-tag", "name": "b", "attrs": {}, "text": "2"}),
+                json!({"type": "i-tag", "name": "/b", "attrs": {}, "text": ", play softly."}),
+                i_tag("/div", []),
+            ]),
+            b_html([i_tag("table", []), i_tag("/table", [])]),
+        ],
+    ));
+
+    let [diag]: [_; 1] = diag.try_into().unwrap();
+    assert_eq!(
+        diag.kind,
+        DiagKind::HtmlIgnoredText {
+            text: "Text in a table.".into()
+        }
+    );
+}
+
 #[test]
 fn parse_punctuation() {
     let input = r#"# Song
@@ -914,3 +2236,181 @@ fn bom() {
     let song = parse_one(input);
     assert_eq!(&*song.title, "Song");
 }
+
+#[test]
+fn normalize_unicode() {
+    // Decomposed e + combining acute accent
+    let input = "# Cafe\u{301}\n\n1. `E\u{301}`Lyrics\n";
+    let (songs, diag) = TetsParser::new(input, ParserConfig::default()).parse();
+    let song = songs.unwrap().remove(0);
+    assert_eq!(&*song.title, "Caf\u{e9}");
+    assert_eq!(&*song.chords()[0].chord, "\u{c9}");
+    assert_eq!(diag.len(), 1);
+    assert!(!diag[0].is_error());
+    assert_eq!(diag[0].line, 1);
+    assert_eq!(diag[0].kind, DiagKind::NotNormalized);
+
+    let (_, diag) = TetsParser::new("# Caf\u{e9}\n", ParserConfig::default()).parse();
+    assert!(diag.is_empty());
+
+    let config = ParserConfig::default().normalize_unicode(false);
+    let (songs, diag) = TetsParser::new(input, config).parse();
+    assert_eq!(&*songs.unwrap()[0].title, "Cafe\u{301}");
+    assert!(diag.is_empty());
+}
+
+#[test]
+fn parse_positions() {
+    let input = "# Song `C`title\n\n1. `G`First !use(ref)\nsecond *line* `Am`here\n";
+    let mut snippets = BTreeMap::new();
+    snippets.insert("ref".to_string(), "snippet `D`text".to_string());
+
+    let visit = |song: &Song| {
+        let mut inlines = vec![];
+        song.visit_inlines(|inline| match inline {
+            Inline::Text { text, line } => inlines.push((text.to_string(), *line)),
+            Inline::Chord(chord) => inlines.push((format!("`{}`", chord.chord), chord.line)),
+            _ => {}
+        });
+        inlines
+    };
+
+    let config = ParserConfig::default()
+        .snippets(snippets.clone())
+        .record_positions(true);
+    let song = TetsParser::new(input, config).parse_one();
+    let inlines = visit(&song);
+    let inlines: Vec<_> = inlines.iter().map(|(t, l)| (t.as_str(), *l)).collect();
+    assert_eq!(
+        inlines,
+        [
+            ("Song ", Some(1)),
+            ("`C`", Some(1)),
+            ("title", Some(1)),
+            ("`G`", Some(3)),
+            // Snippet text is attributed to the reference, chords in it are kept as text
+            ("First snippet `D`text", Some(3)),
+            ("second ", Some(4)),
+            ("line", Some(4)),
+            (" ", Some(4)),
+            ("`Am`", Some(4)),
+            ("here", Some(4)),
+        ]
+    );
+
+    // Not recorded by default
+    let config = ParserConfig::default().snippets(snippets);
+    let song = TetsParser::new(input, config).parse_one();
+    assert!(visit(&song).iter().all(|(_, line)| line.is_none()));
+}
+
+#[test]
+fn parse_sources() {
+    let input = concat!(
+        "Text before the first song.\n\n",
+        "# Song 1\n## Subtitle\n\n1. Lyrics\n`C`lyrics.\n\n",
+        "# Song 2\r\n\r\n> Chorus\r\n",
+        "# Song 3\nText\nwith no final newline",
+    );
+
+    let config = ParserConfig::default().include_sources(true);
+    let songs = TetsParser::new(input, config).parse().0.unwrap();
+    let sources: Vec<_> = songs.iter().map(|s| s.source.as_deref().unwrap()).collect();
+    assert_eq!(
+        sources,
+        [
+            "Text before the first song.\n\n",
+            "# Song 1\n## Subtitle\n\n1. Lyrics\n`C`lyrics.\n\n",
+            "# Song 2\r\n\r\n> Chorus\r\n",
+            "# Song 3\nText\nwith no final newline",
+        ]
+    );
+    assert_eq!(sources.concat(), input);
+
+    // Not recorded by default
+    let songs = parse(input, false);
+    assert!(songs.iter().all(|s| s.source.is_none()));
+}
+
+#[test]
+fn parse_limits() {
+    let limits = InputLimits {
+        max_file_size: 100,
+        max_line_length: 20,
+        max_inlines: 10,
+    };
+    let try_parse = |input: &str| {
+        let config = ParserConfig::default().limits(limits);
+        let (res, diag) = TetsParser::new(input, config).parse();
+        res.unwrap_err();
+        assert_eq!(diag.len(), 1);
+        assert!(diag[0].is_error());
+        diag.into_iter().next().unwrap()
+    };
+
+    let diag = try_parse(&"# Song\n".repeat(20));
+    assert_eq!(diag.file.as_os_str(), "<test>");
+    assert_eq!(
+        diag.kind,
+        DiagKind::FileTooLarge {
+            size: 140,
+            limit: 100
+        }
+    );
+
+    let diag =
+        try_parse("# Song\n\nShort line\nThis line is way too long\nAnother long line is here\n");
+    assert_eq!(diag.line, 4);
+    assert_eq!(
+        diag.kind,
+        DiagKind::LineTooLong {
+            length: 25,
+            limit: 20
+        }
+    );
+
+    let diag = try_parse("# Song\n\n1. `C`a `D`b\n\n`C`a `D`b `E`c\n`F`d `G`e\n");
+    assert_eq!(diag.line, 5);
+    assert_eq!(diag.kind, DiagKind::TooManyInlines { limit: 10 });
+
+    // Within the limits
+    let config = ParserConfig::default().limits(limits);
+    let (res, diag) = TetsParser::new("# Song\n\n`C`a `D`b\n", config).parse();
+    res.unwrap();
+    assert!(diag.is_empty());
+}
+
+#[test]
+fn parse_adversarial() {
+    let backticks = "`C`a ".repeat(10_000);
+    let inputs = [
+        // Backtick storm on a single long line
+        "`".repeat(2_000_000),
+        format!("# Song\n\n{}", "`C`".repeat(700_000)),
+        // Backtick storm on short lines
+        format!("# Song\n\n{}", backticks.replace("a ", "a\n")),
+        // Deep emphasis nesting
+        format!("# Song\n\n{}a{}", "*".repeat(30_000), "*".repeat(30_000)),
+        format!("# Song\n\n{}a{}", "*_".repeat(15_000), "_*".repeat(15_000)),
+        // Deep blockquote and list nesting
+        format!("# Song\n\n{}a", "> ".repeat(30_000)),
+        format!("# Song\n\n{}", "- a\n".repeat(20_000)),
+        // Huge HTML block
+        format!(
+            "# Song\n\n<div>\n{}</div>\n",
+            "<b>a</b>\n".repeat(2_000_000)
+        ),
+        format!("# Song\n\n<p>{}</p>\n", "<b>a</b>".repeat(200_000)),
+    ];
+
+    for (i, input) in inputs.iter().enumerate() {
+        let (res, diag) = try_parse(input, false);
+        assert!(res.is_err(), "Input #{} not rejected", i);
+        assert!(diag.iter().any(|diag| matches!(
+            diag.kind,
+            DiagKind::FileTooLarge { .. }
+                | DiagKind::LineTooLong { .. }
+                | DiagKind::TooManyInlines { .. }
+        )));
+    }
+}
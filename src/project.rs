@@ -1,39 +1,74 @@
-use std::collections::BTreeMap;
+use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::env;
 use std::fs;
+use std::hash::{Hash, Hasher};
 use std::iter;
+use std::mem;
+use std::ops::Range;
 use std::process::Command;
 use std::process::Stdio;
 use std::str;
+use std::time::{Instant, SystemTime};
 
+use globset::{GlobBuilder, GlobMatcher};
+use semver::Version;
 use serde::de::Error as _;
 use serde::{Deserialize, Deserializer};
 
-use crate::app::App;
-use crate::book::{self, Book, Song, SongRef};
+use crate::app::{keeplevel, verbosity, App, DebugStats, InitOpts, InterruptError};
+use crate::book::{self, Book, Song, SongAlias, SongRef};
 use crate::default_project::DEFAULT_PROJECT;
-use crate::music::Notation;
+use crate::diag::{BuildDiagnostic, Phase};
+use crate::music::{MinorChords, Notation, Notations};
+use crate::parser::chordpro;
 use crate::parser::Diagnostic;
 use crate::parser::Parser;
-use crate::parser::ParserConfig;
+use crate::parser::{
+    ChordSettings, ChorusNumbers, InputLimits, ParserConfig, SoftBreaks, SongVariant, TitleChords,
+};
 use crate::prelude::*;
+use crate::render::template;
 use crate::render::tex_tools::TexConfig;
 use crate::render::tex_tools::TexTools;
 use crate::render::Renderer;
-use crate::util::ExitStatusExt;
+use crate::util::{slugify, ExitStatusExt, ProcessLines, SortOrder};
+use crate::util_cmd::SpellcheckSettings;
 
 pub use toml::Value;
 
+mod book_meta;
+pub use book_meta::BookMeta;
+mod effective;
+mod hooks;
+pub use hooks::{Hook, Hooks};
+mod labels;
+pub use labels::Labels;
 mod input;
 use input::{InputSet, SongsGlobs};
+mod legacy;
+mod origin;
+pub use origin::{Origin, Origins};
 mod output;
-pub use output::{Format, Output};
+pub(crate) use output::parse_interval;
+pub use output::{
+    Format, HtmlContent, ImageFormat, LineEndings, Output, ScriptOn, SlideUnit, Volume,
+};
+mod quarantine;
+mod setlist;
+mod state;
+use state::GeneratedFile;
+mod snippets;
+mod styles;
+pub use styles::{Style, Styles};
 
 pub type Metadata = BTreeMap<Box<str>, Value>;
 
 type TomlMap = toml::map::Map<String, Value>;
 
-fn dir_songs() -> PathBuf {
-    "songs".into()
+fn dir_songs() -> Vec<PathBuf> {
+    vec!["songs".into()]
 }
 
 fn dir_templates() -> PathBuf {
@@ -71,30 +106,144 @@ where
     }
 }
 
+/// Like `pathbuf_relative_only()`, but for one path or an array of paths, at least one is required.
+fn pathbufs_relative_only<'de, D>(de: D) -> Result<Vec<PathBuf>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(PathBuf),
+        Many(Vec<PathBuf>),
+    }
+
+    let paths = match OneOrMany::deserialize(de)? {
+        OneOrMany::One(path) => vec![path],
+        OneOrMany::Many(paths) => paths,
+    };
+    if paths.is_empty() {
+        return Err(D::Error::custom("At least one path must be configured."));
+    }
+    if let Some(path) = paths.iter().find(|path| !path.is_relative()) {
+        let err = D::Error::custom(format!(
+            "Configured paths must be relative to the project directory. Path: {:?}",
+            path
+        ));
+        return Err(err);
+    }
+    Ok(paths)
+}
+
 fn default_smart_punctuation() -> bool {
     true
 }
 
+fn default_normalize_unicode() -> bool {
+    true
+}
+
 #[derive(Deserialize, Debug)]
 pub struct Settings {
     songs: SongsGlobs,
 
-    #[serde(default = "dir_songs", deserialize_with = "pathbuf_relative_only")]
-    dir_songs: PathBuf,
+    /// Directories searched for the `songs`, later ones shadow earlier ones, see `InputSet`.
+    #[serde(default = "dir_songs", deserialize_with = "pathbufs_relative_only")]
+    dir_songs: Vec<PathBuf>,
     #[serde(default = "dir_templates", deserialize_with = "pathbuf_relative_only")]
     dir_templates: PathBuf,
     #[serde(default = "dir_output", deserialize_with = "pathbuf_relative_only")]
     dir_output: PathBuf,
 
-    #[serde(default)]
+    /// Name of the notation, resolved into `notation`, see `Settings::resolve_notation()`.
+    #[serde(default, rename = "notation")]
+    notation_name: Option<String>,
+    #[serde(skip)]
     pub notation: Notation,
+    /// User-defined notations from the `[notations]` section.
+    #[serde(default)]
+    pub notations: Notations,
+    #[serde(default)]
+    pub minor_chords: MinorChords,
     #[serde(default = "default_smart_punctuation")]
     pub smart_punctuation: bool,
-    tex: Option<TexConfig>,
+    /// Normalize song text to Unicode NFC, see `ParserConfig::normalize_unicode`.
+    #[serde(default = "default_normalize_unicode")]
+    pub normalize_unicode: bool,
+    #[serde(default)]
+    pub soft_breaks: SoftBreaks,
+    #[serde(default)]
+    pub title_chords: TitleChords,
+    /// Report chords which aren't recognized as warnings even if they aren't transposed.
+    #[serde(default)]
+    pub chord_lint: bool,
+    /// Chords which aren't reported by `chord_lint`, eg. `N.C.`.
+    #[serde(default)]
+    pub chord_lint_allow: Vec<String>,
+    /// Keep chorus numbers in songs with a single chorus, see `Settings::chorus_numbers()`.
+    #[serde(default)]
+    pub always_number_choruses: bool,
+    /// Remove chorus numbers even in songs with multiple choruses.
+    #[serde(default)]
+    pub never_number_choruses: bool,
+    /// Record the Markdown source of each song in the AST.
+    #[serde(default)]
+    pub include_sources: bool,
+    /// Make rebuilds of unchanged sources byte-identical, see `Settings::source_date_epoch()`.
+    #[serde(default)]
+    pub reproducible: bool,
+    /// Remove files generated by previous builds which are no longer produced, see `state::update()`.
+    #[serde(default)]
+    pub prune: bool,
+    /// Text snippets from the `[snippets]` section, referenced in songs with `!use(name)`.
+    #[serde(default)]
+    pub snippets: BTreeMap<String, String>,
+    /// File with more snippets, see `snippets::load()`.
+    snippets_file: Option<PathBuf>,
+    /// Variants of songs by title from the `[variants]` section, each rendered as a separate song.
+    #[serde(default)]
+    pub variants: BTreeMap<String, Vec<SongVariant>>,
+    /// Markdown files rendered before the songs, eg. a preface, relative to the project directory.
+    #[serde(default)]
+    front_matter: Vec<PathBuf>,
+    /// Markdown files rendered after the songs.
+    #[serde(default)]
+    back_matter: Vec<PathBuf>,
+    pub tex: Option<TexConfig>,
+    #[serde(default)]
+    hooks: Hooks,
+    #[serde(default)]
+    pub limits: InputLimits,
+    #[serde(default)]
+    pub chords: ChordSettings,
+    #[serde(default)]
+    pub spellcheck: SpellcheckSettings,
 
     pub output: Vec<Output>,
     #[serde(deserialize_with = "meta_default_chorus_label")]
     pub book: Metadata,
+    /// Parsed from `book.styles`, see `Settings::resolve_styles()`.
+    #[serde(skip)]
+    pub styles: Styles,
+    /// Parsed from `book.labels`, see `Settings::resolve_styles()`.
+    #[serde(skip)]
+    pub labels: Labels,
+    /// Parsed from `book.image_classes`, see `Settings::resolve_styles()`.
+    #[serde(skip)]
+    pub image_classes: Option<Vec<String>>,
+    /// Parsed from `book.description`, `book.author`, `book.url`, and `book.cover_image`,
+    /// see `Settings::resolve_styles()`.
+    #[serde(skip)]
+    pub book_meta: BookMeta,
+    /// Parsed from `book.sort`, see `Settings::resolve_styles()`.
+    #[serde(skip)]
+    pub sort: SortOrder,
+    /// Which values were set in the project file, see `Settings::effective_toml()`.
+    #[serde(skip)]
+    origins: Origins,
+    /// Deprecation warnings from reading a bard 1.x project file, see `legacy::migrate()`.
+    #[serde(skip)]
+    legacy_warnings: Vec<String>,
 }
 
 impl Settings {
@@ -103,6 +252,27 @@ impl Settings {
         major.parse().unwrap()
     }
 
+    /// Major version of bard a project file was created with, from its `version` field,
+    /// either the major version number or a full version string, eg. `"2.0.0"`.
+    /// Project files without the field are from bard 1.x.
+    fn file_version(version: Option<&Value>) -> Result<i64> {
+        let invalid = || {
+            anyhow!(
+                "'version' field expected to be an integer or a version string, eg. `version = 2`"
+            )
+        };
+
+        match version {
+            None => Ok(1),
+            Some(Value::Integer(major)) => Ok(*major),
+            Some(Value::String(version)) => {
+                let version = Version::parse(version.trim()).map_err(|_| invalid())?;
+                i64::try_from(version.major).map_err(|_| invalid())
+            }
+            Some(_) => Err(invalid()),
+        }
+    }
+
     pub fn from_file(path: &Path, project_dir: &Path) -> Result<Settings> {
         let contents = fs::read_to_string(path)
             .with_context(|| format!("Failed to read project file {:?}", path))?;
@@ -110,53 +280,277 @@ impl Settings {
         let parse_err = || format!("Could not parse project file {:?}", path);
 
         // Check version
-        let settings: TomlMap = toml::from_str(&contents).with_context(parse_err)?;
-        let version = settings.get("version").unwrap_or(&Value::Integer(1));
-        let version = version
-            .as_integer()
-            .ok_or_else(|| anyhow!("'version' field expected to be an interger"))
-            .with_context(parse_err)?;
+        let mut raw: TomlMap = toml::from_str(&contents).with_context(parse_err)?;
+        let version = Self::file_version(raw.get("version")).with_context(parse_err)?;
         let self_ver = Self::version();
-        if version < self_ver as _ {
+        let legacy_warnings = if version == 1 && self_ver == 2 {
+            legacy::migrate(&mut raw).with_context(parse_err)?
+        } else if version < self_ver as _ {
             bail!(
                 "This project was created with bard {}.x - to build with bard {}.x please follow the migration guide: https://bard.md/book/migration-{1}.html",
                 version, self_ver);
         } else if version > self_ver as _ {
             bail!("This project was created with a newer version {}.x of bard, the project cannot be built by bard {}.x", version, self_ver);
-        }
+        } else {
+            vec![]
+        };
 
-        let mut settings: Settings = toml::from_str(&contents).with_context(parse_err)?;
+        let mut settings: Settings = if legacy_warnings.is_empty() {
+            toml::from_str(&contents).with_context(parse_err)?
+        } else {
+            Value::Table(raw.clone())
+                .try_into()
+                .with_context(parse_err)?
+        };
+        settings.origins = Origins::from_toml(&raw);
+        settings.legacy_warnings = legacy_warnings;
 
+        settings.resolve_styles().with_context(parse_err)?;
+        settings.resolve_notation().with_context(parse_err)?;
         settings.resolve(project_dir)?;
         Ok(settings)
     }
 
+    pub fn origins(&self) -> &Origins {
+        &self.origins
+    }
+
+    pub fn legacy_warnings(&self) -> &[String] {
+        &self.legacy_warnings
+    }
+
+    /// Timestamp to use instead of the current time, from the `SOURCE_DATE_EPOCH` variable.
+    /// With `reproducible`, the Unix epoch is used if the variable isn't set.
+    pub fn source_date_epoch(&self) -> Option<String> {
+        match env::var("SOURCE_DATE_EPOCH") {
+            Ok(epoch) => Some(epoch),
+            Err(_) if self.reproducible => Some("0".to_string()),
+            Err(_) => None,
+        }
+    }
+
+    /// The first songs directory, where new songs are created.
     pub fn dir_songs(&self) -> &Path {
-        self.dir_songs.as_ref()
+        // NB. There's always at least one, see `pathbufs_relative_only()`
+        &self.dir_songs[0]
+    }
+
+    pub fn dirs_songs(&self) -> &[PathBuf] {
+        &self.dir_songs
+    }
+
+    pub fn dir_templates(&self) -> &Path {
+        self.dir_templates.as_ref()
     }
 
     pub fn dir_output(&self) -> &Path {
         self.dir_output.as_ref()
     }
 
+    /// Whether the `songs` field is a list of plain filenames, ie. contains no glob patterns.
+    pub fn songs_is_explicit_list(&self) -> bool {
+        self.songs.is_explicit_list()
+    }
+
+    /// Move the `styles` and `labels` tables, the `image_classes` list, the `sort` order,
+    /// and the `BookMeta` fields out of the free-form `book` metadata into typed fields.
+    fn resolve_styles(&mut self) -> Result<()> {
+        if let Some(styles) = self.book.remove("styles") {
+            self.styles = styles.try_into().context("Invalid [book.styles] section")?;
+            self.styles.validate()?;
+        }
+        if let Some(labels) = self.book.remove("labels") {
+            self.labels = labels.try_into().context("Invalid [book.labels] section")?;
+            self.labels.validate()?;
+        }
+        if let Some(classes) = self.book.remove("image_classes") {
+            let classes = classes
+                .try_into()
+                .context("Invalid book.image_classes, expected a list of class names")?;
+            self.image_classes = Some(classes);
+        }
+        if let Some(sort) = self.book.remove("sort") {
+            self.sort = sort.try_into().context("Invalid book.sort")?;
+        }
+
+        let meta: TomlMap = BookMeta::KEYS
+            .iter()
+            .filter_map(|&key| Some((key.to_string(), self.book.remove(key)?)))
+            .collect();
+        self.book_meta = Value::Table(meta)
+            .try_into()
+            .context("Invalid book metadata")?;
+        self.book_meta.validate()?;
+
+        Ok(())
+    }
+
+    /// Resolve the `notation` name, which may refer to a user-defined notation.
+    fn resolve_notation(&mut self) -> Result<()> {
+        if let Some(name) = self.notation_name.as_deref() {
+            self.notation = self.notations.get(name).ok_or_else(|| {
+                anyhow!(
+                    "Unknown notation '{}', it's neither a built-in notation nor defined in the [notations] section.",
+                    name
+                )
+            })?;
+        }
+
+        Ok(())
+    }
+
+    pub fn chorus_numbers(&self) -> ChorusNumbers {
+        match (self.always_number_choruses, self.never_number_choruses) {
+            (true, _) => ChorusNumbers::Always,
+            (false, true) => ChorusNumbers::Never,
+            (false, false) => ChorusNumbers::Auto,
+        }
+    }
+
     fn resolve(&mut self, project_dir: &Path) -> Result<()> {
-        self.dir_songs.resolve(project_dir);
+        if self.always_number_choruses && self.never_number_choruses {
+            bail!("always_number_choruses and never_number_choruses can't be both enabled.");
+        }
+
+        for dir in self.dir_songs.iter_mut() {
+            dir.resolve(project_dir);
+        }
         self.dir_templates.resolve(project_dir);
         self.dir_output.resolve(project_dir);
+        if let Some(snippets_file) = self.snippets_file.as_mut() {
+            snippets_file.resolve(project_dir);
+        }
+        for path in self
+            .front_matter
+            .iter_mut()
+            .chain(self.back_matter.iter_mut())
+        {
+            path.resolve(project_dir);
+        }
 
+        let entries = mem::take(&mut self.output);
+        for (i, entry) in entries.into_iter().enumerate() {
+            self.output.extend(entry.expand(i)?);
+        }
         for output in self.output.iter_mut() {
-            output.resolve(&self.dir_templates, &self.dir_output)?;
+            output.resolve(
+                project_dir,
+                &self.dir_templates,
+                &self.dir_output,
+                &self.notations,
+            )?;
         }
 
         Ok(())
     }
 }
 
+/// A volume of an output with the range of songs it contains, see `Project::volumes()`.
+#[derive(Debug)]
+pub struct VolumeSpan<'a> {
+    pub volume: &'a Volume,
+    /// Volume number, counted from 1
+    pub number: usize,
+    /// Indices of the songs in the volume
+    pub songs: Range<usize>,
+    pub file: PathBuf,
+}
+
+/// Report of songs left out by `bard make --quarantine`, in the output directory.
+const QUARANTINE_REPORT: &str = "quarantine.txt";
+
 #[cfg(unix)]
 static SCRIPT_EXT: &str = "sh";
 #[cfg(windows)]
 static SCRIPT_EXT: &str = "bat";
 
+/// Modification time, size and content hash of a file,
+/// used to tell whether a script modified its output file.
+#[derive(PartialEq, Eq, Debug)]
+struct FileStamp {
+    mtime: Option<SystemTime>,
+    len: u64,
+    hash: u64,
+}
+
+impl FileStamp {
+    /// Returns `None` if the file doesn't exist or can't be read.
+    fn of(path: &Path) -> Option<Self> {
+        let meta = fs::metadata(path).ok()?;
+        let content = fs::read(path).ok()?;
+        let mut hasher = DefaultHasher::new();
+        content.hash(&mut hasher);
+        Some(Self {
+            mtime: meta.modified().ok(),
+            len: meta.len(),
+            hash: hasher.finish(),
+        })
+    }
+}
+
+/// Whether a build was started by `bard make` or by `bard watch`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BuildOrigin {
+    Make,
+    Watch,
+}
+
+/// Build information passed to `Project::render()`,
+/// in `bard watch` the same one is used for all the rebuilds.
+#[derive(Debug)]
+pub struct BuildState {
+    origin: BuildOrigin,
+    /// When the scripts last ran, per output file, see `script_min_interval`.
+    script_runs: HashMap<PathBuf, Instant>,
+}
+
+impl BuildState {
+    pub fn new(origin: BuildOrigin) -> Self {
+        Self {
+            origin,
+            script_runs: HashMap::new(),
+        }
+    }
+
+    /// Check whether the script of `output` should run for `file`, returns the reason if not.
+    /// If it should, the run is recorded.
+    fn script_should_run(&mut self, output: &Output, file: &Path) -> Result<(), String> {
+        match (output.script_on, self.origin) {
+            (ScriptOn::Make, BuildOrigin::Watch) => {
+                return Err("script_on is 'make', scripts don't run in watch mode".into())
+            }
+            (ScriptOn::Watch, BuildOrigin::Make) => {
+                return Err("script_on is 'watch', scripts only run in watch mode".into())
+            }
+            _ => {}
+        }
+
+        let now = Instant::now();
+        if let Some((interval, last)) = output.script_min_interval.zip(self.script_runs.get(file)) {
+            let elapsed = now.duration_since(*last);
+            if elapsed < interval {
+                return Err(format!(
+                    "last run {}s ago, script_min_interval is {}s",
+                    elapsed.as_secs(),
+                    interval.as_secs()
+                ));
+            }
+        }
+
+        self.script_runs.insert(file.to_owned(), now);
+        Ok(())
+    }
+}
+
+/// A song file left out of the book because it has errors, see `Project::skipped_files()`.
+#[derive(Debug)]
+pub struct SkippedFile {
+    /// The file, relative to the project directory.
+    pub path: PathBuf,
+    /// The error diagnostics of the file.
+    pub errors: Vec<Diagnostic>,
+}
+
 #[derive(Debug)]
 pub struct Project {
     pub project_dir: PathBuf,
@@ -165,21 +559,44 @@ pub struct Project {
 
     project_file: PathBuf,
     input_paths: Vec<PathBuf>,
+    /// Song files with errors, left out unless `App::strict()` is set.
+    skipped_files: Vec<SkippedFile>,
+    /// See `ParserConfig::record_positions`.
+    record_positions: bool,
 }
 
 impl Project {
     pub fn new<P: AsRef<Path>>(app: &App, cwd: P) -> Result<Project> {
-        let cwd = cwd.as_ref();
-        let (project_file, project_dir) = Self::find_in_parents(cwd).ok_or_else(|| {
-            anyhow!(
-                "Could not find bard.toml file in current or parent directories\nCurrent directory: {:?}",
-                cwd,
-            )
-        })?;
+        Self::load(app, cwd.as_ref(), false)
+    }
+
+    /// Like `new()`, but with source lines recorded on text and chord inlines, see `Inline::line()`.
+    pub fn with_positions<P: AsRef<Path>>(app: &App, cwd: P) -> Result<Project> {
+        Self::load(app, cwd.as_ref(), true)
+    }
 
+    /// Load the project from a `project_file` and `project_dir` found by `locate()`.
+    pub fn from_file(app: &App, project_file: PathBuf, project_dir: PathBuf) -> Result<Project> {
+        Self::load_located(app, project_file, project_dir, false)
+    }
+
+    fn load(app: &App, cwd: &Path, record_positions: bool) -> Result<Project> {
+        let (project_file, project_dir) = Self::locate(app, cwd)?;
+        Self::load_located(app, project_file, project_dir, record_positions)
+    }
+
+    fn load_located(
+        app: &App,
+        project_file: PathBuf,
+        project_dir: PathBuf,
+        record_positions: bool,
+    ) -> Result<Project> {
         app.status("Loading", format!("project at {:?}", project_dir));
 
         let settings = Settings::from_file(&project_file, &project_dir)?;
+        for warning in settings.legacy_warnings() {
+            app.warning(warning);
+        }
         let book = Book::new(&settings);
 
         let mut project = Project {
@@ -187,17 +604,66 @@ impl Project {
             project_dir,
             settings,
             input_paths: vec![],
+            skipped_files: vec![],
             book,
+            record_positions,
         };
 
+        let start = Instant::now();
         project
             .load_md_files(app)
             .context("Failed to load input files")?;
+        app.timing("Parse", start.elapsed());
 
         Ok(project)
     }
 
-    fn find_in_parents(start_dir: &Path) -> Option<(PathBuf, PathBuf)> {
+    /// Find the project file according to the app's `ProjectOpts`:
+    /// In the `--project-dir` directory if set, otherwise in `cwd` or, unless strict mode is on, its parents.
+    /// Returns the path to the project file and the project directory.
+    pub fn locate(app: &App, cwd: &Path) -> Result<(PathBuf, PathBuf)> {
+        let opts = app.project_opts();
+
+        if let Some(dir) = opts.project_dir.as_deref() {
+            let project_dir = cwd.join(dir);
+            let project_file = project_dir.join("bard.toml");
+            if !project_file.exists() {
+                bail!(
+                    "Could not find bard.toml file in the project directory {:?}",
+                    project_dir
+                );
+            }
+            return Ok((project_file, project_dir));
+        }
+
+        let (project_file, project_dir) = Self::find_in_parents(cwd).ok_or_else(|| {
+            anyhow!(
+                "Could not find bard.toml file in current or parent directories\nCurrent directory: {:?}",
+                cwd,
+            )
+        })?;
+
+        if project_dir != cwd {
+            if opts.strict_project_dir {
+                bail!(
+                    "Found bard.toml in parent directory {:?}, but parent directories are not searched in strict project directory mode.\nCurrent directory: {:?}\nHint: Use --project-dir to select the project explicitly.",
+                    project_dir,
+                    cwd,
+                );
+            }
+
+            app.status_notice(
+                "Found",
+                format!("bard.toml in parent directory {:?}", project_dir),
+            );
+        }
+
+        Ok((project_file, project_dir))
+    }
+
+    /// Find `bard.toml` in `start_dir` or its parents.
+    /// Returns the path to the project file and the project directory.
+    pub fn find_in_parents(start_dir: &Path) -> Option<(PathBuf, PathBuf)> {
         assert!(start_dir.is_dir());
 
         let mut parent = start_dir;
@@ -220,30 +686,179 @@ impl Project {
             .try_fold(input_set, InputSet::apply_glob)?
             .finalize()?;
 
-        let diag_sink = move |diag: Diagnostic| {
+        let snippets = snippets::merge(
+            &self.settings.snippets,
+            self.settings.snippets_file.as_deref(),
+        )?;
+
+        // Errors of the file being parsed, kept for the report if the file is left out
+        let file_errors = RefCell::new(vec![]);
+        let diag_sink = |diag: Diagnostic| {
+            if diag.is_error() {
+                file_errors.borrow_mut().push(diag.clone());
+            }
             app.parser_diag(diag);
         };
+        let settings = &self.settings;
+        let project_dir = &self.project_dir;
+        let record_positions = self.record_positions;
+        let html_text = settings
+            .output
+            .iter()
+            .any(|output| output.html_content == HtmlContent::Text);
+        let config = || {
+            ParserConfig::new(settings.notation.clone(), settings.smart_punctuation)
+                .notations(settings.notations.clone())
+                .minor_chords(settings.minor_chords)
+                .soft_breaks(settings.soft_breaks)
+                .title_chords(settings.title_chords)
+                .chorus_numbers(settings.chorus_numbers())
+                .include_sources(settings.include_sources)
+                .limits(settings.limits)
+                .image_classes(settings.image_classes.clone())
+                .snippets(snippets.clone())
+                .variants(settings.variants.clone())
+                .normalize_unicode(settings.normalize_unicode)
+                .record_positions(record_positions)
+                .html_text(html_text)
+                .chord_suffixes(settings.chords.normalize.clone())
+                .chord_lint(
+                    settings
+                        .chord_lint
+                        .then(|| settings.chord_lint_allow.clone()),
+                )
+        };
 
+        // In strict mode, all the files are still parsed so that errors of all of them are reported at once
+        let mut failed_files = vec![];
         for path in self.input_paths.iter() {
             app.check_interrupted()?;
-            let source = fs::read_to_string(path)?;
-            let config = ParserConfig::new(self.settings.notation, self.settings.smart_punctuation);
-            let rel_path = path.strip_prefix(&self.project_dir).unwrap_or(path);
-            let mut parser = Parser::new(&source, rel_path, config, diag_sink);
-            let songs = parser
-                .parse()
-                .map_err(|_| anyhow!("Could not parse file {:?}", path))?;
-            self.book.add_songs(songs);
+            let mut source = fs::read_to_string(path)?;
+            let rel_path = path.strip_prefix(project_dir).unwrap_or(path);
+            if chordpro::is_chordpro(path) {
+                source = chordpro::to_markdown(&source, rel_path, &diag_sink);
+            }
+            let mut parser = Parser::new(&source, rel_path, config(), diag_sink);
+            let res = parser.parse();
+            let errors = file_errors.take();
+            match res {
+                Ok(songs) => self.book.add_songs(songs),
+                Err(_) if app.strict() => failed_files.push(path),
+                Err(_) => {
+                    // The rest of the book is still built, the file counts as a warning
+                    let diag = BuildDiagnostic::warning(
+                        Phase::Parse,
+                        "The file has errors, its songs are left out of the book",
+                    );
+                    app.diag(diag.with_file(rel_path));
+                    self.skipped_files.push(SkippedFile {
+                        path: rel_path.to_owned(),
+                        errors,
+                    });
+                }
+            }
         }
 
+        match &failed_files[..] {
+            [] => {}
+            [path] => bail!("Could not parse file {:?}", path),
+            paths => bail!(
+                "Could not parse {} files: {}",
+                paths.len(),
+                paths
+                    .iter()
+                    .map(|path| format!("{:?}", path))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+
+        for title in settings.variants.keys() {
+            let title_lc = title.trim().to_lowercase();
+            let songs = &self.book.songs;
+            if !songs
+                .iter()
+                .any(|s| s.title.trim().to_lowercase() == title_lc)
+            {
+                app.warning(format!("variants: There's no song titled '{}'", title));
+            }
+        }
+
+        // Front and back matter is parsed as a whole, H1s don't start new songs
+        let load_matter = |path: &PathBuf| {
+            app.check_interrupted()?;
+            let source = fs::read_to_string(path)
+                .with_context(|| format!("Could not read file {:?}", path))?;
+            let rel_path = path.strip_prefix(project_dir).unwrap_or(path);
+            let mut parser = Parser::new(&source, rel_path, config(), diag_sink);
+            let matter = parser
+                .parse_matter()
+                .map_err(|_| anyhow!("Could not parse file {:?}", path))?;
+            Ok(matter)
+        };
+        self.book.front_matter = settings
+            .front_matter
+            .iter()
+            .map(load_matter)
+            .collect::<Result<_>>()?;
+        self.book.back_matter = settings
+            .back_matter
+            .iter()
+            .map(load_matter)
+            .collect::<Result<_>>()?;
+
         self.book
             .postprocess(&self.settings.dir_output, app.img_cache())?;
+        self.settings
+            .book_meta
+            .resolve_cover_image(app, &self.settings.dir_output)?;
 
         Ok(())
     }
 
-    pub fn init<P: AsRef<Path>>(project_dir: P) -> Result<()> {
-        DEFAULT_PROJECT.resolve(project_dir.as_ref()).create()
+    /// Create the default project in `project_dir`, customized according to the `opts` of `bard init`.
+    pub fn init<P: AsRef<Path>>(project_dir: P, opts: &InitOpts) -> Result<()> {
+        DEFAULT_PROJECT
+            .resolve_with(project_dir.as_ref(), opts)
+            .create()
+    }
+
+    /// Whether the font setup of the default templates, `fonts/fonts.tex` and `fonts/fonts.css`,
+    /// is in the output directory. It's not there in projects created with `bard init --no-fonts`.
+    pub fn fonts_available(&self) -> bool {
+        let fonts_dir = self.settings.dir_output().join("fonts");
+        ["fonts.tex", "fonts.css"]
+            .iter()
+            .all(|file| fonts_dir.join(file).is_file())
+    }
+
+    /// Song files left out of the book because they have errors, unless `App::strict()` is set.
+    pub fn skipped_files(&self) -> &[SkippedFile] {
+        &self.skipped_files
+    }
+
+    /// List the skipped files and their errors, so that they're noticed after the build's output.
+    pub fn report_skipped_files(&self, app: &App) {
+        let count = self.skipped_files.len();
+        if count == 0 {
+            return;
+        }
+
+        app.status_notice(
+            "Skipped",
+            format!(
+                "{} file{} with errors, the songs in {} are not in the book:",
+                count,
+                if count == 1 { "" } else { "s" },
+                if count == 1 { "it" } else { "them" },
+            ),
+        );
+        for file in self.skipped_files.iter() {
+            app.indent(file.path.display());
+            for error in file.errors.iter() {
+                app.indent(format!("  line {}: {}", error.line, error.kind));
+            }
+        }
     }
 
     pub fn book_section(&self) -> &Metadata {
@@ -258,12 +873,220 @@ impl Project {
         &self.book.songs_sorted
     }
 
-    fn run_script(&self, app: &App, output: &Output) -> Result<()> {
+    /// Sort order of the songs in `output`, the book's unless the output overrides it.
+    pub fn sort_order<'s>(&'s self, output: &'s Output) -> &'s SortOrder {
+        output.sort.as_ref().unwrap_or(&self.settings.sort)
+    }
+
+    pub fn aliases(&self) -> &[SongAlias] {
+        &self.book.aliases
+    }
+
+    pub fn has_rtl(&self) -> bool {
+        self.book.has_rtl
+    }
+
+    /// Indices of songs in the output's set list, in the set list order, if the output has one.
+    pub fn setlist(&self, output: &Output) -> Result<Option<Vec<usize>>> {
+        let path = match output.setlist.as_deref() {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+
+        let titles = setlist::load(path)?;
+        setlist::resolve(path, &titles, self.songs()).map(Some)
+    }
+
+    /// Indices of the songs rendered by the `output`: those in its set list, in the set list order,
+    /// or those matching its `songs_filter`, in the book order. `None` if the output renders all the songs.
+    pub fn output_songs(&self, output: &Output) -> Result<Option<Vec<usize>>> {
+        if let Some(setlist) = self.setlist(output)? {
+            return Ok(Some(setlist));
+        }
+        if output.songs_filter.is_empty() {
+            return Ok(None);
+        }
+
+        // Song file names relative to the songs directories, as in the `songs` setting
+        let file_names: Vec<_> = self
+            .songs()
+            .iter()
+            .map(|song| {
+                let path = self.project_dir.join(&song.location.file);
+                self.settings
+                    .dir_songs
+                    .iter()
+                    .rev()
+                    .find_map(|dir| path.strip_prefix(dir).ok())
+                    .map(Path::to_owned)
+                    .unwrap_or(path)
+            })
+            .collect();
+        setlist::filter(&output.songs_filter, self.songs(), &file_names)
+            .with_context(|| format!("Invalid songs_filter of output {:?}", output.file))
+            .map(Some)
+    }
+
+    /// Split songs into the volumes of the `output`.
+    /// If the output doesn't define any volumes, an empty `Vec` is returned.
+    pub fn volumes<'o>(&self, output: &'o Output) -> Result<Vec<VolumeSpan<'o>>> {
+        let songs = self.songs();
+        let mut start = 0;
+
+        output
+            .volumes
+            .iter()
+            .enumerate()
+            .map(|(i, volume)| {
+                let end = if let Some(pattern) = volume.until_song.as_deref() {
+                    let matcher = GlobBuilder::new(pattern)
+                        .case_insensitive(true)
+                        .build()?
+                        .compile_matcher();
+                    songs[start..]
+                        .iter()
+                        .rposition(|song| matcher.is_match(&*song.title))
+                        .map(|pos| start + pos + 1)
+                        .ok_or_else(|| {
+                            anyhow!(
+                                "Volume '{}': No song matching '{}' found after the previous volume.",
+                                volume.name,
+                                pattern
+                            )
+                        })?
+                } else if let Some(until) = volume.until_index {
+                    if until <= start || until > songs.len() {
+                        bail!(
+                            "Volume '{}': until_index {} is out of range, expected a song number between {} and {}.",
+                            volume.name,
+                            until,
+                            start + 1,
+                            songs.len()
+                        );
+                    }
+                    until
+                } else {
+                    songs.len()
+                };
+
+                if end <= start {
+                    bail!("Volume '{}' doesn't contain any songs.", volume.name);
+                }
+
+                let span = VolumeSpan {
+                    volume,
+                    number: i + 1,
+                    songs: start..end,
+                    file: output.volume_file(i + 1),
+                };
+                start = end;
+                Ok(span)
+            })
+            .collect()
+    }
+
+    /// Indices of the songs matching `pattern` of `bard make --song`,
+    /// either a song title or its slug, or a glob, all matched case-insensitively.
+    ///
+    /// The pattern is only used as a glob if it matches no title exactly,
+    /// so that titles with glob metacharacters such as `[` can be selected as they are.
+    pub fn match_songs(&self, pattern: &str) -> Result<Vec<usize>> {
+        let pattern_lower = pattern.to_lowercase();
+        let mut songs: Vec<_> = self
+            .songs()
+            .iter()
+            .enumerate()
+            .filter(|(_, song)| {
+                song.title.to_lowercase() == pattern_lower || slugify(&song.title) == pattern_lower
+            })
+            .map(|(idx, _)| idx)
+            .collect();
+
+        if songs.is_empty() && pattern.contains(['*', '?', '[', '{']) {
+            let matcher = GlobBuilder::new(pattern)
+                .case_insensitive(true)
+                .build()
+                .with_context(|| format!("Invalid song title pattern '{}'", pattern))?
+                .compile_matcher();
+
+            songs = self
+                .songs()
+                .iter()
+                .enumerate()
+                .filter(|(_, song)| matcher.is_match(&*song.title))
+                .map(|(idx, _)| idx)
+                .collect();
+        }
+
+        if songs.is_empty() {
+            bail!(
+                "No song matching '{}' found. Titles are matched case-insensitively, glob patterns such as 'Amazing*' can be used.",
+                pattern
+            );
+        }
+
+        Ok(songs)
+    }
+
+    /// Files of the `songs` rendered separately from a PDF `output` by `bard make --song`.
+    /// The files are named after song slugs, such that they don't collide with each other
+    /// nor with files of the project's outputs.
+    pub fn song_files(&self, output: &Output, songs: &[usize]) -> Result<Vec<PathBuf>> {
+        let output_files: Vec<_> = self
+            .settings
+            .output
+            .iter()
+            .flat_map(|o| {
+                iter::once(o.file.clone())
+                    .chain((1..=o.volumes.len()).map(move |i| o.volume_file(i)))
+            })
+            .collect();
+
+        let mut files: Vec<PathBuf> = vec![];
+        for &idx in songs {
+            let song = &self.songs()[idx];
+            let slug = slugify(&song.title);
+            let mut file = output.song_file(&slug);
+            for n in 2.. {
+                if !files.contains(&file) {
+                    break;
+                }
+                file = output.song_file(&format!("{}-{}", slug, n));
+            }
+
+            let tex_file = file.with_extension("tex");
+            if let Some(other) = output_files.iter().find(|&f| *f == file || *f == tex_file) {
+                bail!(
+                    "Song '{}': File {:?} would overwrite the output file {:?}.",
+                    song.title,
+                    file.file_name().unwrap(),
+                    other.file_name().unwrap()
+                );
+            }
+
+            files.push(file);
+        }
+
+        Ok(files)
+    }
+
+    fn run_script(
+        &self,
+        app: &App,
+        output: &Output,
+        file: &Path,
+        build: &mut BuildState,
+    ) -> Result<()> {
         let script_fn = match output.script.as_deref() {
             Some(s) => format!("{}.{}", s, SCRIPT_EXT),
             None => return Ok(()),
         };
 
+        if let Err(reason) = build.script_should_run(output, file) {
+            app.status("Skipping", format!("script '{}': {}", script_fn, reason));
+            return Ok(());
+        }
+
         let script_path = self.settings.dir_output().join(&script_fn);
         if !script_path.exists() {
             bail!(
@@ -273,67 +1096,505 @@ impl Project {
         }
 
         app.status("Running", format!("script '{}'", script_fn));
+        let stamp = FileStamp::of(file);
+        let start = Instant::now();
         let mut child = Command::new(script_path)
             .current_dir(self.settings.dir_output())
             .stdin(Stdio::null())
             .stdout(Stdio::inherit())
             .stderr(Stdio::inherit())
             .env("BARD", app.bard_exe())
-            .env("OUTPUT", output.file.as_os_str())
-            .env("OUTPUT_STEM", output.file.file_stem().unwrap()) // NB. unwrap is fine here, there's always a stem
+            .env("OUTPUT", file.as_os_str())
+            .env("OUTPUT_STEM", file.file_stem().unwrap()) // NB. unwrap is fine here, there's always a stem
             .env("PROJECT_DIR", self.project_dir.as_os_str())
             .env("OUTPUT_DIR", self.settings.dir_output().as_os_str())
             .spawn()?;
         app.child_wait(&mut child)?.into_result()?;
+        app.timing("Script", start.elapsed());
+
+        let modified = FileStamp::of(file) != stamp;
+        if app.verbosity() >= verbosity::VERBOSE {
+            let what = if modified {
+                "modified"
+            } else {
+                "did not modify"
+            };
+            app.indent(format!("Script '{}' {} the output file", script_fn, what));
+        }
+        if output.script_expect_modify && !modified {
+            bail!(
+                "Script '{}' did not modify the output file {:?}, but script_expect_modify is set.",
+                script_fn,
+                file.file_name().unwrap()
+            );
+        }
 
         Ok(())
     }
 
-    pub fn render(&self, app: &App) -> Result<()> {
+    /// Run the `on_update` command of `output` after `file` was rewritten, only in watch mode.
+    /// A failure is reported as a warning, so that watching goes on.
+    fn notify_update(&self, app: &App, output: &Output, file: &Path, build: &BuildState) {
+        let on_update = match output.on_update.as_ref() {
+            Some(on_update) => on_update,
+            None => return,
+        };
+        if build.origin != BuildOrigin::Watch || !app.notify() {
+            return;
+        }
+        if output.is_pdf() && !app.post_process() {
+            // Without TeX the PDF file isn't rewritten
+            return;
+        }
+
+        let filename = file.file_name().unwrap();
+        app.status("Notifying", format!("on_update for {:?}", filename));
+        let res = on_update.command_for_file(file).and_then(|mut cmd| {
+            let mut child = cmd
+                .current_dir(self.settings.dir_output())
+                .stdin(Stdio::null())
+                .stdout(Stdio::inherit())
+                .stderr(Stdio::inherit())
+                .env("BARD", app.bard_exe())
+                .env("OUTPUT", file.as_os_str())
+                .env("PROJECT_DIR", self.project_dir.as_os_str())
+                .env("OUTPUT_DIR", self.settings.dir_output().as_os_str())
+                .spawn()?;
+            app.child_wait(&mut child)?.into_result()
+        });
+
+        if let Err(err) = res {
+            let msg = format!("The on_update command failed: {:#}", err);
+            app.diag(BuildDiagnostic::warning(Phase::Script, msg).with_file(file));
+        }
+    }
+
+    fn run_hook(&self, app: &App, name: &str, hook: Option<&Hook>) -> Result<()> {
+        let hook = match hook {
+            Some(hook) => hook,
+            None => return Ok(()),
+        };
+
+        app.status("Running", format!("{} hook", name));
+        let mut child = hook
+            .command()?
+            .current_dir(&self.project_dir)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .env("BARD", app.bard_exe())
+            .env("PROJECT_DIR", self.project_dir.as_os_str())
+            .env("OUTPUT_DIR", self.settings.dir_output().as_os_str())
+            .spawn()
+            .with_context(|| format!("Could not run {} hook", name))?;
+
+        let mut ps_lines =
+            ProcessLines::new(child.stdout.take().unwrap(), child.stderr.take().unwrap());
+        app.subprocess_output(&mut ps_lines, name, name)?;
+        // In quiet mode the output isn't printed, but it's still collected for error reporting
+        while ps_lines.read_line(app.interrupt_flag())?.is_some() {}
+
+        app.child_wait(&mut child)?
+            .into_result()
+            .map_err(|err| {
+                let output: String = ps_lines
+                    .collected_lines()
+                    .map(String::from_utf8_lossy)
+                    .collect();
+                let output = output.trim_end();
+                if output.is_empty() {
+                    err
+                } else {
+                    err.context(format!("Hook output:\n{}", output))
+                }
+            })
+            .with_context(|| format!("The {} hook failed", name))
+            .map_err(|err| {
+                let msg = format!("{:#}", err);
+                app.record_diag(BuildDiagnostic::error(Phase::Script, msg));
+                err
+            })
+    }
+
+    /// Let the user know about `make` options that don't apply to this project.
+    /// This is only done once per run, not on every rebuild in watch mode.
+    pub fn check_make_opts(&self, app: &App) {
+        let has_pdf = self.settings.output.iter().any(|o| o.is_pdf());
+        // Images outputs keep the HTML pages of the slides
+        let has_images = self
+            .settings
+            .output
+            .iter()
+            .any(|o| o.format() == Format::Images);
+
+        if app.keep_interm() > keeplevel::NONE && !has_pdf && !has_images {
+            app.warning("the --keep flag has no effect: no PDF outputs in this project");
+        }
+
+        if app.quarantine() && !has_pdf {
+            app.warning("the --quarantine flag has no effect: no PDF outputs in this project");
+        }
+
+        if !app.post_process() && app.verbosity() >= verbosity::VERBOSE {
+            let has_scripts = self.settings.output.iter().any(|o| o.script.is_some());
+            let hooks = &self.settings.hooks;
+            let has_hooks = hooks.pre_build.is_some() || hooks.post_build.is_some();
+            if !has_pdf && !has_scripts && !has_hooks {
+                app.indent("--no-postprocess: nothing to skip, no PDF outputs, scripts, or hooks in this project");
+            }
+        }
+    }
+
+    /// Outputs rendered by `render()`, with `bard make --output` only the ones whose file name
+    /// matches one of the names or glob patterns, otherwise all of them.
+    /// Returns the outputs along with the patterns that didn't match any output.
+    pub fn selected_outputs<'p>(
+        &self,
+        patterns: &'p [String],
+    ) -> Result<(Vec<&Output>, Vec<&'p str>)> {
+        if patterns.is_empty() {
+            return Ok((self.settings.output.iter().collect(), vec![]));
+        }
+
+        let matchers = patterns
+            .iter()
+            .map(|pattern| {
+                GlobBuilder::new(pattern)
+                    .build()
+                    .map(|glob| glob.compile_matcher())
+                    .with_context(|| format!("Invalid output file pattern '{}'", pattern))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let is_match = |matcher: &GlobMatcher, output: &Output| {
+            matcher.is_match(output.output_filename().as_ref())
+        };
+
+        let outputs = self
+            .settings
+            .output
+            .iter()
+            .filter(|output| matchers.iter().any(|m| is_match(m, output)))
+            .collect();
+        let unmatched = patterns
+            .iter()
+            .zip(matchers.iter())
+            .filter(|(_, m)| {
+                !self
+                    .settings
+                    .output
+                    .iter()
+                    .any(|output| is_match(m, output))
+            })
+            .map(|(pattern, _)| pattern.as_str())
+            .collect();
+        Ok((outputs, unmatched))
+    }
+
+    pub fn render(&self, app: &App, build: &mut BuildState) -> Result<()> {
+        // With `bard make --output`, only the matching outputs are rendered
+        let (outputs, unmatched) = self.selected_outputs(app.outputs())?;
+        if outputs.is_empty() && !unmatched.is_empty() {
+            let names: Vec<_> = self
+                .settings
+                .output
+                .iter()
+                .map(|output| output.output_filename())
+                .collect();
+            bail!(
+                "--output: No outputs match '{}', the outputs of this project are: {}",
+                unmatched.join("', '"),
+                names.join(", ")
+            );
+        }
+        if !unmatched.is_empty() {
+            app.warning(format!(
+                "--output: No outputs match '{}'",
+                unmatched.join("', '")
+            ));
+        }
+
+        if app.post_process() {
+            self.run_hook(app, "pre_build", self.settings.hooks.pre_build.as_ref())?;
+        }
+
         fs::create_dir_all(&self.settings.dir_output)?;
 
-        if self.settings.output.iter().any(|o| o.is_pdf()) {
+        // Songs left out of outputs with `bard make --quarantine`
+        let mut quarantined = vec![];
+
+        // With `bard make --song`, only PDF outputs are rendered, one file per matching song
+        let songs = app
+            .song()
+            .map(|pattern| self.match_songs(pattern))
+            .transpose()?;
+        if songs.is_some() && !outputs.iter().any(|o| o.is_pdf()) {
+            bail!("--song: There are no PDF outputs in this project to render the songs from.");
+        }
+        if outputs.iter().any(|o| o.is_pdf()) {
             // Initialize Tex tools ahead of actual rendering so that
             // errors are reported early...
             TexTools::initialize(app, self.settings.tex.as_ref())
                 .context("Could not initialize TeX tools.")?;
         }
 
-        self.settings.output.iter().try_for_each(|output| {
-            app.check_interrupted()?;
-            app.status("Rendering", output.output_filename());
-            let context = || {
-                format!(
-                    "Could not render output file {:?}",
-                    output.file.file_name().unwrap()
-                )
-            };
-
-            let renderer = Renderer::new(self, output, app.img_cache()).with_context(context)?;
-            let tpl_version = renderer.version();
+        outputs
+            .into_iter()
+            .filter(|o| songs.is_none() || o.is_pdf())
+            .try_for_each(|output| {
+                app.check_interrupted()?;
+                app.status("Rendering", output.output_filename());
+                let start = Instant::now();
+                let context = || {
+                    format!(
+                        "Could not render output file {:?}",
+                        output.file.file_name().unwrap()
+                    )
+                };
+
+                // The output file may be in a subdirectory of the output dir
+                if let Some(parent) = output.file.parent() {
+                    fs::create_dir_all(parent).with_context(|| {
+                        format!("Could not create output directory {:?}", parent)
+                    })?;
+                }
 
-            let res = renderer.render(app).with_context(context).and_then(|_| {
-                if app.post_process() {
-                    self.run_script(app, output).with_context(|| {
-                        format!(
-                            "Could not run script for output file {:?}",
-                            output.file.file_name().unwrap()
-                        )
+                let renderer = Renderer::new(app, self, output).with_context(context)?;
+                let tpl_version = renderer.version();
+
+                let mut script = |file: &Path| -> Result<()> {
+                    if app.post_process() {
+                        self.run_script(app, output, file, build)
+                            .map_err(|err| {
+                                let diag =
+                                    BuildDiagnostic::error(Phase::Script, format!("{:#}", err));
+                                app.record_diag(diag.with_file(file));
+                                err
+                            })
+                            .with_context(|| {
+                                format!(
+                                    "Could not run script for output file {:?}",
+                                    file.file_name().unwrap()
+                                )
+                            })?;
+                    }
+                    self.notify_update(app, output, file, build);
+                    Ok(())
+                };
+
+                let volumes = self.volumes(output).with_context(context)?;
+                let res = if let Some(songs) = songs.as_deref() {
+                    let files = self.song_files(output, songs).with_context(context)?;
+                    songs.iter().zip(files.iter()).try_for_each(|(&idx, file)| {
+                        app.check_interrupted()?;
+                        app.indent(format!(
+                            "Song '{}': {}",
+                            self.songs()[idx].title,
+                            file.file_name().unwrap().to_string_lossy()
+                        ));
+                        renderer
+                            .render_song(app, idx, file)
+                            .with_context(context)
+                            .and_then(|_| script(file))
                     })
+                } else if volumes.is_empty() {
+                    renderer
+                        .render(app)
+                        .or_else(|err| {
+                            let interrupted = err.downcast_ref::<InterruptError>().is_some();
+                            if app.quarantine() && output.is_pdf() && !interrupted {
+                                let left_out =
+                                    self.render_quarantined(app, &renderer, output, err)?;
+                                quarantined.push((output, left_out));
+                                Ok(())
+                            } else {
+                                Err(err)
+                            }
+                        })
+                        .with_context(context)
+                        .and_then(|_| script(&output.file))
                 } else {
-                    Ok(())
+                    volumes.iter().try_for_each(|volume| {
+                        app.check_interrupted()?;
+                        app.indent(format!(
+                            "Volume '{}': {}",
+                            volume.volume.name,
+                            volume.file.file_name().unwrap().to_string_lossy()
+                        ));
+                        renderer
+                            .render_volume(app, &volumes, volume)
+                            .with_context(context)
+                            .and_then(|_| script(&volume.file))
+                    })
+                };
+
+                // Perform version check of the template (if the Render supports it and there is a template file).
+                // This is done after rendering and preprocessing so that the CLI messages are at the bottom of the log.
+                // Otherwise they tend to be far behind eg. TeX output etc.
+                if let Some((tpl_version, tpl_path)) = tpl_version.zip(output.template.as_ref()) {
+                    book::version::compat_check(app, tpl_path, &tpl_version);
                 }
-            });
 
-            // Perform version check of the template (if the Render supports it and there is a template file).
-            // This is done after rendering and preprocessing so that the CLI messages are at the bottom of the log.
-            // Otherwise they tend to be far behind eg. TeX output etc.
-            if let Some((tpl_version, tpl_path)) = tpl_version.zip(output.template.as_ref()) {
-                book::version::compat_check(app, tpl_path, &tpl_version);
+                if res.is_ok() {
+                    app.finished(output.output_filename(), start.elapsed());
+                }
+                res
+            })?;
+
+        if app.quarantine() {
+            self.write_quarantine_report(&quarantined)?;
+        }
+
+        // With --song, only handouts are rendered, the outputs are left as they are
+        if songs.is_none() {
+            let prune = app.prune() || self.settings.prune;
+            state::update(
+                app,
+                self.settings.dir_output(),
+                self.generated_files()?,
+                prune,
+            )
+            .context("Could not update the record of generated files")?;
+        }
+
+        if app.post_process() {
+            self.run_hook(app, "post_build", self.settings.hooks.post_build.as_ref())?;
+        }
+
+        Ok(())
+    }
+
+    /// Files rendered for the outputs, recorded for `bard make --prune`.
+    fn generated_files(&self) -> Result<Vec<GeneratedFile>> {
+        let dir_output = self.settings.dir_output();
+        let mut files = vec![];
+        for output in self.settings.output.iter() {
+            let name = output.output_filename();
+            let slides = output.format() == Format::Images;
+            let volumes = self.volumes(output)?;
+            if volumes.is_empty() {
+                files.extend(GeneratedFile::new(dir_output, &output.file, &name, slides));
+            } else {
+                files.extend(
+                    volumes
+                        .iter()
+                        .filter_map(|v| GeneratedFile::new(dir_output, &v.file, &name, slides)),
+                );
             }
+        }
+        Ok(files)
+    }
 
-            res
-        })
+    /// Find the songs that make rendering of the PDF `output` fail with `err`
+    /// and render the output without them, see `bard make --quarantine`.
+    /// Returns the songs left out.
+    fn render_quarantined(
+        &self,
+        app: &App,
+        renderer: &Renderer,
+        output: &Output,
+        err: Error,
+    ) -> Result<Vec<usize>> {
+        let msg = format!(
+            "Rendering {} failed, looking for songs that break it ...",
+            output.output_filename()
+        );
+        app.diag(BuildDiagnostic::warning(Phase::Tex, msg));
+
+        let songs = match self.output_songs(output)? {
+            Some(songs) => songs,
+            None => (0..self.songs().len()).collect(),
+        };
+        let stem = output
+            .file
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy();
+        let probe_file = output
+            .file
+            .with_file_name(format!("{}-quarantine.pdf", stem));
+        let mut quiet_app = app.clone();
+        quiet_app.set_verbosity(verbosity::QUIET);
+
+        let mut probes = 0;
+        let failing = quarantine::find_failing(&songs, |set| {
+            app.check_interrupted()?;
+            probes += 1;
+            app.status("Probing", format!("{} songs, pass {}", set.len(), probes));
+            let res = renderer.render_probe(&quiet_app, set, &probe_file);
+            let _ = fs::remove_file(&probe_file);
+            match res {
+                Ok(()) => Ok(false),
+                Err(err) if err.downcast_ref::<InterruptError>().is_some() => Err(err),
+                Err(_) => Ok(true),
+            }
+        });
+        let failing = match failing {
+            Ok(failing) if failing.is_empty() => {
+                return Err(err.context(
+                    "--quarantine: The failure could not be reproduced with a single TeX pass.",
+                ))
+            }
+            Ok(failing) => failing,
+            Err(quarantine_err) if quarantine_err.downcast_ref::<InterruptError>().is_some() => {
+                return Err(quarantine_err)
+            }
+            Err(quarantine_err) => {
+                return Err(err.context(format!("--quarantine: {}", quarantine_err)))
+            }
+        };
+
+        for &idx in failing.iter() {
+            let msg = format!(
+                "Leaving out song '{}', it breaks the TeX run",
+                self.songs()[idx].title
+            );
+            app.diag(BuildDiagnostic::warning(Phase::Tex, msg));
+        }
+        app.status(
+            "Rendering",
+            format!("{} without the failing songs", output.output_filename()),
+        );
+        let remaining: Vec<_> = songs
+            .into_iter()
+            .filter(|idx| !failing.contains(idx))
+            .collect();
+        renderer.render_songs(app, &remaining)?;
+
+        Ok(failing)
+    }
+
+    /// Write the songs left out by `bard make --quarantine` in the output directory,
+    /// or remove the report of a previous build if there are none.
+    fn write_quarantine_report(&self, quarantined: &[(&Output, Vec<usize>)]) -> Result<()> {
+        let path = self.settings.dir_output.join(QUARANTINE_REPORT);
+        if quarantined.is_empty() {
+            if path.exists() {
+                fs::remove_file(&path)
+                    .with_context(|| format!("Could not remove file {:?}", path))?;
+            }
+            return Ok(());
+        }
+
+        let mut report = String::from(
+            "Songs left out by bard make --quarantine because they break the TeX run\n",
+        );
+        for (output, songs) in quarantined {
+            report.push_str(&format!("\n{}:\n", output.output_filename()));
+            for &idx in songs {
+                report.push_str(&format!("  {}\n", self.songs()[idx].title));
+            }
+        }
+
+        fs::write(&path, report).with_context(|| format!("Could not write file {:?}", path))
+    }
+
+    /// Sizes of the book and of the `app`'s caches, see `App::debug_stats()`.
+    pub fn debug_stats(&self, app: &App) -> DebugStats {
+        DebugStats {
+            ast: self.book.ast_stats(),
+            img_cache: app.img_cache().len(),
+            regex_cache: template::regex_cache_len(),
+        }
     }
 
     pub fn input_paths(&self) -> &Vec<PathBuf> {
@@ -356,6 +1617,24 @@ impl Project {
             .iter()
             .filter_map(Output::template_path);
 
+        // Set lists:
+        let setlists = self
+            .settings
+            .output
+            .iter()
+            .filter_map(|o| o.setlist.as_deref());
+
+        // Snippets file:
+        let snippets = self.settings.snippets_file.as_deref();
+
+        // Front and back matter:
+        let matter = self
+            .settings
+            .front_matter
+            .iter()
+            .chain(self.settings.back_matter.iter())
+            .map(PathBuf::as_ref);
+
         // Images:
         let images = self.book.iter_images().map(|i| i.full_path());
 
@@ -363,6 +1642,9 @@ impl Project {
         iter::once(self.project_file.as_path())
             .chain(inputs)
             .chain(templates)
+            .chain(setlists)
+            .chain(snippets)
+            .chain(matter)
             .chain(images)
     }
 }
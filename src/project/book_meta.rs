@@ -0,0 +1,86 @@
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::app::App;
+use crate::book::Image;
+use crate::prelude::*;
+
+/// Typed fields of the `[book]` section describing the book for sharing,
+/// the default HTML template emits them as page metadata and Open Graph tags.
+#[derive(Serialize, Deserialize, Default, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct BookMeta {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    /// Public URL of the HTML songbook.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// Path of the cover image relative to the output directory, resolved into `cover_image`.
+    #[serde(rename(deserialize = "cover_image"), skip_serializing)]
+    pub(crate) cover_image_path: Option<String>,
+    /// The cover image, only set if the file could be read, see `BookMeta::resolve_cover_image()`.
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub cover_image: Option<Image>,
+    /// URL of the cover image, relative to `url` if it's set, otherwise the same as the path.
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub cover_image_url: Option<String>,
+}
+
+impl BookMeta {
+    /// Keys of the `[book]` section parsed into `BookMeta`.
+    pub const KEYS: &'static [&'static str] = &["description", "author", "url", "cover_image"];
+
+    pub fn validate(&self) -> Result<()> {
+        let url = match self.url.as_deref() {
+            Some(url) => url,
+            None => return Ok(()),
+        };
+
+        let parsed = Url::parse(url).with_context(|| format!("Invalid book.url '{}'", url))?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            bail!(
+                "Invalid book.url '{}', expected an http:// or https:// URL",
+                url
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Resolve the cover image in `dir_output` and read its size, the same way as images in songs.
+    /// The image URL is resolved relative to `url`, ie. as if the HTML file was in the output directory.
+    /// If the file can't be read, a warning is reported and the image is left out,
+    /// so that templates don't link to a missing file.
+    pub fn resolve_cover_image(&mut self, app: &App, dir_output: &Path) -> Result<()> {
+        self.cover_image = None;
+        self.cover_image_url = None;
+        let path = match self.cover_image_path.as_deref() {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+
+        let mut image = Image::new(path.into(), "".into(), vec![]);
+        image
+            .resolve(dir_output, app.img_cache())
+            .context("Invalid book.cover_image")?;
+        if let Err(err) = image.check() {
+            app.warning(format!(
+                "book.cover_image: {:#}, the image is left out of the page metadata",
+                err
+            ));
+            return Ok(());
+        }
+
+        // The URL was validated in `validate()`
+        let base = self.url.as_deref().and_then(|url| Url::parse(url).ok());
+        self.cover_image_url = match base.and_then(|base| base.join(path).ok()) {
+            Some(url) => Some(url.into()),
+            None => Some(path.to_string()),
+        };
+        self.cover_image = Some(image);
+
+        Ok(())
+    }
+}
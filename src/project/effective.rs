@@ -0,0 +1,403 @@
+use std::fmt::{self, Write as _};
+
+use serde::Serialize;
+
+use super::{Format, Origin, Settings};
+use crate::prelude::*;
+use crate::render::tex_tools::TexConfig;
+
+/// Writes TOML source with each value annotated with its origin in a trailing comment.
+#[derive(Default)]
+struct ConfigWriter {
+    out: String,
+}
+
+impl ConfigWriter {
+    fn key(key: &str) -> String {
+        let bare = !key.is_empty()
+            && key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        if bare {
+            key.to_string()
+        } else {
+            toml::Value::String(key.into()).to_string()
+        }
+    }
+
+    fn comment(&mut self, text: impl fmt::Display) {
+        writeln!(self.out, "# {}", text).unwrap();
+    }
+
+    fn header(&mut self, header: &str) {
+        writeln!(self.out, "\n{}", header).unwrap();
+    }
+
+    fn value<T>(&mut self, key: &str, value: &T, origin: Origin) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        let value = toml::Value::try_from(value)
+            .with_context(|| format!("Could not serialize configuration value '{}'", key))?;
+        writeln!(self.out, "{} = {}  # {}", Self::key(key), value, origin).unwrap();
+        Ok(())
+    }
+
+    /// Write an optional value, a commented-out note is written if it's not set.
+    fn option<T>(&mut self, key: &str, value: Option<&T>, origin: Origin, unset: &str) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        match value {
+            Some(value) => self.value(key, value, origin),
+            None => {
+                writeln!(self.out, "# {} is not set, {}", key, unset).unwrap();
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Path relative to `base` if possible, as it would be written in `bard.toml`.
+fn relative<'a>(path: &'a Path, base: &Path) -> &'a Path {
+    path.strip_prefix(base).unwrap_or(path)
+}
+
+impl Settings {
+    /// Render the effective configuration as `bard.toml` source,
+    /// annotating each value with its origin.
+    ///
+    /// Every key of the settings must be written here, the `show_config_all_keys` test
+    /// checks this against the fields known to serde.
+    pub fn effective_toml(&self, project_dir: &Path) -> Result<String> {
+        let origins = &self.origins;
+        let mut w = ConfigWriter::default();
+
+        w.comment(format_args!(
+            "Effective configuration of the project at {:?}",
+            project_dir
+        ));
+        w.comment("Each value is annotated with its origin.");
+        w.out.push('\n');
+
+        w.value("version", &Self::version(), origins.get("version"))?;
+        w.value("songs", &self.songs, origins.get("songs"))?;
+        for (key, files) in [
+            ("front_matter", &self.front_matter),
+            ("back_matter", &self.back_matter),
+        ] {
+            let files: Vec<_> = files.iter().map(|p| relative(p, project_dir)).collect();
+            w.value(key, &files, origins.get(key))?;
+        }
+        let dirs_songs: Vec<_> = self
+            .dir_songs
+            .iter()
+            .map(|p| relative(p, project_dir))
+            .collect();
+        match dirs_songs.as_slice() {
+            [dir] => w.value("dir_songs", dir, origins.get("dir_songs"))?,
+            dirs => w.value("dir_songs", dirs, origins.get("dir_songs"))?,
+        }
+        w.value(
+            "dir_templates",
+            relative(&self.dir_templates, project_dir),
+            origins.get("dir_templates"),
+        )?;
+        w.value(
+            "dir_output",
+            relative(&self.dir_output, project_dir),
+            origins.get("dir_output"),
+        )?;
+        w.value("notation", &self.notation, origins.get("notation"))?;
+        w.value(
+            "minor_chords",
+            &self.minor_chords,
+            origins.get("minor_chords"),
+        )?;
+        w.value(
+            "smart_punctuation",
+            &self.smart_punctuation,
+            origins.get("smart_punctuation"),
+        )?;
+        w.value(
+            "normalize_unicode",
+            &self.normalize_unicode,
+            origins.get("normalize_unicode"),
+        )?;
+        w.value("soft_breaks", &self.soft_breaks, origins.get("soft_breaks"))?;
+        w.value(
+            "title_chords",
+            &self.title_chords,
+            origins.get("title_chords"),
+        )?;
+        w.value("chord_lint", &self.chord_lint, origins.get("chord_lint"))?;
+        w.value(
+            "chord_lint_allow",
+            &self.chord_lint_allow,
+            origins.get("chord_lint_allow"),
+        )?;
+        w.value(
+            "always_number_choruses",
+            &self.always_number_choruses,
+            origins.get("always_number_choruses"),
+        )?;
+        w.value(
+            "never_number_choruses",
+            &self.never_number_choruses,
+            origins.get("never_number_choruses"),
+        )?;
+        w.value(
+            "include_sources",
+            &self.include_sources,
+            origins.get("include_sources"),
+        )?;
+        w.value(
+            "reproducible",
+            &self.reproducible,
+            origins.get("reproducible"),
+        )?;
+        w.value("prune", &self.prune, origins.get("prune"))?;
+        w.option(
+            "snippets_file",
+            self.snippets_file
+                .as_deref()
+                .map(|p| relative(p, project_dir)),
+            origins.get("snippets_file"),
+            "snippets are only defined in [snippets]",
+        )?;
+        match TexConfig::try_from_env()? {
+            Some(tex) => w.value("tex", &tex, Origin::Env("BARD_TEX"))?,
+            None => w.option(
+                "tex",
+                self.tex.as_ref(),
+                origins.get("tex"),
+                "the TeX distribution is detected automatically",
+            )?,
+        }
+
+        w.header("[hooks]");
+        w.option(
+            "pre_build",
+            self.hooks.pre_build.as_ref(),
+            origins.get("hooks.pre_build"),
+            "no command is run",
+        )?;
+        w.option(
+            "post_build",
+            self.hooks.post_build.as_ref(),
+            origins.get("hooks.post_build"),
+            "no command is run",
+        )?;
+
+        w.header("[limits]");
+        w.value(
+            "max_file_size",
+            &self.limits.max_file_size,
+            origins.get("limits.max_file_size"),
+        )?;
+        w.value(
+            "max_line_length",
+            &self.limits.max_line_length,
+            origins.get("limits.max_line_length"),
+        )?;
+        w.value(
+            "max_inlines",
+            &self.limits.max_inlines,
+            origins.get("limits.max_inlines"),
+        )?;
+
+        w.header("[chords.normalize]");
+        for (suffix, to) in self.chords.normalize.iter() {
+            w.value(
+                suffix,
+                to,
+                origins.get(&format!("chords.normalize.{}", suffix)),
+            )?;
+        }
+
+        w.header("[spellcheck]");
+        w.value(
+            "ignore",
+            &self.spellcheck.ignore,
+            origins.get("spellcheck.ignore"),
+        )?;
+
+        for table in self.notations.iter() {
+            let origin = |key: &str| origins.get(&format!("notations.{}.{}", table.name(), key));
+
+            w.header(&format!("[notations.{}]", ConfigWriter::key(table.name())));
+            w.value("notes", table.notes(), origin("notes"))?;
+            w.value("lowercase", table.lowercase(), origin("lowercase"))?;
+        }
+
+        w.header("[snippets]");
+        for (name, text) in self.snippets.iter() {
+            w.value(name, text, origins.get(&format!("snippets.{}", name)))?;
+        }
+
+        w.header("[variants]");
+        for (title, variants) in self.variants.iter() {
+            w.value(title, variants, origins.get(&format!("variants.{}", title)))?;
+        }
+
+        w.header("[book]");
+        for (key, value) in self.book.iter() {
+            w.value(key, value, origins.get(&format!("book.{}", key)))?;
+        }
+        w.option(
+            "image_classes",
+            self.image_classes.as_ref(),
+            origins.get("book.image_classes"),
+            "image classes are not checked",
+        )?;
+        w.value("sort", &self.sort, origins.get("book.sort"))?;
+
+        w.header("[book.styles]");
+        w.value(
+            "chorus",
+            &self.styles.chorus,
+            origins.get("book.styles.chorus"),
+        )?;
+
+        w.header("[book.labels]");
+        w.value(
+            "verse_format",
+            &self.labels.verse_format,
+            origins.get("book.labels.verse_format"),
+        )?;
+        w.value(
+            "chorus_format",
+            &self.labels.chorus_format,
+            origins.get("book.labels.chorus_format"),
+        )?;
+
+        for output in self.output.iter() {
+            // Entries with `files` are written as an output for each of the files
+            let origin = |key: &str| origins.get(&format!("output.{}.{}", output.entry, key));
+            let file_origin = match origin("file") {
+                Origin::Default => origin("files"),
+                origin => origin,
+            };
+
+            w.header("[[output]]");
+            w.value(
+                "file",
+                relative(&output.file, &self.dir_output),
+                file_origin,
+            )?;
+            w.option(
+                "template",
+                output
+                    .template
+                    .as_deref()
+                    .map(|t| relative(t, &self.dir_templates)),
+                origin("template"),
+                "the built-in template is used",
+            )?;
+            w.value("format", &output.format(), origin("format"))?;
+            w.value("sans_font", &output.sans_font, origin("sans_font"))?;
+            w.value("font_size", &output.font_size, origin("font_size"))?;
+            w.value("toc_sort", &output.toc_sort, origin("toc_sort"))?;
+            w.value("toc_sort_key", &output.toc_sort_key, origin("toc_sort_key"))?;
+            w.value("dpi", &output.dpi(), origin("dpi"))?;
+            w.option(
+                "tex_runs",
+                output.tex_runs.as_ref(),
+                origin("tex_runs"),
+                "TeX is rerun only if the document has a table of contents or references",
+            )?;
+            w.option(
+                "script",
+                output.script.as_ref(),
+                origin("script"),
+                "no script is run",
+            )?;
+            w.value(
+                "script_expect_modify",
+                &output.script_expect_modify,
+                origin("script_expect_modify"),
+            )?;
+            w.value("script_on", &output.script_on, origin("script_on"))?;
+            w.option(
+                "script_min_interval",
+                output
+                    .script_min_interval
+                    .map(|i| format!("{}s", i.as_secs()))
+                    .as_ref(),
+                origin("script_min_interval"),
+                "the script runs on every rebuild",
+            )?;
+            w.option(
+                "on_update",
+                output.on_update.as_ref(),
+                origin("on_update"),
+                "no command is run",
+            )?;
+            w.option(
+                "line_endings",
+                output.line_endings.as_ref(),
+                origin("line_endings"),
+                "line endings are kept as rendered",
+            )?;
+            w.value(
+                "trim_trailing_whitespace",
+                &output.trim_trailing_whitespace,
+                origin("trim_trailing_whitespace"),
+            )?;
+            w.value(
+                "strict_templates",
+                &output.strict_templates,
+                origin("strict_templates"),
+            )?;
+            w.option(
+                "setlist",
+                output.setlist.as_deref().map(|p| relative(p, project_dir)),
+                origin("setlist"),
+                "all songs are rendered",
+            )?;
+            w.value("songs_filter", &output.songs_filter, origin("songs_filter"))?;
+            w.option(
+                "notation",
+                output.notation.as_ref(),
+                origin("notation"),
+                "chords are in the book's notation",
+            )?;
+            if output.format() == Format::Json {
+                w.value("json_pretty", &output.json_pretty, origin("json_pretty"))?;
+            }
+            if matches!(output.format(), Format::Json | Format::Jsonl) {
+                w.value("sort_keys", &output.sort_keys, origin("sort_keys"))?;
+            }
+            if output.format() == Format::Pdf {
+                w.value("fonts", &output.fonts, origin("fonts"))?;
+            }
+            if matches!(output.format(), Format::Pdf | Format::Hovorka) {
+                w.value("html_content", &output.html_content, origin("html_content"))?;
+            }
+            if output.format() == Format::Images {
+                w.value("slides", &output.slides, origin("slides"))?;
+                w.value("image_format", &output.image_format, origin("image_format"))?;
+                w.value(
+                    "image_command",
+                    &output.image_command(),
+                    origin("image_command"),
+                )?;
+            }
+            if !output.volumes.is_empty() {
+                w.value("volumes", &output.volumes, origin("volumes"))?;
+            }
+
+            if !output.book_overrides.is_empty() || output.sort.is_some() {
+                w.header("[output.book]");
+                for (key, value) in output.book_overrides.iter() {
+                    w.value(key, value, Origin::OutputOverride)?;
+                }
+                if let Some(sort) = output.sort.as_ref() {
+                    w.value("sort", sort, Origin::OutputOverride)?;
+                }
+            }
+        }
+
+        Ok(w.out)
+    }
+}
@@ -0,0 +1,100 @@
+use std::process::Command;
+
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::*;
+
+/// A hook command, either a command line run via the system shell,
+/// or a program followed by its arguments.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum Hook {
+    Shell(String),
+    Argv(Vec<String>),
+}
+
+impl Hook {
+    pub fn command(&self) -> Result<Command> {
+        match self {
+            Self::Shell(cmdline) => Ok(Self::shell_command(cmdline)),
+            Self::Argv(argv) => Self::argv_command(argv.iter().map(String::as_str)),
+        }
+    }
+
+    /// Like `command()`, but with `{file}` replaced by the path of `file`.
+    /// In a shell command line the path is quoted.
+    pub fn command_for_file(&self, file: &Path) -> Result<Command> {
+        self.command_with_paths(&[("file", file)])
+    }
+
+    /// Like `command()`, but with each `{name}` in `paths` replaced by its path,
+    /// eg. `{input}` and `{output}`. In a shell command line the paths are quoted.
+    pub fn command_with_paths(&self, paths: &[(&str, &Path)]) -> Result<Command> {
+        let replace = |arg: &str, quote: bool| {
+            paths.iter().fold(arg.to_string(), |arg, (name, path)| {
+                let path = path.to_string_lossy();
+                let path = if quote {
+                    shell_quote(&path)
+                } else {
+                    path.into()
+                };
+                arg.replace(&format!("{{{}}}", name), &path)
+            })
+        };
+
+        match self {
+            Self::Shell(cmdline) => Ok(Self::shell_command(&replace(cmdline, true))),
+            Self::Argv(argv) => {
+                let argv: Vec<_> = argv.iter().map(|a| replace(a, false)).collect();
+                Self::argv_command(argv.iter().map(String::as_str))
+            }
+        }
+    }
+
+    /// The program run by an argv command, `None` for a shell command line.
+    pub fn program(&self) -> Option<&str> {
+        match self {
+            Self::Shell(_) => None,
+            Self::Argv(argv) => argv.first().map(String::as_str),
+        }
+    }
+
+    fn shell_command(cmdline: &str) -> Command {
+        #[cfg(unix)]
+        let (shell, arg) = ("sh", "-c");
+        #[cfg(windows)]
+        let (shell, arg) = ("cmd", "/C");
+
+        let mut cmd = Command::new(shell);
+        cmd.arg(arg).arg(cmdline);
+        cmd
+    }
+
+    fn argv_command<'a>(mut argv: impl Iterator<Item = &'a str>) -> Result<Command> {
+        let program = argv
+            .next()
+            .ok_or_else(|| anyhow!("Hook command is an empty array"))?;
+        let mut cmd = Command::new(program);
+        cmd.args(argv);
+        Ok(cmd)
+    }
+}
+
+#[cfg(unix)]
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+#[cfg(windows)]
+fn shell_quote(s: &str) -> String {
+    // Double quotes can't appear in Windows paths
+    format!("\"{}\"", s)
+}
+
+/// The `[hooks]` section, commands run before and after the whole project build.
+#[derive(Serialize, Deserialize, Default, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Hooks {
+    pub pre_build: Option<Hook>,
+    pub post_build: Option<Hook>,
+}
@@ -1,12 +1,19 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Write as _;
+use std::fs;
 use std::slice;
 
 use globset::Glob;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
+use super::setlist::distance;
 use crate::prelude::*;
 use crate::util::{read_dir_all, sort_paths_lexical};
 
-#[derive(Deserialize, Debug)]
+/// Max number of nearest-miss file names listed when a pattern matches no files.
+const MAX_NEAREST: usize = 3;
+
+#[derive(Serialize, Deserialize, Debug)]
 #[serde(untagged)]
 pub enum SongsGlobs {
     One(String),
@@ -22,6 +29,13 @@ impl SongsGlobs {
 
         (0..).map_while(move |i| items.get(i).map(move |s| s.as_str()))
     }
+
+    pub fn is_explicit_list(&self) -> bool {
+        match self {
+            Self::One(..) => false,
+            Self::Many(many) => !many.iter().any(InputSet::is_globlike),
+        }
+    }
 }
 
 impl Default for SongsGlobs {
@@ -32,18 +46,35 @@ impl Default for SongsGlobs {
 
 #[derive(Debug)]
 pub struct InputSet<'a> {
-    dir_songs: &'a Path,
-    all_files: Vec<PathBuf>,
+    dirs_songs: &'a [PathBuf],
+    /// Files of all the songs directories by their path relative to the directory.
+    /// A file in a later directory shadows the one with the same relative path in an earlier directory.
+    all_files: BTreeMap<PathBuf, PathBuf>,
     match_set: Vec<PathBuf>,
 }
 
 impl<'a> InputSet<'a> {
-    pub fn new(dir_songs: &'a Path) -> Result<Self> {
-        let all_files = read_dir_all(dir_songs)
-            .with_context(|| format!("Could not read directory {:?}", dir_songs))?;
+    pub fn new(dirs_songs: &'a [PathBuf]) -> Result<Self> {
+        let mut all_files = BTreeMap::new();
+        for dir_songs in dirs_songs {
+            if !dir_songs.is_dir() {
+                bail!(
+                    "songs directory not found: {:?} (set dir_songs in bard.toml)",
+                    dir_songs
+                );
+            }
+
+            let files = read_dir_all(dir_songs)
+                .with_context(|| format!("Could not read directory {:?}", dir_songs))?;
+            for path in files {
+                // NB. Unwrap should be ok here as the paths will all be prefixed by dir_songs
+                let rel_path = path.strip_prefix(dir_songs).unwrap().to_owned();
+                all_files.insert(rel_path, path);
+            }
+        }
 
         Ok(Self {
-            dir_songs,
+            dirs_songs,
             all_files,
             match_set: vec![],
         })
@@ -53,56 +84,178 @@ impl<'a> InputSet<'a> {
         s.as_ref().contains(&['*', '?', '{', '}'][..])
     }
 
-    fn apply_glob_inner<'s>(&'s mut self, glob: &str) -> Result<&'s mut [PathBuf]> {
-        let orig_len = self.match_set.len();
+    /// The songs directories for error messages.
+    fn dirs_display(&self) -> String {
+        match self.dirs_songs {
+            [dir] => format!("directory {:?}", dir),
+            dirs => format!("directories {:?}", dirs),
+        }
+    }
+
+    fn apply_glob_inner(&mut self, glob: &str) -> Result<usize> {
         let glob = Glob::new(glob)
             .with_context(|| format!("Invalid glob pattern: '{}'", glob))?
             .compile_matcher();
-        let dir_songs = self.dir_songs;
-        let match_set = &mut self.match_set;
 
-        for matched in self
+        let mut matched: Vec<_> = self
             .all_files
-            .iter()
-            // NB. Unwrap should be ok here as the paths will all be prefixed by dir_songs
-            .filter(|path| glob.is_match(path.strip_prefix(dir_songs).unwrap()))
-        {
-            match_set.push(matched.clone());
-        }
+            .keys()
+            .filter(|rel_path| glob.is_match(rel_path))
+            .collect();
+
+        // Sort the entries collected for this glob.
+        // This way, paths from one glob pattern are sorted alphabetically,
+        // but order of globs as given in the input array is preserved.
+        sort_paths_lexical(&mut matched);
+        let all_files = &self.all_files;
+        self.match_set
+            .extend(matched.iter().map(|rel_path| all_files[*rel_path].clone()));
+
+        Ok(matched.len())
+    }
+
+    /// Match an absolute `glob` against the files in its directory, which is the part of the path before the first wildcard.
+    fn apply_abs_glob(&mut self, glob: &str) -> Result<usize> {
+        let base: PathBuf = Path::new(glob)
+            .components()
+            .take_while(|comp| !Self::is_globlike(comp.as_os_str().to_string_lossy()))
+            .collect();
+        let files =
+            read_dir_all(&base).with_context(|| format!("Could not read directory {:?}", base))?;
+        let glob = Glob::new(glob)
+            .with_context(|| format!("Invalid glob pattern: '{}'", glob))?
+            .compile_matcher();
+
+        let mut matched: Vec<_> = files
+            .into_iter()
+            .filter(|path| glob.is_match(path))
+            .collect();
+        sort_paths_lexical(&mut matched);
+        let count = matched.len();
+        self.match_set.extend(matched);
 
-        Ok(&mut match_set[orig_len..])
+        Ok(count)
     }
 
     pub fn apply_glob(mut self, glob: &str) -> Result<Self> {
-        if Self::is_globlike(glob) {
+        let absolute = Path::new(glob).is_absolute();
+        if Self::is_globlike(glob) && absolute {
+            // An absolute glob, not bound to the songs directories
+            if self.apply_abs_glob(glob)? == 0 {
+                bail!("No files matched pattern '{}'", glob);
+            }
+        } else if Self::is_globlike(glob) {
             // This might be a glob
-            let added = self.apply_glob_inner(glob)?;
-            if added.is_empty() {
+            if self.apply_glob_inner(glob)? == 0 {
                 bail!(
-                    "No files matched pattern '{}' in diectory {:?}",
+                    "No files matched pattern '{}' in {}\n{}",
                     glob,
-                    self.dir_songs,
+                    self.dirs_display(),
+                    self.explain_no_match(glob),
                 );
             }
-
-            // Sort the entries collected for this glob.
-            // This way, paths from one glob pattern are sorted alphabetically,
-            // but order of globs as given in the input array is preserved.
-            sort_paths_lexical(added);
-        } else {
-            // This is a plain filename
-            let path = self.dir_songs.join(glob);
+        } else if absolute {
+            // A plain absolute path
+            let path = PathBuf::from(glob);
             if !path.exists() {
                 bail!("File not found: {:?}", path);
             }
 
             self.match_set.push(path);
+        } else {
+            // This is a plain filename, the last songs directory that has it wins
+            let path = self
+                .dirs_songs
+                .iter()
+                .rev()
+                .map(|dir| dir.join(glob))
+                .find(|path| path.exists());
+            match (path, self.dirs_songs) {
+                (Some(path), _) => self.match_set.push(path),
+                (None, [dir]) => bail!("File not found: {:?}", dir.join(glob)),
+                (None, _) => bail!("File not found: '{}' in {}", glob, self.dirs_display()),
+            }
         }
 
         Ok(self)
     }
 
-    pub fn finalize(self) -> Result<Vec<PathBuf>> {
+    /// Explain why `glob` matched no files: either there are no files with the pattern's extension,
+    /// in which case the files with other extensions are counted, or the files with that extension
+    /// don't match, in which case the closest ones are listed.
+    fn explain_no_match(&self, glob: &str) -> String {
+        let ext = Path::new(glob)
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_string())
+            .filter(|ext| !Self::is_globlike(ext));
+        let has_ext = |path: &Path| match ext.as_deref() {
+            Some(ext) => path.extension().map_or(false, |e| e == ext),
+            None => true,
+        };
+
+        let candidates: Vec<_> = self.all_files.keys().filter(|p| has_ext(p)).collect();
+        if candidates.is_empty() {
+            if self.all_files.is_empty() {
+                return "The directory is empty.".into();
+            }
+
+            let mut counts = BTreeMap::new();
+            for path in self.all_files.keys() {
+                let ext = path
+                    .extension()
+                    .map(|ext| format!(".{}", ext.to_string_lossy()))
+                    .unwrap_or_else(|| "no extension".into());
+                *counts.entry(ext).or_insert(0) += 1;
+            }
+
+            let mut msg = format!(
+                "The directory has no .{} files, but {} files with other extensions:",
+                ext.as_deref().unwrap_or_default(),
+                self.all_files.len()
+            );
+            for (ext, count) in counts {
+                write!(msg, "\n  {}: {}", ext, count).unwrap();
+            }
+            return msg;
+        }
+
+        // Compare with the wildcards removed, eg. `amazing*.md` is close to `amazng-grace.md`
+        let pattern: Vec<_> = glob
+            .chars()
+            .filter(|c| !matches!(c, '*' | '?' | '{' | '}'))
+            .collect();
+        let mut nearest: Vec<_> = candidates
+            .iter()
+            .map(|path| {
+                let name = path.to_string_lossy();
+                let chars: Vec<_> = name.chars().collect();
+                (distance(&pattern, &chars), name)
+            })
+            .collect();
+        nearest.sort();
+
+        let names: Vec<_> = nearest
+            .into_iter()
+            .take(MAX_NEAREST)
+            .map(|(_, name)| format!("{:?}", name))
+            .collect();
+        let what = match ext.as_deref() {
+            Some(ext) => format!(".{} files", ext),
+            None => "files".into(),
+        };
+        format!(
+            "None of the {} {} in the directory matched, the closest are: {}",
+            candidates.len(),
+            what,
+            names.join(", ")
+        )
+    }
+
+    /// The matched files in order, a file matched more than once is only kept where it first appeared.
+    pub fn finalize(mut self) -> Result<Vec<PathBuf>> {
+        let mut seen = HashSet::new();
+        self.match_set
+            .retain(|path| seen.insert(fs::canonicalize(path).unwrap_or_else(|_| path.clone())));
         Ok(self.match_set)
     }
 }
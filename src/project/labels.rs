@@ -0,0 +1,142 @@
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::*;
+
+fn default_verse_format() -> String {
+    "{n}.".to_string()
+}
+
+fn default_chorus_format() -> String {
+    "{label}{n}.".to_string()
+}
+
+/// The `[book.labels]` section, formats of verse and chorus labels used by the default templates.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Labels {
+    /// Label of a numbered verse, `{n}` is replaced with the verse number.
+    #[serde(default = "default_verse_format")]
+    pub verse_format: String,
+    /// Label of a chorus and of references to it, `{label}` is replaced with `book.chorus_label`
+    /// and `{n}` with the chorus number, or nothing if the chorus isn't numbered.
+    #[serde(default = "default_chorus_format")]
+    pub chorus_format: String,
+}
+
+impl Default for Labels {
+    fn default() -> Self {
+        Self {
+            verse_format: default_verse_format(),
+            chorus_format: default_chorus_format(),
+        }
+    }
+}
+
+impl Labels {
+    pub fn validate(&self) -> Result<()> {
+        validate_format("verse_format", &self.verse_format, &["n"])?;
+        validate_format("chorus_format", &self.chorus_format, &["n", "label"])
+    }
+
+    pub fn verse(&self, n: u64) -> String {
+        let n = n.to_string();
+        substitute(&self.verse_format, |_| n.as_str())
+    }
+
+    pub fn chorus(&self, label: &str, n: Option<u64>) -> String {
+        let n = n.map(|n| n.to_string()).unwrap_or_default();
+        substitute(&self.chorus_format, |name| match name {
+            "label" => label,
+            _ => n.as_str(),
+        })
+    }
+}
+
+/// Split `format` into literal text and `{placeholder}` names, in order.
+/// Returns `Err` with the offending text if a brace isn't a part of a placeholder.
+fn parse_format(format: &str) -> Result<Vec<(&str, bool)>, &str> {
+    let mut parts = vec![];
+    let mut rest = format;
+    while let Some(start) = rest.find(['{', '}']) {
+        if rest[start..].starts_with('}') {
+            return Err(&rest[start..]);
+        }
+        let end = rest[start..].find('}').ok_or(&rest[start..])? + start;
+        parts.push((&rest[..start], false));
+        parts.push((&rest[start + 1..end], true));
+        rest = &rest[end + 1..];
+    }
+    parts.push((rest, false));
+    Ok(parts)
+}
+
+fn validate_format(key: &str, format: &str, placeholders: &[&str]) -> Result<()> {
+    let parts = parse_format(format)
+        .map_err(|at| anyhow!("Invalid {} {:?}: unmatched brace at {:?}", key, format, at))?;
+    for (name, _) in parts.iter().filter(|(_, is_placeholder)| *is_placeholder) {
+        if !placeholders.contains(name) {
+            let allowed: Vec<_> = placeholders.iter().map(|p| format!("{{{}}}", p)).collect();
+            bail!(
+                "Invalid {} {:?}: unknown placeholder {{{}}}, possible placeholders: {}",
+                key,
+                format,
+                name,
+                allowed.join(", ")
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Replace placeholders in a format validated by `validate_format()` with values from `value`.
+fn substitute<'a>(format: &str, value: impl Fn(&str) -> &'a str) -> String {
+    let parts = parse_format(format).unwrap_or_else(|_| vec![(format, false)]);
+    parts
+        .into_iter()
+        .map(|(part, is_placeholder)| if is_placeholder { value(part) } else { part })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn labels_default() {
+        let labels = Labels::default();
+        labels.validate().unwrap();
+        assert_eq!(labels.verse(3), "3.");
+        assert_eq!(labels.chorus("Ch", Some(2)), "Ch2.");
+        assert_eq!(labels.chorus("Ch", None), "Ch.");
+    }
+
+    #[test]
+    fn labels_custom() {
+        let labels = Labels {
+            verse_format: "Strophe {n}".into(),
+            chorus_format: "{n}. {label}".into(),
+        };
+        labels.validate().unwrap();
+        assert_eq!(labels.verse(1), "Strophe 1");
+        assert_eq!(labels.chorus("Refrain", Some(2)), "2. Refrain");
+        assert_eq!(labels.chorus("{n}", Some(2)), "2. {n}");
+    }
+
+    #[test]
+    fn labels_invalid() {
+        for (verse_format, chorus_format) in [
+            ("{label}", "{label}{n}"),
+            ("{n", "{label}{n}"),
+            ("n}", "{label}{n}"),
+            ("{n}.", "{chorus}"),
+            ("{n}.", "{}"),
+        ] {
+            let labels = Labels {
+                verse_format: verse_format.into(),
+                chorus_format: chorus_format.into(),
+            };
+            labels.validate().unwrap_err();
+        }
+    }
+}
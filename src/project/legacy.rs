@@ -0,0 +1,222 @@
+//! Reading of project files created by bard 1.x, see `migrate()`.
+
+use super::{TomlMap, Value};
+use crate::prelude::*;
+
+const MIGRATION_GUIDE: &str = "https://bard.md/book/migration-2.html";
+
+/// Programs that 1.x projects typically ran in `process` to render PDFs, bard does this by itself now.
+const TEX_PROGRAMS: &[&str] = &[
+    "xelatex", "pdflatex", "lualatex", "latex", "latexmk", "tectonic",
+];
+
+/// Post-processing keys of 1.x outputs.
+const PROCESS_KEYS: &[&str] = &["process", "process_win", "post_process"];
+
+/// Convert a raw 1.x project file in-place to the current format, where that can be done unambiguously.
+///
+/// Returns deprecation warnings describing what was converted.
+/// Keys that can't be converted are reported together in one error, each with a migration hint.
+pub fn migrate(raw: &mut TomlMap) -> Result<Vec<String>> {
+    let mut warnings = vec![];
+    let mut errors = vec![];
+
+    if let Some(outputs) = raw.get_mut("output").and_then(Value::as_array_mut) {
+        for (i, output) in outputs.iter_mut().enumerate() {
+            if let Some(output) = output.as_table_mut() {
+                migrate_output(i, output, &mut warnings, &mut errors);
+            }
+        }
+    }
+
+    let backmatter = raw
+        .get("book")
+        .and_then(Value::as_table)
+        .map(|book| book.contains_key("backmatter"))
+        .unwrap_or(false);
+    if backmatter {
+        warnings.push(
+            "book.backmatter: No longer used by the default templates, customize the template instead."
+                .to_string(),
+        );
+    }
+
+    if !errors.is_empty() {
+        bail!(
+            "This project was created with bard 1.x and could not be converted automatically:\n  {}\nPlease follow the migration guide: {}",
+            errors.join("\n  "),
+            MIGRATION_GUIDE
+        );
+    }
+
+    raw.insert("version".into(), Value::Integer(2));
+    warnings.push(format!(
+        "version: This project was created with bard 1.x, add `version = 2` to bard.toml once migrated, see {}",
+        MIGRATION_GUIDE
+    ));
+
+    Ok(warnings)
+}
+
+fn migrate_output(
+    i: usize,
+    output: &mut TomlMap,
+    warnings: &mut Vec<String>,
+    errors: &mut Vec<String>,
+) {
+    let key = |name: &str| format!("output.{}.{}", i, name);
+
+    for &name in PROCESS_KEYS {
+        let value = match output.remove(name) {
+            Some(value) => value,
+            None => continue,
+        };
+
+        match process_programs(&value) {
+            Some(programs) if programs.iter().all(|p| is_tex_program(p)) => warnings.push(format!(
+                "{}: PDF files are now rendered by bard itself, the field is ignored.",
+                key(name)
+            )),
+            _ => errors.push(format!(
+                "{}: Post-processing commands are no longer supported, use an output script instead, see https://bard.md/book/scripts.html",
+                key(name)
+            )),
+        }
+    }
+
+    let file = output
+        .get("file")
+        .and_then(Value::as_str)
+        .map(PathBuf::from);
+    let format = output.get("format").and_then(Value::as_str);
+    let ext = file
+        .as_deref()
+        .and_then(Path::extension)
+        .and_then(|ext| ext.to_str())
+        .map(str::to_ascii_lowercase);
+
+    match (format, ext.as_deref()) {
+        (Some("tex"), _) | (None, Some("tex")) => {
+            output.insert("format".into(), "pdf".into());
+            if let Some(file) = file.filter(|_| ext.as_deref() == Some("tex")) {
+                let pdf = file.with_extension("pdf");
+                output.insert("file".into(), pdf.to_string_lossy().into_owned().into());
+            }
+            warnings.push(format!(
+                "{}: The tex format was replaced by pdf, rendering a PDF file instead.",
+                key("format")
+            ));
+        }
+        (None, Some("htm" | "xhtml" | "xht")) => {
+            output.insert("format".into(), "html".into());
+            warnings.push(format!(
+                "{}: The .{} extension is no longer detected as HTML, set `format = \"html\"` or use .html",
+                key("file"),
+                ext.unwrap()
+            ));
+        }
+        (None, Some("xml")) => {
+            output.insert("format".into(), "hovorka".into());
+            warnings.push(format!(
+                "{}: Treating the .xml file as hovorka as in bard 1.x, set `format = \"hovorka\"` or `format = \"xml\"` explicitly",
+                key("file")
+            ));
+        }
+        _ => {}
+    }
+}
+
+/// Names of programs invoked by a 1.x `process` value, which was either a command line,
+/// an argv array, or an array of such commands. Returns `None` if the value has neither shape.
+fn process_programs(value: &Value) -> Option<Vec<&str>> {
+    fn program(cmd: &Value) -> Option<&str> {
+        match cmd {
+            Value::String(line) => line.split_whitespace().next(),
+            Value::Array(argv) => argv.first().and_then(Value::as_str),
+            _ => None,
+        }
+    }
+
+    match value {
+        Value::Array(cmds) if cmds.iter().any(Value::is_array) => {
+            cmds.iter().map(program).collect()
+        }
+        cmd => program(cmd).map(|p| vec![p]),
+    }
+}
+
+fn is_tex_program(program: &str) -> bool {
+    let stem = Path::new(program)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or(program);
+    TEX_PROGRAMS.contains(&stem)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn migrate_str(toml: &str) -> Result<(TomlMap, Vec<String>)> {
+        let mut raw: TomlMap = toml::from_str(toml).unwrap();
+        migrate(&mut raw).map(|warnings| (raw, warnings))
+    }
+
+    #[test]
+    fn legacy_outputs() {
+        let (raw, warnings) = migrate_str(
+            r#"
+            [[output]]
+            file = "songbook.tex"
+            process = "xelatex {{file}}"
+            process_win = [["latexmk", "-xelatex", "{{file}}"], ["xelatex", "{{file}}"]]
+
+            [[output]]
+            file = "songbook.htm"
+
+            [[output]]
+            file = "songbook.xml"
+
+            [[output]]
+            file = "songbook.json"
+            "#,
+        )
+        .unwrap();
+
+        let outputs = raw["output"].as_array().unwrap();
+        assert_eq!(outputs[0]["file"].as_str(), Some("songbook.pdf"));
+        assert_eq!(outputs[0]["format"].as_str(), Some("pdf"));
+        assert!(outputs[0].get("process").is_none());
+        assert!(outputs[0].get("process_win").is_none());
+        assert_eq!(outputs[1]["format"].as_str(), Some("html"));
+        assert_eq!(outputs[2]["format"].as_str(), Some("hovorka"));
+        assert!(outputs[3].get("format").is_none());
+        assert_eq!(raw["version"].as_integer(), Some(2));
+        assert_eq!(warnings.len(), 6);
+    }
+
+    #[test]
+    fn legacy_process_unsupported() {
+        let err = migrate_str(
+            r#"
+            [[output]]
+            file = "songbook.html"
+            post_process = ["sed", "-i", "s/a/b/", "{{file}}"]
+
+            [[output]]
+            file = "songbook.tex"
+            process = [["xelatex", "{{file}}"], ["pdfcrop", "{{file}}"]]
+            "#,
+        )
+        .unwrap_err()
+        .to_string();
+
+        assert!(
+            err.contains("output.0.post_process: Post-processing"),
+            "{}",
+            err
+        );
+        assert!(err.contains("output.1.process: Post-processing"), "{}", err);
+        assert!(err.contains(MIGRATION_GUIDE), "{}", err);
+    }
+}
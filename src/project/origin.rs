@@ -0,0 +1,77 @@
+use std::collections::BTreeSet;
+use std::fmt;
+
+use super::{TomlMap, Value};
+
+/// Where an effective configuration value came from.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Origin {
+    /// Set in the `bard.toml` project file.
+    File,
+    /// Not set anywhere, the default value applies.
+    Default,
+    /// Overridden by the environment variable.
+    Env(&'static str),
+    /// Set in the output's `book` table, overriding the project `[book]` value.
+    OutputOverride,
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::File => write!(f, "bard.toml"),
+            Self::Default => write!(f, "default"),
+            Self::Env(var) => write!(f, "env override ({})", var),
+            Self::OutputOverride => write!(f, "output override of [book]"),
+        }
+    }
+}
+
+/// Records which configuration keys were set in the project file.
+///
+/// Keys are dotted paths as in the TOML source, array items are indexed,
+/// eg. `book.title` or `output.0.file`.
+#[derive(Default, Debug)]
+pub struct Origins {
+    in_file: BTreeSet<String>,
+}
+
+impl Origins {
+    pub fn from_toml(table: &TomlMap) -> Self {
+        let mut this = Self::default();
+        this.record_table("", table);
+        this
+    }
+
+    fn record_table(&mut self, prefix: &str, table: &TomlMap) {
+        for (key, value) in table.iter() {
+            let path = if prefix.is_empty() {
+                key.clone()
+            } else {
+                format!("{}.{}", prefix, key)
+            };
+
+            match value {
+                Value::Table(table) => self.record_table(&path, table),
+                // Arrays of tables, ie. [[output]]
+                Value::Array(array) if array.iter().all(Value::is_table) => {
+                    for (i, item) in array.iter().enumerate() {
+                        self.record_table(&format!("{}.{}", path, i), item.as_table().unwrap());
+                    }
+                }
+                _ => {}
+            }
+
+            self.in_file.insert(path);
+        }
+    }
+
+    /// Origin of the value at `path`, either `File` or `Default`.
+    pub fn get(&self, path: &str) -> Origin {
+        if self.in_file.contains(path) {
+            Origin::File
+        } else {
+            Origin::Default
+        }
+    }
+}
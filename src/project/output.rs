@@ -1,13 +1,21 @@
 use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::mem;
+use std::time::Duration;
 
-use serde::{Deserialize, Serialize};
-use strum::{Display, EnumVariantNames, VariantNames};
+use globset::Glob;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize};
+use strum::{Display, EnumString, EnumVariantNames, VariantNames};
 
+use crate::music::{Notation, Notations};
 use crate::prelude::*;
-use crate::project::Metadata;
-use crate::util::PathBufExt;
+use crate::project::{Hook, Metadata};
+use crate::util::{self, PathBufExt, SortOrder};
 
-#[derive(Serialize, Deserialize, Display, EnumVariantNames, PartialEq, Eq, Clone, Copy, Debug)]
+#[derive(
+    Serialize, Deserialize, Display, EnumString, EnumVariantNames, PartialEq, Eq, Clone, Copy, Debug,
+)]
 #[serde(rename_all = "lowercase")]
 #[strum(serialize_all = "lowercase")]
 pub enum Format {
@@ -15,15 +23,24 @@ pub enum Format {
     Html,
     Hovorka,
     Json,
+    /// JSON Lines, a header line, a line for each song, and a summary line, see `RJsonl`.
+    Jsonl,
     Xml,
+    /// Plain text with chords above the lyrics, see `RTxt`.
+    Txt,
+    /// ChordPro, as used by apps such as OnSong, see `RChordPro`.
+    ChordPro,
+    /// A directory of images of songs or verses, eg. for slides. Not detected from the file extension.
+    Images,
 }
 
 impl Format {
     pub fn try_from_ext(path: &Path) -> Result<Self> {
         let format_hint = || {
             format!(
-                "Hint: You can specify format with 'format = ...', supported formats are: {:?}.",
-                Format::VARIANTS
+                "Hint: Set the format explicitly, eg.:\n\n    file = {:?}\n    format = \"pdf\"\n\nSupported formats are: {}.",
+                path,
+                Format::VARIANTS.join(", ")
             )
         };
 
@@ -31,7 +48,7 @@ impl Format {
             .extension()
             .ok_or_else(|| {
                 anyhow!(
-                    "Could not detect format for output file {:?} - no extension.\n{}",
+                    "Could not detect format of output file {:?}, it has no extension.\n{}",
                     path,
                     format_hint(),
                 )
@@ -42,39 +59,173 @@ impl Format {
             "pdf" => Self::Pdf,
             "html" => Self::Html,
             "json" => Self::Json,
+            "jsonl" => Self::Jsonl,
             "xml" => Self::Xml,
+            "txt" => Self::Txt,
+            "cho" | "chordpro" => Self::ChordPro,
             _ => bail!(
-                "Could not detect format based file on extension for: {:?}\n{}",
+                "Could not detect format of output file {:?} from its extension.\n{}",
                 path,
                 format_hint(),
             ),
         })
     }
 
+    /// Whether outputs of this format are rendered with a template, see `Output::template_path()`.
+    fn has_template(self) -> bool {
+        match self {
+            Self::Pdf | Self::Html | Self::Hovorka | Self::Images => true,
+            Self::Json | Self::Jsonl | Self::Xml | Self::Txt | Self::ChordPro => false,
+        }
+    }
+
     fn default_dpi(self) -> f32 {
         match self {
-            Self::Html => 1.0,
+            Self::Html | Self::Images => 1.0,
             _ => 144.0,
         }
     }
 }
 
+#[derive(Serialize, Deserialize, Display, PartialEq, Eq, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum LineEndings {
+    Lf,
+    Crlf,
+    Native,
+}
+
+impl LineEndings {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Lf => "\n",
+            Self::Crlf => "\r\n",
+            Self::Native => util::LINE_END,
+        }
+    }
+}
+
+/// Which builds run the output's script.
+#[derive(Serialize, Deserialize, Display, PartialEq, Eq, Clone, Copy, Default, Debug)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum ScriptOn {
+    /// Only `bard make`.
+    Make,
+    /// Only the builds of `bard watch`.
+    Watch,
+    #[default]
+    Both,
+}
+
+/// What each image of an `images` output shows.
+#[derive(Serialize, Deserialize, Display, PartialEq, Eq, Clone, Copy, Default, Debug)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum SlideUnit {
+    #[default]
+    Song,
+    /// Each verse block, including choruses, other blocks are left out.
+    Verse,
+}
+
+/// Image file format of an `images` output, also used as the file extension.
+#[derive(Serialize, Deserialize, Display, PartialEq, Eq, Clone, Copy, Default, Debug)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum ImageFormat {
+    #[default]
+    Png,
+    Svg,
+}
+
+/// How HTML tags in songs are handled by outputs which don't render HTML, see `Output::html_content`.
+#[derive(Serialize, Deserialize, Display, PartialEq, Eq, Clone, Copy, Default, Debug)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum HtmlContent {
+    /// Tags without an extension partial in the template are dropped silently.
+    #[default]
+    Ignore,
+    /// Same as `Ignore`, but the dropped tags are reported for each song.
+    Warn,
+    /// Text inside simple tags such as `<span>` in HTML blocks is rendered as plain text,
+    /// see `parser::html::TEXT_TAGS`.
+    Text,
+}
+
+/// Parse an interval such as `60s`, `5m`, or `1h`, a plain number is in seconds.
+pub(crate) fn parse_interval(s: &str) -> Option<Duration> {
+    let s = s.trim();
+    let (num, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => s.split_at(i),
+        None => (s, "s"),
+    };
+    let num: u64 = num.parse().ok()?;
+    let secs = match unit.trim() {
+        "s" => num,
+        "m" => num.checked_mul(60)?,
+        "h" => num.checked_mul(3600)?,
+        _ => return None,
+    };
+    Some(Duration::from_secs(secs))
+}
+
+fn de_interval<'de, D>(de: D) -> Result<Option<Duration>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(de)?;
+    parse_interval(&s).map(Some).ok_or_else(|| {
+        D::Error::custom(format!(
+            "invalid interval '{}', expected eg. \"30s\", \"5m\", or \"1h\"",
+            s
+        ))
+    })
+}
+
+/// A volume of a PDF output split into multiple files.
+///
+/// Each volume except the last one ends either with the last song whose title matches
+/// the `until_song` glob pattern, or with the song number `until_index` (counted from 1).
+/// The last volume contains the remaining songs.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Volume {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub until_song: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub until_index: Option<usize>,
+}
+
+impl Volume {
+    fn has_limit(&self) -> bool {
+        self.until_song.is_some() || self.until_index.is_some()
+    }
+}
+
 fn default_font_size() -> u32 {
     12
 }
 
-fn default_toc_sort_key() -> String {
-    "numberline\\s+\\{[^}]*}([^}]+)".to_string()
+fn default_json_pretty() -> bool {
+    true
 }
 
-fn default_tex_runs() -> u32 {
-    3
+fn default_toc_sort_key() -> String {
+    "numberline\\s+\\{[^}]*}([^}]+)".to_string()
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Output {
-    #[serde(skip_serializing)]
+    /// Empty if the output was configured with `files`, set by `Output::expand()`.
+    #[serde(default, skip_serializing)]
     pub file: PathBuf,
+    /// Multiple files rendered with the same settings, see `Output::expand()`.
+    #[serde(default, skip_serializing)]
+    pub files: Vec<PathBuf>,
     #[serde(skip_serializing)]
     pub template: Option<PathBuf>,
     pub format: Option<Format>,
@@ -88,34 +239,288 @@ pub struct Output {
     pub toc_sort_key: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub dpi: Option<f32>,
-    #[serde(default = "default_tex_runs")]
-    pub tex_runs: u32,
+    /// Number of TeX runs, if not set, reruns are done only if needed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tex_runs: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub script: Option<String>,
+    /// Make it an error if the script leaves the output file unmodified.
+    #[serde(default, skip_serializing)]
+    pub script_expect_modify: bool,
+    /// Whether the script runs with `bard make`, in `bard watch`, or both.
+    #[serde(default, skip_serializing)]
+    pub script_on: ScriptOn,
+    /// Minimum time between two runs of the script in `bard watch`.
+    #[serde(default, deserialize_with = "de_interval", skip_serializing)]
+    pub script_min_interval: Option<Duration>,
+    /// Command run in `bard watch` after the output file was rewritten,
+    /// `{file}` is replaced by the path of the file, eg. to reload a viewer.
+    #[serde(default, skip_serializing)]
+    pub on_update: Option<Hook>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line_endings: Option<LineEndings>,
+    #[serde(default, skip_serializing)]
+    pub trim_trailing_whitespace: bool,
+    /// Make references to missing values in the template an error.
+    #[serde(default, skip_serializing)]
+    pub strict_templates: bool,
+    /// Whether JSON outputs are indented, otherwise written on a single line.
+    #[serde(default = "default_json_pretty", skip_serializing)]
+    pub json_pretty: bool,
+    /// Sort keys of all JSON objects alphabetically, otherwise they're in the AST definition order.
+    /// Applies to JSON Lines outputs as well.
+    #[serde(default, skip_serializing)]
+    pub sort_keys: bool,
+    #[serde(default, skip_serializing)]
+    pub volumes: Vec<Volume>,
+    /// Set list file, only the songs listed there are rendered, in the set list order.
+    #[serde(default, skip_serializing)]
+    pub setlist: Option<PathBuf>,
+    /// Song titles or glob patterns matched against song file names, only the matching songs are rendered.
+    #[serde(default, skip_serializing)]
+    pub songs_filter: Vec<String>,
+    /// Whether an `images` output has an image per song or per verse.
+    #[serde(default, skip_serializing)]
+    pub slides: SlideUnit,
+    #[serde(default, skip_serializing)]
+    pub image_format: ImageFormat,
+    /// Command converting an HTML page of a slide into an image, see `Output::image_command()`.
+    #[serde(default, skip_serializing)]
+    pub image_command: Option<Hook>,
+    /// Font files for the TeX template, glob patterns relative to the project directory,
+    /// see `render::pdf::FontFiles`.
+    #[serde(default, skip_serializing)]
+    pub fonts: Vec<String>,
+    /// How HTML tags are handled in PDF and Hovorka outputs, templates check this for the `text` mode.
+    #[serde(default)]
+    pub html_content: HtmlContent,
+
+    /// Name of the notation to convert chords to in this output, resolved into `notation`.
+    #[serde(default, rename = "notation", skip_serializing)]
+    pub notation_name: Option<String>,
+    /// Notation of chords in this output if it's converted from the book's, see `Output::resolve()`.
+    #[serde(skip)]
+    pub notation: Option<Notation>,
 
     #[serde(rename = "book", default, skip_serializing)]
     pub book_overrides: Metadata,
+    /// Parsed from `book.sort` of the output, overrides the book's sort order, see `Output::resolve()`.
+    #[serde(skip)]
+    pub sort: Option<SortOrder>,
+    /// Index of the `[[output]]` entry in the project file, see `Output::expand()`.
+    #[serde(skip)]
+    pub entry: usize,
 }
 
 impl Output {
-    pub fn resolve(&mut self, dir_templates: &Path, dir_output: &Path) -> Result<()> {
+    /// Expand the `[[output]]` entry at index `entry` into the outputs it renders:
+    /// either the entry itself with `file`, or a copy for each one of `files`,
+    /// the format is detected for each of the files separately.
+    pub fn expand(mut self, entry: usize) -> Result<Vec<Self>> {
+        self.entry = entry;
+        let files = mem::take(&mut self.files);
+        match (self.file.as_os_str().is_empty(), files.is_empty()) {
+            (false, true) => return Ok(vec![self]),
+            (true, false) => {}
+            (true, true) => bail!(
+                "Output number {} has no file, set eg. `file = \"songbook.pdf\"` or `files = [\"songbook.pdf\", \"songbook.html\"]`.",
+                entry + 1
+            ),
+            (false, false) => bail!(
+                "Output {:?} has both `file` and `files` set, only one of them may be used.",
+                self.file
+            ),
+        }
+
+        let context = || format!("Invalid output with files {:?}", files);
+        let extensions: BTreeSet<_> = files
+            .iter()
+            .map(|file| file.extension().map(|ext| ext.to_ascii_lowercase()))
+            .collect();
+        if let Some(format) = self.format.filter(|_| extensions.len() > 1) {
+            return Err(anyhow!(
+                "The format is set to \"{}\", but the files have different extensions. \
+                Remove `format` to detect it from each file's extension, or use a separate [[output]] for each format.",
+                format
+            ))
+            .with_context(context);
+        }
+
+        // Formats rendered with the template, only one of them can share it
+        let mut templated = vec![];
+        for file in files.iter() {
+            let format = match self.format {
+                Some(format) => format,
+                None => Format::try_from_ext(file).with_context(context)?,
+            };
+            if format.has_template() && !templated.contains(&format) {
+                templated.push(format);
+            }
+        }
+        if self.template.is_some() && templated.len() > 1 {
+            return Err(anyhow!(
+                "The template is shared by all the files, but they have different formats. \
+                Use a separate [[output]] for each format."
+            ))
+            .with_context(context);
+        }
+
+        Ok(files
+            .into_iter()
+            .map(|file| Self {
+                file,
+                ..self.clone()
+            })
+            .collect())
+    }
+
+    pub fn resolve(
+        &mut self,
+        project_dir: &Path,
+        dir_templates: &Path,
+        dir_output: &Path,
+        notations: &Notations,
+    ) -> Result<()> {
         if let Some(template) = self.template.as_mut() {
             template.resolve(dir_templates);
         }
+        if let Some(setlist) = self.setlist.as_mut() {
+            setlist.resolve(project_dir);
+        }
 
         if self.format.is_none() {
             self.format = Some(Format::try_from_ext(&self.file)?);
         }
 
+        if let Some(sort) = self.book_overrides.remove("sort") {
+            let sort = sort
+                .try_into()
+                .with_context(|| format!("Invalid book.sort of output {:?}", self.file))?;
+            self.sort = Some(sort);
+        }
+
+        if let Some(name) = self.notation_name.as_deref() {
+            let notation = notations.get(name).ok_or_else(|| {
+                anyhow!(
+                    "Unknown notation '{}' of output {:?}, it's neither a built-in notation nor defined in the [notations] section.",
+                    name,
+                    self.file
+                )
+            })?;
+            self.notation = Some(notation);
+        }
+
+        if self.setlist.is_some() && !self.songs_filter.is_empty() {
+            bail!(
+                "Invalid songs_filter of output {:?}: An output with a set list cannot have a songs_filter.",
+                self.file
+            );
+        }
+
+        if !self.fonts.is_empty() && !self.is_pdf() {
+            bail!(
+                "Invalid fonts of output {:?}: Fonts are only supported for PDF outputs.",
+                self.file
+            );
+        }
+
         self.file.resolve(dir_output);
+        self.validate_volumes()
+            .with_context(|| format!("Invalid volumes of output {:?}", self.file))
+    }
+
+    fn validate_volumes(&self) -> Result<()> {
+        if self.volumes.is_empty() {
+            return Ok(());
+        }
+
+        if !self.is_pdf() {
+            bail!("Splitting into volumes is only supported for PDF outputs.");
+        }
+
+        if self.setlist.is_some() {
+            bail!("An output with a set list cannot be split into volumes.");
+        }
+
+        if !self.songs_filter.is_empty() {
+            bail!("An output with a songs_filter cannot be split into volumes.");
+        }
+
+        let last = self.volumes.len() - 1;
+        for (i, volume) in self.volumes.iter().enumerate() {
+            if volume.until_song.is_some() && volume.until_index.is_some() {
+                bail!(
+                    "Volume '{}': until_song and until_index cannot be used together.",
+                    volume.name
+                );
+            }
+
+            if i < last && !volume.has_limit() {
+                bail!(
+                    "Volume '{}': until_song or until_index is required for all volumes except the last one.",
+                    volume.name
+                );
+            } else if i == last && volume.has_limit() {
+                bail!(
+                    "Volume '{}': The last volume contains the remaining songs, it cannot have until_song or until_index.",
+                    volume.name
+                );
+            }
+
+            if let Some(pattern) = volume.until_song.as_deref() {
+                Glob::new(pattern).with_context(|| {
+                    format!("Volume '{}': Invalid until_song pattern", volume.name)
+                })?;
+            }
+        }
+
         Ok(())
     }
 
+    /// Path of the file of the volume `number` (counted from 1),
+    /// eg. `songbook-vol1.pdf` for `songbook.pdf`.
+    pub fn volume_file(&self, number: usize) -> PathBuf {
+        let stem = self.file.file_stem().unwrap_or_default().to_string_lossy();
+        let mut filename = format!("{}-vol{}", stem, number);
+        if let Some(ext) = self.file.extension() {
+            filename.push('.');
+            filename.push_str(&ext.to_string_lossy());
+        }
+        self.file.with_file_name(filename)
+    }
+
+    /// File of a single song rendered with `bard make --song`, named after the song's `slug`.
+    pub fn song_file(&self, slug: &str) -> PathBuf {
+        let stem = self.file.file_stem().unwrap_or_default().to_string_lossy();
+        let mut filename = format!("{}-song-{}", stem, slug);
+        if let Some(ext) = self.file.extension() {
+            filename.push('.');
+            filename.push_str(&ext.to_string_lossy());
+        }
+        self.file.with_file_name(filename)
+    }
+
+    /// File of a single song preview rendered with `bard util render-song`,
+    /// for PDF outputs this is the TeX file, TeX isn't run for previews.
+    pub fn preview_file(&self) -> PathBuf {
+        let stem = self.file.file_stem().unwrap_or_default().to_string_lossy();
+        let mut filename = format!("{}.preview", stem);
+        let ext = match self.format() {
+            Format::Pdf => Some(Cow::Borrowed("tex")),
+            _ => self.file.extension().map(|ext| ext.to_string_lossy()),
+        };
+        if let Some(ext) = ext {
+            filename.push('.');
+            filename.push_str(&ext);
+        }
+        self.file.with_file_name(filename)
+    }
+
     pub fn format(&self) -> Format {
         self.format.unwrap()
     }
 
-    pub fn output_filename(&self) -> Cow<str> {
+    pub fn output_filename(&self) -> Cow<'_, str> {
         self.file
             .file_name()
             .expect("OutputSpec: Invalid filename")
@@ -123,9 +528,10 @@ impl Output {
     }
 
     pub fn template_path(&self) -> Option<&Path> {
-        match self.format() {
-            Format::Pdf | Format::Html | Format::Hovorka => self.template.as_deref(),
-            Format::Json | Format::Xml => None,
+        if self.format().has_template() {
+            self.template.as_deref()
+        } else {
+            None
         }
     }
 
@@ -133,11 +539,39 @@ impl Output {
         self.format() == Format::Pdf
     }
 
+    /// The `image_command` of an `images` output, `{input}` is replaced by the HTML page
+    /// and `{output}` by the image file. By default, `wkhtmltoimage` is used.
+    pub fn image_command(&self) -> Cow<'_, Hook> {
+        match self.image_command.as_ref() {
+            Some(command) => Cow::Borrowed(command),
+            None => Cow::Owned(Hook::Argv(
+                ["wkhtmltoimage", "--quiet", "{input}", "{output}"]
+                    .map(String::from)
+                    .to_vec(),
+            )),
+        }
+    }
+
     pub fn dpi(&self) -> f32 {
         self.dpi
             .unwrap_or_else(|| self.format.unwrap().default_dpi())
     }
 
+    /// Apply the `line_endings` and `trim_trailing_whitespace` settings to rendered text.
+    /// If neither is set, the text is returned unmodified.
+    pub fn normalize_text<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        if self.line_endings.is_none() && !self.trim_trailing_whitespace {
+            return Cow::Borrowed(text);
+        }
+
+        let line_end = self.line_endings.map(LineEndings::as_str);
+        Cow::Owned(util::normalize_lines(
+            text,
+            line_end,
+            self.trim_trailing_whitespace,
+        ))
+    }
+
     pub fn override_book_section<'a>(&self, project_book: &'a Metadata) -> Cow<'a, Metadata> {
         if self.book_overrides.is_empty() {
             Cow::Borrowed(project_book)
@@ -152,3 +586,20 @@ impl Output {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interval_parse() {
+        assert_eq!(parse_interval("60s"), Some(Duration::from_secs(60)));
+        assert_eq!(parse_interval("45"), Some(Duration::from_secs(45)));
+        assert_eq!(parse_interval("5m"), Some(Duration::from_secs(300)));
+        assert_eq!(parse_interval(" 2 h "), Some(Duration::from_secs(7200)));
+
+        for invalid in ["", "s", "1d", "-5s", "1.5m", "1m30s"] {
+            assert_eq!(parse_interval(invalid), None, "{:?}", invalid);
+        }
+    }
+}
@@ -0,0 +1,97 @@
+//! Search for songs that break rendering, used by `bard make --quarantine`.
+
+use crate::prelude::*;
+
+/// Find the songs for which `fails` returns `true`.
+///
+/// Failing sets of songs are narrowed down by halving and removed one at a time,
+/// until the remaining songs render fine. If a failure can only be reproduced
+/// with songs from both halves of a set, the whole set is considered failing.
+pub fn find_failing(
+    songs: &[usize],
+    mut fails: impl FnMut(&[usize]) -> Result<bool>,
+) -> Result<Vec<usize>> {
+    if fails(&[])? {
+        bail!("Rendering fails even without any songs, the failure is not caused by a song.");
+    }
+
+    let mut remaining = songs.to_vec();
+    let mut failing = vec![];
+    while fails(&remaining)? {
+        let set = narrow(&remaining, &mut fails)?;
+        remaining.retain(|song| !set.contains(song));
+        failing.extend(set);
+    }
+
+    failing.sort_unstable();
+    Ok(failing)
+}
+
+/// Narrow down a failing set of `songs` to the smallest failing half.
+fn narrow(songs: &[usize], fails: &mut impl FnMut(&[usize]) -> Result<bool>) -> Result<Vec<usize>> {
+    let mut set = songs;
+    while set.len() > 1 {
+        let (first, second) = set.split_at(set.len() / 2);
+        if fails(first)? {
+            set = first;
+        } else if fails(second)? {
+            set = second;
+        } else {
+            break;
+        }
+    }
+
+    Ok(set.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(num_songs: usize, fails: impl Fn(&[usize]) -> bool) -> (Vec<usize>, usize) {
+        let songs: Vec<_> = (0..num_songs).collect();
+        let mut probes = 0;
+        let failing = find_failing(&songs, |set| {
+            probes += 1;
+            Ok(fails(set))
+        })
+        .unwrap();
+        (failing, probes)
+    }
+
+    #[test]
+    fn find_failing_none() {
+        let (failing, probes) = find(100, |_| false);
+        assert!(failing.is_empty());
+        assert_eq!(probes, 2);
+    }
+
+    #[test]
+    fn find_failing_single() {
+        let (failing, probes) = find(100, |set| set.contains(&42));
+        assert_eq!(failing, [42]);
+        assert!(probes < 20, "{}", probes);
+    }
+
+    #[test]
+    fn find_failing_multiple() {
+        let (failing, _) = find(100, |set| set.contains(&3) || set.contains(&97));
+        assert_eq!(failing, [3, 97]);
+    }
+
+    #[test]
+    fn find_failing_combination() {
+        // Fails only when both songs are present, one of them has to go
+        let (failing, _) = find(4, |set| set.contains(&0) && set.contains(&3));
+        assert_eq!(failing, [0, 1, 2, 3]);
+
+        let (failing, _) = find(8, |set| set.contains(&0) && set.contains(&1));
+        assert_eq!(failing, [0, 1]);
+    }
+
+    #[test]
+    fn find_failing_always() {
+        let songs = [0, 1, 2];
+        find_failing(&songs, |_| Ok(true)).unwrap_err();
+    }
+}
@@ -0,0 +1,171 @@
+//! Set lists: a subset of the book's songs in performance order,
+//! defined by a plain text file with one song title per line.
+//!
+//! Also the `songs_filter` of outputs, which selects a subset of the songs in the book order.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::mem;
+
+use globset::GlobBuilder;
+use lexical_sort::iter::iterate_lexical_only_alnum;
+
+use crate::book::Song;
+use crate::prelude::*;
+
+/// Max number of did-you-mean suggestions per unmatched title.
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Load song titles from a set list file, empty lines are skipped.
+pub fn load(path: &Path) -> Result<Vec<String>> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Could not read set list file {:?}", path))?;
+
+    Ok(content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(String::from)
+        .collect())
+}
+
+/// Resolve set list `titles` to indices of `songs`, titles are matched case-insensitively.
+/// The error lists all the unmatched titles along with similar song titles, if any.
+pub fn resolve(path: &Path, titles: &[String], songs: &[Song]) -> Result<Vec<usize>> {
+    if titles.is_empty() {
+        bail!("Set list {:?} doesn't contain any songs.", path);
+    }
+
+    let song_titles: Vec<_> = songs.iter().map(|s| s.title.to_lowercase()).collect();
+
+    let mut indices = Vec::with_capacity(titles.len());
+    let mut unmatched = vec![];
+    for title in titles {
+        let lowercase = title.to_lowercase();
+        match song_titles.iter().position(|t| *t == lowercase) {
+            Some(idx) => indices.push(idx),
+            None => unmatched.push(title),
+        }
+    }
+
+    if unmatched.is_empty() {
+        return Ok(indices);
+    }
+
+    let mut msg = format!("Set list {:?}: Songs not found in the book:", path);
+    for title in unmatched {
+        write!(msg, "\n  {:?}", title).unwrap();
+        let suggestions = suggestions(title, songs);
+        if !suggestions.is_empty() {
+            let suggestions: Vec<_> = suggestions.iter().map(|s| format!("{:?}", s)).collect();
+            write!(msg, ", did you mean: {}?", suggestions.join(", ")).unwrap();
+        }
+    }
+
+    Err(anyhow!(msg))
+}
+
+/// Resolve `songs_filter` entries of an output to indices of `songs`, in the book order.
+/// An entry selects the songs with the same title, matched case-insensitively, and the songs
+/// whose file name in `file_names` it matches as a glob pattern, eg. `live/*.md`.
+/// The error lists all the entries that don't match any song, along with similar song titles, if any.
+pub fn filter(entries: &[String], songs: &[Song], file_names: &[PathBuf]) -> Result<Vec<usize>> {
+    let mut selected = vec![false; songs.len()];
+    let mut unmatched = vec![];
+    for entry in entries {
+        let title = entry.trim().to_lowercase();
+        // An entry that isn't a valid glob can still be a title, eg. `[Untitled`
+        let glob = GlobBuilder::new(entry)
+            .literal_separator(true)
+            .build()
+            .ok()
+            .map(|glob| glob.compile_matcher());
+
+        let mut matched = false;
+        for (idx, song) in songs.iter().enumerate() {
+            let by_file = glob
+                .as_ref()
+                .map_or(false, |glob| glob.is_match(&file_names[idx]));
+            if by_file || song.title.to_lowercase() == title {
+                selected[idx] = true;
+                matched = true;
+            }
+        }
+        if !matched {
+            unmatched.push(entry);
+        }
+    }
+
+    if unmatched.is_empty() {
+        return Ok((0..songs.len()).filter(|&idx| selected[idx]).collect());
+    }
+
+    let mut msg = "No songs match these entries:".to_string();
+    for entry in unmatched {
+        write!(msg, "\n  {:?}", entry).unwrap();
+        let suggestions = suggestions(entry, songs);
+        if !suggestions.is_empty() {
+            let suggestions: Vec<_> = suggestions.iter().map(|s| format!("{:?}", s)).collect();
+            write!(msg, ", did you mean: {}?", suggestions.join(", ")).unwrap();
+        }
+    }
+
+    Err(anyhow!(msg))
+}
+
+/// Normalize a title for fuzzy comparison: lowercase, transliterated to ASCII, alphanumeric only.
+fn normalize(title: &str) -> Vec<char> {
+    iterate_lexical_only_alnum(title).collect()
+}
+
+/// Levenshtein distance of two strings.
+pub(super) fn distance(a: &[char], b: &[char]) -> usize {
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut row = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let subst = prev[j] + usize::from(ca != cb);
+            row[j + 1] = subst.min(prev[j + 1] + 1).min(row[j] + 1);
+        }
+        mem::swap(&mut prev, &mut row);
+    }
+    prev[b.len()]
+}
+
+/// Song titles similar to `title`, the most similar first.
+fn suggestions<'s>(title: &str, songs: &'s [Song]) -> Vec<&'s str> {
+    let title = normalize(title);
+    if title.is_empty() {
+        return vec![];
+    }
+
+    let max_distance = (title.len() / 3).max(1);
+    let mut candidates: Vec<_> = songs
+        .iter()
+        .filter_map(|song| {
+            let normalized = normalize(&song.title);
+            let dist = distance(&title, &normalized);
+            let contains = |hay: &[char], needle: &[char]| {
+                !needle.is_empty() && hay.windows(needle.len()).any(|w| w == needle)
+            };
+
+            if dist <= max_distance
+                || contains(&normalized, &title)
+                || contains(&title, &normalized)
+            {
+                Some((dist, &*song.title))
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    candidates.sort_by_key(|(dist, _)| *dist);
+    candidates.dedup_by_key(|(_, title)| *title);
+    candidates
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, title)| title)
+        .collect()
+}
@@ -0,0 +1,102 @@
+//! Snippets: reusable text referenced from songs with `!use(name)`,
+//! defined in the `[snippets]` section and in an optional snippets file.
+
+use std::collections::BTreeMap;
+use std::fs;
+
+use crate::prelude::*;
+
+/// Load snippets from a snippets file, where each snippet starts with a `# name` heading
+/// followed by the snippet text up until the next heading.
+pub fn load(path: &Path) -> Result<BTreeMap<String, String>> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Could not read snippets file {:?}", path))?;
+    parse(&content).with_context(|| format!("Invalid snippets file {:?}", path))
+}
+
+fn parse(content: &str) -> Result<BTreeMap<String, String>> {
+    let mut snippets = BTreeMap::new();
+    let mut current: Option<(&str, String)> = None;
+    for (num, line) in content.lines().enumerate() {
+        if let Some(name) = line.strip_prefix("# ").map(str::trim) {
+            if name.is_empty() {
+                bail!("Line {}: Missing snippet name", num + 1);
+            }
+            if let Some((name, text)) = current.replace((name, String::new())) {
+                insert(&mut snippets, name, text)?;
+            }
+        } else if let Some((_, text)) = current.as_mut() {
+            text.push_str(line);
+            text.push('\n');
+        } else if !line.trim().is_empty() {
+            bail!(
+                "Line {}: Text outside of a snippet, each snippet needs to start with a `# name` heading",
+                num + 1
+            );
+        }
+    }
+
+    if let Some((name, text)) = current {
+        insert(&mut snippets, name, text)?;
+    }
+
+    Ok(snippets)
+}
+
+fn insert(snippets: &mut BTreeMap<String, String>, name: &str, text: String) -> Result<()> {
+    if snippets
+        .insert(name.to_string(), text.trim().to_string())
+        .is_some()
+    {
+        bail!("Snippet '{}' is defined more than once", name);
+    }
+
+    Ok(())
+}
+
+/// Merge snippets from the `[snippets]` section with those from the snippets file, if any.
+pub fn merge(
+    section: &BTreeMap<String, String>,
+    file: Option<&Path>,
+) -> Result<BTreeMap<String, String>> {
+    let mut snippets = section.clone();
+    let path = match file {
+        Some(path) => path,
+        None => return Ok(snippets),
+    };
+
+    for (name, text) in load(path)? {
+        if snippets.contains_key(&name) {
+            bail!(
+                "Snippet '{}' is defined both in the [snippets] section and in the snippets file {:?}",
+                name,
+                path
+            );
+        }
+        snippets.insert(name, text);
+    }
+
+    Ok(snippets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snippets_parse() {
+        let snippets =
+            parse("\n# trad\nTraditional, arr. Jane Doe\n\n#  bridge \nLa la la\nLa la *la*\n\n")
+                .unwrap();
+        assert_eq!(snippets.len(), 2);
+        assert_eq!(snippets["trad"], "Traditional, arr. Jane Doe");
+        assert_eq!(snippets["bridge"], "La la la\nLa la *la*");
+    }
+
+    #[test]
+    fn snippets_parse_invalid() {
+        parse("Text\n# trad\nTraditional\n").unwrap_err();
+        parse("# \nTraditional\n").unwrap_err();
+        parse("# trad\nTraditional\n# trad\nFolk\n").unwrap_err();
+    }
+}
@@ -0,0 +1,173 @@
+//! Bookkeeping of files generated by bard in the output directory, used by `bard make --prune`.
+//!
+//! Files are only ever removed if they were recorded here by a previous build,
+//! files bard didn't create, eg. fonts or other assets, are never touched.
+
+use std::fs;
+use std::path::Component;
+
+use serde::{Deserialize, Serialize};
+
+use crate::app::App;
+use crate::prelude::*;
+use crate::render::images;
+
+/// The state file in the output directory.
+pub const STATE_FILE: &str = ".bard-state.json";
+
+/// A file or directory rendered by bard.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct GeneratedFile {
+    /// Path relative to the output directory, with `/` separators.
+    pub path: String,
+    /// File name of the output in `bard.toml` the file was rendered for.
+    pub output: String,
+    /// Whether this is the directory of an images output, see `images::remove_output()`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub slides: bool,
+}
+
+impl GeneratedFile {
+    /// Record `file` rendered for `output`, `None` if the file isn't in `dir_output`,
+    /// such files are never removed.
+    pub fn new(dir_output: &Path, file: &Path, output: &str, slides: bool) -> Option<Self> {
+        let relative = file.strip_prefix(dir_output).ok()?;
+        let parts = relative
+            .components()
+            .map(|c| match c {
+                Component::Normal(part) => part.to_str(),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        Some(Self {
+            path: parts.join("/"),
+            output: output.to_string(),
+            slides,
+        })
+    }
+
+    /// Full path of the file, `None` if the recorded path doesn't point inside `dir_output`,
+    /// the state file may have been edited by hand.
+    fn full_path(&self, dir_output: &Path) -> Option<PathBuf> {
+        let path = Path::new(&self.path);
+        let inside = path
+            .components()
+            .all(|c| matches!(c, Component::Normal(..)));
+        (inside && !self.path.is_empty()).then(|| dir_output.join(path))
+    }
+
+    fn remove(&self, dir_output: &Path) -> Result<()> {
+        let path = match self.full_path(dir_output) {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+
+        if self.slides {
+            if path.is_dir() {
+                images::remove_output(&path)?;
+            }
+        } else if path.is_file() {
+            fs::remove_file(&path).with_context(|| format!("Could not remove file {:?}", path))?;
+        }
+
+        Ok(())
+    }
+
+    fn exists(&self, dir_output: &Path) -> bool {
+        self.full_path(dir_output)
+            .map(|path| path.exists())
+            .unwrap_or(false)
+    }
+}
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct OutputState {
+    #[serde(default)]
+    pub generated: Vec<GeneratedFile>,
+}
+
+impl OutputState {
+    /// Load the state of `dir_output`, a missing or unreadable state file is the same as an empty one.
+    pub fn load(dir_output: &Path) -> Self {
+        fs::read_to_string(dir_output.join(STATE_FILE))
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, dir_output: &Path) -> Result<()> {
+        let path = dir_output.join(STATE_FILE);
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(&path, json.as_bytes())
+            .with_context(|| format!("Could not write file {:?}", path))
+    }
+}
+
+/// Record the files `generated` by a build in the state of `dir_output`.
+///
+/// Files recorded by previous builds which are no longer generated are stale.
+/// With `prune`, they are removed, otherwise they are kept in the state as long as they exist,
+/// so that a later build with `prune` can remove them. Files that fail to be removed
+/// are reported and kept in the state as well.
+pub fn update(
+    app: &App,
+    dir_output: &Path,
+    generated: Vec<GeneratedFile>,
+    prune: bool,
+) -> Result<()> {
+    let previous = OutputState::load(dir_output);
+    let mut state = OutputState { generated };
+
+    let stale: Vec<_> = previous
+        .generated
+        .into_iter()
+        .filter(|prev| !state.generated.iter().any(|file| file.path == prev.path))
+        .filter(|prev| prev.exists(dir_output))
+        .collect();
+    for file in stale {
+        if !prune {
+            state.generated.push(file);
+            continue;
+        }
+
+        app.status(
+            "Pruning",
+            format!("{} (from output {})", file.path, file.output),
+        );
+        if let Err(err) = file.remove(dir_output) {
+            app.warning(format!("{:#}", err));
+            state.generated.push(file);
+        }
+    }
+
+    state.save(dir_output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_file_paths() {
+        let dir = Path::new("/project/output");
+        let file = GeneratedFile::new(dir, &dir.join("html/songbook.html"), "songbook.html", false)
+            .unwrap();
+        assert_eq!(file.path, "html/songbook.html");
+        assert_eq!(file.full_path(dir).unwrap(), dir.join("html/songbook.html"));
+
+        // Files outside of the output directory are not recorded
+        let outside = Path::new("/project/songbook.pdf");
+        assert!(GeneratedFile::new(dir, outside, "songbook.pdf", false).is_none());
+
+        // Paths from an edited state file that lead outside are not used
+        for path in ["../songs/song.md", "/etc/passwd", ""] {
+            let file = GeneratedFile {
+                path: path.into(),
+                output: "songbook.pdf".into(),
+                slides: false,
+            };
+            assert!(file.full_path(dir).is_none(), "{}", path);
+        }
+    }
+}
@@ -0,0 +1,49 @@
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::*;
+
+/// Style hints for one kind of verse.
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Style {
+    /// Render the text emphasized (italics).
+    #[serde(default)]
+    pub emph: bool,
+    /// Additional CSS class(es) used in HTML outputs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub html_class: Option<String>,
+}
+
+impl Style {
+    fn validate(&self, name: &str) -> Result<()> {
+        if let Some(class) = self.html_class.as_deref() {
+            let valid = !class.trim().is_empty()
+                && class
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || c == '-' || c == '_' || c == ' ');
+            if !valid {
+                bail!(
+                    "Invalid html_class {:?} for style '{}': expected space-separated CSS class names",
+                    class,
+                    name
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The `[book.styles]` section, style hints applied consistently by the default templates.
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Styles {
+    #[serde(default)]
+    pub chorus: Style,
+}
+
+impl Styles {
+    pub fn validate(&self) -> Result<()> {
+        self.chorus.validate("chorus")
+    }
+}
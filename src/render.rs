@@ -1,90 +1,483 @@
 use std::borrow::Cow;
+use std::collections::BTreeSet;
 
 use semver::Version;
 use serde::Serialize;
 
 use crate::app::App;
-use crate::book::{Song, SongRef};
-use crate::music::Notation;
+use crate::book::{Block, Inline, Matter, Song, SongAlias, SongRef};
+use crate::diag::{BuildDiagnostic, Phase};
+use crate::music::{Notation, NotationLegend};
 use crate::prelude::*;
-use crate::project::{Format, Metadata, Output, Project};
-use crate::util::ImgCache;
+use crate::project::{
+    BookMeta, Format, HtmlContent, Labels, Metadata, Output, Project, Styles, VolumeSpan,
+};
+use crate::time::Duration;
 use crate::{ProgramMeta, PROGRAM_META};
 
 #[macro_use]
 pub mod template;
+pub mod chordpro;
 pub mod hovorka;
 pub mod html;
+pub mod images;
 pub mod json;
 pub mod pdf;
 pub mod tex_tools;
+pub mod txt;
 pub mod xml;
 
+pub use self::chordpro::RChordPro;
+use self::hovorka::HovorkaSong;
 pub use self::hovorka::RHovorka;
 pub use self::html::RHtml;
-pub use self::json::RJson;
+pub use self::images::RImages;
+pub use self::json::{RJson, RJsonl};
+use self::pdf::FontFiles;
 pub use self::pdf::RPdf;
 use self::template::DefaultTemaplate;
+pub use self::txt::RTxt;
 pub use self::xml::RXml;
 
 pub static DEFAULT_TEMPLATES: &[&DefaultTemaplate] = &[
     &pdf::DEFAULT_TEMPLATE,
     &html::DEFAULT_TEMPLATE,
     &hovorka::DEFAULT_TEMPLATE,
+    &images::DEFAULT_TEMPLATE,
 ];
 
+/// An entry of the combined index of all volumes.
+#[derive(Serialize, Debug)]
+pub struct VolumeIndexEntry<'a> {
+    title: &'a str,
+    /// Song number in the whole book, counted from 0
+    idx: usize,
+    /// Number of the volume the song is in, counted from 1
+    volume: usize,
+    volume_name: &'a str,
+}
+
+impl<'a> VolumeIndexEntry<'a> {
+    fn build(project: &'a Project, output: &Output, volumes: &'a [VolumeSpan]) -> Vec<Self> {
+        songs_sorted(project, output)
+            .iter()
+            .filter_map(|song| {
+                let volume = volumes.iter().find(|v| v.songs.contains(&song.idx))?;
+                Some(Self {
+                    title: &project.songs()[song.idx].title,
+                    idx: song.idx,
+                    volume: volume.number,
+                    volume_name: &volume.volume.name,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Information about the volume being rendered, if the output is split into volumes.
+#[derive(Serialize, Debug)]
+pub struct VolumeContext<'a> {
+    name: &'a str,
+    /// Volume number, counted from 1
+    number: usize,
+    /// Total number of volumes
+    count: usize,
+    /// Number of songs in the previous volumes, ie. the offset of the song numbering in this volume
+    song_offset: usize,
+    /// All songs of the book sorted by title along with the volume they're in
+    index: Vec<VolumeIndexEntry<'a>>,
+}
+
+/// The slide being rendered by an `images` output.
+#[derive(Serialize, Debug)]
+pub struct SlideContext<'a> {
+    /// Slide number, counted from 1
+    number: usize,
+    /// Total number of slides
+    count: usize,
+    /// Blocks of the song shown on this slide, either all of them or a single verse
+    blocks: &'a [Block],
+}
+
 #[derive(Serialize, Debug)]
 pub struct RenderContext<'a> {
     book: Cow<'a, Metadata>,
-    songs: &'a [Song],
-    songs_sorted: &'a [SongRef],
+    /// Description, author, URL, and cover image of the book, for page metadata
+    book_meta: &'a BookMeta,
+    /// Book-level content before the songs, empty in volumes other than the first one
+    front_matter: &'a [Matter],
+    songs: Vec<&'a Song>,
+    /// Book-level content after the songs, empty in volumes other than the last one
+    back_matter: &'a [Matter],
+    songs_sorted: Cow<'a, [SongRef]>,
+    /// Songs which have a first line, sorted by it, for an index of first lines
+    songs_by_first_line: Vec<SongRef>,
+    /// Previous titles of the songs, the `idx` of the entries refers to `songs`
+    aliases: Cow<'a, [SongAlias]>,
+    /// Whether any song of the book is right-to-left, templates may need additional setup
+    has_rtl: bool,
+    /// Whether the default fonts are in the output directory, see `Project::fonts_available()`,
+    /// the default templates fall back to the standard font setup otherwise
+    fonts_available: bool,
+    /// Font files of a PDF output, if it has any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fonts: Option<FontFiles>,
+    /// Songs prepared for the Hovorka app, set by the `hovorka` output
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hovorka: Option<Vec<HovorkaSong>>,
+    /// Sum of durations of the songs in this context, if any song has a duration
+    #[serde(skip_serializing_if = "Option::is_none")]
+    total_duration: Option<Duration>,
     notation: Notation,
+    /// Tone names in the book's notation and in the notations of alt chords, for legends
+    notation_legend: Vec<NotationLegend>,
+    styles: &'a Styles,
+    labels: &'a Labels,
     output: &'a Output,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    volume: Option<VolumeContext<'a>>,
+    /// Set when rendering a single song with `bard make --song`, templates should leave out front matter
+    handout: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    slide: Option<SlideContext<'a>>,
     program: &'static ProgramMeta,
 }
 
 impl<'a> RenderContext<'a> {
-    fn new(project: &'a Project, output: &'a Output) -> Self {
+    /// Context with all the `songs`, ie. those of the book or their copies converted
+    /// to the output's notation, see `Renderer::songs()`.
+    fn new(project: &'a Project, output: &'a Output, songs: &'a [Song]) -> Self {
+        let notation = output.notation.as_ref().unwrap_or(&project.book.notation);
+        let songs: Vec<_> = songs.iter().collect();
+        let songs_sorted = songs_sorted(project, output);
         RenderContext {
             book: output.override_book_section(project.book_section()),
-            songs: project.songs(),
-            songs_sorted: project.songs_sorted(),
-            notation: project.settings.notation,
+            book_meta: &project.settings.book_meta,
+            total_duration: total_duration(&songs),
+            front_matter: &project.book.front_matter,
+            songs,
+            back_matter: &project.book.back_matter,
+            songs_by_first_line: songs_by_first_line(project, output, &songs_sorted),
+            songs_sorted,
+            aliases: Cow::Borrowed(project.aliases()),
+            has_rtl: project.has_rtl(),
+            fonts_available: project.fonts_available(),
+            fonts: None,
+            hovorka: None,
+            notation: notation.clone(),
+            notation_legend: NotationLegend::for_book(notation, &project.book.alt_notations),
+            styles: &project.settings.styles,
+            labels: &project.settings.labels,
             output,
+            volume: None,
+            handout: false,
+            slide: None,
             program: &PROGRAM_META,
         }
     }
+
+    /// Context with only the songs of `volume`,
+    /// the `idx` of `songs_sorted` entries refers to the songs of the volume.
+    fn with_volume(
+        project: &'a Project,
+        output: &'a Output,
+        all_songs: &'a [Song],
+        volumes: &'a [VolumeSpan],
+        volume: &'a VolumeSpan,
+    ) -> Self {
+        let range = volume.songs.clone();
+        let songs: Vec<_> = all_songs[range.clone()].iter().collect();
+        let songs_sorted = songs_sorted(project, output)
+            .iter()
+            .filter(|song| range.contains(&song.idx))
+            .map(|song| SongRef {
+                title: song.title.clone(),
+                idx: song.idx - range.start,
+                ..song.clone()
+            })
+            .collect::<Vec<_>>();
+        let aliases = remap_aliases(project, |idx| {
+            range.contains(&idx).then(|| idx - range.start)
+        });
+
+        let (mut front_matter, mut back_matter) = (&[][..], &[][..]);
+        if volume.number == 1 {
+            front_matter = &project.book.front_matter;
+        }
+        if volume.number == volumes.len() {
+            back_matter = &project.book.back_matter;
+        }
+
+        RenderContext {
+            total_duration: total_duration(&songs),
+            front_matter,
+            songs,
+            back_matter,
+            songs_by_first_line: songs_by_first_line(project, output, &songs_sorted),
+            songs_sorted: Cow::Owned(songs_sorted),
+            aliases: Cow::Owned(aliases),
+            volume: Some(VolumeContext {
+                name: &volume.volume.name,
+                number: volume.number,
+                count: volumes.len(),
+                song_offset: range.start,
+                index: VolumeIndexEntry::build(project, output, volumes),
+            }),
+            ..Self::new(project, output, all_songs)
+        }
+    }
+
+    /// Context with only the songs of a set list, in the set list order, or any other subset of the songs,
+    /// the `idx` of `songs_sorted` entries refers to the position in the set list.
+    fn with_setlist(
+        project: &'a Project,
+        output: &'a Output,
+        all_songs: &'a [Song],
+        setlist: &[usize],
+    ) -> Self {
+        let songs: Vec<_> = setlist.iter().map(|&idx| &all_songs[idx]).collect();
+        let mut songs_sorted: Vec<_> = songs
+            .iter()
+            .copied()
+            .enumerate()
+            .map(SongRef::new)
+            .collect();
+        project
+            .sort_order(output)
+            .sort_by(&mut songs_sorted, |song| song.title.as_ref());
+        let aliases = remap_aliases(project, |idx| setlist.iter().position(|&i| i == idx));
+
+        RenderContext {
+            total_duration: total_duration(&songs),
+            songs,
+            songs_by_first_line: songs_by_first_line(project, output, &songs_sorted),
+            songs_sorted: Cow::Owned(songs_sorted),
+            aliases: Cow::Owned(aliases),
+            ..Self::new(project, output, all_songs)
+        }
+    }
+
+    /// Check that the image files of the songs in this context could be read,
+    /// images of songs left out of the context don't matter.
+    fn check_images(&self) -> Result<()> {
+        for song in self.songs.iter() {
+            for image in song.images() {
+                image
+                    .check()
+                    .with_context(|| format!("Image in song '{}'", song.title))?;
+            }
+        }
+        let matter = self.front_matter.iter().chain(self.back_matter.iter());
+        for image in matter.flat_map(Matter::images) {
+            image.check().context("Image in front or back matter")?;
+        }
+        Ok(())
+    }
+
+    /// Context with only the song `idx`, rendered on its own by `bard make --song`.
+    fn with_handout(
+        project: &'a Project,
+        output: &'a Output,
+        all_songs: &'a [Song],
+        idx: usize,
+    ) -> Self {
+        RenderContext {
+            front_matter: &[],
+            back_matter: &[],
+            handout: true,
+            ..Self::with_setlist(project, output, all_songs, &[idx])
+        }
+    }
+}
+
+impl<'a> RenderContext<'a> {
+    /// Context of a single slide of an `images` output showing `blocks` of the song
+    /// at position `idx` in this context. Like a handout, the slide has no front and back matter.
+    fn with_slide(&self, idx: usize, slide: SlideContext<'a>) -> RenderContext<'a> {
+        let song = self.songs[idx];
+        let aliases = self
+            .aliases
+            .iter()
+            .filter(|alias| alias.idx == idx)
+            .map(|alias| SongAlias {
+                idx: 0,
+                ..alias.clone()
+            })
+            .collect();
+
+        RenderContext {
+            book: self.book.clone(),
+            book_meta: self.book_meta,
+            front_matter: &[],
+            songs: vec![song],
+            back_matter: &[],
+            songs_sorted: Cow::Owned(vec![SongRef::new((0, song))]),
+            songs_by_first_line: self
+                .songs_by_first_line
+                .iter()
+                .filter(|song| song.idx == idx)
+                .map(|song| SongRef {
+                    idx: 0,
+                    ..song.clone()
+                })
+                .collect(),
+            aliases: Cow::Owned(aliases),
+            has_rtl: self.has_rtl,
+            fonts_available: self.fonts_available,
+            fonts: None,
+            hovorka: None,
+            total_duration: song.duration,
+            notation: self.notation.clone(),
+            notation_legend: self.notation_legend.clone(),
+            styles: self.styles,
+            labels: self.labels,
+            output: self.output,
+            volume: None,
+            handout: true,
+            slide: Some(slide),
+            program: self.program,
+        }
+    }
+}
+
+/// Aliases of the songs for which `map_idx` returns their index in a context.
+fn remap_aliases(project: &Project, map_idx: impl Fn(usize) -> Option<usize>) -> Vec<SongAlias> {
+    project
+        .aliases()
+        .iter()
+        .filter_map(|alias| {
+            Some(SongAlias {
+                idx: map_idx(alias.idx)?,
+                ..alias.clone()
+            })
+        })
+        .collect()
+}
+
+/// `songs_sorted` of the book, sorted again if `output` overrides the sort order.
+fn songs_sorted<'a>(project: &'a Project, output: &Output) -> Cow<'a, [SongRef]> {
+    let sort = project.sort_order(output);
+    if *sort == project.settings.sort {
+        return Cow::Borrowed(project.songs_sorted());
+    }
+
+    let mut songs_sorted: Vec<_> = project
+        .songs()
+        .iter()
+        .enumerate()
+        .map(SongRef::new)
+        .collect();
+    sort.sort_by(&mut songs_sorted, |song| song.title.as_ref());
+    Cow::Owned(songs_sorted)
+}
+
+/// Entries of `songs_sorted` of songs which have a first line, sorted by the first line.
+fn songs_by_first_line(
+    project: &Project,
+    output: &Output,
+    songs_sorted: &[SongRef],
+) -> Vec<SongRef> {
+    let mut songs: Vec<_> = songs_sorted
+        .iter()
+        .filter(|song| !song.first_line.is_empty())
+        .cloned()
+        .collect();
+    project
+        .sort_order(output)
+        .sort_by(&mut songs, |song| song.first_line.as_ref());
+    songs
+}
+
+/// Copies of the book's songs with chords converted to the `output`'s notation, if it has one.
+/// Chords that can't be converted are reported and left as they are.
+fn convert_songs(app: &App, project: &Project, output: &Output) -> Option<Vec<Song>> {
+    let notation = output.notation.as_ref()?;
+    let songs = project
+        .songs()
+        .iter()
+        .map(|song| {
+            let mut song = song.clone();
+            let failed = song.convert_notation(notation, project.settings.minor_chords);
+            if !failed.is_empty() {
+                let msg = format!(
+                    "Song '{}': Chords that couldn't be converted to the {} notation are left as they are: {}",
+                    song.title,
+                    notation,
+                    failed.join(", ")
+                );
+                app.diag(
+                    BuildDiagnostic::warning(Phase::Render, msg)
+                        .with_file(&song.location.file)
+                        .with_line(song.location.line),
+                );
+            }
+            song
+        })
+        .collect();
+    Some(songs)
+}
+
+/// Sum of the `songs` durations, `None` if none of the songs has a duration.
+fn total_duration(songs: &[&Song]) -> Option<Duration> {
+    let mut durations = songs.iter().filter_map(|song| song.duration).peekable();
+    durations.peek()?;
+    Some(durations.sum())
 }
 
 trait Render {
     /// Render the output file based on `project` and `output`.
     fn render(&self, app: &App, output: &Path, context: RenderContext) -> Result<()>;
 
+    /// Render the output file as quickly as possible to find out whether rendering succeeds,
+    /// see `Project::render_quarantined()`.
+    fn render_probe(&self, app: &App, output: &Path, context: RenderContext) -> Result<()> {
+        self.render(app, output, context)
+    }
+
+    /// Render a preview of the output file for template development, see `bard util render-song`.
+    /// Post-processing such as running TeX is left out.
+    fn render_preview(&self, app: &App, output: &Path, context: RenderContext) -> Result<()> {
+        self.render(app, output, context)
+    }
+
     /// Returns the AST version specified in the template, if any.
     fn version(&self) -> Option<Version> {
         None
     }
+
+    /// Names of HTML tags the template has extension partials for,
+    /// `None` if the output isn't expected to drop HTML tags, see `Output::html_content`.
+    fn html_tags(&self) -> Option<&BTreeSet<String>> {
+        None
+    }
 }
 
 pub struct Renderer<'a> {
     project: &'a Project,
     output: &'a Output,
+    /// Copies of the book's songs converted to the output's notation, if it has one.
+    converted: Option<Vec<Song>>,
     render: Box<dyn Render>,
 }
 
 impl<'a> Renderer<'a> {
-    pub fn new(project: &'a Project, output: &'a Output, img_cache: &ImgCache) -> Result<Self> {
+    pub fn new(app: &App, project: &'a Project, output: &'a Output) -> Result<Self> {
         let render: Box<dyn Render> = match output.format() {
-            Format::Pdf => Box::new(RPdf::new(project, output, img_cache)?),
-            Format::Html => Box::new(RHtml::new(project, output, img_cache)?),
-            Format::Hovorka => Box::new(RHovorka::new(project, output, img_cache)?),
+            Format::Pdf => Box::new(RPdf::new(app, project, output)?),
+            Format::Html => Box::new(RHtml::new(app, project, output)?),
+            Format::Hovorka => Box::new(RHovorka::new(app, project, output)?),
+            Format::Images => Box::new(RImages::new(app, project, output)?),
             Format::Json => Box::new(RJson::new()),
+            Format::Jsonl => Box::new(RJsonl::new()),
             Format::Xml => Box::new(RXml::new()),
+            Format::Txt => Box::new(RTxt::new()),
+            Format::ChordPro => Box::new(RChordPro::new()),
         };
 
         Ok(Self {
             project,
             output,
+            converted: convert_songs(app, project, output),
             render,
         })
     }
@@ -93,8 +486,106 @@ impl<'a> Renderer<'a> {
         self.render.version()
     }
 
+    /// Songs of the book as rendered by this output.
+    fn songs(&self) -> &[Song] {
+        self.converted
+            .as_deref()
+            .unwrap_or_else(|| self.project.songs())
+    }
+
+    fn render_context(&self, app: &App, file: &Path, context: RenderContext) -> Result<()> {
+        context.check_images()?;
+        self.warn_dropped_tags(app, &context);
+        self.render.render(app, file, context)
+    }
+
+    /// With `html_content = "warn"`, report the HTML tags of each song
+    /// which the template has no extension partials for, ie. which are left out of the output.
+    fn warn_dropped_tags(&self, app: &App, context: &RenderContext) {
+        let partials = match self.render.html_tags() {
+            Some(partials) if self.output.html_content == HtmlContent::Warn => partials,
+            _ => return,
+        };
+
+        for song in context.songs.iter() {
+            let mut dropped = BTreeSet::new();
+            song.visit_inlines(|inline| {
+                if let Inline::HtmlTag(tag) = inline {
+                    let name = tag.name.trim_matches('/');
+                    if !partials.contains(name) {
+                        dropped.insert(name);
+                    }
+                }
+            });
+            if dropped.is_empty() {
+                continue;
+            }
+
+            let tags: Vec<_> = dropped.iter().map(|tag| format!("<{}>", tag)).collect();
+            let msg = format!(
+                "Song '{}': HTML tags left out of the output: {}",
+                song.title,
+                tags.join(", ")
+            );
+            app.diag(
+                BuildDiagnostic::warning(Phase::Render, msg)
+                    .with_file(&song.location.file)
+                    .with_line(song.location.line),
+            );
+        }
+    }
+
     pub fn render(&self, app: &App) -> Result<()> {
-        let context = RenderContext::new(self.project, self.output);
-        self.render.render(app, &self.output.file, context)
+        let songs = self.project.output_songs(self.output)?;
+        let context = match songs.as_deref() {
+            Some(songs) => {
+                let subset = match self.output.setlist {
+                    Some(_) => "Set list",
+                    None => "Songs filter",
+                };
+                app.indent(format!("{}: {} songs", subset, songs.len()));
+                RenderContext::with_setlist(self.project, self.output, self.songs(), songs)
+            }
+            None => RenderContext::new(self.project, self.output, self.songs()),
+        };
+        self.render_context(app, &self.output.file, context)
+    }
+
+    /// Render only the `songs` into the output file, in the given order.
+    pub fn render_songs(&self, app: &App, songs: &[usize]) -> Result<()> {
+        let context = RenderContext::with_setlist(self.project, self.output, self.songs(), songs);
+        self.render_context(app, &self.output.file, context)
+    }
+
+    /// Probe whether rendering only the `songs` into `file` succeeds, see `Render::render_probe()`.
+    pub fn render_probe(&self, app: &App, songs: &[usize], file: &Path) -> Result<()> {
+        let context = RenderContext::with_setlist(self.project, self.output, self.songs(), songs);
+        context.check_images()?;
+        self.render.render_probe(app, file, context)
+    }
+
+    /// Render only the song `idx` into `file`, see `Project::song_files()`.
+    pub fn render_song(&self, app: &App, idx: usize, file: &Path) -> Result<()> {
+        let context = RenderContext::with_handout(self.project, self.output, self.songs(), idx);
+        self.render_context(app, file, context)
+    }
+
+    /// Render a preview of only the song `idx` into `file`, see `Render::render_preview()`.
+    pub fn render_preview(&self, app: &App, idx: usize, file: &Path) -> Result<()> {
+        let context = RenderContext::with_handout(self.project, self.output, self.songs(), idx);
+        context.check_images()?;
+        self.render.render_preview(app, file, context)
+    }
+
+    /// Render one of the `volumes` of the output into its own file.
+    pub fn render_volume(
+        &self,
+        app: &App,
+        volumes: &[VolumeSpan],
+        volume: &VolumeSpan,
+    ) -> Result<()> {
+        let context =
+            RenderContext::with_volume(self.project, self.output, self.songs(), volumes, volume);
+        self.render_context(app, &volume.file, context)
     }
 }
@@ -0,0 +1,231 @@
+//! ChordPro output, for exchanging songs with apps such as OnSong or SongBook.
+
+use std::collections::BTreeSet;
+use std::fs;
+
+use super::{Render, RenderContext};
+use crate::app::App;
+use crate::book::{Block, DirectiveKind, Inline, Song, Verse, VerseLabel};
+use crate::diag::{BuildDiagnostic, Phase};
+use crate::prelude::*;
+use crate::project::Labels;
+
+#[derive(Debug, Default)]
+pub struct RChordPro;
+
+impl RChordPro {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Render for RChordPro {
+    fn render(&self, app: &App, output: &Path, context: RenderContext) -> Result<()> {
+        let chorus_label = context
+            .book
+            .get("chorus_label")
+            .and_then(|label| label.as_str())
+            .unwrap_or("");
+
+        let mut text = String::new();
+        for (i, song) in context.songs.iter().enumerate() {
+            if i > 0 {
+                text.push_str("\n{new_song}\n");
+            }
+
+            let mut writer = ChordProWriter {
+                labels: context.labels,
+                chorus_label,
+                lines: vec![],
+                dropped: BTreeSet::new(),
+            };
+            writer.song(song);
+            for line in writer.lines.iter() {
+                text.push_str(line.trim_end());
+                text.push('\n');
+            }
+
+            if !writer.dropped.is_empty() {
+                let dropped: Vec<_> = writer.dropped.into_iter().collect();
+                let msg = format!(
+                    "Song '{}': {} left out of the ChordPro output",
+                    song.title,
+                    dropped.join(", ")
+                );
+                app.diag(
+                    BuildDiagnostic::warning(Phase::Render, msg)
+                        .with_file(&song.location.file)
+                        .with_line(song.location.line),
+                );
+            }
+        }
+        let text = context.output.normalize_text(&text);
+
+        fs::write(output, text.as_bytes())
+            .with_context(|| format!("Error writing output file: {:?}", output))
+    }
+}
+
+struct ChordProWriter<'a> {
+    labels: &'a Labels,
+    chorus_label: &'a str,
+    lines: Vec<String>,
+    /// Kinds of content that ChordPro can't express, reported once per song.
+    dropped: BTreeSet<&'static str>,
+}
+
+impl<'a> ChordProWriter<'a> {
+    fn song(&mut self, song: &Song) {
+        self.lines.push(format!("{{title: {}}}", song.title));
+        for subtitle in song.subtitles.iter() {
+            self.lines.push(format!("{{subtitle: {}}}", subtitle));
+        }
+        if let Some(tempo) = song.tempo.as_deref() {
+            self.lines.push(format!("{{tempo: {}}}", tempo));
+        }
+        if let Some(time) = song.time.as_deref() {
+            self.lines.push(format!("{{time: {}}}", time));
+        }
+        if let Some(key) = song.key.as_deref() {
+            self.lines.push(format!("{{key: {}}}", key));
+        }
+        if let Some(duration) = song.duration {
+            self.lines.push(format!("{{duration: {}}}", duration));
+        }
+
+        for block in song.blocks.iter() {
+            self.lines.push(String::new());
+            self.block(block);
+        }
+    }
+
+    fn block(&mut self, block: &Block) {
+        match block {
+            Block::Verse(verse) => self.verse(verse),
+            Block::BulletList(list) => {
+                for item in list.items.iter() {
+                    self.lines.push(format!("{{comment: {}}}", item));
+                }
+            }
+            Block::HorizontalLine => self.lines.push("{comment: ---}".into()),
+            Block::Pre { text } => {
+                self.lines.push("{start_of_tab}".into());
+                self.lines
+                    .extend(text.trim_end_matches('\n').lines().map(String::from));
+                self.lines.push("{end_of_tab}".into());
+            }
+            Block::HtmlBlock(..) => {
+                self.dropped.insert("HTML tags");
+            }
+            // Headings are only found in front and back matter, which isn't rendered
+            Block::Heading { .. } => {}
+        }
+    }
+
+    fn verse(&mut self, verse: &Verse) {
+        let label = match &verse.label {
+            VerseLabel::Verse(num) => self.labels.verse((*num).into()),
+            VerseLabel::Chorus(num) => self.labels.chorus(self.chorus_label, num.map(u64::from)),
+            VerseLabel::Custom(label) => label.to_string(),
+            VerseLabel::None {} => String::new(),
+        };
+        // A continued verse has the label of the previous one, which is already written
+        if !label.is_empty() && !verse.continued {
+            self.lines.push(format!("{{comment: {}}}", label));
+        }
+
+        let chorus = matches!(verse.label, VerseLabel::Chorus(..));
+        if chorus {
+            self.lines.push("{start_of_chorus}".into());
+        }
+        for (i, para) in verse.paragraphs.iter().enumerate() {
+            if i > 0 {
+                self.lines.push(String::new());
+            }
+            self.paragraph(para);
+        }
+        if chorus {
+            self.lines.push("{end_of_chorus}".into());
+        }
+    }
+
+    /// Write the lines of `inlines` with chords in brackets, alt chords of each line
+    /// are listed in a comment above it.
+    fn paragraph(&mut self, inlines: &[Inline]) {
+        for line in inlines.split(Inline::is_break) {
+            let mut text = String::new();
+            let mut alt_chords = vec![];
+            for inline in line {
+                self.inline(inline, &mut text, &mut alt_chords);
+            }
+
+            if !alt_chords.is_empty() {
+                self.lines
+                    .push(format!("{{comment_italic: {}}}", alt_chords.join(" ")));
+            }
+            self.lines.push(text);
+        }
+    }
+
+    fn inline<'i>(&mut self, inline: &'i Inline, text: &mut String, alt_chords: &mut Vec<&'i str>) {
+        match inline {
+            Inline::Text { text: t, .. } => text.push_str(t),
+            Inline::Chord(chord) => {
+                text.push('[');
+                text.push_str(&chord.chord);
+                text.push(']');
+                if let Some(alt_chord) = chord.alt_chord.as_deref() {
+                    alt_chords.push(alt_chord);
+                }
+                for inline in chord.inlines.iter() {
+                    self.inline(inline, text, alt_chords);
+                }
+            }
+            Inline::Emph(inlines) | Inline::Strong(inlines) => {
+                for inline in inlines.inlines.iter() {
+                    self.inline(inline, text, alt_chords);
+                }
+            }
+            Inline::Link(link) => {
+                text.push_str(&link.text);
+                self.dropped.insert("link URLs");
+            }
+            Inline::Image(..) => {
+                self.dropped.insert("images");
+            }
+            Inline::HtmlTag(..) => {
+                self.dropped.insert("HTML tags");
+            }
+            Inline::ChorusRef(chorus_ref) => {
+                text.push_str(&chorus_ref.prefix_space);
+                let num = chorus_ref.num.map(u64::from);
+                text.push_str(&self.labels.chorus(self.chorus_label, num));
+            }
+            Inline::ChorusRefRange(range) => {
+                text.push_str(&range.prefix_space);
+                text.push_str(
+                    &self
+                        .labels
+                        .chorus(self.chorus_label, Some(range.start.into())),
+                );
+                text.push('\u{2013}');
+                text.push_str(
+                    &self
+                        .labels
+                        .chorus(self.chorus_label, Some(range.end.into())),
+                );
+            }
+            Inline::Directive(directive) => {
+                let value = match directive.kind {
+                    DirectiveKind::Tempo => format!("{} BPM", directive.value),
+                    DirectiveKind::Key => format!("Key: {}", directive.value),
+                    DirectiveKind::Time | DirectiveKind::Duration => directive.value.to_string(),
+                    DirectiveKind::Alias | DirectiveKind::Dir => return,
+                };
+                text.push_str(&directive.prefix_space);
+                text.push_str(&value);
+            }
+            Inline::Break | Inline::Transpose(..) => {}
+        }
+    }
+}
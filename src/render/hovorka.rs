@@ -1,33 +1,207 @@
+//! Output for the Zpěvník Android app by Karel Hovorka.
+//!
+//! The app expects song text with chords in brackets in front of the syllables they belong to
+//! and verse labels in front of the first line of each verse. The text is prepared here,
+//! see `HovorkaSong`, and the template only writes it into the XML file.
+
+use std::collections::BTreeSet;
+
 use semver::Version;
+use serde::Serialize;
 
 use super::template::HbRender;
 use super::{Render, RenderContext};
 use crate::app::App;
+use crate::book::{Block, Chord, Inline, Song, Verse, VerseLabel};
 use crate::prelude::*;
 use crate::project::{Output, Project};
-use crate::util::ImgCache;
 
 default_template!(DEFAULT_TEMPLATE, "hovorka.hbs");
 
+/// A song in the format of the Hovorka app, available to templates as `hovorka`.
+///
+/// The strings are meant to be written into CDATA sections unescaped, ie. with `{{{ }}}`.
+#[derive(Serialize, Debug)]
+pub struct HovorkaSong {
+    title: String,
+    /// Lines of the song text, eg. `1. [Am(Ami)]Lyrics`, paragraphs and verses are separated by empty lines.
+    lines: Vec<String>,
+}
+
+impl HovorkaSong {
+    fn new(song: &Song) -> Self {
+        let mut writer = HovorkaWriter::default();
+        for block in song.blocks.iter() {
+            writer.block(block);
+        }
+        while writer.lines.last().map_or(false, |line| line.is_empty()) {
+            writer.lines.pop();
+        }
+
+        Self {
+            title: cdata_escape(&song.title),
+            lines: writer
+                .lines
+                .iter()
+                .map(|line| cdata_escape(line.trim()))
+                .collect(),
+        }
+    }
+
+    pub fn for_songs(songs: &[&Song]) -> Vec<Self> {
+        songs.iter().map(|song| Self::new(song)).collect()
+    }
+}
+
+/// Split `]]>` so that `text` can't end a CDATA section.
+fn cdata_escape(text: &str) -> String {
+    text.replace("]]>", "]]]]><![CDATA[>")
+}
+
+fn label(label: &VerseLabel) -> String {
+    match label {
+        VerseLabel::Verse(num) => format!("{}.", num),
+        VerseLabel::Chorus(Some(num)) => format!("R{}:", num),
+        VerseLabel::Chorus(None) => "R:".into(),
+        VerseLabel::Custom(label) => label.to_string(),
+        VerseLabel::None {} => String::new(),
+    }
+}
+
+#[derive(Default, Debug)]
+struct HovorkaWriter {
+    lines: Vec<String>,
+}
+
+impl HovorkaWriter {
+    fn block(&mut self, block: &Block) {
+        // Other blocks have no equivalent in the app
+        if let Block::Verse(verse) = block {
+            self.verse(verse);
+            self.lines.push(String::new());
+        }
+    }
+
+    fn verse(&mut self, verse: &Verse) {
+        // A continued verse has the label of the previous one, which is already written
+        let label = if verse.continued {
+            String::new()
+        } else {
+            label(&verse.label)
+        };
+
+        for (i, para) in verse.paragraphs.iter().enumerate() {
+            if i > 0 {
+                self.lines.push(String::new());
+            }
+            let start = self.lines.len();
+            self.paragraph(para);
+            if i == 0 && !label.is_empty() {
+                let first = &mut self.lines[start];
+                *first = if first.is_empty() {
+                    label.clone()
+                } else {
+                    format!("{} {}", label, first)
+                };
+            }
+        }
+    }
+
+    fn paragraph(&mut self, inlines: &[Inline]) {
+        let start = self.lines.len();
+        self.lines.push(String::new());
+        for inline in inlines {
+            self.inline(inline);
+        }
+        // A chorus reference at the end leaves an empty line after it
+        if self.lines.len() > start + 1 && self.text().trim().is_empty() {
+            self.lines.pop();
+        }
+    }
+
+    fn text(&mut self) -> &mut String {
+        self.lines.last_mut().unwrap()
+    }
+
+    fn chord(&mut self, chord: &Chord) {
+        let text = self.text();
+        text.push('[');
+        text.push_str(&chord.chord);
+        // Alt chords which don't differ, eg. `C` in the Czech notation, are left out
+        if let Some(alt_chord) = chord
+            .alt_chord
+            .as_deref()
+            .filter(|&alt| alt != &*chord.chord)
+        {
+            text.push('(');
+            text.push_str(alt_chord);
+            text.push(')');
+        }
+        text.push(']');
+        for inline in chord.inlines.iter() {
+            self.inline(inline);
+        }
+    }
+
+    /// A chorus reference is written on a line of its own.
+    fn chorus_ref(&mut self, reference: String) {
+        let text = self.text();
+        if text.trim().is_empty() {
+            text.clear();
+        } else {
+            self.lines.push(String::new());
+        }
+        self.text().push_str(&reference);
+        self.lines.push(String::new());
+    }
+
+    fn inline(&mut self, inline: &Inline) {
+        match inline {
+            Inline::Text { text, .. } => self.text().push_str(text),
+            Inline::Chord(chord) => self.chord(chord),
+            Inline::Break => self.lines.push(String::new()),
+            Inline::Emph(inlines) | Inline::Strong(inlines) => {
+                inlines.inlines.iter().for_each(|i| self.inline(i))
+            }
+            Inline::Link(link) => self.text().push_str(&link.text),
+            Inline::ChorusRef(chorus_ref) => {
+                self.chorus_ref(label(&VerseLabel::Chorus(chorus_ref.num)))
+            }
+            Inline::ChorusRefRange(range) => {
+                self.chorus_ref(format!("R{}-R{}:", range.start, range.end))
+            }
+            Inline::Image(..)
+            | Inline::HtmlTag(..)
+            | Inline::Directive(..)
+            | Inline::Transpose(..) => {}
+        }
+    }
+}
+
 pub struct RHovorka(HbRender);
 
 impl RHovorka {
-    pub fn new(project: &Project, output: &Output, img_cache: &ImgCache) -> Result<Self> {
+    pub fn new(app: &App, project: &Project, output: &Output) -> Result<Self> {
         Ok(Self(HbRender::new(
+            app,
             project,
             output,
             &DEFAULT_TEMPLATE,
-            img_cache,
         )?))
     }
 }
 
 impl Render for RHovorka {
-    fn render(&self, _app: &App, output: &Path, context: RenderContext) -> Result<()> {
-        self.0.render(output, context)
+    fn render(&self, app: &App, output: &Path, mut context: RenderContext) -> Result<()> {
+        context.hovorka = Some(HovorkaSong::for_songs(&context.songs));
+        self.0.render(app, output, context)
     }
 
     fn version(&self) -> Option<Version> {
         self.0.version()
     }
+
+    fn html_tags(&self) -> Option<&BTreeSet<String>> {
+        Some(&self.0.tag_partials)
+    }
 }
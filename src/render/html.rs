@@ -5,15 +5,14 @@ use super::{Render, RenderContext};
 use crate::app::App;
 use crate::prelude::*;
 use crate::project::{Output, Project};
-use crate::util::ImgCache;
 
 default_template!(DEFAULT_TEMPLATE, "html.hbs");
 
 pub struct RHtml(HbRender);
 
 impl RHtml {
-    pub fn new(project: &Project, output: &Output, img_cache: &ImgCache) -> Result<Self> {
-        let mut hb = HbRender::new(project, output, &DEFAULT_TEMPLATE, img_cache)?;
+    pub fn new(app: &App, project: &Project, output: &Output) -> Result<Self> {
+        let mut hb = HbRender::new(app, project, output, &DEFAULT_TEMPLATE)?;
 
         // Setup HTML-specific helpers
         hb.hb
@@ -24,8 +23,8 @@ impl RHtml {
 }
 
 impl Render for RHtml {
-    fn render(&self, _app: &App, output: &Path, context: RenderContext) -> Result<()> {
-        self.0.render(output, context)
+    fn render(&self, app: &App, output: &Path, context: RenderContext) -> Result<()> {
+        self.0.render(app, output, context)
     }
 
     fn version(&self) -> Option<Version> {
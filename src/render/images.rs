@@ -0,0 +1,243 @@
+//! Images renderer, renders songs or verses as separate images, eg. for presentation slides.
+//!
+//! Each slide is first rendered as a standalone HTML page using the template,
+//! which is then converted into an image by the output's `image_command`.
+//! A `manifest.json` listing the slides in order is written along with the images.
+
+use std::fs;
+use std::process::Stdio;
+
+use semver::Version;
+use serde::{Deserialize, Serialize};
+
+use super::template::{DpiHelper, HbRender};
+use super::{Render, RenderContext, SlideContext};
+use crate::app::{keeplevel, App};
+use crate::book::Block;
+use crate::prelude::*;
+use crate::project::{Hook, ImageFormat, Output, Project, SlideUnit};
+use crate::util::{slugify, ExitStatusExt as _, ProcessLines, TempPath};
+
+default_template!(DEFAULT_TEMPLATE, "images.hbs");
+
+const MANIFEST: &str = "manifest.json";
+
+/// An entry of `manifest.json`.
+#[derive(Serialize, Deserialize, Debug)]
+struct ManifestSlide {
+    /// Image file name, relative to the output directory
+    file: String,
+    song: String,
+    /// Position of the song in the rendered songs, counted from 0
+    song_index: usize,
+    /// Index of the verse block within the song, if the output has a slide per verse
+    #[serde(skip_serializing_if = "Option::is_none")]
+    block_index: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+struct Manifest {
+    slides: Vec<ManifestSlide>,
+}
+
+impl Manifest {
+    /// Remove the files of the slides listed in a manifest from a previous run,
+    /// so that no stale images are left behind when songs are removed or renamed.
+    fn remove_previous(dir: &Path) {
+        let manifest: Manifest = match fs::read_to_string(dir.join(MANIFEST))
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+        {
+            Some(manifest) => manifest,
+            None => return,
+        };
+
+        for slide in manifest.slides {
+            // Only plain file names are removed, the manifest may have been edited by hand
+            if Path::new(&slide.file).file_name() == Some(slide.file.as_ref()) {
+                let _ = fs::remove_file(dir.join(&slide.file));
+            }
+        }
+    }
+}
+
+/// Remove the slides of an images output in `dir` along with the manifest, see `bard make --prune`.
+/// The directory itself is only removed if there's nothing else left in it.
+pub fn remove_output(dir: &Path) -> Result<()> {
+    Manifest::remove_previous(dir);
+    let manifest = dir.join(MANIFEST);
+    if manifest.exists() {
+        fs::remove_file(&manifest)
+            .with_context(|| format!("Could not remove file {:?}", manifest))?;
+    }
+
+    let _ = fs::remove_dir(dir);
+    Ok(())
+}
+
+pub struct RImages {
+    hb: HbRender,
+    slides: SlideUnit,
+    image_format: ImageFormat,
+    command: Hook,
+    /// Whether `command` is the default one, ie. `image_command` isn't set
+    default_command: bool,
+}
+
+impl RImages {
+    pub fn new(app: &App, project: &Project, output: &Output) -> Result<Self> {
+        let mut hb = HbRender::new(app, project, output, &DEFAULT_TEMPLATE)?;
+
+        // Setup HTML-specific helpers
+        hb.hb
+            .register_helper("scale", DpiHelper::new(output, "scale"));
+
+        Ok(Self {
+            hb,
+            slides: output.slides,
+            image_format: output.image_format,
+            command: output.image_command().into_owned(),
+            default_command: output.image_command.is_none(),
+        })
+    }
+
+    /// Slides of the song at `idx` in the `context`, as (block index, blocks) pairs.
+    fn song_slides<'a>(
+        &self,
+        context: &RenderContext<'a>,
+        idx: usize,
+    ) -> Vec<(Option<usize>, &'a [Block])> {
+        let blocks = &context.songs[idx].blocks[..];
+        match self.slides {
+            SlideUnit::Song => vec![(None, blocks)],
+            SlideUnit::Verse => blocks
+                .iter()
+                .enumerate()
+                .filter(|(_, block)| matches!(block, Block::Verse(_)))
+                .map(|(i, _)| (Some(i), &blocks[i..=i]))
+                .collect(),
+        }
+    }
+
+    /// Convert the HTML page `input` into the image `output` using the `image_command`.
+    fn convert(&self, app: &App, input: &Path, output: &Path) -> Result<()> {
+        let mut child = self
+            .command
+            .command_with_paths(&[("input", input), ("output", output)])?
+            .current_dir(input.parent().unwrap())
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(Error::from)
+            .with_context(|| {
+                let hint = match self.command.program() {
+                    Some(program) if self.default_command => format!(
+                        "\nHint: Install {} or set image_command in the output to a program converting HTML into images.",
+                        program
+                    ),
+                    _ => String::new(),
+                };
+                format!("Could not run the image command{}", hint)
+            })?;
+
+        let mut ps_lines =
+            ProcessLines::new(child.stdout.take().unwrap(), child.stderr.take().unwrap());
+        // The output is only collected for error reporting
+        while ps_lines.read_line(app.interrupt_flag())?.is_some() {}
+
+        app.child_wait(&mut child)?
+            .into_result()
+            .map_err(|err| {
+                let output: String = ps_lines
+                    .collected_lines()
+                    .map(String::from_utf8_lossy)
+                    .collect();
+                let output = output.trim_end();
+                if output.is_empty() {
+                    err
+                } else {
+                    err.context(format!("Image command output:\n{}", output))
+                }
+            })
+            .with_context(|| format!("The image command failed to convert {:?}", input))
+    }
+}
+
+impl RImages {
+    /// Render the slides into the `output` directory, with `convert` the HTML pages are converted into images.
+    fn render_slides(
+        &self,
+        app: &App,
+        output: &Path,
+        context: RenderContext,
+        convert: bool,
+    ) -> Result<()> {
+        fs::create_dir_all(output)
+            .with_context(|| format!("Could not create output directory {:?}", output))?;
+        Manifest::remove_previous(output);
+
+        let slides: Vec<_> = (0..context.songs.len())
+            .flat_map(|idx| {
+                self.song_slides(&context, idx)
+                    .into_iter()
+                    .map(move |(block_index, blocks)| (idx, block_index, blocks))
+            })
+            .collect();
+
+        let ext = self.image_format.to_string();
+        let keep = app.keep_interm() >= keeplevel::TEX_ONLY || !convert;
+        let mut manifest = Manifest::default();
+        for (i, &(idx, block_index, blocks)) in slides.iter().enumerate() {
+            app.check_interrupted()?;
+
+            let song = context.songs[idx];
+            let stem = format!("{:03}-{}", i + 1, slugify(&song.title));
+            let page = TempPath::new_file(output.join(format!("{}.html", stem)), !keep);
+            let slide = SlideContext {
+                number: i + 1,
+                count: slides.len(),
+                blocks,
+            };
+            self.hb.render(app, &page, context.with_slide(idx, slide))?;
+
+            let file = if convert {
+                let file = format!("{}.{}", stem, ext);
+                self.convert(app, &page, &output.join(&file))?;
+                file
+            } else {
+                format!("{}.html", stem)
+            };
+
+            manifest.slides.push(ManifestSlide {
+                file,
+                song: song.title.to_string(),
+                song_index: idx,
+                block_index,
+            });
+        }
+
+        app.indent(format!("{} slides", manifest.slides.len()));
+        let json = serde_json::to_string_pretty(&manifest)?;
+        let path = output.join(MANIFEST);
+        fs::write(&path, json.as_bytes())
+            .with_context(|| format!("Error writing output file: {:?}", path))?;
+
+        Ok(())
+    }
+}
+
+impl Render for RImages {
+    fn render(&self, app: &App, output: &Path, context: RenderContext) -> Result<()> {
+        self.render_slides(app, output, context, app.post_process())
+    }
+
+    /// Only the HTML pages of the slides are rendered.
+    fn render_preview(&self, app: &App, output: &Path, context: RenderContext) -> Result<()> {
+        self.render_slides(app, output, context, false)
+    }
+
+    fn version(&self) -> Option<Version> {
+        self.hb.version()
+    }
+}
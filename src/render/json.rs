@@ -1,8 +1,15 @@
-use std::fs::File;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::mem;
+
+use serde_json::{json, Map, Value};
 
 use super::{Render, RenderContext};
 use crate::app::App;
+use crate::book;
 use crate::prelude::*;
+use crate::project::LineEndings;
 
 #[derive(Debug, Default)]
 pub struct RJson;
@@ -13,11 +20,132 @@ impl RJson {
     }
 }
 
+/// Recursively reorder keys of all objects in `value` alphabetically.
+fn sort_keys(value: Value) -> Value {
+    match value {
+        Value::Object(map) => {
+            let sorted: BTreeMap<_, _> = map.into_iter().map(|(k, v)| (k, sort_keys(v))).collect();
+            Value::Object(sorted.into_iter().collect())
+        }
+        Value::Array(items) => Value::Array(items.into_iter().map(sort_keys).collect()),
+        other => other,
+    }
+}
+
 impl Render for RJson {
     fn render(&self, _app: &App, output: &Path, context: RenderContext) -> Result<()> {
-        File::create(output)
-            .map_err(Error::from)
-            .and_then(|mut f| serde_json::to_writer_pretty(&mut f, &context).map_err(Error::from))
+        let pretty = context.output.json_pretty;
+        let json = if context.output.sort_keys {
+            let value = sort_keys(serde_json::to_value(&context)?);
+            if pretty {
+                serde_json::to_string_pretty(&value)?
+            } else {
+                serde_json::to_string(&value)?
+            }
+        } else if pretty {
+            serde_json::to_string_pretty(&context)?
+        } else {
+            serde_json::to_string(&context)?
+        };
+        let json = context.output.normalize_text(&json);
+
+        fs::write(output, json.as_bytes())
             .with_context(|| format!("Error writing output file: {:?}", output))
     }
 }
+
+/// JSON Lines output, for consumers processing the songs as they're read.
+///
+/// The first line is a header object with the `ast_version`, the `song_count`,
+/// and the same fields as the JSON output except `songs`. Each song then has a line with its `idx`
+/// and the `song` itself. The last line is a summary with the `song_count` and a `checksum`,
+/// the hex FNV-1a hash of all the preceding lines including line endings, to detect truncated files.
+#[derive(Debug, Default)]
+pub struct RJsonl;
+
+impl RJsonl {
+    pub fn new() -> Self {
+        Self
+    }
+
+    fn write_line(w: &mut impl Write, value: Value, sort: bool, line_end: &str) -> Result<()> {
+        let value = if sort { sort_keys(value) } else { value };
+        serde_json::to_writer(&mut *w, &value)?;
+        w.write_all(line_end.as_bytes())?;
+        Ok(())
+    }
+}
+
+/// Writer computing the FNV-1a hash of the data written, for the `RJsonl` checksum.
+struct HashWriter<W> {
+    inner: W,
+    hash: u64,
+}
+
+impl<W> HashWriter<W> {
+    fn new(inner: W) -> Self {
+        Self {
+            inner,
+            hash: 0xcbf29ce484222325,
+        }
+    }
+}
+
+impl<W: Write> Write for HashWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hash = buf[..written].iter().fold(self.hash, |hash, &b| {
+            (hash ^ b as u64).wrapping_mul(0x100000001b3)
+        });
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl Render for RJsonl {
+    fn render(&self, _app: &App, output: &Path, mut context: RenderContext) -> Result<()> {
+        let sort = context.output.sort_keys;
+        let line_end = context
+            .output
+            .line_endings
+            .map_or("\n", LineEndings::as_str);
+        let songs = mem::take(&mut context.songs);
+
+        let mut header = Map::new();
+        header.insert("type".into(), "header".into());
+        header.insert(
+            "ast_version".into(),
+            book::version::current().to_string().into(),
+        );
+        header.insert("song_count".into(), songs.len().into());
+        if let Value::Object(fields) = serde_json::to_value(&context)? {
+            header.extend(fields.into_iter().filter(|(key, _)| key != "songs"));
+        }
+
+        let write = || -> Result<()> {
+            let mut w = HashWriter::new(BufWriter::new(File::create(output)?));
+            Self::write_line(&mut w, Value::Object(header), sort, line_end)?;
+            for (idx, song) in songs.iter().enumerate() {
+                let line = json!({
+                    "type": "song",
+                    "idx": idx,
+                    "song": serde_json::to_value(song)?,
+                });
+                Self::write_line(&mut w, line, sort, line_end)?;
+            }
+
+            let summary = json!({
+                "type": "summary",
+                "song_count": songs.len(),
+                "checksum": format!("{:016x}", w.hash),
+            });
+            Self::write_line(&mut w, summary, sort, line_end)?;
+            w.flush()?;
+            Ok(())
+        };
+        write().with_context(|| format!("Error writing output file: {:?}", output))
+    }
+}
@@ -1,14 +1,20 @@
+use std::collections::BTreeSet;
+use std::path::Component;
+use std::time::Instant;
+
+use globset::GlobBuilder;
 use handlebars::handlebars_helper;
 use semver::Version;
+use serde::Serialize;
 
 use super::template::{DpiHelper, HbRender};
 use super::tex_tools::TexTools;
 use super::{Render, RenderContext};
-use crate::app::App;
+use crate::app::{keeplevel, App};
 use crate::prelude::*;
 use crate::project::{Output, Project};
 use crate::render::tex_tools::TexRenderJob;
-use crate::util::ImgCache;
+use crate::util::{read_dir_all, sort_paths_lexical};
 
 default_template!(DEFAULT_TEMPLATE, "pdf.hbs");
 
@@ -41,15 +47,116 @@ handlebars_helper!(hb_pre: |input: str| {
     latex_escape(input, true)
 });
 
+/// Font files of a PDF output matched by its `fonts` patterns, see `Output::fonts`.
+///
+/// TeX runs in the output directory, so templates refer to the fonts by the absolute `dir`,
+/// eg. `\setmainfont[Path={{{ fonts.dir }}}]{Font.otf}`. The directory is also passed to TeX
+/// in the `OSFONTDIR` environment variable.
+#[derive(Serialize, Debug)]
+pub struct FontFiles {
+    /// Absolute path of the directory of the fonts, with `/` separators and a trailing `/`.
+    dir: String,
+    /// File names of the fonts, sorted for each pattern.
+    files: Vec<String>,
+}
+
+impl FontFiles {
+    /// Match the `patterns` relative to `project_dir`. Every pattern needs to match a file,
+    /// and the files need to be in a single directory.
+    fn resolve(project_dir: &Path, patterns: &[String]) -> Result<Option<Self>> {
+        if patterns.is_empty() {
+            return Ok(None);
+        }
+
+        let mut paths = vec![];
+        for pattern in patterns {
+            let mut matched = Self::glob(project_dir, pattern)?;
+            if matched.is_empty() {
+                bail!(
+                    "No font files matched pattern '{}' in directory {:?}",
+                    pattern,
+                    project_dir
+                );
+            }
+            sort_paths_lexical(&mut matched);
+            paths.extend(matched);
+        }
+
+        let dir = paths[0].parent().unwrap();
+        if let Some(other) = paths.iter().find(|path| path.parent() != Some(dir)) {
+            bail!(
+                "Font files need to be in a single directory, found {:?} and {:?}",
+                paths[0],
+                other
+            );
+        }
+
+        let dir = dir
+            .to_str()
+            .ok_or_else(|| anyhow!("Font directory {:?} is not valid UTF-8", dir))?;
+        let files = paths
+            .iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        Ok(Some(Self {
+            dir: format!("{}/", dir.replace('\\', "/")),
+            files,
+        }))
+    }
+
+    /// Files under `project_dir` matching `pattern`, only the directory before the first
+    /// glob component is searched.
+    fn glob(project_dir: &Path, pattern: &str) -> Result<Vec<PathBuf>> {
+        let matcher = GlobBuilder::new(pattern)
+            .literal_separator(true)
+            .build()
+            .with_context(|| format!("Invalid font pattern '{}'", pattern))?
+            .compile_matcher();
+
+        let base: PathBuf = Path::new(pattern)
+            .components()
+            .take_while(|c| match c {
+                Component::Normal(part) => !part.to_string_lossy().contains(['*', '?', '[', '{']),
+                _ => true,
+            })
+            .collect();
+        let base = project_dir.join(base);
+        if base.is_file() {
+            return Ok(vec![base]);
+        } else if !base.is_dir() {
+            return Ok(vec![]);
+        }
+
+        let files = read_dir_all(&base)
+            .with_context(|| format!("Could not read font directory {:?}", base))?
+            .into_iter()
+            .filter(|path| {
+                path.strip_prefix(project_dir)
+                    .map(|rel| matcher.is_match(rel))
+                    .unwrap_or(false)
+            })
+            .collect();
+        Ok(files)
+    }
+
+    pub fn dir(&self) -> &str {
+        &self.dir
+    }
+}
+
 pub struct RPdf {
     hb: HbRender,
+    project_dir: PathBuf,
+    out_dir: PathBuf,
     toc_sort_key: Option<String>,
-    tex_runs: u32,
+    tex_runs: Option<u32>,
+    /// `SOURCE_DATE_EPOCH` for TeX with the `reproducible` setting.
+    source_date_epoch: Option<String>,
 }
 
 impl RPdf {
-    pub fn new(project: &Project, output: &Output, img_cache: &ImgCache) -> Result<Self> {
-        let mut hb = HbRender::new(project, output, &DEFAULT_TEMPLATE, img_cache)?;
+    pub fn new(app: &App, project: &Project, output: &Output) -> Result<Self> {
+        let mut hb = HbRender::new(app, project, output, &DEFAULT_TEMPLATE)?;
 
         // Setup TeX escaping and TeX-specific helpers
         hb.hb.register_escape_fn(hb_latex_escape);
@@ -59,34 +166,87 @@ impl RPdf {
 
         Ok(Self {
             hb,
+            project_dir: project.project_dir.clone(),
+            out_dir: project.settings.dir_output().to_owned(),
             toc_sort_key: output.toc_sort.then(|| output.toc_sort_key.clone()),
             tex_runs: output.tex_runs,
+            source_date_epoch: if project.settings.reproducible {
+                project.settings.source_date_epoch()
+            } else {
+                None
+            },
         })
     }
 }
 
-impl Render for RPdf {
-    fn render(&self, app: &App, output: &Path, context: RenderContext) -> Result<()> {
+impl RPdf {
+    fn render_tex(
+        &self,
+        app: &App,
+        output: &Path,
+        mut context: RenderContext,
+        tex_runs: Option<u32>,
+        keep: u8,
+    ) -> Result<()> {
+        // Missing fonts are an error before TeX runs
+        let fonts = FontFiles::resolve(&self.project_dir, &context.output.fonts)?;
+        let font_dir = fonts.as_ref().map(|fonts| fonts.dir().to_string());
+        context.fonts = fonts;
+
         // Render TeX first
         let tex_file = output.with_extension("tex");
-        self.hb.render(&tex_file, context)?;
-        if self.tex_runs == 0 || !app.post_process() {
+        self.hb.render(app, &tex_file, context)?;
+        if tex_runs == Some(0) || !app.post_process() {
             // TODO: test this
             return Ok(());
         }
 
+        // Explicit tex_runs setting wins, otherwise either the template tells us it doesn't
+        // need a TOC, or the need for reruns is detected after the first TeX run.
+        let reruns = match (tex_runs, self.hb.needs_toc()) {
+            (Some(runs), _) => Some(runs - 1),
+            (None, Some(false)) => Some(0),
+            (None, _) => None,
+        };
+
         // Run TeX
         let job = TexRenderJob::new(
             tex_file,
             output,
-            app.keep_interm(),
+            &self.out_dir,
+            keep,
             self.toc_sort_key.as_deref(),
-            self.tex_runs - 1,
-        )?;
-        TexTools::get().render_pdf(app, job)
+            reruns,
+            self.source_date_epoch.as_deref(),
+        )?
+        .font_dir(font_dir.as_deref());
+        let start = Instant::now();
+        TexTools::get().render_pdf(app, job)?;
+        app.timing("TeX", start.elapsed());
+        Ok(())
+    }
+}
+
+impl Render for RPdf {
+    fn render(&self, app: &App, output: &Path, context: RenderContext) -> Result<()> {
+        self.render_tex(app, output, context, self.tex_runs, app.keep_interm())
+    }
+
+    /// A single TeX pass without keeping any intermediate files.
+    fn render_probe(&self, app: &App, output: &Path, context: RenderContext) -> Result<()> {
+        self.render_tex(app, output, context, Some(1), keeplevel::NONE)
+    }
+
+    /// Only the TeX file is rendered.
+    fn render_preview(&self, app: &App, output: &Path, context: RenderContext) -> Result<()> {
+        self.render_tex(app, output, context, Some(0), keeplevel::NONE)
     }
 
     fn version(&self) -> Option<Version> {
         self.hb.version()
     }
+
+    fn html_tags(&self) -> Option<&BTreeSet<String>> {
+        Some(&self.hb.tag_partials)
+    }
 }
@@ -1,27 +1,38 @@
-use std::collections::HashMap;
-use std::fmt;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::{self, Write as _};
 use std::fs;
 use std::io;
+use std::mem;
 use std::str::FromStr;
 use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime};
 
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, Utc};
 use handlebars::{self as hb, handlebars_helper, Handlebars, HelperDef, JsonValue, RenderError};
-use image::image_dimensions;
 use once_cell::sync::Lazy;
 use regex::{Error as ReError, Regex};
 use semver::Version;
 use serde_json::Number;
 
 use super::RenderContext;
+use crate::app::{verbosity, App};
+use crate::diag::{BuildDiagnostic, Phase};
+use crate::music::{self, MinorChords, Notation, Notations};
 use crate::prelude::*;
 use crate::project::Format;
-use crate::project::{Output, Project};
+use crate::project::{Labels, Output, Project};
 use crate::util::ImgCache;
 
 type RegexCache = HashMap<String, Result<Regex, ReError>>;
 
 static REGEX_CACHE: Lazy<Mutex<RegexCache>> = Lazy::new(|| Mutex::new(RegexCache::new()));
 
+/// Number of regular expressions cached by the `matches` helper, shared by all templates.
+pub fn regex_cache_len() -> usize {
+    REGEX_CACHE.lock().unwrap().len()
+}
+
 // Default templates
 
 pub struct DefaultTemaplate {
@@ -29,6 +40,70 @@ pub struct DefaultTemaplate {
     pub content: &'static str,
 }
 
+impl DefaultTemaplate {
+    /// Content written into a template file that doesn't exist yet,
+    /// ie. the default content preceded by the marker line, see `TemplateState`.
+    pub fn saved_content(&self) -> String {
+        format!(
+            "{}{:016x}{}\n{}",
+            TEMPLATE_MARKER_PREFIX,
+            template_hash(self.content),
+            TEMPLATE_MARKER_SUFFIX,
+            self.content
+        )
+    }
+}
+
+const TEMPLATE_MARKER_PREFIX: &str = "{{!-- bard default template ";
+const TEMPLATE_MARKER_SUFFIX: &str =
+    ", remove this line to keep the template as is when the default changes --}}";
+
+/// FNV-1a hash of the template content, which unlike `DefaultHasher` is stable
+/// across bard builds. Line endings are normalized, as git may convert them on checkout.
+fn template_hash(content: &str) -> u64 {
+    content
+        .bytes()
+        .filter(|&b| b != b'\r')
+        .fold(0xcbf29ce484222325, |hash, b| {
+            (hash ^ b as u64).wrapping_mul(0x100000001b3)
+        })
+}
+
+/// State of a template file with regard to the default template it may have been created from.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum TemplateState {
+    /// The file has no marker line, it was written by the user or the marker was removed.
+    Custom,
+    /// Unmodified copy of the current default.
+    Current,
+    /// Unmodified copy of an older default, it can be updated.
+    Outdated,
+    /// Created from a default and modified since, `outdated` if the default has changed too.
+    Modified { outdated: bool },
+}
+
+impl TemplateState {
+    fn of(content: &str, default: &DefaultTemaplate) -> Self {
+        let (marker, body) = content.split_once('\n').unwrap_or((content, ""));
+        let hash = marker
+            .trim_end()
+            .strip_prefix(TEMPLATE_MARKER_PREFIX)
+            .and_then(|marker| marker.strip_suffix(TEMPLATE_MARKER_SUFFIX))
+            .and_then(|hash| u64::from_str_radix(hash, 16).ok());
+        let hash = match hash {
+            Some(hash) => hash,
+            None => return Self::Custom,
+        };
+
+        let outdated = hash != template_hash(default.content);
+        match (hash == template_hash(body), outdated) {
+            (true, false) => Self::Current,
+            (true, true) => Self::Outdated,
+            (false, outdated) => Self::Modified { outdated },
+        }
+    }
+}
+
 macro_rules! default_template {
     ($name:ident, $filename:expr) => {
         pub static $name: crate::render::template::DefaultTemaplate =
@@ -75,16 +150,74 @@ handlebars_helper!(hb_eq: |v1: Json, v2: Json| {
     v1 == v2
 });
 
-handlebars_helper!(hb_contains: |obj: object, key: str| {
-    obj.contains_key(key)
+handlebars_helper!(hb_contains: |obj: Json, key: str| {
+    match obj {
+        JsonValue::Object(obj) => obj.contains_key(key),
+        JsonValue::Array(items) => items.iter().any(|item| item.as_str() == Some(key)),
+        _ => false,
+    }
 });
 
-handlebars_helper!(hb_default: |value: Json, def: Json| {
-    match value {
-        JsonValue::Null => def.clone(),
-        other => other.clone(),
+/// Returns `value` if it is not `null`, otherwise `default`.
+///
+/// Not defined with `handlebars_helper!`, because a missing `value` is fine here even in strict mode.
+struct DefaultHelper;
+
+impl HelperDef for DefaultHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &hb::Helper<'reg, 'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc hb::Context,
+        _: &mut hb::RenderContext<'reg, 'rc>,
+    ) -> Result<hb::ScopedJson<'reg, 'rc>, RenderError> {
+        let value = h
+            .param(0)
+            .map(|x| x.value())
+            .ok_or_else(|| hb_err!("default: No value supplied"))?;
+        let default = h
+            .param(1)
+            .map(|x| x.value())
+            .ok_or_else(|| hb_err!("default: No default value supplied"))?;
+
+        let res = match value {
+            JsonValue::Null => default.clone(),
+            other => other.clone(),
+        };
+        Ok(hb::ScopedJson::Derived(res))
     }
-});
+}
+
+/// Registered as `helperMissing`, which handlebars calls when a value to be rendered is missing
+/// (in non-strict mode). The paths of missing values are collected so that they can be reported.
+struct MissingValueHelper {
+    missing: Arc<Mutex<BTreeSet<String>>>,
+}
+
+impl MissingValueHelper {
+    fn new() -> (Self, Arc<Mutex<BTreeSet<String>>>) {
+        let missing = Arc::new(Mutex::new(BTreeSet::new()));
+        let this = Self {
+            missing: missing.clone(),
+        };
+        (this, missing)
+    }
+}
+
+impl HelperDef for MissingValueHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &hb::Helper<'reg, 'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc hb::Context,
+        _: &mut hb::RenderContext<'reg, 'rc>,
+        _: &mut dyn hb::Output,
+    ) -> hb::HelperResult {
+        // Render nothing, just like handlebars does without this helper
+        self.missing.lock().unwrap().insert(h.name().to_string());
+        Ok(())
+    }
+}
 
 struct Cat<'a>(Vec<&'a JsonValue>);
 
@@ -178,10 +311,10 @@ impl HelperDef for ImgHelper {
 
         let pathbuf = Path::new(&path).to_owned().resolved(&self.out_dir);
 
-        let (w, h) = self.cache.try_get(&pathbuf, || {
-            image_dimensions(&pathbuf)
-                .map_err(|e| hb_err!(e, "{}: Couldn't read image at {:?}", self.name, pathbuf))
-        })?;
+        let (w, h) = self
+            .cache
+            .dimensions(&pathbuf)
+            .map_err(|e| hb_err!(e, "{}: Couldn't read image at {:?}", self.name, pathbuf))?;
 
         let res = [w, h][self.result_i];
         Ok(hb::ScopedJson::Derived(JsonValue::from(res)))
@@ -229,7 +362,9 @@ impl HelperDef for DpiHelper {
             })?;
 
         let res = match self.format {
-            Format::Html => JsonValue::from((self.dpi as f64 * value).round() as u32),
+            Format::Html | Format::Images => {
+                JsonValue::from((self.dpi as f64 * value).round() as u32)
+            }
             _ => JsonValue::from((value / self.dpi as f64) * Self::INCH_MM),
         };
 
@@ -277,6 +412,188 @@ impl HelperDef for VersionCheckHelper {
     }
 }
 
+/// Lets a template declare whether it needs TeX reruns to resolve the table of contents,
+/// eg. `{{ needs_toc false }}` for templates that don't have one.
+struct NeedsTocHelper {
+    needs_toc: Arc<Mutex<Option<bool>>>,
+}
+
+impl NeedsTocHelper {
+    fn new() -> (Self, Arc<Mutex<Option<bool>>>) {
+        let needs_toc = Arc::new(Mutex::new(None));
+        let this = Self {
+            needs_toc: needs_toc.clone(),
+        };
+        (this, needs_toc)
+    }
+}
+
+impl HelperDef for NeedsTocHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &hb::Helper<'reg, 'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc hb::Context,
+        _: &mut hb::RenderContext<'reg, 'rc>,
+    ) -> Result<hb::ScopedJson<'reg, 'rc>, RenderError> {
+        let needs_toc = h
+            .param(0)
+            .map(|x| x.value())
+            .ok_or_else(|| hb_err!("needs_toc: No value supplied"))
+            .and_then(|x| {
+                x.as_bool()
+                    .ok_or_else(|| hb_err!("needs_toc: Input value not a boolean"))
+            })?;
+
+        *self.needs_toc.lock().unwrap() = Some(needs_toc);
+        Ok(hb::ScopedJson::Derived(JsonValue::String(String::new())))
+    }
+}
+
+/// Formats a verse label or a chorus reference according to the `[book.labels]` settings.
+///
+/// The argument is a verse `label` object, ie. one of `{"verse": n}`, `{"chorus": n}`,
+/// `{"custom": "text"}`, or `{}`, or an `i-chorus-ref` or `i-chorus-ref-range` inline.
+/// The labels of a range are joined with an en dash. The chorus label is taken
+/// from `book.chorus_label` of the render context, so that output overrides apply.
+struct FormatLabelHelper {
+    labels: Labels,
+}
+
+impl HelperDef for FormatLabelHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &hb::Helper<'reg, 'rc>,
+        _: &'reg Handlebars<'reg>,
+        ctx: &'rc hb::Context,
+        _: &mut hb::RenderContext<'reg, 'rc>,
+    ) -> Result<hb::ScopedJson<'reg, 'rc>, RenderError> {
+        let value = h
+            .param(0)
+            .map(|x| x.value())
+            .ok_or_else(|| hb_err!("format_label: No label supplied"))?;
+        let label = value
+            .as_object()
+            .ok_or_else(|| hb_err!("format_label: Label is not an object"))?;
+        let chorus_label = || {
+            ctx.data()
+                .pointer("/book/chorus_label")
+                .and_then(JsonValue::as_str)
+                .unwrap_or("")
+        };
+
+        let typ = label.get("type").and_then(JsonValue::as_str);
+        let res = if typ == Some("i-chorus-ref") {
+            let num = label.get("num").and_then(JsonValue::as_u64);
+            self.labels.chorus(chorus_label(), num)
+        } else if typ == Some("i-chorus-ref-range") {
+            let start = label.get("start").and_then(JsonValue::as_u64);
+            let end = label.get("end").and_then(JsonValue::as_u64);
+            format!(
+                "{}\u{2013}{}",
+                self.labels.chorus(chorus_label(), start),
+                self.labels.chorus(chorus_label(), end)
+            )
+        } else if let Some(verse) = label.get("verse") {
+            let num = verse
+                .as_u64()
+                .ok_or_else(|| hb_err!("format_label: Verse number is not a number"))?;
+            self.labels.verse(num)
+        } else if let Some(chorus) = label.get("chorus") {
+            self.labels.chorus(chorus_label(), chorus.as_u64())
+        } else if let Some(custom) = label.get("custom") {
+            custom.as_str().unwrap_or("").to_string()
+        } else {
+            String::new()
+        };
+
+        Ok(hb::ScopedJson::Derived(JsonValue::String(res)))
+    }
+}
+
+/// Transposes a chord or a set of chords, optionally converting it to another notation.
+///
+/// Usage: `{{ transpose chord 2 }}`, `{{ transpose chord 0 "english" "german" }}`
+///
+/// The source notation defaults to the notation of the output, the destination notation to the source one.
+/// Notations are looked up by name like the `notation` setting, including the ones from `[notations]`.
+/// If the chord can't be transposed, it's rendered as it is. The warnings are collected
+/// and reported after rendering, see `HbRender::report_transpose_warnings()`.
+struct TransposeHelper {
+    notations: Notations,
+    notation: Notation,
+    minor: MinorChords,
+    warnings: Arc<Mutex<BTreeSet<String>>>,
+}
+
+impl TransposeHelper {
+    fn new(project: &Project, output: &Output) -> (Self, Arc<Mutex<BTreeSet<String>>>) {
+        let warnings = Arc::new(Mutex::new(BTreeSet::new()));
+        let this = Self {
+            notations: project.settings.notations.clone(),
+            notation: output
+                .notation
+                .clone()
+                .unwrap_or_else(|| project.book.notation.clone()),
+            minor: project.settings.minor_chords,
+            warnings: warnings.clone(),
+        };
+        (this, warnings)
+    }
+
+    fn notation(&self, h: &hb::Helper, idx: usize, default: &Notation) -> Result<Notation, String> {
+        let name = match h.param(idx).map(|x| x.value()) {
+            Some(JsonValue::String(name)) => name,
+            Some(other) => {
+                return Err(format!(
+                    "Notation is not a string, it's {:?} as JSON",
+                    other
+                ))
+            }
+            None => return Ok(default.clone()),
+        };
+        self.notations
+            .get(name)
+            .ok_or_else(|| format!("Unknown notation '{}'", name))
+    }
+
+    fn transpose(&self, h: &hb::Helper, chord: &str, by: i64) -> Result<String, String> {
+        let src = self.notation(h, 2, &self.notation)?;
+        let dst = self.notation(h, 3, &src)?;
+        music::transpose(chord, by, &src, &dst, self.minor)
+            .map_err(|chunk| format!("Could not transpose '{}' in '{}'", chunk, chord))
+    }
+}
+
+impl HelperDef for TransposeHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &hb::Helper<'reg, 'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc hb::Context,
+        _: &mut hb::RenderContext<'reg, 'rc>,
+    ) -> Result<hb::ScopedJson<'reg, 'rc>, RenderError> {
+        let chord = h
+            .param(0)
+            .map(|x| x.value())
+            .ok_or_else(|| hb_err!("transpose: No chord supplied"))?
+            .as_str()
+            .ok_or_else(|| hb_err!("transpose: Chord is not a string"))?;
+        let by = h
+            .param(1)
+            .map(|x| x.value())
+            .ok_or_else(|| hb_err!("transpose: No number of semitones supplied"))?
+            .as_i64()
+            .ok_or_else(|| hb_err!("transpose: Number of semitones is not an integer"))?;
+
+        let res = self.transpose(h, chord, by).unwrap_or_else(|err| {
+            self.warnings.lock().unwrap().insert(err);
+            chord.to_string()
+        });
+        Ok(hb::ScopedJson::Derived(JsonValue::String(res)))
+    }
+}
+
 /// Simple math helper, which can do the usual arithmetic operations on integers and floats. Tries to handle most edge-cases and provide useful error message to the user.
 ///
 /// Usage: `{{ math 5 "+" 3 }}`, `{{ math 23.8 "/" -1.5 }}`, `{{ math "3" "*" 8.5 }}`
@@ -381,11 +698,243 @@ impl HelperDef for MathHelper {
     }
 }
 
+/// Default format of the `now` and `date_fmt` helpers.
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// Returns the `format` hash argument of a date helper, checked to be a valid strftime format.
+fn date_format<'a>(h: &'a hb::Helper, name: &str) -> Result<Vec<Item<'a>>, RenderError> {
+    let format = match h.hash_get("format").map(|x| x.value()) {
+        None => DATE_FORMAT,
+        Some(JsonValue::String(format)) => format.as_str(),
+        Some(other) => {
+            return Err(hb_err!(
+                "{}: The format is not a string, it's {:?} as JSON.",
+                name,
+                other
+            ))
+        }
+    };
+
+    let items: Vec<_> = StrftimeItems::new(format).collect();
+    if items.iter().any(|item| matches!(item, Item::Error)) {
+        return Err(hb_err!("{}: Invalid date format '{}'", name, format));
+    }
+    Ok(items)
+}
+
+/// Formats the date with the items from `date_format()`.
+///
+/// Formatting may still fail if the format refers to fields the value doesn't have,
+/// eg. the time of a plain date.
+fn format_date(
+    name: &str,
+    value: impl fmt::Display,
+    input: &dyn fmt::Debug,
+) -> Result<JsonValue, RenderError> {
+    let mut res = String::new();
+    write!(res, "{}", value).map_err(|_| {
+        hb_err!(
+            "{}: Could not format date {:?}, the format refers to fields the date doesn't have",
+            name,
+            input
+        )
+    })?;
+    Ok(JsonValue::String(res))
+}
+
+/// Renders the build timestamp, in UTC.
+///
+/// The timestamp is taken from the `SOURCE_DATE_EPOCH` variable if set, for reproducible builds,
+/// see `Settings::source_date_epoch()`.
+/// Usage: `{{ now }}`, `{{ now format="%Y-%m-%d %H:%M" }}`
+struct NowHelper {
+    now: Result<DateTime<Utc>, String>,
+}
+
+impl NowHelper {
+    fn new(project: &Project) -> Self {
+        let epoch = project.settings.source_date_epoch();
+        Self::with_epoch(epoch.as_deref())
+    }
+
+    fn with_epoch(epoch: Option<&str>) -> Self {
+        let now = match epoch {
+            Some(epoch) => epoch
+                .trim()
+                .parse()
+                .ok()
+                .and_then(|secs| DateTime::from_timestamp(secs, 0))
+                .ok_or_else(|| epoch.to_string()),
+            None => Ok(SystemTime::now().into()),
+        };
+        Self { now }
+    }
+}
+
+impl HelperDef for NowHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &hb::Helper<'reg, 'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc hb::Context,
+        _: &mut hb::RenderContext<'reg, 'rc>,
+    ) -> Result<hb::ScopedJson<'reg, 'rc>, RenderError> {
+        let now = self.now.as_ref().map_err(|epoch| {
+            hb_err!(
+                "now: Invalid SOURCE_DATE_EPOCH value '{}', expected a number of seconds",
+                epoch
+            )
+        })?;
+        let items = date_format(h, "now")?;
+        let res = format_date("now", now.format_with_items(items.iter()), now)?;
+        Ok(hb::ScopedJson::Derived(res))
+    }
+}
+
+/// A date parsed by the `date_fmt` helper.
+enum HbDate {
+    Date(NaiveDate),
+    DateTime(NaiveDateTime),
+    DateTimeOffset(DateTime<FixedOffset>),
+}
+
+impl HbDate {
+    fn parse(s: &str) -> Option<Self> {
+        let s = s.trim();
+        if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+            Some(Self::Date(date))
+        } else if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
+            Some(Self::DateTimeOffset(dt))
+        } else {
+            ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"]
+                .iter()
+                .find_map(|fmt| NaiveDateTime::parse_from_str(s, fmt).ok())
+                .map(Self::DateTime)
+        }
+    }
+
+    fn format(&self, input: &str, items: &[Item]) -> Result<JsonValue, RenderError> {
+        let items = items.iter();
+        match self {
+            Self::Date(d) => format_date("date_fmt", d.format_with_items(items), &input),
+            Self::DateTime(dt) => format_date("date_fmt", dt.format_with_items(items), &input),
+            Self::DateTimeOffset(dt) => {
+                format_date("date_fmt", dt.format_with_items(items), &input)
+            }
+        }
+    }
+}
+
+/// Formats a date, which is either a string or a TOML date from `bard.toml`.
+///
+/// Usage: `{{ date_fmt book.date format="%-d. %-m. %Y" }}`
+struct DateFmtHelper;
+
+impl DateFmtHelper {
+    /// Key under which a TOML datetime ends up when serialized to JSON.
+    const TOML_DATETIME_KEY: &'static str = "$__toml_private_datetime";
+}
+
+impl HelperDef for DateFmtHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &hb::Helper<'reg, 'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc hb::Context,
+        _: &mut hb::RenderContext<'reg, 'rc>,
+    ) -> Result<hb::ScopedJson<'reg, 'rc>, RenderError> {
+        let value = h
+            .param(0)
+            .map(|x| x.value())
+            .ok_or_else(|| hb_err!("date_fmt: No date supplied"))?;
+        let input = match value {
+            JsonValue::String(s) => s.as_str(),
+            JsonValue::Object(obj) if obj.len() == 1 => obj
+                .get(Self::TOML_DATETIME_KEY)
+                .and_then(JsonValue::as_str)
+                .ok_or_else(|| hb_err!("date_fmt: Date is not a string or a TOML date"))?,
+            other => {
+                return Err(hb_err!(
+                    "date_fmt: Date is not a string or a TOML date, it's {:?} as JSON.",
+                    other
+                ))
+            }
+        };
+
+        let items = date_format(h, "date_fmt")?;
+        let date = HbDate::parse(input).ok_or_else(|| {
+            hb_err!(
+                "date_fmt: Could not parse date '{}', expected eg. 2024-05-27 or 2024-05-27T14:30:00",
+                input
+            )
+        })?;
+        let res = date.format(input, &items)?;
+        Ok(hb::ScopedJson::Derived(res))
+    }
+}
+
+// Template loading
+
+const BOM_UTF8: &[u8] = b"\xef\xbb\xbf";
+const BOM_UTF16_LE: &[u8] = b"\xff\xfe";
+const BOM_UTF16_BE: &[u8] = b"\xfe\xff";
+
+/// Read a template file, making sure it is UTF-8 encoded.
+///
+/// Handlebars would otherwise only report a generic error for files in other encodings.
+/// A UTF-8 BOM, if present, is stripped.
+pub(crate) fn read_template_file(path: &Path) -> Result<String> {
+    let bytes =
+        fs::read(path).with_context(|| format!("Could not read template file {:?}", path))?;
+
+    if bytes.starts_with(BOM_UTF16_LE) || bytes.starts_with(BOM_UTF16_BE) {
+        bail!(
+            "Template file {:?} appears to be UTF-16 encoded; bard requires UTF-8",
+            path
+        );
+    }
+
+    let bom_len = if bytes.starts_with(BOM_UTF8) {
+        BOM_UTF8.len()
+    } else {
+        0
+    };
+
+    match std::str::from_utf8(&bytes[bom_len..]) {
+        Ok(content) => Ok(content.to_string()),
+        Err(err) => bail!(
+            "Template file {:?} is not valid UTF-8, invalid byte sequence at offset {}; bard requires UTF-8",
+            path,
+            bom_len + err.valid_up_to(),
+        ),
+    }
+}
+
+/// Names of the HTML tags for which the template `source` defines `h-` inline partials,
+/// for the start tag, end tag, or both. Commented out partials don't count.
+fn tag_partials(source: &str) -> BTreeSet<String> {
+    static COMMENT: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"(?s)\{\{!--.*?--\}\}|\{\{![^}]*\}\}").unwrap());
+    static PARTIAL: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r#"\{\{~?#\*inline\s+"h-/?([^"/]+)/?""#).unwrap());
+
+    let source = COMMENT.replace_all(source, "");
+    PARTIAL
+        .captures_iter(&source)
+        .map(|caps| caps[1].to_string())
+        .collect()
+}
+
 #[derive(Debug)]
 pub(crate) struct HbRender {
     pub(crate) hb: Handlebars<'static>,
     pub(crate) tpl_name: String,
     pub(crate) version: Arc<Mutex<Option<Version>>>,
+    pub(crate) needs_toc: Arc<Mutex<Option<bool>>>,
+    missing: Arc<Mutex<BTreeSet<String>>>,
+    transpose_warnings: Arc<Mutex<BTreeSet<String>>>,
+    /// Names of HTML tags the template defines extension partials for, see `tag_partials()`.
+    pub(crate) tag_partials: BTreeSet<String>,
 }
 
 impl HbRender {
@@ -393,22 +942,38 @@ impl HbRender {
     const ASSUMED_FIRST_VERSION: Version = Version::new(1, 0, 0);
 
     pub(crate) fn new(
+        app: &App,
         project: &Project,
         output: &Output,
         default: &DefaultTemaplate,
-        img_cache: &ImgCache,
     ) -> Result<Self> {
+        let img_cache = app.img_cache();
         let (version_helper, version) = VersionCheckHelper::new();
+        let (needs_toc_helper, needs_toc) = NeedsTocHelper::new();
+        let (missing_helper, missing) = MissingValueHelper::new();
+        let (transpose_helper, transpose_warnings) = TransposeHelper::new(project, output);
         let mut hb = Handlebars::new()
             .with_helper("eq", hb_eq)
             .with_helper("contains", hb_contains)
             .with_helper("cat", hb_cat)
-            .with_helper("default", hb_default)
+            .with_helper("default", DefaultHelper)
             .with_helper("matches", hb_matches)
             .with_helper("math", MathHelper)
+            .with_helper("now", NowHelper::new(project))
+            .with_helper("date_fmt", DateFmtHelper)
+            .with_helper(
+                "format_label",
+                FormatLabelHelper {
+                    labels: project.settings.labels.clone(),
+                },
+            )
+            .with_helper("transpose", transpose_helper)
             .with_helper("img_w", ImgHelper::width(project, img_cache))
             .with_helper("img_h", ImgHelper::height(project, img_cache))
-            .with_helper("version_check", version_helper);
+            .with_helper("version_check", version_helper)
+            .with_helper("needs_toc", needs_toc_helper)
+            .with_helper("helperMissing", missing_helper);
+        hb.set_strict_mode(output.strict_templates);
 
         let tpl_name = output
             .template
@@ -416,40 +981,97 @@ impl HbRender {
             .map(|t| t.to_string_lossy().to_string())
             .unwrap_or_else(|| default.filename.to_string());
 
-        if let Some(template) = output.template.as_ref() {
+        let source = if let Some(template) = output.template.as_ref() {
             if template.exists() {
-                hb.register_template_file(&tpl_name, template)
+                let content = Self::check_default(app, template, default)?;
+                hb.register_template_string(&tpl_name, &content)
                     .with_context(|| format!("Error in template file {:?}", template))?;
+                content
             } else {
                 let parent = template.parent().unwrap(); // The temaplate should've been resolved as absolute in Project
                 fs::create_dir_all(parent)
-                    .and_then(|_| fs::write(template, default.content.as_bytes()))
+                    .and_then(|_| fs::write(template, default.saved_content().as_bytes()))
                     .with_context(|| {
                         format!("Error writing default template to file: {:?}", template)
                     })?;
 
                 hb.register_template_string(&tpl_name, default.content)
                     .expect("Internal error: Could not load default template");
+                default.content.to_string()
             }
         } else {
             hb.register_template_string(&tpl_name, default.content)
                 .expect("Internal error: Could not load default template");
-        }
+            default.content.to_string()
+        };
 
         // Render with no data to an IO Sink.
         // This will certainly fail, but if the version_check() helper is used on top
         // of the template, we will get the version in self.version.
+        // Same goes for the needs_toc() helper.
+        // The missing values and other warnings reported while rendering with no data are of no interest.
         let _ = hb.render_to_write(&tpl_name, &(), io::sink());
+        missing.lock().unwrap().clear();
+        transpose_warnings.lock().unwrap().clear();
 
         Ok(Self {
             hb,
             tpl_name,
             version,
+            needs_toc,
+            missing,
+            transpose_warnings,
+            tag_partials: tag_partials(&source),
         })
     }
 
-    pub(crate) fn render(&self, output: &Path, context: RenderContext) -> Result<()> {
+    /// Read the `template` file, comparing it to the `default` it may have been created from.
+    ///
+    /// An unmodified copy of an older default is updated with `--update-templates`,
+    /// a modified copy is left alone.
+    fn check_default(app: &App, template: &Path, default: &DefaultTemaplate) -> Result<String> {
+        let content = read_template_file(template)?;
+        match TemplateState::of(&content, default) {
+            TemplateState::Outdated if app.update_templates() => {
+                let content = default.saved_content();
+                fs::write(template, content.as_bytes()).with_context(|| {
+                    format!("Error writing default template to file: {:?}", template)
+                })?;
+                app.status("Updated", format!("template {:?}", template));
+                Ok(content)
+            }
+            TemplateState::Outdated => {
+                app.status_notice(
+                    "Outdated",
+                    format!(
+                        "template {:?} is an unmodified copy of an older default template.\n\
+                        Hint: Run with --update-templates to update it.",
+                        template
+                    ),
+                );
+                Ok(content)
+            }
+            TemplateState::Modified { outdated: true } if app.verbosity() >= verbosity::VERBOSE => {
+                app.indent(format!(
+                    "Template {:?} was modified, a newer version of the default {} is available",
+                    template, default.filename
+                ));
+                Ok(content)
+            }
+            _ => Ok(content),
+        }
+    }
+
+    pub(crate) fn render(&self, app: &App, output: &Path, context: RenderContext) -> Result<()> {
+        let output_cfg = context.output;
+        self.missing.lock().unwrap().clear();
+        self.transpose_warnings.lock().unwrap().clear();
+        let start = Instant::now();
         let rendered = self.hb.render(&self.tpl_name, &context)?;
+        app.timing("Template", start.elapsed());
+        let rendered = output_cfg.normalize_text(&rendered);
+        self.report_missing(app);
+        self.report_transpose_warnings(app);
 
         fs::write(output, rendered.as_bytes())
             .with_context(|| format!("Error writing output file: {:?}", output))?;
@@ -457,6 +1079,31 @@ impl HbRender {
         Ok(())
     }
 
+    /// Warn about values the template referenced but which were missing and so rendered as empty.
+    fn report_missing(&self, app: &App) {
+        let missing = mem::take(&mut *self.missing.lock().unwrap());
+        if missing.is_empty() {
+            return;
+        }
+
+        let paths: Vec<_> = missing.into_iter().collect();
+        let msg = format!(
+            "Template references missing values, these were rendered as empty: {}\n\
+            Hint: Set 'strict_templates = true' in the output to make this an error.",
+            paths.join(", "),
+        );
+        app.diag(BuildDiagnostic::warning(Phase::Render, msg).with_file(&self.tpl_name));
+    }
+
+    /// Warn about chords the `transpose` helper couldn't transpose and so rendered as they are.
+    fn report_transpose_warnings(&self, app: &App) {
+        let warnings = mem::take(&mut *self.transpose_warnings.lock().unwrap());
+        for warning in warnings {
+            let msg = format!("'transpose' helper: {}", warning);
+            app.diag(BuildDiagnostic::warning(Phase::Render, msg).with_file(&self.tpl_name));
+        }
+    }
+
     pub(crate) fn version(&self) -> Option<Version> {
         Some(
             self.version
@@ -466,6 +1113,11 @@ impl HbRender {
                 .unwrap_or(Self::ASSUMED_FIRST_VERSION),
         )
     }
+
+    /// Whether the template needs a table of contents, if it declared so using the needs_toc() helper.
+    pub(crate) fn needs_toc(&self) -> Option<bool> {
+        *self.needs_toc.lock().unwrap()
+    }
 }
 
 #[cfg(test)]
@@ -36,3 +36,140 @@ fn hb_helper_math() {
     assert_eq!(math(r#"90.0 "/" 3.0"#), "30.0");
     assert_eq!(math(r#"11.5 "%" 2.0"#), "1.5");
 }
+
+#[test]
+fn hb_helper_missing() {
+    let (helper, missing) = MissingValueHelper::new();
+    let hb = Handlebars::new().with_helper("helperMissing", helper);
+    let data = serde_json::json!({ "a": 1, "b": { "c": null } });
+
+    let rendered = hb
+        .render_template(
+            "{{ a }} {{ x }} {{ b.c }} {{ b.d }} {{ x }} {{#if y}}{{ z }}{{/if}}",
+            &data,
+        )
+        .unwrap();
+    assert_eq!(rendered, "1     ");
+
+    let missing: Vec<_> = missing.lock().unwrap().iter().cloned().collect();
+    assert_eq!(missing, ["b.d", "x"]);
+}
+
+#[test]
+fn hb_helper_now() {
+    let hb = Handlebars::new().with_helper("now", NowHelper::with_epoch(Some("1700000000")));
+    let now = |tpl: &str| hb.render_template(tpl, &0);
+
+    assert_eq!(now("{{ now }}").unwrap(), "2023-11-14");
+    assert_eq!(
+        now(r#"{{ now format="%-d. %-m. %Y %H:%M" }}"#).unwrap(),
+        "14. 11. 2023 22:13"
+    );
+
+    let err = now(r#"{{ now format="%Q" }}"#).unwrap_err().to_string();
+    assert!(err.contains("now: Invalid date format '%Q'"), "{}", err);
+
+    let hb = Handlebars::new().with_helper("now", NowHelper::with_epoch(Some("yesterday")));
+    let err = hb.render_template("{{ now }}", &0).unwrap_err().to_string();
+    assert!(
+        err.contains("now: Invalid SOURCE_DATE_EPOCH value 'yesterday'"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn hb_helper_date_fmt() {
+    let hb = Handlebars::new().with_helper("date_fmt", DateFmtHelper);
+    let data = serde_json::json!({
+        "date": "2024-05-27",
+        "datetime": "2024-05-27T14:30:00",
+        "rfc3339": "2024-05-27T14:30:00+02:00",
+        "toml": { "$__toml_private_datetime": "1979-05-27T07:32:00Z" },
+        "bad": "27/05/2024",
+    });
+    let date_fmt = |tpl: &str| hb.render_template(tpl, &data);
+
+    assert_eq!(date_fmt("{{ date_fmt date }}").unwrap(), "2024-05-27");
+    assert_eq!(
+        date_fmt(r#"{{ date_fmt date format="%-d. %-m. %Y" }}"#).unwrap(),
+        "27. 5. 2024"
+    );
+    assert_eq!(
+        date_fmt(r#"{{ date_fmt datetime format="%H:%M" }}"#).unwrap(),
+        "14:30"
+    );
+    assert_eq!(
+        date_fmt(r#"{{ date_fmt rfc3339 format="%H:%M %:z" }}"#).unwrap(),
+        "14:30 +02:00"
+    );
+    assert_eq!(
+        date_fmt(r#"{{ date_fmt toml format="%Y" }}"#).unwrap(),
+        "1979"
+    );
+
+    let err = date_fmt("{{ date_fmt bad }}").unwrap_err().to_string();
+    assert!(
+        err.contains("date_fmt: Could not parse date '27/05/2024'"),
+        "{}",
+        err
+    );
+    let err = date_fmt(r#"{{ date_fmt date format="%Q" }}"#)
+        .unwrap_err()
+        .to_string();
+    assert!(
+        err.contains("date_fmt: Invalid date format '%Q'"),
+        "{}",
+        err
+    );
+    let err = date_fmt(r#"{{ date_fmt date format="%H:%M" }}"#)
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("date_fmt: Could not format date"), "{}", err);
+}
+
+#[test]
+fn template_state() {
+    let old = DefaultTemaplate {
+        filename: "test.hbs",
+        content: "{{ title }}\n",
+    };
+    let new = DefaultTemaplate {
+        filename: "test.hbs",
+        content: "<h1>{{ title }}</h1>\n",
+    };
+
+    let saved = new.saved_content();
+    assert_eq!(TemplateState::of(&saved, &new), TemplateState::Current);
+    assert_eq!(
+        TemplateState::of(&saved.replace('\n', "\r\n"), &new),
+        TemplateState::Current
+    );
+
+    let saved = old.saved_content();
+    assert_eq!(TemplateState::of(&saved, &new), TemplateState::Outdated);
+
+    let modified = format!("{}{{{{ subtitle }}}}\n", saved);
+    assert_eq!(
+        TemplateState::of(&modified, &new),
+        TemplateState::Modified { outdated: true }
+    );
+    assert_eq!(
+        TemplateState::of(&modified, &old),
+        TemplateState::Modified { outdated: false }
+    );
+
+    assert_eq!(TemplateState::of(old.content, &new), TemplateState::Custom);
+    let marker_removed = saved.split_once('\n').unwrap().1;
+    assert_eq!(
+        TemplateState::of(marker_removed, &new),
+        TemplateState::Custom
+    );
+
+    // The marker doesn't end up in the rendered output
+    let hb = Handlebars::new();
+    let rendered = hb
+        .render_template(&new.saved_content(), &serde_json::json!({ "title": "T" }))
+        .unwrap();
+    assert_eq!(rendered, "<h1>T</h1>\n");
+}
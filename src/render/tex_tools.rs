@@ -7,12 +7,15 @@ use std::str::FromStr;
 use std::time::Duration;
 use std::{env, fmt, fs, io, thread};
 
+use once_cell::sync::Lazy;
 use parking_lot::{const_mutex, Mutex, MutexGuard};
+use regex::Regex;
 use serde::de::Error as _;
 use serde::{Deserialize, Serialize};
 use strum::{Display, EnumString, EnumVariantNames, VariantNames as _};
 
 use crate::app::{keeplevel, verbosity, App, InterruptFlag};
+use crate::diag::{BuildDiagnostic, Phase};
 use crate::prelude::*;
 use crate::util::{ExitStatusExt, ProcessLines, StrExt, TempPath};
 use crate::util_cmd;
@@ -50,12 +53,16 @@ pub struct TexConfig {
 }
 
 impl TexConfig {
-    fn try_from_env() -> Result<Option<Self>> {
+    pub(crate) fn try_from_env() -> Result<Option<Self>> {
         env::var_os("BARD_TEX")
             .map(|var| Self::try_from(var.as_ref()))
             .transpose()
     }
 
+    pub fn distro(&self) -> TexDistro {
+        self.distro
+    }
+
     fn with_distro(distro: TexDistro) -> Self {
         Self {
             distro,
@@ -75,6 +82,13 @@ impl TexConfig {
             return Ok(());
         }
 
+        let version = self.probe_version(app)?;
+        app.indent(version);
+        Ok(())
+    }
+
+    /// Check that the distro's program runs, returns its version.
+    fn probe_version(&mut self, app: &App) -> Result<String> {
         if self.program.is_none() {
             self.program = self.distro.default_program(app);
         }
@@ -99,17 +113,27 @@ impl TexConfig {
             _ => unreachable!(),
         };
 
-        app.indent(version);
-        Ok(())
+        Ok(version)
     }
 
     fn render_args(&self, job: &TexRenderJob) -> Vec<OsString> {
         let mut args = match self.distro {
-            TexDistro::Xelatex => vec![
-                "-interaction=nonstopmode".to_os_string(),
-                "-output-directory".to_os_string(),
-                job.tmp_dir.to_os_string(),
-            ],
+            TexDistro::Xelatex => {
+                let mut jobname = "-jobname=".to_os_string();
+                jobname.push(job.tex_file.file_stem().unwrap());
+                let mut args = vec![
+                    "-interaction=nonstopmode".to_os_string(),
+                    // Explicit so that the job name doesn't depend on the setup, it ends up in the PDF
+                    jobname,
+                    "-output-directory".to_os_string(),
+                    job.tmp_dir.to_os_string(),
+                ];
+                if job.source_date_epoch.is_some() {
+                    // The default comment contains the time of the build
+                    args.push("-output-comment=bard".to_os_string());
+                }
+                args
+            }
             TexDistro::Tectonic => vec![
                 "-k".to_os_string(),
                 "-r".to_os_string(),
@@ -125,12 +149,22 @@ impl TexConfig {
                     search_path.push(job.tmp_dir.as_os_str());
                     search_path
                 },
+                // Tectonic looks up files relative to the TeX file, which may be
+                // in a subdirectory, images are relative to the output dir.
+                "-Z".to_os_string(),
+                {
+                    let mut search_path = "search-path=".to_os_string();
+                    search_path.push(job.work_dir.as_os_str());
+                    search_path
+                },
             ],
             TexDistro::TectonicEmbedded => vec![
                 // With embedded tectonic the search path ToC workaround is done in tectonic_embed.
                 "tectonic".to_os_string(),
                 "-o".to_os_string(),
                 job.tmp_dir.to_os_string(),
+                "-s".to_os_string(),
+                job.work_dir.as_os_str().to_owned(),
             ],
             TexDistro::None => unreachable!(),
         };
@@ -141,7 +175,7 @@ impl TexConfig {
 
     /// Returns what should be the stderr status prefix when logging lines in scrolled mode,
     /// see `App::subprocess_output()`.
-    fn program_status(&self) -> Cow<str> {
+    fn program_status(&self) -> Cow<'_, str> {
         match self.distro {
             TexDistro::Xelatex | TexDistro::Tectonic => {
                 self.program.as_ref().unwrap().to_string_lossy()
@@ -255,7 +289,8 @@ fn test_program(
         .stdin(Stdio::null())
         .stdout(Stdio::piped())
         .stderr(Stdio::null())
-        .spawn()?;
+        .spawn()
+        .with_context(|| format!("Could not run program {:?}", program))?;
 
     // Crude way to wait for the subprocess with a timeout.
     for _ in 0..30 {
@@ -281,13 +316,63 @@ fn test_program(
     Ok(first_line)
 }
 
-fn run_program(
+/// Pattern-match errors and warnings in TeX output `lines`.
+///
+/// Recognized are XeLaTeX errors (`! Message` followed by `l.123` with the line number),
+/// LaTeX and package warnings, and Tectonic's `error: file.tex:123: Message`.
+fn tex_diagnostics<'l>(
+    lines: impl Iterator<Item = &'l [u8]>,
+    tex_file: &Path,
+) -> Vec<BuildDiagnostic> {
+    static TECTONIC_RE: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"^(error|warning): [^:]+:(\d+): (.+)$").unwrap());
+    static LINE_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^l\.(\d+)").unwrap());
+    static WARNING_RE: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"^(LaTeX|Package \S+) Warning: (.+)$").unwrap());
+
+    let lines: Vec<_> = lines
+        .map(|line| String::from_utf8_lossy(line).trim_end().to_string())
+        .collect();
+
+    let mut diags = vec![];
+    for (i, line) in lines.iter().enumerate() {
+        if let Some(message) = line.strip_prefix("! ") {
+            // The line number follows in the context of the error
+            let line = lines[i + 1..]
+                .iter()
+                .take(10)
+                .find_map(|l| LINE_RE.captures(l))
+                .and_then(|caps| caps[1].parse().ok());
+            let mut diag = BuildDiagnostic::error(Phase::Tex, message).with_file(tex_file);
+            diag.line = line;
+            diags.push(diag);
+        } else if let Some(caps) = TECTONIC_RE.captures(line) {
+            let diag = match &caps[1] {
+                "error" => BuildDiagnostic::error(Phase::Tex, &caps[3]),
+                _ => BuildDiagnostic::warning(Phase::Tex, &caps[3]),
+            };
+            let line = caps[2].parse().unwrap_or(0);
+            diags.push(diag.with_file(tex_file).with_line(line));
+        } else if let Some(caps) = WARNING_RE.captures(line) {
+            diags.push(BuildDiagnostic::warning(Phase::Tex, &caps[2]).with_file(tex_file));
+        }
+    }
+
+    diags
+}
+
+/// Run TeX on `tex_file`, diagnostics are pattern-matched in its output.
+/// Errors are collected by `app`, warnings are returned so that only those of the last run get reported,
+/// earlier runs warn about eg. references which get resolved by the reruns.
+fn run_tex(
     app: &App,
     program: impl AsRef<OsStr>,
     args: &[impl AsRef<OsStr>],
+    env: &[(&str, &str)],
     cwd: &Path,
     status: &str,
-) -> Result<()> {
+    tex_file: &Path,
+) -> Result<Vec<BuildDiagnostic>> {
     let program = program.as_ref();
     if app.verbosity() >= verbosity::VERBOSE {
         app.status_bare("Command", program.to_string_lossy());
@@ -299,6 +384,7 @@ fn run_program(
 
     let mut child = Command::new(program)
         .args(args)
+        .envs(env.iter().copied())
         .current_dir(cwd)
         .stdin(Stdio::null())
         .stdout(Stdio::piped())
@@ -310,11 +396,23 @@ fn run_program(
         ProcessLines::new(child.stdout.take().unwrap(), child.stderr.take().unwrap());
 
     app.subprocess_output(&mut ps_lines, program, status)?;
+    // In quiet mode the output isn't printed, but it's still collected for diagnostics
+    while ps_lines.read_line(app.interrupt_flag())?.is_some() {}
 
     let status = app
         .child_wait(&mut child)
         .with_context(|| format!("Error running program {:?}", program))?;
 
+    let mut warnings = vec![];
+    for diag in tex_diagnostics(ps_lines.collected_lines(), tex_file) {
+        if diag.is_error() {
+            // The TeX output is printed below on failure, which is the error of the build
+            app.record_diag(diag);
+        } else {
+            warnings.push(diag);
+        }
+    }
+
     if !status.success() && app.verbosity() == verbosity::NORMAL {
         app.status_bare("Command", program.to_string_lossy());
         for arg in args.iter() {
@@ -329,7 +427,7 @@ fn run_program(
         }
     }
 
-    status.into_result()
+    status.into_result().map(|_| warnings)
 }
 
 #[derive(Debug)]
@@ -337,31 +435,62 @@ pub struct TexRenderJob<'a> {
     pub tex_file: TempPath,
     tmp_dir: TempPath,
     pdf_file: &'a Path,
+    /// Working directory of the TeX process, the output dir, so that relative paths resolve
+    /// even when the PDF is in a subdirectory.
+    work_dir: &'a Path,
     toc_sort_key: Option<&'a str>,
-    reruns: u32,
+    /// Number of TeX reruns after the first run, `None` to detect automatically.
+    reruns: Option<u32>,
+    /// `SOURCE_DATE_EPOCH` for a reproducible build, if set, the temporary directory
+    /// also gets a fixed name as its path may end up in the PDF.
+    source_date_epoch: Option<&'a str>,
+    /// Directory of the output's fonts, added to the font search path of TeX, see `FontFiles`.
+    font_dir: Option<&'a str>,
 }
 
 impl<'a> TexRenderJob<'a> {
     pub fn new(
         tex_file: PathBuf,
         pdf_path: &'a Path,
+        work_dir: &'a Path,
         keep: u8,
         toc_sort_key: Option<&'a str>,
-        reruns: u32,
+        reruns: Option<u32>,
+        source_date_epoch: Option<&'a str>,
     ) -> Result<Self> {
+        let remove_tmp = keep < keeplevel::ALL;
+        let tmp_dir = if source_date_epoch.is_some() {
+            let mut path = pdf_path.as_os_str().to_owned();
+            path.push(".build");
+            TempPath::make_fixed_dir(path, remove_tmp)?
+        } else {
+            TempPath::make_temp_dir(pdf_path, remove_tmp)?
+        };
+
         Ok(Self {
             tex_file: TempPath::new_file(tex_file, keep < keeplevel::TEX_ONLY),
-            tmp_dir: TempPath::make_temp_dir(pdf_path, keep < keeplevel::ALL)?,
+            tmp_dir,
             pdf_file: pdf_path,
+            work_dir,
             toc_sort_key,
             reruns,
+            source_date_epoch,
+            font_dir: None,
         })
     }
+
+    pub fn font_dir(mut self, font_dir: Option<&'a str>) -> Self {
+        self.font_dir = font_dir;
+        self
+    }
 }
 
 impl<'a> TexRenderJob<'a> {
+    /// Number of reruns when these are detected to be needed.
+    const AUTO_RERUNS: u32 = 2;
+
     fn cwd(&self) -> &'a Path {
-        self.pdf_file.parent().unwrap()
+        self.work_dir
     }
 
     fn sort_toc(&self) -> Result<()> {
@@ -381,6 +510,20 @@ impl<'a> TexRenderJob<'a> {
         Ok(())
     }
 
+    /// Checks the temporary files after the first TeX run to see whether reruns are needed,
+    /// ie. whether a TOC was written or there are references to resolve.
+    fn needs_reruns(&self) -> bool {
+        let tex_stem = self.tex_file.file_stem().unwrap();
+        let toc = self.tmp_dir.join_stem(tex_stem, ".toc");
+        let aux = self.tmp_dir.join_stem(tex_stem, ".aux");
+
+        let has_toc = fs::metadata(toc).map(|m| m.len() > 0).unwrap_or(false);
+        let has_refs = fs::read_to_string(aux)
+            .map(|aux| aux.contains("\\newlabel"))
+            .unwrap_or(false);
+        has_toc || has_refs
+    }
+
     fn move_pdf(&self) -> Result<()> {
         let tex_stem = self.tex_file.file_stem().unwrap();
         let out_pdf = self.tmp_dir.join_stem(tex_stem, ".pdf");
@@ -389,6 +532,26 @@ impl<'a> TexRenderJob<'a> {
     }
 }
 
+/// A minimal document using the packages of the default PDF template, see `TexTools::smoke_test()`.
+const SMOKE_TEST_TEX: &str = r"\documentclass[12pt,a5paper,openany]{memoir}
+\usepackage{geometry}
+\usepackage{fontspec}
+\usepackage{titlesec}
+\usepackage{xcolor}
+\usepackage{enumitem}
+\usepackage[defaultlines=5,all]{nowidow}
+\usepackage[pdfusetitle,colorlinks=true]{hyperref}
+\usepackage{float}
+\usepackage{graphicx}
+\usepackage{wrapfig}
+\usepackage{marginnote}
+\usepackage{amssymb}
+\begin{document}
+\section{Smoke Test}
+\begin{tabular}[b]{l}\textbf{\sffamily\color{red}Am}\\Lyrics\end{tabular}
+\end{document}
+";
+
 pub struct TexTools {
     config: TexConfig,
 }
@@ -434,6 +597,12 @@ impl TexTools {
         bail!("No TeX distribution found. Please install a TeX distribution. For more information see https://bard.md/book/install.html.");
     }
 
+    /// Probe the `distro` with its default program, returns its version.
+    /// Unlike `initialize()`, this doesn't select the distro.
+    pub fn probe_distro(app: &App, distro: TexDistro) -> Result<String> {
+        TexConfig::with_distro(distro).probe_version(app)
+    }
+
     pub fn get() -> impl Deref<Target = Self> {
         struct Guard(MutexGuard<'static, Option<TexTools>>);
 
@@ -454,6 +623,36 @@ impl TexTools {
         Ok(())
     }
 
+    /// The selected TeX distribution and program.
+    pub fn config(&self) -> &TexConfig {
+        &self.config
+    }
+
+    /// Compile a minimal document in a temporary directory under `dir`
+    /// to check the TeX distribution has the packages needed by the default template.
+    pub fn smoke_test(&self, app: &App, dir: &Path) -> Result<()> {
+        let tmp_dir = TempPath::make_temp_dir(dir.join("bard-smoke-test"), true)?;
+        let tex_file = tmp_dir.join("smoke-test.tex");
+        let pdf_file = tmp_dir.join("smoke-test.pdf");
+        fs::write(&tex_file, SMOKE_TEST_TEX)
+            .with_context(|| format!("Could not write file {:?}", tex_file))?;
+
+        let job = TexRenderJob::new(
+            tex_file,
+            &pdf_file,
+            &tmp_dir,
+            keeplevel::NONE,
+            None,
+            Some(0),
+            None,
+        )?;
+        self.render_pdf(app, job)?;
+        if !pdf_file.exists() {
+            bail!("TeX did not produce a PDF file.");
+        }
+        Ok(())
+    }
+
     pub fn render_pdf(&self, app: &App, mut job: TexRenderJob) -> Result<()> {
         if self.config.distro.is_none() {
             // TODO: test this:
@@ -466,11 +665,33 @@ impl TexTools {
         let args = self.config.render_args(&job);
         let program = self.config.program.as_ref().unwrap();
         let status = self.config.program_status();
+        let mut env = match job.source_date_epoch {
+            Some(epoch) => vec![("SOURCE_DATE_EPOCH", epoch), ("FORCE_SOURCE_DATE", "1")],
+            None => vec![],
+        };
+        // The trailing separator makes kpathsea add the default font directories
+        let os_font_dir = job
+            .font_dir
+            .map(|dir| format!("{}{}", dir, if cfg!(windows) { ';' } else { ':' }));
+        if let Some(dir) = os_font_dir.as_deref() {
+            env.push(("OSFONTDIR", dir));
+        }
 
-        run_program(app, program, &args, job.cwd(), &status)?;
-        for _ in 0..job.reruns {
+        let tex_file = job.tex_file.to_path_buf();
+        let mut warnings = run_tex(app, program, &args, &env, job.cwd(), &status, &tex_file)?;
+        let reruns = job.reruns.unwrap_or_else(|| {
+            if job.needs_reruns() {
+                TexRenderJob::AUTO_RERUNS
+            } else {
+                0
+            }
+        });
+        for _ in 0..reruns {
             job.sort_toc()?;
-            run_program(app, program, &args, job.cwd(), &status)?;
+            warnings = run_tex(app, program, &args, &env, job.cwd(), &status, &tex_file)?;
+        }
+        for warning in warnings {
+            app.diag(warning);
         }
 
         job.move_pdf()?;
@@ -520,4 +741,43 @@ mod tests {
         test_program(interrupt, "false", "").unwrap_err();
         test_program(interrupt, "sleep", "9800").unwrap_err();
     }
+
+    #[test]
+    fn tex_output_diagnostics() {
+        let output = [
+            "This is XeTeX, Version 3.141592653",
+            "! Undefined control sequence.",
+            "<recently read> \\foo",
+            "l.42 \\foo",
+            "LaTeX Warning: Reference `bar' on page 1 undefined on input line 7.",
+            "Package fontspec Warning: Font \"Foo\" does not contain requested Script.",
+            "error: songbook.tex:13: Missing $ inserted",
+            "warning: songbook.tex:5: Underfull \\hbox",
+        ];
+        let tex_file = Path::new("songbook.tex");
+        let diags = tex_diagnostics(output.iter().map(|l| l.as_bytes()), tex_file);
+
+        let diags: Vec<_> = diags
+            .iter()
+            .map(|d| (d.is_error(), d.line, d.message.as_str()))
+            .collect();
+        assert_eq!(
+            diags,
+            [
+                (true, Some(42), "Undefined control sequence."),
+                (
+                    false,
+                    None,
+                    "Reference `bar' on page 1 undefined on input line 7."
+                ),
+                (
+                    false,
+                    None,
+                    "Font \"Foo\" does not contain requested Script."
+                ),
+                (true, Some(13), "Missing $ inserted"),
+                (false, Some(5), "Underfull \\hbox"),
+            ]
+        );
+    }
 }
@@ -0,0 +1,314 @@
+//! Plain text output, a monospaced layout with chords on a line above the lyrics.
+
+use std::fs;
+
+use unicode_width::UnicodeWidthStr;
+
+use super::{Render, RenderContext};
+use crate::app::App;
+use crate::book::{Block, Chord, DirectiveKind, Inline, Song, Verse, VerseLabel};
+use crate::prelude::*;
+use crate::project::{HtmlContent, Labels};
+
+/// Width of horizontal lines.
+const RULE_WIDTH: usize = 40;
+
+#[derive(Debug, Default)]
+pub struct RTxt;
+
+impl RTxt {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Render for RTxt {
+    fn render(&self, _app: &App, output: &Path, context: RenderContext) -> Result<()> {
+        let chorus_label = context
+            .book
+            .get("chorus_label")
+            .and_then(|label| label.as_str())
+            .unwrap_or("");
+        let mut writer = TxtWriter {
+            labels: context.labels,
+            chorus_label,
+            html_text: context.output.html_content == HtmlContent::Text,
+            lines: vec![],
+        };
+
+        // Handouts leave out the book title, like front matter
+        let title = context.book.get("title").and_then(|title| title.as_str());
+        if let (Some(title), false) = (title, context.handout) {
+            writer.heading(title, '=');
+            if let Some(subtitle) = context.book.get("subtitle").and_then(|s| s.as_str()) {
+                writer.lines.push(subtitle.into());
+            }
+            writer.lines.push(String::new());
+        }
+
+        for matter in context.front_matter.iter() {
+            writer.blocks(&matter.blocks, 0);
+            writer.separator();
+        }
+        for (i, song) in context.songs.iter().enumerate() {
+            if i > 0 {
+                writer.separator();
+            }
+            writer.song(song);
+        }
+        for matter in context.back_matter.iter() {
+            writer.separator();
+            writer.blocks(&matter.blocks, 0);
+        }
+
+        let mut text = String::new();
+        for line in writer.lines.iter() {
+            text.push_str(line.trim_end());
+            text.push('\n');
+        }
+        let text = context.output.normalize_text(text.trim_start_matches('\n'));
+
+        fs::write(output, text.as_bytes())
+            .with_context(|| format!("Error writing output file: {:?}", output))
+    }
+}
+
+/// Display width of `s` in a monospaced font, ie. wide characters take two columns.
+fn width(s: &str) -> usize {
+    UnicodeWidthStr::width(s)
+}
+
+/// Pad `s` with spaces up to the column `col`.
+fn pad(s: &mut String, col: usize) {
+    let w = width(s);
+    if w < col {
+        s.extend(std::iter::repeat(' ').take(col - w));
+    }
+}
+
+/// A line of lyrics along with the rows of chords and alt chords above it.
+#[derive(Default, Debug)]
+struct TxtLine {
+    chords: String,
+    alt_chords: String,
+    lyrics: String,
+}
+
+impl TxtLine {
+    /// Place `chord` above the current end of the lyrics.
+    /// If the previous chord is in the way, the lyrics are padded so that the chords don't run together.
+    fn push_chord(&mut self, chord: &Chord) {
+        let mut col = width(&self.lyrics);
+        for row in [&self.chords, &self.alt_chords] {
+            if !row.is_empty() {
+                col = col.max(width(row) + 1);
+            }
+        }
+
+        pad(&mut self.lyrics, col);
+        pad(&mut self.chords, col);
+        self.chords.push_str(&chord.chord);
+        if let Some(alt_chord) = chord.alt_chord.as_deref() {
+            pad(&mut self.alt_chords, col);
+            self.alt_chords.push_str(alt_chord);
+        }
+    }
+
+    /// The rows of this line which aren't empty, top to bottom, or just the empty lyrics.
+    fn rows(self) -> Vec<String> {
+        let mut rows = vec![self.chords, self.alt_chords];
+        rows.retain(|row| !row.trim().is_empty());
+        if rows.is_empty() || !self.lyrics.trim().is_empty() {
+            rows.push(self.lyrics);
+        }
+        rows
+    }
+}
+
+struct TxtWriter<'a> {
+    labels: &'a Labels,
+    chorus_label: &'a str,
+    /// Whether to keep the text of HTML tags, see `HtmlContent::Text`.
+    html_text: bool,
+    lines: Vec<String>,
+}
+
+impl<'a> TxtWriter<'a> {
+    fn heading(&mut self, text: &str, underline: char) {
+        self.lines.push(text.into());
+        self.lines
+            .push(std::iter::repeat(underline).take(width(text)).collect());
+    }
+
+    fn separator(&mut self) {
+        self.lines.push(String::new());
+        self.lines.push(String::new());
+    }
+
+    fn song(&mut self, song: &Song) {
+        self.heading(&song.title, '-');
+        self.lines
+            .extend(song.subtitles.iter().map(|s| s.to_string()));
+
+        let mut directives = vec![];
+        if let Some(tempo) = song.tempo.as_deref() {
+            directives.push(format!("{} BPM", tempo));
+        }
+        if let Some(time) = song.time.as_deref() {
+            directives.push(time.to_string());
+        }
+        if let Some(key) = song.key.as_deref() {
+            directives.push(format!("Key: {}", key));
+        }
+        if let Some(duration) = song.duration {
+            directives.push(duration.to_string());
+        }
+        if !directives.is_empty() {
+            self.lines.push(directives.join(", "));
+        }
+
+        // Verses are indented by the widest label so that their lyrics line up
+        let indent = song
+            .blocks
+            .iter()
+            .filter_map(|block| match block {
+                Block::Verse(verse) => Some(width(&self.label(&verse.label))),
+                _ => None,
+            })
+            .filter(|&w| w > 0)
+            .map(|w| w + 1)
+            .max()
+            .unwrap_or(0);
+
+        self.lines.push(String::new());
+        self.blocks(&song.blocks, indent);
+    }
+
+    fn blocks(&mut self, blocks: &[Block], indent: usize) {
+        for (i, block) in blocks.iter().enumerate() {
+            if i > 0 {
+                self.lines.push(String::new());
+            }
+            self.block(block, indent);
+        }
+    }
+
+    fn block(&mut self, block: &Block, indent: usize) {
+        match block {
+            Block::Verse(verse) => self.verse(verse, indent),
+            Block::BulletList(list) => {
+                for item in list.items.iter() {
+                    self.lines.push(format!("- {}", item));
+                }
+            }
+            Block::HorizontalLine => self.lines.push("-".repeat(RULE_WIDTH)),
+            Block::Pre { text } => self
+                .lines
+                .extend(text.trim_end_matches('\n').lines().map(String::from)),
+            Block::HtmlBlock(inlines) => self.paragraph(&inlines.inlines, "", 0),
+            Block::Heading { level, text } => {
+                self.heading(text, if *level == 1 { '=' } else { '-' })
+            }
+        }
+    }
+
+    fn label(&self, label: &VerseLabel) -> String {
+        match label {
+            VerseLabel::Verse(num) => self.labels.verse((*num).into()),
+            VerseLabel::Chorus(num) => self.labels.chorus(self.chorus_label, num.map(u64::from)),
+            VerseLabel::Custom(label) => label.to_string(),
+            VerseLabel::None {} => String::new(),
+        }
+    }
+
+    fn verse(&mut self, verse: &Verse, indent: usize) {
+        // A continued verse has the label of the previous one, which is already written
+        let label = if verse.continued {
+            String::new()
+        } else {
+            self.label(&verse.label)
+        };
+
+        for (i, para) in verse.paragraphs.iter().enumerate() {
+            if i > 0 {
+                self.lines.push(String::new());
+            }
+            self.paragraph(para, if i == 0 { &label } else { "" }, indent);
+        }
+    }
+
+    /// Write the lines of `inlines` indented by `indent`, the `label` is put in front of the first row of lyrics.
+    fn paragraph(&mut self, inlines: &[Inline], label: &str, indent: usize) {
+        let mut lines = vec![TxtLine::default()];
+        for inline in inlines {
+            if inline.is_break() {
+                lines.push(TxtLine::default());
+            } else {
+                let line = lines.last_mut().unwrap();
+                self.inline(inline, line);
+            }
+        }
+
+        for (n, line) in lines.into_iter().enumerate() {
+            let rows = line.rows();
+            let last = rows.len() - 1;
+            for (i, row) in rows.into_iter().enumerate() {
+                let mut prefix = if n == 0 && i == last {
+                    label.to_string()
+                } else {
+                    String::new()
+                };
+                pad(&mut prefix, indent);
+                self.lines.push(prefix + &row);
+            }
+        }
+    }
+
+    fn inline(&self, inline: &Inline, line: &mut TxtLine) {
+        match inline {
+            Inline::Text { text, .. } => line.lyrics.push_str(text),
+            // Baseline chords are written next to the lyrics
+            Inline::Chord(chord) if chord.baseline => line.lyrics.push_str(&chord.chord),
+            Inline::Chord(chord) => {
+                line.push_chord(chord);
+                chord.inlines.iter().for_each(|i| self.inline(i, line));
+            }
+            Inline::Emph(inlines) | Inline::Strong(inlines) => {
+                inlines.inlines.iter().for_each(|i| self.inline(i, line))
+            }
+            Inline::Link(link) => line.lyrics.push_str(&link.text),
+            Inline::ChorusRef(chorus_ref) => {
+                line.lyrics.push_str(&chorus_ref.prefix_space);
+                let num = chorus_ref.num.map(u64::from);
+                line.lyrics
+                    .push_str(&self.labels.chorus(self.chorus_label, num));
+            }
+            Inline::ChorusRefRange(range) => {
+                line.lyrics.push_str(&range.prefix_space);
+                line.lyrics.push_str(&format!(
+                    "{}\u{2013}{}",
+                    self.labels
+                        .chorus(self.chorus_label, Some(range.start.into())),
+                    self.labels
+                        .chorus(self.chorus_label, Some(range.end.into())),
+                ));
+            }
+            Inline::HtmlTag(tag) => {
+                if let Some(text) = tag.text.as_deref().filter(|_| self.html_text) {
+                    line.lyrics.push_str(text);
+                }
+            }
+            Inline::Directive(directive) => {
+                let value = match directive.kind {
+                    DirectiveKind::Tempo => format!("{} BPM", directive.value),
+                    DirectiveKind::Key => format!("Key: {}", directive.value),
+                    DirectiveKind::Time | DirectiveKind::Duration => directive.value.to_string(),
+                    DirectiveKind::Alias | DirectiveKind::Dir => return,
+                };
+                line.lyrics.push_str(&directive.prefix_space);
+                line.lyrics.push_str(&value);
+            }
+            Inline::Break | Inline::Image(..) | Inline::Transpose(..) => {}
+        }
+    }
+}
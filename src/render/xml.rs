@@ -1,17 +1,18 @@
 //! XML Renderer.
 
-use std::fs::File;
+use std::fs;
 use std::io;
-use std::io::Write;
 
 use super::Render;
 use super::RenderContext;
 use crate::app::App;
+use crate::music::{LegendEntry, NotationLegend};
 use crate::prelude::*;
 use crate::ProgramMeta;
 
 use crate::project::Format;
-use crate::project::Output;
+use crate::project::{BookMeta, Labels, Style, Styles};
+use crate::project::{LineEndings, Output};
 use crate::util::xml_support::*;
 use crate::xml_write;
 
@@ -40,8 +41,18 @@ impl XmlWrite for Format {
     }
 }
 
+impl XmlWrite for LineEndings {
+    fn write<W>(&self, mut writer: &mut Writer<W>) -> quick_xml::Result<()>
+    where
+        W: io::Write,
+    {
+        writer.write_text(self)
+    }
+}
+
 xml_write!(struct Output {
     file,
+    files,
     template,
     format,
     toc_sort,
@@ -51,11 +62,53 @@ xml_write!(struct Output {
     dpi,
     tex_runs,
     script,
+    script_expect_modify,
+    script_on,
+    script_min_interval,
+    on_update,
+    line_endings,
+    trim_trailing_whitespace,
+    strict_templates,
+    json_pretty,
+    sort_keys,
+    volumes,
+    setlist,
+    songs_filter,
+    slides,
+    image_format,
+    image_command,
+    fonts,
+    html_content,
+    notation_name,
+    notation,
     book_overrides,
+    sort,
+    entry,
 } -> |w| {
     let _ = file;
+    let _ = files;
     let _ = template;
+    let _ = script_expect_modify;
+    let _ = script_on;
+    let _ = script_min_interval;
+    let _ = on_update;
+    let _ = trim_trailing_whitespace;
+    let _ = strict_templates;
+    let _ = json_pretty;
+    let _ = sort_keys;
+    let _ = volumes;
+    let _ = setlist;
+    let _ = songs_filter;
+    let _ = slides;
+    let _ = image_format;
+    let _ = image_command;
+    let _ = fonts;
+    let _ = html_content;
+    let _ = notation_name;
+    let _ = notation;
     let _ = book_overrides;
+    let _ = sort;
+    let _ = entry;
     w.tag("output")
         .content()?
         .field_opt(format)?
@@ -64,31 +117,152 @@ xml_write!(struct Output {
         .field(toc_sort)?
         .field(toc_sort_key)?
         .field_opt(dpi)?
-        .field(tex_runs)?
+        .field_opt(tex_runs)?
         .field_opt(script)?
+        .field_opt(line_endings)?
+});
+
+/// Writes a `Style` as an empty tag named after the verse kind it applies to.
+struct StyleTag<'a>(&'static str, &'a Style);
+
+impl<'a> XmlWrite for StyleTag<'a> {
+    fn write<W>(&self, writer: &mut Writer<W>) -> quick_xml::Result<()>
+    where
+        W: io::Write,
+    {
+        let StyleTag(name, style) = self;
+        writer
+            .tag(name)
+            .attr(("emph", style.emph))
+            .attr_opt("html_class", &style.html_class)
+            .finish()
+    }
+}
+
+xml_write!(struct Styles {
+    chorus,
+} -> |w| {
+    w.tag("styles")
+        .content()?
+        .value(StyleTag("chorus", chorus.unwrap()))?
+});
+
+xml_write!(struct Labels {
+    verse_format,
+    chorus_format,
+} -> |w| {
+    w.tag("labels")
+        .attr(verse_format)
+        .attr(chorus_format)
+});
+
+xml_write!(struct BookMeta {
+    description,
+    author,
+    url,
+    cover_image_path,
+    cover_image,
+    cover_image_url,
+} -> |w| {
+    // Only the resolved image is written
+    let _ = cover_image_path;
+    w.tag("book-meta")
+        .content()?
+        .field_opt(description)?
+        .field_opt(author)?
+        .field_opt(url)?
+        .field_opt(cover_image)?
+        .field_opt(cover_image_url)?
+});
+
+xml_write!(struct LegendEntry {
+    chromatic_index,
+    name,
+    alt_name,
+} -> |w| {
+    w.tag("tone")
+        .attr(chromatic_index)
+        .attr(name)
+        .attr_opt("alt_name", alt_name.unwrap())
+});
+
+xml_write!(struct NotationLegend {
+    notation,
+    alt_notation,
+    entries,
+} -> |w| {
+    let alt_notation = alt_notation.unwrap().as_ref().map(|n| n.to_string());
+    w.tag("legend")
+        .attr(notation)
+        .attr_opt("alt_notation", &alt_notation)
+        .content()?
+        .many(entries)?
 });
 
 xml_write!(struct RenderContext<'a> {
     book,
+    book_meta,
+    front_matter,
     songs,
+    back_matter,
     songs_sorted,
+    songs_by_first_line,
+    aliases,
+    has_rtl,
+    fonts_available,
+    fonts,
+    hovorka,
+    total_duration,
     notation,
+    notation_legend,
+    styles,
+    labels,
     output,
+    volume,
+    handout,
+    slide,
     program,
 } -> |w| {
+    // Volumes and handouts are only supported for PDF outputs, slides for images outputs
+    let _ = volume;
+    let _ = handout;
+    let _ = slide;
+    // Fonts only matter to templates
+    let _ = fonts_available;
+    let _ = fonts;
+    // The Hovorka song text is derived from the songs
+    let _ = hovorka;
     w.tag("songbook")
         .attr(notation)
+        .attr_opt("has-rtl", &has_rtl.unwrap().then_some("true"))
+        .attr_opt("total-duration", &total_duration.unwrap().map(|d| d.to_string()))
         .content()?
         .comment("The [book] section in bard.toml")?
         .field(book)?
+        .comment("Description, author, URL, and cover image from the [book] section in bard.toml")?
+        .value(book_meta)?
         .comment("References to <song> elements in alphabetically-sorted order")?
         .value_wrap("songs-sorted", songs_sorted)?
+        .comment("References to <song> elements of songs with lyrics, sorted by their first line")?
+        .value_wrap("songs-by-first-line", songs_by_first_line)?
+        .comment("Previous titles of songs from !alias() directives")?
+        .value_wrap("aliases", aliases)?
+        .comment("Style hints from the [book.styles] section in bard.toml")?
+        .value(styles)?
+        .comment("Tone names in the book's notation and in the notations of alt chords")?
+        .value_wrap("notation-legend", notation_legend)?
+        .comment("Label formats from the [book.labels] section in bard.toml")?
+        .value(labels)?
         .comment("Fields in the [[output]] section in bard.toml")?
         .value_wrap("output", output)?
         .comment("Software metadata")?
         .value(program)?
+        .comment("Front matter files, rendered before the songs")?
+        .value_wrap("front-matter", front_matter)?
         .comment("Song data")?
         .field(songs)?
+        .comment("Back matter files, rendered after the songs")?
+        .value_wrap("back-matter", back_matter)?
 });
 
 #[derive(Debug, Default)]
@@ -102,16 +276,15 @@ impl RXml {
 
 impl Render for RXml {
     fn render(&self, _app: &App, output: &Path, context: RenderContext) -> anyhow::Result<()> {
-        File::create(output)
-            .map_err(Error::from)
-            .and_then(|f| {
-                let mut writer = Writer::new_with_indent(f, b' ', 2);
-                context.write(&mut writer)?;
-
-                let mut f = writer.into_inner();
-                f.write_all(b"\n")?;
-                Ok(())
-            })
+        let mut writer = Writer::new_with_indent(vec![], b' ', 2);
+        context.write(&mut writer)?;
+
+        let mut xml = writer.into_inner();
+        xml.push(b'\n');
+        let xml = String::from_utf8(xml)?;
+        let xml = context.output.normalize_text(&xml);
+
+        fs::write(output, xml.as_bytes())
             .with_context(|| format!("Error writing output file: {:?}", output))
     }
 }
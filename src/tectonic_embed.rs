@@ -1,5 +1,3 @@
-use std::iter;
-
 use tectonic::config::PersistentConfig;
 use tectonic::driver;
 use tectonic::status::plain::PlainStatusBackend;
@@ -34,6 +32,9 @@ pub struct Tectonic {
     /// Output directory path
     #[arg(short)]
     out_dir: Option<PathBuf>,
+    /// Additional search path
+    #[arg(short)]
+    search_path: Vec<PathBuf>,
 
     /// Input TeX file
     input: PathBuf,
@@ -84,13 +85,17 @@ impl Tectonic {
             .print_stdout(app.verbosity() >= 2)
             .output_format(driver::OutputFormat::Pdf);
 
+        let mut search_paths = self.search_path.clone();
         if let Some(out_dir) = self.out_dir.as_ref() {
-            sb.output_dir(out_dir)
-                // A workaround for https://github.com/tectonic-typesetting/tectonic/issues/981
-                // see also TexConfig::render_args()
-                .unstables(UnstableOptions::from_unstable_args(iter::once(
-                    UnstableArg::SearchPath(out_dir.clone()),
-                )));
+            sb.output_dir(out_dir);
+            // A workaround for https://github.com/tectonic-typesetting/tectonic/issues/981
+            // see also TexConfig::render_args()
+            search_paths.push(out_dir.clone());
+        }
+        if !search_paths.is_empty() {
+            sb.unstables(UnstableOptions::from_unstable_args(
+                search_paths.into_iter().map(UnstableArg::SearchPath),
+            ));
         }
 
         let mut sess = sb
@@ -0,0 +1,166 @@
+//! Song durations: parsing of the `!duration()` directive value or the `duration` metadata key
+//! and display formatting.
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::iter::Sum;
+use std::ops::Add;
+
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+
+/// Duration with a precision of seconds, such as the length of a song.
+///
+/// Serialized as a struct with the number of `seconds` and the `display` form,
+/// ie. `m:ss`, or `h:mm:ss` for durations of an hour or longer.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Debug)]
+pub struct Duration {
+    seconds: u32,
+}
+
+impl Duration {
+    pub const fn from_secs(seconds: u32) -> Self {
+        Self { seconds }
+    }
+
+    pub fn as_secs(self) -> u32 {
+        self.seconds
+    }
+
+    /// Parse a duration in the `mm:ss` format or a plain number of seconds.
+    /// Zero durations are rejected as well as seconds out of range in `mm:ss`.
+    pub fn parse(s: &str) -> Option<Self> {
+        let digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+
+        let seconds = match s.trim().split_once(':') {
+            Some((mins, secs)) => {
+                if !digits(mins) || !digits(secs) || secs.len() != 2 {
+                    return None;
+                }
+                let mins: u32 = mins.parse().ok()?;
+                let secs: u32 = secs.parse().ok()?;
+                if secs >= 60 {
+                    return None;
+                }
+                mins.checked_mul(60)?.checked_add(secs)?
+            }
+            None if digits(s.trim()) => s.trim().parse().ok()?,
+            None => return None,
+        };
+
+        (seconds > 0).then_some(Self { seconds })
+    }
+
+    /// Parse the `duration` value of a song's metadata block,
+    /// either a string as in `parse()` or an integer number of seconds.
+    pub fn from_toml(value: &toml::Value) -> Option<Self> {
+        match value {
+            toml::Value::String(s) => Self::parse(s),
+            toml::Value::Integer(secs) => u32::try_from(*secs)
+                .ok()
+                .filter(|&secs| secs > 0)
+                .map(Self::from_secs),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Duration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (hours, mins, secs) = (
+            self.seconds / 3600,
+            self.seconds / 60 % 60,
+            self.seconds % 60,
+        );
+        if hours > 0 {
+            write!(f, "{}:{:02}:{:02}", hours, mins, secs)
+        } else {
+            write!(f, "{}:{:02}", mins, secs)
+        }
+    }
+}
+
+impl Add for Duration {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self {
+            seconds: self.seconds.saturating_add(rhs.seconds),
+        }
+    }
+}
+
+impl Sum for Duration {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::default(), Add::add)
+    }
+}
+
+impl Serialize for Duration {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct("Duration", 2)?;
+        s.serialize_field("seconds", &self.seconds)?;
+        s.serialize_field("display", &self.to_string())?;
+        s.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn duration_parse() {
+        assert_eq!(Duration::parse("3:45"), Some(Duration::from_secs(225)));
+        assert_eq!(Duration::parse("03:05"), Some(Duration::from_secs(185)));
+        assert_eq!(Duration::parse("0:30"), Some(Duration::from_secs(30)));
+        assert_eq!(Duration::parse("75:00"), Some(Duration::from_secs(4500)));
+        assert_eq!(Duration::parse("225"), Some(Duration::from_secs(225)));
+
+        for invalid in [
+            "",
+            "0",
+            "0:00",
+            "3:60",
+            "3:5",
+            "3:456",
+            ":45",
+            "3:",
+            "-3:45",
+            "3.5",
+            "1:02:03",
+            "abc",
+            "99999999999",
+        ] {
+            assert_eq!(Duration::parse(invalid), None, "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn duration_from_toml() {
+        use toml::Value;
+
+        let from_toml = |v: Value| Duration::from_toml(&v);
+        assert_eq!(from_toml("3:45".into()), Some(Duration::from_secs(225)));
+        assert_eq!(from_toml(225.into()), Some(Duration::from_secs(225)));
+        assert_eq!(from_toml(0.into()), None);
+        assert_eq!(from_toml((-5).into()), None);
+        assert_eq!(from_toml(i64::MAX.into()), None);
+        assert_eq!(from_toml(3.75.into()), None);
+        assert_eq!(from_toml("3:99".into()), None);
+    }
+
+    #[test]
+    fn duration_display() {
+        assert_eq!(Duration::from_secs(5).to_string(), "0:05");
+        assert_eq!(Duration::from_secs(225).to_string(), "3:45");
+        assert_eq!(Duration::from_secs(3600).to_string(), "1:00:00");
+        assert_eq!(Duration::from_secs(4509).to_string(), "1:15:09");
+
+        let total: Duration = [185, 225, 40]
+            .iter()
+            .copied()
+            .map(Duration::from_secs)
+            .sum();
+        assert_eq!(total, Duration::from_secs(450));
+    }
+}
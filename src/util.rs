@@ -1,10 +1,14 @@
 use std::error::Error as StdError;
 use std::hash::Hash;
 use std::path::Path as StdPath;
+use std::process::{Command, Stdio};
 use std::sync::Arc;
+use std::thread;
+use std::time::SystemTime;
 use std::{collections::HashMap, ffi::OsString};
 use std::{fmt, fs};
 
+use image::{image_dimensions, ImageError};
 use lexical_sort::{lexical_cmp, PathSort};
 use parking_lot::RwLock;
 
@@ -12,16 +16,47 @@ use crate::prelude::*;
 
 mod path;
 mod process;
+mod slug;
+mod sort;
 pub mod xml_support;
 
 pub use path::{PathBufExt, PathExt, TempPath};
 pub use process::{ExitStatusExt, ProcessLines};
+pub use slug::{slugify, slugify_id};
+pub use sort::SortOrder;
 
 #[cfg(unix)]
 pub const LINE_END: &str = "\n";
 #[cfg(not(unix))]
 pub const LINE_END: &str = "\r\n";
 
+/// Rewrite line endings of `text` to `line_end`, or keep the original ones if `None`,
+/// optionally removing trailing whitespace on each line.
+pub fn normalize_lines(text: &str, line_end: Option<&str>, trim_trailing: bool) -> String {
+    let mut res = String::with_capacity(text.len());
+    for line in text.split_inclusive('\n') {
+        let (content, orig_end) = if let Some(content) = line.strip_suffix("\r\n") {
+            (content, "\r\n")
+        } else if let Some(content) = line.strip_suffix('\n') {
+            (content, "\n")
+        } else {
+            (line, "")
+        };
+
+        if trim_trailing {
+            res.push_str(content.trim_end());
+        } else {
+            res.push_str(content);
+        }
+
+        if !orig_end.is_empty() {
+            res.push_str(line_end.unwrap_or(orig_end));
+        }
+    }
+
+    res
+}
+
 /// `Vec` utils
 pub trait VecExt {
     fn remove_prefix(&mut self, size: usize) -> Self;
@@ -44,7 +79,6 @@ where
 pub type BStr = Box<str>;
 
 /// `str` utils
-
 pub trait StrExt {
     fn to_os_string(&self) -> OsString;
     fn clone_bstr(&self) -> Box<str>;
@@ -138,6 +172,38 @@ pub fn read_dir_all<P: AsRef<Path>>(path: P) -> Result<Vec<PathBuf>> {
     Ok(res)
 }
 
+/// Open a file in the system's default viewer application.
+pub fn open(path: &Path) -> Result<()> {
+    #[cfg(target_os = "macos")]
+    let mut cmd = Command::new("open");
+    #[cfg(windows)]
+    let mut cmd = {
+        let mut cmd = Command::new("cmd");
+        cmd.args(["/C", "start", ""]);
+        cmd
+    };
+    #[cfg(all(unix, not(target_os = "macos")))]
+    let mut cmd = Command::new("xdg-open");
+
+    let mut child = cmd
+        .arg(path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .with_context(|| {
+            format!(
+                "Could not run {:?} to open file {:?}",
+                cmd.get_program(),
+                path
+            )
+        })?;
+
+    // The opener may keep running with the viewer, reap it in the background.
+    thread::spawn(move || child.wait());
+    Ok(())
+}
+
 /// A very simple cache.
 #[derive(Clone)]
 pub struct Cache<K, V>(Arc<RwLock<HashMap<K, V>>>);
@@ -146,6 +212,20 @@ impl<K, V> Cache<K, V> {
     pub fn new() -> Self {
         Self(Arc::new(RwLock::new(HashMap::new())))
     }
+
+    /// Number of cached entries.
+    pub fn len(&self) -> usize {
+        self.0.read().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.read().is_empty()
+    }
+
+    /// Number of entries the cache can hold without reallocating.
+    pub fn capacity(&self) -> usize {
+        self.0.read().capacity()
+    }
 }
 
 impl<K, V> Cache<K, V>
@@ -165,6 +245,28 @@ where
         self.0.write().insert(key.clone(), value.clone());
         Ok(value)
     }
+
+    /// Like `try_get()`, but `f` can't fail and a cached value is only used if `valid` returns `true` for it.
+    pub fn get_valid(&self, key: &K, valid: impl FnOnce(&V) -> bool, f: impl FnOnce() -> V) -> V {
+        let cache = self.0.read();
+        if let Some(value) = cache.get(key).filter(|value| valid(value)) {
+            return value.clone();
+        }
+
+        drop(cache);
+
+        let value = f();
+        self.0.write().insert(key.clone(), value.clone());
+        value
+    }
+
+    pub fn remove(&self, key: &K) {
+        self.0.write().remove(key);
+    }
+
+    pub fn clear(&self) {
+        self.0.write().clear();
+    }
 }
 
 impl<K, V> Default for Cache<K, V> {
@@ -183,5 +285,50 @@ impl<K, V> fmt::Debug for Cache<K, V> {
     }
 }
 
+/// Image dimensions read from a file, or the error reading it.
+type ImgProbe = Result<(u32, u32), Arc<ImageError>>;
+
 /// Cache of image dimensions.
-pub type ImgCache = Cache<PathBuf, (u32, u32)>;
+///
+/// Failures are cached too so that a corrupt file isn't decoded again for every output.
+/// Entries are keyed by path and only used while the file's modification time stays the same.
+#[derive(Clone, Default, Debug)]
+pub struct ImgCache(Cache<PathBuf, (Option<SystemTime>, ImgProbe)>);
+
+impl ImgCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn dimensions(&self, path: &Path) -> ImgProbe {
+        let modified = fs::metadata(path).and_then(|meta| meta.modified()).ok();
+        let (_, res) = self.0.get_valid(
+            &path.to_owned(),
+            |(cached, _)| *cached == modified,
+            || (modified, image_dimensions(path).map_err(Arc::new)),
+        );
+        res
+    }
+
+    /// Forget the dimensions of the image at `path`, eg. when `bard watch` sees it changed.
+    /// The modification time may stay the same if the file is overwritten quickly.
+    pub fn invalidate(&self, path: &Path) {
+        self.0.remove(&path.to_owned());
+    }
+
+    pub fn clear(&self) {
+        self.0.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.0.capacity()
+    }
+}
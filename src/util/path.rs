@@ -109,6 +109,20 @@ impl TempPath {
         );
     }
 
+    /// Like `make_temp_dir()`, but with a predictable name, for reproducible builds.
+    /// A leftover directory of the same name is removed first.
+    pub fn make_fixed_dir(path: impl Into<PathBuf>, remove: bool) -> Result<Self> {
+        let path = path.into();
+        if path.exists() {
+            fs::remove_dir_all(&path)
+                .with_context(|| format!("Could not remove directory {:?}", path))?;
+        }
+        if !Self::create_dir(&path)? {
+            bail!("Could not create directory {:?}, it already exists", path);
+        }
+        Ok(Self::new_dir(path, remove))
+    }
+
     fn create_dir(path: impl AsRef<OsStr>) -> Result<bool> {
         let path = Path::new(path.as_ref());
         match fs::create_dir(path) {
@@ -0,0 +1,78 @@
+//! Slugs of song titles, used in file names and ids of songs.
+
+use icu_normalizer::DecomposingNormalizer;
+
+/// Make a filename stem out of a song title,
+/// ie. lowercase alphanumerics separated by dashes.
+pub fn slugify(title: &str) -> String {
+    let mut res = String::with_capacity(title.len());
+    for c in title.chars() {
+        if c.is_alphanumeric() {
+            res.extend(c.to_lowercase());
+        } else if !res.is_empty() && !res.ends_with('-') {
+            res.push('-');
+        }
+    }
+
+    while res.ends_with('-') {
+        res.pop();
+    }
+
+    if res.is_empty() {
+        res.push_str("song");
+    }
+    res
+}
+
+/// Make an id for links out of a song title, like `slugify()`, but with diacritics removed
+/// from letters so that the id can be typed in a URL, eg. `Čerešně` becomes `ceresne`.
+pub fn slugify_id(title: &str) -> String {
+    slugify(&strip_diacritics(title))
+}
+
+/// Remove diacritics from latin letters, letters without a decomposition are transliterated,
+/// eg. `ł` becomes `l`, other characters are kept as they are.
+fn strip_diacritics(text: &str) -> String {
+    let decomposed = DecomposingNormalizer::new_nfd().normalize(text);
+    let mut res = String::with_capacity(decomposed.len());
+    for c in decomposed.chars() {
+        match c {
+            // Combining diacritical marks
+            '\u{0300}'..='\u{036f}' => {}
+            'ß' => res.push_str("ss"),
+            'æ' | 'Æ' => res.push_str("ae"),
+            'œ' | 'Œ' => res.push_str("oe"),
+            'þ' | 'Þ' => res.push_str("th"),
+            'ø' | 'Ø' => res.push('o'),
+            'ł' | 'Ł' => res.push('l'),
+            'đ' | 'Đ' | 'ð' | 'Ð' => res.push('d'),
+            'ı' => res.push('i'),
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slugify_titles() {
+        assert_eq!(slugify("Danny Boy"), "danny-boy");
+        assert_eq!(slugify("  Whiskey in the Jar!  "), "whiskey-in-the-jar");
+        assert_eq!(slugify("Hej, sokoli"), "hej-sokoli");
+        assert_eq!(slugify("Čerešně"), "čerešně");
+        assert_eq!(slugify("?!"), "song");
+    }
+
+    #[test]
+    fn slugify_ids() {
+        assert_eq!(slugify_id("Danny Boy"), "danny-boy");
+        assert_eq!(slugify_id("Čerešně"), "ceresne");
+        assert_eq!(slugify_id("Pověste ho vejš"), "poveste-ho-vejs");
+        assert_eq!(slugify_id("Łódź, Straße"), "lodz-strasse");
+        assert_eq!(slugify_id("Калинка"), "калинка");
+        assert_eq!(slugify_id("?!"), "song");
+    }
+}
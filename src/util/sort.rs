@@ -0,0 +1,128 @@
+//! Ordering of song titles in the index, see `SortOrder`.
+
+use std::fmt;
+use std::str::FromStr;
+
+use icu_collator::{Collator, CollatorOptions};
+use icu_provider::DataLocale;
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::*;
+
+/// How songs are ordered in `songs_sorted`, set with `sort` in the `[book]` section.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Default, Debug)]
+#[serde(try_from = "String", into = "String")]
+pub enum SortOrder {
+    /// Order of the lexical-sort crate, letters with diacritics sort as their base letters.
+    #[default]
+    Lexical,
+    /// Collation rules of a locale, eg. `locale:cs`, where `Ch` sorts after `H`.
+    Locale(String),
+    /// The order of the songs in the input files.
+    None,
+}
+
+impl SortOrder {
+    fn collator(locale: &str) -> Result<Collator> {
+        let data_locale: DataLocale = locale
+            .parse()
+            .map_err(|_| anyhow!("Invalid locale '{}'", locale))?;
+        Collator::try_new(&data_locale, CollatorOptions::new())
+            .map_err(|err| anyhow!("Could not load collation of locale '{}': {}", locale, err))
+    }
+
+    /// Sort `slice` by the strings returned by `key_fn`, the sort is stable.
+    pub fn sort_by<T, F>(&self, slice: &mut [T], mut key_fn: F)
+    where
+        F: FnMut(&T) -> &str,
+    {
+        match self {
+            Self::Lexical => super::sort_lexical_by(slice, key_fn),
+            Self::Locale(locale) => {
+                // The locale was checked when parsing
+                let collator = Self::collator(locale).expect("Invalid locale");
+                slice.sort_by(|lhs, rhs| collator.compare(key_fn(lhs), key_fn(rhs)));
+            }
+            Self::None => {}
+        }
+    }
+}
+
+impl FromStr for SortOrder {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "lexical" => Ok(Self::Lexical),
+            "none" => Ok(Self::None),
+            _ => match s.strip_prefix("locale:") {
+                Some(locale) => {
+                    Self::collator(locale)?;
+                    Ok(Self::Locale(locale.to_string()))
+                }
+                None => bail!(
+                    "Invalid sort order '{}', expected \"lexical\", \"none\", or \"locale:<code>\", eg. \"locale:cs\"",
+                    s
+                ),
+            },
+        }
+    }
+}
+
+impl TryFrom<String> for SortOrder {
+    type Error = Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<SortOrder> for String {
+    fn from(order: SortOrder) -> Self {
+        order.to_string()
+    }
+}
+
+impl fmt::Display for SortOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Lexical => f.write_str("lexical"),
+            Self::Locale(locale) => write!(f, "locale:{}", locale),
+            Self::None => f.write_str("none"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TITLES: &[&str] = &["Ivan", "Chata", "The Boxer", "Hora", "Čas"];
+
+    fn sorted(order: &str) -> Vec<&'static str> {
+        let order: SortOrder = order.parse().unwrap();
+        let mut titles = TITLES.to_vec();
+        order.sort_by(&mut titles, |title| title);
+        titles
+    }
+
+    #[test]
+    fn sort_order() {
+        assert_eq!(
+            sorted("lexical"),
+            ["Čas", "Chata", "Hora", "Ivan", "The Boxer"]
+        );
+        assert_eq!(
+            sorted("locale:cs"),
+            ["Čas", "Hora", "Chata", "Ivan", "The Boxer"]
+        );
+        assert_eq!(sorted("none"), TITLES);
+
+        assert_eq!(
+            SortOrder::from_str("locale:cs").unwrap().to_string(),
+            "locale:cs"
+        );
+        assert!(SortOrder::from_str("locale:").is_err());
+        assert!(SortOrder::from_str("alphabetical").is_err());
+    }
+}
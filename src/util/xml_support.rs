@@ -24,7 +24,7 @@ pub trait XmlWrite {
         W: io::Write;
 }
 
-impl<'a, T> XmlWrite for &'a T
+impl<T> XmlWrite for &T
 where
     T: XmlWrite + ?Sized,
 {
@@ -50,16 +50,13 @@ where
 
 impl<'a, T> XmlWrite for Cow<'a, T>
 where
-    T: XmlWrite + Clone + ?Sized,
+    T: XmlWrite + ToOwned + ?Sized,
 {
     fn write<W>(&self, writer: &mut Writer<W>) -> XmlResult<()>
     where
         W: io::Write,
     {
-        match self {
-            Cow::Borrowed(b) => b.write(writer),
-            Cow::Owned(o) => o.write(writer),
-        }
+        T::write(self, writer)
     }
 }
 
@@ -98,6 +95,18 @@ where
     }
 }
 
+impl<I> XmlWrite for Vec<I>
+where
+    I: XmlWrite,
+{
+    fn write<W>(&self, writer: &mut Writer<W>) -> XmlResult<()>
+    where
+        W: io::Write,
+    {
+        XmlWrite::write(self.as_slice(), writer)
+    }
+}
+
 impl<K, V> XmlWrite for Map<K, V>
 where
     K: AsRef<str>,
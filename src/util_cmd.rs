@@ -1,21 +1,156 @@
-use std::fs::File;
-use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::collections::HashSet;
+use std::env;
+use std::fs;
 use std::str::FromStr;
+use std::time::Duration;
 
 use regex::Regex;
 
 use crate::app::App;
+use crate::music::{Key, Notation, Notations};
 use crate::prelude::*;
+use crate::project::{Project, Settings};
 use crate::util::sort_lexical_by;
 
+mod audit;
+pub use audit::audit;
+mod check_links;
+pub use check_links::{check_links, CheckLinksOpts, LinkCheck, LinkStatus};
+mod chord_suffixes;
+pub use chord_suffixes::{chord_suffixes, ChordSuffix};
+mod detect_key;
+pub use detect_key::{detect_key, DetectKeyOpts, SongKey};
+mod doctor;
+pub use doctor::{doctor, Check, CheckStatus};
+mod dump_ast;
+pub use dump_ast::{dump_ast, DumpAstOpts};
+mod grep;
+pub use grep::{grep, GrepMatch, GrepOpts};
+mod new_song;
+pub use new_song::{new_song, NewSong, NewSongOpts};
+mod render_song;
+pub use render_song::{render_song, RenderSongOpts, SongPreview};
+mod show_config;
+pub use show_config::show_config;
+mod spellcheck;
+pub use spellcheck::{
+    spellcheck, spellcheck_with, Dictionary, Misspelling, SpellcheckOpts, SpellcheckSettings,
+    WordList,
+};
+mod transpose;
+pub use transpose::{transpose, TransposeOpts};
+
 #[derive(clap::Parser)]
 pub enum UtilCmd {
-    /// Alphabetically sorts lines of a file in-place
+    /// Alphabetically sorts lines of a file in-place.
+    /// The sort is stable, lines with equal keys keep their order, lines without a key stay in place
     SortLines {
         /// Regular expression that extracts the sort key from each line via a capture group
         regex: String,
         /// The file whose lines to sort, in-place
         file: String,
+        /// Warn about keys found on more than one line, listing them
+        #[arg(long)]
+        unique: bool,
+    },
+    /// Creates a new song file from a skeleton template
+    NewSong {
+        /// Title of the song
+        title: String,
+        /// Artist of the song, added as a subtitle
+        #[arg(short, long)]
+        artist: Option<String>,
+        /// Path of the new file relative to the project directory, derived from the title by default
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+        /// Song skeleton template file in the templates directory, a built-in one is used by default
+        #[arg(short, long)]
+        template: Option<PathBuf>,
+    },
+    /// Prints the effective project configuration, annotated with where each value came from
+    ShowConfig,
+    /// Checks that URLs of links in songs are reachable
+    CheckLinks {
+        /// Timeout of each request in seconds
+        #[arg(long, default_value_t = 5)]
+        timeout: u64,
+        /// Only validate URL syntax, don't make any requests
+        #[arg(long)]
+        offline: bool,
+    },
+    /// Guesses keys of songs from their chords
+    DetectKey {
+        /// Song files to analyze
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+        /// Chord notation used in the songs: english, german, nashville, roman, or one from the project's [notations]
+        #[arg(short, long, default_value = "english")]
+        notation: String,
+        /// Insert a !key() directive into songs whose key is recognized unambiguously
+        #[arg(short, long)]
+        write: bool,
+    },
+    /// Checks the TeX distribution and the environment for common problems
+    Doctor,
+    /// Checks spelling of song texts against a hunspell dictionary
+    Spellcheck {
+        /// Dictionary language, eg. en_US or cs
+        #[arg(short, long, default_value = "en_US")]
+        lang: String,
+        /// File with additional accepted words, one per line
+        #[arg(short, long)]
+        dict: Option<PathBuf>,
+        /// Only warn about misspelled words, don't fail
+        #[arg(long)]
+        warn_only: bool,
+    },
+    /// Checks the book for structural problems, eg. references to missing choruses or missing images.
+    /// Fails if any errors are found
+    Audit,
+    /// Renders a single song with an output's template into a preview file, for template development.
+    /// TeX isn't run for PDF outputs, the TeX file is written instead
+    RenderSong {
+        /// Title of the song, or a glob pattern matching a single song
+        #[arg(short, long, value_name = "TITLE")]
+        song: String,
+        /// The output file as configured in bard.toml, eg. songbook.html
+        #[arg(short, long, value_name = "FILE")]
+        output: String,
+    },
+    /// Searches text of songs for a regular expression, prints matches with the file and line of each
+    Grep {
+        /// Regular expression to search for
+        regex: String,
+        /// Search chords instead of text, chords on the same line are separated by a space
+        #[arg(long)]
+        chords: bool,
+    },
+    /// Lists distinct chord suffixes in songs with their counts, eg. to set up `[chords.normalize]`
+    ChordSuffixes,
+    /// Prints the AST of songs in a file as JSON, without loading a project, for template development
+    DumpAst {
+        /// The song file to parse
+        file: PathBuf,
+        /// Chord notation used in the songs: english, german, nashville, roman, or one from the project's [notations]
+        #[arg(short, long, default_value = "english")]
+        notation: String,
+        /// Don't transpose chords, transposition extensions are kept in the AST instead
+        #[arg(long)]
+        no_xpose: bool,
+    },
+    /// Transposes chords of a song file in-place, the rest of the file is kept exactly as it is
+    Transpose {
+        /// The song file to transpose
+        file: PathBuf,
+        /// Number of semitones to transpose by, may be negative
+        #[arg(short, long, allow_negative_numbers = true)]
+        semitones: i32,
+        /// Chord notation used in the song: english, german, nashville, roman, or one from the project's [notations]
+        #[arg(short, long, default_value = "english")]
+        notation: String,
+        /// Chord notation to write the chords in, the song's notation by default
+        #[arg(long)]
+        to_notation: Option<String>,
     },
 }
 
@@ -24,71 +159,450 @@ impl UtilCmd {
         use UtilCmd::*;
 
         match self {
-            SortLines { regex, file } => {
-                if sort_lines(&regex, file)? == 0 {
+            SortLines {
+                regex,
+                file,
+                unique,
+            } => {
+                let sorted = sort_lines(&regex, file)?;
+                if sorted.count == 0 {
                     app.warning("sort-lines: No lines matched the regex.");
                 }
+                if unique && !sorted.duplicates.is_empty() {
+                    let keys: Vec<_> = sorted
+                        .duplicates
+                        .iter()
+                        .map(|key| format!("'{}'", key))
+                        .collect();
+                    app.warning(format!(
+                        "sort-lines: Duplicate keys found: {}",
+                        keys.join(", ")
+                    ));
+                }
+                Ok(())
+            }
+            NewSong {
+                title,
+                artist,
+                file,
+                template,
+            } => {
+                let cwd = env::current_dir().context("Could not read current directory")?;
+                let opts = NewSongOpts {
+                    title: &title,
+                    artist: artist.as_deref(),
+                    file: file.as_deref(),
+                    template: template.as_deref(),
+                };
+
+                let song = new_song(app, &cwd, &opts)?;
+                app.status("Created", format!("song file {:?}", song.path));
+                if song.added_to_project {
+                    app.indent("Song file added to the songs list in bard.toml.");
+                }
+                Ok(())
+            }
+            ShowConfig => {
+                let cwd = env::current_dir().context("Could not read current directory")?;
+                print!("{}", show_config(app, &cwd)?);
+                Ok(())
+            }
+            CheckLinks { timeout, offline } => {
+                let cwd = env::current_dir().context("Could not read current directory")?;
+                let opts = CheckLinksOpts {
+                    timeout: Duration::from_secs(timeout),
+                    offline,
+                };
+
+                let checks = check_links(app, &cwd, &opts)?;
+                for check in checks.iter() {
+                    println!("{}: {} ({})", check.song, check.url, check.status);
+                }
+
+                let broken = checks.iter().filter(|c| c.status.is_broken()).count();
+                if broken > 0 {
+                    bail!("{} of {} links are broken.", broken, checks.len());
+                }
+                app.success(format!("{} links checked", checks.len()));
+                Ok(())
+            }
+            DetectKey {
+                files,
+                notation,
+                write,
+            } => {
+                let notations = project_notations(app)?;
+                let opts = DetectKeyOpts {
+                    files: &files,
+                    notation: parse_notation(&notations, &notation)?,
+                    notations,
+                    write,
+                };
+
+                let name = |key: Key| key.name(&opts.notation);
+                for key in detect_key(app, &opts)? {
+                    let guess = match key.guess {
+                        Some(guess) if guess.is_ambiguous() => format!(
+                            "ambiguous, {} or {} (confidence {:.2})",
+                            name(guess.key),
+                            name(guess.alternative.unwrap()),
+                            guess.confidence
+                        ),
+                        Some(guess) => {
+                            format!("{} (confidence {:.2})", name(guess.key), guess.confidence)
+                        }
+                        None => "no chords".to_string(),
+                    };
+                    print!("{}: {}: {}", key.file.display(), key.song, guess);
+                    if let Some(existing) = key.existing {
+                        print!(", the song has !key({})", existing);
+                    }
+                    if key.written {
+                        print!(", written");
+                    }
+                    println!();
+                }
+                Ok(())
+            }
+            Doctor => {
+                let cwd = env::current_dir().context("Could not read current directory")?;
+                let checks = doctor(app, &cwd)?;
+                for check in checks.iter() {
+                    println!("[{}] {}: {}", check.status, check.name, check.detail);
+                    if let Some(hint) = check.hint {
+                        println!("       {}", hint);
+                    }
+                }
+
+                let failed = checks
+                    .iter()
+                    .filter(|c| c.status == CheckStatus::Fail)
+                    .count();
+                if failed > 0 {
+                    bail!("{} of {} checks failed.", failed, checks.len());
+                }
+                app.success(format!("{} checks done", checks.len()));
+                Ok(())
+            }
+            Spellcheck {
+                lang,
+                dict,
+                warn_only,
+            } => {
+                let cwd = env::current_dir().context("Could not read current directory")?;
+                let opts = SpellcheckOpts {
+                    lang: &lang,
+                    dict: dict.as_deref(),
+                };
+
+                let misspellings = spellcheck(app, &cwd, &opts)?;
+                for m in misspellings.iter() {
+                    print!("{}:{}: {}", m.file.display(), m.line, m.word);
+                    if !m.suggestions.is_empty() {
+                        print!(" (suggestions: {})", m.suggestions.join(", "));
+                    }
+                    println!();
+                }
+
+                if misspellings.is_empty() {
+                    app.success("No misspelled words");
+                } else if warn_only {
+                    app.warning(format!("{} misspelled words found.", misspellings.len()));
+                } else {
+                    bail!("{} misspelled words found.", misspellings.len());
+                }
+                Ok(())
+            }
+            Audit => {
+                let cwd = env::current_dir().context("Could not read current directory")?;
+                let findings = audit(app, &cwd)?;
+
+                let mut checks = findings.iter().map(|f| f.check).collect::<Vec<_>>();
+                checks.dedup();
+                for check in checks {
+                    let group: Vec<_> = findings.iter().filter(|f| f.check == check).collect();
+                    println!("{} ({}):", check, group.len());
+                    for f in group {
+                        println!(
+                            "  {}:{}: {}: '{}': {}",
+                            f.location.file.display(),
+                            f.location.line,
+                            f.severity,
+                            f.song,
+                            f.message
+                        );
+                    }
+                }
+
+                let errors = findings.iter().filter(|f| f.is_error()).count();
+                let warnings = findings.len() - errors;
+                if errors > 0 {
+                    bail!("{} errors and {} warnings found.", errors, warnings);
+                } else if warnings > 0 {
+                    app.warning(format!("{} warnings found.", warnings));
+                } else {
+                    app.success("No problems found");
+                }
+                Ok(())
+            }
+            RenderSong { song, output } => {
+                let cwd = env::current_dir().context("Could not read current directory")?;
+                let opts = RenderSongOpts {
+                    song: &song,
+                    output: &output,
+                };
+
+                let preview = render_song(app, &cwd, &opts)?;
+                app.status("Written", format!("preview {:?}", preview.file));
+                Ok(())
+            }
+            Grep { regex, chords } => {
+                let cwd = env::current_dir().context("Could not read current directory")?;
+                let opts = GrepOpts {
+                    regex: &regex,
+                    chords,
+                };
+
+                let matches = grep(app, &cwd, &opts)?;
+                for m in matches.iter() {
+                    println!("{}:{}: {}: {}", m.file.display(), m.line, m.song, m.text);
+                }
+
+                if matches.is_empty() {
+                    app.warning("No matches found.");
+                } else {
+                    app.success(format!("{} matches found", matches.len()));
+                }
+                Ok(())
+            }
+            ChordSuffixes => {
+                let cwd = env::current_dir().context("Could not read current directory")?;
+                let suffixes = chord_suffixes(app, &cwd)?;
+                for s in suffixes.iter() {
+                    println!("{}: {} (eg. {})", s.suffix, s.count, s.example);
+                }
+
+                app.success(format!("{} distinct suffixes found", suffixes.len()));
+                Ok(())
+            }
+            DumpAst {
+                file,
+                notation,
+                no_xpose,
+            } => {
+                let notations = project_notations(app)?;
+                let opts = DumpAstOpts {
+                    file: &file,
+                    notation: parse_notation(&notations, &notation)?,
+                    xp_disabled: no_xpose,
+                };
+
+                println!("{}", dump_ast(app, &opts)?);
+                Ok(())
+            }
+            Transpose {
+                file,
+                semitones,
+                notation,
+                to_notation,
+            } => {
+                let notations = project_notations(app)?;
+                let opts = TransposeOpts {
+                    file: &file,
+                    semitones,
+                    notation: parse_notation(&notations, &notation)?,
+                    to_notation: to_notation
+                        .as_deref()
+                        .map(|name| parse_notation(&notations, name))
+                        .transpose()?,
+                };
+
+                let count = transpose(&opts)?;
+                app.status("Transposed", format!("{} chord spans in {:?}", count, file));
                 Ok(())
             }
         }
     }
 }
 
-#[derive(Debug)]
-struct Line {
-    line: String,
-    key: Option<String>,
+/// User-defined notations of the project in the current directory, if any,
+/// so that they can be used in `--notation` arguments like the built-in ones.
+fn project_notations(app: &App) -> Result<Notations> {
+    let cwd = env::current_dir().context("Could not read current directory")?;
+    match Project::locate(app, &cwd) {
+        Ok((project_file, project_dir)) => {
+            let settings = Settings::from_file(&project_file, &project_dir)?;
+            Ok(settings.notations)
+        }
+        // Not in a project, only built-in notations are available
+        Err(_) => Ok(Notations::default()),
+    }
 }
 
-fn line_read(mut lines: Vec<Line>, line: io::Result<String>, regex: &Regex) -> Result<Vec<Line>> {
-    let line = line?;
-    let key = if let Some(caps) = regex.captures(&line) {
-        caps.get(1)
-            .map(|m| Some(m.as_str().to_owned()))
-            .with_context(|| {
-                format!(
-                    "No capture group in regex: '{}', the sort key has to be in a capture group",
-                    regex
-                )
-            })?
-    } else {
-        None
-    };
+/// Parse the `--notation` argument, looking it up in `notations` like the `notation` setting.
+fn parse_notation(notations: &Notations, notation: &str) -> Result<Notation> {
+    notations.get(notation).ok_or_else(|| {
+        let mut choices = vec!["english", "german", "nashville", "roman"];
+        choices.extend(notations.iter().map(|table| table.name()));
+        anyhow!(
+            "Unknown notation '{}', possible choices: {}",
+            notation,
+            choices.join(", ")
+        )
+    })
+}
 
-    lines.push(Line { line, key });
+/// The result of `sort_lines()`.
+#[derive(Debug)]
+pub struct SortedLines {
+    /// Number of lines that had a sort key.
+    pub count: usize,
+    /// Keys found on more than one line, in the order they are first repeated.
+    pub duplicates: Vec<String>,
+}
 
-    Ok(lines)
+#[derive(Debug)]
+struct Line<'a> {
+    line: &'a str,
+    key: Option<&'a str>,
 }
 
-pub fn sort_lines(regex: &str, path: impl Into<PathBuf>) -> Result<usize> {
-    let regex = Regex::from_str(regex).with_context(|| format!("Invalid regex: '{}'", regex))?;
+fn line_key<'a>(line: &'a str, regex: &Regex) -> Result<Option<&'a str>> {
+    let caps = match regex.captures(line) {
+        Some(caps) => caps,
+        None => return Ok(None),
+    };
 
-    let path = path.into();
-    let file = File::open(&path).with_context(|| format!("Could not open file {:?}", path))?;
-    let reader = BufReader::new(file);
+    caps.get(1).map(|m| Some(m.as_str())).with_context(|| {
+        format!(
+            "No capture group in regex: '{}', the sort key has to be in a capture group",
+            regex
+        )
+    })
+}
 
-    let mut lines = reader
-        .lines()
-        .try_fold(Vec::new(), |lines, line| line_read(lines, line, &regex))
-        .with_context(|| format!("Could not sort file {:?}", path))?;
+/// Sort lines of `text` by keys extracted with `regex`, returns the sorted text.
+///
+/// The sort is stable, lines with equal keys keep their original order.
+/// Lines without a key stay exactly in place, they split the text into runs
+/// which are sorted separately. Line endings stay in place as well, so CRLF line endings
+/// and a missing newline at the end of the text are preserved.
+fn sort_text(regex: &Regex, text: &str) -> Result<(String, SortedLines)> {
+    let mut lines = vec![];
+    let mut endings = vec![];
+    for line in text.split_inclusive('\n') {
+        let ending = if line.ends_with("\r\n") {
+            "\r\n"
+        } else if line.ends_with('\n') {
+            "\n"
+        } else {
+            ""
+        };
+        let line = &line[..line.len() - ending.len()];
+        let key = line_key(line, regex)?;
+        lines.push(Line { line, key });
+        endings.push(ending);
+    }
+
+    let mut duplicates: Vec<String> = vec![];
+    let mut keys = HashSet::new();
+    for key in lines.iter().filter_map(|line| line.key) {
+        if !keys.insert(key) && !duplicates.iter().any(|dup| dup == key) {
+            duplicates.push(key.to_string());
+        }
+    }
 
     let count = lines
         .as_mut_slice()
         .split_mut(|line| line.key.is_none())
         .map(|slice| {
-            sort_lexical_by(slice, |line| line.key.as_ref().unwrap());
+            sort_lexical_by(slice, |line| line.key.unwrap());
             slice.len()
         })
         .sum();
 
-    let write_err = || format!("Could not write file {:?}", path);
-    let mut file = File::create(&path)
-        .map(BufWriter::new)
-        .with_context(write_err)?;
-    for line in &lines[..] {
-        writeln!(&mut file, "{}", &line.line).with_context(write_err)?;
+    let sorted = lines.iter().zip(endings).fold(
+        String::with_capacity(text.len()),
+        |mut sorted, (line, ending)| {
+            sorted.push_str(line.line);
+            sorted.push_str(ending);
+            sorted
+        },
+    );
+
+    Ok((sorted, SortedLines { count, duplicates }))
+}
+
+/// Sort lines of the file at `path` in-place by keys extracted with `regex`,
+/// the key is the first capture group of the regex. See `sort_text()` for how
+/// lines with equal keys and lines without a key are handled.
+pub fn sort_lines(regex: &str, path: impl Into<PathBuf>) -> Result<SortedLines> {
+    let regex = Regex::from_str(regex).with_context(|| format!("Invalid regex: '{}'", regex))?;
+
+    let path = path.into();
+    let text =
+        fs::read_to_string(&path).with_context(|| format!("Could not open file {:?}", path))?;
+    let (sorted, result) =
+        sort_text(&regex, &text).with_context(|| format!("Could not sort file {:?}", path))?;
+
+    fs::write(&path, sorted.as_bytes())
+        .with_context(|| format!("Could not write file {:?}", path))?;
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sort(text: &str) -> (String, SortedLines) {
+        let regex = Regex::new(r#"key=(\S+)"#).unwrap();
+        sort_text(&regex, text).unwrap()
+    }
+
+    #[test]
+    fn sort_text_duplicates() {
+        let (sorted, result) = sort("key=b 1\nkey=a 1\nkey=b 2\nkey=c\nkey=a 2\nkey=b 3\n");
+        assert_eq!(
+            sorted,
+            "key=a 1\nkey=a 2\nkey=b 1\nkey=b 2\nkey=b 3\nkey=c\n"
+        );
+        assert_eq!(result.count, 6);
+        assert_eq!(result.duplicates, ["b", "a"]);
+
+        let (_, result) = sort("key=b\nkey=a\nkey=c\n");
+        assert!(result.duplicates.is_empty());
+    }
+
+    #[test]
+    fn sort_text_keyless() {
+        let (sorted, result) = sort("start\n\nkey=b\nkey=a\nmiddle\nkey=d\nkey=c\nend\n\n");
+        assert_eq!(
+            sorted,
+            "start\n\nkey=a\nkey=b\nmiddle\nkey=c\nkey=d\nend\n\n"
+        );
+        assert_eq!(result.count, 4);
+
+        // Equal keys in separate runs are still reported as duplicates
+        let (sorted, result) = sort("key=b\nkey=a\nxxx\nkey=a\n");
+        assert_eq!(sorted, "key=a\nkey=b\nxxx\nkey=a\n");
+        assert_eq!(result.duplicates, ["a"]);
+
+        let (sorted, result) = sort("xxx\nyyy\n");
+        assert_eq!(sorted, "xxx\nyyy\n");
+        assert_eq!(result.count, 0);
     }
-    file.flush().with_context(write_err)?;
 
-    Ok(count)
+    #[test]
+    fn sort_text_line_endings() {
+        let (sorted, _) = sort("xxx\r\nkey=b\r\nkey=a\r\n");
+        assert_eq!(sorted, "xxx\r\nkey=a\r\nkey=b\r\n");
+
+        // The last line without a newline stays the last one in the file
+        let (sorted, _) = sort("key=b\nkey=c\nkey=a");
+        assert_eq!(sorted, "key=a\nkey=b\nkey=c");
+
+        let (sorted, _) = sort("");
+        assert_eq!(sorted, "");
+    }
 }
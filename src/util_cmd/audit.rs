@@ -0,0 +1,14 @@
+//! The `audit` utility, checks the book for structural problems before printing.
+
+use crate::app::App;
+use crate::book::audit::{self, Finding};
+use crate::prelude::*;
+use crate::project::Project;
+
+/// Load the project found at `cwd` (or its parents, see `Project::locate()`)
+/// and run the checks of `book::audit` over its songs. No outputs are rendered.
+pub fn audit(app: &App, cwd: &Path) -> Result<Vec<Finding>> {
+    let project = Project::new(app, cwd)?;
+    app.status("Auditing", format!("{} songs", project.book.songs.len()));
+    Ok(audit::audit(&project.book))
+}
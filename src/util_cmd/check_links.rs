@@ -0,0 +1,175 @@
+//! The `check-links` utility, validates URLs of links in songs.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use url::{ParseError, Url};
+
+use crate::app::App;
+use crate::prelude::*;
+use crate::project::Project;
+
+/// Max number of requests in flight.
+const MAX_CONCURRENCY: usize = 8;
+
+pub struct CheckLinksOpts {
+    /// Timeout of each request.
+    pub timeout: Duration,
+    /// Only validate URL syntax, don't make any requests.
+    pub offline: bool,
+}
+
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum LinkStatus {
+    /// The server responded with a success status (after redirects).
+    Ok(u16),
+    /// The URL is syntactically valid, only reported in offline mode.
+    Valid,
+    /// Not checked, eg. a relative link or a `mailto:` link.
+    Skipped(String),
+    /// The URL is malformed.
+    Invalid(String),
+    /// The server responded with an error status.
+    Status(u16),
+    /// The request failed, eg. the host could not be resolved or the request timed out.
+    Failed(String),
+}
+
+impl LinkStatus {
+    pub fn is_broken(&self) -> bool {
+        matches!(
+            self,
+            Self::Invalid(..) | Self::Status(..) | Self::Failed(..)
+        )
+    }
+}
+
+impl fmt::Display for LinkStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ok(code) => write!(f, "OK {}", code),
+            Self::Valid => write!(f, "valid"),
+            Self::Skipped(reason) => write!(f, "skipped: {}", reason),
+            Self::Invalid(err) => write!(f, "invalid URL: {}", err),
+            Self::Status(code) => write!(f, "HTTP {}", code),
+            Self::Failed(err) => write!(f, "failed: {}", err),
+        }
+    }
+}
+
+/// Result of checking one link. Links don't carry source positions,
+/// so they're identified by the song they're in.
+#[derive(Clone, Debug)]
+pub struct LinkCheck {
+    pub song: String,
+    pub url: String,
+    pub status: LinkStatus,
+}
+
+/// Parse `url`, returns the URL if it should be requested, otherwise the final status.
+fn parse_url(url: &str) -> Result<Url, LinkStatus> {
+    match Url::parse(url) {
+        Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => Ok(parsed),
+        Ok(parsed) => Err(LinkStatus::Skipped(format!("{} link", parsed.scheme()))),
+        Err(ParseError::RelativeUrlWithoutBase) => Err(LinkStatus::Skipped("relative link".into())),
+        Err(err) => Err(LinkStatus::Invalid(err.to_string())),
+    }
+}
+
+/// Request `url` with HEAD, falling back to GET if the server refuses HEAD.
+fn request(agent: &ureq::Agent, url: &Url) -> LinkStatus {
+    let status = |res: Result<ureq::Response, ureq::Error>| match res {
+        Ok(res) => LinkStatus::Ok(res.status()),
+        Err(ureq::Error::Status(code, _)) => LinkStatus::Status(code),
+        Err(ureq::Error::Transport(err)) => LinkStatus::Failed(err.to_string()),
+    };
+
+    // Some servers don't implement HEAD or respond with an error to it
+    match status(agent.request_url("HEAD", url).call()) {
+        LinkStatus::Status(_) => status(agent.request_url("GET", url).call()),
+        other => other,
+    }
+}
+
+/// Check `urls` using a bounded number of worker threads.
+/// Results are received on the calling thread, which handles interruption.
+fn check_urls(
+    app: &App,
+    urls: BTreeSet<Url>,
+    timeout: Duration,
+) -> Result<BTreeMap<Url, LinkStatus>> {
+    let agent = ureq::AgentBuilder::new().timeout(timeout).build();
+    let num_workers = MAX_CONCURRENCY.min(urls.len());
+    let queue = Arc::new(Mutex::new(urls.into_iter()));
+    let interrupt = app.interrupt_flag();
+    let (tx, rx) = mpsc::channel();
+
+    for _ in 0..num_workers {
+        let agent = agent.clone();
+        let queue = queue.clone();
+        let tx = tx.clone();
+        thread::spawn(move || loop {
+            let url = match queue.lock().unwrap().next() {
+                Some(url) if !interrupt.interrupted() => url,
+                _ => break,
+            };
+            let status = request(&agent, &url);
+            if tx.send((url, status)).is_err() {
+                break;
+            }
+        });
+    }
+    drop(tx);
+
+    let mut statuses = BTreeMap::new();
+    while let Some((url, status)) = interrupt.channel_recv(&rx)? {
+        statuses.insert(url, status);
+    }
+
+    // Workers stop early when interrupted, the results may be incomplete
+    app.check_interrupted()?;
+    Ok(statuses)
+}
+
+/// Load the project found at `cwd` (or its parents, see `Project::locate()`)
+/// and check the URLs of all links in its songs. Each distinct URL is only requested once.
+pub fn check_links(app: &App, cwd: &Path, opts: &CheckLinksOpts) -> Result<Vec<LinkCheck>> {
+    let project = Project::new(app, cwd)?;
+
+    let links: Vec<_> = project
+        .book
+        .iter_links()
+        .map(|(song, link)| (song, link, parse_url(&link.url)))
+        .collect();
+
+    let urls: BTreeSet<_> = links
+        .iter()
+        .filter_map(|(_, _, url)| url.as_ref().ok().cloned())
+        .collect();
+    let statuses = if opts.offline || urls.is_empty() {
+        BTreeMap::new()
+    } else {
+        app.status("Checking", format!("{} links", urls.len()));
+        check_urls(app, urls, opts.timeout)?
+    };
+
+    Ok(links
+        .into_iter()
+        .map(|(song, link, url)| {
+            let status = match url {
+                Ok(_) if opts.offline => LinkStatus::Valid,
+                Ok(url) => statuses[&url].clone(),
+                Err(status) => status,
+            };
+            LinkCheck {
+                song: song.title.to_string(),
+                url: link.url.to_string(),
+                status,
+            }
+        })
+        .collect())
+}
@@ -0,0 +1,64 @@
+//! The `chord-suffixes` utility, lists distinct suffixes of chords in songs,
+//! to help with setting up `[chords.normalize]`.
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+use crate::app::App;
+use crate::book::Inline;
+use crate::music;
+use crate::prelude::*;
+use crate::project::Project;
+
+/// A suffix found by `chord_suffixes()`.
+#[derive(Debug)]
+pub struct ChordSuffix {
+    pub suffix: String,
+    /// Number of chords with the suffix.
+    pub count: usize,
+    /// The first chord found with the suffix, eg. `Amin7` for `min7`.
+    pub example: String,
+}
+
+/// Load the project found at `cwd` (or its parents, see `Project::locate()`)
+/// and collect distinct non-empty suffixes of its chords, the most common first.
+///
+/// Chords are taken as they are in the book, ie. after transposition and `[chords.normalize]`,
+/// so that suffixes left to normalize can be found once the map is set up.
+/// Chords that aren't recognized in the song's notation are skipped.
+pub fn chord_suffixes(app: &App, cwd: &Path) -> Result<Vec<ChordSuffix>> {
+    let project = Project::new(app, cwd)?;
+    app.status("Scanning", format!("{} songs", project.songs().len()));
+
+    let mut suffixes: Vec<ChordSuffix> = vec![];
+    let mut indices = HashMap::new();
+    for song in project.songs() {
+        song.visit_inlines(|inline| {
+            let chord = match inline {
+                Inline::Chord(chord) => chord,
+                _ => return,
+            };
+
+            for parts in music::split_chords(&chord.chord, &song.notation) {
+                let parts = match parts {
+                    Ok(parts) if !parts.suffix.is_empty() => parts,
+                    _ => continue,
+                };
+
+                let i = *indices.entry(parts.suffix.to_string()).or_insert_with(|| {
+                    suffixes.push(ChordSuffix {
+                        suffix: parts.suffix.to_string(),
+                        count: 0,
+                        example: format!("{}{}", parts.root, parts.suffix),
+                    });
+                    suffixes.len() - 1
+                });
+                suffixes[i].count += 1;
+            }
+        });
+    }
+
+    // Stable, suffixes with equal counts stay in order of first appearance
+    suffixes.sort_by_key(|s| Reverse(s.count));
+    Ok(suffixes)
+}
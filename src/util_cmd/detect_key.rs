@@ -0,0 +1,144 @@
+//! The `detect-key` utility, guesses keys of songs from their chords.
+
+use std::fs;
+
+use crate::app::App;
+use crate::music::{self, KeyGuess, Notation, Notations};
+use crate::parser::{Diagnostic, Parser, ParserConfig};
+use crate::prelude::*;
+
+/// Options for `detect_key()`, these correspond to the CLI arguments.
+#[derive(Debug)]
+pub struct DetectKeyOpts<'a> {
+    pub files: &'a [PathBuf],
+    /// Chord notation of the songs.
+    pub notation: Notation,
+    /// User-defined notations available to notation extensions, see `ParserConfig::notations()`.
+    pub notations: Notations,
+    /// Insert a `!key()` directive into songs whose key is guessed unambiguously.
+    pub write: bool,
+}
+
+/// Result of `detect_key()` for one song.
+#[derive(Debug)]
+pub struct SongKey {
+    pub file: PathBuf,
+    pub song: String,
+    /// `None` if the song has no chords.
+    pub guess: Option<KeyGuess>,
+    /// Key from a `!key()` directive already in the song, if any.
+    pub existing: Option<String>,
+    /// The guessed key was written into the song file.
+    pub written: bool,
+}
+
+/// Guess keys of the songs in `opts.files` and with `opts.write`, write them into the files,
+/// songs which already have a key and ambiguous guesses are left as they are.
+pub fn detect_key(app: &App, opts: &DetectKeyOpts) -> Result<Vec<SongKey>> {
+    let mut keys = vec![];
+    for path in opts.files {
+        app.check_interrupted()?;
+        keys.extend(detect_file(app, path, opts)?);
+    }
+
+    Ok(keys)
+}
+
+fn detect_file(app: &App, path: &Path, opts: &DetectKeyOpts) -> Result<Vec<SongKey>> {
+    let source =
+        fs::read_to_string(path).with_context(|| format!("Could not read file {:?}", path))?;
+
+    // Transposition is disabled so that the key is guessed from the chords as written
+    let config = ParserConfig::new(opts.notation.clone(), true)
+        .xp_disabled(true)
+        .include_sources(opts.write)
+        .notations(opts.notations.clone());
+    let diag_sink = |diag: Diagnostic| app.parser_diag(diag);
+    let songs = Parser::new(&source, path, config, diag_sink)
+        .parse()
+        .map_err(|_| anyhow!("Could not parse file {:?}", path))?;
+
+    let mut keys = vec![];
+    let mut output = String::with_capacity(source.len() + 16 * songs.len());
+    for song in songs.iter() {
+        let chords: Vec<_> = song
+            .chords()
+            .iter()
+            .flat_map(|chord| music::chord_roots(&chord.chord, &song.notation))
+            .collect();
+        let guess = music::detect_key(&chords);
+        let existing = song.key.as_ref().map(|key| key.to_string());
+
+        let song_source = song.source.as_deref().unwrap_or("");
+        let written = match guess {
+            Some(guess) if opts.write && existing.is_none() && !guess.is_ambiguous() => {
+                let key = guess.key.name(&song.notation);
+                output.push_str(&insert_key(song_source, &key));
+                true
+            }
+            _ => {
+                output.push_str(song_source);
+                false
+            }
+        };
+
+        keys.push(SongKey {
+            file: path.to_owned(),
+            song: song.title.to_string(),
+            guess,
+            existing,
+            written,
+        });
+    }
+
+    if keys.iter().any(|key| key.written) {
+        fs::write(path, output).with_context(|| format!("Could not write file {:?}", path))?;
+    }
+
+    Ok(keys)
+}
+
+/// Insert a `!key()` directive into the `source` of a song, right after its title and subtitles.
+fn insert_key(source: &str, key: &str) -> String {
+    let mut at = 0;
+    let mut title_seen = false;
+    for line in source.split_inclusive('\n') {
+        let trimmed = line.trim();
+        if !title_seen {
+            title_seen = trimmed == "#" || trimmed.starts_with("# ");
+        } else if !trimmed.is_empty() && !trimmed.starts_with("## ") {
+            break;
+        }
+        at += line.len();
+    }
+
+    if !title_seen {
+        // The song has no title, insert at the start
+        at = 0;
+    }
+
+    let (head, tail) = source.split_at(at);
+    let separator = if head.is_empty() || head.ends_with('\n') {
+        ""
+    } else {
+        "\n"
+    };
+    format!("{}{}!key({})\n\n{}", head, separator, key, tail)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_key_after_title() {
+        let source = "# Song\n## Artist\n\n1. `G`Lyrics\n\n";
+        assert_eq!(
+            insert_key(source, "G"),
+            "# Song\n## Artist\n\n!key(G)\n\n1. `G`Lyrics\n\n"
+        );
+
+        assert_eq!(insert_key("# Song", "Am"), "# Song\n!key(Am)\n\n");
+        assert_eq!(insert_key("`C`Lyrics\n", "C"), "!key(C)\n\n`C`Lyrics\n");
+    }
+}
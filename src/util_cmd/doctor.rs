@@ -0,0 +1,233 @@
+//! The `doctor` utility, checks the TeX distribution and the environment bard runs in.
+
+use std::env;
+use std::fmt;
+use std::fs;
+
+use crate::app::App;
+use crate::prelude::*;
+use crate::project::Project;
+use crate::render::tex_tools::{TexDistro, TexTools};
+use crate::watch::Watch;
+
+const HINT_INSTALL: &str =
+    "Install XeLaTeX or Tectonic, see https://bard.md/book/install.html, or set the `tex` field in bard.toml or the BARD_TEX variable.";
+const HINT_SMOKE_TEST: &str =
+    "The TeX distribution may be missing packages used by the default template, such as fontspec. Tectonic needs network access to download them on the first run.";
+const HINT_FONTS: &str =
+    "The default PDF template reads fonts from the output/fonts directory, a new project created with `bard init` contains the default ones. Without fonts.tex, the default template falls back to the standard fonts.";
+const HINT_WATCH: &str =
+    "`bard make --watch` won't work. On Linux, the inotify limits may need raising, see fs.inotify.max_user_watches and fs.inotify.max_user_instances.";
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+impl fmt::Display for CheckStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Pass => write!(f, "pass"),
+            Self::Warn => write!(f, "warn"),
+            Self::Fail => write!(f, "FAIL"),
+        }
+    }
+}
+
+/// Result of one check of `doctor()`.
+#[derive(Clone, Debug)]
+pub struct Check {
+    pub name: String,
+    pub status: CheckStatus,
+    pub detail: String,
+    /// What to do about a warning or a failure.
+    pub hint: Option<&'static str>,
+}
+
+impl Check {
+    fn pass(name: impl Into<String>, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            status: CheckStatus::Pass,
+            detail: detail.into(),
+            hint: None,
+        }
+    }
+
+    fn warn(name: impl Into<String>, detail: impl Into<String>, hint: &'static str) -> Self {
+        Self {
+            name: name.into(),
+            status: CheckStatus::Warn,
+            detail: detail.into(),
+            hint: Some(hint),
+        }
+    }
+
+    fn fail(name: impl Into<String>, detail: impl Into<String>, hint: &'static str) -> Self {
+        Self {
+            name: name.into(),
+            status: CheckStatus::Fail,
+            detail: detail.into(),
+            hint: Some(hint),
+        }
+    }
+}
+
+/// Run all the checks, project checks are only done if `cwd` is in a project.
+pub fn doctor(app: &App, cwd: &Path) -> Result<Vec<Check>> {
+    let mut checks = vec![];
+
+    for distro in [
+        TexDistro::Xelatex,
+        TexDistro::Tectonic,
+        TexDistro::TectonicEmbedded,
+    ] {
+        app.check_interrupted()?;
+        let name = format!("TeX distribution {}", distro);
+        checks.push(match TexTools::probe_distro(app, distro) {
+            Ok(version) => Check::pass(name, version),
+            Err(err) => Check::warn(name, format!("{:#}", err), HINT_INSTALL),
+        });
+    }
+
+    let project = match Project::locate(app, cwd) {
+        Ok((project_file, project_dir)) => {
+            // Not located again, which would eg. report a project in a parent directory twice
+            let project = Project::from_file(app, project_file, project_dir);
+            match project {
+                Ok(project) => {
+                    checks.push(Check::pass("Project", format!("{:?}", project.project_dir)));
+                    Some(project)
+                }
+                Err(err) => {
+                    checks.push(Check::fail(
+                        "Project",
+                        format!("{:#}", err),
+                        "Fix the error in the project, then run the checks again.",
+                    ));
+                    None
+                }
+            }
+        }
+        Err(err) => {
+            checks.push(Check::warn(
+                "Project",
+                format!("{:#}", err),
+                "Run the checks in a project directory to also check its fonts and output directory.",
+            ));
+            None
+        }
+    };
+
+    check_tex(app, project.as_ref(), &mut checks)?;
+
+    if let Some(project) = project.as_ref() {
+        checks.push(check_fonts(project));
+        checks.push(check_output_dir(project));
+    }
+
+    let watch_dir = project
+        .as_ref()
+        .map(|p| p.project_dir.as_path())
+        .unwrap_or(cwd);
+    checks.push(match Watch::probe(watch_dir) {
+        Ok(()) => Check::pass("File watching", "available"),
+        Err(err) => Check::warn("File watching", format!("{:#}", err), HINT_WATCH),
+    });
+
+    Ok(checks)
+}
+
+/// Select the TeX distribution the same way `bard make` does and compile a test document with it.
+fn check_tex(app: &App, project: Option<&Project>, checks: &mut Vec<Check>) -> Result<()> {
+    let from_settings = project.and_then(|p| p.settings.tex.as_ref());
+    if let Err(err) = TexTools::initialize(app, from_settings) {
+        checks.push(Check::fail(
+            "Selected TeX distribution",
+            format!("{:#}", err),
+            HINT_INSTALL,
+        ));
+        return Ok(());
+    }
+
+    let tex_tools = TexTools::get();
+    if tex_tools.config().distro() == TexDistro::None {
+        checks.push(Check::warn(
+            "Selected TeX distribution",
+            "none, TeX is disabled",
+            "PDF outputs only produce TeX files.",
+        ));
+        return Ok(());
+    }
+    checks.push(Check::pass(
+        "Selected TeX distribution",
+        tex_tools.config().to_string(),
+    ));
+
+    app.check_interrupted()?;
+    checks.push(match tex_tools.smoke_test(app, &env::temp_dir()) {
+        Ok(()) => Check::pass("TeX smoke test", "a test document was compiled"),
+        Err(err) => Check::fail("TeX smoke test", format!("{:#}", err), HINT_SMOKE_TEST),
+    });
+
+    Ok(())
+}
+
+/// Check the fonts used by the default PDF template are in the output directory and readable.
+fn check_fonts(project: &Project) -> Check {
+    const NAME: &str = "Fonts";
+
+    let fonts_dir = project.settings.dir_output().join("fonts");
+    let fonts_tex = fonts_dir.join("fonts.tex");
+    let fonts_tex = match fs::read_to_string(&fonts_tex) {
+        Ok(content) => content,
+        Err(err) => {
+            let detail = format!("Could not read {:?}: {}", fonts_tex, err);
+            return Check::warn(NAME, detail, HINT_FONTS);
+        }
+    };
+
+    let mut num_fonts = 0;
+    for family in ["BardSerif", "BardSans"] {
+        if !fonts_tex.contains(family) {
+            // Fonts customized in fonts.tex
+            continue;
+        }
+
+        for style in ["Regular", "Bold", "Italic", "BoldItalic"] {
+            let path = fonts_dir.join(format!("{}-{}.ttf", family, style));
+            if let Err(err) = fs::File::open(&path) {
+                let detail = format!("Could not read {:?}: {}", path, err);
+                return Check::warn(NAME, detail, HINT_FONTS);
+            }
+            num_fonts += 1;
+        }
+    }
+
+    Check::pass(NAME, format!("{} default fonts found", num_fonts))
+}
+
+/// Check the output directory can be written to, by writing and removing a file.
+fn check_output_dir(project: &Project) -> Check {
+    const NAME: &str = "Output directory";
+
+    let dir = project.settings.dir_output();
+    if !dir.exists() {
+        return Check::pass(NAME, format!("{:?} will be created", dir));
+    }
+
+    let probe = dir.join(".bard-doctor");
+    match fs::write(&probe, b"") {
+        Ok(()) => {
+            let _ = fs::remove_file(&probe);
+            Check::pass(NAME, format!("{:?} is writable", dir))
+        }
+        Err(err) => Check::fail(
+            NAME,
+            format!("Could not write to {:?}: {}", dir, err),
+            "Check permissions of the output directory.",
+        ),
+    }
+}
@@ -0,0 +1,36 @@
+//! The `dump-ast` utility, prints the AST of a single song file for template development and debugging.
+
+use std::fs;
+
+use crate::app::App;
+use crate::music::Notation;
+use crate::parser::{Diagnostic, Parser, ParserConfig};
+use crate::prelude::*;
+
+/// Options for `dump_ast()`, these correspond to the CLI arguments.
+#[derive(Debug)]
+pub struct DumpAstOpts<'a> {
+    pub file: &'a Path,
+    /// Chord notation of the songs.
+    pub notation: Notation,
+    /// Disable transposition, so that `Inline::Transpose` nodes are kept and chords stay as written.
+    pub xp_disabled: bool,
+}
+
+/// Parse `opts.file` without a project and return its songs as pretty printed JSON,
+/// serialized the same way as in the JSON output.
+///
+/// Parser diagnostics are reported to the `app`.
+pub fn dump_ast(app: &App, opts: &DumpAstOpts) -> Result<String> {
+    let path = opts.file;
+    let source =
+        fs::read_to_string(path).with_context(|| format!("Could not read file {:?}", path))?;
+
+    let config = ParserConfig::new(opts.notation.clone(), true).xp_disabled(opts.xp_disabled);
+    let diag_sink = |diag: Diagnostic| app.parser_diag(diag);
+    let songs = Parser::new(&source, path, config, diag_sink)
+        .parse()
+        .map_err(|_| anyhow!("Could not parse file {:?}", path))?;
+
+    serde_json::to_string_pretty(&songs).context("Could not serialize the AST")
+}
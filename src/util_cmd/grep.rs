@@ -0,0 +1,81 @@
+//! The `grep` utility, searches text or chords of songs with a regex.
+
+use regex::Regex;
+
+use crate::app::App;
+use crate::book::{Inline, Song};
+use crate::prelude::*;
+use crate::project::Project;
+
+/// Options for `grep()`, these correspond to the CLI arguments.
+#[derive(Debug)]
+pub struct GrepOpts<'a> {
+    pub regex: &'a str,
+    /// Search chords instead of text, chords on a line are separated by a space.
+    pub chords: bool,
+}
+
+/// A match found by `grep()`.
+#[derive(Debug)]
+pub struct GrepMatch {
+    /// The song file, relative to the project directory.
+    pub file: PathBuf,
+    pub line: usize,
+    pub song: String,
+    /// The matched part of the line.
+    pub text: String,
+}
+
+/// Text or chords of `song` joined by source line, see `Song::visit_inlines()`.
+fn song_lines(song: &Song, chords: bool) -> Vec<(usize, String)> {
+    let mut lines: Vec<(usize, String)> = vec![];
+    song.visit_inlines(|inline| {
+        let (text, line) = match inline {
+            Inline::Text { text, line } if !chords => (text, *line),
+            Inline::Chord(chord) if chords => (&chord.chord, chord.line),
+            _ => return,
+        };
+
+        // Text without a line, eg. a soft break joined as a space, continues the previous line
+        let line = line
+            .map(|line| line as usize)
+            .or_else(|| lines.last().map(|(line, _)| *line))
+            .unwrap_or(song.location.line);
+        match lines.last_mut() {
+            Some((last, joined)) if *last == line => {
+                if chords {
+                    joined.push(' ');
+                }
+                joined.push_str(text);
+            }
+            _ => lines.push((line, text.to_string())),
+        }
+    });
+    lines
+}
+
+/// Load the project found at `cwd` (or its parents, see `Project::locate()`)
+/// and search the text of its songs for `opts.regex`, or their chords with `opts.chords`.
+///
+/// The text is searched line by line of the source files, so that phrases split by chords
+/// or emphasis are found, lines in snippets are attributed to the line of the reference.
+pub fn grep(app: &App, cwd: &Path, opts: &GrepOpts) -> Result<Vec<GrepMatch>> {
+    let regex =
+        Regex::new(opts.regex).with_context(|| format!("Invalid regex: '{}'", opts.regex))?;
+    let project = Project::with_positions(app, cwd)?;
+    app.status("Searching", format!("{} songs", project.songs().len()));
+
+    let mut matches = vec![];
+    for song in project.songs() {
+        for (line, text) in song_lines(song, opts.chords) {
+            matches.extend(regex.find_iter(&text).map(|m| GrepMatch {
+                file: song.location.file.clone(),
+                line,
+                song: song.title.to_string(),
+                text: m.as_str().to_string(),
+            }));
+        }
+    }
+
+    Ok(matches)
+}
@@ -0,0 +1,198 @@
+//! The `new-song` utility, creates a song file from a skeleton template.
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write as _};
+
+use handlebars::{no_escape, Handlebars};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::Serialize;
+
+use crate::app::App;
+use crate::prelude::*;
+use crate::project::{Project, Settings};
+use crate::util::slugify;
+
+/// The built-in song skeleton, used when no `--template` is given.
+const DEFAULT_SKELETON: &str = r#"# {{ title }}
+{{#if artist}}## {{ artist }}
+{{/if}}
+
+1.
+
+> `C`Chorus lyrics `G`go here...
+"#;
+
+static SONGS_ARRAY: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?m)^(songs\s*=\s*\[)([^\]]*)\]").unwrap());
+
+#[derive(Serialize, Debug)]
+struct SkeletonContext<'a> {
+    title: &'a str,
+    artist: Option<&'a str>,
+}
+
+/// Options for `new_song()`, these correspond to the CLI arguments.
+#[derive(Debug, Default)]
+pub struct NewSongOpts<'a> {
+    pub title: &'a str,
+    pub artist: Option<&'a str>,
+    /// Path of the new song file relative to the project directory.
+    pub file: Option<&'a Path>,
+    /// Skeleton template file relative to the templates directory.
+    pub template: Option<&'a Path>,
+}
+
+/// Result of `new_song()`.
+#[derive(Debug)]
+pub struct NewSong {
+    /// Path of the newly created song file.
+    pub path: PathBuf,
+    /// Whether the song file was appended to the `songs` list in `bard.toml`.
+    pub added_to_project: bool,
+}
+
+fn render_skeleton(skeleton: &str, context: &SkeletonContext) -> Result<String> {
+    let mut hb = Handlebars::new();
+    hb.register_escape_fn(no_escape);
+    hb.render_template(skeleton, context)
+        .context("Could not render song skeleton template")
+}
+
+/// Append `entry` to the `songs = [ ... ]` array in the `bard.toml` source text.
+///
+/// Formatting and comments of the rest of the file are kept intact.
+/// Returns `None` if the array couldn't be located.
+fn append_songs_entry(toml_src: &str, entry: &str) -> Option<String> {
+    let caps = SONGS_ARRAY.captures(toml_src)?;
+    let items = caps.get(2).unwrap();
+    let entry = toml::Value::String(entry.into()).to_string();
+
+    let content = items.as_str();
+    let content_trimmed = content.trim_end();
+    let comma = if content_trimmed.is_empty() || content_trimmed.ends_with(',') {
+        ""
+    } else {
+        ","
+    };
+
+    let mut res = String::with_capacity(toml_src.len() + entry.len() + 8);
+    res.push_str(&toml_src[..items.start() + content_trimmed.len()]);
+    res.push_str(comma);
+    if content.contains('\n') {
+        res.push_str("\n    ");
+        res.push_str(&entry);
+        res.push_str(",\n");
+    } else {
+        if !content_trimmed.is_empty() {
+            res.push(' ');
+        }
+        res.push_str(&entry);
+    }
+    res.push_str(&toml_src[items.end()..]);
+
+    // Verify the result is still valid TOML
+    toml::from_str::<toml::Table>(&res).ok()?;
+    Some(res)
+}
+
+fn add_to_project(project_file: &Path, settings: &Settings, song_path: &Path) -> Result<bool> {
+    if !settings.songs_is_explicit_list() {
+        return Ok(false);
+    }
+
+    let entry = match song_path.strip_prefix(settings.dir_songs()) {
+        Ok(entry) => entry,
+        Err(_) => return Ok(false),
+    };
+    let entry = entry.to_string_lossy().replace('\\', "/");
+
+    let toml_src = fs::read_to_string(project_file)
+        .with_context(|| format!("Failed to read project file {:?}", project_file))?;
+    let toml_src = match append_songs_entry(&toml_src, &entry) {
+        Some(src) => src,
+        None => return Ok(false),
+    };
+
+    fs::write(project_file, toml_src)
+        .with_context(|| format!("Could not write project file {:?}", project_file))?;
+    Ok(true)
+}
+
+/// Create a new song file in the project found at `cwd` (or its parents, see `Project::locate()`).
+pub fn new_song(app: &App, cwd: &Path, opts: &NewSongOpts) -> Result<NewSong> {
+    let (project_file, project_dir) = Project::locate(app, cwd)?;
+    let settings = Settings::from_file(&project_file, &project_dir)?;
+
+    let skeleton = match opts.template {
+        Some(template) => {
+            let path = settings.dir_templates().join(template);
+            fs::read_to_string(&path)
+                .with_context(|| format!("Could not read song template {:?}", path))?
+        }
+        None => DEFAULT_SKELETON.to_string(),
+    };
+
+    let context = SkeletonContext {
+        title: opts.title,
+        artist: opts.artist,
+    };
+    let content = render_skeleton(&skeleton, &context)?;
+
+    let path = match opts.file {
+        Some(file) => project_dir.join(file),
+        None => settings
+            .dir_songs()
+            .join(format!("{}.md", slugify(opts.title))),
+    };
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Could not create directory {:?}", parent))?;
+    }
+
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&path)
+        .and_then(|mut f| f.write_all(content.as_bytes()))
+        .map_err(|err| match err.kind() {
+            io::ErrorKind::AlreadyExists => anyhow!("File already exists: {:?}", path),
+            _ => Error::from(err).context(format!("Could not write song file {:?}", path)),
+        })?;
+
+    let added_to_project = add_to_project(&project_file, &settings, &path)?;
+
+    Ok(NewSong {
+        path,
+        added_to_project,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn songs_entry_append() {
+        let src = "version = 2\nsongs = [\"a.md\"]\n";
+        assert_eq!(
+            append_songs_entry(src, "b.md").unwrap(),
+            "version = 2\nsongs = [\"a.md\", \"b.md\"]\n"
+        );
+
+        let src = "songs = [\n    \"a.md\",\n]\n\n[book]\ntitle = \"x\"\n";
+        assert_eq!(
+            append_songs_entry(src, "b.md").unwrap(),
+            "songs = [\n    \"a.md\",\n    \"b.md\",\n]\n\n[book]\ntitle = \"x\"\n"
+        );
+
+        let src = "songs = [\n    \"a.md\"\n]\n";
+        assert_eq!(
+            append_songs_entry(src, "b.md").unwrap(),
+            "songs = [\n    \"a.md\",\n    \"b.md\",\n]\n"
+        );
+
+        assert!(append_songs_entry("songs = \"*.md\"\n", "b.md").is_none());
+    }
+}
@@ -0,0 +1,94 @@
+//! The `render-song` utility, renders a single song with an output's template
+//! for quick previews while developing templates.
+
+use std::fs;
+
+use crate::app::App;
+use crate::book;
+use crate::prelude::*;
+use crate::project::{Output, Project};
+use crate::render::Renderer;
+
+/// Options for `render_song()`, these correspond to the CLI arguments.
+#[derive(Debug)]
+pub struct RenderSongOpts<'a> {
+    /// Title of the song or a glob pattern matching a single song.
+    pub song: &'a str,
+    /// The output file as configured in `bard.toml`, eg. `songbook.html`.
+    pub output: &'a str,
+}
+
+/// A preview written by `render_song()`.
+#[derive(Debug)]
+pub struct SongPreview {
+    pub song: String,
+    pub file: PathBuf,
+}
+
+/// Find the output whose file is `name`, relative to the output directory or just the file name.
+fn find_output<'p>(project: &'p Project, name: &str) -> Result<&'p Output> {
+    let dir_output = project.settings.dir_output();
+    let outputs = &project.settings.output;
+    outputs
+        .iter()
+        .find(|o| o.file.strip_prefix(dir_output).ok() == Some(Path::new(name)))
+        .or_else(|| outputs.iter().find(|o| o.output_filename() == name))
+        .ok_or_else(|| {
+            let names: Vec<_> = outputs.iter().map(|o| o.output_filename()).collect();
+            anyhow!(
+                "No output '{}' in this project, the outputs are: {}",
+                name,
+                names.join(", ")
+            )
+        })
+}
+
+/// Load the project found at `cwd` (or its parents, see `Project::locate()`)
+/// and render only the song matching `opts.song` using the template of `opts.output`.
+///
+/// The preview is written next to the output file, see `Output::preview_file()`,
+/// no TeX, scripts, or image commands are run.
+pub fn render_song(app: &App, cwd: &Path, opts: &RenderSongOpts) -> Result<SongPreview> {
+    let project = Project::new(app, cwd)?;
+    let output = find_output(&project, opts.output)?;
+
+    let songs = project.match_songs(opts.song)?;
+    let idx = match songs[..] {
+        [idx] => idx,
+        _ => {
+            let titles: Vec<_> = songs
+                .iter()
+                .map(|&idx| format!("'{}'", project.songs()[idx].title))
+                .collect();
+            bail!(
+                "'{}' matches {} songs: {}. Only a single song can be previewed.",
+                opts.song,
+                songs.len(),
+                titles.join(", ")
+            );
+        }
+    };
+
+    let song = project.songs()[idx].title.to_string();
+    let file = output.preview_file();
+    app.status(
+        "Rendering",
+        format!("'{}' with {}", song, output.output_filename()),
+    );
+
+    if let Some(parent) = file.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Could not create output directory {:?}", parent))?;
+    }
+    let context = || format!("Could not render preview of output {:?}", opts.output);
+    let renderer = Renderer::new(app, &project, output).with_context(context)?;
+    renderer
+        .render_preview(app, idx, &file)
+        .with_context(context)?;
+
+    if let Some((tpl_version, tpl_path)) = renderer.version().zip(output.template.as_ref()) {
+        book::version::compat_check(app, tpl_path, &tpl_version);
+    }
+
+    Ok(SongPreview { song, file })
+}
@@ -0,0 +1,13 @@
+//! The `show-config` utility, prints the effective project configuration.
+
+use crate::app::App;
+use crate::prelude::*;
+use crate::project::{Project, Settings};
+
+/// Load the project found at `cwd` (or its parents, see `Project::locate()`) and render
+/// its effective configuration as `bard.toml` source annotated with the origin of each value.
+pub fn show_config(app: &App, cwd: &Path) -> Result<String> {
+    let (project_file, project_dir) = Project::locate(app, cwd)?;
+    let settings = Settings::from_file(&project_file, &project_dir)?;
+    settings.effective_toml(&project_dir)
+}
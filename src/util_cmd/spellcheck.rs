@@ -0,0 +1,327 @@
+//! The `spellcheck` utility, checks words in songs against a hunspell dictionary.
+//!
+//! Hunspell is only linked with the `spellcheck` cargo feature, the checking itself
+//! works with any `Dictionary`.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+
+use serde::{Deserialize, Serialize};
+
+use crate::app::App;
+use crate::prelude::*;
+use crate::project::Project;
+
+/// The `[spellcheck]` section of the project file.
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct SpellcheckSettings {
+    /// Words never reported as misspelled, eg. names, compared case-insensitively.
+    pub ignore: Vec<String>,
+}
+
+/// Options for `spellcheck()`, these correspond to the CLI arguments.
+#[derive(Debug)]
+pub struct SpellcheckOpts<'a> {
+    /// Dictionary language, eg. `en_US` or `cs`.
+    pub lang: &'a str,
+    /// File with additional accepted words, one per line.
+    pub dict: Option<&'a Path>,
+}
+
+/// A spelling dictionary.
+pub trait Dictionary {
+    /// Whether `word` is spelled correctly.
+    fn check(&self, word: &str) -> bool;
+
+    /// Corrections of a misspelled `word`, best first.
+    fn suggest(&self, word: &str) -> Vec<String>;
+}
+
+/// A plain list of correct words compared case-insensitively, offers no suggestions.
+#[derive(Default, Debug)]
+pub struct WordList {
+    words: HashSet<String>,
+}
+
+impl WordList {
+    pub fn new<I, S>(words: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        Self {
+            words: words
+                .into_iter()
+                .map(|w| w.as_ref().to_lowercase())
+                .collect(),
+        }
+    }
+
+    fn extend<I, S>(&mut self, words: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.words
+            .extend(words.into_iter().map(|w| w.as_ref().to_lowercase()));
+    }
+}
+
+impl Dictionary for WordList {
+    fn check(&self, word: &str) -> bool {
+        self.words.contains(&word.to_lowercase())
+    }
+
+    fn suggest(&self, _word: &str) -> Vec<String> {
+        vec![]
+    }
+}
+
+#[cfg(feature = "spellcheck")]
+mod hunspell {
+    use std::env;
+
+    use hunspell_rs::{CheckResult, Hunspell};
+
+    use super::Dictionary;
+    use crate::prelude::*;
+
+    /// Searched for dictionaries after the directories in `DICPATH`.
+    const DIC_DIRS: &[&str] = &[
+        "/usr/share/hunspell",
+        "/usr/share/myspell",
+        "/usr/share/myspell/dicts",
+        "/usr/local/share/hunspell",
+        "/Library/Spelling",
+    ];
+
+    pub struct HunspellDictionary(Hunspell);
+
+    impl Dictionary for HunspellDictionary {
+        fn check(&self, word: &str) -> bool {
+            self.0.check(word) == CheckResult::FoundInDictionary
+        }
+
+        fn suggest(&self, word: &str) -> Vec<String> {
+            self.0.suggest(word)
+        }
+    }
+
+    /// Find the `.aff` and `.dic` pair of `lang` in `dir`, returns the path without extension.
+    /// A language without a region, eg. `cs`, matches any region, eg. `cs_CZ`.
+    fn find_in(dir: &Path, lang: &str) -> Option<PathBuf> {
+        let exists = |base: &Path| {
+            base.with_extension("aff").is_file() && base.with_extension("dic").is_file()
+        };
+
+        let base = dir.join(lang);
+        if exists(&base) {
+            return Some(base);
+        }
+        if lang.contains('_') {
+            return None;
+        }
+
+        let prefix = format!("{}_", lang);
+        let mut candidates: Vec<_> = dir
+            .read_dir()
+            .ok()?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().map_or(false, |ext| ext == "dic"))
+            .filter(|path| {
+                path.file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .map_or(false, |stem| stem.starts_with(&prefix))
+            })
+            .map(|path| path.with_extension(""))
+            .filter(|base| exists(base))
+            .collect();
+        candidates.sort();
+        candidates.into_iter().next()
+    }
+
+    pub fn open(lang: &str) -> Result<HunspellDictionary> {
+        let dicpath = env::var_os("DICPATH")
+            .map(|paths| env::split_paths(&paths).collect::<Vec<_>>())
+            .unwrap_or_default();
+        let base = dicpath
+            .into_iter()
+            .chain(DIC_DIRS.iter().map(PathBuf::from))
+            .find_map(|dir| find_in(&dir, lang))
+            .ok_or_else(|| {
+                anyhow!(
+                    "Could not find a hunspell dictionary for language '{}'. Install one or point the DICPATH variable to a directory with {0}.aff and {0}.dic files.",
+                    lang
+                )
+            })?;
+
+        let aff = base.with_extension("aff");
+        let dic = base.with_extension("dic");
+        Ok(HunspellDictionary(Hunspell::new(
+            &aff.to_string_lossy(),
+            &dic.to_string_lossy(),
+        )))
+    }
+}
+
+/// A misspelled word found by `spellcheck()`.
+#[derive(Debug)]
+pub struct Misspelling {
+    /// The song file, relative to the project directory.
+    pub file: PathBuf,
+    pub line: usize,
+    pub song: String,
+    pub word: String,
+    pub suggestions: Vec<String>,
+}
+
+fn is_apostrophe(c: char) -> bool {
+    c == '\'' || c == '’'
+}
+
+/// Split `text` into words, ie. runs of letters possibly joined by apostrophes.
+/// Words containing digits, eg. `2nd`, are skipped.
+fn words(text: &str) -> impl Iterator<Item = &str> {
+    text.split(|c: char| !c.is_alphanumeric() && !is_apostrophe(c))
+        .map(|word| word.trim_matches(is_apostrophe))
+        .filter(|word| !word.is_empty() && !word.chars().any(char::is_numeric))
+}
+
+/// Attributes words of a song to lines of its source file.
+///
+/// The AST doesn't carry positions of inlines, so words are looked up in the source
+/// in order of appearance, starting at the song's line. A word that can't be found,
+/// eg. one split by a chord, is attributed to the line of the previous word.
+struct LineFinder<'s> {
+    lines: Vec<&'s str>,
+    /// 0-indexed.
+    cursor: usize,
+}
+
+impl<'s> LineFinder<'s> {
+    fn new(source: &'s str, line: usize) -> Self {
+        let lines: Vec<_> = source.lines().collect();
+        let cursor = line.saturating_sub(1).min(lines.len().saturating_sub(1));
+        Self { lines, cursor }
+    }
+
+    fn find(&mut self, word: &str) -> usize {
+        if let Some(offset) = self
+            .lines
+            .iter()
+            .skip(self.cursor)
+            .position(|line| line.contains(word))
+        {
+            self.cursor += offset;
+        }
+        self.cursor + 1
+    }
+}
+
+/// Load the project found at `cwd` (or its parents, see `Project::locate()`)
+/// and check spelling of all its songs against the hunspell dictionary of `opts.lang`.
+#[cfg(feature = "spellcheck")]
+pub fn spellcheck(app: &App, cwd: &Path, opts: &SpellcheckOpts) -> Result<Vec<Misspelling>> {
+    let dictionary = hunspell::open(opts.lang)?;
+    spellcheck_with(app, cwd, opts, &dictionary)
+}
+
+#[cfg(not(feature = "spellcheck"))]
+pub fn spellcheck(app: &App, cwd: &Path, opts: &SpellcheckOpts) -> Result<Vec<Misspelling>> {
+    let _ = (app, cwd, opts);
+    bail!("This bard binary was not built with spell checking, rebuild it with the `spellcheck` feature enabled.")
+}
+
+/// Like `spellcheck()`, but check against the given `dictionary`.
+pub fn spellcheck_with(
+    app: &App,
+    cwd: &Path,
+    opts: &SpellcheckOpts,
+    dictionary: &dyn Dictionary,
+) -> Result<Vec<Misspelling>> {
+    let project = Project::new(app, cwd)?;
+
+    let mut ignore = WordList::new(&project.settings.spellcheck.ignore);
+    if let Some(dict) = opts.dict {
+        let words = fs::read_to_string(dict)
+            .with_context(|| format!("Could not read dictionary file {:?}", dict))?;
+        ignore.extend(words.lines().map(str::trim).filter(|w| !w.is_empty()));
+    }
+
+    app.status("Checking", format!("{} songs", project.book.songs.len()));
+
+    let mut sources = BTreeMap::new();
+    let mut suggestions = HashMap::new();
+    let mut misspellings = vec![];
+    for song in project.book.songs.iter() {
+        app.check_interrupted()?;
+
+        let file = &song.location.file;
+        if !sources.contains_key(file) {
+            let path = project.project_dir.join(file);
+            let source = fs::read_to_string(&path)
+                .with_context(|| format!("Could not read file {:?}", path))?;
+            sources.insert(file.clone(), source);
+        }
+
+        let mut lines = LineFinder::new(&sources[file], song.location.line);
+        song.visit_text(|text| {
+            for word in words(text) {
+                let line = lines.find(word);
+                if ignore.check(word) || dictionary.check(word) {
+                    continue;
+                }
+
+                let suggestions = suggestions
+                    .entry(word.to_string())
+                    .or_insert_with(|| dictionary.suggest(word));
+                misspellings.push(Misspelling {
+                    file: file.clone(),
+                    line,
+                    song: song.title.to_string(),
+                    word: word.to_string(),
+                    suggestions: suggestions.clone(),
+                });
+            }
+        });
+    }
+
+    Ok(misspellings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_words() {
+        let text = "Don't 'quote' me, it's 2nd of May–June\nžluťoučký kůň";
+        assert_eq!(
+            words(text).collect::<Vec<_>>(),
+            [
+                "Don't",
+                "quote",
+                "me",
+                "it's",
+                "of",
+                "May",
+                "June",
+                "žluťoučký",
+                "kůň"
+            ]
+        );
+    }
+
+    #[test]
+    fn line_finder() {
+        let source = "# Song\n\n1. Hello there\nhello [C]again\n\n# Other\n\nHello\n";
+        let mut lines = LineFinder::new(source, 1);
+        assert_eq!(lines.find("Song"), 1);
+        assert_eq!(lines.find("Hello"), 3);
+        assert_eq!(lines.find("hello"), 4);
+        assert_eq!(lines.find("missing"), 4);
+        assert_eq!(lines.find("Hello"), 8);
+    }
+}
@@ -0,0 +1,201 @@
+//! The `transpose` utility, permanently transposes chords of a song file.
+//!
+//! The file isn't round-tripped through the Markdown parser, instead chord spans are found
+//! by a light-weight scanner and only their content is rewritten,
+//! so that everything else in the file stays exactly as it was, byte for byte.
+
+use std::fs;
+
+use crate::music::{self, MinorChords, Notation};
+use crate::prelude::*;
+
+/// Options for `transpose()`, these correspond to the CLI arguments.
+#[derive(Debug)]
+pub struct TransposeOpts<'a> {
+    pub file: &'a Path,
+    pub semitones: i32,
+    /// Chord notation of the song.
+    pub notation: Notation,
+    /// Notation to write the chords in, the song's notation by default.
+    pub to_notation: Option<Notation>,
+}
+
+/// Transpose chords of `opts.file` in place, returns the number of chord spans.
+///
+/// If any chord is not recognized, nothing is written and all the unrecognized chords are reported in the error.
+pub fn transpose(opts: &TransposeOpts) -> Result<usize> {
+    let path = opts.file;
+    let source =
+        fs::read_to_string(path).with_context(|| format!("Could not read file {:?}", path))?;
+
+    let to_notation = opts.to_notation.as_ref().unwrap_or(&opts.notation);
+    let mut errors = vec![];
+    let mut count = 0;
+    let output = map_chord_spans(&source, |chord_set, line| {
+        count += 1;
+        transpose_span(chord_set, opts.semitones, &opts.notation, to_notation).unwrap_or_else(
+            |chord| {
+                errors.push(format!(
+                    "{}:{}: Unrecognized chord: {}",
+                    path.display(),
+                    line,
+                    chord
+                ));
+                chord_set.to_string()
+            },
+        )
+    });
+
+    if !errors.is_empty() {
+        bail!(
+            "Could not transpose file {:?}, it was left as it is:\n{}",
+            path,
+            errors.join("\n")
+        );
+    }
+
+    if output != source {
+        fs::write(path, output).with_context(|| format!("Could not write file {:?}", path))?;
+    }
+    Ok(count)
+}
+
+/// Transpose the content of a chord span, the leading and trailing whitespace
+/// and the underscores of baseline chords are kept where they are.
+fn transpose_span<'s>(
+    chord_set: &'s str,
+    by: i32,
+    src_notation: &Notation,
+    to_notation: &Notation,
+) -> Result<String, &'s str> {
+    let is_padding = |c: char| c.is_whitespace() || c == '_';
+    let start = chord_set.len() - chord_set.trim_start_matches(is_padding).len();
+    let end = chord_set.trim_end_matches(is_padding).len().max(start);
+
+    let mut res = String::with_capacity(chord_set.len() + 8);
+    res.push_str(&chord_set[..start]);
+    for (i, chords) in chord_set[start..end].split('_').enumerate() {
+        if i > 0 {
+            res.push('_');
+        }
+        if !chords.is_empty() {
+            let chords =
+                music::transpose(chords, by, src_notation, to_notation, MinorChords::Keep)?;
+            res.push_str(&chords);
+        }
+    }
+    res.push_str(&chord_set[end..]);
+    Ok(res)
+}
+
+/// Copy `source` replacing the content of each inline code span, ie. chord span, with the result of `f`,
+/// which is given the content and the line number of the span.
+///
+/// Code spans are only looked for within a line, fenced code blocks are skipped.
+fn map_chord_spans(source: &str, mut f: impl FnMut(&str, usize) -> String) -> String {
+    let mut res = String::with_capacity(source.len() + source.len() / 8);
+    let mut fence = None::<(char, usize)>;
+    for (num, line) in source.split_inclusive('\n').enumerate() {
+        if let Some(marker) = fence_marker(line) {
+            match fence {
+                None => fence = Some(marker),
+                Some((c, len)) if marker.0 == c && marker.1 >= len => fence = None,
+                Some(_) => {}
+            }
+            res.push_str(line);
+            continue;
+        }
+        if fence.is_some() {
+            res.push_str(line);
+            continue;
+        }
+
+        let mut rest = line;
+        while let Some(pos) = rest.find(['`', '\\']) {
+            res.push_str(&rest[..pos]);
+            rest = &rest[pos..];
+
+            if rest.starts_with('\\') {
+                // A backslash escape, a backtick following it doesn't start a span
+                let escaped = rest[1..].starts_with(|c: char| c.is_ascii_punctuation());
+                let len = if escaped { 2 } else { 1 };
+                res.push_str(&rest[..len]);
+                rest = &rest[len..];
+                continue;
+            }
+
+            let ticks = backticks(rest);
+            match find_closing(&rest[ticks..], ticks) {
+                Some(end) => {
+                    res.push_str(&rest[..ticks]);
+                    res.push_str(&f(&rest[ticks..ticks + end], num + 1));
+                    res.push_str(&rest[ticks + end..ticks + end + ticks]);
+                    rest = &rest[ticks + end + ticks..];
+                }
+                None => {
+                    // Not a code span, the backticks are literal
+                    res.push_str(&rest[..ticks]);
+                    rest = &rest[ticks..];
+                }
+            }
+        }
+        res.push_str(rest);
+    }
+
+    res
+}
+
+/// Number of backticks at the start of `s`.
+fn backticks(s: &str) -> usize {
+    s.len() - s.trim_start_matches('`').len()
+}
+
+/// Position of the first run of exactly `ticks` backticks in `s`.
+fn find_closing(s: &str, ticks: usize) -> Option<usize> {
+    let mut pos = 0;
+    while let Some(start) = s[pos..].find('`') {
+        let start = pos + start;
+        let len = backticks(&s[start..]);
+        if len == ticks {
+            return Some(start);
+        }
+        pos = start + len;
+    }
+    None
+}
+
+/// The fence character and its count if `line` opens or closes a fenced code block,
+/// also within a blockquote, eg. `` ``` `` or `> ~~~`.
+fn fence_marker(line: &str) -> Option<(char, usize)> {
+    let line = line.trim_start_matches(['>', ' ', '\t']);
+    let c = line.chars().next().filter(|&c| c == '`' || c == '~')?;
+    let len = line.len() - line.trim_start_matches(c).len();
+    (len >= 3).then_some((c, len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn xp(source: &str, by: i32) -> String {
+        map_chord_spans(source, |chords, _| {
+            transpose_span(chords, by, &Notation::English, &Notation::English).unwrap()
+        })
+    }
+
+    #[test]
+    fn transpose_chord_spans() {
+        assert_eq!(
+            xp("# Song `G`\n\n1. `C`Lyrics ``Am7``more `C_D`\n", 2),
+            "# Song `A`\n\n1. `D`Lyrics ``Bm7``more `D_E`\n"
+        );
+        assert_eq!(
+            xp("`` C ``, \\`E\\`, \\\\`F`, `G\r\n", 2),
+            "`` D ``, \\`E\\`, \\\\`G`, `G\r\n"
+        );
+        assert_eq!(
+            xp("```\n`C`\n```\n> ~~~~\n> ~~~\n`C`\n~~~~\n`C`\n", 2),
+            "```\n`C`\n```\n> ~~~~\n> ~~~\n`C`\n~~~~\n`D`\n"
+        );
+    }
+}
@@ -1,92 +1,218 @@
+use std::collections::VecDeque;
+use std::fs;
 use std::path::PathBuf;
-use std::sync::mpsc::{channel, Receiver};
+use std::sync::mpsc::{channel, Receiver, Sender};
 use std::sync::{Arc, Barrier};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 
-use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use notify::{Config, PollWatcher, RecursiveMode, Watcher};
 
 use crate::app::{InterruptError, InterruptFlag};
 use crate::prelude::*;
 use crate::project::Project;
 
+mod keys;
+
+use keys::KeyListener;
+pub use keys::KEYS_HELP;
+
 type NotifyResult = notify::Result<notify::Event>;
 
+/// Modification time and size of a watched file, `None` if it couldn't be read.
+type FileStamp = Option<(SystemTime, u64)>;
+
+fn file_stamp(path: &Path) -> FileStamp {
+    let meta = fs::metadata(path).ok()?;
+    Some((meta.modified().ok()?, meta.len()))
+}
+
+/// An event that `Watch::watch()` returns with.
+#[derive(Debug)]
+pub enum WatchEvent {
+    /// Files at these paths have changed.
+    Change(Vec<PathBuf>),
+    /// The `r` key: Rebuild the project.
+    Rebuild,
+    /// The `o` key: Open the first output in the default viewer.
+    Open,
+    /// The `d` key: Toggle verbose diagnostics.
+    ToggleVerbose,
+    /// The `q` key or interrupt: Stop watching.
+    Quit,
+}
+
 pub struct Watch {
-    watcher: RecommendedWatcher,
-    evt_rx: Receiver<NotifyResult>,
+    watcher: Box<dyn Watcher + Send>,
+    /// Set when files are polled rather than watched using notifications of the OS.
+    poll_interval: Option<Duration>,
+    /// Stamps of the watched files when watching started, to detect changes without notifications.
+    stamps: Vec<(PathBuf, FileStamp)>,
+    /// Whether a rebuild requested while watching found changed files no notification was received for.
+    missed_changes: bool,
+    evt_tx: Sender<Result<WatchEvent>>,
+    evt_rx: Receiver<Result<WatchEvent>>,
+    /// Key events received while debouncing file changes.
+    pending: VecDeque<WatchEvent>,
+    keys: Option<KeyListener>,
     test_barrier: Option<Arc<Barrier>>,
 }
 
 #[derive(Debug)]
 pub struct WatchControl {
+    evt_tx: Sender<Result<WatchEvent>>,
     test_barrier: Arc<Barrier>,
 }
 
 impl Watch {
+    /// Watch files using notifications of the OS.
     pub fn new() -> Result<Self> {
+        Self::with_watcher(None, |handler| {
+            Ok(Box::new(notify::recommended_watcher(handler)?))
+        })
+    }
+
+    /// Watch files by checking them for changes every `interval`, for filesystems
+    /// which don't deliver notifications, such as network filesystems.
+    pub fn with_poll(interval: Duration) -> Result<Self> {
+        Self::with_watcher(Some(interval), |handler| {
+            // Modification times are only compared in whole seconds, contents catch quick successive edits
+            let config = Config::default()
+                .with_poll_interval(interval)
+                .with_compare_contents(true);
+            Ok(Box::new(PollWatcher::new(handler, config)?))
+        })
+    }
+
+    fn with_watcher<F>(poll_interval: Option<Duration>, make_watcher: F) -> Result<Self>
+    where
+        F: FnOnce(Box<dyn FnMut(NotifyResult) + Send>) -> Result<Box<dyn Watcher + Send>>,
+    {
         let (evt_tx, evt_rx) = channel();
 
-        let watcher = notify::recommended_watcher(move |res: NotifyResult| {
-            match res {
-                Ok(evt) if evt.kind.is_access() => {} // Ignore access events
-                other => {
-                    let _ = evt_tx.send(other);
-                }
-            }
-        })?;
+        let notify_tx = evt_tx.clone();
+        let watcher = make_watcher(Box::new(move |res: NotifyResult| {
+            let evt = match res {
+                Ok(evt) if evt.kind.is_access() => return, // Ignore access events
+                Ok(evt) => Ok(WatchEvent::Change(evt.paths)),
+                Err(err) => Err(err.into()),
+            };
+            let _ = notify_tx.send(evt);
+        }))?;
 
         Ok(Watch {
             watcher,
+            poll_interval,
+            stamps: vec![],
+            missed_changes: false,
+            evt_tx,
             evt_rx,
+            pending: VecDeque::new(),
+            keys: None,
             test_barrier: None,
         })
     }
 
-    /// Create with the test sync flag on, for testing.
-    pub fn with_test_sync() -> Result<(Self, WatchControl)> {
-        let mut this = Self::new()?;
+    /// Check that files can be watched by watching the `dir` for a moment, see `bard util doctor`.
+    pub fn probe(dir: &Path) -> Result<()> {
+        let mut watch = Self::new()?;
+        watch
+            .watcher
+            .watch(dir, RecursiveMode::NonRecursive)
+            .context("Error watching files")?;
+        let _ = watch.watcher.unwatch(dir);
+        Ok(())
+    }
 
+    /// Enable the keyboard listener, if stdin is an interactive terminal.
+    pub fn with_key_listener(mut self) -> Self {
+        self.keys = KeyListener::start(self.evt_tx.clone());
+        self
+    }
+
+    pub fn has_key_listener(&self) -> bool {
+        self.keys.is_some()
+    }
+
+    pub fn poll_interval(&self) -> Option<Duration> {
+        self.poll_interval
+    }
+
+    /// Turn the test sync flag on, for testing.
+    pub fn with_test_sync(mut self) -> (Self, WatchControl) {
         let test_barrier = Arc::new(Barrier::new(2));
         let control = WatchControl {
+            evt_tx: self.evt_tx.clone(),
             test_barrier: test_barrier.clone(),
         };
 
-        this.test_barrier = Some(test_barrier);
-        Ok((this, control))
+        self.test_barrier = Some(test_barrier);
+        (self, control)
     }
 
-    pub fn watch(
-        &mut self,
-        project: &Project,
-        interrupt: InterruptFlag,
-    ) -> Result<Option<Vec<PathBuf>>> {
+    pub fn watch(&mut self, project: &Project, interrupt: InterruptFlag) -> Result<WatchEvent> {
         self.watch_files(project)?;
+        if self.poll_interval.is_none() {
+            self.stamps = project
+                .watch_paths()
+                .map(|path| (path.to_owned(), file_stamp(path)))
+                .collect();
+        }
 
         // Synchronize with test code, if any
         self.test_barrier.as_deref().map(Barrier::wait);
 
-        let paths = match interrupt.channel_recv(&self.evt_rx) {
-            Ok(Some(res)) => res.context("Error watching files")?.paths,
+        let res = self.next_event(interrupt);
+        self.unwatch_files(project);
+
+        if let Ok(WatchEvent::Rebuild) = res {
+            self.missed_changes = self
+                .stamps
+                .iter()
+                .any(|(path, stamp)| file_stamp(path) != *stamp);
+        }
+        res
+    }
+
+    /// Whether files changed while watching without a notification being received,
+    /// as found on the last requested rebuild. Polling should be used in that case.
+    pub fn missed_changes(&self) -> bool {
+        self.missed_changes
+    }
+
+    fn next_event(&mut self, interrupt: InterruptFlag) -> Result<WatchEvent> {
+        if let Some(event) = self.pending.pop_front() {
+            return Ok(event);
+        }
+
+        let event = match interrupt.channel_recv(&self.evt_rx) {
+            Ok(Some(res)) => res.context("Error watching files")?,
             Ok(None) => bail!("Internal error: Channel receive failed"),
-            Err(InterruptError) => return Ok(None),
+            Err(InterruptError) => return Ok(WatchEvent::Quit),
         };
 
-        // Delaying mechanism - don't return back until we've
-        // seen no event for a timeout's duration.
-        loop {
-            thread::sleep(Duration::from_millis(250));
+        if let WatchEvent::Change(..) = event {
+            // Delaying mechanism - don't return back until we've
+            // seen no file event for a timeout's duration.
+            loop {
+                thread::sleep(Duration::from_millis(250));
+
+                // Drain all immediately available evts, keeping key events for later
+                let mut seen = false;
+                while let Ok(res) = self.evt_rx.try_recv() {
+                    match res {
+                        Ok(WatchEvent::Change(..)) | Err(_) => seen = true,
+                        Ok(event) => self.pending.push_back(event),
+                    }
+                }
 
-            if self.evt_rx.try_recv().is_ok() {
-                // Drain all immediately available evts
-                while self.evt_rx.try_recv().is_ok() {}
-            } else {
-                break;
+                if !seen {
+                    break;
+                }
             }
         }
 
-        self.unwatch_files(project);
-        Ok(Some(paths))
+        Ok(event)
     }
 
     fn watch_files(&mut self, project: &Project) -> Result<()> {
@@ -112,4 +238,11 @@ impl WatchControl {
     pub fn wait_watching(&self) {
         self.test_barrier.wait();
     }
+
+    /// Send an event to the `Watch` as if a key was pressed.
+    ///
+    /// **To be used in tests.**
+    pub fn send(&self, event: WatchEvent) {
+        let _ = self.evt_tx.send(Ok(event));
+    }
 }
@@ -0,0 +1,160 @@
+//! Keyboard listener for `bard watch`.
+//!
+//! Keys are read on a background thread and sent as `WatchEvent`s
+//! into the same channel as file change events.
+//!
+//! The listener is only available on unix. Elsewhere, reading a key blocks until one is pressed
+//! and can't be interrupted, so the thread would be left blocked on stdin when watching stops.
+
+use std::sync::mpsc::Sender;
+
+use super::WatchEvent;
+use crate::prelude::*;
+
+/// Help text listing the key bindings.
+pub const KEYS_HELP: &str =
+    "Press r to rebuild, o to open output, d to toggle verbose output, q to quit.";
+
+#[cfg(unix)]
+pub use imp::KeyListener;
+
+#[cfg(not(unix))]
+pub struct KeyListener;
+
+#[cfg(not(unix))]
+impl KeyListener {
+    /// The listener is not supported here, see the module docs.
+    pub fn start(_tx: Sender<Result<WatchEvent>>) -> Option<Self> {
+        None
+    }
+}
+
+#[cfg(unix)]
+mod imp {
+    use std::io;
+    use std::os::fd::{AsFd, AsRawFd};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::thread::{self, JoinHandle};
+
+    use console::{Key, Term};
+    use nix::errno::Errno;
+    use nix::poll::{self, PollFd, PollFlags};
+    use nix::sys::termios::{self, LocalFlags, SetArg, Termios};
+    use nix::unistd;
+
+    use super::*;
+
+    fn key_event(key: Key) -> Option<WatchEvent> {
+        match key {
+            Key::Char('r' | 'R') => Some(WatchEvent::Rebuild),
+            Key::Char('o' | 'O') => Some(WatchEvent::Open),
+            Key::Char('d' | 'D') => Some(WatchEvent::ToggleVerbose),
+            Key::Char('q' | 'Q') => Some(WatchEvent::Quit),
+            _ => None,
+        }
+    }
+
+    pub struct KeyListener {
+        stop: Arc<AtomicBool>,
+        thread: Option<JoinHandle<()>>,
+        _mode: TermMode,
+    }
+
+    impl KeyListener {
+        /// Start listening for keys, returns `None` if stdin is not an interactive terminal,
+        /// such as in CI or with input redirected from a file.
+        pub fn start(tx: Sender<Result<WatchEvent>>) -> Option<Self> {
+            // Keys are read from stdin, eg. `bard watch < /dev/null` doesn't listen
+            // even in a terminal. Console only reads keys if stderr is a terminal as well.
+            let stdin_tty = unistd::isatty(io::stdin().as_raw_fd()).unwrap_or(false);
+            if !stdin_tty || !Term::stderr().is_term() {
+                return None;
+            }
+
+            let mode = TermMode::set()?;
+
+            let stop = Arc::new(AtomicBool::new(false));
+            let stop2 = stop.clone();
+            let thread = thread::spawn(move || {
+                let term = Term::stderr();
+                while !stop2.load(Ordering::Relaxed) {
+                    if !key_ready() {
+                        continue;
+                    }
+
+                    // If there's an error, such as Ctrl+C being pressed in raw mode,
+                    // the listener is stopped, console takes care of raising SIGINT.
+                    let key = match term.read_key() {
+                        Ok(key) => key,
+                        Err(_) => break,
+                    };
+
+                    if let Some(event) = key_event(key) {
+                        if tx.send(Ok(event)).is_err() {
+                            break;
+                        }
+                    }
+                }
+            });
+
+            Some(Self {
+                stop,
+                thread: Some(thread),
+                _mode: mode,
+            })
+        }
+    }
+
+    impl Drop for KeyListener {
+        fn drop(&mut self) {
+            // The thread checks the stop flag periodically, see `key_ready()`
+            self.stop.store(true, Ordering::Relaxed);
+            if let Some(thread) = self.thread.take() {
+                let _ = thread.join();
+            }
+        }
+    }
+
+    /// Puts the terminal in non-canonical mode without echo so that keys
+    /// are available without pressing Enter. Signals and output processing stay enabled.
+    /// The original mode is restored on drop.
+    struct TermMode {
+        original: Termios,
+    }
+
+    impl TermMode {
+        fn set() -> Option<Self> {
+            let stdin = io::stdin();
+            let original = termios::tcgetattr(stdin.as_fd()).ok()?;
+
+            let mut mode = original.clone();
+            mode.local_flags
+                .remove(LocalFlags::ICANON | LocalFlags::ECHO);
+            termios::tcsetattr(stdin.as_fd(), SetArg::TCSANOW, &mode).ok()?;
+
+            Some(Self { original })
+        }
+    }
+
+    impl Drop for TermMode {
+        fn drop(&mut self) {
+            let _ = termios::tcsetattr(io::stdin().as_fd(), SetArg::TCSANOW, &self.original);
+        }
+    }
+
+    /// Wait a short time for input on stdin.
+    fn key_ready() -> bool {
+        let stdin = io::stdin();
+        let mut fds = [PollFd::new(stdin.as_fd(), PollFlags::POLLIN)];
+        match poll::poll(&mut fds, 50u16) {
+            Ok(0) | Err(Errno::EINTR) => false,
+            Ok(_) => true,
+            Err(_) => {
+                // Polling doesn't work, don't busy-loop.
+                std::thread::sleep(std::time::Duration::from_millis(50));
+                false
+            }
+        }
+    }
+}
@@ -0,0 +1,67 @@
+mod util_ng;
+pub use util_ng::*;
+
+#[test]
+fn aliases() {
+    let build = TestProject::new("aliases")
+        .song(
+            "songs.md",
+            indoc! {r#"
+            # Danny Boy
+            !alias(Oh Danny Boy) !alias(The Pipes Are Calling)
+
+            1. Lyrics.
+
+            # Amazing Grace
+
+            1. Lyrics.
+            "#},
+        )
+        .output("songbook.html")
+        .output("songbook.json")
+        .build()
+        .unwrap();
+
+    let html = build.read_output(".html");
+    assert!(html.contains(r#"<a id="oh-danny-boy"></a>"#));
+    assert!(html.contains(r#"<a id="the-pipes-are-calling"></a>"#));
+    assert!(!html.contains("Oh Danny Boy"));
+
+    let json: serde_json::Value = serde_json::from_str(&build.read_output(".json")).unwrap();
+    let aliases = json["aliases"].as_array().unwrap();
+    assert_eq!(aliases.len(), 2);
+    assert_eq!(aliases[0]["alias"], "Oh Danny Boy");
+    assert_eq!(aliases[0]["slug"], "oh-danny-boy");
+    assert_eq!(aliases[0]["idx"], 0);
+    assert_eq!(json["songs"][0]["aliases"][1], "The Pipes Are Calling");
+}
+
+#[test]
+fn aliases_conflict() {
+    let build = TestProject::new("aliases-conflict")
+        .song(
+            "songs.md",
+            indoc! {r#"
+            # Danny Boy
+            !alias(Amazing Grace!)
+
+            1. Lyrics.
+
+            # Amazing Grace
+
+            1. Lyrics.
+            "#},
+        )
+        .output("songbook.html")
+        .build()
+        .unwrap();
+
+    let err = format!("{:#}", build.unwrap_err());
+    assert!(
+        err.contains(
+            "Alias 'Amazing Grace!' of song 'Danny Boy' conflicts with song 'Amazing Grace'"
+        ),
+        "{}",
+        err
+    );
+}
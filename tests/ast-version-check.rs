@@ -3,7 +3,6 @@ use bard::prelude::*;
 use bard::project::Project;
 use bard::render::Renderer;
 
-use bard::util::ImgCache;
 use semver::Version;
 
 mod util;
@@ -12,9 +11,10 @@ pub use util::*;
 #[track_caller]
 fn get_output_versions(project: &Project) -> Vec<(Version, PathBuf)> {
     // Imperative code so that track_caller works
+    let app = Builder::app(false);
     let mut res = vec![];
     for o in &project.settings.output {
-        let renderer = Renderer::new(project, o, &ImgCache::new()).unwrap();
+        let renderer = Renderer::new(&app, project, o).unwrap();
         if let Some(ver) = renderer.version() {
             res.push((ver, o.file.clone()));
         }
@@ -0,0 +1,59 @@
+use std::fs;
+use std::process::Command;
+
+use bard::book::audit::AuditCheck;
+use bard::util_cmd::audit;
+
+mod util_ng;
+pub use util_ng::*;
+
+const SONGS: &str = "# Song\n\n1. `Am`Lyrics !>\n\n# Other Song\n\n> Chorus.\n\n1. `X`Lyrics !>\n";
+
+#[test]
+fn audit_project() {
+    let build = TestProject::new("audit")
+        .song("songs.md", SONGS)
+        .output("songbook.html")
+        .build()
+        .unwrap();
+    let project = build.unwrap();
+
+    let findings = audit(build.app(), &project.project_dir).unwrap();
+    let found: Vec<_> = findings
+        .iter()
+        .map(|f| (f.check, f.song.as_str(), f.location.line, f.is_error()))
+        .collect();
+    assert_eq!(
+        found,
+        [
+            (AuditCheck::ChorusRefs, "Song", 1, true),
+            (AuditCheck::Chords, "Other Song", 5, false),
+        ]
+    );
+
+    // The audit fails on errors and doesn't write any outputs
+    let html = build.dir_output().join("songbook.html");
+    fs::remove_file(&html).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_bard"))
+        .args(["util", "audit"])
+        .current_dir(&project.project_dir)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(!output.status.success());
+    assert!(stdout.contains("Chorus references (1):"), "{}", stdout);
+    assert!(
+        stdout.contains(
+            "songs/songs.md:5: warning: 'Other Song': Unrecognized chord 'X' in english notation"
+        ),
+        "{}",
+        stdout
+    );
+    assert!(
+        stderr.contains("1 errors and 1 warnings found."),
+        "{}",
+        stderr
+    );
+    assert!(!html.exists());
+}
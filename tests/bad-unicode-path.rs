@@ -39,7 +39,7 @@ fn bad_unicode_path() {
     fs::create_dir(&workdir).unwrap();
 
     let app = Builder::app(false);
-    bard::bard_init_at::<&Path>(&app, &workdir)
+    bard::bard_init_at::<&Path>(&app, &workdir, &Default::default())
         .context("Failed to initialize")
         .unwrap();
     bard::bard_make_at::<&Path>(&app, &workdir)
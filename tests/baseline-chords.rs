@@ -0,0 +1,51 @@
+mod util_ng;
+pub use util_ng::*;
+
+const SONG: &str = indoc! {"
+    # Baseline
+
+    1. `C___G`
+    `C_______G`
+    Lyrics `  __ __ Am_D __ __  `
+"};
+
+#[test]
+fn baseline_chords_tex_spacing() {
+    let build = TestProject::new("baseline-chords-tex-spacing")
+        .song("baseline.md", SONG)
+        .output("songbook.pdf")
+        .settings(|toml| {
+            toml.set("tex", "none");
+        })
+        .build()
+        .unwrap();
+
+    let tex = build.read_output(".tex");
+    assert!(tex.contains("{C~~~G}"));
+    assert!(tex.contains("{C~~~~~~~G}"));
+    assert!(tex.contains("{~~~Am~D~~~}"));
+}
+
+#[test]
+#[ignore = "requires poppler/pdftotext"]
+fn baseline_chords_pdf_columns() {
+    let build = TestProject::new("baseline-chords-pdf-columns")
+        .postprocess(true)
+        .song("baseline.md", SONG)
+        .output("songbook.pdf")
+        .build()
+        .unwrap();
+    let pdf_text = build.pdf_to_text(".pdf", 2..).unwrap();
+
+    let gap = |line: &str| {
+        let c = line.find('C').unwrap();
+        line[c..].find('G').unwrap()
+    };
+
+    let mut lines = pdf_text.lines().filter(|l| l.trim_start().starts_with('C'));
+    let short = gap(lines.next().unwrap());
+    let long = gap(lines.next().unwrap());
+
+    // Each space is printed, so the second chord set needs to be roughly twice as wide
+    assert!(long > short + 2, "short: {}, long: {}", short, long);
+}
@@ -0,0 +1,146 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::process::Command;
+use std::thread;
+use std::time::Duration;
+
+use bard::util_cmd::{check_links, CheckLinksOpts, LinkStatus};
+
+mod util_ng;
+pub use util_ng::*;
+
+/// Serve `/ok` with 200, `/no-head` with 405 to HEAD and 200 to GET, anything else with 404.
+/// Returns the base URL.
+fn serve() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request = String::new();
+            reader.read_line(&mut request).unwrap();
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).unwrap();
+                if header.trim().is_empty() {
+                    break;
+                }
+            }
+
+            let mut parts = request.split_whitespace();
+            let (method, path) = (parts.next().unwrap(), parts.next().unwrap());
+            let status = match (method, path) {
+                (_, "/ok") | ("GET", "/no-head") => "200 OK",
+                ("HEAD", "/no-head") => "405 Method Not Allowed",
+                _ => "404 Not Found",
+            };
+            let response = format!(
+                "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                status
+            );
+            let _ = stream.write_all(response.as_bytes());
+        }
+    });
+
+    format!("http://{}", addr)
+}
+
+fn prepare_project(name: &str, base: &str) -> TestBuild {
+    let songs = format!(
+        r#"# First Song
+
+1. Lyrics, see [source]({base}/ok) and *[video]({base}/gone)*.
+
+# Second Song
+
+> Chorus [again]({base}/ok), [tabs]({base}/no-head), [text](text.html), [mail](mailto:bard@example.com).
+"#,
+        base = base
+    );
+
+    TestProject::new(name)
+        .song("songs.md", songs)
+        .output("songbook.html")
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn check_links_online() {
+    let base = serve();
+    let build = prepare_project("check-links-online", &base);
+    let project = build.unwrap();
+
+    let opts = CheckLinksOpts {
+        timeout: Duration::from_secs(5),
+        offline: false,
+    };
+    let checks = check_links(build.app(), &project.project_dir, &opts).unwrap();
+    let checks: Vec<_> = checks
+        .iter()
+        .map(|c| {
+            (
+                c.song.as_str(),
+                c.url.strip_prefix(&base).unwrap_or(&c.url),
+                &c.status,
+            )
+        })
+        .collect();
+
+    assert_eq!(
+        checks,
+        [
+            ("First Song", "/ok", &LinkStatus::Ok(200)),
+            ("First Song", "/gone", &LinkStatus::Status(404)),
+            ("Second Song", "/ok", &LinkStatus::Ok(200)),
+            ("Second Song", "/no-head", &LinkStatus::Ok(200)),
+            (
+                "Second Song",
+                "text.html",
+                &LinkStatus::Skipped("relative link".into())
+            ),
+            (
+                "Second Song",
+                "mailto:bard@example.com",
+                &LinkStatus::Skipped("mailto link".into())
+            ),
+        ]
+    );
+}
+
+#[test]
+fn check_links_offline() {
+    let build = TestProject::new("check-links-offline")
+        .song(
+            "song.md",
+            "# Song\n\n1. [ok](https://example.com/song) [bad](https://example.com:99999/song)\n",
+        )
+        .output("songbook.html")
+        .build()
+        .unwrap();
+    let project = build.unwrap();
+
+    let opts = CheckLinksOpts {
+        timeout: Duration::from_secs(5),
+        offline: true,
+    };
+    let checks = check_links(build.app(), &project.project_dir, &opts).unwrap();
+    assert_eq!(checks[0].status, LinkStatus::Valid);
+    assert!(matches!(checks[1].status, LinkStatus::Invalid(..)));
+
+    // The command fails if any link is broken
+    let output = Command::new(env!("CARGO_BIN_EXE_bard"))
+        .args(["util", "check-links", "--offline"])
+        .current_dir(&project.project_dir)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(!output.status.success());
+    assert!(stdout.contains("Song: https://example.com/song (valid)"));
+    assert!(
+        stdout.contains("Song: https://example.com:99999/song (invalid URL: invalid port number)")
+    );
+    assert!(stderr.contains("1 of 2 links are broken."));
+}
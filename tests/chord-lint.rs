@@ -0,0 +1,40 @@
+use bard::parser::DiagKind;
+
+mod util_ng;
+pub use util_ng::*;
+
+const SONG: &str = "# Song\n\n1. `Am`Lyrics `la la` `N.C.`la.\n";
+
+#[test]
+fn chord_lint() {
+    let build = TestProject::new("chord-lint")
+        .song("song.md", SONG)
+        .output("songbook.html")
+        .settings(|toml| {
+            toml.set("chord_lint", true);
+            toml.set("chord_lint_allow", vec!["N.C."]);
+        })
+        .build()
+        .unwrap();
+
+    // The unrecognized chord is a warning, the song is rendered
+    let project = build.unwrap();
+    assert_eq!(project.songs().len(), 1);
+    build.assert_parser_diag(DiagKind::UnrecognizedChord {
+        chord: "la".into(),
+        notation: Default::default(),
+    });
+    assert_eq!(build.app().warnings(), 1);
+}
+
+#[test]
+fn chord_lint_off() {
+    let build = TestProject::new("chord-lint-off")
+        .song("song.md", SONG)
+        .output("songbook.html")
+        .build()
+        .unwrap();
+
+    build.unwrap();
+    assert_eq!(build.app().warnings(), 0);
+}
@@ -0,0 +1,82 @@
+use bard::util_cmd::chord_suffixes;
+
+mod util_ng;
+pub use util_ng::*;
+
+const SONGS: &str = r#"# Song
+
+1. `Amin`Lyrics `A-/G`more `Cmaj7 Dmi`lyrics `_Emi_` `Fmaj7`end.
+
+# Other Song
+
+> `Ami`Chorus `Bmin`text.
+"#;
+
+/// Suffixes are normalized after notation conversion.
+const SONG_GERMAN: &str = "# German Song\n\n!german\n\n1. `Bmin`Lyrics `Bbmaj7`more.\n";
+
+fn chords_used(build: &TestBuild) -> Vec<serde_json::Value> {
+    let json = build.read_output(".json");
+    let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+    json["songs"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|song| song["chords_used"].clone())
+        .collect()
+}
+
+#[test]
+fn chord_suffixes_normalize() {
+    let build = TestProject::new("chord-suffixes-normalize")
+        .song("songs.md", SONGS)
+        .song("german.md", SONG_GERMAN)
+        .settings(|toml| {
+            let chords: toml::Table = toml::toml! {
+                [normalize]
+                min = "m"
+                mi = "m"
+                "-" = "m"
+                maj7 = "Δ"
+            };
+            toml.insert("chords".into(), chords.into());
+        })
+        .output("songbook.json")
+        .build()
+        .unwrap();
+    build.unwrap();
+
+    assert_eq!(
+        chords_used(&build),
+        [
+            serde_json::json!(["Am", "Am/G", "CΔ Dm", "Em", "FΔ"]),
+            serde_json::json!(["Am", "Bm"]),
+            serde_json::json!(["Hm", "BΔ"]),
+        ]
+    );
+}
+
+#[test]
+fn chord_suffixes_report() {
+    let build = TestProject::new("chord-suffixes-report")
+        .song("songs.md", SONGS)
+        .output("songbook.json")
+        .build()
+        .unwrap();
+    let project = build.unwrap();
+
+    let suffixes = chord_suffixes(build.app(), &project.project_dir).unwrap();
+    let suffixes: Vec<_> = suffixes
+        .iter()
+        .map(|s| format!("{}: {} (eg. {})", s.suffix, s.count, s.example))
+        .collect();
+    assert_eq!(
+        suffixes,
+        [
+            "mi: 3 (eg. Dmi)",
+            "min: 2 (eg. Amin)",
+            "maj7: 2 (eg. Cmaj7)",
+            "-: 1 (eg. A-)",
+        ]
+    );
+}
@@ -0,0 +1,88 @@
+use serde_json::json;
+
+mod util_ng;
+pub use util_ng::*;
+
+const SONG_MD: &str = r#"# Markdown Song
+
+1. `G`Lyrics.
+"#;
+
+const SONG_CHO: &str = r#"{title: Wild Mountain Thyme}
+{subtitle: Irish & Scottish traditional}
+{key: G}
+{artist: Francis McPeake}
+
+# A ChordPro comment
+Oh the [G]summer [C]time is [G]coming
+And the [C]trees are sweetly [G]bloomin'
+
+{soc}
+And we'll [Em]all go [C]to[D]ge[G]ther
+{eoc}
+
+I will [G]build my love a [C]bower
+
+{comment: Bridge}
+1. *Not* a [Am]list! !>
+
+{start_of_tab}
+e|--3--|
+{end_of_tab}
+"#;
+
+#[test]
+fn chordpro_input() {
+    let build = TestProject::new("chordpro-input")
+        .song("a.md", SONG_MD)
+        .song("b.cho", SONG_CHO)
+        .output("songbook.json")
+        .build()
+        .unwrap();
+
+    build.unwrap();
+    build.assert_parser_diag(bard::parser::DiagKind::ChordProDirective {
+        directive: "{artist: Francis McPeake}".into(),
+    });
+
+    let json = build.read_output(".json");
+    let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+    let songs = json["songs"].as_array().unwrap();
+    assert_eq!(songs.len(), 2);
+    assert_eq!(songs[0]["title"], "Markdown Song");
+
+    let song = &songs[1];
+    assert_eq!(song["title"], "Wild Mountain Thyme");
+    assert_eq!(song["subtitles"][0], "Irish & Scottish traditional");
+    assert_eq!(song["key"], "G");
+    assert_eq!(song["chords_used"], json!(["G", "C", "Em", "D", "Am"]));
+
+    let blocks = song["blocks"].as_array().unwrap();
+    let labels: Vec<_> = blocks.iter().map(|b| &b["label"]).collect();
+    assert_eq!(
+        labels,
+        [
+            &json!({ "verse": 1 }),
+            &json!({ "chorus": null }),
+            &json!({ "verse": 2 }),
+            &json!({ "custom": "Bridge" }),
+            &serde_json::Value::Null,
+        ]
+    );
+    assert_eq!(blocks[4]["type"], "b-pre");
+
+    // Chords are placed before the syllables they were in front of in brackets
+    let verse = blocks[0]["paragraphs"][0].as_array().unwrap();
+    assert_eq!(verse[0]["text"], "Oh the ");
+    assert_eq!(verse[1]["chord"], "G");
+    assert_eq!(verse[1]["inlines"][0]["text"], "summer ");
+    assert_eq!(verse[3]["inlines"][0]["text"], "coming");
+    assert_eq!(verse[4]["type"], "i-break");
+    let chorus = blocks[1]["paragraphs"][0].as_array().unwrap();
+    assert_eq!(chorus[3]["chord"], "D");
+    assert_eq!(chorus[3]["inlines"][0]["text"], "ge");
+
+    // Markdown syntax in ChordPro lyrics is regular text
+    let bridge = &blocks[3]["paragraphs"][0];
+    assert_eq!(bridge[0]["text"], "1. *Not* a ");
+}
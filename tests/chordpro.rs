@@ -0,0 +1,75 @@
+mod util_ng;
+pub use util_ng::*;
+
+const SONG: &str = r#"# Wild Mountain Thyme
+## Irish & Scottish traditional
+
+!!+2
+
+1. Oh the `G`summer `C`time is `G`coming
+And the `C`trees are sweetly `G`bloomin'
+
+> And we'll `Em`all go `C`to`D`ge`G`ther
+
+2. I will `G`build my love a `C`bower
+By yon `C`clear and crystal `G`fountain
+"#;
+
+#[test]
+fn chordpro_output() {
+    let build = TestProject::new("chordpro-output")
+        .song("songs.md", SONG)
+        .song(
+            "tags.md",
+            "# Tags\n\n1. Some <small>small</small> [link](https://example.com)\n",
+        )
+        .output("songbook.cho")
+        .output("songbook.chordpro")
+        .build()
+        .unwrap();
+
+    build.unwrap();
+    let cho = build.read_output("songbook.cho");
+    assert_eq!(cho, build.read_output("songbook.chordpro"));
+    assert_eq!(
+        cho.lines().collect::<Vec<_>>(),
+        [
+            "{title: Wild Mountain Thyme}",
+            "{subtitle: Irish & Scottish traditional}",
+            "",
+            "{comment: 1.}",
+            "{comment_italic: A D A}",
+            "Oh the [G]summer [C]time is [G]coming",
+            "{comment_italic: D A}",
+            "And the [C]trees are sweetly [G]bloomin’",
+            "",
+            "{comment: Ch.}",
+            "{start_of_chorus}",
+            "{comment_italic: F#m D E A}",
+            "And we’ll [Em]all go [C]to[D]ge[G]ther",
+            "{end_of_chorus}",
+            "",
+            "{comment: 2.}",
+            "{comment_italic: A D}",
+            "I will [G]build my love a [C]bower",
+            "{comment_italic: D A}",
+            "By yon [C]clear and crystal [G]fountain",
+            "",
+            "{new_song}",
+            "{title: Tags}",
+            "",
+            "{comment: 1.}",
+            "Some small link",
+        ]
+    );
+
+    let diags = build.app().diags();
+    assert_eq!(diags.len(), 2);
+    assert!(
+        diags[0]
+            .message
+            .ends_with("Song 'Tags': HTML tags, link URLs left out of the ChordPro output"),
+        "{}",
+        diags[0].message
+    );
+}
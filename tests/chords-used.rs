@@ -0,0 +1,49 @@
+use bard::render::{html, pdf};
+
+mod util_ng;
+pub use util_ng::*;
+
+const SONG: &str = "# Song\n\n> `Am`Chorus `C`text `N.C.`end.\n\n1. `C`Lyrics `Am`more.\n";
+
+/// Lists the chords of each song at the start of the output.
+const CHORDS_USED: &str = r#"{{#each songs}}{{#each chords_used}}[{{ this }}]{{/each}}{{/each}}"#;
+
+#[test]
+fn chords_used() {
+    let build = TestProject::new("chords-used")
+        .song("songs.md", SONG)
+        .settings(|toml| toml.set("tex", "none"))
+        .output("songbook.pdf")
+        .template_prefix_default(
+            "songbook.pdf",
+            "pdf.hbs",
+            CHORDS_USED,
+            &pdf::DEFAULT_TEMPLATE,
+        )
+        .output("songbook.html")
+        .template_prefix_default(
+            "songbook.html",
+            "html.hbs",
+            CHORDS_USED,
+            &html::DEFAULT_TEMPLATE,
+        )
+        .output("songbook.json")
+        .output("songbook.xml")
+        .build()
+        .unwrap();
+
+    build.unwrap();
+    assert!(build.read_output(".tex").contains("[Am][C][N.C.]"));
+    assert!(build.read_output(".html").contains("[Am][C][N.C.]"));
+
+    let json = build.read_output(".json");
+    let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(
+        json["songs"][0]["chords_used"],
+        serde_json::json!(["Am", "C", "N.C."])
+    );
+    assert_eq!(json["songs"][0]["alt_chords_used"], serde_json::json!([]));
+
+    let xml = build.read_output(".xml");
+    assert!(xml.contains("<chord-used>N.C.</chord-used>"), "{}", xml);
+}
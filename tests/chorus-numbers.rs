@@ -0,0 +1,50 @@
+mod util_ng;
+pub use util_ng::*;
+
+const SONGS: &str = "# Song\n\n> Chorus.\n\n1. Lyrics !>\n\n# Other Song\n\n> Chorus one.\n\n>> Chorus two.\n\n1. Lyrics !>>\n";
+
+fn prepare_project(name: &str, always: bool, never: bool) -> TestProject {
+    TestProject::new(name)
+        .song("songs.md", SONGS)
+        .settings(move |toml| {
+            toml.set("always_number_choruses", always);
+            toml.set("never_number_choruses", never);
+        })
+        .output("songbook.json")
+}
+
+#[test]
+fn chorus_numbers_always() {
+    let build = prepare_project("chorus-numbers-always", true, false)
+        .build()
+        .unwrap();
+
+    let json = build.read_output(".json");
+    assert!(!json.contains(r#""chorus": null"#), "{}", json);
+    assert!(!json.contains(r#""num": null"#), "{}", json);
+}
+
+#[test]
+fn chorus_numbers_never() {
+    let build = prepare_project("chorus-numbers-never", false, true)
+        .build()
+        .unwrap();
+
+    let json = build.read_output(".json");
+    assert!(!json.contains(r#""chorus": 2"#), "{}", json);
+    assert!(!json.contains(r#""num": 2"#), "{}", json);
+}
+
+#[test]
+fn chorus_numbers_conflict() {
+    let build = prepare_project("chorus-numbers-conflict", true, true)
+        .build()
+        .unwrap();
+
+    let err = format!("{:#}", build.unwrap_err());
+    assert!(
+        err.contains("always_number_choruses and never_number_choruses can't be both enabled."),
+        "{}",
+        err
+    );
+}
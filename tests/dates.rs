@@ -0,0 +1,60 @@
+use std::fs;
+use std::process::Command;
+
+mod util_ng;
+pub use util_ng::*;
+
+const TEMPLATE: &str = r#"{{ now format="%Y-%m-%d %H:%M:%S" }}
+{{ date_fmt book.date format="%-d. %-m. %Y" }}
+"#;
+
+#[test]
+fn date_helpers_source_date_epoch() {
+    let build = TestProject::new("date-helpers-source-date-epoch")
+        .settings(|toml| {
+            let book = toml["book"].as_table_mut().unwrap();
+            book.insert(
+                "date".into(),
+                toml::Value::Datetime("2024-05-27".parse().unwrap()),
+            );
+        })
+        .output("songbook.html")
+        .template("songbook.html", "songbook.hbs", TEMPLATE)
+        .build()
+        .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_bard"))
+        .arg("make")
+        .env("SOURCE_DATE_EPOCH", "1700000000")
+        .current_dir(&build.unwrap().project_dir)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let html = fs::read_to_string(build.dir_output().join("songbook.html")).unwrap();
+    assert_eq!(html, "2023-11-14 22:13:20\n27. 5. 2024\n");
+}
+
+#[test]
+fn date_helpers_invalid_date() {
+    let build = TestProject::new("date-helpers-invalid-date")
+        .settings(|toml| {
+            let book = toml["book"].as_table_mut().unwrap();
+            book.insert("date".into(), "someday".into());
+        })
+        .output("songbook.html")
+        .template("songbook.html", "songbook.hbs", TEMPLATE)
+        .build()
+        .unwrap();
+
+    let err = format!("{:?}", build.unwrap_err());
+    assert!(
+        err.contains("date_fmt: Could not parse date 'someday'"),
+        "{}",
+        err
+    );
+}
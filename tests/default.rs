@@ -36,11 +36,11 @@ fn project_default_templates_save() {
     let templates = build.dir.join("templates");
 
     let html = fs::read_to_string(templates.join("html.hbs")).unwrap();
-    assert_eq!(html, render::html::DEFAULT_TEMPLATE.content);
+    assert_eq!(html, render::html::DEFAULT_TEMPLATE.saved_content());
 
     let pdf = fs::read_to_string(templates.join("pdf.hbs")).unwrap();
-    assert_eq!(pdf, render::pdf::DEFAULT_TEMPLATE.content);
+    assert_eq!(pdf, render::pdf::DEFAULT_TEMPLATE.saved_content());
 
     let hovorka = fs::read_to_string(templates.join("hovorka.hbs")).unwrap();
-    assert_eq!(hovorka, render::hovorka::DEFAULT_TEMPLATE.content);
+    assert_eq!(hovorka, render::hovorka::DEFAULT_TEMPLATE.saved_content());
 }
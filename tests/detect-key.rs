@@ -0,0 +1,123 @@
+use std::fs;
+use std::process::Command;
+
+mod util_ng;
+pub use util_ng::*;
+
+const SONGS: &str = r#"# Rising Sun
+
+1. `Am`There `C`is a `D`house in `F`New Orleans
+They `Am`call the `C`Rising `E`Sun
+
+# Let It Be
+## The Beatles
+
+!tempo(72)
+
+1. `C`When I find `G`myself in `Am`times of `F`trouble
+`C`Mother Mary `G`comes to `F`me `C`
+
+# Seesaw
+
+`G`Up `C`down `G`up `C`down
+
+# Keyed
+
+!key(D)
+
+`D`Lyrics `A`more `D`lyrics
+"#;
+
+#[test]
+fn detect_key_write() {
+    let build = TestProject::new("detect-key")
+        .song("songs.md", SONGS)
+        .build()
+        .unwrap();
+    let songs = build.dir_songs().join("songs.md");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_bard"))
+        .args(["util", "detect-key", "--write"])
+        .arg(&songs)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<_> = stdout.lines().collect();
+    assert_eq!(lines.len(), 4, "{}", stdout);
+    assert!(
+        lines[0].contains(": Rising Sun: Am (confidence"),
+        "{}",
+        stdout
+    );
+    assert!(lines[0].ends_with(", written"), "{}", stdout);
+    assert!(
+        lines[1].contains(": Let It Be: C (confidence"),
+        "{}",
+        stdout
+    );
+    assert!(lines[2].contains(": Seesaw: ambiguous, "), "{}", stdout);
+    assert!(lines[3].contains(", the song has !key(D)"), "{}", stdout);
+
+    let written = fs::read_to_string(&songs).unwrap();
+    assert!(written.starts_with("# Rising Sun\n\n!key(Am)\n\n1. `Am`There"));
+    assert!(written.contains("## The Beatles\n\n!key(C)\n\n!tempo(72)\n"));
+    assert!(written.contains("# Seesaw\n\n`G`Up"));
+    assert_eq!(written.matches("!key(").count(), 3);
+
+    // The keys are now a part of the songs
+    let output = Command::new(env!("CARGO_BIN_EXE_bard"))
+        .args(["util", "detect-key"])
+        .arg(&songs)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let existing: Vec<_> = stdout
+        .lines()
+        .map(|line| line.contains(", the song has !key("))
+        .collect();
+    assert_eq!(existing, [true, true, false, true], "{}", stdout);
+}
+
+#[test]
+fn detect_key_custom_notation() {
+    let build = TestProject::new("detect-key-custom-notation")
+        .song("song.md", "# Song\n\n1. `Lam`Lyrics `Do`more `Mi`lyrics\n")
+        .settings(|toml| {
+            toml.set(
+                "notations",
+                toml! {
+                    [solfege]
+                    notes = ["Do", "Do#", "Re", "Mib", "Mi", "Fa", "Fa#", "Sol", "Lab", "La", "Sib", "Si"]
+                },
+            );
+        })
+        .build()
+        .unwrap();
+
+    // The notation is looked up in the project
+    let output = Command::new(env!("CARGO_BIN_EXE_bard"))
+        .args(["util", "detect-key", "--notation", "solfege", "song.md"])
+        .current_dir(build.dir_songs())
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(output.status.success(), "{}", stderr);
+    assert!(stdout.contains(": Song: Lam (confidence"), "{}", stdout);
+
+    // Without a project, only the built-in notations are available,
+    // the project isn't found from its subdirectory in strict mode
+    let outside = build.dir_output().join("outside");
+    fs::create_dir_all(&outside).unwrap();
+    fs::copy(build.dir_songs().join("song.md"), outside.join("song.md")).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_bard"))
+        .args(["util", "detect-key", "--notation", "solfege", "song.md"])
+        .current_dir(&outside)
+        .env("BARD_STRICT_PROJECT_DIR", "1")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Unknown notation 'solfege'"), "{}", stderr);
+}
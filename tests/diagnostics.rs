@@ -0,0 +1,58 @@
+use bard::diag::{Phase, Severity};
+use bard::parser::DiagKind;
+
+mod util_ng;
+pub use util_ng::*;
+
+const SONG: &str = r#"# Song
+
+!duration(forever)
+
+1. Lyrics.
+"#;
+
+#[test]
+fn diagnostics_phases() {
+    let build = TestProject::new("diagnostics-phases")
+        .song("song.md", SONG)
+        .output("songbook.html")
+        .template("songbook.html", "songbook.hbs", "{{ book.titel }}")
+        .build()
+        .unwrap();
+
+    build.unwrap();
+    build.assert_parser_diag(DiagKind::InvalidDuration {
+        value: "forever".into(),
+    });
+
+    let diags = build.app().diags();
+    let phases: Vec<_> = diags.iter().map(|d| (d.phase, d.severity)).collect();
+    assert_eq!(
+        phases,
+        [
+            (Phase::Parse, Severity::Warning),
+            (Phase::Render, Severity::Warning)
+        ]
+    );
+    assert_eq!(diags[0].file.as_deref(), Some("songs/song.md".as_ref()));
+    assert_eq!(diags[0].line, Some(3));
+    assert!(diags[1].message.contains("book.titel"));
+    assert_eq!(build.app().warnings(), 2);
+}
+
+#[cfg(unix)]
+#[test]
+fn diagnostics_script() {
+    let build = TestProject::new("diagnostics-script")
+        .output("songbook.html")
+        .settings(|toml| toml.set("hooks", toml! { post_build = "exit 3" }))
+        .postprocess(true)
+        .build()
+        .unwrap();
+
+    build.unwrap_err();
+    let diags = build.app().diags();
+    assert_eq!(diags.len(), 1);
+    assert_eq!(diags[0].phase, Phase::Script);
+    assert!(diags[0].is_error());
+}
@@ -0,0 +1,86 @@
+mod util_ng;
+pub use util_ng::*;
+
+const SONG: &str = r#"# Song
+
+!tempo(120) !time(3/4)
+
+1. Lyrics !time(6/8) more lyrics.
+"#;
+
+fn prepare_project(name: &str) -> TestProject {
+    TestProject::new(name)
+        .song("song.md", SONG)
+        .settings(|toml| toml.set("tex", "none"))
+        .output("songbook.pdf")
+        .output("songbook.html")
+        .output("songbook.json")
+        .output("songbook.xml")
+}
+
+#[test]
+fn directives_default_templates() {
+    let build = prepare_project("directives-default-templates")
+        .build()
+        .unwrap();
+
+    let html = build.read_output(".html");
+    assert!(html.contains(r#"<span class="directive directive-tempo">120&nbsp;BPM</span>"#));
+    assert!(html.contains(r#"<span class="directive directive-time">3/4</span>"#));
+    assert!(
+        html.contains(r#"Lyrics <span class="directive directive-time">6/8</span> more lyrics."#)
+    );
+
+    let tex = build.read_output(".tex");
+    assert!(tex.contains(r"\directive{120~BPM}\quad\directive{3/4}\par"));
+    assert!(tex.contains(r"Lyrics \directive{6/8}~more~lyrics."));
+}
+
+#[test]
+fn directives_ast() {
+    let build = prepare_project("directives-ast").build().unwrap();
+
+    let json = build.read_output(".json");
+    let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+    let song = &json["songs"][0];
+    assert_eq!(song["tempo"], "120");
+    assert_eq!(song["time"], "3/4");
+    let inlines = song["blocks"][0]["paragraphs"][0].as_array().unwrap();
+    assert_eq!(inlines[1]["type"], "i-directive");
+    assert_eq!(inlines[1]["kind"], "time");
+    assert_eq!(inlines[1]["value"], "6/8");
+
+    let xml = build.read_output(".xml");
+    assert!(
+        xml.contains(r#"<song chord-pos="before" dir="ltr" id="song" notation="english" tempo="120" time="3/4" title="Song">"#)
+    );
+    assert!(xml.contains(r#"<directive kind="time" prefix_space=" " value="6/8"/>"#));
+}
+
+#[test]
+fn directives_key() {
+    let build = TestProject::new("directives-key")
+        .song(
+            "song.md",
+            "# Song\n\n!key(Am) !tempo(90)\n\n1. `Am`Lyrics.\n",
+        )
+        .settings(|toml| toml.set("tex", "none"))
+        .output("songbook.pdf")
+        .output("songbook.html")
+        .output("songbook.xml")
+        .build()
+        .unwrap();
+
+    let html = build.read_output(".html");
+    assert!(html.contains(
+        r#"<span class="directive directive-key">Key:&nbsp;Am</span> &emsp; <span class="directive directive-tempo">"#
+    ));
+
+    let tex = build.read_output(".tex");
+    assert!(tex.contains(r"\directive{Key:~Am}\quad\directive{90~BPM}\par"));
+
+    let xml = build.read_output(".xml");
+    assert!(xml.contains(
+        r#"<song chord-pos="before" dir="ltr" id="song" key="Am" notation="english" tempo="90" title="Song">"#
+    ));
+}
@@ -0,0 +1,118 @@
+use std::process::{Command, Output};
+
+mod util_ng;
+pub use util_ng::*;
+
+fn run_doctor(build: &TestBuild, bard_tex: &str) -> (Output, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_bard"))
+        .args(["util", "doctor"])
+        .env("BARD_TEX", bard_tex)
+        .current_dir(&build.unwrap().project_dir)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout.clone()).unwrap();
+    (output, stdout)
+}
+
+#[test]
+fn doctor_tex_mock() {
+    let build = TestProject::new("doctor-tex-mock")
+        .settings(|toml| toml.set("tex", "none"))
+        .output("songbook.pdf")
+        .build()
+        .unwrap();
+
+    let tex_mock = env!("CARGO_BIN_EXE_tex-mock");
+    let (output, stdout) = run_doctor(&build, &format!("xelatex:{}", tex_mock));
+    assert!(output.status.success(), "{}", stdout);
+    assert!(
+        stdout.contains("[pass] Selected TeX distribution: xelatex:"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("[pass] TeX smoke test"), "{}", stdout);
+    assert!(
+        stdout.contains("[pass] Output directory") && stdout.contains("is writable"),
+        "{}",
+        stdout
+    );
+    assert!(!stdout.contains("[FAIL]"), "{}", stdout);
+}
+
+#[test]
+fn doctor_tex_missing() {
+    let build = TestProject::new("doctor-tex-missing")
+        .settings(|toml| toml.set("tex", "none"))
+        .output("songbook.pdf")
+        .build()
+        .unwrap();
+
+    let (output, stdout) = run_doctor(&build, "xelatex:/nonexistent/xelatex");
+    assert!(!output.status.success(), "{}", stdout);
+    assert!(
+        stdout.contains("[FAIL] Selected TeX distribution"),
+        "{}",
+        stdout
+    );
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("checks failed"), "{}", stderr);
+}
+
+#[test]
+fn doctor_tex_none() {
+    let build = TestProject::new("doctor-tex-none")
+        .settings(|toml| toml.set("tex", "none"))
+        .output("songbook.pdf")
+        .build()
+        .unwrap();
+
+    let (output, stdout) = run_doctor(&build, "none");
+    assert!(output.status.success(), "{}", stdout);
+    assert!(
+        stdout.contains("[warn] Selected TeX distribution: none"),
+        "{}",
+        stdout
+    );
+    assert!(!stdout.contains("TeX smoke test"), "{}", stdout);
+}
+
+#[test]
+fn doctor_parent_dir() {
+    let build = TestProject::new("doctor-parent-dir")
+        .settings(|toml| toml.set("tex", "none"))
+        .output("songbook.html")
+        .build()
+        .unwrap();
+
+    let run = |strict: &str| {
+        let output = Command::new(env!("CARGO_BIN_EXE_bard"))
+            .args(["util", "doctor"])
+            .env("BARD_TEX", "none")
+            .env("BARD_STRICT_PROJECT_DIR", strict)
+            .current_dir(build.dir_songs())
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        (stdout, stderr)
+    };
+
+    // The project is only located once
+    let (stdout, stderr) = run("0");
+    assert!(stdout.contains("[pass] Project"), "{}", stdout);
+    assert_eq!(
+        stderr.matches("bard.toml in parent directory").count(),
+        1,
+        "{}",
+        stderr
+    );
+
+    // The actual error is reported
+    let (stdout, _) = run("1");
+    assert!(
+        stdout.contains("[warn] Project: Found bard.toml in parent directory"),
+        "{}",
+        stdout
+    );
+    assert!(!stdout.contains("no bard.toml found"), "{}", stdout);
+}
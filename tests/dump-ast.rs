@@ -0,0 +1,74 @@
+use std::process::Command;
+
+use serde_json::{json, Value};
+
+mod util_ng;
+pub use util_ng::*;
+
+const SONG: &str = r#"# Song
+
+!+2
+
+1. `C`Lyrics `Am`more lyrics.
+"#;
+
+#[test]
+fn dump_ast() {
+    let build = TestProject::new("dump-ast")
+        .song("song.md", SONG)
+        .song("german.md", "# Song\n\n!+1\n\n1. `H`Lyrics.\n")
+        .song("bad.md", "# Song\n\n!+1\n\n1. `X`Lyrics.\n")
+        .build()
+        .unwrap();
+
+    let run = |file: &str, args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_bard"))
+            .args(["util", "dump-ast"])
+            .args(args)
+            .arg(build.dir_songs().join(file))
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        (output.status.success(), stdout, stderr)
+    };
+    let chords = |json: &Value| -> Vec<Value> {
+        json[0]["blocks"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .flat_map(|block| block["paragraphs"][0].as_array().unwrap())
+            .map(|inline| inline["chord"].clone())
+            .collect()
+    };
+
+    let (success, stdout, stderr) = run("song.md", &[]);
+    assert!(success, "{}", stderr);
+    let json: Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(json[0]["title"], "Song");
+    assert_eq!(chords(&json), [json!("D"), json!("Bm")]);
+
+    // Transposition extensions are kept with --no-xpose
+    let (success, stdout, stderr) = run("song.md", &["--no-xpose"]);
+    assert!(success, "{}", stderr);
+    let json: Value = serde_json::from_str(&stdout).unwrap();
+    let xpose = &json[0]["blocks"][0]["paragraphs"][0][0];
+    assert_eq!(xpose["type"], "i-transpose");
+    assert_eq!(xpose["t-transpose"], 2);
+    assert_eq!(chords(&json)[1..], [json!("C"), json!("Am")]);
+
+    let (success, stdout, stderr) = run("german.md", &["--notation", "german"]);
+    assert!(success, "{}", stderr);
+    let json: Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(chords(&json), [json!("C")]);
+
+    let (success, stdout, stderr) = run("bad.md", &[]);
+    assert!(!success);
+    assert!(stdout.is_empty());
+    assert!(stderr.contains("Unrecognized chord: X"), "{}", stderr);
+    assert!(stderr.contains("Could not parse file"), "{}", stderr);
+
+    let (success, _, stderr) = run("song.md", &["--notation", "klingon"]);
+    assert!(!success);
+    assert!(stderr.contains("Unknown notation 'klingon'"), "{}", stderr);
+}
@@ -0,0 +1,158 @@
+use bard::parser::DiagKind;
+
+mod util_ng;
+pub use util_ng::*;
+
+const SONGS: &str = r#"# First Song
+
+!tempo(120) !duration(3:45)
+
+1. Lyrics.
+
+# Second Song
+
+!duration(95)
+
+1. Lyrics.
+
+# Third Song
+
+1. Lyrics.
+"#;
+
+#[test]
+fn durations() {
+    let build = TestProject::new("durations")
+        .song("songs.md", SONGS)
+        .settings(|toml| toml.set("tex", "none"))
+        .output("songbook.pdf")
+        .output("songbook.json")
+        .output("songbook.xml")
+        .output("songbook.html")
+        .build()
+        .unwrap();
+
+    let json = build.read_output(".json");
+    let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+    let songs = &json["songs"];
+    assert_eq!(songs[0]["duration"]["seconds"], 225);
+    assert_eq!(songs[0]["duration"]["display"], "3:45");
+    assert_eq!(songs[1]["duration"]["display"], "1:35");
+    assert!(songs[2]["duration"].is_null());
+    assert_eq!(json["total_duration"]["seconds"], 320);
+    assert_eq!(json["total_duration"]["display"], "5:20");
+
+    let xml = build.read_output(".xml");
+    assert!(xml.contains(r#"<songbook notation="english" total-duration="5:20">"#));
+    assert!(xml.contains(
+        r#"<song chord-pos="before" dir="ltr" duration="3:45" id="first-song" notation="english" tempo="120" title="First Song">"#
+    ));
+
+    let html = build.read_output(".html");
+    assert!(html.contains(
+        r#"<span class="directive directive-tempo">120&nbsp;BPM</span> &emsp; <span class="directive directive-duration">3:45</span>"#
+    ));
+
+    let tex = build.read_output(".tex");
+    assert!(tex.contains(r"\directive{120~BPM}\quad\directive{3:45}\par"));
+    assert!(tex.contains(r"\hfill\directive{1:35}\par"));
+}
+
+#[test]
+fn durations_setlist() {
+    let build = TestProject::new("durations-setlist")
+        .song("songs.md", SONGS)
+        .text_asset("setlist.txt", "Third Song\nSecond Song\n")
+        .output_toml(toml! {
+            file = "songbook.json"
+            setlist = "output/setlist.txt"
+        })
+        .build()
+        .unwrap();
+
+    let json = build.read_output(".json");
+    let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(json["total_duration"]["display"], "1:35");
+}
+
+#[test]
+fn durations_none() {
+    let build = TestProject::new("durations-none")
+        .song("song.md", "# Song\n\n1. Lyrics.\n")
+        .output("songbook.json")
+        .build()
+        .unwrap();
+
+    let json = build.read_output(".json");
+    let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert!(json.get("total_duration").is_none());
+}
+
+#[test]
+fn durations_invalid() {
+    let build = TestProject::new("durations-invalid")
+        .song("song.md", "# Song\n\n!duration(3:99)\n\n1. Lyrics.\n")
+        .output("songbook.json")
+        .build()
+        .unwrap();
+
+    build.unwrap();
+    build.assert_parser_diag(DiagKind::InvalidDuration {
+        value: "3:99".into(),
+    });
+
+    let diag = build.app().parser_diags().lock()[0].clone();
+    assert!(!diag.is_error());
+    assert!(diag.file.ends_with("song.md"));
+    assert_eq!(diag.line, 3);
+}
+
+#[test]
+fn durations_metadata() {
+    let songs = indoc! {r#"
+        # First Song
+
+        ```meta
+        duration = "3:45"
+        ```
+
+        1. Lyrics.
+
+        # Second Song
+
+        ```meta
+        duration = 95
+        ```
+
+        !duration(2:00)
+
+        1. Lyrics.
+
+        # Third Song
+
+        ```meta
+        duration = "3:99"
+        ```
+
+        1. Lyrics.
+    "#};
+
+    let build = TestProject::new("durations-metadata")
+        .song("songs.md", songs)
+        .output("songbook.json")
+        .build()
+        .unwrap();
+
+    let json = build.read_output(".json");
+    let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+    let songs = &json["songs"];
+    assert_eq!(songs[0]["duration"]["display"], "3:45");
+    // The directive takes precedence
+    assert_eq!(songs[1]["duration"]["display"], "2:00");
+    assert!(songs[2]["duration"].is_null());
+    assert_eq!(json["total_duration"]["display"], "5:45");
+
+    build.assert_parser_diag(DiagKind::InvalidDuration {
+        value: "3:99".into(),
+    });
+}
@@ -0,0 +1,55 @@
+use bard::parser::DiagKind;
+
+mod util_ng;
+pub use util_ng::*;
+
+const SONG: &str = r#"# Song
+
+!duration(forever)
+
+1. Lyrics.
+"#;
+
+fn prepare_project(name: &str, song: &str, fail_on_warnings: bool) -> TestProject {
+    TestProject::new(name)
+        .song("song.md", song)
+        .fail_on_warnings(fail_on_warnings)
+        .output("songbook.html")
+}
+
+#[test]
+fn fail_on_warnings() {
+    let build = prepare_project("fail-on-warnings", SONG, true)
+        .build()
+        .unwrap();
+
+    build.assert_parser_diag(DiagKind::InvalidDuration {
+        value: "forever".into(),
+    });
+    let err = format!("{:#}", build.unwrap_err());
+    assert!(
+        err.contains("1 warning was emitted, failing because of --fail-on-warnings"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn fail_on_warnings_off() {
+    let build = prepare_project("fail-on-warnings-off", SONG, false)
+        .build()
+        .unwrap();
+
+    build.unwrap();
+    assert_eq!(build.app().warnings(), 1);
+}
+
+#[test]
+fn fail_on_warnings_clean() {
+    let build = prepare_project("fail-on-warnings-clean", "# Song\n\n1. Lyrics.\n", true)
+        .build()
+        .unwrap();
+
+    build.unwrap();
+    assert_eq!(build.app().warnings(), 0);
+}
@@ -0,0 +1,92 @@
+use serde_json::Value;
+
+mod util_ng;
+pub use util_ng::*;
+
+const SONGS: &str = r#"# Zebra
+
+> `Am`Chorus *comes* `C`first,\
+> second line
+
+1. Verse.
+
+# Apple
+
+- A note
+
+<div>Html</div>
+
+1. `G`
+`G` `C`\
+Yellow `D`submarine
+
+# Empty
+
+# Mango
+
+1. !tempo(120)
+Lyrics of mango
+"#;
+
+#[test]
+fn first_lines() {
+    let build = TestProject::new("first-lines")
+        .song("songs.md", SONGS)
+        .output("songbook.json")
+        .output("songbook.xml")
+        .build()
+        .unwrap();
+    build.unwrap();
+
+    let json = build.read_output(".json");
+    let json: Value = serde_json::from_str(&json).unwrap();
+    let first_lines: Vec<_> = json["songs_sorted"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|song| {
+            (
+                song["title"].as_str().unwrap(),
+                song["first_line"].as_str().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        first_lines,
+        [
+            ("Apple", "Yellow submarine"),
+            ("Empty", ""),
+            ("Mango", "Lyrics of mango"),
+            ("Zebra", "Chorus comes first,"),
+        ]
+    );
+
+    let index: Vec<_> = json["songs_by_first_line"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|song| {
+            (
+                song["first_line"].as_str().unwrap(),
+                song["idx"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        index,
+        [
+            ("Chorus comes first,", 0),
+            ("Lyrics of mango", 3),
+            ("Yellow submarine", 1),
+        ]
+    );
+
+    let xml = build.read_output(".xml");
+    assert!(
+        xml.contains(
+            r#"<song-ref first-line="Yellow submarine" id="apple" idx="1" title="Apple"/>"#
+        ),
+        "{}",
+        xml
+    );
+}
@@ -0,0 +1,29 @@
+use std::fs;
+
+mod util_ng;
+pub use util_ng::*;
+
+#[test]
+fn fonts_fallback() {
+    let build = TestProject::new("fonts-fallback")
+        .settings(|toml| toml.set("tex", "none"))
+        .output("songbook.html")
+        .output("songbook.pdf")
+        .build()
+        .unwrap();
+    let project = build.unwrap();
+    assert!(project.fonts_available());
+    assert!(build
+        .read_output(".html")
+        .contains(r#"<link rel="stylesheet" href="fonts/fonts.css">"#));
+    assert!(build
+        .read_output(".tex")
+        .contains(r"\input{./fonts/fonts.tex}"));
+
+    // Without the default fonts, the templates don't refer to them
+    fs::remove_dir_all(build.dir_output().join("fonts")).unwrap();
+    let project = bard::bard_make_at(build.app(), &project.project_dir).unwrap();
+    assert!(!project.fonts_available());
+    assert!(!build.read_output(".html").contains("fonts.css"));
+    assert!(!build.read_output(".tex").contains("fonts.tex"));
+}
@@ -0,0 +1,68 @@
+use bard::util_cmd::{grep, GrepOpts};
+
+mod util_ng;
+pub use util_ng::*;
+
+const SONGS: &str = r#"# Amazing Grace
+
+1. `G`Amazing `C`grace, how `G`sweet the sound
+That saved a *wretch* like me
+
+# Other Song
+
+> `Am`Amazing `F`lyrics `C`here `G`too.
+"#;
+
+fn prepare_project(name: &str) -> TestBuild {
+    TestProject::new(name)
+        .song("songs.md", SONGS)
+        .output("songbook.html")
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn grep_text() {
+    let build = prepare_project("grep-text");
+    let project = build.unwrap();
+
+    let opts = GrepOpts {
+        regex: "(?i)amazing gr|wretch like",
+        chords: false,
+    };
+    let matches = grep(build.app(), &project.project_dir, &opts).unwrap();
+    let matches: Vec<_> = matches
+        .iter()
+        .map(|m| format!("{}:{}: {}: {}", m.file.display(), m.line, m.song, m.text))
+        .collect();
+    assert_eq!(
+        matches,
+        [
+            "songs/songs.md:1: Amazing Grace: Amazing Gr",
+            "songs/songs.md:3: Amazing Grace: Amazing gr",
+            "songs/songs.md:4: Amazing Grace: wretch like",
+        ]
+    );
+}
+
+#[test]
+fn grep_chords() {
+    let build = prepare_project("grep-chords");
+    let project = build.unwrap();
+
+    let opts = GrepOpts {
+        regex: "F C G",
+        chords: true,
+    };
+    let matches = grep(build.app(), &project.project_dir, &opts).unwrap();
+    assert_eq!(matches.len(), 1);
+    assert_eq!(matches[0].line, 8);
+    assert_eq!(matches[0].song, "Other Song");
+
+    let opts = GrepOpts {
+        regex: "(",
+        chords: true,
+    };
+    let err = grep(build.app(), &project.project_dir, &opts).unwrap_err();
+    assert_eq!(err.to_string(), "Invalid regex: '('");
+}
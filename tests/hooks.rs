@@ -0,0 +1,57 @@
+#![cfg(unix)]
+
+use std::fs;
+
+mod util_ng;
+pub use util_ng::*;
+
+fn prepare_project(name: &str, postprocess: bool, hooks: toml::Table) -> TestProject {
+    TestProject::new(name)
+        .postprocess(postprocess)
+        .output("songbook.html")
+        .settings(move |toml| {
+            toml.set("hooks", hooks);
+        })
+}
+
+fn hooks() -> toml::Table {
+    toml! {
+        pre_build = "echo \"$OUTPUT_DIR\" > pre.txt; test ! -e \"$OUTPUT_DIR/songbook.html\""
+        post_build = ["sh", "-c", "test -e \"$OUTPUT_DIR/songbook.html\" && echo \"$PROJECT_DIR\" > post.txt"]
+    }
+}
+
+#[test]
+fn project_hooks() {
+    let build = prepare_project("hooks", true, hooks()).build().unwrap();
+    let project_dir = &build.unwrap().project_dir;
+
+    let pre = fs::read_to_string(project_dir.join("pre.txt")).unwrap();
+    assert_eq!(pre.trim_end(), build.dir_output().to_str().unwrap());
+    let post = fs::read_to_string(project_dir.join("post.txt")).unwrap();
+    assert_eq!(post.trim_end(), project_dir.to_str().unwrap());
+}
+
+#[test]
+fn project_hooks_no_ps() {
+    let build = prepare_project("hooks-no-ps", false, hooks())
+        .build()
+        .unwrap();
+    let project_dir = &build.unwrap().project_dir;
+
+    assert!(!project_dir.join("pre.txt").exists());
+    assert!(!project_dir.join("post.txt").exists());
+}
+
+#[test]
+fn project_hooks_fail() {
+    let hooks = toml! {
+        pre_build = "echo 'Could not pull' >&2; exit 3"
+        post_build = "touch post.txt"
+    };
+    let build = prepare_project("hooks-fail", true, hooks).build().unwrap();
+    let err = format!("{:#}", build.unwrap_err());
+    assert!(err.contains("pre_build hook failed"), "{}", err);
+    assert!(err.contains("Could not pull"), "{}", err);
+    assert!(err.contains("code: 3"), "{}", err);
+}
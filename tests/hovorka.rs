@@ -0,0 +1,76 @@
+mod util_ng;
+pub use util_ng::*;
+
+const SONG: &str = r#"# Bláznova & ukolébavka
+
+!!czech
+
+1. Ta `Am`noc je `B7`dlouhá,\
+a `C`den je krátký
+
+> `G`Refrén *zní* `Am`tu
+
+### Mezihra
+`G` `B`
+
+!>
+
+2. Druhá `E`sloka\
+!>
+"#;
+
+#[test]
+fn hovorka_output() {
+    let build = TestProject::new("hovorka-output")
+        .song("song.md", SONG)
+        .output_toml(toml! {
+            file = "songbook.xml"
+            format = "hovorka"
+        })
+        .build()
+        .unwrap();
+
+    build.unwrap();
+    let xml = build.read_output("songbook.xml");
+    let song = &xml[xml.find("<song>").unwrap()..];
+    let song = &song[..song.find("</song>").unwrap()];
+    assert_eq!(
+        song,
+        r#"<song>
+  <ID><![CDATA[song-0]]></ID>
+  <title><![CDATA[Bláznova & ukolébavka]]></title>
+  <author><![CDATA[My Songbook]]></author>
+  <groupname><![CDATA[[local]]]></groupname>
+  <songtext><![CDATA[
+1. Ta [Am]noc je [B7(H7)]dlouhá,
+a [C]den je krátký
+
+R: [G]Refrén zní [Am]tu
+
+Mezihra [G] [B(H)]
+
+R:
+
+2. Druhá [E]sloka
+R:
+]]></songtext>
+"#
+    );
+}
+
+#[test]
+fn hovorka_cdata() {
+    let build = TestProject::new("hovorka-cdata")
+        .song("song.md", "# Song ]]>\n\n1. Lyrics ]]> `C`end\n")
+        .output_toml(toml! {
+            file = "songbook.xml"
+            format = "hovorka"
+        })
+        .build()
+        .unwrap();
+
+    build.unwrap();
+    let xml = build.read_output("songbook.xml");
+    assert!(xml.contains("<title><![CDATA[Song ]]]]><![CDATA[>]]></title>"));
+    assert!(xml.contains("\n1. Lyrics ]]]]><![CDATA[> [C]end\n"));
+}
@@ -0,0 +1,80 @@
+use bard::diag::Phase;
+use bard::render::pdf;
+
+mod util_ng;
+pub use util_ng::*;
+
+const SONG: &str = r#"# Song
+
+<div class="note">Capo 2</div>
+
+1. Some <small>small</small> <span>text</span>.
+"#;
+
+#[test]
+fn html_content_warn() {
+    let build = TestProject::new("html-content-warn")
+        .song("songs.md", SONG)
+        .settings(|toml| toml.set("tex", "none"))
+        .output_toml(toml! {
+            file = "songbook.pdf"
+            html_content = "warn"
+        })
+        .template_prefix_default(
+            "songbook.pdf",
+            "pdf.hbs",
+            r#"{{#*inline "h-small"}}\small{}{{/inline}}"#,
+            &pdf::DEFAULT_TEMPLATE,
+        )
+        .output("songbook.html")
+        .build()
+        .unwrap();
+
+    build.unwrap();
+    let diags: Vec<_> = build
+        .app()
+        .diags()
+        .into_iter()
+        .filter(|d| d.phase == Phase::Render)
+        .collect();
+    assert_eq!(diags.len(), 1);
+    let diag = &diags[0];
+    assert_eq!(diag.file.as_deref(), Some("songs/songs.md".as_ref()));
+    assert_eq!(diag.line, Some(1));
+    assert!(
+        diag.message
+            .ends_with("HTML tags left out of the output: <div>, <span>"),
+        "{}",
+        diag.message
+    );
+
+    // Text in the HTML block is still ignored
+    build.assert_parser_diag(bard::parser::DiagKind::HtmlIgnoredText {
+        text: "Capo 2".into(),
+    });
+    assert!(!build.read_output(".tex").contains("Capo"));
+}
+
+#[test]
+fn html_content_text() {
+    let build = TestProject::new("html-content-text")
+        .song("songs.md", SONG)
+        .settings(|toml| toml.set("tex", "none"))
+        .output_toml(toml! {
+            file = "text.pdf"
+            html_content = "text"
+        })
+        .output("ignore.pdf")
+        .output("songbook.html")
+        .build()
+        .unwrap();
+
+    build.unwrap();
+    assert_eq!(build.app().warnings(), 0);
+
+    let tex = build.read_output("text.tex");
+    assert!(tex.contains("Capo~2"));
+    assert!(tex.contains("Some~small~text."));
+    assert!(!build.read_output("ignore.tex").contains("Capo"));
+    assert!(!build.read_output(".html").contains("Capo"));
+}
@@ -0,0 +1,77 @@
+mod util_ng;
+pub use util_ng::*;
+
+const SMOL_PNG: &str = "iVBORw0KGgoAAAANSUhEUgAAAQAAAAEAAQMAAABmvDolAAAAA1BMVEW10NBjBBbqAAAAH0lEQVRoge3BAQ0AAADCoPdPbQ43oAAAAAAAAAAAvg0hAAABmmDh1QAAAABJRU5ErkJggg==";
+
+fn prepare_project(name: &str, cover_image: &str, url: &str) -> TestProject {
+    let (cover_image, url) = (cover_image.to_string(), url.to_string());
+    TestProject::new(name)
+        .song("songs.md", "# Song\n\n1. Lyrics.\n")
+        .binary_asset("cover.png", SMOL_PNG)
+        .settings(move |toml| {
+            let book = toml["book"].as_table_mut().unwrap();
+            book.insert("description".into(), "Songs & more.".into());
+            book.insert("author".into(), "Jane Doe".into());
+            book.insert("url".into(), url.into());
+            book.insert("cover_image".into(), cover_image.into());
+        })
+        .output("songbook.html")
+}
+
+#[test]
+fn html_meta() {
+    let build = prepare_project("html-meta", "cover.png", "https://example.com/songs/")
+        .build()
+        .unwrap();
+
+    let project = build.unwrap();
+    assert!(!project.book_section().contains_key("description"));
+
+    let html = build.read_output(".html");
+    assert!(html.contains(r#"<meta name="description" content="Songs &amp; more.">"#));
+    assert!(html.contains(r#"<meta property="og:description" content="Songs &amp; more.">"#));
+    assert!(html.contains(r#"<meta name="author" content="Jane Doe">"#));
+    assert!(html.contains(r#"<meta property="og:url" content="https://example.com/songs/">"#));
+    assert!(html
+        .contains(r#"<meta property="og:image" content="https://example.com/songs/cover.png">"#));
+    assert!(html.contains(r#"<meta property="og:image:width" content="256">"#));
+    assert!(html.contains(r#"<meta property="og:image:height" content="256">"#));
+}
+
+#[test]
+fn html_meta_missing_cover() {
+    let build = prepare_project(
+        "html-meta-missing-cover",
+        "missing.png",
+        "https://example.com/",
+    )
+    .build()
+    .unwrap();
+    let html = build.read_output(".html");
+    assert!(html.contains(r#"<meta name="author" content="Jane Doe">"#));
+    assert!(!html.contains("og:image"));
+
+    let build = prepare_project(
+        "html-meta-missing-cover-fail",
+        "missing.png",
+        "https://example.com/",
+    )
+    .fail_on_warnings(true)
+    .build()
+    .unwrap();
+    let err = format!("{:#}", build.unwrap_err());
+    assert!(err.contains("1 warning was emitted"), "{}", err);
+}
+
+#[test]
+fn html_meta_invalid_url() {
+    let build = prepare_project("html-meta-invalid-url", "cover.png", "ftp://example.com/")
+        .build()
+        .unwrap();
+    let err = format!("{:#}", build.unwrap_err());
+    assert!(
+        err.contains("Invalid book.url 'ftp://example.com/', expected an http:// or https:// URL"),
+        "{}",
+        err
+    );
+}
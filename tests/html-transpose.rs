@@ -0,0 +1,23 @@
+mod util_ng;
+pub use util_ng::*;
+
+#[test]
+fn html_transpose() {
+    let build = TestProject::new("html-transpose")
+        .song(
+            "songs.md",
+            "# Song\n\n1. `Am7`Lyrics `C/G`more `X`lyrics.\n\n# No Chords\n\n1. Lyrics.\n",
+        )
+        .output("songbook.html")
+        .build()
+        .unwrap();
+
+    let html = build.read_output(".html");
+    assert!(html.contains(r#"<td data-root="9" data-suffix="m7">Am7</td>"#));
+    assert!(html.contains("<td>C/G</td>"));
+    assert!(html.contains("<td>X</td>"));
+    assert!(html.contains(
+        r#"var tones = ["C", "C#", "D", "Eb", "E", "F", "F#", "G", "Ab", "A", "Bb", "B"];"#
+    ));
+    assert_eq!(html.matches(r#"<div class="transpose faint">"#).count(), 2);
+}
@@ -68,6 +68,7 @@ fn project_html_reserved_tags() {
     let build = TestProject::new("html-reserved-tags")
         .output("songbook.html")
         .song("song.md", song)
+        .strict(true)
         .build()
         .unwrap();
 
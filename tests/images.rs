@@ -0,0 +1,87 @@
+use std::fs;
+use std::path::PathBuf;
+
+use bard::parser::DiagKind;
+
+mod util_ng;
+pub use util_ng::*;
+
+const SMOL_PNG: &str = "iVBORw0KGgoAAAANSUhEUgAAAQAAAAEAAQMAAABmvDolAAAAA1BMVEW10NBjBBbqAAAAH0lEQVRoge3BAQ0AAADCoPdPbQ43oAAAAAAAAAAAvg0hAAABmmDh1QAAAABJRU5ErkJggg==";
+
+const SONG: &str = r#"# Song
+
+1. Lyrics ![qr {.qr .right}](smol.png)
+
+![wide](smol.png "fullwidth")
+
+2. More lyrics ![typo](smol.png "cetner")
+"#;
+
+fn prepare_project(name: &str) -> TestProject {
+    TestProject::new(name)
+        .song("song.md", SONG)
+        .binary_asset("smol.png", SMOL_PNG)
+        .settings(|toml| {
+            toml.set("tex", "none");
+            let book = toml["book"].as_table_mut().unwrap();
+            book.insert("image_classes".into(), toml::Value::from(vec!["qr"]));
+        })
+        .output("songbook.pdf")
+        .output("songbook.html")
+}
+
+#[test]
+fn images_classes() {
+    let build = prepare_project("images-classes").build().unwrap();
+
+    let html = build.read_output(".html");
+    assert!(html.contains(r#"<img class="qr right" src="smol.png" title="qr""#));
+    assert!(html.contains(r#"<img class="fullwidth" src="smol.png" title="wide""#));
+    assert!(html.contains(r#"<img class="cetner" src="smol.png" title="typo""#));
+
+    let tex = build.read_output(".tex");
+    assert!(tex.contains(r"\begin{wrapfigure}"));
+    assert!(tex.contains(r"\includegraphics[width=\linewidth]{smol.png}"));
+    // Unknown classes don't affect the layout in PDF
+    assert!(tex.contains(r"More~lyrics~\includegraphics[width="));
+
+    build.assert_parser_diag(DiagKind::UnknownImageClass {
+        class: "cetner".into(),
+    });
+    let diags = build.app().parser_diags().lock();
+    assert_eq!(diags.len(), 1);
+    assert_eq!(diags[0].line, 7);
+}
+
+#[test]
+fn images_corrupt_only_fails_outputs_using_them() {
+    // The PNG signature and a part of the header only, like a half-downloaded file
+    let truncated = &SMOL_PNG[..24];
+
+    let build = TestProject::new("images-corrupt")
+        .song("good.md", "# Good Song\n\n1. Lyrics.\n")
+        .song("bad.md", "# Bad Song\n\n1. Lyrics ![cover](cover.png)\n")
+        .binary_asset("cover.png", truncated)
+        .text_asset("setlist.txt", "Good Song\n")
+        .output_toml(toml! {
+            file = "good.html"
+            setlist = "output/setlist.txt"
+        })
+        .output("songbook.html")
+        .build()
+        .unwrap();
+
+    // The output without the song is rendered before the one with it fails
+    let good = PathBuf::from(env!("CARGO_TARGET_TMPDIR"))
+        .join("test-projects")
+        .join("images-corrupt")
+        .join("output")
+        .join("good.html");
+    let good = fs::read_to_string(good).unwrap();
+    assert!(good.contains("Good Song"));
+
+    let err = format!("{:#}", build.unwrap_err());
+    assert!(err.contains("songbook.html"), "{}", err);
+    assert!(err.contains("Image in song 'Bad Song'"), "{}", err);
+    assert!(err.contains("cover.png"), "{}", err);
+}
@@ -1,7 +1,10 @@
 mod util;
 use std::fs;
+use std::process::Command;
 
+use bard::app::InitOpts;
 use bard::default_project::DEFAULT_PROJECT;
+use bard::project::Format;
 pub use util::*;
 
 #[test]
@@ -20,9 +23,9 @@ fn init_doesnt_overwrite_1() {
     fs::write(&test_file, test_content).unwrap();
 
     let app = Builder::app(false);
-    bard::bard_init_at(&app, &work_dir).unwrap_err();
+    bard::bard_init_at(&app, &work_dir, &Default::default()).unwrap_err();
 
-    let default_project = DEFAULT_PROJECT.resolve(&work_dir);
+    let default_project = DEFAULT_PROJECT.resolve(&work_dir, true);
     default_project.files().find(|&f| f == test_file).unwrap();
     default_project
         .files()
@@ -42,10 +45,101 @@ fn init_doesnt_overwrite_2() {
     fs::create_dir_all(&out_dir).unwrap();
 
     let app = Builder::app(false);
-    bard::bard_init_at(&app, &work_dir).unwrap_err();
+    bard::bard_init_at(&app, &work_dir, &Default::default()).unwrap_err();
 
-    let default_project = DEFAULT_PROJECT.resolve(&work_dir);
+    let default_project = DEFAULT_PROJECT.resolve(&work_dir, true);
     default_project.dirs().find(|&d| d == out_dir).unwrap();
     assert!(out_dir.exists());
     assert!(!project_file.exists());
 }
+
+#[test]
+fn init_no_fonts() {
+    let work_dir = work_dir("init-no-fonts", true).unwrap();
+    fs::create_dir_all(&work_dir).unwrap();
+
+    let app = Builder::app(false);
+    let opts = InitOpts {
+        no_fonts: true,
+        ..Default::default()
+    };
+    bard::bard_init_at(&app, &work_dir, &opts).unwrap();
+
+    assert!(work_dir.join("bard.toml").exists());
+    assert!(work_dir.join("output").is_dir());
+    assert!(!work_dir.join("output/fonts").exists());
+}
+
+#[test]
+fn init_customized() {
+    let work_dir = work_dir("init-customized", true).unwrap();
+    fs::create_dir_all(&work_dir).unwrap();
+
+    let app = Builder::app(false);
+    let opts = InitOpts {
+        no_fonts: true,
+        title: Some("Choir \"Songs\"".into()),
+        name: Some("choir".into()),
+        format: vec![Format::Html, Format::Json],
+        no_example_song: true,
+        ..Default::default()
+    };
+    bard::bard_init_at(&app, &work_dir, &opts).unwrap();
+
+    let bard_toml = fs::read_to_string(work_dir.join("bard.toml")).unwrap();
+    assert!(
+        bard_toml.contains(r#"title = "Choir \"Songs\"""#),
+        "{}",
+        bard_toml
+    );
+    assert!(!bard_toml.contains("yippie.md"), "{}", bard_toml);
+    assert!(!bard_toml.contains("songbook.pdf"), "{}", bard_toml);
+    assert!(work_dir.join("songs").is_dir());
+    assert!(!work_dir.join("songs/yippie.md").exists());
+
+    fs::write(work_dir.join("songs/song.md"), "# Song\n\n1. Lyrics.\n").unwrap();
+    bard::bard_make_at(&app, &work_dir).unwrap();
+    let html = fs::read_to_string(work_dir.join("output/choir.html")).unwrap();
+    assert!(html.contains(r#"Choir &quot;Songs&quot;"#), "{}", html);
+    assert!(work_dir.join("output/choir.json").exists());
+}
+
+#[test]
+fn init_customized_cli() {
+    let work_dir = work_dir("init-customized-cli", true).unwrap();
+    fs::create_dir_all(&work_dir).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_bard"))
+        .args([
+            "init",
+            "--no-fonts",
+            "--title",
+            "Choir",
+            "--no-example-song",
+        ])
+        .args(["--format", "html,Hovorka"])
+        .current_dir(&work_dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+
+    let bard_toml = fs::read_to_string(work_dir.join("bard.toml")).unwrap();
+    assert!(bard_toml.contains("title = \"Choir\"\n"), "{}", bard_toml);
+    assert!(
+        bard_toml.contains(
+            "[[output]]\nfile = \"songbook.html\"\n\n[[output]]\nfile = \"songbook.xml\"\nformat = \"hovorka\"\n"
+        ),
+        "{}",
+        bard_toml
+    );
+    assert!(!work_dir.join("songs/yippie.md").exists());
+
+    let output = Command::new(env!("CARGO_BIN_EXE_bard"))
+        .args(["init", "--format", "docx"])
+        .current_dir(&work_dir)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("unknown format 'docx'"), "{}", stderr);
+}
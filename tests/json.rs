@@ -0,0 +1,198 @@
+use serde_json::Value;
+
+mod util_ng;
+pub use util_ng::*;
+
+/// The transposition extension is removed and the text around it merged into a single inline.
+const SONG: &str = "# Song\n\n1. `C`Lyrics !+2 more.\n";
+
+/// The `songs` array as rendered by default, ie. pretty-printed in the AST definition order.
+const GOLDEN_PRETTY: &str = r#"  "songs": [
+    {
+      "title": "Song",
+      "id": "song",
+      "title_inlines": [
+        {
+          "type": "i-text",
+          "text": "Song"
+        }
+      ],
+      "subtitles": [],
+      "blocks": [
+        {
+          "type": "b-verse",
+          "label": {
+            "verse": 1
+          },
+          "paragraphs": [
+            [
+              {
+                "type": "i-chord",
+                "chord": "C",
+                "alt_chord": null,
+                "root_chromatic": 0,
+                "suffix": "",
+                "backticks": 1,
+                "baseline": false,
+                "inlines": [
+                  {
+                    "type": "i-text",
+                    "text": "Lyrics more."
+                  }
+                ]
+              }
+            ]
+          ],
+          "first_verse": true,
+          "last_verse": true,
+          "continued": false,
+          "block_index": 0
+        }
+      ],
+      "notation": "english",
+      "tempo": null,
+      "time": null,
+      "duration": null,
+      "key": null,
+      "aliases": [],
+      "metadata": {},
+      "dir": "ltr",
+      "chord_pos": "before",
+      "source": null,
+      "chords_used": [
+        "C"
+      ],
+      "alt_chords_used": []
+    }
+  ],
+"#;
+
+/// The `songs` array as rendered with `json_pretty = false` and `sort_keys = true`.
+const GOLDEN_COMPACT_SORTED: &str = concat!(
+    r#""songs":[{"aliases":[],"alt_chords_used":[],"blocks":[{"block_index":0,"continued":false,"first_verse":true,"label":{"verse":1},"last_verse":true,"#,
+    r#""paragraphs":[[{"alt_chord":null,"backticks":1,"baseline":false,"chord":"C","#,
+    r#""inlines":[{"text":"Lyrics more.","type":"i-text"}],"root_chromatic":0,"suffix":"","type":"i-chord"}]],"type":"b-verse"}],"chord_pos":"before","chords_used":["C"],"dir":"ltr","duration":null,"id":"song","key":null,"metadata":{},"notation":"english","source":null,"subtitles":[],"#,
+    r#""tempo":null,"time":null,"title":"Song","title_inlines":[{"text":"Song","type":"i-text"}]}],"#,
+);
+
+fn assert_keys_sorted(value: &Value) {
+    match value {
+        Value::Object(map) => {
+            let keys: Vec<_> = map.keys().collect();
+            let mut sorted = keys.clone();
+            sorted.sort();
+            assert_eq!(keys, sorted);
+            map.values().for_each(assert_keys_sorted);
+        }
+        Value::Array(items) => items.iter().for_each(assert_keys_sorted),
+        _ => {}
+    }
+}
+
+#[test]
+fn json_default() {
+    let build = TestProject::new("json-default")
+        .song("song.md", SONG)
+        .output("songbook.json")
+        .build()
+        .unwrap();
+
+    let json = build.read_output(".json");
+    assert!(json.contains(GOLDEN_PRETTY), "{}", json);
+}
+
+#[test]
+fn json_compact_sorted() {
+    let build = TestProject::new("json-compact-sorted")
+        .song("song.md", SONG)
+        .output_toml(toml! {
+            file = "songbook.json"
+            json_pretty = false
+            sort_keys = true
+        })
+        .build()
+        .unwrap();
+
+    let json = build.read_output(".json");
+    assert!(!json.contains('\n'));
+    assert!(json.contains(GOLDEN_COMPACT_SORTED), "{}", json);
+    assert_keys_sorted(&serde_json::from_str(&json).unwrap());
+}
+
+#[test]
+fn json_pretty_sorted() {
+    let build = TestProject::new("json-pretty-sorted")
+        .song("song.md", SONG)
+        .output_toml(toml! {
+            file = "songbook.json"
+            sort_keys = true
+        })
+        .build()
+        .unwrap();
+
+    let json = build.read_output(".json");
+    assert!(
+        json.starts_with("{\n  \"aliases\": [],\n  \"back_matter\": [],\n  \"book\": {\n"),
+        "{}",
+        json
+    );
+    assert_keys_sorted(&serde_json::from_str(&json).unwrap());
+}
+
+fn fnv1a(data: &str) -> u64 {
+    data.bytes().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[test]
+fn jsonl() {
+    let build = TestProject::new("jsonl")
+        .song("song.md", SONG)
+        .song("other.md", "# Other Song\n\n1. More lyrics.\n")
+        .output("songbook.json")
+        .output("songbook.jsonl")
+        .build()
+        .unwrap();
+
+    let json: Value = serde_json::from_str(&build.read_output(".json")).unwrap();
+    let jsonl = build.read_output(".jsonl");
+    let lines: Vec<Value> = jsonl
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 4);
+
+    // The header and the song lines put together are the same as the JSON output
+    let header = &lines[0];
+    assert_eq!(header["type"], "header");
+    assert_eq!(header["song_count"], 2);
+    assert_eq!(
+        header["ast_version"],
+        bard::book::version::current().to_string()
+    );
+    let mut header = header.as_object().unwrap().clone();
+    for key in ["type", "ast_version", "song_count"] {
+        header.remove(key);
+    }
+    let songs: Vec<_> = lines[1..3]
+        .iter()
+        .enumerate()
+        .map(|(idx, line)| {
+            assert_eq!(line["type"], "song");
+            assert_eq!(line["idx"], idx);
+            line["song"].clone()
+        })
+        .collect();
+    header.insert("songs".into(), songs.into());
+    // Apart from the output's format
+    header["output"]["format"] = "json".into();
+    assert_eq!(Value::Object(header), json);
+
+    // The checksum covers all the lines before the summary
+    let summary = &lines[3];
+    assert_eq!(summary["type"], "summary");
+    assert_eq!(summary["song_count"], 2);
+    let content = &jsonl[..jsonl.trim_end().rfind('\n').unwrap() + 1];
+    assert_eq!(summary["checksum"], format!("{:016x}", fnv1a(content)));
+}
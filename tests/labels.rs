@@ -0,0 +1,135 @@
+//! Tests of the `[book.labels]` settings in default templates.
+
+mod util_ng;
+pub use util_ng::*;
+
+const SONGS: &str = r#"# Song
+
+1. Verse one.
+
+> Chorus one.
+
+>> Chorus two.
+
+2. Verse two. !>>
+
+### Bridge
+Custom.
+"#;
+
+/// `book` values are added to the `[book]` section.
+fn prepare_project(name: &str, book: Option<toml::Table>) -> TestProject {
+    TestProject::new(name)
+        .song("songs.md", SONGS)
+        .settings(move |toml| {
+            toml.set("tex", "none");
+            if let Some(book) = book {
+                toml["book"].as_table_mut().unwrap().extend(book);
+            }
+        })
+        .output("songbook.pdf")
+        .output("songbook.html")
+}
+
+#[test]
+fn labels_default() {
+    let build = prepare_project("labels-default", None).build().unwrap();
+
+    let html = build.read_output(".html");
+    assert!(html.contains(r#"<span class="label">1.</span>"#));
+    assert!(html.contains(r#"<span class="label">Ch2.</span>"#));
+    assert!(html.contains(r#"<span class="label">Bridge</span>"#));
+    assert!(html.contains("<em> Ch2.</em>"));
+
+    let tex = build.read_output(".tex");
+    assert!(tex.contains(r"\Verse{1.}"));
+    assert!(tex.contains(r"\Verse{Ch1.}"));
+    assert!(tex.contains(r"\emph{Ch2.}"));
+}
+
+#[test]
+fn labels_custom() {
+    let book = toml! {
+        chorus_label = "Refrain"
+
+        [labels]
+        verse_format = "Strophe {n}"
+        chorus_format = "{label} {n}:"
+    };
+    let build = prepare_project("labels-custom", Some(book))
+        .build()
+        .unwrap();
+
+    let html = build.read_output(".html");
+    assert!(html.contains(r#"<span class="label">Strophe 1</span>"#));
+    assert!(html.contains(r#"<span class="label">Refrain 2:</span>"#));
+    assert!(html.contains(r#"<span class="label">Bridge</span>"#));
+    assert!(html.contains("<em> Refrain 2:</em>"));
+
+    let tex = build.read_output(".tex");
+    assert!(tex.contains(r"\Verse{Strophe 1}"));
+    assert!(tex.contains(r"\Verse{Refrain 1:}"));
+    assert!(tex.contains(r"\emph{Refrain 2:}"));
+}
+
+#[test]
+fn labels_invalid() {
+    let book = toml! {
+        [labels]
+        verse_format = "{verse}."
+    };
+    let build = prepare_project("labels-invalid", Some(book))
+        .build()
+        .unwrap();
+
+    let err = format!("{:#}", build.unwrap_err());
+    assert!(err.contains("Invalid verse_format \"{verse}.\""), "{}", err);
+}
+
+#[test]
+fn labels_continued_verse() {
+    let build = TestProject::new("labels-continued-verse")
+        .song(
+            "songs.md",
+            "# Song\n\n1. Verse one.\n\n```\nInterlude\n```\n\n!cont More of verse one.\n",
+        )
+        .settings(|toml| toml.set("tex", "none"))
+        .output("songbook.pdf")
+        .output("songbook.html")
+        .build()
+        .unwrap();
+
+    // The label of a continued verse isn't repeated
+    let html = build.read_output(".html");
+    assert_eq!(html.matches(r#"<span class="label">1.</span>"#).count(), 1);
+    assert!(html.contains(r#"<span class="label"></span>"#));
+    assert!(html.contains("More of verse one."));
+
+    let tex = build.read_output(".tex");
+    assert_eq!(tex.matches(r"\Verse{1.}").count(), 1);
+    assert!(tex.contains(r"\Verse{}"));
+}
+
+#[test]
+fn labels_chorus_range() {
+    let build = TestProject::new("labels-chorus-range")
+        .song(
+            "songs.md",
+            "# Song\n\n> Chorus one.\n\n>> Chorus two.\n\n1. Verse one. !>1-2\n",
+        )
+        .settings(|toml| {
+            toml.set("tex", "none");
+            let book = toml["book"].as_table_mut().unwrap();
+            book.insert(
+                "labels".into(),
+                toml! { chorus_format = "{label}{n}" }.into(),
+            );
+        })
+        .output("songbook.pdf")
+        .output("songbook.html")
+        .build()
+        .unwrap();
+
+    assert!(build.read_output(".html").contains("<em> Ch1–Ch2</em>"));
+    assert!(build.read_output(".tex").contains(r"\emph{Ch1–Ch2}"));
+}
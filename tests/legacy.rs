@@ -0,0 +1,78 @@
+use bard::book::{Block, VerseLabel};
+use bard::project::{Format, Project};
+
+mod util;
+pub use util::*;
+
+#[test]
+fn legacy_1x_project() {
+    let work_dir = prepare_project(TEST_PROJECTS / "legacy-1x", "legacy-1x").unwrap();
+    let app = Builder::app(false);
+    let project = Project::new(&app, &work_dir).unwrap();
+    let settings = &project.settings;
+
+    let outputs: Vec<_> = settings
+        .output
+        .iter()
+        .map(|o| (o.file.file_name().unwrap().to_str().unwrap(), o.format()))
+        .collect();
+    assert_eq!(
+        outputs,
+        [
+            ("songbook.pdf", Format::Pdf),
+            ("songbook.htm", Format::Html),
+            ("songbook.xml", Format::Hovorka),
+        ]
+    );
+
+    let warnings = settings.legacy_warnings().join("\n");
+    for key in [
+        "output.0.process:",
+        "output.0.process_win:",
+        "output.0.format:",
+        "output.1.file:",
+        "output.2.file:",
+        "book.backmatter:",
+        "version:",
+    ] {
+        assert!(warnings.contains(key), "{}", warnings);
+    }
+
+    // The 1.x `> >` chorus style is the same as `>>`
+    let labels: Vec<_> = project.book.songs[0]
+        .blocks
+        .iter()
+        .filter_map(|block| match block {
+            Block::Verse(verse) => Some(&verse.label),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        labels,
+        [
+            &VerseLabel::Verse(1),
+            &VerseLabel::Chorus(Some(1)),
+            &VerseLabel::Chorus(Some(2)),
+        ]
+    );
+}
+
+#[test]
+fn legacy_1x_project_process() {
+    let work_dir = prepare_project(TEST_PROJECTS / "legacy-1x", "legacy-1x-process").unwrap();
+    modify_settings(&work_dir, |mut toml| {
+        let output = toml["output"][1].as_table_mut().unwrap();
+        output.insert("process".into(), "tidy -m {{file}}".into());
+        Ok(toml)
+    })
+    .unwrap();
+
+    let app = Builder::app(false);
+    let err = format!("{:#}", Project::new(&app, &work_dir).unwrap_err());
+    assert!(
+        err.contains("output.1.process: Post-processing commands are no longer supported"),
+        "{}",
+        err
+    );
+    assert!(err.contains("migration-2.html"), "{}", err);
+}
@@ -0,0 +1,75 @@
+mod util_ng;
+pub use util_ng::*;
+
+const TEMPLATE: &str =
+    "<html>  \n<body>\r\n{{#each songs}}<h2>{{ title }}</h2>\t\n{{/each}}</body>\n</html>\n";
+
+fn prepare_project(name: &str) -> TestProject {
+    TestProject::new(name).song("song.md", "# Song\n\n1. Lyrics.\n")
+}
+
+#[test]
+fn line_endings_default() {
+    let build = prepare_project("line-endings-default")
+        .output("songbook.html")
+        .template("songbook.html", "songbook.hbs", TEMPLATE)
+        .build()
+        .unwrap();
+
+    let html = build.read_output(".html");
+    assert_eq!(
+        html,
+        "<html>  \n<body>\r\n<h2>Song</h2>\t\n</body>\n</html>\n"
+    );
+}
+
+#[test]
+fn line_endings_crlf_trim() {
+    let build = prepare_project("line-endings-crlf-trim")
+        .output_toml(toml! {
+            file = "songbook.html"
+            line_endings = "crlf"
+            trim_trailing_whitespace = true
+        })
+        .template("songbook.html", "songbook.hbs", TEMPLATE)
+        .output_toml(toml! {
+            file = "songbook.json"
+            line_endings = "crlf"
+        })
+        .output_toml(toml! {
+            file = "songbook.xml"
+            line_endings = "crlf"
+        })
+        .build()
+        .unwrap();
+
+    let html = build.read_output(".html");
+    assert_eq!(
+        html,
+        "<html>\r\n<body>\r\n<h2>Song</h2>\r\n</body>\r\n</html>\r\n"
+    );
+
+    for suffix in [".json", ".xml"] {
+        let output = build.read_output(suffix);
+        assert!(output.contains("\r\n"));
+        assert!(!output.replace("\r\n", "").contains('\n'));
+    }
+}
+
+#[test]
+fn line_endings_lf() {
+    let build = prepare_project("line-endings-lf")
+        .output_toml(toml! {
+            file = "songbook.html"
+            line_endings = "lf"
+        })
+        .template("songbook.html", "songbook.hbs", TEMPLATE)
+        .build()
+        .unwrap();
+
+    let html = build.read_output(".html");
+    assert_eq!(
+        html,
+        "<html>  \n<body>\n<h2>Song</h2>\t\n</body>\n</html>\n"
+    );
+}
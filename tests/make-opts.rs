@@ -0,0 +1,55 @@
+use std::process::Command;
+
+mod util_ng;
+pub use util_ng::*;
+
+fn run_make(build: &TestBuild, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_bard"))
+        .arg("make")
+        .args(args)
+        .current_dir(&build.unwrap().project_dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stderr).unwrap()
+}
+
+#[test]
+fn make_opts_keep_no_pdf() {
+    let build = TestProject::new("make-opts-keep-no-pdf")
+        .song("song.md", "# Song\n\n1. Lyrics.\n")
+        .output("songbook.html")
+        .build()
+        .unwrap();
+
+    let stderr = run_make(&build, &["-kk"]);
+    let warnings: Vec<_> = stderr.lines().filter(|l| l.contains("Warning")).collect();
+    assert_eq!(warnings.len(), 1, "{}", stderr);
+    assert!(
+        warnings[0].contains("the --keep flag has no effect: no PDF outputs in this project"),
+        "{}",
+        stderr
+    );
+
+    let stderr = run_make(&build, &[]);
+    assert!(!stderr.contains("Warning"), "{}", stderr);
+}
+
+#[test]
+fn make_opts_no_postprocess() {
+    let build = TestProject::new("make-opts-no-postprocess")
+        .song("song.md", "# Song\n\n1. Lyrics.\n")
+        .output("songbook.html")
+        .build()
+        .unwrap();
+
+    let stderr = run_make(&build, &["-p", "-v"]);
+    assert!(
+        stderr.contains("--no-postprocess: nothing to skip"),
+        "{}",
+        stderr
+    );
+
+    let stderr = run_make(&build, &["-p"]);
+    assert!(!stderr.contains("nothing to skip"), "{}", stderr);
+}
@@ -0,0 +1,49 @@
+mod util_ng;
+pub use util_ng::*;
+
+fn prepare_project(name: &str, outputs: &[&str]) -> TestProject {
+    TestProject::new(name)
+        .song("song.md", "# Song\n\n1. Lyrics.\n")
+        // The TeX distribution doesn't exist, the PDF output must not be rendered
+        .settings(|toml| toml.set("tex", "xelatex:/nonexistent/xelatex"))
+        .output("songbook.pdf")
+        .output("songbook.html")
+        .output("songbook.json")
+        .output("songbook.xml")
+        .make_outputs(outputs)
+}
+
+#[test]
+fn make_outputs() {
+    let build = prepare_project("make-outputs", &["songbook.html", "*.json", "missing.pdf"])
+        .build()
+        .unwrap();
+    build.unwrap();
+
+    assert!(build.try_read_output(".html").is_ok());
+    assert!(build.try_read_output(".json").is_ok());
+    assert!(build.try_read_output(".xml").is_err());
+    assert!(build.try_read_output(".tex").is_err());
+
+    // Warned about 'missing.pdf'
+    assert_eq!(build.app().warnings(), 1);
+}
+
+#[test]
+fn make_outputs_no_match() {
+    let build = prepare_project("make-outputs-no-match", &["*.txt"])
+        .build()
+        .unwrap();
+
+    let err = format!("{:#}", build.unwrap_err());
+    assert!(
+        err.contains("--output: No outputs match '*.txt'"),
+        "{}",
+        err
+    );
+    assert!(
+        err.contains("songbook.pdf, songbook.html, songbook.json, songbook.xml"),
+        "{}",
+        err
+    );
+}
@@ -0,0 +1,91 @@
+use std::fs;
+use std::process::{Command, Output};
+
+mod util_ng;
+pub use util_ng::*;
+
+fn prepare_project(name: &str) -> TestBuild {
+    TestProject::new(name)
+        .song("amazing-grace.md", "# Amazing Grace\n\n1. Lyrics.\n")
+        .song("danny-boy.md", "# Danny Boy\n\n1. Lyrics.\n")
+        .settings(|toml| toml.set("tex", "none"))
+        .output("songbook.pdf")
+        .output("songbook.html")
+        .build()
+        .unwrap()
+}
+
+fn run_make_song(build: &TestBuild, song: &str) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_bard"))
+        .args(["make", "--song", song])
+        .current_dir(&build.unwrap().project_dir)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn make_song() {
+    let build = prepare_project("make-song");
+
+    let output = run_make_song(&build, "AMAZING*");
+    assert!(output.status.success());
+
+    let tex = build.dir_output().join("songbook-song-amazing-grace.tex");
+    let tex = fs::read_to_string(tex).unwrap();
+    assert!(tex.contains(r"\songtitle{Amazing Grace}"));
+    assert!(!tex.contains("Danny Boy"));
+    assert!(!tex.contains(r"\tableofcontents"));
+    assert!(!build
+        .dir_output()
+        .join("songbook-song-danny-boy.tex")
+        .exists());
+
+    // The book itself still has the ToC
+    assert!(build
+        .read_output("songbook.tex")
+        .contains(r"\tableofcontents"));
+}
+
+#[test]
+fn make_song_no_match() {
+    let build = prepare_project("make-song-no-match");
+
+    let output = run_make_song(&build, "Yippie*");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("No song matching 'Yippie*' found"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn make_song_match() {
+    let build = TestProject::new("make-song-match")
+        .song("amazing-grace.md", "# Amazing Grace\n\n1. Lyrics.\n")
+        .song("danny-boy.md", "# Danny Boy\n\n1. Lyrics.\n")
+        .song("live.md", "# Who? [Live] {Encore\n\n1. Lyrics.\n")
+        .output("songbook.html")
+        .build()
+        .unwrap();
+    let project = build.unwrap();
+    let titles = |pattern: &str| -> Vec<String> {
+        project
+            .match_songs(pattern)
+            .unwrap()
+            .into_iter()
+            .map(|idx| project.songs()[idx].title.to_string())
+            .collect()
+    };
+
+    assert_eq!(titles("danny boy"), ["Danny Boy"]);
+    assert_eq!(titles("danny-boy"), ["Danny Boy"]);
+    // Titles with glob metacharacters are matched as they are
+    assert_eq!(titles("who? [live] {encore"), ["Who? [Live] {Encore"]);
+    assert_eq!(titles("*a*"), ["Amazing Grace", "Danny Boy"]);
+    assert_eq!(titles("{amazing,danny}*"), ["Amazing Grace", "Danny Boy"]);
+
+    let err = project.match_songs("Danny").unwrap_err();
+    assert!(err.to_string().contains("No song matching 'Danny' found"));
+}
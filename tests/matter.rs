@@ -0,0 +1,87 @@
+mod util_ng;
+pub use util_ng::*;
+
+#[test]
+fn matter() {
+    let build = TestProject::new("matter")
+        .song("songs.md", "# Song\n\n1. Lyrics.\n")
+        .text_asset(
+            "preface.md",
+            "# Preface\n\nCollected over the years.\n\n# Second Chapter\n\nMore text.\n",
+        )
+        .text_asset("thanks.md", "## Thanks\n\nTo everyone.\n")
+        .settings(|toml| {
+            toml.set("tex", "none");
+            toml.set("front_matter", vec!["output/preface.md"]);
+            toml.set("back_matter", vec!["output/thanks.md"]);
+        })
+        .output("songbook.html")
+        .output("songbook.pdf")
+        .build()
+        .unwrap();
+
+    let project = build.unwrap();
+    // Matter is neither split into songs nor listed in the TOC
+    assert_eq!(project.book.songs.len(), 1);
+    assert_eq!(project.book.front_matter.len(), 1);
+    assert_eq!(project.book.back_matter.len(), 1);
+    let watch_paths: Vec<_> = project.watch_paths().collect();
+    assert!(watch_paths.iter().any(|p| p.ends_with("preface.md")));
+    assert!(watch_paths.iter().any(|p| p.ends_with("thanks.md")));
+
+    let html = build.read_output(".html");
+    let preface = html.find("<h2>Preface</h2>").unwrap();
+    assert!(html.contains("<h2>Second Chapter</h2>"));
+    let song = html.find(r#"<section id="song""#).unwrap();
+    let thanks = html.find("<h3>Thanks</h3>").unwrap();
+    assert!(preface < song && song < thanks);
+    assert_eq!(html.matches("Preface").count(), 1);
+
+    let tex = build.read_output(".tex");
+    let preface = tex.find(r"\chapter*{Preface}").unwrap();
+    let song = tex.find(r"\songtitle{Song}").unwrap();
+    let thanks = tex.find(r"\section*{Thanks}").unwrap();
+    assert!(preface < song && song < thanks);
+}
+
+#[test]
+fn matter_volumes() {
+    let build = TestProject::new("matter-volumes")
+        .song("a.md", "# Song A\n\n1. Lyrics.\n")
+        .song("b.md", "# Song B\n\n1. Lyrics.\n")
+        .text_asset("preface.md", "# Preface\n\nText.\n")
+        .text_asset("thanks.md", "# Thanks\n\nText.\n")
+        .settings(|toml| {
+            toml.set("tex", "none");
+            toml.set("front_matter", vec!["output/preface.md"]);
+            toml.set("back_matter", vec!["output/thanks.md"]);
+        })
+        .output_toml(toml! {
+            file = "songbook.pdf"
+            volumes = [
+                { name = "One", until_index = 1 },
+                { name = "Two" },
+            ]
+        })
+        .build()
+        .unwrap();
+
+    let one = build.read_output("vol1.tex");
+    assert!(one.contains("Preface"));
+    assert!(!one.contains("Thanks"));
+    let two = build.read_output("vol2.tex");
+    assert!(!two.contains("Preface"));
+    assert!(two.contains("Thanks"));
+}
+
+#[test]
+fn matter_missing_file() {
+    let build = TestProject::new("matter-missing-file")
+        .settings(|toml| toml.set("front_matter", vec!["preface.md"]))
+        .output("songbook.html")
+        .build()
+        .unwrap();
+
+    let err = format!("{:?}", build.unwrap_err());
+    assert!(err.contains("preface.md"));
+}
@@ -0,0 +1,70 @@
+use bard::render::pdf;
+
+mod util_ng;
+pub use util_ng::*;
+
+const SONG: &str = r#"# Song
+
+```meta
+author = "Harlej"
+capo = 2
+```
+
+## Subtitle
+
+1. Lyrics.
+"#;
+
+#[test]
+fn song_metadata() {
+    let build = TestProject::new("song-metadata")
+        .song("songs.md", SONG)
+        .settings(|toml| toml.set("tex", "none"))
+        .output("songbook.pdf")
+        .template_prefix_default(
+            "songbook.pdf",
+            "pdf.hbs",
+            "% {{#each songs}}[{{this.metadata.author}}/{{this.metadata.capo}}]{{/each}}\n",
+            &pdf::DEFAULT_TEMPLATE,
+        )
+        .output("songbook.json")
+        .output("songbook.xml")
+        .build()
+        .unwrap();
+
+    build.unwrap();
+    assert_eq!(build.app().warnings(), 0);
+
+    let tex = build.read_output(".tex");
+    assert!(tex.contains("% [Harlej/2]"), "{}", tex);
+    assert!(tex.contains("Subtitle"));
+    assert!(!tex.contains("capo = 2"));
+
+    let json = build.read_output(".json");
+    let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+    let song = &json["songs"][0];
+    assert_eq!(song["metadata"]["author"], "Harlej");
+    assert_eq!(song["metadata"]["capo"], 2);
+    assert_eq!(song["subtitles"][0], "Subtitle");
+
+    let xml = build.read_output(".xml");
+    assert!(xml.contains("<author>Harlej</author>"), "{}", xml);
+}
+
+#[test]
+fn song_metadata_invalid() {
+    let build = TestProject::new("song-metadata-invalid")
+        .song(
+            "songs.md",
+            "# Song\n\n```meta\ncapo = \n```\n\n1. Lyrics.\n",
+        )
+        .output("songbook.json")
+        .build()
+        .unwrap();
+
+    build.unwrap();
+    let json = build.read_output(".json");
+    let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(json["songs"][0]["metadata"], serde_json::json!({}));
+    assert_eq!(build.app().warnings(), 1);
+}
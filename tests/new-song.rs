@@ -0,0 +1,95 @@
+use std::fs;
+use std::path::Path;
+
+use bard::util_cmd::{self, NewSongOpts};
+
+mod util_ng;
+pub use util_ng::*;
+
+fn prepare_project(name: &str) -> TestBuild {
+    TestProject::new(name)
+        .song("song.md", "# Song\n\n1. Lyrics.\n")
+        .output("songbook.html")
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn new_song_default() {
+    let build = prepare_project("new-song-default");
+    let project = build.unwrap();
+
+    let opts = NewSongOpts {
+        title: "Danny Boy",
+        artist: Some("Frederic Weatherly"),
+        ..Default::default()
+    };
+    let song = util_cmd::new_song(build.app(), &project.project_dir, &opts).unwrap();
+    assert_eq!(song.path, build.dir_songs().join("danny-boy.md"));
+    assert!(song.added_to_project);
+
+    let content = fs::read_to_string(&song.path).unwrap();
+    assert!(content.starts_with("# Danny Boy\n## Frederic Weatherly\n\n1."));
+
+    let bard_toml = fs::read_to_string(project.project_dir.join("bard.toml")).unwrap();
+    assert!(bard_toml.contains(r#""song.md", "danny-boy.md""#));
+
+    // Refuse to overwrite
+    util_cmd::new_song(build.app(), &project.project_dir, &opts).unwrap_err();
+
+    // The project still builds with the new song
+    bard::bard_make_at(build.app(), &project.project_dir).unwrap();
+}
+
+#[test]
+fn new_song_template_file() {
+    let build = prepare_project("new-song-template-file");
+    let project = build.unwrap();
+
+    let tpl_dir = project.project_dir.join("templates");
+    fs::create_dir_all(&tpl_dir).unwrap();
+    fs::write(
+        tpl_dir.join("song.md"),
+        "# {{ title }}\n\nCustom skeleton.\n",
+    )
+    .unwrap();
+
+    let file = Path::new("other").join("yippie.md");
+    let opts = NewSongOpts {
+        title: "Yippie",
+        file: Some(&file),
+        template: Some(Path::new("song.md")),
+        ..Default::default()
+    };
+    let song = util_cmd::new_song(build.app(), &project.project_dir, &opts).unwrap();
+
+    // Not in the songs directory, bard.toml is left alone
+    assert_eq!(song.path, project.project_dir.join(&file));
+    assert!(!song.added_to_project);
+
+    let content = fs::read_to_string(&song.path).unwrap();
+    assert_eq!(content, "# Yippie\n\nCustom skeleton.\n");
+}
+
+#[test]
+fn new_song_glob() {
+    let build = TestProject::new("new-song-glob")
+        .song("song.md", "# Song\n\n1. Lyrics.\n")
+        .output("songbook.html")
+        .settings(|toml| {
+            toml.set("songs", "*.md");
+        })
+        .build()
+        .unwrap();
+    let project = build.unwrap();
+
+    let opts = NewSongOpts {
+        title: "Yippie",
+        ..Default::default()
+    };
+    let song = util_cmd::new_song(build.app(), &project.project_dir, &opts).unwrap();
+    assert!(!song.added_to_project);
+
+    let content = fs::read_to_string(&song.path).unwrap();
+    assert!(content.starts_with("# Yippie\n\n1."));
+}
@@ -0,0 +1,46 @@
+mod util_ng;
+pub use util_ng::*;
+
+/// The title written with a decomposed é, ie. e followed by a combining acute accent.
+const SONG_NFD: &str = "# Cafe\u{301} Blues\n\n1. `E\u{301}`Lyrics\n";
+const SONG_NFC: &str = "# Caf\u{e9} Blues\n\n!alias(Caf\u{e9} Blues Again)\n\n1. More lyrics\n";
+
+#[test]
+fn normalize_unicode() {
+    let build = TestProject::new("normalize-unicode")
+        .song("nfd.md", SONG_NFD)
+        .song("nfc.md", SONG_NFC)
+        .output("songbook.json")
+        .build()
+        .unwrap();
+    let project = build.unwrap();
+
+    // Both titles are the same and sort together in the index
+    let sorted: Vec<_> = project.songs_sorted().iter().map(|s| &*s.title).collect();
+    assert_eq!(sorted, ["Caf\u{e9} Blues", "Caf\u{e9} Blues"]);
+    let chords: Vec<_> = project.songs()[0]
+        .chords()
+        .iter()
+        .map(|c| c.chord.to_string())
+        .collect();
+    assert_eq!(chords, ["\u{c9}"]);
+
+    // Only the decomposed file is reported
+    assert_eq!(build.app().warnings(), 1);
+}
+
+#[test]
+fn normalize_unicode_disabled() {
+    let build = TestProject::new("normalize-unicode-disabled")
+        .song("nfd.md", SONG_NFD)
+        .song("nfc.md", SONG_NFC)
+        .settings(|toml| toml.set("normalize_unicode", false))
+        .output("songbook.json")
+        .build()
+        .unwrap();
+    let project = build.unwrap();
+
+    let titles: Vec<_> = project.songs().iter().map(|s| &*s.title).collect();
+    assert_eq!(titles, ["Cafe\u{301} Blues", "Caf\u{e9} Blues"]);
+    assert_eq!(build.app().warnings(), 0);
+}
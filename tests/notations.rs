@@ -0,0 +1,128 @@
+mod util_ng;
+pub use util_ng::*;
+
+const SONGS_SOLFEGE: &str = r#"# First Song
+
+!!english
+
+1. `Do`Lyrics `Lam/Sol`more `sib7`lyrics.
+
+# Second Song
+
+!english !+2
+
+1. `Do`Lyrics `Lam/Sol`more lyrics.
+"#;
+
+const SONGS_ENGLISH: &str = r#"# Song
+
+!!solfege
+
+1. `C`Lyrics `Am/G`more `Bb7`lyrics.
+"#;
+
+fn prepare_project(name: &str, notation: &str, songs: &str) -> TestProject {
+    let notation = notation.to_string();
+    TestProject::new(name)
+        .song("songs.md", songs)
+        .settings(move |toml| {
+            toml.set("notation", notation);
+            toml.set(
+                "notations",
+                toml! {
+                    [solfege]
+                    notes = ["Do", "Do#", "Re", "Mib", "Mi", "Fa", "Fa#", "Sol", "Lab", "La", "Sib", "Si"]
+                },
+            );
+        })
+        .output("songbook.json")
+}
+
+fn chords(song: &serde_json::Value) -> Vec<(String, String)> {
+    song["blocks"][0]["paragraphs"][0]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|inline| inline["type"] == "i-chord")
+        .map(|chord| {
+            (
+                chord["chord"].as_str().unwrap().to_string(),
+                chord["alt_chord"].as_str().unwrap_or("").to_string(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn notations_custom() {
+    let build = prepare_project("notations-custom", "solfege", SONGS_SOLFEGE)
+        .build()
+        .unwrap();
+
+    let json = build.read_output(".json");
+    let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(json["notation"], "solfege");
+
+    let song = &json["songs"][0];
+    assert_eq!(song["notation"], "solfege");
+    assert_eq!(
+        chords(song),
+        [
+            ("Do".into(), "C".into()),
+            ("Lam/Sol".into(), "Am/G".into()),
+            ("sib7".into(), "bb7".into()),
+        ]
+    );
+
+    // The !!english conversion carries over from the previous song in the same file
+    let song = &json["songs"][1];
+    assert_eq!(
+        chords(song),
+        [("D".into(), "C".into()), ("Bm/A".into(), "Am/G".into())]
+    );
+}
+
+#[test]
+fn notations_custom_conversion() {
+    let build = prepare_project("notations-custom-conversion", "english", SONGS_ENGLISH)
+        .build()
+        .unwrap();
+
+    let json = build.read_output(".json");
+    let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(
+        chords(&json["songs"][0]),
+        [
+            ("C".into(), "Do".into()),
+            ("Am/G".into(), "Lam/Sol".into()),
+            ("Bb7".into(), "Sib7".into()),
+        ]
+    );
+}
+
+#[test]
+fn notations_unknown() {
+    let build = prepare_project("notations-unknown", "tonic-solfa", SONGS_ENGLISH)
+        .build()
+        .unwrap();
+    let err = format!("{:#}", build.unwrap_err());
+    assert!(err.contains("Unknown notation 'tonic-solfa'"), "{}", err);
+}
+
+#[test]
+fn notations_legend() {
+    let build = prepare_project("notations-legend", "english", SONGS_ENGLISH)
+        .build()
+        .unwrap();
+
+    let json = build.read_output(".json");
+    let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+    let legend = json["notation_legend"].as_array().unwrap();
+    assert_eq!(legend.len(), 1);
+    assert_eq!(legend[0]["notation"], "english");
+    assert_eq!(legend[0]["alt_notation"], "solfege");
+    let entry = &legend[0]["entries"][10];
+    assert_eq!(entry["chromatic_index"], 10);
+    assert_eq!(entry["name"], "Bb");
+    assert_eq!(entry["alt_name"], "Sib");
+}
@@ -0,0 +1,68 @@
+use std::fs;
+
+use bard::watch::WatchEvent;
+
+mod util_ng;
+pub use util_ng::*;
+
+fn prepare_project(name: &str, on_update: &str) -> TestProject {
+    let on_update = on_update.to_string();
+    TestProject::new(name)
+        .song("song.md", "# Song\n\n1. Lyrics.\n")
+        .output_toml(toml! {
+            file = "songbook.html"
+            on_update = on_update
+        })
+}
+
+/// Run `bard watch` with one rebuild.
+fn watch_rebuild(build: &TestBuild) {
+    let (watch_thread, control) = build.watch();
+    control.wait_watching();
+    control.send(WatchEvent::Rebuild);
+    control.wait_watching();
+    control.send(WatchEvent::Quit);
+    watch_thread.join().unwrap();
+}
+
+#[test]
+fn on_update_watch_only() {
+    let build = prepare_project("on-update-watch-only", "echo {file} >> notified.txt")
+        .build()
+        .unwrap();
+
+    // bard make doesn't run the command
+    let notified = build.dir_output().join("notified.txt");
+    assert!(!notified.exists());
+
+    watch_rebuild(&build);
+
+    let notified = fs::read_to_string(notified).unwrap();
+    assert_eq!(notified.lines().count(), 2);
+    assert!(notified.lines().all(|l| l.contains("songbook.html")));
+}
+
+#[test]
+fn on_update_no_notify() {
+    let build = prepare_project("on-update-no-notify", "echo {file} >> notified.txt")
+        .notify(false)
+        .build()
+        .unwrap();
+
+    watch_rebuild(&build);
+
+    assert!(!build.dir_output().join("notified.txt").exists());
+}
+
+#[test]
+fn on_update_failure_is_warning() {
+    let build = prepare_project("on-update-failure", "echo {file} >> notified.txt && exit 1")
+        .build()
+        .unwrap();
+
+    // The watch goes on after the failed command
+    watch_rebuild(&build);
+
+    let notified = build.read_output("notified.txt");
+    assert_eq!(notified.lines().count(), 2);
+}
@@ -0,0 +1,151 @@
+use bard::project::Format;
+use bard::util_cmd;
+
+mod util_ng;
+pub use util_ng::*;
+
+const SONG: &str = "# Song\n\n1. Lyrics.\n";
+
+#[test]
+fn output_files() {
+    let build = TestProject::new("output-files")
+        .song("song.md", SONG)
+        .settings(|toml| toml.set("tex", "none"))
+        .output_toml(toml! {
+            files = ["songbook.pdf", "songbook.html", "songbook.json"]
+            font_size = 10
+        })
+        .output_toml(toml! {
+            files = ["slides.html", "slides.xml"]
+            template = "slides.hbs"
+        })
+        .output("songbook.xml")
+        .build()
+        .unwrap();
+    let project = build.unwrap();
+
+    let outputs: Vec<_> = project
+        .settings
+        .output
+        .iter()
+        .map(|o| (o.output_filename().to_string(), o.format(), o.entry))
+        .collect();
+    assert_eq!(
+        outputs,
+        [
+            ("songbook.pdf".into(), Format::Pdf, 0),
+            ("songbook.html".into(), Format::Html, 0),
+            ("songbook.json".into(), Format::Json, 0),
+            ("slides.html".into(), Format::Html, 1),
+            ("slides.xml".into(), Format::Xml, 1),
+            ("songbook.xml".into(), Format::Xml, 2),
+        ]
+    );
+    assert!(project.settings.output[..3]
+        .iter()
+        .all(|o| o.font_size == 10));
+
+    build.read_output("songbook.tex");
+    build.read_output("songbook.html");
+    build.read_output("songbook.json");
+    build.read_output("slides.html");
+    build.read_output("slides.xml");
+
+    // The template only applies to the HTML file
+    let templates: Vec<_> = project
+        .watch_paths()
+        .filter(|p| p.ends_with("slides.hbs"))
+        .collect();
+    assert_eq!(templates.len(), 1);
+    assert!(project.settings.output[4].template_path().is_none());
+
+    let config = util_cmd::show_config(build.app(), &project.project_dir).unwrap();
+    assert!(
+        config.contains("file = \"slides.xml\"  # bard.toml"),
+        "{}",
+        config
+    );
+}
+
+#[test]
+fn output_files_script() {
+    let build = TestProject::new("output-files-script")
+        .song("song.md", SONG)
+        .postprocess(true)
+        .output_toml(toml! {
+            files = ["songbook.html", "songbook.json"]
+        })
+        .script(
+            ".html",
+            "script",
+            "#!/bin/sh\necho \"${OUTPUT}\" >> outputs.txt\n",
+            "@ECHO OFF\r\necho %OUTPUT%>> outputs.txt\r\n",
+        )
+        .build()
+        .unwrap();
+    build.unwrap();
+
+    let outputs = build.read_output("outputs.txt");
+    let outputs: Vec<_> = outputs.lines().map(str::trim).collect();
+    assert_eq!(
+        outputs,
+        [
+            build.dir_output().join("songbook.html").to_str().unwrap(),
+            build.dir_output().join("songbook.json").to_str().unwrap(),
+        ]
+    );
+}
+
+fn build_error(name: &str, output: toml::Table) -> String {
+    let build = TestProject::new(name)
+        .song("song.md", SONG)
+        .output_toml(output)
+        .build()
+        .unwrap();
+    format!("{:#}", build.unwrap_err())
+}
+
+#[test]
+fn output_files_errors() {
+    let err = build_error(
+        "output-files-format",
+        toml! {
+            files = ["songbook.pdf", "songbook.html"]
+            format = "pdf"
+        },
+    );
+    assert!(
+        err.contains("the files have different extensions"),
+        "{}",
+        err
+    );
+
+    let err = build_error(
+        "output-files-template",
+        toml! {
+            files = ["songbook.pdf", "songbook.html"]
+            template = "songbook.hbs"
+        },
+    );
+    assert!(err.contains("they have different formats"), "{}", err);
+
+    let err = build_error(
+        "output-files-both",
+        toml! {
+            file = "songbook.pdf"
+            files = ["songbook.html"]
+        },
+    );
+    assert!(err.contains("both `file` and `files` set"), "{}", err);
+
+    let err = build_error("output-files-none", toml! { font_size = 10 });
+    assert!(err.contains("Output number 1 has no file"), "{}", err);
+}
+
+#[test]
+fn output_no_extension() {
+    let err = build_error("output-no-extension", toml! { file = "songbook" });
+    assert!(err.contains("it has no extension"), "{}", err);
+    assert!(err.contains("format = \"pdf\""), "{}", err);
+    assert!(err.contains("pdf, html, hovorka"), "{}", err);
+}
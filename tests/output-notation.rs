@@ -0,0 +1,59 @@
+mod util_ng;
+pub use util_ng::*;
+
+const SONG: &str = r#"# Song
+
+!key(Hm)
+
+1. `Hm`Lyrics `B`more `N.C.`lyrics.
+"#;
+
+#[test]
+fn output_notation() {
+    let build = TestProject::new("output-notation")
+        .song("songs.md", SONG)
+        .settings(|toml| toml.set("notation", "german"))
+        .output("songbook.html")
+        .output_toml(toml! {
+            file = "songbook-en.html"
+            notation = "english"
+        })
+        .output_toml(toml! {
+            file = "songbook-en.json"
+            notation = "english"
+        })
+        .build()
+        .unwrap();
+    build.unwrap();
+
+    // The book's notation
+    let html = build.read_output("songbook.html");
+    assert!(html.contains(r#"<td data-root="11" data-suffix="m">Hm</td>"#));
+    assert!(html.contains(r#"<td data-root="10" data-suffix="">B</td>"#));
+    assert!(html.contains(
+        r#"var tones = ["C", "C#", "D", "Eb", "E", "F", "F#", "G", "Ab", "A", "B", "H"];"#
+    ));
+
+    let html = build.read_output("songbook-en.html");
+    assert!(
+        html.contains(r#"<td data-root="11" data-suffix="m">Bm</td>"#),
+        "{}",
+        html
+    );
+    assert!(html.contains(r#"<td data-root="10" data-suffix="">Bb</td>"#));
+    assert!(html.contains("<td>N.C.</td>"));
+    assert!(html.contains(
+        r#"var tones = ["C", "C#", "D", "Eb", "E", "F", "F#", "G", "Ab", "A", "Bb", "B"];"#
+    ));
+
+    let json = build.read_output("songbook-en.json");
+    let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+    let song = &json["songs"][0];
+    assert_eq!(json["notation"], "english");
+    assert_eq!(song["notation"], "english");
+    assert_eq!(song["key"], "Bm");
+    assert_eq!(song["chords_used"], serde_json::json!(["Bm", "Bb", "N.C."]));
+
+    // N.C. isn't recognized, reported for each of the converted outputs
+    assert_eq!(build.app().warnings(), 2);
+}
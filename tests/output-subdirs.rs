@@ -0,0 +1,66 @@
+use std::collections::HashMap;
+use std::fs;
+
+mod util_ng;
+pub use util_ng::*;
+
+#[test]
+fn output_subdirs() {
+    let build = TestProject::new("output-subdirs")
+        .song("song.md", "# Song\n\n1. Lyrics.\n")
+        .settings(|toml| toml.set("tex", "none"))
+        .output("web/songbook.html")
+        .output("print/a4/songbook.pdf")
+        .build()
+        .unwrap();
+
+    let html = build.dir_output().join("web").join("songbook.html");
+    let html = fs::read_to_string(html).unwrap();
+    assert!(html.contains("Song"));
+
+    let tex = build
+        .dir_output()
+        .join("print")
+        .join("a4")
+        .join("songbook.tex");
+    let tex = fs::read_to_string(tex).unwrap();
+    assert!(tex.contains(r"\songtitle{Song}"));
+}
+
+#[test]
+fn output_subdirs_script() {
+    let build = TestProject::new("output-subdirs-script")
+        .postprocess(true)
+        .output("web/songbook.html")
+        .script(
+            ".html",
+            "script",
+            indoc! {r#"
+            #!/bin/sh
+
+            echo "OUTPUT = \"${OUTPUT}\"" > "${OUTPUT_STEM}.toml"
+
+            "#},
+            indoc! {r#"
+            @ECHO OFF
+
+            set OUTPUT=%OUTPUT:\=\\%
+            echo OUTPUT = "%OUTPUT%" > "%OUTPUT_STEM%.toml"
+
+            "#},
+        )
+        .build()
+        .unwrap();
+
+    let out = build.read_output("songbook.toml");
+    let out: HashMap<String, String> = toml::from_str(&out).unwrap();
+    assert_eq!(
+        out["OUTPUT"],
+        build
+            .dir_output()
+            .join("web")
+            .join("songbook.html")
+            .to_str()
+            .unwrap()
+    );
+}
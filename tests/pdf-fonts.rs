@@ -0,0 +1,54 @@
+mod util_ng;
+pub use util_ng::*;
+
+const TEMPLATE: &str = r#"{{{ fonts.dir }}}
+{{#each fonts.files}}{{{ this }}}
+{{/each}}"#;
+
+fn prepare_project(name: &str, fonts: &'static str) -> TestProject {
+    TestProject::new(name)
+        .settings(|toml| toml.set("tex", "none"))
+        .output_toml(toml! {
+            file = "songbook.pdf"
+            fonts = [fonts]
+        })
+        .template("songbook.pdf", "fonts.hbs", TEMPLATE)
+}
+
+#[test]
+fn pdf_fonts() {
+    let build = prepare_project("pdf-fonts", "output/fonts/BardSans-*.ttf")
+        .build()
+        .unwrap();
+
+    let tex = build.read_output(".tex");
+    let mut lines = tex.lines();
+    let dir = lines.next().unwrap();
+    let fonts_dir = build.dir_output().join("fonts");
+    assert_eq!(
+        dir,
+        format!("{}/", fonts_dir.to_str().unwrap().replace('\\', "/"))
+    );
+    assert_eq!(
+        lines.collect::<Vec<_>>(),
+        [
+            "BardSans-Bold.ttf",
+            "BardSans-BoldItalic.ttf",
+            "BardSans-Italic.ttf",
+            "BardSans-Regular.ttf",
+        ]
+    );
+}
+
+#[test]
+fn pdf_fonts_missing() {
+    let build = prepare_project("pdf-fonts-missing", "templates/fonts/*.otf")
+        .build()
+        .unwrap();
+    let err = format!("{:#}", build.unwrap_err());
+    assert!(
+        err.contains("No font files matched pattern 'templates/fonts/*.otf'"),
+        "{}",
+        err
+    );
+}
@@ -0,0 +1,111 @@
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+use bard::app::STRICT_PROJECT_DIR_ENV;
+
+mod util_ng;
+pub use util_ng::*;
+
+fn prepare_project(name: &str) -> TestBuild {
+    TestProject::new(name)
+        .song("song.md", "# Song\n\n1. Lyrics.\n")
+        .output("songbook.html")
+        .build()
+        .unwrap()
+}
+
+fn run_bard(cwd: &Path, args: &[&str], strict_env: bool) -> (Output, String) {
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_bard"));
+    cmd.args(args).current_dir(cwd);
+    if strict_env {
+        cmd.env(STRICT_PROJECT_DIR_ENV, "1");
+    } else {
+        cmd.env_remove(STRICT_PROJECT_DIR_ENV);
+    }
+
+    let output = cmd.output().unwrap();
+    let stderr = String::from_utf8(output.stderr.clone()).unwrap();
+    (output, stderr)
+}
+
+#[test]
+fn project_dir_parent() {
+    let build = prepare_project("project-dir-parent");
+    let project_dir = &build.unwrap().project_dir;
+
+    // In the project directory, no notice is printed:
+    let (output, stderr) = run_bard(project_dir, &["make"], false);
+    assert!(output.status.success(), "{}", stderr);
+    assert!(!stderr.contains("parent directory"), "{}", stderr);
+
+    // In a subdirectory, the project is found with a notice:
+    let (output, stderr) = run_bard(build.dir_songs(), &["make"], false);
+    assert!(output.status.success(), "{}", stderr);
+    let notice = format!("Found bard.toml in parent directory {:?}", project_dir);
+    assert!(stderr.contains(&notice), "{}", stderr);
+}
+
+#[test]
+fn project_dir_strict() {
+    let build = prepare_project("project-dir-strict");
+    let project_dir = &build.unwrap().project_dir;
+    let html = build.dir_output().join("songbook.html");
+    fs::remove_file(&html).unwrap();
+
+    for (args, strict_env) in [
+        (&["make", "--strict-project-dir"][..], false),
+        (&["make"][..], true),
+    ] {
+        let (output, stderr) = run_bard(build.dir_songs(), args, strict_env);
+        assert!(!output.status.success());
+        assert!(
+            stderr.contains("strict project directory mode"),
+            "{}",
+            stderr
+        );
+        assert!(!html.exists());
+    }
+
+    // The project directory itself is fine in strict mode:
+    let (output, stderr) = run_bard(project_dir, &["make", "--strict-project-dir"], true);
+    assert!(output.status.success(), "{}", stderr);
+    assert!(html.exists());
+}
+
+#[test]
+fn project_dir_explicit() {
+    let build = prepare_project("project-dir-explicit");
+    let project_dir = build.unwrap().project_dir.to_str().unwrap();
+    let html = build.dir_output().join("songbook.html");
+    fs::remove_file(&html).unwrap();
+
+    // Even in strict mode, --project-dir pins the project, before or after the subcommand:
+    let other_dir = build.dir_songs();
+    let (output, stderr) = run_bard(other_dir, &["--project-dir", project_dir, "make"], true);
+    assert!(output.status.success(), "{}", stderr);
+    assert!(!stderr.contains("parent directory"), "{}", stderr);
+    assert!(html.exists());
+
+    let (output, stderr) = run_bard(
+        other_dir,
+        &["util", "show-config", "--project-dir", ".."],
+        false,
+    );
+    assert!(output.status.success(), "{}", stderr);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("songbook.html"));
+
+    // A directory with no bard.toml is an error:
+    let (output, stderr) = run_bard(
+        project_dir.as_ref(),
+        &["make", "--project-dir", "songs"],
+        false,
+    );
+    assert!(!output.status.success());
+    assert!(
+        stderr.contains("Could not find bard.toml file in the project directory"),
+        "{}",
+        stderr
+    );
+}
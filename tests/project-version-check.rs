@@ -1,31 +1,45 @@
+use bard::project::Project;
+
 mod util;
 pub use util::*;
 
 #[test]
 fn project_version_check_1_implicit() {
-    let err = Builder::init_modify_build("project-version-1-implicit", |mut settings| {
+    let app = Builder::app(false);
+    let project_dir = init_project(&app, "project-version-1-implicit").unwrap();
+    modify_settings(&project_dir, |mut settings| {
         settings.remove("version");
         Ok(settings)
     })
-    .unwrap_err();
+    .unwrap();
+
+    // 1.x projects are loaded with a deprecation warning
+    let project = Project::new(&app, &project_dir).unwrap();
+    let warnings = project.settings.legacy_warnings();
     assert!(
-        format!("{:?}", err).contains("1.x"),
-        "actual error: {}",
-        err
+        warnings.iter().any(|w| w.contains("1.x")),
+        "actual warnings: {:?}",
+        warnings
     );
 }
 
 #[test]
 fn project_version_check_1_explicit() {
-    let err = Builder::init_modify_build("project-version-1-explicit", |mut settings| {
+    let app = Builder::app(false);
+    let project_dir = init_project(&app, "project-version-1-explicit").unwrap();
+    modify_settings(&project_dir, |mut settings| {
         settings.insert("version".to_string(), 1.into());
         Ok(settings)
     })
-    .unwrap_err();
+    .unwrap();
+
+    // 1.x projects are loaded with a deprecation warning
+    let project = Project::new(&app, &project_dir).unwrap();
+    let warnings = project.settings.legacy_warnings();
     assert!(
-        format!("{:?}", err).contains("1.x"),
-        "actual error: {}",
-        err
+        warnings.iter().any(|w| w.contains("1.x")),
+        "actual warnings: {:?}",
+        warnings
     );
 }
 
@@ -48,3 +62,31 @@ fn project_version_check_9001() {
         err
     );
 }
+
+#[test]
+fn project_version_check_string() {
+    let app = Builder::app(false);
+    let project_dir = init_project(&app, "project-version-string").unwrap();
+    modify_settings(&project_dir, |mut settings| {
+        settings.insert("version".to_string(), "2.0.1".into());
+        Ok(settings)
+    })
+    .unwrap();
+
+    let project = Project::new(&app, &project_dir).unwrap();
+    assert!(project.settings.legacy_warnings().is_empty());
+}
+
+#[test]
+fn project_version_check_invalid() {
+    let err = Builder::init_modify_build("project-version-invalid", |mut settings| {
+        settings.insert("version".to_string(), "two".into());
+        Ok(settings)
+    })
+    .unwrap_err();
+    assert!(
+        format!("{:?}", err).contains("version string"),
+        "actual error: {}",
+        err
+    );
+}
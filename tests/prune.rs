@@ -0,0 +1,84 @@
+use std::fs;
+
+use bard::app::App;
+
+mod util_ng;
+pub use util_ng::*;
+
+/// Remove the output with `file` from the project's bard.toml.
+fn remove_output(build: &TestBuild, file: &str) {
+    let path = build.unwrap().project_dir.join("bard.toml");
+    let mut toml: toml::Table = fs::read_to_string(&path).unwrap().parse().unwrap();
+    toml["output"]
+        .as_array_mut()
+        .unwrap()
+        .retain(|output| output["file"].as_str() != Some(file));
+    fs::write(&path, toml::to_string_pretty(&toml).unwrap()).unwrap();
+}
+
+fn rebuild(build: &TestBuild, app: &App) {
+    bard::bard_make_at(app, &build.unwrap().project_dir).unwrap();
+}
+
+#[test]
+fn prune_stale_output() {
+    let build = TestProject::new("prune-stale-output")
+        .output("songbook.html")
+        .output("songbook.json")
+        .output("html/extra.html")
+        .text_asset("font.woff2", "not a font")
+        .text_asset("notes.json", "{}")
+        .build()
+        .unwrap();
+    let dir = build.dir_output();
+    assert!(dir.join("songbook.json").exists());
+
+    // Without pruning, the stale output is kept
+    remove_output(&build, "songbook.json");
+    rebuild(&build, build.app());
+    assert!(dir.join("songbook.json").exists());
+
+    // It's still recorded and removed by a later build with pruning
+    let app = build.app().clone().with_prune(true);
+    rebuild(&build, &app);
+    assert!(!dir.join("songbook.json").exists());
+    assert!(dir.join("songbook.html").exists());
+
+    // Outputs in subdirectories are pruned as well
+    remove_output(&build, "html/extra.html");
+    rebuild(&build, &app);
+    assert!(!dir.join("html/extra.html").exists());
+    assert!(dir.join("songbook.html").exists());
+
+    // Files not generated by bard are never touched
+    assert_eq!(
+        fs::read_to_string(dir.join("font.woff2")).unwrap(),
+        "not a font"
+    );
+    assert_eq!(fs::read_to_string(dir.join("notes.json")).unwrap(), "{}");
+}
+
+#[test]
+fn prune_setting_keeps_user_files() {
+    let build = TestProject::new("prune-setting-keeps-user-files")
+        .output("songbook.html")
+        .text_asset("songbook.json", "user file")
+        .settings(|toml| toml.set("prune", true))
+        .build()
+        .unwrap();
+    let dir = build.dir_output();
+
+    // A file with the name of an output which was never rendered isn't bard's to remove
+    rebuild(&build, build.app());
+    assert_eq!(
+        fs::read_to_string(dir.join("songbook.json")).unwrap(),
+        "user file"
+    );
+    assert!(dir.join("songbook.html").exists());
+
+    // Removing all outputs leaves only the user files
+    remove_output(&build, "songbook.html");
+    rebuild(&build, build.app());
+    assert!(!dir.join("songbook.html").exists());
+    assert!(dir.join("songbook.json").exists());
+}
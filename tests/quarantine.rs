@@ -0,0 +1,63 @@
+//! Disabled for Windows, because `PATH` overriding doesn't work there, see `tex-tools.rs`.
+#![cfg(not(windows))]
+
+use std::fs;
+
+mod util;
+pub use util::*;
+
+/// Project with a song that makes the TeX mock fail.
+fn broken_project(name: &str) -> ExeBuilder {
+    let builder = ExeBuilder::init(name)
+        .unwrap()
+        .with_xelatex_bin()
+        .with_env("BARD_TEX", "xelatex");
+
+    let songs_dir = builder.work_dir.join("songs");
+    for (file, content) in [
+        ("a.md", "# Good One\n\n1. Lyrics.\n"),
+        ("b.md", "# Broken Song\n\n1. tex-mock-fail\n"),
+        ("c.md", "# Good Two\n\n1. Lyrics.\n"),
+    ] {
+        fs::write(songs_dir.join(file), content).unwrap();
+    }
+
+    modify_settings(&builder.work_dir, |mut settings| {
+        settings.insert("songs".to_string(), "*.md".into());
+        Ok(settings)
+    })
+    .unwrap();
+
+    builder
+}
+
+#[test]
+fn quarantine() {
+    let builder = broken_project("quarantine")
+        .run(&["make", "-k", "--quarantine"])
+        .unwrap();
+
+    let report = fs::read_to_string(builder.output("quarantine.txt")).unwrap();
+    assert!(
+        report.contains("songbook.pdf:\n  Broken Song\n"),
+        "{}",
+        report
+    );
+    assert!(!report.contains("Good"), "{}", report);
+
+    let tex = fs::read_to_string(builder.output("songbook.tex")).unwrap();
+    assert!(tex.contains("Good One"));
+    assert!(tex.contains("Good Two"));
+    assert!(!tex.contains("tex-mock-fail"));
+    assert!(builder.output("songbook.pdf").exists());
+
+    // Probe files are cleaned up
+    assert!(builder.find_tmp_dir("songbook-quarantine.pdf").is_none());
+    assert!(!builder.output("songbook-quarantine.tex").exists());
+}
+
+#[test]
+fn quarantine_off() {
+    let res = broken_project("quarantine-off").run(&["make"]);
+    assert!(res.is_err());
+}
@@ -0,0 +1,79 @@
+use std::fs;
+
+use bard::util_cmd::{render_song, RenderSongOpts};
+
+mod util_ng;
+pub use util_ng::*;
+
+const SONGS: &str = "# Amazing Song\n\n1. `C`First verse.\n\n# Amazing Grace\n\n1. Lyrics.\n\n# Other Song\n\n1. More lyrics.\n";
+
+fn prepare_project(name: &str) -> TestBuild {
+    TestProject::new(name)
+        .song("songs.md", SONGS)
+        .settings(|toml| toml.set("tex", "none"))
+        .output("songbook.pdf")
+        .output("html/songbook.html")
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn render_song_preview() {
+    let build = prepare_project("render-song-preview");
+    let project = build.unwrap();
+
+    let opts = RenderSongOpts {
+        song: "other song",
+        output: "songbook.html",
+    };
+    let preview = render_song(build.app(), &project.project_dir, &opts).unwrap();
+    assert_eq!(preview.song, "Other Song");
+    assert_eq!(
+        preview.file,
+        build.dir_output().join("html/songbook.preview.html")
+    );
+    let html = fs::read_to_string(&preview.file).unwrap();
+    assert!(html.contains("More lyrics."));
+    assert!(!html.contains("First verse."));
+
+    // For PDF outputs, only the TeX file is rendered
+    let opts = RenderSongOpts {
+        song: "Amazing S*",
+        output: "songbook.pdf",
+    };
+    let preview = render_song(build.app(), &project.project_dir, &opts).unwrap();
+    assert_eq!(
+        preview.file,
+        build.dir_output().join("songbook.preview.tex")
+    );
+    let tex = fs::read_to_string(&preview.file).unwrap();
+    assert!(tex.contains("\\songtitle{Amazing Song}"));
+    assert!(!tex.contains("Other Song"));
+    assert!(!build.dir_output().join("songbook.preview.pdf").exists());
+}
+
+#[test]
+fn render_song_errors() {
+    let build = prepare_project("render-song-errors");
+    let project = build.unwrap();
+
+    let opts = RenderSongOpts {
+        song: "Amazing*",
+        output: "songbook.pdf",
+    };
+    let err = render_song(build.app(), &project.project_dir, &opts).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "'Amazing*' matches 2 songs: 'Amazing Song', 'Amazing Grace'. Only a single song can be previewed."
+    );
+
+    let opts = RenderSongOpts {
+        song: "Other Song",
+        output: "songbook.json",
+    };
+    let err = render_song(build.app(), &project.project_dir, &opts).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "No output 'songbook.json' in this project, the outputs are: songbook.pdf, songbook.html"
+    );
+}
@@ -0,0 +1,46 @@
+mod util_ng;
+pub use util_ng::*;
+
+fn prepare_project(name: &str, songs: &str) -> TestBuild {
+    TestProject::new(name)
+        .song("songs.md", songs)
+        .settings(|toml| toml.set("tex", "none"))
+        .output("songbook.html")
+        .output("songbook.pdf")
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn rtl() {
+    let build = prepare_project(
+        "rtl",
+        indoc! {r#"
+        # Danny Boy
+
+        1. Lyrics.
+
+        # הבה נגילה
+
+        1. `Am`Lyrics.
+        "#},
+    );
+
+    let html = build.read_output(".html");
+    assert!(html.contains(r#"<section id="danny-boy" class="song pad" dir="ltr">"#));
+    assert!(html.contains(r#"<section id="הבה-נגילה" class="song pad" dir="rtl">"#));
+
+    let tex = build.read_output(".tex");
+    assert!(tex.contains(r"\usepackage{bidi}"));
+    assert_eq!(tex.matches(r"\begin{RTL}").count(), 1);
+    assert_eq!(tex.matches(r"\end{RTL}").count(), 1);
+}
+
+#[test]
+fn rtl_none() {
+    let build = prepare_project("rtl-none", "# Danny Boy\n\n1. Lyrics.\n");
+
+    let tex = build.read_output(".tex");
+    assert!(!tex.contains("bidi"));
+    assert!(!tex.contains("RTL"));
+}
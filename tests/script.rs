@@ -1,4 +1,7 @@
 use std::collections::HashMap;
+use std::fs;
+
+use bard::watch::WatchEvent;
 
 mod util_ng;
 pub use util_ng::*;
@@ -86,3 +89,143 @@ fn project_script_fail() {
         .unwrap()
         .unwrap_err();
 }
+
+fn prepare_project_expect_modify(name: &str, content_sh: &str, content_bat: &str) -> TestProject {
+    TestProject::new(name)
+        .postprocess(true)
+        .output_toml(toml! {
+            file = "songbook.html"
+            script_expect_modify = true
+        })
+        .script(".html", "script", content_sh, content_bat)
+}
+
+#[test]
+fn project_script_expect_modify() {
+    let build = prepare_project_expect_modify(
+        "script-expect-modify",
+        indoc! {r#"
+        #!/bin/sh
+        echo "<!-- cover -->" >> "${OUTPUT}"
+        "#},
+        indoc! {r#"
+        @ECHO OFF
+        echo ^<!-- cover --^> >> "%OUTPUT%"
+        "#},
+    )
+    .build()
+    .unwrap();
+
+    let html = build.read_output(".html");
+    assert!(html.contains("<!-- cover -->"));
+}
+
+#[test]
+fn project_script_expect_modify_noop() {
+    let build = prepare_project_expect_modify(
+        "script-expect-modify-noop",
+        indoc! {r#"
+        #!/bin/sh
+        cp "${OUTPUT}" /nonexistent/cover.html 2> /dev/null
+        exit 0
+        "#},
+        indoc! {r#"
+        @ECHO OFF
+        exit 0
+        "#},
+    )
+    .build()
+    .unwrap();
+
+    let err = format!("{:#}", build.unwrap_err());
+    assert!(
+        err.contains("did not modify the output file \"songbook.html\""),
+        "{}",
+        err
+    );
+}
+
+fn prepare_project_throttled(name: &str, output: toml::Table) -> TestProject {
+    TestProject::new(name)
+        .postprocess(true)
+        .output_toml(output)
+        .script(
+            ".html",
+            "script",
+            indoc! {r#"
+            #!/bin/sh
+            echo run >> "${OUTPUT_STEM}.log"
+            "#},
+            indoc! {r#"
+            @ECHO OFF
+            echo run >> "%OUTPUT_STEM%.log"
+            "#},
+        )
+}
+
+#[test]
+fn project_script_on_watch() {
+    let build = prepare_project_throttled(
+        "script-on-watch",
+        toml! {
+            file = "songbook.html"
+            script_on = "watch"
+        },
+    )
+    .build()
+    .unwrap();
+
+    // bard make doesn't run the script
+    build.try_read_output(".log").unwrap_err();
+
+    let (watch_thread, control) = build.watch();
+    control.wait_watching();
+    control.send(WatchEvent::Quit);
+    watch_thread.join().unwrap();
+
+    assert_eq!(build.read_output(".log").lines().count(), 1);
+}
+
+#[test]
+fn project_script_min_interval() {
+    let build = prepare_project_throttled(
+        "script-min-interval",
+        toml! {
+            file = "songbook.html"
+            script_on = "both"
+            script_min_interval = "1h"
+        },
+    )
+    .build()
+    .unwrap();
+    let log = build.dir_output().join("songbook.log");
+    fs::remove_file(&log).unwrap();
+
+    let (watch_thread, control) = build.watch();
+    control.wait_watching();
+    assert_eq!(build.read_output(".log").lines().count(), 1);
+
+    // The rebuild is within the interval, the script is skipped
+    control.send(WatchEvent::Rebuild);
+    control.wait_watching();
+    control.send(WatchEvent::Quit);
+    watch_thread.join().unwrap();
+
+    assert_eq!(build.read_output(".log").lines().count(), 1);
+}
+
+#[test]
+fn project_script_min_interval_invalid() {
+    let build = prepare_project_throttled(
+        "script-min-interval-invalid",
+        toml! {
+            file = "songbook.html"
+            script_min_interval = "1 fortnight"
+        },
+    )
+    .build()
+    .unwrap();
+
+    let err = format!("{:#}", build.unwrap_err());
+    assert!(err.contains("invalid interval '1 fortnight'"), "{}", err);
+}
@@ -0,0 +1,109 @@
+mod util_ng;
+pub use util_ng::*;
+
+const SONGS: &str = r#"# Danny Boy
+
+1. Lyrics.
+
+# Amazing Grace
+
+1. Lyrics.
+
+# Whiskey in the Jar
+
+1. Lyrics.
+"#;
+
+fn prepare_project(name: &str, setlist: &str) -> TestProject {
+    TestProject::new(name)
+        .song("songs.md", SONGS)
+        .text_asset("friday.txt", setlist)
+        .output_toml(toml! {
+            file = "songbook.json"
+            setlist = "output/friday.txt"
+        })
+        .output("songbook.html")
+}
+
+#[test]
+fn setlist() {
+    let build = prepare_project(
+        "setlist",
+        "whiskey in the jar\n\n  Danny Boy  \nWHISKEY IN THE JAR\n",
+    )
+    .build()
+    .unwrap();
+
+    let json = build.read_output(".json");
+    let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+    let titles: Vec<_> = json["songs"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|song| song["title"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        titles,
+        ["Whiskey in the Jar", "Danny Boy", "Whiskey in the Jar"]
+    );
+
+    let sorted: Vec<_> = json["songs_sorted"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|song| {
+            (
+                song["title"].as_str().unwrap(),
+                song["idx"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        sorted,
+        [
+            ("Danny Boy", 1),
+            ("Whiskey in the Jar", 0),
+            ("Whiskey in the Jar", 2)
+        ]
+    );
+
+    // Outputs without a set list contain the whole book
+    let html = build.read_output(".html");
+    assert!(html.contains("Amazing Grace"));
+
+    let project = build.unwrap();
+    assert!(project
+        .watch_paths()
+        .any(|path| path.ends_with("output/friday.txt")));
+}
+
+#[test]
+fn setlist_unmatched() {
+    let build = prepare_project("setlist-unmatched", "Dany Boy\nAmazing Grace\nFoo\n")
+        .build()
+        .unwrap();
+    let err = format!("{:#}", build.unwrap_err());
+    assert!(err.contains("Songs not found in the book"), "{}", err);
+    assert!(
+        err.contains(r#""Dany Boy", did you mean: "Danny Boy"?"#),
+        "{}",
+        err
+    );
+    assert!(err.ends_with("\n  \"Foo\""), "{}", err);
+    assert!(!err.contains(r#""Amazing Grace""#), "{}", err);
+}
+
+#[test]
+fn setlist_volumes() {
+    let build = TestProject::new("setlist-volumes")
+        .song("songs.md", SONGS)
+        .output_toml(toml! {
+            file = "songbook.pdf"
+            setlist = "friday.txt"
+            volumes = [{ name = "Volume 1" }]
+        })
+        .build()
+        .unwrap();
+    let err = format!("{:#}", build.unwrap_err());
+    assert!(err.contains("cannot be split into volumes"), "{}", err);
+}
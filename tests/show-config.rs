@@ -0,0 +1,280 @@
+use std::fs;
+use std::process::Command;
+
+use bard::parser::{ChordSettings, InputLimits};
+use bard::project::{Hooks, Output, Settings};
+use bard::util_cmd::{self, SpellcheckSettings};
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+
+mod util_ng;
+pub use util_ng::*;
+
+fn prepare_project(name: &str) -> TestBuild {
+    TestProject::new(name)
+        .song("song.md", "# Song\n\n1. Lyrics.\n")
+        .output("songbook.html")
+        .output_toml(toml! {
+            file = "songbook.json"
+            notation = "solfege"
+            book = { title = "JSON Songbook" }
+        })
+        .settings(|toml| {
+            toml.set("notation", "german");
+            toml.set("book", toml! { title = "My \"Songbook\"" });
+            toml.set(
+                "notations",
+                toml! {
+                    [solfege]
+                    notes = ["Do", "Do#", "Re", "Mib", "Mi", "Fa", "Fa#", "Sol", "Lab", "La", "Sib", "Si"]
+                },
+            );
+        })
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn show_config_provenance() {
+    let build = prepare_project("show-config-provenance");
+    let project = build.unwrap();
+
+    let config = util_cmd::show_config(build.app(), &project.project_dir).unwrap();
+    let lines: Vec<_> = config.lines().collect();
+    for expected in [
+        r#"notation = "german"  # bard.toml"#,
+        r#"smart_punctuation = true  # default"#,
+        r#"dir_output = "output"  # default"#,
+        r#"chorus_label = "Ch"  # default"#,
+        r#"title = "My \"Songbook\""  # bard.toml"#,
+        r#"file = "songbook.json"  # bard.toml"#,
+        r#"format = "json"  # default"#,
+        r#"notation = "solfege"  # bard.toml"#,
+        "# notation is not set, chords are in the book's notation",
+        r#"title = "JSON Songbook"  # output override of [book]"#,
+        "[output.book]",
+        "[notations.solfege]",
+        r#"lowercase = ["do", "do#", "re", "mib", "mi", "fa", "fa#", "sol", "lab", "la", "sib", "si"]  # default"#,
+    ] {
+        assert!(
+            lines.contains(&expected),
+            "{:?} not in:\n{}",
+            expected,
+            config
+        );
+    }
+}
+
+#[test]
+fn show_config_round_trip() {
+    let build = prepare_project("show-config-round-trip");
+    let project = build.unwrap();
+
+    let config = util_cmd::show_config(build.app(), &project.project_dir).unwrap();
+    let parsed: toml::Table = toml::from_str(&config).unwrap();
+
+    // The effective configuration is usable as a project file and yields the same configuration
+    fs::write(project.project_dir.join("bard.toml"), &config).unwrap();
+    let config2 = util_cmd::show_config(build.app(), &project.project_dir).unwrap();
+    let parsed2: toml::Table = toml::from_str(&config2).unwrap();
+    assert_eq!(parsed, parsed2);
+    assert!(!config2.contains("# default"));
+
+    bard::bard_make_at(build.app(), &project.project_dir).unwrap();
+}
+
+#[test]
+fn show_config_env_override() {
+    let build = prepare_project("show-config-env-override");
+    let project = build.unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_bard"))
+        .args(["util", "show-config"])
+        .current_dir(&project.project_dir)
+        .env("BARD_TEX", "none")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let config = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        config.contains("\ntex = \"none\"  # env override (BARD_TEX)\n"),
+        "{}",
+        config
+    );
+}
+
+/// Deserializer which only records the field names of the struct being deserialized.
+struct FieldsProbe<'a>(&'a mut &'static [&'static str]);
+
+impl<'de, 'a> Deserializer<'de> for FieldsProbe<'a> {
+    type Error = de::value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+        Err(de::Error::custom("FieldsProbe only supports structs"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        *self.0 = fields;
+        Err(de::Error::custom("fields recorded"))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map enum identifier ignored_any
+    }
+}
+
+/// Keys accepted by a settings struct, as listed by its `Deserialize` impl.
+fn keys<T: for<'de> Deserialize<'de>>() -> &'static [&'static str] {
+    let mut fields: &'static [&'static str] = &[];
+    let _ = T::deserialize(FieldsProbe(&mut fields));
+    assert!(!fields.is_empty());
+    fields
+}
+
+/// Project file with every setting set, including the ones only shown for some output formats.
+const FULL_PROJECT: &str = indoc! {r#"
+    version = 2
+    songs = "*.md"
+    dir_songs = "songs"
+    dir_templates = "templates"
+    dir_output = "output"
+    notation = "german"
+    minor_chords = "lowercase"
+    smart_punctuation = false
+    normalize_unicode = false
+    soft_breaks = "space"
+    title_chords = "parens"
+    chord_lint = true
+    chord_lint_allow = ["N.C."]
+    always_number_choruses = true
+    never_number_choruses = false
+    include_sources = true
+    reproducible = true
+    prune = true
+    snippets_file = "snippets.toml"
+    front_matter = ["preface.md"]
+    back_matter = ["afterword.md"]
+    tex = "none"
+
+    [hooks]
+    pre_build = "echo pre"
+    post_build = "echo post"
+
+    [limits]
+    max_file_size = 1000000
+    max_line_length = 1000
+    max_inlines = 1000
+
+    [chords.normalize]
+    min = "m"
+
+    [spellcheck]
+    ignore = ["bard"]
+
+    [notations.solfege]
+    notes = ["Do", "Do#", "Re", "Mib", "Mi", "Fa", "Fa#", "Sol", "Lab", "La", "Sib", "Si"]
+
+    [snippets]
+    intro = "Intro"
+
+    [variants]
+    Song = [{ suffix = "(in A)", transpose = 2 }]
+
+    [book]
+    title = "Songbook"
+
+    [[output]]
+    file = "songbook.pdf"
+    template = "pdf.hbs"
+    sans_font = true
+    font_size = 11
+    toc_sort = true
+    toc_sort_key = "title"
+    dpi = 300.0
+    tex_runs = 2
+    script = "pdf"
+    script_expect_modify = true
+    script_on = "make"
+    script_min_interval = "5s"
+    on_update = "echo updated"
+    line_endings = "lf"
+    trim_trailing_whitespace = true
+    strict_templates = true
+    notation = "solfege"
+    fonts = ["Noto Serif"]
+    html_content = "text"
+    volumes = [{ name = "Vol 1" }]
+    book = { title = "PDF Songbook" }
+
+    [[output]]
+    files = ["songbook.json", "songbook.jsonl"]
+    songs_filter = ["Song"]
+    json_pretty = false
+    sort_keys = true
+
+    [[output]]
+    file = "slides"
+    format = "images"
+    slides = "verse"
+    image_format = "svg"
+    image_command = "convert"
+    setlist = "setlist.txt"
+"#};
+
+#[test]
+fn show_config_all_keys() {
+    let build = prepare_project("show-config-all-keys");
+    let project = build.unwrap();
+    fs::write(project.project_dir.join("bard.toml"), FULL_PROJECT).unwrap();
+
+    let config = util_cmd::show_config(build.app(), &project.project_dir).unwrap();
+    let shown = |key: &str| {
+        config.lines().any(|line| {
+            if line.starts_with('[') {
+                // A table header, eg. `[chords.normalize]` or `[[output]]`
+                line.trim_matches(['[', ']']).split('.').any(|k| k == key)
+            } else {
+                line.starts_with(&format!("{} = ", key))
+                    || line.starts_with(&format!("# {} is not set", key))
+            }
+        })
+    };
+
+    let all_keys = keys::<Settings>()
+        .iter()
+        .chain(keys::<Hooks>())
+        .chain(keys::<InputLimits>())
+        .chain(keys::<ChordSettings>())
+        .chain(keys::<SpellcheckSettings>())
+        .chain(keys::<Output>())
+        // Outputs with several files are shown as an output with `file` for each of them
+        .filter(|&&key| key != "files");
+    for key in all_keys {
+        assert!(shown(key), "{:?} not shown in:\n{}", key, config);
+    }
+
+    // All values from the project file are shown as such
+    for line in [
+        r#"chord_lint_allow = ["N.C."]  # bard.toml"#,
+        r#"pre_build = "echo pre"  # bard.toml"#,
+        r#"max_inlines = 1000  # bard.toml"#,
+        r#"min = "m"  # bard.toml"#,
+        r#"file = "songbook.jsonl"  # bard.toml"#,
+        r#"image_command = "convert"  # bard.toml"#,
+        r#"title = "PDF Songbook"  # output override of [book]"#,
+    ] {
+        assert!(
+            config.lines().any(|l| l == line),
+            "{:?} not in:\n{}",
+            line,
+            config
+        );
+    }
+}
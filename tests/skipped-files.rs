@@ -0,0 +1,101 @@
+use std::path::Path;
+
+use bard::diag::Phase;
+use bard::parser::DiagKind;
+
+mod util_ng;
+pub use util_ng::*;
+
+const GOOD: &str = "# Good Song\n\n1. Lyrics.\n";
+const BAD: &str = "# Bad Song\n\n1. Lyrics \x07bell.\n";
+
+fn prepare_project(name: &str) -> TestProject {
+    TestProject::new(name)
+        .song("a-good.md", GOOD)
+        .song("b-bad.md", BAD)
+        .song("c-good.md", "# Other Song\n\n1. More lyrics.\n")
+        .output("songbook.html")
+}
+
+#[test]
+fn skipped_files() {
+    let build = prepare_project("skipped-files").build().unwrap();
+
+    let project = build.unwrap();
+    build.assert_parser_diag(DiagKind::ControlChar { char: 7 });
+    let titles: Vec<_> = project.songs().iter().map(|s| &*s.title).collect();
+    assert_eq!(titles, ["Good Song", "Other Song"]);
+
+    let skipped = project.skipped_files();
+    assert_eq!(skipped.len(), 1);
+    assert_eq!(skipped[0].path, Path::new("songs/b-bad.md"));
+    assert_eq!(skipped[0].errors.len(), 1);
+    assert_eq!(skipped[0].errors[0].line, 3);
+
+    // The skipped file counts as a warning, the control char is an error
+    assert_eq!(build.app().warnings(), 1);
+    let diag = build
+        .app()
+        .diags()
+        .into_iter()
+        .find(|d| d.phase == Phase::Parse && !d.is_error())
+        .unwrap();
+    assert_eq!(diag.file.as_deref(), Some("songs/b-bad.md".as_ref()));
+
+    let html = build.read_output(".html");
+    assert!(html.contains("Other Song"));
+    assert!(!html.contains("Bad Song"));
+}
+
+#[test]
+fn skipped_files_fail_on_warnings() {
+    let build = prepare_project("skipped-files-fail-on-warnings")
+        .fail_on_warnings(true)
+        .build()
+        .unwrap();
+
+    let err = format!("{:#}", build.unwrap_err());
+    assert!(
+        err.contains("1 warning was emitted, failing because of --fail-on-warnings"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn skipped_files_strict() {
+    let build = prepare_project("skipped-files-strict")
+        .strict(true)
+        .build()
+        .unwrap();
+
+    let err = format!("{:#}", build.unwrap_err());
+    assert!(err.contains("Could not parse file"), "{}", err);
+    assert!(err.contains("b-bad.md"), "{}", err);
+    build.assert_parser_diag(DiagKind::ControlChar { char: 7 });
+}
+
+#[test]
+fn skipped_files_strict_all_reported() {
+    let build = prepare_project("skipped-files-strict-all-reported")
+        .song("d-bad.md", "# Another Bad Song\n\n1. Lyrics \x01.\n")
+        .strict(true)
+        .build()
+        .unwrap();
+
+    let err = format!("{:#}", build.unwrap_err());
+    assert!(err.contains("Could not parse 2 files"), "{}", err);
+    assert!(err.contains("b-bad.md"), "{}", err);
+    assert!(err.contains("d-bad.md"), "{}", err);
+
+    let diags = build.app().parser_diags().lock();
+    let files: Vec<_> = diags
+        .iter()
+        .filter(|diag| diag.is_error())
+        .map(|diag| diag.file.as_path())
+        .collect();
+    assert_eq!(
+        files,
+        [Path::new("songs/b-bad.md"), Path::new("songs/d-bad.md")]
+    );
+}
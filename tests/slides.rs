@@ -0,0 +1,114 @@
+use std::fs;
+
+use serde_json::Value;
+
+mod util_ng;
+pub use util_ng::*;
+
+const SONGS: &str = "# Amazing Song\n\n1. First verse.\n\n> Chorus.\n\n2. Second verse.\n\n# Other Song\n\n1. Lyrics.\n";
+
+fn manifest(build: &TestBuild) -> Value {
+    let path = build.dir_output().join("slides/manifest.json");
+    serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap()
+}
+
+#[test]
+fn slides_no_postprocess() {
+    let build = TestProject::new("slides-no-postprocess")
+        .song("songs.md", SONGS)
+        .output_toml(toml! {
+            file = "slides"
+            format = "images"
+        })
+        .build()
+        .unwrap();
+
+    // Without post-processing, the HTML pages of the slides are left in place of the images
+    let manifest = manifest(&build);
+    let slides = manifest["slides"].as_array().unwrap();
+    assert_eq!(slides.len(), 2);
+    assert_eq!(slides[0]["file"], "001-amazing-song.html");
+    assert_eq!(slides[1]["song"], "Other Song");
+    assert_eq!(slides[1]["song_index"], 1);
+
+    let dir = build.dir_output().join("slides");
+    let page = fs::read_to_string(dir.join("001-amazing-song.html")).unwrap();
+    assert!(page.contains("<h1>Amazing Song</h1>"));
+    assert!(page.contains("Second verse."));
+    assert!(!page.contains("Other Song"));
+}
+
+#[test]
+fn slides_verse() {
+    let build = TestProject::new("slides-verse")
+        .song("songs.md", SONGS)
+        .output_toml(toml! {
+            file = "slides"
+            format = "images"
+            slides = "verse"
+        })
+        .build()
+        .unwrap();
+
+    let manifest = manifest(&build);
+    let slides = manifest["slides"].as_array().unwrap();
+    let blocks: Vec<_> = slides
+        .iter()
+        .map(|s| {
+            (
+                s["song_index"].as_u64().unwrap(),
+                s["block_index"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(blocks, [(0, 0), (0, 1), (0, 2), (1, 0)]);
+
+    let dir = build.dir_output().join("slides");
+    let page = fs::read_to_string(dir.join("002-amazing-song.html")).unwrap();
+    assert!(page.contains("Chorus."));
+    assert!(!page.contains("First verse."));
+    assert!(page.contains("(2/4)"));
+}
+
+#[cfg(unix)]
+#[test]
+fn slides_image_command() {
+    let build = TestProject::new("slides-image-command")
+        .song("songs.md", SONGS)
+        .output_toml(toml! {
+            file = "slides"
+            format = "images"
+            image_format = "svg"
+            image_command = "cp {input} {output}"
+        })
+        .postprocess(true)
+        .build()
+        .unwrap();
+
+    let dir = build.dir_output().join("slides");
+    let image = fs::read_to_string(dir.join("001-amazing-song.svg")).unwrap();
+    assert!(image.contains("<h1>Amazing Song</h1>"));
+    assert!(dir.join("002-other-song.svg").exists());
+
+    let manifest = manifest(&build);
+    assert_eq!(manifest["slides"][1]["file"], "002-other-song.svg");
+}
+
+#[cfg(unix)]
+#[test]
+fn slides_image_command_failure() {
+    let build = TestProject::new("slides-image-command-failure")
+        .song("songs.md", SONGS)
+        .output_toml(toml! {
+            file = "slides"
+            format = "images"
+            image_command = "echo conversion failed && exit 1"
+        })
+        .postprocess(true)
+        .build()
+        .unwrap();
+
+    let err = format!("{:?}", build.unwrap_err());
+    assert!(err.contains("The image command failed"));
+    assert!(err.contains("conversion failed"));
+}
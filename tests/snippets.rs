@@ -0,0 +1,62 @@
+use bard::parser::DiagKind;
+
+mod util_ng;
+pub use util_ng::*;
+
+const SONG: &str = r#"# Song
+
+1. Lyrics.
+!use(bridge)
+
+> Chorus.
+
+*!use(trad)*
+"#;
+
+const SNIPPETS: &str = r#"
+# bridge
+La la la,
+**la** la la.
+"#;
+
+fn prepare_project(name: &str, song: &str) -> TestProject {
+    TestProject::new(name)
+        .song("song.md", song)
+        .text_asset("snippets.md", SNIPPETS)
+        .settings(|toml| {
+            toml.set("tex", "none");
+            toml.set("snippets_file", "output/snippets.md");
+            toml.insert(
+                "snippets".into(),
+                toml! { trad = "Traditional, arr. !>" }.into(),
+            );
+        })
+        .output("songbook.html")
+}
+
+#[test]
+fn snippets() {
+    let build = prepare_project("snippets", SONG).build().unwrap();
+
+    let html = build.read_output(".html");
+    assert!(html.contains("La la la,<br>"), "{}", html);
+    assert!(html.contains("<strong>la</strong> la la."), "{}", html);
+    assert!(html.contains("<em>Traditional, arr."), "{}", html);
+
+    let project = build.unwrap();
+    let snippets_file = project.project_dir.join("output").join("snippets.md");
+    assert!(project.watch_paths().any(|path| path == snippets_file));
+}
+
+#[test]
+fn snippets_unknown() {
+    let build = prepare_project("snippets-unknown", "# Song\n\n!use(brigde)\n")
+        .strict(true)
+        .build()
+        .unwrap();
+
+    build.unwrap_err();
+    build.assert_parser_diag(DiagKind::UnknownSnippet {
+        name: "brigde".into(),
+    });
+}
@@ -0,0 +1,60 @@
+use serde_json::Value;
+
+mod util_ng;
+pub use util_ng::*;
+
+const SONGS: &str = r#"# Pověste ho vejš
+
+1. Lyrics.
+
+# Danny Boy
+
+1. Lyrics.
+
+# Danny boy!
+
+1. Lyrics.
+"#;
+
+#[test]
+fn song_ids() {
+    let build = TestProject::new("song-ids")
+        .song("songs.md", SONGS)
+        .output("songbook.html")
+        .output("songbook.json")
+        .output("songbook.xml")
+        .build()
+        .unwrap();
+    build.unwrap();
+
+    let html = build.read_output(".html");
+    for id in ["poveste-ho-vejs", "danny-boy", "danny-boy-2"] {
+        assert!(
+            html.contains(&format!(r#"<section id="{}" class="song pad""#, id)),
+            "{}",
+            html
+        );
+        assert!(html.contains(&format!(r##"<a href="#{}">"##, id)));
+    }
+
+    let json = build.read_output(".json");
+    let json: Value = serde_json::from_str(&json).unwrap();
+    let ids: Vec<_> = json["songs"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|song| song["id"].as_str().unwrap())
+        .collect();
+    assert_eq!(ids, ["poveste-ho-vejs", "danny-boy", "danny-boy-2"]);
+    assert_eq!(json["songs_sorted"][0]["id"], "danny-boy");
+
+    let xml = build.read_output(".xml");
+    assert!(xml.contains(r#"id="poveste-ho-vejs""#), "{}", xml);
+    assert!(
+        xml.contains(
+            r#"<song-ref first-line="Lyrics." id="danny-boy-2" idx="2" title="Danny boy!"/>"#
+        ),
+        "{}",
+        xml
+    );
+}
@@ -0,0 +1,83 @@
+use serde_json::Value;
+
+mod util_ng;
+pub use util_ng::*;
+
+fn titles(json: &str) -> Vec<String> {
+    let json: Value = serde_json::from_str(json).unwrap();
+    json["songs"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|song| song["title"].as_str().unwrap().to_owned())
+        .collect()
+}
+
+#[test]
+fn songs_filter() {
+    let build = TestProject::new("songs-filter")
+        .song("danny-boy.md", "# Danny Boy\n\n1. Lyrics.\n")
+        .song("amazing-grace.md", "# Amazing Grace\n\n1. Lyrics.\n")
+        .output_toml(toml! {
+            file = "songbook.json"
+            songs_filter = ["danny boy"]
+        })
+        .output("songbook.html")
+        .build()
+        .unwrap();
+    build.unwrap();
+
+    let json = build.read_output(".json");
+    assert_eq!(titles(&json), ["Danny Boy"]);
+    let json: Value = serde_json::from_str(&json).unwrap();
+    let sorted = json["songs_sorted"].as_array().unwrap();
+    assert_eq!(sorted.len(), 1);
+    assert_eq!(sorted[0]["idx"], 0);
+
+    // Other outputs contain the whole book
+    let html = build.read_output(".html");
+    assert!(html.contains("Amazing Grace"));
+    assert!(html.contains("Danny Boy"));
+}
+
+#[test]
+fn songs_filter_glob() {
+    let build = TestProject::new("songs-filter-glob")
+        .song("a.md", "# Danny Boy\n\n1. Lyrics.\n")
+        .song("live-b.md", "# Amazing Grace\n\n1. Lyrics.\n")
+        .song("live-c.md", "# Whiskey in the Jar\n\n1. Lyrics.\n")
+        .output_toml(toml! {
+            file = "songbook.json"
+            songs_filter = ["live-*.md", "Danny Boy"]
+        })
+        .build()
+        .unwrap();
+    build.unwrap();
+
+    let json = build.read_output(".json");
+    assert_eq!(
+        titles(&json),
+        ["Danny Boy", "Amazing Grace", "Whiskey in the Jar"]
+    );
+}
+
+#[test]
+fn songs_filter_unmatched() {
+    let build = TestProject::new("songs-filter-unmatched")
+        .song("songs.md", "# Danny Boy\n\n1. Lyrics.\n")
+        .output_toml(toml! {
+            file = "songbook.json"
+            songs_filter = ["Dany Boy", "Danny Boy", "extra-*.md"]
+        })
+        .build()
+        .unwrap();
+
+    let err = format!("{:#}", build.unwrap_err());
+    assert!(err.contains("Invalid songs_filter of output"), "{}", err);
+    assert!(
+        err.contains(r#""Dany Boy", did you mean: "Danny Boy"?"#),
+        "{}",
+        err
+    );
+    assert!(err.ends_with("\n  \"extra-*.md\""), "{}", err);
+}
@@ -0,0 +1,110 @@
+mod util_ng;
+pub use util_ng::*;
+
+const SONGS: &str = r#"# Ivan
+
+1. Lyrics.
+
+# Chata
+
+1. Lyrics.
+
+# The Boxer
+
+1. Lyrics.
+
+# Hora
+
+1. Lyrics.
+
+# Čas
+
+1. Lyrics.
+"#;
+
+fn sorted_titles(build: &TestBuild, file: &str) -> Vec<String> {
+    let json = build.read_output(file);
+    let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+    json["songs_sorted"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|song| song["title"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[test]
+fn sort_order() {
+    let build = TestProject::new("sort-order")
+        .song("songs.md", SONGS)
+        .output("lexical.json")
+        .output_toml(toml! {
+            file = "cs.json"
+            book = { sort = "locale:cs" }
+        })
+        .output_toml(toml! {
+            file = "none.json"
+            book = { sort = "none" }
+        })
+        .output("songbook.html")
+        .build()
+        .unwrap();
+
+    assert_eq!(
+        sorted_titles(&build, "lexical.json"),
+        ["Čas", "Chata", "Hora", "Ivan", "The Boxer"]
+    );
+    assert_eq!(
+        sorted_titles(&build, "cs.json"),
+        ["Čas", "Hora", "Chata", "Ivan", "The Boxer"]
+    );
+    assert_eq!(
+        sorted_titles(&build, "none.json"),
+        ["Ivan", "Chata", "The Boxer", "Hora", "Čas"]
+    );
+}
+
+#[test]
+fn sort_order_book() {
+    let build = TestProject::new("sort-order-book")
+        .song("songs.md", SONGS)
+        .settings(|toml| {
+            let book = toml["book"].as_table_mut().unwrap();
+            book.insert("sort".into(), "locale:cs".into());
+        })
+        .output("songbook.json")
+        .output_toml(toml! {
+            file = "lexical.json"
+            book = { sort = "lexical" }
+        })
+        .build()
+        .unwrap();
+
+    let project = build.unwrap();
+    assert!(!project.book_section().contains_key("sort"));
+    assert_eq!(
+        sorted_titles(&build, "songbook.json"),
+        ["Čas", "Hora", "Chata", "Ivan", "The Boxer"]
+    );
+    assert_eq!(
+        sorted_titles(&build, "lexical.json"),
+        ["Čas", "Chata", "Hora", "Ivan", "The Boxer"]
+    );
+}
+
+#[test]
+fn sort_order_invalid() {
+    let build = TestProject::new("sort-order-invalid")
+        .song("songs.md", SONGS)
+        .settings(|toml| {
+            let book = toml["book"].as_table_mut().unwrap();
+            book.insert("sort".into(), "alphabetical".into());
+        })
+        .output("songbook.json")
+        .build()
+        .unwrap();
+
+    let err = format!("{:#}", build.unwrap_err());
+    assert!(err.contains("Invalid book.sort"), "{}", err);
+    assert!(err.contains("Invalid sort order 'alphabetical'"), "{}", err);
+}
@@ -38,11 +38,12 @@ xxx
 
     fs::write(&file, content_to_sort.as_bytes()).unwrap();
 
-    let count = util_cmd::sort_lines(r#"baz=(.+)$"#, &file).unwrap();
+    let sorted = util_cmd::sort_lines(r#"baz=(.+)$"#, &file).unwrap();
     let sorted_content = fs::read_to_string(&file).unwrap();
 
     assert_eq!(sorted_content, expected);
-    assert_eq!(count, 7);
+    assert_eq!(sorted.count, 7);
+    assert_eq!(sorted.duplicates, ["b", "a"]);
 }
 
 #[test]
@@ -63,6 +64,6 @@ zzz
 "#;
 
     fs::write(&file, content_to_sort.as_bytes()).unwrap();
-    let count = util_cmd::sort_lines(r#"baz=(.+)$"#, file).unwrap();
-    assert_eq!(count, 0);
+    let sorted = util_cmd::sort_lines(r#"baz=(.+)$"#, file).unwrap();
+    assert_eq!(sorted.count, 0);
 }
@@ -0,0 +1,64 @@
+mod util_ng;
+pub use util_ng::*;
+
+const SONGS: &str = r#"# First Song
+
+1. `C`Lyrics <b>bold</b>.
+
+# Second Song
+## Subtitle
+
+> Chorus.
+"#;
+
+#[test]
+fn sources() {
+    let build = TestProject::new("sources")
+        .song("songs.md", SONGS)
+        .settings(|toml| toml.set("include_sources", true))
+        .output("songbook.json")
+        .output("songbook.xml")
+        .output("songbook.html")
+        .template(
+            "songbook.html",
+            "songbook.hbs",
+            "{{#each songs}}<pre>{{ source }}</pre>{{/each}}",
+        )
+        .build()
+        .unwrap();
+
+    let json = build.read_output(".json");
+    let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+    let sources: Vec<_> = json["songs"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|song| song["source"].as_str().unwrap())
+        .collect();
+    assert_eq!(sources.len(), 2);
+    assert!(sources[1].starts_with("# Second Song\n"));
+    assert_eq!(sources.concat(), SONGS);
+
+    let xml = build.read_output(".xml");
+    assert!(
+        xml.contains("<source># First Song\n\n1. `C`Lyrics &lt;b&gt;bold&lt;/b&gt;.\n\n</source>")
+    );
+
+    let html = build.read_output(".html");
+    assert!(html.contains(
+        "<pre># First Song\n\n1. &#x60;C&#x60;Lyrics &lt;b&gt;bold&lt;/b&gt;.\n\n</pre>"
+    ));
+}
+
+#[test]
+fn sources_disabled() {
+    let build = TestProject::new("sources-disabled")
+        .song("songs.md", SONGS)
+        .output("songbook.json")
+        .build()
+        .unwrap();
+
+    let json = build.read_output(".json");
+    let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert!(json["songs"][0]["source"].is_null());
+}
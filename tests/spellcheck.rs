@@ -0,0 +1,79 @@
+use std::fs;
+use std::path::Path;
+
+use bard::util_cmd::{spellcheck_with, SpellcheckOpts, WordList};
+
+mod util_ng;
+pub use util_ng::*;
+
+#[test]
+fn spellcheck() {
+    let build = TestProject::new("spellcheck")
+        .song(
+            "a.md",
+            "# Song Tilte\n\n1. Hel`Cmaj7`lo wrold\nsecond `Dm`line <b>Jarmila</b>\n\n```\nxyzzy\n```\n\n2. Again wrold\n",
+        )
+        .song("b.md", "# Other\n\nBad speling\n")
+        .settings(|toml| toml.set("spellcheck", toml! { ignore = ["jarmila"] }))
+        .output("songbook.html")
+        .build()
+        .unwrap();
+    let project = build.unwrap();
+
+    let dictionary = WordList::new([
+        "song", "hello", "world", "second", "line", "again", "other", "bad",
+    ]);
+    let opts = SpellcheckOpts {
+        lang: "en_US",
+        dict: None,
+    };
+    let misspellings =
+        spellcheck_with(build.app(), &project.project_dir, &opts, &dictionary).unwrap();
+    let found: Vec<_> = misspellings
+        .iter()
+        .map(|m| (m.file.as_path(), m.line, m.word.as_str()))
+        .collect();
+    let a = Path::new("songs/a.md");
+    let b = Path::new("songs/b.md");
+    assert_eq!(
+        found,
+        [
+            (a, 1, "Tilte"),
+            (a, 3, "wrold"),
+            (a, 10, "wrold"),
+            (b, 3, "speling")
+        ]
+    );
+    assert_eq!(misspellings[3].song, "Other");
+
+    // Words from the --dict file are accepted
+    let words = project.project_dir.join("words.txt");
+    fs::write(&words, "tilte\nWROLD\n").unwrap();
+    let opts = SpellcheckOpts {
+        lang: "en_US",
+        dict: Some(&words),
+    };
+    let misspellings =
+        spellcheck_with(build.app(), &project.project_dir, &opts, &dictionary).unwrap();
+    assert_eq!(misspellings.len(), 1);
+    assert_eq!(misspellings[0].word, "speling");
+}
+
+#[cfg(not(feature = "spellcheck"))]
+#[test]
+fn spellcheck_no_feature() {
+    let build = TestProject::new("spellcheck-no-feature")
+        .output("songbook.html")
+        .build()
+        .unwrap();
+    let project = build.unwrap();
+
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_bard"))
+        .args(["util", "spellcheck", "--lang", "cs"])
+        .current_dir(&project.project_dir)
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(!output.status.success());
+    assert!(stderr.contains("not built with spell checking"));
+}
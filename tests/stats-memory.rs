@@ -0,0 +1,67 @@
+use std::process::Command;
+
+mod util_ng;
+pub use util_ng::*;
+
+const SMOL_PNG: &str = "iVBORw0KGgoAAAANSUhEUgAAAQAAAAEAAQMAAABmvDolAAAAA1BMVEW10NBjBBbqAAAAH0lEQVRoge3BAQ0AAADCoPdPbQ43oAAAAAAAAAAAvg0hAAABmmDh1QAAAABJRU5ErkJggg==";
+
+const SONGS: &str = r#"# Song
+
+1. `C`Lyrics *with emphasis* ![](smol.png)
+
+> Chorus.
+
+# Other Song
+
+1. More lyrics.
+"#;
+
+const TEMPLATE: &str = r#"{{#each songs}}
+{{#if (matches title "^Other")}}other{{/if}}
+{{#if (matches title "Song$")}}song{{/if}}
+{{/each}}
+"#;
+
+fn prepare_project(name: &str) -> TestBuild {
+    TestProject::new(name)
+        .song("songs.md", SONGS)
+        .binary_asset("smol.png", SMOL_PNG)
+        .output("songbook.html")
+        .template("songbook.html", "songbook.hbs", TEMPLATE)
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn stats_memory_counts() {
+    let build = prepare_project("stats-memory-counts");
+    let stats = build.unwrap().debug_stats(build.app());
+
+    assert_eq!(stats.ast.songs, 2);
+    assert_eq!(stats.ast.blocks, 3);
+    // Titles: 2 texts, first verse: chord, 2 texts, emphasis with a text, image,
+    // chorus: 1 text, second song's verse: 1 text
+    assert_eq!(stats.ast.inlines, 10);
+    assert!(stats.ast.heap_bytes > 0);
+    assert_eq!(stats.img_cache, 1);
+    assert_eq!(stats.regex_cache, 2);
+}
+
+#[test]
+fn stats_memory_cli() {
+    let build = prepare_project("stats-memory-cli");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_bard"))
+        .args(["make", "--stats-memory"])
+        .current_dir(&build.unwrap().project_dir)
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{}", stderr);
+    assert!(stderr.contains("Stats 2 songs, 3 blocks, "), "{}", stderr);
+    assert!(
+        stderr.contains("Cached images: 1, cached regexes: 2"),
+        "{}",
+        stderr
+    );
+}
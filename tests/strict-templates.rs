@@ -0,0 +1,89 @@
+use std::process::Command;
+
+mod util_ng;
+pub use util_ng::*;
+
+const SONG: &str = r#"# Song
+## Subtitle
+
+!tempo(120)
+
+1. `C`Lyrics <foo>ext</foo> !>
+
+> Chorus.
+"#;
+
+const TEMPLATE: &str = "{{ book.titel }} {{ book.title }} {{ program.nmae }}";
+
+#[test]
+fn strict_templates_error() {
+    let build = TestProject::new("strict-templates-error")
+        .song("song.md", SONG)
+        .output_toml(toml! {
+            file = "songbook.html"
+            strict_templates = true
+        })
+        .template("songbook.html", "songbook.hbs", TEMPLATE)
+        .build()
+        .unwrap();
+
+    let err = format!("{:?}", build.unwrap_err());
+    assert!(err.contains("book.titel"), "actual error: {}", err);
+}
+
+#[test]
+fn strict_templates_default_templates() {
+    let build = TestProject::new("strict-templates-default-templates")
+        .song("song.md", SONG)
+        .settings(|toml| {
+            toml.set("tex", "none");
+            let book = toml["book"].as_table_mut().unwrap();
+            book.remove("subtitle");
+            book.remove("title_note");
+        })
+        .output_toml(toml! {
+            file = "songbook.pdf"
+            strict_templates = true
+        })
+        .output_toml(toml! {
+            file = "songbook.html"
+            strict_templates = true
+        })
+        .output_toml(toml! {
+            file = "songbook.xml"
+            format = "hovorka"
+            strict_templates = true
+        })
+        .build()
+        .unwrap();
+
+    build.unwrap();
+}
+
+#[test]
+fn missing_values_warning() {
+    let build = TestProject::new("missing-values-warning")
+        .song("song.md", SONG)
+        .output("songbook.html")
+        .template("songbook.html", "songbook.hbs", TEMPLATE)
+        .output("songbook-default.html")
+        .build()
+        .unwrap();
+
+    // Re-run with the binary to capture the warnings output
+    let output = Command::new(env!("CARGO_BIN_EXE_bard"))
+        .arg("make")
+        .current_dir(&build.unwrap().project_dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let warnings: Vec<_> = stderr.lines().filter(|l| l.contains("Warning")).collect();
+    assert_eq!(warnings.len(), 1, "{}", stderr);
+    assert!(
+        warnings[0].contains("book.titel, program.nmae"),
+        "{}",
+        stderr
+    );
+}
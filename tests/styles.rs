@@ -0,0 +1,74 @@
+//! Tests of the `[book.styles]` settings in default templates.
+#![cfg(not(windows))]
+
+use std::fs;
+
+mod util;
+pub use util::*;
+
+const SONG: &str = "# Song\n\n1. Verse lyrics.\n\n> `C`Chorus lyrics.\n";
+
+fn init_with_styles(name: &str, styles: Option<toml::Table>) -> ExeBuilder {
+    let builder = ExeBuilder::init(name).unwrap().with_xelatex_bin();
+    fs::write(builder.work_dir.join("songs").join("yippie.md"), SONG).unwrap();
+    modify_settings(&builder.work_dir, |mut toml| {
+        if let Some(styles) = styles {
+            let book = toml["book"].as_table_mut().unwrap();
+            book.insert("styles".into(), styles.into());
+        }
+        Ok(toml)
+    })
+    .unwrap();
+    builder
+}
+
+#[test]
+fn styles_chorus() {
+    let styles = toml::toml! {
+        [chorus]
+        emph = true
+        html_class = "chorus-tinted"
+    };
+    let builder = init_with_styles("styles-chorus", Some(styles))
+        .run(&["make", "-kv"])
+        .unwrap();
+
+    let html = fs::read_to_string(builder.output("songbook.html")).unwrap();
+    assert!(html.contains(r#"<li class="chorus emph chorus-tinted">"#));
+
+    let tex = fs::read_to_string(builder.output("songbook.tex")).unwrap();
+    assert!(tex.contains(r"\begingroup\itshape"));
+    assert!(tex.contains(r"\endgroup"));
+}
+
+#[test]
+fn styles_default() {
+    let builder = init_with_styles("styles-default", None)
+        .run(&["make", "-kv"])
+        .unwrap();
+
+    let html = fs::read_to_string(builder.output("songbook.html")).unwrap();
+    assert!(html.contains(r#"<li class="chorus">"#));
+
+    let tex = fs::read_to_string(builder.output("songbook.tex")).unwrap();
+    assert!(!tex.contains(r"\itshape"));
+}
+
+#[test]
+fn styles_invalid() {
+    let styles = toml::toml! {
+        [chorus]
+        html_class = "\"><script>"
+    };
+    let builder = init_with_styles("styles-invalid", Some(styles));
+    let res = builder.run(&["make"]);
+    assert!(res.is_err());
+
+    let styles = toml::toml! {
+        [refrain]
+        emph = true
+    };
+    let builder = init_with_styles("styles-unknown", Some(styles));
+    let res = builder.run(&["make"]);
+    assert!(res.is_err());
+}
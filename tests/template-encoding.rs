@@ -0,0 +1,47 @@
+use std::fs;
+
+use bard::prelude::*;
+
+mod util;
+pub use util::*;
+
+fn build_with_template(name: &str, template: &'static str) -> Result<Builder> {
+    let project_dir = prepare_project(TEST_PROJECTS / "template-encoding", name)?;
+    modify_settings(&project_dir, |mut toml| {
+        toml["output"][0]["template"] = template.into();
+        Ok(toml)
+    })?;
+
+    let app = Builder::app(false);
+    let project = bard::bard_make_at(&app, &project_dir)?;
+    Ok(Builder {
+        project,
+        dir: project_dir,
+        app,
+    })
+}
+
+#[test]
+fn template_encoding_utf8_bom() {
+    let build = build_with_template("template-encoding-utf8-bom", "utf8-bom.hbs").unwrap();
+    let html = build.dir.join("output").join("songbook.html");
+    let html = fs::read_to_string(html).unwrap();
+    assert!(html.starts_with("<!DOCTYPE html>"), "BOM not stripped");
+}
+
+#[test]
+fn template_encoding_utf16() {
+    let err = build_with_template("template-encoding-utf16", "utf16.hbs").unwrap_err();
+    let msg = format!("{:#}", err);
+    assert!(msg.contains("utf16.hbs"), "{}", msg);
+    assert!(msg.contains("appears to be UTF-16 encoded"), "{}", msg);
+}
+
+#[test]
+fn template_encoding_invalid() {
+    let err = build_with_template("template-encoding-invalid", "invalid.hbs").unwrap_err();
+    let msg = format!("{:#}", err);
+    assert!(msg.contains("invalid.hbs"), "{}", msg);
+    assert!(msg.contains("not valid UTF-8"), "{}", msg);
+    assert!(msg.contains("at offset 80"), "{}", msg);
+}
@@ -4,6 +4,8 @@
 //! This may or may not be a manifestation of <https://github.com/rust-lang/rust/issues/37519>.
 #![cfg(not(windows))]
 
+use std::fs;
+
 mod util;
 pub use util::*;
 
@@ -73,6 +75,107 @@ fn tex_tools_none() {
     assert!(builder.out_dir().join("songbook.tex").exists());
 }
 
+/// Count TeX runs recorded in the output PDF by the tex-mock binary.
+fn count_tex_runs(builder: &ExeBuilder) -> usize {
+    let pdf = fs::read_to_string(builder.out_dir().join("songbook.pdf")).unwrap();
+    pdf.lines().filter(|line| *line == "--").count()
+}
+
+/// Use a custom template for the PDF output, optionally setting `tex_runs`.
+fn with_pdf_template(builder: ExeBuilder, template: &str, tex_runs: Option<u32>) -> ExeBuilder {
+    let tpl_dir = builder.work_dir.join("templates");
+    fs::create_dir_all(&tpl_dir).unwrap();
+    fs::write(tpl_dir.join("custom.hbs"), template).unwrap();
+
+    modify_settings(&builder.work_dir, |mut settings| {
+        let output = settings["output"].as_array_mut().unwrap()[0]
+            .as_table_mut()
+            .unwrap();
+        output.insert("template".to_string(), "custom.hbs".into());
+        if let Some(tex_runs) = tex_runs {
+            output.insert("tex_runs".to_string(), (tex_runs as i64).into());
+        }
+        Ok(settings)
+    })
+    .unwrap();
+
+    builder
+}
+
+const TEMPLATE_NO_TOC: &str = r#"{{~ version_check "1.34.0" ~}}
+\documentclass{article}
+\begin{document}
+{{#each songs}}{{ title }}\par{{/each}}
+\end{document}
+"#;
+
+const TEMPLATE_TOC_NOT_NEEDED: &str = r#"{{~ version_check "1.34.0" ~}}
+{{~ needs_toc false ~}}
+\documentclass{article}
+\begin{document}
+% The TOC here is just for show, reruns were turned off by the template.
+\tableofcontents
+{{#each songs}}\section{ {{~ title ~}} }{{/each}}
+\end{document}
+"#;
+
+#[test]
+fn tex_runs_auto_default_template() {
+    let builder = ExeBuilder::init("tex-runs-auto-default-template")
+        .unwrap()
+        .with_xelatex_bin()
+        .with_env("BARD_TEX", "xelatex")
+        .run(&["make"])
+        .unwrap();
+
+    assert_eq!(count_tex_runs(&builder), 3);
+}
+
+#[test]
+fn tex_runs_auto_no_toc() {
+    let builder = ExeBuilder::init("tex-runs-auto-no-toc").unwrap();
+    let builder = with_pdf_template(builder, TEMPLATE_NO_TOC, None)
+        .with_xelatex_bin()
+        .with_env("BARD_TEX", "xelatex")
+        .run(&["make"])
+        .unwrap();
+
+    assert_eq!(count_tex_runs(&builder), 1);
+}
+
+#[test]
+fn tex_runs_auto_toc_not_needed() {
+    let builder = ExeBuilder::init("tex-runs-auto-toc-not-needed").unwrap();
+    let builder = with_pdf_template(builder, TEMPLATE_TOC_NOT_NEEDED, None)
+        .with_xelatex_bin()
+        .with_env("BARD_TEX", "xelatex")
+        .run(&["make"])
+        .unwrap();
+
+    assert_eq!(count_tex_runs(&builder), 1);
+}
+
+#[test]
+fn tex_runs_explicit() {
+    let builder = ExeBuilder::init("tex-runs-explicit").unwrap();
+    let builder = with_pdf_template(builder, TEMPLATE_NO_TOC, Some(2))
+        .with_xelatex_bin()
+        .with_env("BARD_TEX", "xelatex")
+        .run(&["make"])
+        .unwrap();
+
+    assert_eq!(count_tex_runs(&builder), 2);
+
+    let builder = ExeBuilder::init("tex-runs-explicit-toc-not-needed").unwrap();
+    let builder = with_pdf_template(builder, TEMPLATE_TOC_NOT_NEEDED, Some(3))
+        .with_xelatex_bin()
+        .with_env("BARD_TEX", "xelatex")
+        .run(&["make"])
+        .unwrap();
+
+    assert_eq!(count_tex_runs(&builder), 3);
+}
+
 #[cfg(not(feature = "tectonic"))]
 #[test]
 fn tex_tools_set_embedded_without_feature() {
@@ -117,3 +220,30 @@ fn tex_tools_tectonic_embedded_explicit() {
     let pdf = builder.out_dir().join("songbook.pdf");
     assert_file_is_pdf(&pdf);
 }
+
+#[test]
+fn tex_reproducible() {
+    let builder = ExeBuilder::init("tex-reproducible")
+        .unwrap()
+        .with_xelatex_bin()
+        .with_env("BARD_TEX", "xelatex");
+    modify_settings(&builder.work_dir, |mut settings| {
+        settings.insert("reproducible".to_string(), true.into());
+        Ok(settings)
+    })
+    .unwrap();
+
+    let read_outputs = |builder: &ExeBuilder| {
+        ["songbook.pdf", "songbook.html"].map(|file| fs::read(builder.output(file)).unwrap())
+    };
+
+    let builder = builder.run(&["make"]).unwrap();
+    let first = read_outputs(&builder);
+    let builder = builder.run(&["make"]).unwrap();
+    let second = read_outputs(&builder);
+    assert_eq!(first, second);
+
+    let pdf = String::from_utf8_lossy(&first[0]);
+    assert!(pdf.lines().any(|line| line == "-jobname=songbook"));
+    assert!(pdf.lines().any(|line| line == "SOURCE_DATE_EPOCH=0"));
+}
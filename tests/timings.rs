@@ -0,0 +1,44 @@
+use std::process::Command;
+
+mod util_ng;
+pub use util_ng::*;
+
+fn make(name: &str, args: &[&str]) -> String {
+    let build = TestProject::new(name)
+        .output("songbook.html")
+        .build()
+        .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_bard"))
+        .arg("make")
+        .args(args)
+        .current_dir(&build.unwrap().project_dir)
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(output.status.success(), "{}", stderr);
+    stderr
+}
+
+#[test]
+fn timings_summary() {
+    let stderr = make("timings-summary", &[]);
+    assert!(stderr.contains("Finished songbook.html in "), "{}", stderr);
+    assert!(stderr.contains("Finished build in "), "{}", stderr);
+    assert!(!stderr.contains("Template: "), "{}", stderr);
+}
+
+#[test]
+fn timings_detailed() {
+    let stderr = make("timings-detailed", &["--timings"]);
+    assert!(stderr.contains("Finished songbook.html in "), "{}", stderr);
+    assert!(stderr.contains("Parse: "), "{}", stderr);
+    assert!(stderr.contains("Template: "), "{}", stderr);
+}
+
+#[test]
+fn timings_quiet() {
+    let stderr = make("timings-quiet", &["--timings", "--quiet"]);
+    assert!(!stderr.contains("Finished"), "{}", stderr);
+    assert!(!stderr.contains("Template: "), "{}", stderr);
+}
@@ -98,3 +98,24 @@ fn project_toc_sort_pdf() {
     assert!(pos2 < pos3);
     assert!(pos3 < pos4);
 }
+
+#[test]
+fn project_toc_sort_title_chords() {
+    let build = prepare_project("toc-sort-title-chords")
+        .song("medley.md", "# `Am`Song 0 Medley `C`\n\nFoo bar.\n")
+        .output_toml(toml! {
+            file = "songbook.html"
+            toc_sort = true
+        })
+        .build()
+        .unwrap();
+    let html = build.read_output(".html");
+
+    // chords are left out of the title, so the song sorts first
+    let (pos1, pos2) = (
+        html.find("Song 0 Medley").unwrap(),
+        html.find("Song A").unwrap(),
+    );
+    assert!(pos1 < pos2);
+    assert!(!html.contains("AmSong"));
+}
@@ -0,0 +1,52 @@
+mod util_ng;
+pub use util_ng::*;
+
+const SONG: &str = r#"# Song
+
+1. `G`One `C`two `A`three `Bb`four `N.C.`five
+"#;
+
+const TEMPLATE: &str = r#"{{#each songs}}{{#each blocks}}{{#each paragraphs}}{{#each this}}{{#if chord}}
+{{ chord }} {{ transpose chord 2 "english" "german" }} {{ transpose chord -1 }} {{ transpose chord 0 "english" "nashville" }} {{ transpose chord 0 "klingon" }}
+{{~/if}}{{/each}}{{/each}}{{/each}}{{/each}}
+"#;
+
+#[test]
+fn transpose_helper() {
+    let build = TestProject::new("transpose-helper")
+        .song("song.md", SONG)
+        .output("songbook.html")
+        .template("songbook.html", "songbook.hbs", TEMPLATE)
+        .build()
+        .unwrap();
+
+    build.unwrap();
+    let html = build.read_output("songbook.html");
+    assert_eq!(
+        html.lines().collect::<Vec<_>>(),
+        [
+            "",
+            "G A F# 5 G",
+            "C D B 1 C",
+            "A H Ab 6 A",
+            "Bb C A 7b Bb",
+            "N.C. N.C. N.C. N.C. N.C.",
+        ]
+    );
+
+    // Each distinct failure is reported once as a build warning
+    let warnings: Vec<_> = build
+        .app()
+        .diags()
+        .into_iter()
+        .map(|diag| diag.message)
+        .collect();
+    assert_eq!(
+        warnings,
+        [
+            "'transpose' helper: Could not transpose 'N.C.' in 'N.C.'",
+            "'transpose' helper: Unknown notation 'klingon'",
+        ]
+    );
+    assert_eq!(build.app().warnings(), 2);
+}
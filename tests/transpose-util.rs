@@ -0,0 +1,52 @@
+use std::fs;
+use std::process::Command;
+
+mod util_ng;
+pub use util_ng::*;
+
+const SONG: &str = "# Song `G`\r\n\r\n!key(G)\r\n\r\n1. `G`Lyrics ``D7``more  `Em_C`lyrics.\\\r\n   Escaped \\`G\\`, ```Am```.\r\n\r\n```\r\n`G`\r\n```\r\n";
+
+#[test]
+fn transpose_util() {
+    let build = TestProject::new("transpose-util")
+        .song("song.md", SONG)
+        .song("german.md", "# Song\n\n1. `Hm`Lyrics `B`.\n")
+        .song("bad.md", "# Song\n\n1. `G`Lyrics.\n\n2. `X`Lyrics.\n")
+        .build()
+        .unwrap();
+
+    let run = |file: &str, args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_bard"))
+            .args(["util", "transpose"])
+            .args(args)
+            .arg(build.dir_songs().join(file))
+            .output()
+            .unwrap();
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        (output.status.success(), stderr)
+    };
+    let read = |file: &str| fs::read_to_string(build.dir_songs().join(file)).unwrap();
+
+    let (success, stderr) = run("song.md", &["--semitones", "-2"]);
+    assert!(success, "{}", stderr);
+    assert_eq!(
+        read("song.md"),
+        "# Song `F`\r\n\r\n!key(G)\r\n\r\n1. `F`Lyrics ``C7``more  `Dm_Bb`lyrics.\\\r\n   Escaped \\`G\\`, ```Gm```.\r\n\r\n```\r\n`G`\r\n```\r\n"
+    );
+
+    let (success, stderr) = run(
+        "german.md",
+        &["-s", "1", "-n", "german", "--to-notation", "english"],
+    );
+    assert!(success, "{}", stderr);
+    assert_eq!(read("german.md"), "# Song\n\n1. `Cm`Lyrics `B`.\n");
+
+    let (success, stderr) = run("bad.md", &["-s", "1"]);
+    assert!(!success);
+    assert!(
+        stderr.contains("bad.md:5: Unrecognized chord: X"),
+        "{}",
+        stderr
+    );
+    assert_eq!(read("bad.md"), "# Song\n\n1. `G`Lyrics.\n\n2. `X`Lyrics.\n");
+}
@@ -0,0 +1,67 @@
+mod util_ng;
+pub use util_ng::*;
+
+const SONG: &str = r#"# Wild Mountain Thyme
+
+!!+2
+
+1. Oh the `G`summer `C`time is `G`coming
+And the `C`trees are sweetly `G`bloomin'
+
+2. 日本`Emaj7`語`D` no
+
+> And we'll `Em`all go `C`to`D`ge`G`ther
+- Ukulele
+- Guitar
+
+***
+
+    Riff
+"#;
+
+#[test]
+fn txt_output() {
+    let build = TestProject::new("txt-output")
+        .song("songs.md", SONG)
+        .output("songbook.txt")
+        .build()
+        .unwrap();
+
+    build.unwrap();
+    let txt = build.read_output("songbook.txt");
+    let lines: Vec<_> = txt.lines().collect();
+    let song = lines
+        .iter()
+        .position(|&line| line == "Wild Mountain Thyme")
+        .expect("No song title");
+    assert_eq!(
+        &lines[song..],
+        [
+            "Wild Mountain Thyme",
+            "-------------------",
+            "",
+            "           G      C       G",
+            "           A      D       A",
+            "1.  Oh the summer time is coming",
+            "            C                 G",
+            "            D                 A",
+            "    And the trees are sweetly bloomin’",
+            "",
+            // Lyrics are padded to make room for chords, wide characters take two columns
+            "        Emaj7  D",
+            "        F#maj7 E",
+            "2.  日本語      no",
+            "",
+            "              Em     C D G",
+            "              F#m    D E A",
+            "Ch. And we’ll all go together",
+            "",
+            "- Ukulele",
+            "- Guitar",
+            "",
+            "----------------------------------------",
+            "",
+            "Riff",
+        ]
+    );
+}
@@ -1,9 +1,11 @@
 //! Small binary that mocks xelatex and tectonic CLI, used in some integration tests.
+//! Documents containing the text `tex-mock-fail` make it fail.
 
 use std::env;
-use std::fs::File;
+use std::fs::{self, OpenOptions};
 use std::io::Write;
 use std::path::PathBuf;
+use std::process;
 
 fn main() {
     // NB. clap doesn't support -flags, so parsing by hand here...
@@ -25,13 +27,33 @@ fn main() {
         (&args[flag_pos + 1]).into()
     };
 
-    let mut tex: PathBuf = args.iter().last().unwrap().into();
-    tex.set_extension("pdf");
-    let pdf = tex.file_name().unwrap();
+    let tex: PathBuf = args.iter().last().unwrap().into();
+    let stem = tex.file_stem().unwrap();
 
-    let mut dest = File::create(out_dir.join(pdf)).unwrap();
+    // Like TeX, write a TOC file if the document has one.
+    let source = fs::read_to_string(&tex).unwrap_or_default();
+    if source.contains("tex-mock-fail") {
+        eprintln!("! Mock TeX failure.");
+        process::exit(1);
+    }
+    if source.contains("\\tableofcontents") {
+        let toc = out_dir.join(stem).with_extension("toc");
+        fs::write(toc, "\\contentsline {section}{Song}{1}\n").unwrap();
+    }
+
+    // Append the arguments to the PDF file so that each run is recorded.
+    let pdf = out_dir.join(stem).with_extension("pdf");
+    let mut dest = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(pdf)
+        .unwrap();
     for arg in env::args() {
         dest.write_all(arg.as_bytes()).unwrap();
         dest.write_all(b"\n").unwrap();
     }
+    // Also record the timestamp passed for reproducible builds
+    if let Ok(epoch) = env::var("SOURCE_DATE_EPOCH") {
+        writeln!(dest, "SOURCE_DATE_EPOCH={}", epoch).unwrap();
+    }
 }
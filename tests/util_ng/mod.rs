@@ -6,6 +6,7 @@ use std::{
     process::Command,
     sync::atomic::{AtomicBool, Ordering},
     thread::{self, JoinHandle},
+    time::Duration,
 };
 
 use base64::{engine::general_purpose::STANDARD as BASE_64, Engine as _};
@@ -31,11 +32,18 @@ pub fn interrupt() {
     INTERRUPT.store(true, Ordering::Relaxed);
 }
 
+type ModifySettings = Box<dyn FnOnce(&mut toml::Table)>;
+
 pub struct TestProject {
     path: PathBuf,
     postprocess: bool,
+    fail_on_warnings: bool,
+    strict: bool,
+    notify: bool,
+    /// See `App::with_outputs()`.
+    make_outputs: Vec<String>,
     outputs: Vec<Toml>,
-    modify_settings: Option<Box<dyn FnOnce(&mut toml::Table)>>,
+    modify_settings: Option<ModifySettings>,
     songs: Vec<(PathBuf, String)>,
     templates: Vec<Template>,
     scripts: Vec<Script>,
@@ -51,6 +59,10 @@ impl TestProject {
         Self {
             path,
             postprocess: false,
+            fail_on_warnings: false,
+            strict: false,
+            notify: true,
+            make_outputs: vec![],
             outputs: vec![],
             modify_settings: None,
             songs: vec![],
@@ -65,6 +77,27 @@ impl TestProject {
         self
     }
 
+    pub fn fail_on_warnings(mut self, fail_on_warnings: bool) -> Self {
+        self.fail_on_warnings = fail_on_warnings;
+        self
+    }
+
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    pub fn notify(mut self, notify: bool) -> Self {
+        self.notify = notify;
+        self
+    }
+
+    /// Render only the matching outputs, as with `bard make --output`.
+    pub fn make_outputs(mut self, outputs: &[&str]) -> Self {
+        self.make_outputs = outputs.iter().map(|o| o.to_string()).collect();
+        self
+    }
+
     pub fn output(self, file: impl Into<String>) -> Self {
         let file = file.into();
         self.output_toml(toml! { file = file })
@@ -149,6 +182,19 @@ impl TestProject {
         self
     }
 
+    /// Like `binary_asset()`, but with plain text content.
+    pub fn text_asset(mut self, path: impl Into<PathBuf>, content: impl AsRef<str>) -> Self {
+        let path = path.into();
+        if !path.is_relative() {
+            panic!("Asset path must be relative: {:?}", path);
+        }
+
+        let bytes = content.as_ref().as_bytes().into();
+        self.assets.push((path, bytes));
+
+        self
+    }
+
     pub fn build(mut self) -> Result<TestBuild> {
         // Create project directory
         if self.path.exists() {
@@ -160,13 +206,15 @@ impl TestProject {
             .with_context(|| format!("Couldn't create directory: {:?}", self.path))?;
 
         // Instantiate App
-        let bard_exe = option_env!("CARGO_BIN_EXE_bard")
-            .expect("$CARGO_BIN_EXE_bard")
-            .into();
-        let app = App::with_test_mode(self.postprocess, bard_exe, InterruptFlag(&INTERRUPT));
+        let bard_exe = env!("CARGO_BIN_EXE_bard").into();
+        let app = App::with_test_mode(self.postprocess, bard_exe, InterruptFlag(&INTERRUPT))
+            .with_fail_on_warnings(self.fail_on_warnings)
+            .with_strict(self.strict)
+            .with_outputs(self.make_outputs.clone())
+            .with_notify(self.notify);
 
         // Init default project
-        bard::bard_init_at(&app, &self.path)
+        bard::bard_init_at(&app, &self.path, &Default::default())
             .with_context(|| format!("Failed to initialize project at: {:?}", self.path))?;
 
         let bard_toml_path = self.path.join("bard.toml");
@@ -185,6 +233,10 @@ impl TestProject {
                 .with_context(|| format!("Couldn't create songs directory: {:?}", songs_dir))?;
             for (path, content) in self.songs.iter() {
                 let path = songs_dir.join(path);
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)
+                        .with_context(|| format!("Couldn't create directory: {:?}", parent))?;
+                }
                 fs::write(&path, content.as_bytes())
                     .with_context(|| format!("Couldn't write song file: {:?}", path))?;
             }
@@ -222,8 +274,8 @@ impl TestProject {
             fs::create_dir_all(&out_dir)
                 .with_context(|| format!("Couldn't create output directory: {:?}", tpl_dir))?;
             for script in self.scripts.iter() {
-                let path_sh = out_dir.join(&format!("{}.sh", script.name));
-                let path_bat = out_dir.join(&format!("{}.bat", script.name));
+                let path_sh = out_dir.join(format!("{}.sh", script.name));
+                let path_bat = out_dir.join(format!("{}.bat", script.name));
                 fs::write(&path_sh, script.content_sh.as_bytes())
                     .and_then(|_| path_sh.chmod(0o755))
                     .with_context(|| format!("Couldn't write script file: {:?}", path_sh))?;
@@ -346,7 +398,7 @@ impl TestBuild {
             Bound::Excluded(&f) => Some(f + 1),
             Bound::Unbounded => None,
         } {
-            cmd.arg("-f".to_string()).arg(format!("{}", f));
+            cmd.arg("-f").arg(format!("{}", f));
         };
 
         if let Some(l) = match pages.end_bound() {
@@ -354,7 +406,7 @@ impl TestBuild {
             Bound::Excluded(&i) => Some(i - 1),
             Bound::Unbounded => None,
         } {
-            cmd.arg("-l".to_string()).arg(format!("{}", l));
+            cmd.arg("-l").arg(format!("{}", l));
         };
 
         let output = self.output_path(output_suffix)?;
@@ -368,9 +420,18 @@ impl TestBuild {
 
     /// Start bard watch in another thread.
     pub fn watch(&self) -> (JoinHandle<()>, WatchControl) {
+        self.watch_with(Watch::new().unwrap())
+    }
+
+    /// Like `watch()`, but polling files for changes every `interval`.
+    pub fn watch_poll(&self, interval: Duration) -> (JoinHandle<()>, WatchControl) {
+        self.watch_with(Watch::with_poll(interval).unwrap())
+    }
+
+    fn watch_with(&self, watch: Watch) -> (JoinHandle<()>, WatchControl) {
         let dir_output = self.dir_output().to_owned();
         let app = self.app.clone();
-        let (watch, control) = Watch::with_test_sync().unwrap();
+        let (watch, control) = watch.with_test_sync();
 
         let watch_thread = thread::spawn(move || {
             bard::bard_watch_at(&app, &dir_output, watch).unwrap();
@@ -393,6 +454,18 @@ struct Script {
     content_bat: String,
 }
 
+/// Whether the `output` TOML has a file ending with `suffix`, either as its `file` or one of its `files`.
+fn output_has_file(output: &Toml, suffix: &str) -> bool {
+    let file = output.get("file").into_iter();
+    let files = output
+        .get("files")
+        .and_then(Toml::as_array)
+        .into_iter()
+        .flatten();
+    file.chain(files)
+        .any(|file| file.as_str().unwrap().ends_with(suffix))
+}
+
 pub trait TomlTableExt {
     fn set(&mut self, key: impl Into<String>, value: impl Into<Toml>);
     fn output(&self, suffix: &str) -> &toml::Table;
@@ -410,14 +483,7 @@ impl TomlTableExt for toml::Table {
             .as_array()
             .unwrap()
             .iter()
-            .find(|o| {
-                o.get("file")
-                    .unwrap()
-                    .as_str()
-                    .unwrap()
-                    .strip_suffix(suffix)
-                    .is_some()
-            })
+            .find(|o| output_has_file(o, suffix))
             .unwrap()
             .as_table()
             .unwrap()
@@ -429,14 +495,7 @@ impl TomlTableExt for toml::Table {
             .as_array_mut()
             .unwrap()
             .iter_mut()
-            .find(|o| {
-                o.get("file")
-                    .unwrap()
-                    .as_str()
-                    .unwrap()
-                    .strip_suffix(suffix)
-                    .is_some()
-            })
+            .find(|o| output_has_file(o, suffix))
             .unwrap()
             .as_table_mut()
             .unwrap()
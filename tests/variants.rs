@@ -0,0 +1,105 @@
+use bard::book::{Block, Inline};
+
+mod util_ng;
+pub use util_ng::*;
+
+const SONGS: &str = r#"# Wild Mountain Thyme
+
+!alias(Purple Heather)
+
+1. Oh the `G`summer time is `C`coming
+
+# Amazing Grace
+
+1. `G`Amazing grace
+"#;
+
+/// Chords and lyrics of the song's first paragraph.
+fn chords_and_lyrics(blocks: &[Block]) -> (Vec<String>, String) {
+    let mut chords = vec![];
+    let mut lyrics = String::new();
+    let verse = match &blocks[0] {
+        Block::Verse(verse) => verse,
+        block => panic!("Unexpected block: {:?}", block),
+    };
+    for inline in verse.paragraphs[0].iter() {
+        match inline {
+            Inline::Chord(chord) => {
+                chords.push(chord.chord.to_string());
+                for inline in chord.inlines.iter() {
+                    if let Inline::Text { text, .. } = inline {
+                        lyrics += text;
+                    }
+                }
+            }
+            Inline::Text { text, .. } => lyrics += text,
+            _ => {}
+        }
+    }
+    (chords, lyrics)
+}
+
+#[test]
+fn variants() {
+    let build = TestProject::new("variants")
+        .song("songs.md", SONGS)
+        .settings(|toml| {
+            let variants: toml::Table = toml::toml! {
+                "Wild Mountain Thyme" = [
+                    { suffix = "(D)", transpose = 7 },
+                    { suffix = "(Bb)", transpose = 3 },
+                ]
+                "Nonexistent Song" = [{ suffix = "(C)", transpose = 1 }]
+            };
+            toml.insert("variants".into(), variants.into());
+        })
+        .output("songbook.json")
+        .build()
+        .unwrap();
+    let project = build.unwrap();
+
+    let songs = project.songs();
+    let titles: Vec<_> = songs.iter().map(|s| &*s.title).collect();
+    assert_eq!(
+        titles,
+        [
+            "Wild Mountain Thyme",
+            "Wild Mountain Thyme (D)",
+            "Wild Mountain Thyme (Bb)",
+            "Amazing Grace"
+        ]
+    );
+
+    // Chords differ, lyrics are the same
+    let (chords, lyrics) = chords_and_lyrics(&songs[0].blocks);
+    assert_eq!(chords, ["G", "C"]);
+    let (chords_d, lyrics_d) = chords_and_lyrics(&songs[1].blocks);
+    assert_eq!(chords_d, ["D", "G"]);
+    let (chords_bb, lyrics_bb) = chords_and_lyrics(&songs[2].blocks);
+    assert_eq!(chords_bb, ["Bb", "Eb"]);
+    assert_eq!(lyrics, "Oh the summer time is coming");
+    assert_eq!(lyrics_d, lyrics);
+    assert_eq!(lyrics_bb, lyrics);
+
+    // Variants are separate songs in the TOC, the alias only refers to the original
+    let sorted: Vec<_> = project
+        .songs_sorted()
+        .iter()
+        .map(|s| (&*s.title, s.idx))
+        .collect();
+    assert_eq!(
+        sorted,
+        [
+            ("Amazing Grace", 3),
+            ("Wild Mountain Thyme", 0),
+            ("Wild Mountain Thyme (Bb)", 2),
+            ("Wild Mountain Thyme (D)", 1),
+        ]
+    );
+    let aliases: Vec<_> = project.aliases().iter().map(|a| a.idx).collect();
+    assert_eq!(aliases, [0]);
+
+    let json = build.read_output(".json");
+    assert!(json.contains(r#""title": "Wild Mountain Thyme (Bb)""#));
+    assert_eq!(build.app().warnings(), 1);
+}
@@ -0,0 +1,117 @@
+use std::fs;
+
+mod util_ng;
+pub use util_ng::*;
+
+const TEMPLATE: &str = r#"{{#each songs}}{{ math ../volume.song_offset "+" @index }}: {{ title }}
+{{/each}}sorted: {{#each songs_sorted}}{{ idx }} {{/each}}
+{{ volume.number }}/{{ volume.count }} {{ volume.name }}
+{{#each volume.index}}{{ title }} {{ volume }}
+{{/each}}"#;
+
+fn project(name: &str, volumes: toml::Value) -> TestProject {
+    ["Alpha", "Bravo", "Charlie", "Delta", "Echo"]
+        .iter()
+        .fold(TestProject::new(name), |project, title| {
+            project.song(
+                format!("{}.md", title.to_lowercase()),
+                format!("# {}\n\n1. Lyrics.\n", title),
+            )
+        })
+        .output_toml(toml! {
+            file = "songbook.pdf"
+            volumes = volumes
+        })
+        .settings(|toml| {
+            toml.set("tex", "none");
+        })
+}
+
+fn volumes() -> toml::Value {
+    toml::Value::try_from(vec![
+        toml! { name = "Vol 1" until_song = "b*" },
+        toml! { name = "Vol 2" until_index = 4 },
+        toml! { name = "Vol 3" },
+    ])
+    .unwrap()
+}
+
+#[test]
+fn volumes_default_template() {
+    let build = project("volumes-default-template", volumes())
+        .build()
+        .unwrap();
+    let out_dir = build.dir_output();
+
+    assert!(!out_dir.join("songbook.tex").exists());
+    let vol1 = fs::read_to_string(out_dir.join("songbook-vol1.tex")).unwrap();
+    let vol2 = fs::read_to_string(out_dir.join("songbook-vol2.tex")).unwrap();
+    let vol3 = fs::read_to_string(out_dir.join("songbook-vol3.tex")).unwrap();
+
+    assert!(vol1.contains(r"\songtitle{Alpha}"));
+    assert!(vol1.contains(r"\songtitle{Bravo}"));
+    assert!(!vol1.contains(r"\songtitle{Charlie}"));
+    assert!(vol2.contains(r"\songtitle{Charlie}"));
+    assert!(vol2.contains(r"\songtitle{Delta}"));
+    assert!(!vol2.contains(r"\songtitle{Echo}"));
+    assert!(vol3.contains(r"\songtitle{Echo}"));
+    assert!(vol3.contains(r"\Large{Vol 3}"));
+
+    // Song numbers continue from the previous volume
+    assert!(vol1.contains("%% song 1\n"));
+    assert!(vol2.contains("%% song 2\n"));
+    assert!(vol2.contains("%% song 3\n"));
+    assert!(!vol2.contains("%% song 0\n"));
+    assert!(vol3.contains("%% song 4\n"));
+
+    // Each volume contains the combined index
+    for vol in [&vol1, &vol2, &vol3] {
+        assert!(vol.contains(r"Alpha\dotfill{}Vol 1\par"));
+        assert!(vol.contains(r"Delta\dotfill{}Vol 2\par"));
+        assert!(vol.contains(r"Echo\dotfill{}Vol 3\par"));
+    }
+}
+
+#[test]
+fn volumes_context() {
+    let build = project("volumes-context", volumes())
+        .template("songbook.pdf", "volumes.hbs", TEMPLATE)
+        .build()
+        .unwrap();
+    let out_dir = build.dir_output();
+
+    let vol2 = fs::read_to_string(out_dir.join("songbook-vol2.tex")).unwrap();
+    assert_eq!(
+        vol2,
+        "2: Charlie\n3: Delta\nsorted: 0 1 \n2/3 Vol 2\nAlpha 1\nBravo 1\nCharlie 2\nDelta 2\nEcho 3\n"
+    );
+}
+
+#[test]
+fn volumes_no_match() {
+    let volumes = toml::Value::try_from(vec![
+        toml! { name = "Vol 1" until_song = "x*" },
+        toml! { name = "Vol 2" },
+    ])
+    .unwrap();
+    let build = project("volumes-no-match", volumes).build().unwrap();
+    let err = format!("{:#}", build.unwrap_err());
+    assert!(
+        err.contains("Volume 'Vol 1': No song matching 'x*'"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn volumes_invalid() {
+    let volumes =
+        toml::Value::try_from(vec![toml! { name = "Vol 1" }, toml! { name = "Vol 2" }]).unwrap();
+    let build = project("volumes-invalid", volumes).build().unwrap();
+    let err = format!("{:#}", build.unwrap_err());
+    assert!(
+        err.contains("until_song or until_index is required"),
+        "{}",
+        err
+    );
+}
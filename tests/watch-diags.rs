@@ -0,0 +1,44 @@
+use std::fs::File;
+use std::io::Write as _;
+
+mod util_ng;
+pub use util_ng::*;
+
+#[test]
+fn watch_diags_reset() {
+    let build = TestProject::new("watch-diags-reset")
+        .song(
+            "watch.md",
+            indoc! {"
+            # Watch Test
+
+            1. Lyrics !label:Misplaced.
+        "},
+        )
+        .output("songbook.html")
+        .build()
+        .unwrap();
+    assert_eq!(build.app().diags().len(), 1);
+
+    let (watch_thread, control) = build.watch();
+    control.wait_watching();
+    assert_eq!(build.app().diags().len(), 1);
+
+    // Two rebuilds, each reports the same warning once
+    let md_file = build.dir_songs().join("watch.md");
+    for i in 0..2 {
+        File::options()
+            .append(true)
+            .open(&md_file)
+            .unwrap()
+            .write_all(format!("\n2. Rebuild {}.\n", i).as_bytes())
+            .unwrap();
+        control.wait_watching();
+
+        assert_eq!(build.app().diags().len(), 1);
+        assert_eq!(build.app().warnings(), 1);
+    }
+
+    build.interrupt();
+    watch_thread.join().unwrap();
+}
@@ -0,0 +1,45 @@
+use std::fs::File;
+use std::io::Write as _;
+
+mod util_ng;
+pub use util_ng::*;
+
+/// 256x256 pixels.
+const SMOL_PNG: &str = "iVBORw0KGgoAAAANSUhEUgAAAQAAAAEAAQMAAABmvDolAAAAA1BMVEW10NBjBBbqAAAAH0lEQVRoge3BAQ0AAADCoPdPbQ43oAAAAAAAAAAAvg0hAAABmmDh1QAAAABJRU5ErkJggg==";
+/// 2x1 pixels.
+const WIDE_PNG: &str =
+    "iVBORw0KGgoAAAANSUhEUgAAAAIAAAABCAIAAAB7QOjdAAAAC0lEQVR4nGP4DwYAFPIF+6QNfF4AAAAASUVORK5CYII=";
+
+// In its own test binary, as interrupting the watch interrupts all the tests of a binary
+
+#[test]
+fn watch_image_dimensions() {
+    let build = TestProject::new("watch-image-dimensions")
+        .song("watch.md", "# Watch Test\n\n1. ![smol](smol.png)\n")
+        .binary_asset("smol.png", SMOL_PNG)
+        .output("songbook.html")
+        .build()
+        .unwrap();
+
+    let (watch_thread, control) = build.watch();
+    control.wait_watching();
+
+    let html = build.read_output(".html");
+    assert!(html.contains(r#"width="256" height="256""#));
+
+    // Overwrite the image with one of different dimensions,
+    // its modification time may be the same as the cached one:
+    let img_file = build.dir_output().join("smol.png");
+    File::create(&img_file)
+        .unwrap()
+        .write_all(&WIDE_PNG.decode_base64())
+        .unwrap();
+
+    control.wait_watching();
+    build.interrupt();
+
+    let html = build.read_output(".html");
+    assert!(html.contains(r#"width="2" height="1""#), "{}", html);
+
+    watch_thread.join().unwrap();
+}
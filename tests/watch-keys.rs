@@ -0,0 +1,46 @@
+use std::fs;
+
+use bard::watch::WatchEvent;
+
+mod util_ng;
+pub use util_ng::*;
+
+#[test]
+fn watch_key_events() {
+    let build = TestProject::new("watch-keys")
+        .song(
+            "watch.md",
+            indoc! {r#"
+            # Watch Test
+
+            1. `C`Watch.
+        "#},
+        )
+        .output("songbook.html")
+        .build()
+        .unwrap();
+
+    let (watch_thread, control) = build.watch();
+
+    // Wait for the initial render pass:
+    control.wait_watching();
+
+    // Remove the output and request a rebuild:
+    let output = build.dir_output().join("songbook.html");
+    fs::remove_file(&output).unwrap();
+    control.send(WatchEvent::Rebuild);
+
+    // Wait for the watching to resume after the rebuild:
+    control.wait_watching();
+    assert!(output.exists());
+
+    // Toggling verbosity doesn't trigger a rebuild:
+    fs::remove_file(&output).unwrap();
+    control.send(WatchEvent::ToggleVerbose);
+    control.wait_watching();
+    assert!(!output.exists());
+
+    // Quit cleanly, without interrupting:
+    control.send(WatchEvent::Quit);
+    watch_thread.join().unwrap();
+}
@@ -0,0 +1,39 @@
+use std::fs::File;
+use std::io::Write as _;
+use std::time::Duration;
+
+mod util_ng;
+pub use util_ng::*;
+
+// In its own test binary, as interrupting the watch interrupts all the tests of a binary
+
+#[test]
+fn watch_poll() {
+    const TEST_STR: &str = "watch poll test";
+
+    let build = TestProject::new("watch-poll")
+        .song("watch.md", "# Watch Test\n\n1. Watch.\n")
+        .output("songbook.html")
+        .build()
+        .unwrap();
+
+    let (watch_thread, control) = build.watch_poll(Duration::from_millis(100));
+    control.wait_watching();
+
+    let md_file = build.dir_songs().join("watch.md");
+    File::options()
+        .append(true)
+        .open(&md_file)
+        .unwrap()
+        .write_all(TEST_STR.as_bytes())
+        .unwrap();
+
+    // Wait for the watching to resume after the polled change triggered a render pass:
+    control.wait_watching();
+    build.interrupt();
+
+    let html = build.read_output(".html");
+    assert!(html.contains(TEST_STR));
+
+    watch_thread.join().unwrap();
+}
@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 mod util_ng;
 pub use util_ng::*;
 
@@ -27,7 +29,7 @@ fn project_wildcards_n() {
     let proj = TestProject::new("wildcards-n");
     let build = ['a', 'b', 'c']
         .iter()
-        .flat_map(|c| (1..4).into_iter().map(move |i| (c, i)))
+        .flat_map(|c| (1..4).map(move |i| (c, i)))
         .fold(proj, |proj, (c, i)| {
             proj.song(
                 format!("{}-{}.md", c, i),
@@ -53,3 +55,119 @@ fn project_wildcards_n() {
         }
     }
 }
+
+#[test]
+fn project_wildcards_missing_dir() {
+    let build = TestProject::new("wildcards-missing-dir")
+        .output("songbook.html")
+        .settings(|toml| {
+            toml.set("dir_songs", "sogns");
+        })
+        .build()
+        .unwrap();
+
+    let err = format!("{:?}", build.unwrap_err());
+    assert!(err.contains("songs directory not found"));
+    assert!(err.contains("sogns"));
+    assert!(err.contains("set dir_songs in bard.toml"));
+}
+
+#[test]
+fn project_wildcards_other_extensions() {
+    let build = TestProject::new("wildcards-other-extensions")
+        .song("a.txt", "# Song A\n")
+        .song("b.txt", "# Song B\n")
+        .song("c.chopro", "{title: Song C}\n")
+        .output("songbook.html")
+        .settings(|toml| {
+            toml.set("songs", "*.md");
+        })
+        .build()
+        .unwrap();
+
+    let err = format!("{:?}", build.unwrap_err());
+    assert!(err.contains("No files matched pattern '*.md'"));
+    assert!(err.contains("no .md files, but 3 files with other extensions"));
+    assert!(err.contains(".txt: 2"));
+    assert!(err.contains(".chopro: 1"));
+}
+
+#[test]
+fn project_wildcards_nearest_miss() {
+    let build = TestProject::new("wildcards-nearest-miss")
+        .song("amazng-grace.md", "# Amazing Grace\n")
+        .song("other.md", "# Other\n")
+        .song("zzz-unrelated-song.md", "# Unrelated\n")
+        .output("songbook.html")
+        .settings(|toml| {
+            toml.set("songs", "amazing*.md");
+        })
+        .build()
+        .unwrap();
+
+    let err = format!("{:?}", build.unwrap_err());
+    assert!(err.contains("No files matched pattern 'amazing*.md'"));
+    assert!(err.contains("None of the 3 .md files in the directory matched"));
+    let nearest = err.find("the closest are: \"amazng-grace.md\"");
+    assert!(nearest.is_some(), "{}", err);
+}
+
+#[test]
+fn project_multiple_song_dirs() {
+    let extra = PathBuf::from(env!("CARGO_TARGET_TMPDIR"))
+        .join("test-projects")
+        .join("multiple-song-dirs")
+        .join("songs")
+        .join("extra")
+        .join("extra.md");
+    let build = TestProject::new("multiple-song-dirs")
+        .song("one/a.md", "# Song A\n\n1. From one\n")
+        .song("one/shadowed.md", "# Shadowed\n\n1. Shadowed in one\n")
+        .song("two/b.md", "# Song B\n\n1. From two\n")
+        .song("two/shadowed.md", "# Shadowed\n\n1. Shadowing in two\n")
+        .song("extra/extra.md", "# Extra\n\n1. Absolute path\n")
+        .output("songbook.html")
+        .settings(move |toml| {
+            toml.set("dir_songs", vec!["songs/one", "songs/two"]);
+            let extra = extra.to_string_lossy().to_string();
+            toml.set("songs", vec!["*.md".to_string(), extra.clone(), extra]);
+        })
+        .build()
+        .unwrap();
+
+    let titles: Vec<_> = build
+        .unwrap()
+        .songs()
+        .iter()
+        .map(|song| song.title.to_string())
+        .collect();
+    assert_eq!(titles, ["Song A", "Song B", "Shadowed", "Extra"]);
+
+    let html = build.read_output(".html");
+    assert!(html.contains("From one"));
+    assert!(html.contains("From two"));
+    assert!(html.contains("Shadowing in two"));
+    assert!(!html.contains("Shadowed in one"));
+    assert!(html.contains("Absolute path"));
+}
+
+#[test]
+fn project_multiple_song_dirs_file_not_found() {
+    let build = TestProject::new("multiple-song-dirs-file-not-found")
+        .song("one/a.md", "# Song A\n")
+        .song("two/b.md", "# Song B\n")
+        .output("songbook.html")
+        .settings(|toml| {
+            toml.set("dir_songs", vec!["songs/one", "songs/two"]);
+            toml.set("songs", vec!["a.md", "c.md"]);
+        })
+        .build()
+        .unwrap();
+
+    let err = format!("{:?}", build.unwrap_err());
+    assert!(
+        err.contains("File not found: 'c.md' in directories"),
+        "{}",
+        err
+    );
+}